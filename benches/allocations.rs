@@ -0,0 +1,94 @@
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Instant;
+
+use ironlobe::account::Account;
+use ironlobe::book::Book;
+use ironlobe::order::{Order, OrderType};
+use ironlobe::quantity::Quantity;
+
+/* Counts the heap allocations each book operation makes, alongside its
+ * throughput, to keep the matching path from copying orders around. Run
+ * with `cargo bench --bench allocations`. */
+
+struct CountingAllocator;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) ->
+        *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+const ORDERS: u128 = 10_000;
+
+fn build_order(id: u128, order_type: OrderType, price: f64,
+               quantity: u64) -> Order {
+    let mut holdings: HashMap<String, Quantity> = HashMap::new();
+    holdings.insert("BOOK".to_string(), Quantity::from(u64::MAX));
+
+    let owner: Account = Account::new(id, "Account".to_string(),
+                                      f64::MAX / 2.0, holdings);
+
+    Order::new(id, owner, "BOOK".to_string(), order_type, price,
+               Quantity::from(quantity))
+}
+
+/* runs `operation` on each of `inputs`, which are built beforehand so as
+ * not to be counted */
+fn measure<T>(name: &str, book: &mut Book, inputs: Vec<T>,
+              mut operation: impl FnMut(&mut Book, T)) {
+    let count: usize = inputs.len();
+    let before: usize = ALLOCATIONS.load(Ordering::Relaxed);
+    let started: Instant = Instant::now();
+
+    for input in inputs {
+        operation(book, input);
+    }
+
+    let elapsed: f64 = started.elapsed().as_secs_f64();
+    let allocations: usize = ALLOCATIONS.load(Ordering::Relaxed) - before;
+
+    println!("{:<24} {:>8.2} allocations/op {:>12.0} ops/s", name,
+             allocations as f64 / count as f64, count as f64 / elapsed);
+}
+
+fn main() {
+    let mut book: Book = Book::builder(1, "BOOK".to_string())
+        .event_capacity(1024)
+        .build()
+        .unwrap();
+
+    let asks: Vec<Order> = (1..=ORDERS)
+        .map(|id| build_order(id, OrderType::Ask,
+                              100.00 + (id % 100) as f64 * 0.01, 10))
+        .collect();
+    measure("submit (resting)", &mut book, asks,
+            |book, order| book.submit(order).unwrap());
+
+    let cancels: Vec<u128> = (1..=ORDERS).step_by(2).collect();
+    measure("cancel", &mut book, cancels,
+            |book, id| { book.cancel(id).unwrap(); });
+
+    let bids: Vec<Order> = (ORDERS + 1..=ORDERS * 3 / 2)
+        .map(|id| build_order(id, OrderType::Bid, 101.00, 10))
+        .collect();
+    measure("submit (filling)", &mut book, bids,
+            |book, order| book.submit(order).unwrap());
+}
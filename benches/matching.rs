@@ -0,0 +1,82 @@
+use std::collections::HashMap;
+
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion};
+
+use ironlobe::account::Account;
+use ironlobe::book::{Book, DynBook};
+use ironlobe::matching::{MatchingPolicy, PriceTime};
+use ironlobe::order::{Order, OrderType};
+use ironlobe::quantity::Quantity;
+
+const LEVELS: u128 = 100;
+const ORDERS_PER_LEVEL: u128 = 10;
+
+fn build_order(id: u128, order_type: OrderType, price: f64,
+               quantity: u64) -> Order {
+    let mut holdings: HashMap<String, Quantity> = HashMap::new();
+    holdings.insert("BOOK".to_string(), Quantity::from(u64::MAX));
+
+    let owner: Account = Account::new(id, "Account".to_string(), 0.00,
+                                      holdings);
+
+    Order::new(id, owner, "BOOK".to_string(), order_type, price,
+               Quantity::from(quantity))
+}
+
+/* resting asks spread over `LEVELS` levels, and one bid that sweeps them
+ * all */
+fn build_flow() -> (Vec<Order>, Order) {
+    let mut asks: Vec<Order> = vec![];
+
+    for level in 0..LEVELS {
+        for i in 0..ORDERS_PER_LEVEL {
+            asks.push(build_order(level * ORDERS_PER_LEVEL + i + 1,
+                                  OrderType::Ask, 100.00 + level as f64, 10));
+        }
+    }
+
+    let sweep: Order = build_order(LEVELS * ORDERS_PER_LEVEL + 1,
+                                   OrderType::Bid, 100.00 + LEVELS as f64,
+                                   (LEVELS * ORDERS_PER_LEVEL * 10) as u64);
+
+    (asks, sweep)
+}
+
+fn bench_static_dispatch(c: &mut Criterion) {
+    c.bench_function("sweep (static PriceTime)", |b| {
+        b.iter_batched(|| {
+            let (asks, sweep) = build_flow();
+            let mut book: Book = Book::new(1, "Book".to_string(),
+                                           "BOOK".to_string());
+
+            for ask in asks {
+                book.submit(ask).unwrap();
+            }
+
+            (book, sweep)
+        }, |(mut book, sweep)| book.submit(sweep).unwrap(),
+        BatchSize::SmallInput)
+    });
+}
+
+fn bench_dynamic_dispatch(c: &mut Criterion) {
+    c.bench_function("sweep (boxed PriceTime)", |b| {
+        b.iter_batched(|| {
+            let (asks, sweep) = build_flow();
+            let policy: Box<dyn MatchingPolicy> = Box::new(PriceTime);
+            let mut book: DynBook = Book::with_policy(1, "Book".to_string(),
+                                                      "BOOK".to_string(),
+                                                      policy);
+
+            for ask in asks {
+                book.submit(ask).unwrap();
+            }
+
+            (book, sweep)
+        }, |(mut book, sweep)| book.submit(sweep).unwrap(),
+        BatchSize::SmallInput)
+    });
+}
+
+criterion_group!(benches, bench_static_dispatch, bench_dynamic_dispatch);
+criterion_main!(benches);
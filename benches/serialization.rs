@@ -0,0 +1,55 @@
+use chrono::Utc;
+use criterion::{criterion_group, criterion_main, Criterion};
+
+use ironlobe::binary;
+use ironlobe::event::{Event, EventKind, Trade};
+use ironlobe::order::OrderType;
+
+const EVENTS: u64 = 10_000;
+
+fn build_events() -> Vec<Event> {
+    (1..=EVENTS)
+        .map(|seq| {
+            let kind: EventKind = if seq % 3 == 0 {
+                EventKind::Match(Trade::new(Utc::now(), 100.25, 10u32.into(),
+                                            seq as u128, seq as u128 - 1,
+                                            OrderType::Bid))
+            } else {
+                EventKind::Post {
+                    order: seq as u128,
+                    order_type: OrderType::Ask,
+                    price: 100.00 + (seq % 50) as f64 * 0.01,
+                    quantity: 10u32.into(),
+                    hidden: false
+                }
+            };
+
+            Event::new(seq, Utc::now(), kind)
+        })
+        .collect()
+}
+
+fn bench_encode(c: &mut Criterion) {
+    let events: Vec<Event> = build_events();
+
+    c.bench_function("encode events (json)",
+                     |b| b.iter(|| serde_json::to_vec(&events).unwrap()));
+    c.bench_function("encode events (binary)",
+                     |b| b.iter(|| binary::encode(&events).unwrap()));
+}
+
+fn bench_decode(c: &mut Criterion) {
+    let events: Vec<Event> = build_events();
+    let json: Vec<u8> = serde_json::to_vec(&events).unwrap();
+    let bytes: Vec<u8> = binary::encode(&events).unwrap();
+
+    c.bench_function("decode events (json)", |b| b.iter(|| {
+        serde_json::from_slice::<Vec<Event>>(&json).unwrap()
+    }));
+    c.bench_function("decode events (binary)", |b| b.iter(|| {
+        binary::decode::<Vec<Event>>(&bytes).unwrap()
+    }));
+}
+
+criterion_group!(benches, bench_encode, bench_decode);
+criterion_main!(benches);
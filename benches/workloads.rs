@@ -0,0 +1,183 @@
+use std::collections::HashMap;
+
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion};
+
+use ironlobe::account::Account;
+use ironlobe::book::Book;
+use ironlobe::order::{Order, OrderId, OrderType};
+use ironlobe::quantity::Quantity;
+use ironlobe::sim::flow::{FlowAction, FlowConfig, OrderFlow};
+
+const MID: f64 = 100.00;
+const DEPTH: u64 = 50;
+const OPERATIONS: usize = 10_000;
+
+/* xorshift, so that every run sees the same flow */
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    fn below(&mut self, bound: u64) -> u64 {
+        self.next() % bound
+    }
+}
+
+#[derive(Clone)]
+enum Operation {
+    Submit(Box<Order>),
+    Cancel(OrderId)
+}
+
+fn build_order(id: u128, order_type: OrderType, price: f64,
+               quantity: u64) -> Order {
+    let mut holdings: HashMap<String, Quantity> = HashMap::new();
+    holdings.insert("BOOK".to_string(), Quantity::from(u64::MAX));
+
+    let owner: Account = Account::new(id, "Account".to_string(),
+                                      f64::MAX / 2.0, holdings);
+
+    Order::new(id, owner, "BOOK".to_string(), order_type, price,
+               Quantity::from(quantity))
+}
+
+fn side(rng: &mut Rng) -> OrderType {
+    if rng.below(2) == 0 {
+        OrderType::Bid
+    } else {
+        OrderType::Ask
+    }
+}
+
+/* a passive price `ticks` away from the mid on the order's own side */
+fn passive_price(order_type: &OrderType, ticks: u64) -> f64 {
+    match order_type {
+        OrderType::Bid => MID - 0.01 * (ticks + 1) as f64,
+        OrderType::Ask => MID + 0.01 * (ticks + 1) as f64
+    }
+}
+
+/* a price that crosses up to `ticks` levels into the other side */
+fn aggressive_price(order_type: &OrderType, ticks: u64) -> f64 {
+    passive_price(&match order_type {
+        OrderType::Bid => OrderType::Ask,
+        OrderType::Ask => OrderType::Bid
+    }, ticks)
+}
+
+fn seed_book(rng: &mut Rng, next_id: &mut u128) -> (Book, Vec<OrderId>) {
+    let mut book: Book = Book::new(1, "Book".to_string(), "BOOK".to_string());
+    let mut resting: Vec<OrderId> = vec![];
+
+    for ticks in 0..DEPTH {
+        for order_type in [OrderType::Bid, OrderType::Ask] {
+            let price: f64 = passive_price(&order_type, ticks);
+
+            book.submit(build_order(*next_id, order_type, price,
+                                    rng.below(10) + 1)).unwrap();
+            resting.push(*next_id);
+            *next_id += 1;
+        }
+    }
+
+    (book, resting)
+}
+
+/* nine cancels of resting orders, each replaced so the book keeps its
+ * shape, for every aggressive order */
+fn cancel_heavy_flow(seed: u64) -> (Book, Vec<Operation>) {
+    let mut rng: Rng = Rng(seed);
+    let mut next_id: u128 = 1;
+    let (book, mut resting) = seed_book(&mut rng, &mut next_id);
+    let mut operations: Vec<Operation> = vec![];
+
+    while operations.len() < OPERATIONS {
+        if rng.below(10) == 0 {
+            let order_type: OrderType = side(&mut rng);
+            let price: f64 = aggressive_price(&order_type, 0);
+
+            operations.push(Operation::Submit(Box::new(
+                build_order(next_id, order_type, price, 5))));
+        } else if !resting.is_empty() {
+            let index: usize = rng.below(resting.len() as u64) as usize;
+            operations.push(Operation::Cancel(resting.swap_remove(index)));
+
+            let order_type: OrderType = side(&mut rng);
+            let price: f64 = passive_price(&order_type, rng.below(DEPTH));
+            operations.push(Operation::Submit(Box::new(
+                build_order(next_id, order_type, price,
+                            rng.below(10) + 1))));
+            resting.push(next_id);
+        }
+
+        next_id += 1;
+    }
+
+    (book, operations)
+}
+
+/* Limit orders, aggressive orders and cancels each arrive as independent
+ * Poisson processes, on top of the seeded book. */
+fn poisson_flow(seed: u64) -> (Book, Vec<Operation>) {
+    let mut rng: Rng = Rng(seed);
+    let mut next_id: u128 = 1;
+    let (book, _) = seed_book(&mut rng, &mut next_id);
+    let config: FlowConfig = FlowConfig {
+        mid: MID,
+        first_id: next_id,
+        ..FlowConfig::default()
+    };
+    let operations: Vec<Operation> = OrderFlow::new(config, seed)
+        .take(OPERATIONS)
+        .map(|event| match event.into_action() {
+            FlowAction::Submit(order) => Operation::Submit(order),
+            FlowAction::Cancel(id) => Operation::Cancel(id)
+        })
+        .collect();
+
+    (book, operations)
+}
+
+/* orders may have been filled by the time their cancel arrives, so
+ * failures are expected and ignored */
+fn run(book: &mut Book, operations: Vec<Operation>) {
+    for operation in operations {
+        match operation {
+            Operation::Submit(order) => book.submit(*order).unwrap(),
+            Operation::Cancel(id) => { let _ = book.cancel(id); }
+        }
+    }
+}
+
+fn bench_cancel_heavy(c: &mut Criterion) {
+    c.bench_function("cancel heavy (90% cancel / 10% trade)", |b| {
+        b.iter_batched(|| cancel_heavy_flow(0x2545_f491_4f6c_dd1d),
+                       |(mut book, operations)| run(&mut book, operations),
+                       BatchSize::SmallInput)
+    });
+}
+
+fn bench_poisson_flow(c: &mut Criterion) {
+    c.bench_function("poisson flow", |b| {
+        b.iter_batched(|| poisson_flow(0x9e37_79b9_7f4a_7c15),
+                       |(mut book, operations)| run(&mut book, operations),
+                       BatchSize::SmallInput)
+    });
+}
+
+fn bench_snapshots(c: &mut Criterion) {
+    let (mut book, operations) = poisson_flow(0x9e37_79b9_7f4a_7c15);
+    run(&mut book, operations);
+
+    c.bench_function("levels", |b| b.iter(|| book.levels()));
+    c.bench_function("top of book", |b| b.iter(|| book.top_of_book()));
+}
+
+criterion_group!(benches, bench_cancel_heavy, bench_poisson_flow,
+                 bench_snapshots);
+criterion_main!(benches);
@@ -0,0 +1,20 @@
+/* Generates the gRPC service from `proto/ironlobe.proto` when building with
+ * the `grpc` feature, using a vendored `protoc` so that none need be
+ * installed. */
+
+#[cfg(feature = "grpc")]
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    std::env::set_var("PROTOC", protoc_bin_vendored::protoc_bin_path()?);
+    println!("cargo:rerun-if-changed=proto/ironlobe.proto");
+    /* the generated `connect` needs the 2021 prelude; clients can be made
+     * from a `Channel` instead */
+    tonic_build::configure()
+        .build_transport(false)
+        .compile_protos(&["proto/ironlobe.proto"], &["proto"])?;
+    Ok(())
+}
+
+#[cfg(not(feature = "grpc"))]
+fn main() {
+    println!("cargo:rerun-if-changed=build.rs");
+}
@@ -0,0 +1,168 @@
+use std::collections::HashMap;
+use std::env;
+use std::io::{stdin, stdout, BufRead};
+
+use ironlobe::command::{self, Command, USAGE};
+use ironlobe::io::*;
+use ironlobe::prelude::*;
+use ironlobe::render::RenderOptions;
+
+const TICKER: &str = "BOOK";
+
+fn build_owner(id: u128) -> Account {
+    let mut holdings: HashMap<String, Quantity> = HashMap::new();
+    holdings.insert(TICKER.to_string(), Quantity::from(1_000_000u32));
+
+    Account::new(id, "Account".to_string(), 1_000_000.00, holdings)
+}
+
+fn sample_orders() -> Vec<OrderRecord> {
+    vec![
+        OrderRecord::new(1, OrderType::Bid, 11.75, 40u32.into(), None),
+        OrderRecord::new(2, OrderType::Bid, 12.00, 25u32.into(), None),
+        OrderRecord::new(3, OrderType::Bid, 12.00, 10u32.into(), None),
+        OrderRecord::new(4, OrderType::Ask, 12.25, 15u32.into(), None),
+        OrderRecord::new(5, OrderType::Ask, 12.50, 60u32.into(), None),
+        OrderRecord::new(6, OrderType::Ask, 12.75, 30u32.into(), None),
+        OrderRecord::new(7, OrderType::Bid, 12.25, 20u32.into(), None),
+    ]
+}
+
+fn render(book: &Book, levels: usize) -> String {
+    book.render(RenderOptions {
+        levels,
+        cumulative: true,
+        color: true,
+        bars: true
+    })
+}
+
+/* Reads commands from stdin, one per line, e.g. `buy 100 @ 12.5`,
+ * `cancel 3` or `book`, or orders as JSON lines; see `command::USAGE`.
+ * Orders typed in are numbered on from the highest ID seen. */
+fn interactive(book: &mut Book) -> ironlobe::Result<()> {
+    let mut next_id: OrderId = 1;
+
+    for line in stdin().lock().lines() {
+        let line: String = line.map_err(IoError::from)?;
+        let command: Command = match command::parse(&line) {
+            Ok(Some(command)) => command,
+            Ok(None) => continue,
+            Err(e) => {
+                eprintln!("{}", e);
+                continue;
+            }
+        };
+        let traded: usize = book.get_trades().len();
+
+        let result: Result<(), BookError> = match command {
+            Command::Submit { side, quantity, price } => {
+                let id: OrderId = next_id;
+
+                next_id += 1;
+                println!("order {}", id);
+                book.submit(Order::new(id, build_owner(id),
+                                       TICKER.to_string(), side, price,
+                                       quantity))
+            },
+            Command::Record(record) => {
+                let id: OrderId = record.get_id();
+
+                next_id = next_id.max(id + 1);
+                book.submit(record.into_order(build_owner(id),
+                                              TICKER.to_string()))
+            },
+            Command::Modify { id, quantity, price } =>
+                book.modify(id, price, quantity),
+            Command::Cancel(id) => book.cancel(id).map(|_| ()),
+            Command::Book(levels) => {
+                println!("{}", render(book, levels.unwrap_or(10)));
+                Ok(())
+            },
+            Command::Trades => {
+                write_trades_csv(book.get_trades(), stdout())?;
+                Ok(())
+            },
+            Command::Help => {
+                println!("{}", USAGE);
+                Ok(())
+            },
+            Command::Quit => break
+        };
+
+        if let Err(e) = result {
+            eprintln!("rejected: {}", e);
+        }
+
+        for trade in book.get_trades().get(traded..).unwrap_or_default() {
+            println!("traded {} @ {}", trade.get_quantity(),
+                     trade.get_price());
+        }
+    }
+
+    Ok(())
+}
+
+/* usage: basic [--histogram WIDTH] [orders.csv | orders.jsonl | -]
+ *
+ * With `--histogram`, prints the resting depth summed into price bins of the
+ * given width as JSON, `{"bids": [[price, quantity], ...], "asks": ...}`,
+ * instead of rendering the book and its trades. With `-`, reads commands
+ * from stdin instead; see `interactive`. */
+fn main() -> ironlobe::Result<()> {
+    let mut args: Vec<String> = env::args().skip(1).collect();
+    let histogram: Option<f64> = match args.iter()
+        .position(|arg| arg == "--histogram") {
+        Some(index) => {
+            let width: String = args.get(index + 1).cloned()
+                .ok_or_else(|| IoError::MissingField("histogram".to_string()))?;
+            args.drain(index..=index + 1);
+            Some(width.parse::<f64>()
+                 .ok()
+                 .filter(|width| width.is_finite() && *width > 0.0)
+                 .ok_or_else(|| IoError::InvalidField("histogram".to_string(),
+                                                      width))?)
+        },
+        None => None
+    };
+
+    if args.first().map(String::as_str) == Some("-") {
+        let mut book: Book = Book::new(1, "Book".to_string(),
+                                       TICKER.to_string());
+
+        return interactive(&mut book);
+    }
+
+    let records: Vec<OrderRecord> = match args.first() {
+        Some(path) if path.ends_with(".csv") =>
+            CsvOrderReader::open(path, Schema::default())?
+                .collect::<Result<Vec<OrderRecord>, IoError>>()?,
+        Some(path) => JsonLinesOrderReader::open(path, Schema::default())?
+            .collect::<Result<Vec<OrderRecord>, IoError>>()?,
+        None => sample_orders()
+    };
+
+    let mut book: Book = Book::new(1, "Book".to_string(), TICKER.to_string());
+
+    for record in records {
+        let order: Order = record.clone().into_order(
+            build_owner(record.get_id()), TICKER.to_string());
+
+        if let Err(e) = book.submit(order) {
+            eprintln!("order {} rejected: {:?}", record.get_id(), e);
+        }
+    }
+
+    if let Some(width) = histogram {
+        serde_json::to_writer(stdout(), &book.histogram(width))
+            .map_err(IoError::from)?;
+        println!();
+        return Ok(());
+    }
+
+    println!("{}\n", render(&book, 10));
+
+    write_trades_csv(book.get_trades(), stdout())?;
+
+    Ok(())
+}
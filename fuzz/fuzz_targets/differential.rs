@@ -0,0 +1,52 @@
+#![no_main]
+
+/* Differential fuzzing of `Book` against `ReferenceBook`; see
+ * `tests/differential.rs`. Each operation is decoded from four bytes: what
+ * to do, which order, a price a few ticks either side of a mid, and a
+ * quantity. Run with `cargo fuzz run differential`. */
+
+use libfuzzer_sys::fuzz_target;
+
+use ironlobe::order::{OrderId, OrderType};
+use ironlobe::quantity::Quantity;
+use ironlobe::reference::{differential, DiffOp};
+
+fn decode(data: &[u8]) -> Vec<DiffOp> {
+    let mut next_id: OrderId = 1;
+
+    data.chunks_exact(4)
+        .map(|chunk| {
+            let (action, target, price, quantity) =
+                (chunk[0], chunk[1], chunk[2], chunk[3]);
+            let id: OrderId = OrderId::from(target);
+            let price: f64 = 95.0 + f64::from(price % 11);
+            let quantity: Quantity = Quantity::from(1 + quantity % 50);
+
+            match action % 8 {
+                0 => DiffOp::Cancel(id),
+                1 => DiffOp::Modify { id, price, quantity },
+                2 => DiffOp::Reduce { id, quantity },
+                side => {
+                    next_id += 1;
+                    DiffOp::Submit {
+                        id: next_id - 1,
+                        order_type: if side % 2 == 0 {
+                            OrderType::Bid
+                        } else {
+                            OrderType::Ask
+                        },
+                        price,
+                        quantity
+                    }
+                }
+            }
+        })
+        .collect()
+}
+
+fuzz_target!(|data: &[u8]| {
+    if let Err(divergence) = differential(&decode(data)) {
+        panic!("diverged at step {} ({:?}): {}", divergence.get_index(),
+               divergence.get_op(), divergence.get_reason());
+    }
+});
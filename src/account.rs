@@ -1,25 +1,77 @@
 use std::collections::HashMap;
 
+use serde::{Serialize, Deserialize};
+
+use crate::book::Book;
+use crate::clock::Clock;
+use crate::matching::MatchingPolicy;
+use crate::order::OrderType;
+use crate::position::{Pnl, Position};
+use crate::price::PriceType;
+use crate::quantity::{Quantity, ZERO};
+use crate::sink::EventSink;
+
 pub type AccountId = u128;
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq, thiserror::Error)]
 pub enum AccountError {
+    #[error("account holds none of that asset")]
     AssetNotFound,
+    #[error("account does not hold enough of that asset")]
+    InsufficientHoldings,
+    #[error("holding would overflow")]
+    HoldingOverflow,
+    /* a sale would take the account's short position past its limit */
+    #[error("sale would exceed the account's borrow limit")]
+    BorrowLimitExceeded,
 }
 
-#[derive(Debug, Default, Clone, PartialEq)]
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Account {
     id: AccountId,
     name: String,
     balance: f64,
-    holdings: HashMap<String, u128>
+    holdings: HashMap<String, Quantity>,
+    /* By ticker, one for all of the account's orders, kept up to date as
+     * they fill by whatever settles them: a book's ledger (see
+     * `Book::get_account`) or an exchange's `Settlement`. These start flat
+     * whatever was held beforehand, as its cost is unknown, so selling out
+     * of existing holdings shows as going short. */
+    #[serde(default)]
+    positions: HashMap<String, Position>,
+    /* how much of each ticker the account may be short, if it may be short
+     * of it at all; `None` for no limit */
+    #[serde(default)]
+    borrow_limits: HashMap<String, Option<Quantity>>,
+    /* how much of each ticker the account is short */
+    #[serde(default)]
+    borrowed: HashMap<String, Quantity>
+}
+
+/* only copies `ticker` for a new entry, as this is on the path of every
+ * fill */
+fn set(map: &mut HashMap<String, Quantity>, ticker: &str, quantity: Quantity) {
+    match map.get_mut(ticker) {
+        Some(held) => *held = quantity,
+        None => {
+            map.insert(ticker.to_string(), quantity);
+        }
+    }
 }
 
 #[allow(dead_code)]
 impl Account {
     pub fn new(id: AccountId, name: String, balance: f64,
-               holdings: HashMap<String, u128>) -> Account {
-        Account {id, name, balance, holdings}
+               holdings: HashMap<String, Quantity>) -> Account {
+        Account {
+            id,
+            name,
+            balance,
+            holdings,
+            positions: HashMap::new(),
+            borrow_limits: HashMap::new(),
+            borrowed: HashMap::new()
+        }
     }
 
     pub fn get_id(&self) -> AccountId {
@@ -58,15 +110,14 @@ impl Account {
         self.holdings.contains_key(&ticker)
     }
 
-    pub fn get_holding(&self, ticker: String) -> Result<u128, AccountError> {
-        if self.holds(ticker.clone()) {
-            Ok(self.holdings[&ticker])
-        } else {
-            Err(AccountError::AssetNotFound)
+    pub fn get_holding(&self, ticker: String) -> Result<Quantity, AccountError> {
+        match self.holdings.get(&ticker) {
+            Some(quantity) => Ok(*quantity),
+            None => Err(AccountError::AssetNotFound)
         }
     }
 
-    pub fn set_holding(&mut self, ticker: String, quantity: u128) -> 
+    pub fn set_holding(&mut self, ticker: String, quantity: Quantity) -> 
         Result<(), AccountError> {
         if self.holds(ticker.clone()) {
             self.holdings.remove(&ticker);
@@ -78,24 +129,169 @@ impl Account {
         Ok(())
     }
 
-    pub fn add_holding(&mut self, ticker: String, quantity: u128) -> Result<(), AccountError> {
-        if self.holds(ticker.clone()) {
-            self.set_holding(ticker.clone(), self.get_holding(ticker.clone())? + quantity)?;
+    pub fn can_add_holding(&self, ticker: &str, quantity: Quantity) ->
+        Result<(), AccountError> {
+        match self.holdings.get(ticker).unwrap_or(&ZERO)
+            .checked_add(quantity) {
+            Some(_) => Ok(()),
+            None => Err(AccountError::HoldingOverflow)
+        }
+    }
+
+    pub fn can_take_holding(&self, ticker: &str, quantity: Quantity) ->
+        Result<(), AccountError> {
+        let held: Quantity = self.holdings.get(ticker).copied()
+            .unwrap_or(ZERO);
+
+        if held >= quantity {
+            return Ok(());
+        }
+
+        let limit: Option<Quantity> = match self.borrow_limits.get(ticker) {
+            Some(limit) => *limit,
+            None if self.holdings.contains_key(ticker) =>
+                return Err(AccountError::InsufficientHoldings),
+            None => return Err(AccountError::AssetNotFound)
+        };
+        let short: Quantity = self.get_borrowed(ticker)
+            .checked_add(quantity - held)
+            .ok_or(AccountError::HoldingOverflow)?;
+
+        match limit {
+            Some(limit) if short > limit =>
+                Err(AccountError::BorrowLimitExceeded),
+            _ => Ok(())
+        }
+    }
+
+    /* covers any short position in `ticker` first */
+    pub fn add_holding(&mut self, ticker: &str, quantity: Quantity) -> Result<(), AccountError> {
+        let short: Quantity = self.get_borrowed(ticker);
+        let covered: Quantity = short.min(quantity);
+        let held: Quantity = self.holdings.get(ticker).copied()
+            .unwrap_or(ZERO);
+
+        set(&mut self.holdings, ticker, held.checked_add(quantity - covered)
+            .ok_or(AccountError::HoldingOverflow)?);
+
+        if covered == short {
+            self.borrowed.remove(ticker);
         } else {
-            return Err(AccountError::AssetNotFound);
+            set(&mut self.borrowed, ticker, short - covered);
         }
 
         Ok(())
     }
 
-    pub fn take_holding(&mut self, ticker: String, quantity: u128) -> Result<(), AccountError> {
-        if self.holds(ticker.clone()) {
-            self.set_holding(ticker.clone(), self.get_holding(ticker.clone())? - quantity)?;
-        } else {
-            return Err(AccountError::AssetNotFound);
+    /* borrows whatever the account does not hold, if it may go short */
+    pub fn take_holding(&mut self, ticker: &str, quantity: Quantity) -> Result<(), AccountError> {
+        self.can_take_holding(ticker, quantity)?;
+
+        let held: Quantity = self.holdings.get(ticker).copied()
+            .unwrap_or(ZERO);
+        let shortfall: Quantity = quantity - quantity.min(held);
+
+        set(&mut self.holdings, ticker, held - (quantity - shortfall));
+
+        if shortfall > ZERO {
+            let short: Quantity = self.get_borrowed(ticker);
+            set(&mut self.borrowed, ticker, short + shortfall);
         }
 
         Ok(())
     }
-}
 
+    /* lets the account sell `ticker` short, up to `limit` if there is
+     * one */
+    pub fn allow_short(&mut self, ticker: String, limit: Option<Quantity>) {
+        self.borrow_limits.insert(ticker, limit);
+    }
+
+    /* `None` if the account may not go short of `ticker` */
+    pub fn get_borrow_limit(&self, ticker: &str) -> Option<Option<Quantity>> {
+        self.borrow_limits.get(ticker).copied()
+    }
+
+    pub fn get_borrowed(&self, ticker: &str) -> Quantity {
+        self.borrowed.get(ticker).copied().unwrap_or(ZERO)
+    }
+
+    pub fn positions(&self) -> &HashMap<String, Position> {
+        &self.positions
+    }
+
+    pub fn get_position(&self, ticker: &str) -> Option<&Position> {
+        self.positions.get(ticker)
+    }
+
+    pub(crate) fn record_fill(&mut self, ticker: &str, side: &OrderType,
+                              quantity: Quantity, price: f64) {
+        if !self.positions.contains_key(ticker) {
+            self.positions.insert(ticker.to_string(), Position::default());
+        }
+
+        if let Some(position) = self.positions.get_mut(ticker) {
+            position.apply_fill(side, quantity, price);
+        }
+    }
+
+    /* Settles a fill of `quantity` of `ticker` at `price` on `side`
+     * against the account's balance, holdings and position. */
+    pub(crate) fn fill(&mut self, ticker: &str, side: &OrderType,
+                       quantity: Quantity, price: f64) ->
+        Result<(), AccountError> {
+        let value: f64 = price * crate::quantity::to_f64(quantity);
+
+        match side {
+            OrderType::Bid => {
+                self.add_holding(ticker, quantity)?;
+                self.take_balance(value);
+            },
+            OrderType::Ask => {
+                self.take_holding(ticker, quantity)?;
+                self.add_balance(value);
+            }
+        }
+
+        self.record_fill(ticker, side, quantity, price);
+        Ok(())
+    }
+
+    /* takes back a fill, as when its trade is busted: the balance and
+     * holdings are restored, and the position traded back out at the same
+     * price */
+    pub(crate) fn unfill(&mut self, ticker: &str, side: &OrderType,
+                         quantity: Quantity, price: f64) ->
+        Result<(), AccountError> {
+        let value: f64 = price * crate::quantity::to_f64(quantity);
+        let reverse: OrderType = match side {
+            OrderType::Bid => {
+                self.take_holding(ticker, quantity)?;
+                self.add_balance(value);
+                OrderType::Ask
+            },
+            OrderType::Ask => {
+                self.add_holding(ticker, quantity)?;
+                self.take_balance(value);
+                OrderType::Bid
+            }
+        };
+
+        self.record_fill(ticker, &reverse, quantity, price);
+        Ok(())
+    }
+
+    /* The account's profit and loss in `book`'s ticker, marked against
+     * the mid, or the last trade if either side of the book is empty.
+     * Nothing is unrealized if the book has never traded. */
+    pub fn pnl<M, S, C, P>(&self, book: &Book<M, S, C, P>) -> Pnl
+        where M: MatchingPolicy, S: EventSink, C: Clock, P: PriceType {
+        let mark: Option<f64> = book.top_of_book()
+            .get_mid()
+            .or_else(|| book.get_ltp().ok());
+
+        self.positions.get(&book.get_ticker())
+            .map(|position| position.pnl(mark))
+            .unwrap_or_default()
+    }
+}
@@ -0,0 +1,301 @@
+use std::convert::TryFrom;
+
+use chrono::{DateTime, Duration, Utc};
+use serde::{Serialize, Deserialize};
+
+use crate::account::Account;
+use crate::book::{Book, BookError};
+use crate::clock::{Clock, SimulatedClock};
+use crate::event::Trade;
+use crate::id::OrderIdGenerator;
+use crate::matching::MatchingPolicy;
+use crate::order::{Order, OrderId, OrderType};
+use crate::price::PriceType;
+use crate::quantity::{self, Quantity, ZERO};
+use crate::sink::EventSink;
+
+/* Execution algorithms: a parent order worked into a book as a schedule of
+ * child orders over time, for trying out in simulation or as the basis of
+ * a backtest. */
+
+/* `quantity` more of the parent, to be sent at `due` */
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Slice {
+    pub due: DateTime<Utc>,
+    pub quantity: Quantity
+}
+
+/* `quantity` split between buckets evenly spaced over `start` to `end` in
+ * proportion to `weights`, each bucket's slice due at its start. Rounding
+ * is carried forward, so the slices always sum to `quantity` (unless every
+ * weight is zero, when there are none). */
+pub fn weighted(quantity: Quantity, start: DateTime<Utc>, end: DateTime<Utc>,
+                weights: &[u64]) -> Vec<Slice> {
+    let total: Quantity = weights.iter().copied().map(Quantity::from).sum();
+    let buckets: i32 = match i32::try_from(weights.len()) {
+        Ok(buckets) if buckets > 0 && total > ZERO => buckets,
+        _ => return vec![]
+    };
+    let interval: Duration = (end - start) / buckets;
+    let mut cumulative: Quantity = ZERO;
+    let mut sent: Quantity = ZERO;
+    let mut slices: Vec<Slice> = Vec::with_capacity(weights.len());
+
+    for (bucket, weight) in (0..buckets).zip(weights.iter()) {
+        cumulative += Quantity::from(*weight);
+
+        let target: Quantity = quantity::share(cumulative, total, quantity);
+
+        slices.push(Slice {
+            due: start + interval * bucket,
+            quantity: target - sent
+        });
+        sent = target;
+    }
+
+    slices
+}
+
+/* time-weighted: `quantity` in `slices` equal parts over `start` to `end` */
+pub fn twap(quantity: Quantity, start: DateTime<Utc>, end: DateTime<Utc>,
+            slices: usize) -> Vec<Slice> {
+    weighted(quantity, start, end, &vec![1; slices])
+}
+
+/* volume-weighted: `quantity` split as the volume traded in each of the
+ * buckets of `profile` (e.g. each half hour of a typical day) over `start`
+ * to `end` */
+pub fn vwap(quantity: Quantity, start: DateTime<Utc>, end: DateTime<Utc>,
+            profile: &[u64]) -> Vec<Slice> {
+    weighted(quantity, start, end, profile)
+}
+
+/* Works a parent order into a book slice by slice. Each slice goes in as a
+ * single child order that takes what it can from the other side, at no
+ * worse than the parent's limit if it has one, or at the touch if not.
+ * Whatever a child leaves is cancelled rather than left resting, and is
+ * added to the next slice. */
+#[derive(Debug, Clone, PartialEq)]
+pub struct Execution {
+    owner: Account,
+    ticker: String,
+    side: OrderType,
+    limit: Option<f64>,
+    schedule: Vec<Slice>,
+    /* the next slice to send */
+    next: usize,
+    /* sent but not filled, for the next slice */
+    shortfall: Quantity,
+    filled: Quantity,
+    notional: f64,
+    children: Vec<OrderId>
+}
+
+impl Execution {
+    pub fn new(owner: Account, ticker: String, side: OrderType,
+               schedule: Vec<Slice>) -> Execution {
+        Execution {
+            owner,
+            ticker,
+            side,
+            limit: None,
+            schedule,
+            next: 0,
+            shortfall: ZERO,
+            filled: ZERO,
+            notional: 0.0,
+            children: vec![]
+        }
+    }
+
+    pub fn limit(mut self, limit: f64) -> Execution {
+        self.limit = Some(limit);
+        self
+    }
+
+    pub fn get_schedule(&self) -> &[Slice] {
+        &self.schedule
+    }
+
+    pub fn get_filled(&self) -> Quantity {
+        self.filled
+    }
+
+    /* left unfilled by the slices sent so far */
+    pub fn get_shortfall(&self) -> Quantity {
+        self.shortfall
+    }
+
+    /* every child order sent, in order */
+    pub fn get_children(&self) -> &[OrderId] {
+        &self.children
+    }
+
+    pub fn average_price(&self) -> Option<f64> {
+        if self.filled == ZERO {
+            None
+        } else {
+            Some(self.notional / quantity::to_f64(self.filled))
+        }
+    }
+
+    pub fn next_due(&self) -> Option<DateTime<Utc>> {
+        self.schedule.get(self.next).map(|slice| slice.due)
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.next >= self.schedule.len()
+    }
+
+    /* the price a child should go in at, if there is anything to take */
+    fn price<M, S, C, P>(&self, book: &Book<M, S, C, P>) -> Option<f64>
+    where M: MatchingPolicy, S: EventSink, C: Clock, P: PriceType {
+        let touch: f64 = match self.side {
+            OrderType::Bid => book.top_of_book().get_ask(),
+            OrderType::Ask => book.top_of_book().get_bid()
+        }.map(|(price, _)| price)?;
+
+        match (self.limit, &self.side) {
+            (Some(limit), OrderType::Bid) if touch > limit => None,
+            (Some(limit), OrderType::Ask) if touch < limit => None,
+            (limit, _) => Some(limit.unwrap_or(touch))
+        }
+    }
+
+    /* Sends every slice that is due by the book's clock, returning the
+     * children sent. A slice with nothing to take is carried forward
+     * without sending anything. */
+    pub fn step<M, S, C, P, G>(&mut self, book: &mut Book<M, S, C, P>,
+                               ids: &mut G) -> Result<Vec<OrderId>, BookError>
+    where M: MatchingPolicy, S: EventSink, C: Clock, P: PriceType,
+          G: OrderIdGenerator {
+        let now: DateTime<Utc> = book.get_clock().now();
+        let mut sent: Vec<OrderId> = vec![];
+
+        while let Some(slice) = self.schedule.get(self.next)
+            .filter(|slice| slice.due <= now) {
+            let quantity: Quantity = self.shortfall + slice.quantity;
+
+            self.next += 1;
+            self.shortfall = quantity;
+
+            let price: f64 = match self.price(book) {
+                Some(price) if quantity > ZERO => price,
+                _ => continue
+            };
+            let id: OrderId = ids.next_id();
+            let traded: usize = book.get_trades().len();
+
+            book.submit(Order::new(id, self.owner.clone(),
+                                   self.ticker.clone(), self.side,
+                                   price, quantity))?;
+
+            for trade in book.get_trades().get(traded..).unwrap_or_default()
+                .iter()
+                .filter(|trade: &&Trade| trade.get_aggressor() == id) {
+                self.filled += trade.get_quantity();
+                self.shortfall -= trade.get_quantity();
+                self.notional += trade.get_price() *
+                    quantity::to_f64(trade.get_quantity());
+            }
+
+            if book.get_order(id).is_ok() {
+                book.cancel(id)?;
+            }
+
+            self.children.push(id);
+            sent.push(id);
+        }
+
+        Ok(sent)
+    }
+}
+
+/* Works `execution` to the end of its schedule, moving the book's clock on
+ * to each slice as it falls due; the rest of the book stands still in the
+ * meantime. */
+pub fn run<M, S, P, G>(execution: &mut Execution,
+                       book: &mut Book<M, S, SimulatedClock, P>,
+                       ids: &mut G) -> Result<(), BookError>
+where M: MatchingPolicy, S: EventSink, P: PriceType, G: OrderIdGenerator {
+    while let Some(due) = execution.next_due() {
+        book.get_clock().advance_to(due);
+        execution.step(book, ids)?;
+    }
+
+    Ok(())
+}
+
+#[cfg(all(test, not(feature = "decimal-quantity")))]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use crate::builder::BookBuilder;
+    use crate::event::{Event, EventKind};
+    use crate::id::MonotonicIdGenerator;
+
+    fn account(id: u128) -> Account {
+        let mut holdings: HashMap<String, Quantity> = HashMap::new();
+        holdings.insert("BOOK".to_string(), 1000);
+
+        Account::new(id, "Account".to_string(), 100000.00, holdings)
+    }
+
+    #[test]
+    fn test_schedules() {
+        let start: DateTime<Utc> = DateTime::<Utc>::default();
+        let end: DateTime<Utc> = start + Duration::minutes(10);
+
+        assert_eq!(twap(10, start, end, 4).iter()
+                       .map(|slice| (slice.due - start, slice.quantity))
+                       .collect::<Vec<(Duration, Quantity)>>(),
+                   vec![(Duration::zero(), 2),
+                        (Duration::seconds(150), 3),
+                        (Duration::seconds(300), 2),
+                        (Duration::seconds(450), 3)]);
+        assert_eq!(vwap(100, start, end, &[1, 3, 0]).iter()
+                       .map(|slice| slice.quantity)
+                       .collect::<Vec<Quantity>>(),
+                   vec![25, 75, 0]);
+        assert!(vwap(100, start, end, &[0, 0]).is_empty());
+    }
+
+    #[test]
+    fn test_twap_execution() -> crate::Result<()> {
+        let start: DateTime<Utc> = DateTime::<Utc>::default();
+        let mut book: Book<_, _, SimulatedClock> =
+            BookBuilder::new(1, "BOOK".to_string())
+            .clock(SimulatedClock::new(start))
+            .build()?;
+        let mut ids: MonotonicIdGenerator = MonotonicIdGenerator::new();
+
+        for (price, quantity) in [(10.00, 30), (10.50, 30), (11.00, 100)] {
+            let id: OrderId = ids.next_id();
+
+            book.submit(Order::new(id, account(1), "BOOK".to_string(),
+                                   OrderType::Ask, price, quantity))?;
+        }
+
+        let mut execution: Execution =
+            Execution::new(account(2), "BOOK".to_string(), OrderType::Bid,
+                           twap(120, start, start + Duration::minutes(4), 4))
+            .limit(10.50);
+
+        run(&mut execution, &mut book, &mut ids)?;
+
+        /* the last slice finds nothing within the limit */
+        assert!(execution.is_done());
+        assert_eq!(execution.get_children().len(), 2);
+        assert_eq!(execution.get_filled(), 60);
+        assert_eq!(execution.get_shortfall(), 60);
+        assert_eq!(execution.average_price(), Some(10.25));
+
+        let times: Vec<DateTime<Utc>> = book.get_events().iter()
+            .filter(|event| matches!(event.get_kind(), EventKind::Match(_)))
+            .map(Event::get_timestamp)
+            .collect();
+
+        assert_eq!(times, vec![start, start + Duration::minutes(1)]);
+        Ok(())
+    }
+}
@@ -0,0 +1,700 @@
+use std::collections::VecDeque;
+use std::convert::TryFrom;
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use serde::{Serialize, Deserialize};
+
+use crate::book::BookId;
+use crate::event::{Event, EventKind};
+use crate::feed::normalize::MarketTrade;
+use crate::levels::{Level, TopOfBook};
+use crate::order::OrderType;
+use crate::quantity::{self, Quantity};
+use crate::sink::{EventSink, SinkError};
+
+/* the windows a book keeps statistics over unless told otherwise */
+pub const DEFAULT_WINDOWS: [Duration; 3] = [Duration::from_secs(60),
+                                            Duration::from_secs(300),
+                                            Duration::from_secs(3600)];
+
+/* how long each candle covers, and how many are kept, unless told
+ * otherwise */
+pub const DEFAULT_CANDLE_INTERVAL: Duration = Duration::from_secs(60);
+pub const DEFAULT_CANDLE_LIMIT: usize = 1440;
+
+/* where a mark price came from */
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum MarkSource {
+    Mid,
+    LastTrade,
+    /* the book's configured reference price; see `BookConfig` */
+    Reference
+}
+
+/* which of the mid and the last trade to mark at, first choice first */
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize,
+         Deserialize)]
+pub enum MarkFallback {
+    #[default]
+    MidThenLast,
+    LastThenMid,
+    MidOnly,
+    LastOnly
+}
+
+/* How a book picks its mark price (see `Book::mark_price`): the first
+ * source in `fallback` updated within `stale_after`, or failing that the
+ * first with a price at all, marked stale, or failing that the book's
+ * reference price. Without `stale_after`, nothing is ever stale. */
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct MarkPolicy {
+    pub fallback: MarkFallback,
+    pub stale_after: Option<Duration>
+}
+
+/* one price to mark positions at, and how current it is */
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Mark {
+    pub price: f64,
+    pub source: MarkSource,
+    /* when the source last changed, if known */
+    pub updated: Option<DateTime<Utc>>,
+    /* how long ago that was, as of marking */
+    pub age: Option<Duration>,
+    pub stale: bool
+}
+
+impl MarkPolicy {
+    /* marks at the mid or the last trade, each given with when it last
+     * changed, if known, or else at `reference` */
+    pub fn mark(&self, mid: Option<(f64, Option<DateTime<Utc>>)>,
+                last: Option<(f64, Option<DateTime<Utc>>)>,
+                reference: Option<f64>, now: DateTime<Utc>) -> Option<Mark> {
+        let (mid, last) = (mid.map(|(price, updated)| (MarkSource::Mid,
+                                                       price, updated)),
+                           last.map(|(price, updated)| (MarkSource::LastTrade,
+                                                        price, updated)));
+        let candidates: Vec<(MarkSource, f64, Option<DateTime<Utc>>)> =
+            match self.fallback {
+                MarkFallback::MidThenLast => vec![mid, last],
+                MarkFallback::LastThenMid => vec![last, mid],
+                MarkFallback::MidOnly => vec![mid],
+                MarkFallback::LastOnly => vec![last]
+            }.into_iter().flatten().collect();
+        let marks: Vec<Mark> = candidates.into_iter()
+            .map(|(source, price, updated)| {
+                let age: Option<Duration> = updated
+                    .and_then(|updated| (now - updated).to_std().ok());
+                let stale: bool = match self.stale_after {
+                    Some(limit) => age.is_none_or(|age| age > limit),
+                    None => false
+                };
+
+                Mark {price, source, updated, age, stale}
+            })
+            .collect();
+
+        marks.iter().find(|mark| !mark.stale)
+            .or_else(|| marks.first())
+            .copied()
+            .or_else(|| reference.map(|price| Mark {
+                price,
+                source: MarkSource::Reference,
+                updated: None,
+                age: None,
+                stale: false
+            }))
+    }
+}
+
+/* what happened over one window, as of the latest event */
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct WindowStats {
+    pub length: Duration,
+    pub trades: usize,
+    pub volume: Quantity,
+    /* volume by aggressor side; trades whose side couldn't be told count
+     * towards neither */
+    pub buy_volume: Quantity,
+    pub sell_volume: Quantity,
+    /* the square root of the sum of squared log returns between successive
+     * trades, unannualised */
+    pub realized_volatility: f64
+}
+
+impl WindowStats {
+    /* buyer-initiated less seller-initiated volume */
+    pub fn signed_volume(&self) -> f64 {
+        quantity::to_f64(self.buy_volume) - quantity::to_f64(self.sell_volume)
+    }
+
+    /* signed volume as a share of the volume whose side is known, from -1
+     * (all selling) to 1 (all buying); zero if there is none */
+    pub fn imbalance(&self) -> f64 {
+        let known: f64 = quantity::to_f64(self.buy_volume) +
+            quantity::to_f64(self.sell_volume);
+
+        if known > 0.0 {
+            self.signed_volume() / known
+        } else {
+            0.0
+        }
+    }
+}
+
+/* The trades in one interval, from `start` up to the next interval. Made
+ * from trades as they are seen, so a late trade adds to its candle's high,
+ * low and volume but never becomes its open or close. */
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Candle {
+    pub start: DateTime<Utc>,
+    pub open: f64,
+    pub high: f64,
+    pub low: f64,
+    pub close: f64,
+    pub volume: Quantity,
+    pub trades: usize
+}
+
+impl Candle {
+    fn new(start: DateTime<Utc>, price: f64, quantity: Quantity) -> Candle {
+        Candle {
+            start,
+            open: price,
+            high: price,
+            low: price,
+            close: price,
+            volume: quantity,
+            trades: 1
+        }
+    }
+
+    fn add(&mut self, price: f64, quantity: Quantity, latest: bool) {
+        self.high = self.high.max(price);
+        self.low = self.low.min(price);
+        self.volume += quantity;
+        self.trades += 1;
+
+        if latest {
+            self.close = price;
+        }
+    }
+}
+
+/* the most recent candles, oldest first, with no candles for intervals
+ * without trades */
+#[derive(Debug, Clone, PartialEq)]
+struct Candles {
+    interval: Duration,
+    limit: usize,
+    candles: VecDeque<Candle>
+}
+
+impl Candles {
+    fn new(interval: Duration, limit: usize) -> Candles {
+        Candles {
+            interval,
+            limit,
+            candles: VecDeque::new()
+        }
+    }
+
+    /* the start of the interval `timestamp` falls in */
+    fn start(&self, timestamp: DateTime<Utc>) -> DateTime<Utc> {
+        let interval: i64 = i64::try_from(self.interval.as_millis())
+            .unwrap_or(i64::MAX)
+            .max(1);
+        let millis: i64 = timestamp.timestamp_millis();
+
+        DateTime::<Utc>::from_timestamp_millis(
+            millis - millis.rem_euclid(interval))
+            .unwrap_or(timestamp)
+    }
+
+    fn push(&mut self, timestamp: DateTime<Utc>, price: f64,
+            quantity: Quantity) {
+        let start: DateTime<Utc> = self.start(timestamp);
+        let latest: Option<DateTime<Utc>> = self.candles.back()
+            .map(|candle| candle.start);
+
+        match latest {
+            Some(latest) if start < latest => {
+                if let Some(candle) = self.candles.iter_mut()
+                    .find(|candle| candle.start == start) {
+                    candle.add(price, quantity, false);
+                }
+            },
+            Some(latest) if start == latest => {
+                if let Some(candle) = self.candles.back_mut() {
+                    candle.add(price, quantity, true);
+                }
+            },
+            _ => {
+                self.candles.push_back(Candle::new(start, price, quantity));
+
+                while self.candles.len() > self.limit {
+                    self.candles.pop_front();
+                }
+            }
+        }
+    }
+}
+
+/* a trade still inside a window */
+#[derive(Debug, Clone, PartialEq)]
+struct Sample {
+    timestamp: DateTime<Utc>,
+    quantity: Quantity,
+    aggressor_side: Option<OrderType>,
+    /* the squared log return from the trade before, if there was one */
+    squared_return: f64
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Window {
+    length: Duration,
+    samples: VecDeque<Sample>,
+    volume: Quantity,
+    buy_volume: Quantity,
+    sell_volume: Quantity,
+    squared_returns: f64
+}
+
+impl Window {
+    fn new(length: Duration) -> Window {
+        Window {
+            length,
+            samples: VecDeque::new(),
+            volume: quantity::ZERO,
+            buy_volume: quantity::ZERO,
+            sell_volume: quantity::ZERO,
+            squared_returns: 0.0
+        }
+    }
+
+    fn side_volume(&mut self, side: Option<OrderType>) ->
+        Option<&mut Quantity> {
+        match side? {
+            OrderType::Bid => Some(&mut self.buy_volume),
+            OrderType::Ask => Some(&mut self.sell_volume)
+        }
+    }
+
+    fn push(&mut self, sample: Sample) {
+        self.volume += sample.quantity;
+
+        if let Some(volume) = self.side_volume(sample.aggressor_side) {
+            *volume += sample.quantity;
+        }
+
+        self.squared_returns += sample.squared_return;
+        self.samples.push_back(sample);
+    }
+
+    /* drops whatever is older than the window as of `now` */
+    fn expire(&mut self, now: DateTime<Utc>) {
+        let length: chrono::Duration = chrono::Duration::from_std(self.length)
+            .unwrap_or(chrono::Duration::MAX);
+
+        while let Some(oldest) = self.samples.front() {
+            if now.signed_duration_since(oldest.timestamp) <= length {
+                break;
+            }
+
+            let (quantity, side) = (oldest.quantity, oldest.aggressor_side);
+
+            self.volume -= quantity;
+            self.squared_returns -= oldest.squared_return;
+            self.samples.pop_front();
+
+            if let Some(volume) = self.side_volume(side) {
+                *volume -= quantity;
+            }
+        }
+
+        /* rather than let rounding errors pile up */
+        if self.samples.is_empty() {
+            self.squared_returns = 0.0;
+        }
+    }
+
+    fn stats(&self) -> WindowStats {
+        WindowStats {
+            length: self.length,
+            trades: self.samples.len(),
+            volume: self.volume,
+            buy_volume: self.buy_volume,
+            sell_volume: self.sell_volume,
+            realized_volatility: self.squared_returns.max(0.0).sqrt()
+        }
+    }
+}
+
+/* Infers which side aggressed in trades reported without it, after Lee
+ * and Ready (1991): a trade above the mid was bought, one below it sold,
+ * and one at the mid (or with no quote to go on) is signed by the tick
+ * test, as a buy if it traded above the last different price and a sale if
+ * below. Feed it every trade in order, including those whose side is
+ * known, so that the tick test has the prices before each. */
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct TradeClassifier {
+    last_price: Option<f64>,
+    /* the direction of the last price change */
+    tick: Option<OrderType>
+}
+
+impl TradeClassifier {
+    pub fn new() -> TradeClassifier {
+        TradeClassifier::default()
+    }
+
+    /* the aggressor side of a trade at `price` given the `mid` prevailing
+     * when it happened, if it can be told at all */
+    pub fn classify(&mut self, price: f64, mid: Option<f64>) ->
+        Option<OrderType> {
+        let tick: Option<OrderType> = match self.last_price {
+            Some(last) if price > last => Some(OrderType::Bid),
+            Some(last) if price < last => Some(OrderType::Ask),
+            _ => self.tick
+        };
+
+        self.last_price = Some(price);
+        self.tick = tick;
+
+        match mid {
+            Some(mid) if price > mid => Some(OrderType::Bid),
+            Some(mid) if price < mid => Some(OrderType::Ask),
+            _ => tick
+        }
+    }
+}
+
+/* Everything a dashboard shows about a book, in one go; see `Book::stats`.
+ * Depths and order counts are of displayed orders only, as for
+ * `Book::levels`. */
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BookStats {
+    pub book: BookId,
+    pub ticker: String,
+    /* of the last event the statistics reflect */
+    pub seq: u64,
+    /* by the book's clock */
+    pub timestamp: DateTime<Utc>,
+    pub best_bid: Option<Level>,
+    pub best_ask: Option<Level>,
+    pub spread: Option<f64>,
+    pub mid: Option<f64>,
+    pub last_price: Option<f64>,
+    pub bid_depth: Quantity,
+    pub ask_depth: Quantity,
+    pub bid_levels: usize,
+    pub ask_levels: usize,
+    pub bid_orders: usize,
+    pub ask_orders: usize,
+    /* since the book opened, less any busted */
+    pub trades: usize,
+    pub volume: Quantity
+}
+
+/* Spread, mid and rolling trade statistics, kept up to date from a book's
+ * events as they happen so that nothing need rescan the tape. Windows are
+ * measured in event time, so they work as well under a simulated clock as
+ * a live one. Books keep one (see `Book::market_stats`); it is also a sink,
+ * for following a book from elsewhere. */
+#[derive(Debug, Clone, PartialEq)]
+pub struct MarketStats {
+    top: TopOfBook,
+    /* when the top of book last changed, if it was seen to */
+    top_updated: Option<DateTime<Utc>>,
+    last_price: Option<f64>,
+    /* when the last trade was, if one was seen */
+    last_traded: Option<DateTime<Utc>>,
+    classifier: TradeClassifier,
+    windows: Vec<Window>,
+    candles: Candles
+}
+
+impl Default for MarketStats {
+    fn default() -> MarketStats {
+        MarketStats::new(&DEFAULT_WINDOWS)
+    }
+}
+
+impl MarketStats {
+    pub fn new(windows: &[Duration]) -> MarketStats {
+        MarketStats {
+            top: TopOfBook::default(),
+            top_updated: None,
+            last_price: None,
+            last_traded: None,
+            classifier: TradeClassifier::new(),
+            windows: windows.iter().copied().map(Window::new).collect(),
+            candles: Candles::new(DEFAULT_CANDLE_INTERVAL,
+                                  DEFAULT_CANDLE_LIMIT)
+        }
+    }
+
+    /* starts the windows afresh with these lengths */
+    pub fn set_windows(&mut self, windows: &[Duration]) {
+        self.windows = windows.iter().copied().map(Window::new).collect();
+    }
+
+    /* starts the candles afresh, each `interval` long, keeping the latest
+     * `limit` of them */
+    pub fn set_candles(&mut self, interval: Duration, limit: usize) {
+        self.candles = Candles::new(interval, limit);
+    }
+
+    pub fn observe(&mut self, event: &Event) {
+        match event.get_kind() {
+            EventKind::TopOfBook { current, .. } => {
+                self.top = *current;
+                self.top_updated = Some(event.get_timestamp());
+            },
+            EventKind::Match(trade) => {
+                self.classifier.classify(trade.get_price(), self.get_mid());
+                self.record(event.get_timestamp(), trade.get_price(),
+                            trade.get_quantity(),
+                            Some(trade.get_aggressor_side()));
+            },
+            _ => {}
+        }
+
+        self.expire(event.get_timestamp());
+    }
+
+    /* A trade from elsewhere, e.g. a venue's feed. Those without an
+     * aggressor side are classified against the current top of book (see
+     * `TradeClassifier`), so keep it current with `observe_top`. */
+    pub fn observe_trade(&mut self, trade: &MarketTrade) {
+        let classified: Option<OrderType> =
+            self.classifier.classify(trade.get_price(), self.get_mid());
+
+        self.record(trade.get_timestamp(), trade.get_price(),
+                    trade.get_quantity(),
+                    trade.get_aggressor_side().or(classified));
+        self.expire(trade.get_timestamp());
+    }
+
+    /* the best bid and ask from elsewhere, as for `observe_trade` */
+    pub fn observe_top(&mut self, top: TopOfBook) {
+        self.top = top;
+    }
+
+    fn record(&mut self, timestamp: DateTime<Utc>, price: f64,
+              quantity: Quantity, aggressor_side: Option<OrderType>) {
+        let squared_return: f64 = match self.last_price {
+            Some(last) if last > 0.0 && price > 0.0 =>
+                (price / last).ln().powi(2),
+            _ => 0.0
+        };
+
+        for window in self.windows.iter_mut() {
+            window.push(Sample {
+                timestamp,
+                quantity,
+                aggressor_side,
+                squared_return
+            });
+        }
+
+        self.candles.push(timestamp, price, quantity);
+        self.last_price = Some(price);
+        self.last_traded = Some(timestamp);
+    }
+
+    fn expire(&mut self, now: DateTime<Utc>) {
+        for window in self.windows.iter_mut() {
+            window.expire(now);
+        }
+    }
+
+    pub fn get_top(&self) -> TopOfBook {
+        self.top
+    }
+
+    pub fn get_top_updated(&self) -> Option<DateTime<Utc>> {
+        self.top_updated
+    }
+
+    pub fn get_spread(&self) -> Option<f64> {
+        self.top.get_spread()
+    }
+
+    pub fn get_mid(&self) -> Option<f64> {
+        self.top.get_mid()
+    }
+
+    pub fn get_last_price(&self) -> Option<f64> {
+        self.last_price
+    }
+
+    pub fn get_last_traded(&self) -> Option<DateTime<Utc>> {
+        self.last_traded
+    }
+
+    /* the statistics over the window of exactly `length`, if kept */
+    pub fn window(&self, length: Duration) -> Option<WindowStats> {
+        self.windows.iter()
+            .find(|window| window.length == length)
+            .map(Window::stats)
+    }
+
+    /* every window's statistics, shortest first as given */
+    pub fn windows(&self) -> Vec<WindowStats> {
+        self.windows.iter().map(Window::stats).collect()
+    }
+
+    pub fn get_candle_interval(&self) -> Duration {
+        self.candles.interval
+    }
+
+    /* the candles kept, oldest first */
+    pub fn candles(&self) -> impl Iterator<Item=&Candle> {
+        self.candles.candles.iter()
+    }
+}
+
+impl EventSink for MarketStats {
+    fn write(&mut self, event: &Event) -> Result<(), SinkError> {
+        self.observe(event);
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), SinkError> {
+        Ok(())
+    }
+}
+
+#[cfg(all(test, not(feature = "decimal-quantity")))]
+mod tests {
+    use super::*;
+    use crate::event::Trade;
+    use crate::order::OrderType;
+
+    fn trade(seq: u64, seconds: i64, price: f64, quantity: Quantity) ->
+        Event {
+        let timestamp: DateTime<Utc> = DateTime::<Utc>::default() +
+            chrono::Duration::seconds(seconds);
+
+        Event::new(seq, timestamp, EventKind::Match(
+            Trade::new(timestamp, price, quantity, 2, 1, OrderType::Bid)))
+    }
+
+    #[test]
+    fn test_mark_fallback() {
+        let now: DateTime<Utc> = Utc::now();
+        let old: DateTime<Utc> = now - chrono::Duration::minutes(5);
+        let policy: MarkPolicy = MarkPolicy {
+            fallback: MarkFallback::LastThenMid,
+            stale_after: Some(Duration::from_secs(60))
+        };
+        let pick = |policy: MarkPolicy, mid, last| policy
+            .mark(mid, last, None, now)
+            .map(|mark| (mark.source, mark.stale));
+
+        assert_eq!(pick(policy, Some((11.5, Some(now))), Some((12.0, None))),
+                   Some((MarkSource::Mid, false)));
+        assert_eq!(pick(policy, Some((11.5, Some(old))), Some((12.0, None))),
+                   Some((MarkSource::LastTrade, true)));
+        assert_eq!(pick(MarkPolicy::default(), None, Some((12.0, Some(old)))),
+                   Some((MarkSource::LastTrade, false)));
+        assert_eq!(pick(MarkPolicy {
+                            fallback: MarkFallback::MidOnly,
+                            stale_after: None
+                        }, None, Some((12.0, Some(now)))),
+                   None);
+    }
+
+    #[test]
+    fn test_rolling_windows() {
+        let minute: Duration = Duration::from_secs(60);
+        let mut stats: MarketStats =
+            MarketStats::new(&[minute, Duration::from_secs(3600)]);
+
+        stats.observe(&trade(1, 0, 100.0, 5));
+        stats.observe(&trade(2, 30, 110.0, 3));
+        stats.observe(&trade(3, 90, 99.0, 2));
+
+        let actual: Option<WindowStats> = stats.window(minute);
+
+        /* the first trade has dropped out of the minute */
+        assert_eq!(actual.map(|window| (window.trades, window.volume)),
+                   Some((2, 5)));
+        assert_eq!(stats.windows()[1].trades, 3);
+
+        let expected_volatility: f64 = ((110.0f64 / 100.0).ln().powi(2) +
+                                        (99.0f64 / 110.0).ln().powi(2))
+            .sqrt();
+
+        assert!((stats.windows()[1].realized_volatility -
+                 expected_volatility).abs() < 1e-12);
+        assert_eq!(stats.get_last_price(), Some(99.0));
+    }
+
+    #[test]
+    fn test_candles() {
+        let mut stats: MarketStats = MarketStats::default();
+
+        stats.set_candles(Duration::from_secs(60), 2);
+        stats.observe(&trade(1, 0, 100.0, 5));
+        stats.observe(&trade(2, 30, 110.0, 3));
+        stats.observe(&trade(3, 45, 95.0, 1));
+        stats.observe(&trade(4, 90, 99.0, 2));
+        /* a late trade only widens its candle */
+        stats.observe(&trade(5, 20, 120.0, 1));
+
+        let candles: Vec<(f64, f64, f64, f64, Quantity)> = stats.candles()
+            .map(|candle| (candle.open, candle.high, candle.low, candle.close,
+                           candle.volume))
+            .collect();
+
+        assert_eq!(candles, vec![(100.0, 120.0, 95.0, 95.0, 10),
+                                 (99.0, 99.0, 99.0, 99.0, 2)]);
+
+        /* only the latest are kept */
+        stats.observe(&trade(6, 150, 101.0, 1));
+        assert_eq!(stats.candles().map(|candle| candle.open)
+                       .collect::<Vec<f64>>(),
+                   vec![99.0, 101.0]);
+    }
+
+    #[test]
+    fn test_order_flow() {
+        let minute: Duration = Duration::from_secs(60);
+        let mut stats: MarketStats = MarketStats::new(&[minute]);
+        let at = |seconds: i64| DateTime::<Utc>::default() +
+            chrono::Duration::seconds(seconds);
+
+        /* a buy, then venue trades signed by quote, then by tick */
+        stats.observe(&trade(1, 0, 100.0, 5));
+        stats.observe_top(TopOfBook::new(Some((99.0, 10)),
+                                         Some((101.0, 10))));
+        stats.observe_trade(&MarketTrade::new(at(1), 99.0, 4, None));
+        stats.observe_trade(&MarketTrade::new(at(2), 100.0, 2, None));
+        stats.observe_trade(&MarketTrade::new(at(3), 100.0, 1, None));
+        stats.observe_trade(&MarketTrade::new(at(4), 100.5, 3,
+                                              Some(OrderType::Ask)));
+
+        let window: Option<WindowStats> = stats.window(minute);
+
+        assert_eq!(window.map(|w| (w.volume, w.buy_volume, w.sell_volume)),
+                   Some((15, 8, 7)));
+        assert_eq!(window.map(|w| w.signed_volume()), Some(1.0));
+        assert_eq!(window.map(|w| w.imbalance()), Some(1.0 / 15.0));
+    }
+
+    #[test]
+    fn test_tick_test() {
+        let mut classifier: TradeClassifier = TradeClassifier::new();
+        let actual: Vec<Option<OrderType>> = [10.0, 10.5, 10.5, 10.25, 10.25]
+            .iter()
+            .map(|price| classifier.classify(*price, None))
+            .collect();
+
+        assert_eq!(actual, vec![None, Some(OrderType::Bid),
+                                Some(OrderType::Bid), Some(OrderType::Ask),
+                                Some(OrderType::Ask)]);
+    }
+}
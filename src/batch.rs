@@ -0,0 +1,62 @@
+use serde::{Serialize, Deserialize};
+
+use crate::book::{BookError, CancelResult};
+use crate::order::{Order, OrderId};
+use crate::quantity::Quantity;
+
+/* one operation of a batch; see `Book::apply_batch` */
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum BookOp {
+    Submit(Box<Order>),
+    Cancel(OrderId),
+    Modify {
+        id: OrderId,
+        price: f64,
+        quantity: Quantity
+    },
+    Reduce {
+        id: OrderId,
+        quantity: Quantity
+    }
+}
+
+/* what each operation of a batch did, as its own method would have
+ * returned */
+#[derive(Debug, Clone, PartialEq)]
+pub enum BookOpOutcome {
+    Submitted,
+    Cancelled(Box<CancelResult>),
+    Modified,
+    /* what is left of the order */
+    Reduced(Quantity)
+}
+
+/* the outcome of every operation, in order, or the first that failed */
+pub type BatchResult = Result<Vec<BookOpOutcome>, BatchError>;
+
+#[derive(Debug, thiserror::Error)]
+#[error("operation {index} of the batch failed: {error}")]
+pub struct BatchError {
+    index: usize,
+    #[source]
+    error: BookError
+}
+
+impl BatchError {
+    pub fn new(index: usize, error: BookError) -> BatchError {
+        BatchError {index, error}
+    }
+
+    /* the position of the failing operation in the batch */
+    pub fn get_index(&self) -> usize {
+        self.index
+    }
+
+    pub fn get_error(&self) -> &BookError {
+        &self.error
+    }
+
+    pub fn into_error(self) -> BookError {
+        self.error
+    }
+}
@@ -0,0 +1,89 @@
+use std::collections::HashMap;
+use std::env;
+use std::fs::File;
+use std::io::{stdin, BufRead, BufWriter};
+
+use ironlobe::batch::BookOp;
+use ironlobe::command::{self, Command, USAGE};
+use ironlobe::io::IoError;
+use ironlobe::journal::JournalWriter;
+use ironlobe::prelude::*;
+use ironlobe::render::RenderOptions;
+
+const TICKER: &str = "BOOK";
+
+fn build_owner(id: u128) -> Account {
+    let mut holdings: HashMap<String, Quantity> = HashMap::new();
+    holdings.insert(TICKER.to_string(), Quantity::from(1_000_000u32));
+
+    Account::new(id, "Account".to_string(), 1_000_000.00, holdings)
+}
+
+/* usage: ironlobe-record RECORDING
+ *
+ * Reads order flow from stdin, one command per line as for the `basic`
+ * example (`buy 100 @ 12.5`, `cancel 3`, an order as a line of JSON, ...;
+ * see `command::USAGE`), applies it to a fresh book and records it, and
+ * everything the book did with it, to RECORDING for `ironlobe-replay`. */
+fn main() -> ironlobe::Result<()> {
+    let path: String = env::args().nth(1)
+        .ok_or_else(|| IoError::MissingField("recording".to_string()))?;
+    let file: File = File::create(&path).map_err(IoError::from)?;
+    let mut book: Book = Book::new(1, "Book".to_string(), TICKER.to_string());
+    let mut journal: JournalWriter<BufWriter<File>> =
+        JournalWriter::start(&book, BufWriter::new(file))?;
+    let mut next_id: OrderId = 1;
+
+    for line in stdin().lock().lines() {
+        let line: String = line.map_err(IoError::from)?;
+        let op: BookOp = match command::parse(&line) {
+            Ok(Some(Command::Submit { side, quantity, price })) => {
+                let id: OrderId = next_id;
+
+                next_id += 1;
+                println!("order {}", id);
+                BookOp::Submit(Box::new(Order::new(id, build_owner(id),
+                                                   TICKER.to_string(), side,
+                                                   price, quantity)))
+            },
+            Ok(Some(Command::Record(record))) => {
+                let id: OrderId = record.get_id();
+
+                next_id = next_id.max(id + 1);
+                BookOp::Submit(Box::new(record.into_order(
+                    build_owner(id), TICKER.to_string())))
+            },
+            Ok(Some(Command::Modify { id, quantity, price })) =>
+                BookOp::Modify { id, price, quantity },
+            Ok(Some(Command::Cancel(id))) => BookOp::Cancel(id),
+            Ok(Some(Command::Book(levels))) => {
+                println!("{}", book.render(RenderOptions {
+                    levels: levels.unwrap_or(10),
+                    ..RenderOptions::default()
+                }));
+                continue;
+            },
+            Ok(Some(Command::Trades)) => {
+                println!("{} trades", book.get_trades().len());
+                continue;
+            },
+            Ok(Some(Command::Help)) => {
+                println!("{}", USAGE);
+                continue;
+            },
+            Ok(Some(Command::Quit)) => break,
+            Ok(None) => continue,
+            Err(e) => {
+                eprintln!("{}", e);
+                continue;
+            }
+        };
+
+        if let Err(e) = journal.apply(&mut book, op)? {
+            eprintln!("rejected: {}", e);
+        }
+    }
+
+    println!("recorded {} events to {}", book.last_seq(), path);
+    Ok(())
+}
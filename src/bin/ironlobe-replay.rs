@@ -0,0 +1,42 @@
+use std::env;
+use std::fs::File;
+use std::io::BufReader;
+use std::process::ExitCode;
+
+use ironlobe::io::IoError;
+use ironlobe::journal::{self, Replay};
+use ironlobe::render::RenderOptions;
+
+/* usage: ironlobe-replay RECORDING
+ *
+ * Replays a recording made by `ironlobe-record` (or a `JournalWriter`) into
+ * a fresh book, checking that it does exactly what it did the first time,
+ * and shows the book it ends up with. Exits non-zero if it doesn't. */
+fn replay(path: &str) -> ironlobe::Result<Replay> {
+    let file: File = File::open(path).map_err(IoError::from)?;
+
+    Ok(journal::replay(BufReader::new(file))?)
+}
+
+fn main() -> ExitCode {
+    let path: String = match env::args().nth(1) {
+        Some(path) => path,
+        None => {
+            eprintln!("usage: ironlobe-replay RECORDING");
+            return ExitCode::from(2);
+        }
+    };
+
+    match replay(&path) {
+        Ok(replay) => {
+            println!("{}\n", replay.book.render(RenderOptions::default()));
+            println!("replayed {} operations and {} events: identical",
+                     replay.ops, replay.events);
+            ExitCode::SUCCESS
+        },
+        Err(e) => {
+            eprintln!("{}: {}", path, e);
+            ExitCode::FAILURE
+        }
+    }
+}
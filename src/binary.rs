@@ -0,0 +1,174 @@
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+
+use crate::event::{Event, Trade};
+use crate::levels::Levels;
+use crate::snapshot::BookSnapshot;
+
+/* A compact alternative to JSON for replaying and storing large volumes of
+ * events and snapshots. Every encoding starts with a small header:
+ *
+ *     | "ILOB" (4) | version (u16, LE) | payload kind (u8) | payload |
+ *
+ * where the payload is bincode. Readers refuse versions they don't know
+ * rather than misinterpreting them. */
+
+const MAGIC: [u8; 4] = *b"ILOB";
+/* 2 added events' actor and reason, 3 snapshots' pegs, 4 books' integrity
+ * checking, 5 events' status transitions, 6 hidden orders, 7 expiry and
+ * settlement, 8 trade IDs and busts, 9 reference prices, 10 bracket
+ * orders, 11 sweep limits, 12 mark price policies, 13 client order IDs
+ * and tags, 14 trades' accounts, 15 circuit breaker cancels, 16 hidden
+ * orders' events, 17 snapshots' accounts */
+pub const VERSION: u16 = 17;
+const HEADER_LENGTH: usize = 7;
+
+#[derive(Debug, thiserror::Error)]
+pub enum BinaryError {
+    #[error("not an ironlobe binary encoding")]
+    BadMagic,
+    #[error("unsupported encoding version {0}")]
+    UnsupportedVersion(u16),
+    #[error("expected payload kind {expected} but found {found}")]
+    WrongKind { expected: u8, found: u8 },
+    #[error("encoding is truncated")]
+    Truncated,
+    #[error("bincode error: {0}")]
+    Encoding(#[from] bincode::Error),
+}
+
+/* things with a binary encoding, each tagged so that one can't be decoded
+ * as another */
+pub trait BinaryPayload: Serialize + DeserializeOwned {
+    const KIND: u8;
+}
+
+impl BinaryPayload for Event {
+    const KIND: u8 = 1;
+}
+
+impl BinaryPayload for Vec<Event> {
+    const KIND: u8 = 2;
+}
+
+impl BinaryPayload for Levels {
+    const KIND: u8 = 3;
+}
+
+impl BinaryPayload for BookSnapshot {
+    const KIND: u8 = 4;
+}
+
+impl BinaryPayload for Trade {
+    const KIND: u8 = 5;
+}
+
+pub fn encode<T: BinaryPayload>(value: &T) -> Result<Vec<u8>, BinaryError> {
+    let mut bytes: Vec<u8> = Vec::with_capacity(HEADER_LENGTH);
+
+    bytes.extend_from_slice(&MAGIC);
+    bytes.extend_from_slice(&VERSION.to_le_bytes());
+    bytes.push(T::KIND);
+    bincode::serialize_into(&mut bytes, value)?;
+
+    Ok(bytes)
+}
+
+pub fn decode<T: BinaryPayload>(bytes: &[u8]) -> Result<T, BinaryError> {
+    if bytes.len() < HEADER_LENGTH {
+        return Err(BinaryError::Truncated);
+    }
+
+    let (header, payload) = bytes.split_at(HEADER_LENGTH);
+
+    match header {
+        [m0, m1, m2, m3, v0, v1, kind] => {
+            if [*m0, *m1, *m2, *m3] != MAGIC {
+                return Err(BinaryError::BadMagic);
+            }
+
+            let version: u16 = u16::from_le_bytes([*v0, *v1]);
+
+            if version != VERSION {
+                return Err(BinaryError::UnsupportedVersion(version));
+            }
+
+            if *kind != T::KIND {
+                return Err(BinaryError::WrongKind {
+                    expected: T::KIND,
+                    found: *kind
+                });
+            }
+        },
+        _ => return Err(BinaryError::Truncated)
+    }
+
+    Ok(bincode::deserialize(payload)?)
+}
+
+#[cfg(all(test, not(feature = "decimal-quantity")))]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+    use crate::event::{EventKind, Trade};
+    use crate::order::OrderType;
+
+    fn events() -> Vec<Event> {
+        vec![
+            Event::new(1, Utc::now(), EventKind::Post {
+                order: 1,
+                order_type: OrderType::Ask,
+                price: 12.5,
+                quantity: 10,
+                hidden: false
+            }),
+            Event::new(2, Utc::now(), EventKind::Match(
+                Trade::new(Utc::now(), 12.5, 10, 2, 1, OrderType::Bid))),
+        ]
+    }
+
+    #[test]
+    fn test_events_round_trip() -> Result<(), BinaryError> {
+        let expected_events: Vec<Event> = events();
+
+        let bytes: Vec<u8> = encode(&expected_events)?;
+        let actual_events: Vec<Event> = decode(&bytes)?;
+
+        assert_eq!(actual_events, expected_events);
+        assert!(bytes.len() < serde_json::to_vec(&expected_events)
+                    .map(|json| json.len())
+                    .unwrap_or(0));
+        Ok(())
+    }
+
+    #[test]
+    fn test_levels_round_trip() -> Result<(), BinaryError> {
+        let expected_levels: Levels = Levels::new(vec![(12.0, 15)],
+                                                  vec![(13.0, 7), (14.0, 8)]);
+
+        let actual_levels: Levels = decode(&encode(&expected_levels)?)?;
+
+        assert_eq!(actual_levels, expected_levels);
+        Ok(())
+    }
+
+    #[test]
+    fn test_header_checked() -> Result<(), BinaryError> {
+        let mut bytes: Vec<u8> = encode(&Levels::default())?;
+
+        assert!(matches!(decode::<Event>(&bytes),
+                         Err(BinaryError::WrongKind { expected: 1,
+                                                      found: 3 })));
+        assert!(matches!(decode::<Levels>(&bytes[..4]),
+                         Err(BinaryError::Truncated)));
+
+        bytes[4] = 99;
+        assert!(matches!(decode::<Levels>(&bytes),
+                         Err(BinaryError::UnsupportedVersion(99))));
+
+        bytes[0] = b'X';
+        assert!(matches!(decode::<Levels>(&bytes),
+                         Err(BinaryError::BadMagic)));
+        Ok(())
+    }
+}
@@ -1,45 +1,423 @@
-use std::collections::{HashMap, BTreeMap, VecDeque};
-use std::iter::FromIterator;
-extern crate ordered_float;
+/* Nothing in here may panic on behalf of a caller: failures surface as
+ * `BookError`s, and anything that cannot fail is noted where it happens. */
+#![deny(clippy::unwrap_used, clippy::expect_used, clippy::panic,
+        clippy::indexing_slicing, clippy::unimplemented)]
 
-use ordered_float::OrderedFloat;
+use std::collections::{HashMap, HashSet, BTreeMap, BTreeSet, VecDeque};
+use std::collections::hash_map;
+use std::fmt;
+#[cfg(feature = "metrics")]
+use std::time::Instant;
+use std::time::Duration;
+use std::sync::mpsc::Receiver;
+
+use chrono::{DateTime, Utc};
+use serde::{Serialize, Deserialize};
+use crate::account::{Account, AccountError, AccountId};
+use crate::analytics::{BookStats, Mark, MarketStats};
+use crate::batch::{BatchError, BatchResult, BookOp, BookOpOutcome};
+use crate::clock::{Clock, SystemClock};
+use crate::checksum::ChecksumFormat;
+use crate::feed::normalize::MarketTrade;
+use crate::half_book::{HalfBook, LevelsIter};
+use crate::builder::{self, BookBuilder, BookConfig,
+                     CapacityLimit, CapacityPolicy, CircuitBreaker,
+                     SweepLimit, SweepRemainder};
+use crate::sink::{EventSink, MemorySink, SinkError};
+use crate::event::*;
 use crate::order::*;
+use crate::matching::*;
+use crate::integrity::{IntegrityMode, Violation};
+use crate::iter::{LevelIter, OrderRef, SideIter};
+use crate::levels::*;
+use crate::margin::MarginModel;
+use crate::metadata::Metadata;
+use crate::id::OrderIdGenerator;
+use crate::peg::Peg;
+use crate::pool::{self, LevelPool, DEFAULT_POOL_SIZE};
+#[cfg(feature = "metrics")]
+use crate::metrics::BookMetrics;
+use crate::price::{F64Price, PriceType, Ticks};
+#[cfg(feature = "decimal")]
+use rust_decimal::Decimal;
+use crate::render::{self, RenderOptions};
+#[cfg(feature = "viz")]
+use crate::viz::{self, DepthChartOptions, VizError};
+use crate::session::SessionState;
+use crate::snapshot::BookSnapshot;
+use crate::subscription::{EventFilter, Subscribers};
+use crate::quantity::{self, Quantity, ZERO};
+use crate::quote::{QuoteBuilder, QuoteResult};
 
-#[derive(Debug)]
+#[derive(Debug, thiserror::Error)]
 #[allow(dead_code)]
 pub enum BookError {
+    #[error("no such resting order")]
     OrderNotFound,
+    #[error("no such trade")]
+    TradeNotFound,
+    #[error("that side of the book is empty")]
     SideEmpty,
+    #[error("nothing has traded yet")]
     NoTrades,
+    #[error("account error: {0}")]
+    Account(#[from] AccountError),
+    #[error("matching policy returned an invalid allocation")]
+    InvalidAllocation,
+    #[error("price not accepted")]
+    InvalidPrice,
+    #[error("quantity not accepted")]
+    InvalidQuantity,
+    #[error("order ID has already been used")]
+    DuplicateOrderId,
+    #[error("client order ID {0:?} is in use by an open order")]
+    DuplicateClientId(String),
+    #[error("trading is halted")]
+    MarketHalted,
+    #[error("the market is closed")]
+    MarketClosed,
+    #[error("the instrument has expired")]
+    Expired,
+    #[error("cannot go from {0:?} to {1:?}")]
+    InvalidStateTransition(SessionState, SessionState),
+    #[error("snapshot is invalid")]
+    InvalidSnapshot,
+    #[error("events have already been evicted")]
+    EventsEvicted,
+    #[error("nothing to peg to")]
+    NoPegReference,
+    #[error("event sink error: {0}")]
+    Sink(#[from] SinkError),
+    /* a bid needed more buying power than its owner had left */
+    #[error("needs {required} of buying power but only {available} is left")]
+    InsufficientBuyingPower {
+        required: f64,
+        available: f64
+    },
+}
+
+/* everything a batch might change, as it was before the batch started; the
+ * trades and pending events are only ever appended to, so their lengths
+ * will do */
+struct Checkpoint<P: PriceType> {
+    orders: HashMap<OrderId, Order>,
+    seen: HashSet<OrderId>,
+    bids: HalfBook<P>,
+    asks: HalfBook<P>,
+    ltp: f64,
+    has_traded: bool,
+    state: SessionState,
+    sequencer: Sequencer,
+    trades: usize,
+    reserved: HashMap<AccountId, f64>,
+    accounts: HashMap<AccountId, Account>,
+    statuses: HashMap<OrderId, OrderStatus>,
+    labels: HashMap<OrderId, OrderLabel>,
+    client_ids: HashMap<String, OrderId>,
+    amendments: HashMap<OrderId, Vec<Amendment>>,
+    filled: HashMap<OrderId, Order>,
+    pegs: HashMap<OrderId, Peg>,
+    peg_top: TopOfBook,
+    pending: usize
 }
 
+/* stands in for a level that doesn't exist */
+static EMPTY_LEVEL: VecDeque<OrderId> = VecDeque::new();
+
 pub type BookId = u128;
-pub type PriceKey = OrderedFloat<f64>;
+/* the default `PriceType` */
+pub type PriceKey = F64Price;
+
+/* a book whose matching policy is chosen at runtime */
+pub type DynBook = Book<Box<dyn MatchingPolicy>>;
+
+/* books keyed on whole ticks, e.g. `TickBook<100>` for prices in cents */
+pub type TickBook<const PER_UNIT: u64> =
+    Book<PriceTime, MemorySink, SystemClock, Ticks<PER_UNIT>>;
+
+/* books keyed on exact decimal prices */
+#[cfg(feature = "decimal")]
+pub type DecimalBook = Book<PriceTime, MemorySink, SystemClock, Decimal>;
+
+/* what cancelling an order took off the book: the order as it was when
+ * cancelled, how much of it had already executed, and how much of it was
+ * left to cancel (which is all that came off the depth) */
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CancelResult {
+    pub order: Order,
+    pub filled_quantity: Quantity,
+    pub remaining_cancelled: Quantity
+}
+
+/* what changed a resting order's terms */
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum AmendmentKind {
+    Modify,
+    Reduce,
+    /* a pegged order following its reference */
+    Reprice
+}
+
+/* one change to a resting order's price or quantity, and the event that
+ * recorded it; see `Book::order_history` */
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Amendment {
+    pub seq: u64,
+    pub timestamp: DateTime<Utc>,
+    pub kind: AmendmentKind,
+    pub previous_price: f64,
+    pub previous_quantity: Quantity,
+    pub price: f64,
+    pub quantity: Quantity
+}
 
+/* What a book is holding on to, as reported by `Book::memory_usage`.
+ * `bytes` is approximate: it counts the book's own tables and queues by
+ * their capacity, but not what the orders in them point to (their owners'
+ * names and holdings, say), nor the sink's events. */
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MemoryUsage {
+    /* resting orders, and how many the book has room for */
+    pub orders: usize,
+    pub order_capacity: usize,
+    pub levels: usize,
+    /* order IDs the level queues have room for, between them */
+    pub queue_capacity: usize,
+    /* emptied level queues kept for reuse */
+    pub pooled: usize,
+    /* orders whose status the book remembers */
+    pub statuses: usize,
+    pub trades: usize,
+    pub bytes: usize
+}
+
+/* Events are written through to `S` as each operation completes. The
+ * default keeps them in memory, so they can be read back with
+ * `get_events`. Levels are keyed on `P`, see `PriceType`. */
 #[derive(Debug)]
-pub struct Book<'a> {
+pub struct Book<M: MatchingPolicy = PriceTime, S: EventSink = MemorySink,
+                C: Clock = SystemClock, P: PriceType = F64Price> {
     id: BookId,
     name: String,
     ticker: String,
     orders: HashMap<OrderId, Order>,
-    bids: BTreeMap<PriceKey, VecDeque<&'a mut Order>>,
-    asks: BTreeMap<PriceKey, VecDeque<&'a mut Order>>,
+    /* every ID ever accepted, resting or not, so none is reused */
+    seen: HashSet<OrderId>,
+    bids: HalfBook<P>,
+    asks: HalfBook<P>,
+    /* emptied level queues, for new levels to reuse */
+    pool: LevelPool,
     ltp: f64,
-    has_traded: bool
+    has_traded: bool,
+    /* the last traded price from before the book's own trades, as
+     * restored from a snapshot or seeded from history, if there was one */
+    prior_ltp: Option<f64>,
+    state: SessionState,
+    policy: M,
+    config: BookConfig,
+    sequencer: Sequencer,
+    /* as of the last event published */
+    top: TopOfBook,
+    sink: S,
+    clock: C,
+    /* events raised by the operation in progress, not yet written out */
+    pending: Vec<Event>,
+    trades: Vec<Trade>,
+    /* buying power held by each account's resting bids */
+    reserved: HashMap<AccountId, f64>,
+    /* The book's ledger of every account that has had an order on it, as
+     * its fills have left them. An account starts out as the owner of its
+     * first order unless given with `add_account`; later orders' copies of
+     * their owner are not consulted. */
+    accounts: HashMap<AccountId, Account>,
+    /* found by integrity checks, see `BookConfig::integrity` */
+    violations: Vec<Violation>,
+    /* of every order the book has accepted or refused */
+    statuses: HashMap<OrderId, OrderStatus>,
+    /* of every order the book has accepted or refused that had a client
+     * ID or tags */
+    labels: HashMap<OrderId, OrderLabel>,
+    /* the last order accepted with each client ID */
+    client_ids: HashMap<String, OrderId>,
+    /* every change to each order's terms, oldest first */
+    amendments: HashMap<OrderId, Vec<Amendment>>,
+    /* resting orders that filled completely, as they were when they did,
+     * for putting back if one of their trades is busted */
+    filled: HashMap<OrderId, Order>,
+    /* while a batch is being applied, nothing is published */
+    batching: bool,
+    /* as of the last event published */
+    stats: MarketStats,
+    /* attributed every event published while set */
+    actor: Option<Actor>,
+    /* sent the events they filter for as they are published */
+    subscribers: Subscribers,
+    /* the terms of each pegged order, resting or filled while resting */
+    pegs: HashMap<OrderId, Peg>,
+    /* the unpegged best bid and ask they were last priced from */
+    peg_top: TopOfBook,
+    #[cfg(feature = "metrics")]
+    metrics: BookMetrics
+}
+
+impl Book {
+    pub fn new(id: BookId, name: String, ticker: String) -> Book {
+        Book::with_policy(id, name, ticker, PriceTime)
+    }
+
+    pub fn builder(id: BookId, ticker: String) -> BookBuilder {
+        BookBuilder::new(id, ticker)
+    }
+
+    pub fn from_snapshot(snapshot: BookSnapshot) -> Result<Book, BookError> {
+        Book::restore(snapshot, PriceTime, MemorySink::new(), SystemClock)
+    }
+
+    /* Seeds a book for `metadata`'s instrument from aggregated depth, such
+     * as a venue's snapshot, so that orders and deltas (see `LevelMirror`)
+     * can be applied on top. Each level is stood in for by
+     * `orders_per_level` orders from `owner`, sharing its quantity as
+     * evenly as whole lots allow, with IDs from `ids`. As when restoring a
+     * snapshot, nothing is checked against `owner`'s account and no events
+     * are recorded. Depth that is crossed, off the tick grid or not in
+     * whole lots is refused. */
+    pub fn from_levels<G: OrderIdGenerator>(id: BookId, metadata: &Metadata,
+                                            levels: &Levels, owner: &Account,
+                                            orders_per_level: usize,
+                                            ids: &mut G) ->
+        crate::Result<Book> {
+        let mut book: Book = metadata.builder(id).build()?;
+        let parts: Quantity = Quantity::from(orders_per_level.max(1) as u64);
+
+        if let (Some((bid, _)), Some((ask, _))) = (levels.get_bids().first(),
+                                                   levels.get_asks().first()) {
+            if bid >= ask {
+                return Err(BookError::InvalidSnapshot.into());
+            }
+        }
+
+        for (side, levels) in [(OrderType::Bid, levels.get_bids()),
+                               (OrderType::Ask, levels.get_asks())] {
+            for (price, quantity) in levels {
+                book.check_price(*price)?;
+                book.check_quantity(*quantity)?;
+
+                let share: Quantity = book.config.round_to_lot(
+                    quantity::share(Quantity::from(1u64), parts, *quantity));
+                let first: Quantity = quantity::checked_sub(
+                    *quantity,
+                    quantity::total((1..orders_per_level).map(|_| share)))
+                    .ok_or(BookError::InvalidQuantity)?;
+
+                for part in std::iter::once(first)
+                    .chain((1..orders_per_level).map(|_| share))
+                    .filter(|part| *part > ZERO) {
+                    let order: Order = Order::new(ids.next_id(), owner.clone(),
+                                                  book.ticker.clone(), side,
+                                                  *price, part);
+
+                    if !book.seen.insert(order.get_id()) {
+                        return Err(BookError::DuplicateOrderId.into());
+                    }
+
+                    book.statuses.insert(order.get_id(), OrderStatus::New);
+                    book.rest(order)?;
+                }
+            }
+        }
+
+        book.top = book.top_of_book();
+        book.peg_top = book.peg_reference();
+        Ok(book)
+    }
+}
+
+impl<M: MatchingPolicy> Book<M> {
+    pub fn with_policy(id: BookId, name: String, ticker: String,
+                       policy: M) -> Book<M> {
+        Book::with_sink(id, name, ticker, policy, MemorySink::new())
+    }
+}
+
+impl<M: MatchingPolicy, C: Clock, P: PriceType> Book<M, MemorySink, C, P> {
+    pub fn get_events(&self) -> &[Event] {
+        self.sink.get_events()
+    }
+
+    /* The events after `seq`, for bringing a copy of the book taken then
+     * (e.g. a `BookReplica`) up to date. Fails if the sink has already
+     * dropped any of them, in which case a copy can start again from
+     * `get_compacted`, if the book compacts, or a fresh snapshot. */
+    pub fn events_since(&self, seq: u64) -> Result<&[Event], BookError> {
+        self.sink.events_since(seq).ok_or(BookError::EventsEvicted)
+    }
+
+    /* as `events_since`, but copied */
+    pub fn delta_since(&self, seq: u64) -> Result<Vec<Event>, BookError> {
+        self.events_since(seq).map(<[Event]>::to_vec)
+    }
+
+    /* the snapshot the book last compacted its events into; see
+     * `BookConfig::compact_every` */
+    pub fn get_compacted(&self) -> Option<&BookSnapshot> {
+        self.sink.get_compacted()
+    }
+}
+
+impl<M: MatchingPolicy, S: EventSink> Book<M, S> {
+    pub fn with_sink(id: BookId, name: String, ticker: String, policy: M,
+                     sink: S) -> Book<M, S> {
+        Book::with_config(id, name, ticker, policy, sink, SystemClock,
+                          BookConfig::default(), SessionState::default())
+    }
 }
 
 #[allow(dead_code, unused_variables)]
-impl Book<'_> {
-    pub fn new(id: u128, name: String, ticker: String) -> Book<'static> {
+impl<M: MatchingPolicy, S: EventSink, C: Clock, P: PriceType>
+    Book<M, S, C, P> {
+    /* unvalidated; `BookBuilder` is the public way to configure a book */
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn with_config(id: BookId, name: String, ticker: String,
+                              policy: M, sink: S, clock: C,
+                              config: BookConfig, state: SessionState) ->
+        Book<M, S, C, P> {
         Book {
-            id: id,
-            name: name,
-            ticker: ticker,
+            id,
+            name,
+            ticker,
             orders: HashMap::new(),
-            bids: BTreeMap::new(),
-            asks: BTreeMap::new(),
+            seen: HashSet::new(),
+            bids: HalfBook::new(OrderType::Bid),
+            asks: HalfBook::new(OrderType::Ask),
             ltp: 0.00,
-            has_traded: false
+            has_traded: false,
+            prior_ltp: None,
+            state,
+            policy,
+            config,
+            sequencer: Sequencer::default(),
+            top: TopOfBook::default(),
+            sink,
+            clock,
+            pending: vec![],
+            trades: vec![],
+            reserved: HashMap::new(),
+            accounts: HashMap::new(),
+            violations: vec![],
+            statuses: HashMap::new(),
+            labels: HashMap::new(),
+            client_ids: HashMap::new(),
+            amendments: HashMap::new(),
+            filled: HashMap::new(),
+            pegs: HashMap::new(),
+            peg_top: TopOfBook::default(),
+            pool: LevelPool::new(DEFAULT_POOL_SIZE),
+            stats: MarketStats::default(),
+            actor: None,
+            subscribers: Subscribers::default(),
+            batching: false,
+            #[cfg(feature = "metrics")]
+            metrics: BookMetrics::new()
         }
     }
 
@@ -55,6 +433,18 @@ impl Book<'_> {
         self.ticker.clone()
     }
 
+    pub fn get_policy(&self) -> &M {
+        &self.policy
+    }
+
+    pub fn get_clock(&self) -> &C {
+        &self.clock
+    }
+
+    pub fn get_config(&self) -> &BookConfig {
+        &self.config
+    }
+
     pub fn get_order(&self, id: OrderId) -> Result<&Order, BookError> {
         match self.orders.get(&id) {
             Some(order) => Ok(order),
@@ -78,311 +468,4992 @@ impl Book<'_> {
         }
     }
 
-    pub fn submit(&mut self, mut order: Order) -> Result<(), BookError> {
-        let order_id: OrderId = order.get_id();
-        let order_type: OrderType = order.get_order_type();
-        let order_price: f64 = order.get_price();
-        let order_quantity: u128 = order.get_quantity();
-        let order_ticker: String = order.get_ticker();
+    pub fn get_state(&self) -> SessionState {
+        self.state
+    }
 
-        let &mut Book {
-            ref mut id,
-            ref mut name,
-            ref mut ticker,
-            ref mut orders,
-            ref mut bids,
-            ref mut asks,
-            .. } = self;
-       
-        match order_type {
-            OrderType::Bid => {
-                let matched: bool = Book::match_order(orders, asks, &mut order)?;
-
-                if !matched {
-                    orders.insert(order_id, order);
-                    
-                    if !bids.contains_key(&OrderedFloat::from(order_price)) {
-                        bids.insert(OrderedFloat::from(order_price),
-                        VecDeque::from_iter(vec![]));
-                    }   
-                }
-            },
-            OrderType::Ask => { 
-                let matched: bool = Book::match_order(orders, bids, &mut order)?;
-
-                if !matched {
-                    orders.insert(order_id, order);
-                    
-                    if !asks.contains_key(&OrderedFloat::from(order_price)) {
-                        asks.insert(OrderedFloat::from(order_price),
-                        VecDeque::from_iter(vec![]));
-                    }
-                }
-            }
+    pub fn is_halted(&self) -> bool {
+        self.state == SessionState::Halted
+    }
+
+    /* Moves the book to another session state, if that transition is
+     * allowed. Entering continuous trading from an auction uncrosses the
+     * book first; see `uncross`. */
+    pub fn set_state(&mut self, state: SessionState) -> Result<(), BookError> {
+        if state == self.state {
+            return Ok(());
         }
 
-        Ok(())
-    }
+        if !self.state.can_transition(state) {
+            return Err(BookError::InvalidStateTransition(self.state, state));
+        }
+
+        let event: Event = self.sequencer.stamp(EventKind::StateChange {
+            previous: self.state,
+            current: state
+        }, self.clock.now());
+        self.pending.push(event);
+        self.state = state;
+
+        let uncrossed: Result<(), BookError> = if state.matches() {
+            #[cfg(feature = "tracing")]
+            let _span = tracing::debug_span!("uncross", book = %self.id,
+                                             state = ?state).entered();
+            #[cfg(any(feature = "metrics", feature = "tracing"))]
+            let trades: usize = self.trades.len();
+            let uncrossed: Result<(), BookError> = self.uncross();
+
+            #[cfg(feature = "metrics")]
+            self.metrics.record_trades(self.trades.len() - trades);
+            #[cfg(feature = "tracing")]
+            self.trace_fills(trades);
+            uncrossed
+        } else {
+            Ok(())
+        };
+        let published: Result<(), BookError> = self.publish();
 
-    pub fn cancel(&mut self, id: OrderId) -> Result<(), BookError> {
-        unimplemented!();
+        uncrossed?;
+        published
     }
 
-    fn execute_order(order: &mut Order) -> Result<(), BookError> {
-        Book::partially_execute_order(order, order.get_quantity())
+    /* whether the instrument's expiry, if it has one, has come */
+    pub fn is_expired(&self) -> bool {
+        self.config.expiry.is_some_and(|expiry| expiry <= self.clock.now())
     }
 
-    fn partially_execute_order(order: &mut Order, quantity: u128) ->
-        Result<(), BookError> {
-        let order_type: OrderType = order.get_order_type();
-        let ticker: String = order.get_ticker();
-        let price: f64 = order.get_price();
+    /* Closes the book out for good, as at an instrument's expiry: every
+     * resting order is cancelled as `Reason::Expired`, a `Settlement` event
+     * gives the price to settle open positions at (see `Position::settle`)
+     * if there is one, and the book is `Closed`. Returns what was
+     * cancelled. Orders are refused with `BookError::Expired` from the
+     * configured expiry on, whether or not this has been called yet. */
+    pub fn expire(&mut self, settlement: Option<f64>) ->
+        Result<Vec<CancelResult>, BookError> {
+        let mut ids: Vec<(DateTime<Utc>, OrderId)> = self.iter_all()
+            .map(|order| (order.get_created(), order.get_id()))
+            .collect();
+        ids.sort();
 
-        match order_type {
-            OrderType::Bid => {
-                order.get_owner_mut().take_balance(price * quantity as f64);
-                order.get_owner_mut().add_holding(ticker, quantity).unwrap();
-            },
-            OrderType::Ask => {
-                order.get_owner_mut().add_balance(price * quantity as f64);
-                order.get_owner_mut().take_holding(ticker, quantity).unwrap();
-            }
+        let cancelled: Vec<CancelResult> = ids.into_iter()
+            .map(|(_, id)| self.cancel_with_reason(id, Reason::Expired))
+            .collect::<Result<_, _>>()?;
+
+        if let Some(price) = settlement {
+            self.record(EventKind::Settlement { price })?;
         }
 
-        Ok(())
+        self.set_state(SessionState::Closed)?;
+        Ok(cancelled)
     }
 
-    fn match_order(orders: &mut HashMap<OrderId, Order>,
-                   side: &mut BTreeMap<OrderedFloat<f64>, VecDeque<&mut Order>>,
-                   mut order: &mut Order) -> Result<bool, BookError> {
-        let order_price: f64 = order.get_price();
-        let order_quantity: u128 = order.get_quantity();
-        let mut matched: bool = false;
-
-        for (level_price, level_orders) in side.iter_mut() {
-            if level_price <= &OrderedFloat::from(order_price) {
-                for counter_order in level_orders.iter_mut() {
-                    let counter_price: f64 = counter_order.get_price();
-                    let counter_quantity: u128 = counter_order.get_quantity();
-
-                    if counter_quantity < order_quantity {
-                        Book::execute_order(counter_order)?;
-                        orders.remove(&counter_order.get_id());
-
-                        Book::partially_execute_order(&mut order, counter_quantity)?;
-                    } else if counter_quantity == order_quantity {
-                        Book::execute_order(counter_order)?;
-                        orders.remove(&counter_order.get_id());
-
-                        Book::execute_order(&mut order)?;
-                        matched = true;
-                        break;
-                    } else if counter_quantity > order_quantity {
-                        Book::partially_execute_order(counter_order, order_quantity)?;
-
-                        Book::execute_order(&mut order)?;
-                        matched = true;
-                        break;
-                    }
-                }
-
-                if matched {
-                    break;
-                }
-            }
+    /* lifts a circuit breaker halt */
+    pub fn resume(&mut self) -> Result<(), BookError> {
+        if self.is_halted() {
+            self.set_state(SessionState::Continuous)
+        } else {
+            Ok(())
         }
+    }
 
-        Ok(matched)
+    /* the centre of the circuit breaker's band, if one applies */
+    fn band_reference(&self) -> Option<f64> {
+        self.config.circuit_breaker?.centre(self.reference_price())
     }
 
-}
+    /* The last traded price or, before there has been a trade, the
+     * configured reference price (e.g. the previous close), if there is
+     * one. Price bands around the last trade are centred on it, auctions
+     * break ties towards it, and pegged orders follow it while their own
+     * reference doesn't exist. */
+    pub fn reference_price(&self) -> Option<f64> {
+        if self.has_traded {
+            Some(self.ltp)
+        } else {
+            self.config.reference_price
+        }
+    }
 
+    /* takes effect from the next order; has no effect once the book has
+     * traded */
+    pub fn set_reference_price(&mut self, price: Option<f64>) {
+        self.config.reference_price = price;
+    }
 
-impl PartialEq for Book<'_> {
-    fn eq(&self, other: &Self) -> bool {
-        self.id == other.id &&
-            self.name == other.name &&
-            self.ticker == other.ticker &&
-            self.ltp == other.ltp &&
-            self.has_traded == other.has_traded &&
-            self.bids.iter().len() == other.bids.iter().len() &&
-            self.asks.iter().len() == other.asks.iter().len() &&
-            Vec::new().extend(self.bids.iter().map(|x| x)) == 
-                Vec::new().extend(other.bids.iter().map(|x| x)) &&
-            Vec::new().extend(self.asks.iter().map(|x| x)) == 
-                Vec::new().extend(other.asks.iter().map(|x| x))
+    /* The one price to mark positions in this book at, picked by the
+     * book's `MarkPolicy` from the mid, the last trade and the reference
+     * price, and how current it is. `None` if there is none of them. */
+    pub fn mark_price(&self) -> Option<Mark> {
+        let mid: Option<(f64, Option<DateTime<Utc>>)> = self.top_of_book()
+            .get_mid()
+            .map(|mid| (mid, self.stats.get_top_updated()));
+        let last: Option<(f64, Option<DateTime<Utc>>)> = self.get_ltp().ok()
+            .map(|ltp| (ltp, self.trades.last().map(Trade::get_timestamp)
+                        .or_else(|| self.stats.get_last_traded())));
+
+        self.config.mark.mark(mid, last, self.config.reference_price,
+                              self.clock.now())
     }
-}
 
+    /* The price the book would uncross at if the auction ended now: the
+     * one executing the most crossed interest, with ties going to the
+     * smallest imbalance, then nearest the reference price. `None` unless
+     * the book is crossed, which outside of auctions it never is. */
+    pub fn indicative_price(&self) -> Option<f64> {
+        self.clearing_price().map(|(price, _)| price)
+    }
 
-#[cfg(test)]
-mod tests { 
-    use super::*;
-    use std::collections::HashMap;
-    use crate::account::*;
+    /* how much would execute at `indicative_price` */
+    pub fn indicative_volume(&self) -> Quantity {
+        self.clearing_price().map_or(ZERO, |(_, volume)| volume)
+    }
 
-    #[test]
-    fn test_new() -> Result<(), BookError> {
-        let id: u128 = 1;
-        let name: String = "Book".to_string();
-        let ticker: String = "BOOK".to_string();
+    /* Attributes every event published from now on to `actor` (unless it
+     * already has one), until set again; `None` stops attributing them. An
+     * exchange layer would set this around each client's instructions. */
+    pub fn set_actor(&mut self, actor: Option<Actor>) {
+        self.actor = actor;
+    }
 
-        let actual_book: Book = Book::new(id, name.clone(), ticker.clone());
-        let expected_book: Book = Book{
-            id: id,
-            name: name.clone(),
-            ticker: ticker.clone(),
-            orders: HashMap::new(),
-            bids: BTreeMap::new(),
-            asks: BTreeMap::new(),
-            ltp: 0.00,
-            has_traded: false
-        };
+    pub fn get_actor(&self) -> Option<&Actor> {
+        self.actor.as_ref()
+    }
 
-        assert_eq!(actual_book, expected_book);
-        Ok(())
+    pub fn get_sink(&self) -> &S {
+        &self.sink
     }
 
-    #[test]
-    fn test_submit_single_bid() -> Result<(), BookError> {
-        /* build account */
-        let account_id: AccountId = 1;
-        let account_name: String = "Account".to_string();
-        let account_balance: f64 = 12000.00;
-        let account_holdings: HashMap<String, u128> = HashMap::new();
-        let actual_account: Account = Account::new(account_id,
-                                                   account_name,
-                                                   account_balance,
-                                                   account_holdings);
+    pub fn get_sink_mut(&mut self) -> &mut S {
+        &mut self.sink
+    }
 
-        /* build order */
-        let order_id: OrderId = 1;
-        let order_owner: Account = actual_account;
-        let order_ticker: String = "BOOK".to_string();
-        let order_type: OrderType = OrderType::Bid;
-        let order_price: f64 = 12.00;
-        let order_quantity: u128 = 33;
-        let actual_order: Order = Order::new(order_id,
-                                                 order_owner,
-                                                 order_ticker,
-                                                 order_type,
-                                                 order_price,
-                                                 order_quantity);
+    /* pushes anything the sink is holding on to downstream */
+    pub fn flush_events(&mut self) -> Result<(), BookError> {
+        Ok(self.sink.flush()?)
+    }
 
-        /* build book */
-        let book_id: BookId = 1;
-        let book_name: String = "Book".to_string();
-        let book_ticker: String = "BOOK".to_string();
-        let mut actual_book: Book = Book::new(book_id,
-                                              book_name.clone(),
-                                              book_ticker.clone());
+    /* the sequence number of the most recent event, or zero if there have
+     * been none */
+    pub fn last_seq(&self) -> u64 {
+        self.sequencer.last_seq()
+    }
 
-        /* we need to build this field of the expected book due to movement
-         * of values */
-        let mut expected_orders: HashMap<OrderId, Order> = HashMap::new();
-        expected_orders.insert(order_id, actual_order.clone());
- 
-        /* submit order to book */
-        actual_book.submit(actual_order)?;
+    #[cfg(feature = "metrics")]
+    pub fn metrics(&self) -> &BookMetrics {
+        &self.metrics
+    }
 
-        /* build expected fields */
-        let mut cloned_expected_orders: HashMap<OrderId, Order> =
-            expected_orders.clone();
-        let mut expected_bids: BTreeMap<OrderedFloat<f64>,
-        VecDeque<&mut Order>> =
-            BTreeMap::new();
-        expected_bids.insert(OrderedFloat::from(order_price),
-            VecDeque::from_iter(
-                vec![cloned_expected_orders.get_mut(&order_id).unwrap()]));
+    #[cfg(feature = "metrics")]
+    pub fn metrics_mut(&mut self) -> &mut BookMetrics {
+        &mut self.metrics
+    }
 
-        let expected_asks: BTreeMap<OrderedFloat<f64>,
-        VecDeque<&mut Order>> =
-            BTreeMap::new();
+    /* spread, mid and rolling trade statistics */
+    pub fn market_stats(&self) -> &MarketStats {
+        &self.stats
+    }
 
-        let expected_book: Book = Book {
-            id: book_id,
-            name: book_name.clone(),
-            ticker: book_ticker.clone(),
-            orders: expected_orders,
-            bids: expected_bids,
-            asks: expected_asks,
-            ltp: 0.00,
-            has_traded: false
+    /* the book at a glance, e.g. for a dashboard; see `BookStats` */
+    pub fn stats(&self) -> BookStats {
+        let top: TopOfBook = self.top_of_book();
+        let levels: Levels = self.levels();
+        let depth = |levels: &[Level]| -> Quantity {
+            levels.iter().map(|(_, quantity)| *quantity).sum()
         };
+        let orders = |side: OrderType| self.orders.values()
+            .filter(|order| order.get_order_type() == side &&
+                            !order.is_hidden())
+            .count();
 
-        assert_eq!(actual_book, expected_book);
-        Ok(())
+        BookStats {
+            book: self.id,
+            ticker: self.ticker.clone(),
+            seq: self.last_seq(),
+            timestamp: self.clock.now(),
+            best_bid: top.get_bid(),
+            best_ask: top.get_ask(),
+            spread: top.get_spread(),
+            mid: top.get_mid(),
+            last_price: self.get_ltp().ok(),
+            bid_depth: depth(levels.get_bids()),
+            ask_depth: depth(levels.get_asks()),
+            bid_levels: levels.get_bids().len(),
+            ask_levels: levels.get_asks().len(),
+            bid_orders: orders(OrderType::Bid),
+            ask_orders: orders(OrderType::Ask),
+            trades: self.trades.len(),
+            volume: self.trades.iter().map(Trade::get_quantity).sum()
+        }
     }
 
-    #[test]
-    fn test_submit_single_ask() -> Result<(), BookError> {
-        /* build account */
-        let account_id: AccountId = 1;
-        let account_name: String = "Account".to_string();
-        let account_balance: f64 = 12000.00;
-        let account_holdings: HashMap<String, u128> = HashMap::new();
-        let actual_account: Account = Account::new(account_id,
-                                                   account_name,
-                                                   account_balance,
-                                                   account_holdings);
+    /* Warms the book up from trades made before it started (e.g. earlier in
+     * the session, before a restart), oldest first: the last becomes the
+     * last traded price, and all of them go into the rolling statistics
+     * and candles. Resting orders, the book's own trades and its events
+     * are untouched. Once the book has traded itself, its own trades are
+     * the more recent, and the history is ignored. */
+    pub fn seed_history(&mut self, trades: &[Trade]) {
+        if self.has_traded {
+            return;
+        }
 
-        /* build order */
-        let order_id: OrderId = 1;
-        let order_owner: Account = actual_account;
-        let order_ticker: String = "BOOK".to_string();
-        let order_type: OrderType = OrderType::Ask;
-        let order_price: f64 = 12.00;
-        let order_quantity: u128 = 33;
-        let actual_order: Order = Order::new(order_id,
-                                                 order_owner,
-                                                 order_ticker,
-                                                 order_type,
-                                                 order_price,
-                                                 order_quantity);
+        for trade in trades {
+            self.stats.observe_trade(&MarketTrade::new(
+                trade.get_timestamp(), trade.get_price(),
+                trade.get_quantity(), Some(trade.get_aggressor_side())));
+        }
 
-        /* build book */
-        let book_id: BookId = 1;
-        let book_name: String = "Book".to_string();
-        let book_ticker: String = "BOOK".to_string();
-        let mut actual_book: Book = Book::new(book_id,
-                                              book_name.clone(),
-                                              book_ticker.clone());
+        if let Some(last) = trades.last() {
+            self.ltp = last.get_price();
+            self.has_traded = true;
+            self.prior_ltp = Some(self.ltp);
+        }
+    }
 
-        /* we need to build this field of the expected book due to movement
-         * of values */
-        let mut expected_orders: HashMap<OrderId, Order> = HashMap::new();
-        expected_orders.insert(order_id, actual_order.clone());
- 
-        /* submit order to book */
-        actual_book.submit(actual_order)?;
+    /* starts keeping trade statistics over these windows instead, from
+     * now on */
+    pub fn set_stats_windows(&mut self, windows: &[Duration]) {
+        self.stats.set_windows(windows);
+    }
 
-        /* build expected fields */
-        let expected_bids: BTreeMap<OrderedFloat<f64>,
-        VecDeque<&mut Order>> =
-            BTreeMap::new();
+    pub fn get_trades(&self) -> &[Trade] {
+        &self.trades
+    }
 
-        let mut cloned_expected_orders: HashMap<OrderId, Order> =
-            expected_orders.clone();
-        let mut expected_asks: BTreeMap<OrderedFloat<f64>,
-        VecDeque<&mut Order>> =
-            BTreeMap::new();
-        expected_asks.insert(OrderedFloat::from(order_price),
-            VecDeque::from_iter(
-                vec![cloned_expected_orders.get_mut(&order_id).unwrap()]));
+    pub fn levels(&self) -> Levels {
+        self.depth(usize::MAX)
+    }
 
-        let expected_book: Book = Book {
-            id: book_id,
-            name: book_name.clone(),
-            ticker: book_ticker.clone(),
-            orders: expected_orders,
-            bids: expected_bids,
-            asks: expected_asks,
-            ltp: 0.00,
-            has_traded: false
-        };
+    /* as `levels`, labelled with the book and the last event; see
+     * `DepthSnapshot` */
+    pub fn levels_snapshot(&self) -> DepthSnapshot {
+        self.depth_snapshot(usize::MAX)
+    }
 
-        assert_eq!(actual_book, expected_book);
-        Ok(())
+    /* as `depth`, labelled with the book and the last event */
+    pub fn depth_snapshot(&self, depth: usize) -> DepthSnapshot {
+        DepthSnapshot {
+            book: self.id,
+            ticker: self.ticker.clone(),
+            seq: self.last_seq(),
+            timestamp: self.clock.now(),
+            levels: self.depth(depth)
+        }
+    }
+
+    /* as `levels`, summed into price bins; see `Levels::histogram` */
+    pub fn histogram(&self, bucket_width: f64) -> Levels {
+        self.levels().histogram(bucket_width)
+    }
+
+    /* as `levels`, but only the best `depth` levels on each side */
+    pub fn depth(&self, depth: usize) -> Levels {
+        let bids: Vec<Level> = self.bids.iter()
+            .filter_map(|(price, queue)| self.displayed(price, queue))
+            .take(depth)
+            .collect();
+        let asks: Vec<Level> = self.asks.iter()
+            .filter_map(|(price, queue)| self.displayed(price, queue))
+            .take(depth)
+            .collect();
+
+        Levels::new(bids, asks)
     }
-}
 
+    /* for checking a book mirrored from a venue's feed against the
+     * checksums the venue publishes */
+    pub fn checksum(&self, format: &ChecksumFormat) -> u32 {
+        self.depth(format.depth).checksum(format)
+    }
+
+    /* resting bids, best price first and in time priority at each price */
+    pub fn iter_bids(&self) -> SideIter<'_> {
+        SideIter::new(self.bids.queues(), &self.orders)
+    }
+
+    /* resting asks, best price first and in time priority at each price */
+    pub fn iter_asks(&self) -> SideIter<'_> {
+        SideIter::new(self.asks.queues(), &self.orders)
+    }
+
+    /* the orders resting at `price` on the given side, in time priority */
+    pub fn iter_level(&self, side: OrderType, price: f64) -> LevelIter<'_> {
+        let levels: &HalfBook<P> = self.half(side);
+        let queue: &VecDeque<OrderId> = P::from_price(price)
+            .and_then(|price| levels.get(&price))
+            .unwrap_or(&EMPTY_LEVEL);
+
+        LevelIter::new(queue, &self.orders)
+    }
+
+    /* as `iter_level`, as the ID, quantity and priority of each order,
+     * first in the queue first */
+    pub fn level_orders(&self, side: OrderType, price: f64) ->
+        Vec<OrderRef> {
+        self.iter_level(side, price).map(OrderRef::from).collect()
+    }
+
+    /* the queue `order` would rest in */
+    fn queue_of(&self, order: &Order) -> Option<&VecDeque<OrderId>> {
+        self.half(order.get_order_type())
+            .get(&P::from_price(order.get_price())?)
+    }
+
+    /* the resting orders on `side` */
+    fn half(&self, side: OrderType) -> &HalfBook<P> {
+        match side {
+            OrderType::Bid => &self.bids,
+            OrderType::Ask => &self.asks
+        }
+    }
+
+    /* the side an order of `order_type` would trade against */
+    fn contra(&self, order_type: OrderType) -> &HalfBook<P> {
+        match order_type {
+            OrderType::Bid => &self.asks,
+            OrderType::Ask => &self.bids
+        }
+    }
+
+    /* how many orders are ahead of a resting order at its price */
+    pub fn queue_position(&self, id: OrderId) -> Option<usize> {
+        let order: &Order = self.orders.get(&id)?;
+
+        self.queue_of(order)?.iter().position(|queued| *queued == id)
+    }
+
+    /* how much has to trade at a resting order's price before it can */
+    pub fn orders_ahead(&self, id: OrderId) -> Option<Quantity> {
+        let order: &Order = self.orders.get(&id)?;
+
+        Some(self.queue_of(order)?.iter()
+             .take_while(|queued| **queued != id)
+             .filter_map(|queued| self.orders.get(queued))
+             .map(|ahead| ahead.get_quantity())
+             .sum())
+    }
+
+    /* what everything resting on `side` is worth, hidden orders included,
+     * in whatever the book's prices are quoted in */
+    pub fn notional(&self, side: OrderType) -> f64 {
+        let resting: SideIter<'_> = match side {
+            OrderType::Bid => self.iter_bids(),
+            OrderType::Ask => self.iter_asks()
+        };
+
+        resting.map(|order| order.get_price() *
+                            quantity::to_f64(order.get_quantity()))
+            .sum()
+    }
+
+    /* the most `amount` buys at `price` in this book's whole lots */
+    pub fn quantity_for(&self, price: f64, amount: f64) -> Quantity {
+        self.config.round_to_lot(quantity::from_f64(amount / price.max(0.0)))
+    }
+
+    /* every resting order, in no particular order */
+    pub fn iter_all(&self) -> hash_map::Values<'_, OrderId, Order> {
+        self.orders.values()
+    }
+
+    /* What an order of `order_type` for `quantity`, priced to take any
+     * level, would fill and at what prices right now, without touching the
+     * book. Fills are allocated by the book's policy, so resting orders
+     * that would refuse a fill are skipped just as they would be. `None`
+     * if nothing would fill. */
+    pub fn quote(&self, order_type: OrderType, quantity: Quantity) ->
+        Option<QuoteResult> {
+        let levels: LevelsIter<'_, P> = self.contra(order_type).iter();
+        let mut quote: QuoteBuilder = QuoteBuilder::default();
+        let mut remaining: Quantity = quantity;
+
+        for (price, queue) in levels {
+            if remaining == ZERO {
+                break;
+            }
+
+            let filled: Quantity = self.policy
+                .allocate(queue, &self.orders, remaining)
+                .iter()
+                .map(|(_, fill)| *fill)
+                .sum();
+
+            quote.add(price.to_price(), filled);
+            remaining = quantity::saturating_sub(remaining, filled);
+        }
+
+        quote.build()
+    }
+
+    pub fn top_of_book(&self) -> TopOfBook {
+        TopOfBook::new(self.bids.iter()
+                           .find_map(|(price, queue)| self.displayed(price,
+                                                                     queue)),
+                       self.asks.iter()
+                           .find_map(|(price, queue)| self.displayed(price,
+                                                                     queue)))
+    }
+
+    pub fn render(&self, options: RenderOptions) -> String {
+        render::render(&self.levels(), &options)
+    }
+
+    /* writes an SVG chart of the book's cumulative depth to `path` */
+    #[cfg(feature = "viz")]
+    pub fn render_depth_chart<Q: AsRef<std::path::Path>>(
+        &self, path: Q, options: &DepthChartOptions) -> Result<(), VizError> {
+        viz::render_depth_chart(&self.levels(), path, options)
+    }
+
+    /* everything queued at a level, hidden or not */
+    fn level_depth(&self, queue: &VecDeque<OrderId>) -> Quantity {
+        quantity::total(queue.iter()
+                            .filter_map(|id| self.orders.get(id))
+                            .map(|order| order.get_quantity()))
+    }
+
+    /* a level as the market sees it, if anything there is displayed */
+    fn displayed(&self, price: &P, queue: &VecDeque<OrderId>) ->
+        Option<Level> {
+        let depth: Quantity = quantity::total(queue.iter()
+            .filter_map(|id| self.orders.get(id))
+            .filter(|order| !order.is_hidden())
+            .map(|order| order.get_quantity()));
+
+        if depth > ZERO {
+            Some((price.to_price(), depth))
+        } else {
+            None
+        }
+    }
+
+    /* checks an incoming order's ID is new, and the order itself against
+     * the book's tick size, lot size and price band */
+    fn validate(&self, order: &Order) -> Result<(), BookError> {
+        if self.seen.contains(&order.get_id()) {
+            return Err(BookError::DuplicateOrderId);
+        }
+
+        if let Some(client_id) = order.get_client_id() {
+            if self.find_by_client_id(client_id)
+                .and_then(|id| self.status(id))
+                .is_some_and(|status| status.is_open()) {
+                return Err(BookError::DuplicateClientId(
+                    client_id.to_string()));
+            }
+        }
+
+        self.check_price(order.get_price())?;
+        self.check_quantity(order.get_quantity())?;
+        self.check_buying_power(order, order.get_price(),
+                                order.get_quantity(), 0.0)
+    }
+
+    /* whether `order`'s owner can afford to bid `quantity` at `price`, if
+     * `releasing` of what it already has reserved were given back first */
+    fn check_buying_power(&self, order: &Order, price: f64,
+                          quantity: Quantity, releasing: f64) ->
+        Result<(), BookError> {
+        let margin: MarginModel = match self.config.margin {
+            Some(margin) if order.get_order_type() == OrderType::Bid => margin,
+            _ => return Ok(())
+        };
+        let owner: &Account = Self::account_of(&self.accounts, order);
+        let available: f64 = margin.buying_power(owner.get_balance()) -
+            (self.get_reserved(owner.get_id()) - releasing);
+        let required: f64 = price * quantity::to_f64(quantity);
+
+        if required > available {
+            return Err(BookError::InsufficientBuyingPower {
+                required,
+                available
+            });
+        }
+
+        Ok(())
+    }
+
+    /* buying power a resting order holds for `quantity` of itself */
+    fn reservation(order: &Order, quantity: Quantity) -> f64 {
+        match order.get_order_type() {
+            OrderType::Bid => order.get_price() * quantity::to_f64(quantity),
+            OrderType::Ask => 0.0
+        }
+    }
+
+    fn reserve(reserved: &mut HashMap<AccountId, f64>, order: &Order,
+               quantity: Quantity) {
+        let amount: f64 = Self::reservation(order, quantity);
+
+        if amount != 0.0 {
+            *reserved.entry(order.get_owner_ref().get_id()).or_insert(0.0) +=
+                amount;
+        }
+    }
+
+    fn release(reserved: &mut HashMap<AccountId, f64>, order: &Order,
+               quantity: Quantity) {
+        let account: AccountId = order.get_owner_ref().get_id();
+        let amount: f64 = Self::reservation(order, quantity);
+
+        if let Some(held) = reserved.get_mut(&account) {
+            *held -= amount;
+
+            /* allowing for rounding */
+            if *held <= 1e-9 * amount.abs().max(1.0) {
+                reserved.remove(&account);
+            }
+        }
+    }
+
+    /* as of the last event about the order, if the book has ever seen it;
+     * only resting orders' statuses survive a snapshot */
+    pub fn status(&self, id: OrderId) -> Option<OrderStatus> {
+        self.statuses.get(&id).copied()
+    }
+
+    /* how much of the order is still resting, zero once it is done */
+    pub fn open_quantity(&self, id: OrderId) -> Option<Quantity> {
+        match self.orders.get(&id) {
+            Some(order) => Some(order.get_quantity()),
+            None => self.status(id).map(|_| ZERO)
+        }
+    }
+
+    /* a resting order's status, by what it has filled */
+    fn open_status(order: &Order) -> OrderStatus {
+        if order.get_filled_quantity() > ZERO {
+            OrderStatus::PartiallyFilled
+        } else {
+            OrderStatus::New
+        }
+    }
+
+    /* an order's status after a fill */
+    fn fill_status(order: &Order) -> OrderStatus {
+        if order.get_quantity() == ZERO {
+            OrderStatus::Filled
+        } else {
+            OrderStatus::PartiallyFilled
+        }
+    }
+
+    /* moves `id` on to `status`, noting the change on `event` */
+    fn transition(statuses: &mut HashMap<OrderId, OrderStatus>, event: Event,
+                  id: OrderId, status: OrderStatus) -> Event {
+        match statuses.insert(id, status) {
+            Some(previous) if previous == status => event,
+            previous => event.with_transition(StatusChange {
+                order: id,
+                previous,
+                current: status
+            })
+        }
+    }
+
+    /* Subscribes to the events `filter` picks out, from the next one the
+     * book publishes. The filter is applied here, before anything is sent,
+     * so narrow subscribers cost the book little. A subscriber that drops
+     * its receiver is forgotten the next time the book has an event for
+     * it. */
+    pub fn subscribe(&mut self, filter: EventFilter) -> Receiver<Event> {
+        let receiver: Receiver<Event> = self.subscribers.subscribe(filter);
+
+        /* orders resting already are theirs too */
+        for order in self.orders.values() {
+            self.subscribers.track(order);
+        }
+
+        receiver
+    }
+
+    /* how many subscribers the book has, as of the last event */
+    pub fn subscribers(&self) -> usize {
+        self.subscribers.len()
+    }
+
+    /* labels `event` with those of the orders whose statuses it changed */
+    fn label(labels: &HashMap<OrderId, OrderLabel>, event: Event) -> Event {
+        let found: Vec<OrderLabel> = event.get_transitions().iter()
+            .filter(|change| event.get_label(change.order).is_none())
+            .filter_map(|change| labels.get(&change.order))
+            .cloned()
+            .collect();
+
+        found.into_iter().fold(event, Event::with_label)
+    }
+
+    /* The client ID and tags of an order the book has accepted or refused,
+     * if it had any, even once it is done: e.g. for either side of a trade
+     * on the tape. */
+    pub fn get_label(&self, id: OrderId) -> Option<&OrderLabel> {
+        self.labels.get(&id)
+    }
+
+    /* the last order the book accepted with `client_id`, whether or not it
+     * is still open */
+    pub fn find_by_client_id(&self, client_id: &str) -> Option<OrderId> {
+        self.client_ids.get(client_id).copied()
+    }
+
+    /* how much buying power an account's resting bids hold */
+    pub fn get_reserved(&self, account: AccountId) -> f64 {
+        self.reserved.get(&account).copied().unwrap_or(0.0)
+    }
+
+    /* Sets the account orders from `account`'s ID are checked and settled
+     * against, replacing whatever the book had for it. */
+    pub fn add_account(&mut self, account: Account) {
+        self.accounts.insert(account.get_id(), account);
+    }
+
+    /* an account as the book's fills have left it, if it has had an order
+     * on the book */
+    pub fn get_account(&self, id: AccountId) -> Option<&Account> {
+        self.accounts.get(&id)
+    }
+
+    pub fn accounts(&self) -> hash_map::Values<'_, AccountId, Account> {
+        self.accounts.values()
+    }
+
+    /* the account `order` is checked against: its owner's in the ledger,
+     * or the copy it carries if its owner is new to the book */
+    fn account_of<'a>(accounts: &'a HashMap<AccountId, Account>,
+                      order: &'a Order) -> &'a Account {
+        accounts.get(&order.get_owner_ref().get_id())
+            .unwrap_or(order.get_owner_ref())
+    }
+
+    fn account_of_mut<'a>(accounts: &'a mut HashMap<AccountId, Account>,
+                          order: &Order) -> &'a mut Account {
+        accounts.entry(order.get_owner_ref().get_id())
+            .or_insert_with(|| order.get_owner())
+    }
+
+    /* the ledger account of whoever placed `order`, on one side of a
+     * trade, if the book knows them */
+    fn trader<'a>(accounts: &'a mut HashMap<AccountId, Account>,
+                  orders: &HashMap<OrderId, Order>,
+                  filled: &HashMap<OrderId, Order>, order: OrderId,
+                  account: Option<AccountId>) -> Option<&'a mut Account> {
+        match (orders.get(&order).or_else(|| filled.get(&order)), account) {
+            (Some(order), _) => Some(Self::account_of_mut(accounts, order)),
+            (None, Some(account)) => accounts.get_mut(&account),
+            (None, None) => None
+        }
+    }
+
+    /* The buying power each account's resting bids should hold, and the
+     * size each level should have, worked out from the resting orders
+     * alone. */
+    fn recompute(&self) -> (HashMap<AccountId, f64>, BTreeMap<P, Quantity>,
+                            BTreeMap<P, Quantity>) {
+        let mut reserved: HashMap<AccountId, f64> = HashMap::new();
+        let mut bids: BTreeMap<P, Quantity> = BTreeMap::new();
+        let mut asks: BTreeMap<P, Quantity> = BTreeMap::new();
+
+        for order in self.orders.values() {
+            let sizes: &mut BTreeMap<P, Quantity> =
+                match order.get_order_type() {
+                    OrderType::Bid => &mut bids,
+                    OrderType::Ask => &mut asks
+                };
+
+            if let Some(price) = P::from_price(order.get_price()) {
+                *sizes.entry(price).or_insert(ZERO) += order.get_quantity();
+            }
+
+            Self::reserve(&mut reserved, order, order.get_quantity());
+        }
+
+        (reserved, bids, asks)
+    }
+
+    /* Everything inconsistent about the book right now: crossed while
+     * matching, empty levels, queued orders that aren't resting, levels
+     * whose size disagrees with the orders at their price, and buying
+     * power reserved that disagrees with the bids holding it. Empty for a
+     * sound book. */
+    pub fn check_integrity(&self) -> Vec<Violation> {
+        let mut violations: Vec<Violation> = vec![];
+        let (reserved, bid_sizes, ask_sizes) = self.recompute();
+
+        if let (true, Some((bid, _)), Some((ask, _))) =
+            (self.state.matches(), self.bids.best(), self.asks.best()) {
+            if bid >= ask {
+                violations.push(Violation::Crossed {
+                    bid: bid.to_price(),
+                    ask: ask.to_price()
+                });
+            }
+        }
+
+        for (side, levels, sizes) in [(OrderType::Bid, &self.bids, bid_sizes),
+                                      (OrderType::Ask, &self.asks, ask_sizes)] {
+            let prices: BTreeSet<&P> = levels.levels().keys()
+                .chain(sizes.keys())
+                .collect();
+
+            for price in prices {
+                let queue: &VecDeque<OrderId> = levels.get(price)
+                    .unwrap_or(&EMPTY_LEVEL);
+                let cached: Quantity = self.level_depth(queue);
+                let recomputed: Quantity = sizes.get(price).copied()
+                    .unwrap_or(ZERO);
+
+                if levels.contains(price) && queue.is_empty() {
+                    violations.push(Violation::EmptyLevel {
+                        side,
+                        price: price.to_price()
+                    });
+                }
+
+                violations.extend(queue.iter()
+                    .filter(|id| !self.orders.contains_key(id))
+                    .map(|id| Violation::UnknownOrder {
+                        side,
+                        price: price.to_price(),
+                        id: *id
+                    }));
+
+                if cached != recomputed {
+                    violations.push(Violation::Depth {
+                        side,
+                        price: price.to_price(),
+                        cached,
+                        recomputed
+                    });
+                }
+            }
+        }
+
+        let accounts: BTreeSet<&AccountId> = self.reserved.keys()
+            .chain(reserved.keys())
+            .collect();
+
+        for account in accounts {
+            let cached: f64 = self.get_reserved(*account);
+            let recomputed: f64 = reserved.get(account).copied()
+                .unwrap_or(0.0);
+
+            /* allowing for rounding */
+            if (cached - recomputed).abs() > 1e-9 * recomputed.abs().max(1.0) {
+                violations.push(Violation::Reserved {
+                    account: *account,
+                    cached,
+                    recomputed
+                });
+            }
+        }
+
+        violations
+    }
+
+    /* what integrity checks have found so far */
+    pub fn get_violations(&self) -> &[Violation] {
+        &self.violations
+    }
+
+    pub fn take_violations(&mut self) -> Vec<Violation> {
+        std::mem::take(&mut self.violations)
+    }
+
+    /* checks the book after an operation, as its config says to */
+    #[allow(clippy::panic)]
+    fn enforce_integrity(&mut self) -> Result<(), BookError> {
+        let mode: IntegrityMode = match self.config.integrity {
+            Some(mode) => mode,
+            None => return Ok(())
+        };
+        let violations: Vec<Violation> = self.check_integrity();
+
+        if violations.is_empty() {
+            return Ok(());
+        }
+
+        /* strict checking is asked to panic */
+        if mode == IntegrityMode::Strict {
+            let found: Vec<String> = violations.iter()
+                .map(Violation::to_string)
+                .collect();
+
+            panic!("book {} is inconsistent: {}", self.ticker,
+                   found.join("; "));
+        }
+
+        self.violations.extend(violations);
+
+        if mode == IntegrityMode::Repair {
+            self.repair()?;
+        }
+
+        Ok(())
+    }
+
+    /* Requeues the resting orders, keeping those already queued in the
+     * right place in their order and putting the rest behind them by
+     * priority, recomputes reserved buying power, then uncrosses the book
+     * if it should be matching. */
+    fn repair(&mut self) -> Result<(), BookError> {
+        let orders: &HashMap<OrderId, Order> = &self.orders;
+        let mut queued: HashSet<OrderId> = HashSet::new();
+
+        for (side, levels) in [(OrderType::Bid, &mut self.bids),
+                               (OrderType::Ask, &mut self.asks)] {
+            for (price, queue) in levels.queues_mut() {
+                queue.retain(|id| orders.get(id).is_some_and(|order|
+                    order.get_order_type() == side &&
+                    P::from_price(order.get_price()).as_ref() == Some(price) &&
+                    queued.insert(*id)));
+            }
+        }
+
+        let mut strays: Vec<&Order> = orders.values()
+            .filter(|order| !queued.contains(&order.get_id()))
+            .collect();
+
+        strays.sort_by_key(|order| (order.get_priority(), order.get_id()));
+
+        for order in strays {
+            let levels: &mut HalfBook<P> = match order.get_order_type() {
+                OrderType::Bid => &mut self.bids,
+                OrderType::Ask => &mut self.asks
+            };
+
+            if let Some(price) = P::from_price(order.get_price()) {
+                enqueue(levels.level_mut(price, &mut self.pool), orders,
+                        order);
+            }
+        }
+
+        for levels in [&mut self.bids, &mut self.asks] {
+            levels.prune(&mut self.pool);
+        }
+
+        self.reserved = self.recompute().0;
+
+        if self.state.matches() {
+            self.uncross()?;
+        }
+
+        Ok(())
+    }
+
+    fn check_price(&self, price: f64) -> Result<(), BookError> {
+        if !price.is_finite() || P::from_price(price).is_none() {
+            return Err(BookError::InvalidPrice);
+        }
+
+        if let Some(tick_size) = self.config.tick_size {
+            if !builder::on_tick(price, tick_size) {
+                return Err(BookError::InvalidPrice);
+            }
+        }
+
+        if let Some((lower, upper)) = self.config.price_band {
+            if price < lower || price > upper {
+                return Err(BookError::InvalidPrice);
+            }
+        }
+
+        if let (Some(breaker), Some(reference)) =
+            (self.config.circuit_breaker, self.band_reference()) {
+            let (lower, upper) = breaker.band(reference);
+
+            if price < lower || price > upper {
+                return Err(BookError::InvalidPrice);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn check_quantity(&self, quantity: Quantity) -> Result<(), BookError> {
+        if let Some(lot_size) = self.config.lot_size {
+            if quantity % lot_size != ZERO {
+                return Err(BookError::InvalidQuantity);
+            }
+        }
+
+        Ok(())
+    }
+
+    /* Writes out the current operation's events, followed by a top of book
+     * event if it changed the best bid or ask. By the time this runs the
+     * book has already changed, so a sink error does not undo anything: it
+     * only means the events from the failing one onwards were not
+     * recorded. */
+    fn publish(&mut self) -> Result<(), BookError> {
+        if self.batching {
+            return Ok(());
+        }
+
+        /* whatever the repricing came to is published regardless */
+        let repriced: Result<(), BookError> = self.reprice_pegs();
+        let checked: Result<(), BookError> = self.enforce_integrity();
+        let top: TopOfBook = self.top_of_book();
+
+        if top != self.top {
+            let event: Event = self.sequencer.stamp(EventKind::TopOfBook {
+                previous: self.top,
+                current: top
+            }, self.clock.now());
+
+            self.pending.push(event);
+            self.top = top;
+        }
+
+        /* whether these events cross a multiple of `compact_every` */
+        let compact: bool = match (self.config.compact_every,
+                                   self.pending.first()) {
+            (Some(every), Some(first)) if every > 0 =>
+                (first.get_seq() - 1) / every != self.last_seq() / every,
+            _ => false
+        };
+
+        let (bids, asks) = (&self.bids, &self.asks);
+        /* the `n`th best price on `side` */
+        let nth = |side: OrderType, n: usize| n.checked_sub(1)
+            .and_then(|skip| match side {
+                OrderType::Bid => bids,
+                OrderType::Ask => asks
+            }.iter().filter(|(_, queue)| !queue.is_empty()).nth(skip))
+            .map(|(price, _)| price.to_price());
+
+        for event in self.pending.drain(..) {
+            let event: Event = match (&self.actor, event.get_actor()) {
+                (Some(actor), None) => event.with_actor(actor.clone()),
+                _ => event
+            };
+            let event: Event = Self::label(&self.labels, event);
+
+            self.stats.observe(&event);
+            self.sink.write(&event)?;
+            self.subscribers.dispatch(&event, nth);
+        }
+
+        if compact {
+            let snapshot: BookSnapshot = self.snapshot();
+            self.sink.compact(&snapshot)?;
+        }
+
+        repriced.and(checked)
+    }
+
+    /* writes out an event that did not come from the book itself */
+    pub(crate) fn record(&mut self, kind: EventKind) -> Result<(), BookError> {
+        let event: Event = self.sequencer.stamp(kind, self.clock.now());
+        self.pending.push(event);
+
+        self.publish()
+    }
+
+    /* as `record`, giving `reason` for it */
+    pub(crate) fn record_with_reason(&mut self, kind: EventKind,
+                                     reason: Reason) -> Result<(), BookError> {
+        let event: Event = self.sequencer.stamp(kind, self.clock.now())
+            .with_reason(reason);
+        self.pending.push(event);
+
+        self.publish()
+    }
+
+    /* If a fill cannot be settled (e.g. the seller does not hold enough of
+     * the asset), submission stops with an error: fills already executed
+     * stand and the remainder of the order is not rested. If the circuit
+     * breaker trips part way through a sweep, the book halts (see
+     * `is_halted`) and the remainder is cancelled as
+     * `Reason::CircuitBreaker`, but the order was accepted, so this still
+     * succeeds. A sink error does not stop the remainder resting. */
+    pub fn submit(&mut self, order: Order) -> Result<(), BookError> {
+        #[cfg(feature = "metrics")]
+        let started: Instant = Instant::now();
+        let order_id: OrderId = order.get_id();
+        #[cfg(feature = "tracing")]
+        let _span = tracing::debug_span!("submit", book = %self.id,
+                                         order = %order_id,
+                                         side = ?order.get_order_type(),
+                                         price = order.get_price(),
+                                         quantity = %order.get_quantity())
+            .entered();
+
+        let admitted: Result<(), BookError> = match self.state {
+            _ if self.is_expired() => Err(BookError::Expired),
+            SessionState::Halted => Err(BookError::MarketHalted),
+            SessionState::Closed => Err(BookError::MarketClosed),
+            _ => self.validate(&order)
+        };
+
+        /* an order reusing an ID keeps the label of the one it clashes
+         * with */
+        if !self.seen.contains(&order_id) {
+            if let Some(label) = OrderLabel::of(&order) {
+                self.labels.insert(order_id, label);
+            }
+        }
+
+        if let Err(BookError::InsufficientBuyingPower { required,
+                                                        available }) =
+            admitted {
+            let event: Event = self.sequencer.stamp(
+                EventKind::BuyingPowerReject {
+                    order: order_id,
+                    account: order.get_owner_ref().get_id(),
+                    required,
+                    available
+                }, self.clock.now())
+                .with_reason(Reason::RiskReject);
+            let event: Event = Self::transition(&mut self.statuses, event,
+                                                order_id,
+                                                OrderStatus::Rejected);
+            self.pending.push(event);
+            self.publish()?;
+        } else if admitted.is_err() && !self.seen.contains(&order_id) {
+            /* refused before the book recorded anything about it */
+            self.statuses.insert(order_id, OrderStatus::Rejected);
+        }
+
+        admitted.map_err(|e| self.reject(e))?;
+        self.subscribers.track(&order);
+
+        if let Some(client_id) = order.get_client_id() {
+            self.client_ids.insert(client_id.to_string(), order_id);
+        }
+
+        self.seen.insert(order_id);
+        Self::account_of_mut(&mut self.accounts, &order);
+        let placed: Result<(), BookError> = self.place(order);
+
+        #[cfg(feature = "metrics")]
+        self.metrics.record_submit(started.elapsed());
+        placed
+    }
+
+    /* as `execute`, publishing the result */
+    fn place(&mut self, order: Order) -> Result<(), BookError> {
+        let executed: Result<(), BookError> = self.execute(order);
+        let published: Result<(), BookError> = self.publish();
+
+        executed?;
+        published
+    }
+
+    /* matches an admitted order, if the session allows, and rests whatever
+     * is left of it */
+    fn execute(&mut self, mut order: Order) -> Result<(), BookError> {
+        let order_id: OrderId = order.get_id();
+        let order_type: OrderType = order.get_order_type();
+        let matched: Result<Matched, BookError> = if self.state.matches() {
+            #[cfg(feature = "metrics")]
+            let (matching, trades) = (Instant::now(), self.trades.len());
+            #[cfg(feature = "tracing")]
+            let traded: usize = self.trades.len();
+            let matched: Result<Matched, BookError> =
+                self.match_order(&mut order);
+
+            #[cfg(feature = "metrics")]
+            self.metrics.record_match(matching.elapsed(),
+                                      self.trades.len() - trades);
+            #[cfg(feature = "tracing")]
+            self.trace_fills(traded);
+            matched
+        } else {
+            Ok(Matched::Done)
+        };
+        let post: bool = match matched {
+            Ok(Matched::SweepLimit) if order.get_quantity() > ZERO =>
+                self.limit_remainder(&mut order),
+            Ok(Matched::Halted) => {
+                self.cancel_remainder(&order, Reason::CircuitBreaker);
+                false
+            },
+            Ok(_) => order.get_quantity() > ZERO,
+            Err(_) => false
+        } && self.make_room(&order)?;
+
+        /* whatever could not be matched rests on the book */
+        if post {
+            let event: Event = self.sequencer.stamp(EventKind::Post {
+                order: order_id,
+                order_type,
+                price: order.get_price(),
+                quantity: order.get_quantity(),
+                hidden: order.is_hidden()
+            }, self.clock.now());
+            let event: Event = Self::transition(&mut self.statuses, event,
+                                                order_id,
+                                                Self::open_status(&order));
+            order.set_priority(event.get_seq());
+            self.pending.push(event);
+            #[cfg(feature = "tracing")]
+            tracing::debug!(price = order.get_price(),
+                            quantity = %order.get_quantity(), "rested");
+            self.rest(order)?;
+        }
+
+        matched.map(|_| ())
+    }
+
+    /* Deals with what `order` could not take for the book's sweep limit,
+     * returning whether it is to rest. It has traded by now, so the last
+     * trade was its last fill. A remainder to be posted rests at that
+     * price rather than its own limit: the limit stopped it short of
+     * liquidity it could still reach, so resting at the limit would leave
+     * the book crossed. It is cancelled instead if even the last price
+     * would, e.g. when it stopped part way through a level. */
+    fn limit_remainder(&mut self, order: &mut Order) -> bool {
+        let remainder: Option<SweepRemainder> = self.config.sweep_limit
+            .map(|limit| limit.remainder);
+        let now: DateTime<Utc> = self.clock.now();
+
+        if remainder == Some(SweepRemainder::Post) &&
+            !self.crosses_book(order.get_order_type(), self.ltp) {
+            order.amend(self.ltp, order.get_quantity(), now);
+            return true;
+        }
+
+        self.cancel_remainder(order, Reason::SweepLimit);
+        false
+    }
+
+    /* cancels what is left of an incoming order instead of resting it */
+    fn cancel_remainder(&mut self, order: &Order, reason: Reason) {
+        let event: Event = self.sequencer.stamp(EventKind::Cancel {
+            order: order.get_id(),
+            order_type: order.get_order_type(),
+            price: order.get_price(),
+            quantity: order.get_quantity(),
+            hidden: order.is_hidden()
+        }, self.clock.now()).with_reason(reason);
+        let event: Event = Self::transition(&mut self.statuses, event,
+                                            order.get_id(),
+                                            OrderStatus::Cancelled);
+
+        self.pending.push(event);
+    }
+
+    /* Makes room for `order` to rest within the book's capacity limit, if
+     * it has one, as its `CapacityPolicy` says, returning whether it may.
+     * If it may not, what is left of it has been cancelled. */
+    fn make_room(&mut self, order: &Order) -> Result<bool, BookError> {
+        let limit: CapacityLimit = match self.config.capacity {
+            Some(limit) => limit,
+            None => return Ok(true)
+        };
+        let price: P = P::from_price(order.get_price())
+            .ok_or(BookError::InvalidPrice)?;
+        let side: OrderType = order.get_order_type();
+
+        /* each time round, the orders that would have to go for it to fit,
+         * if any, or none if nothing can */
+        while let Some(victims) = self.crowded(&limit, side, price) {
+            if victims.is_empty() ||
+                limit.policy == CapacityPolicy::RejectNew {
+                self.cancel_remainder(order, Reason::CapacityLimit);
+                return Ok(false);
+            }
+
+            for id in victims {
+                self.withdraw(id, Reason::CapacityLimit)?;
+            }
+        }
+
+        Ok(true)
+    }
+
+    /* Which limit resting an order of `side` at `price` would break first,
+     * as the resting orders `RejectOldest` would cancel for it; `None` if
+     * it breaks none. */
+    fn crowded(&self, limit: &CapacityLimit, side: OrderType, price: P) ->
+        Option<Vec<OrderId>> {
+        let levels: &HalfBook<P> = self.half(side);
+        let level: Option<&VecDeque<OrderId>> = levels.get(&price);
+        let at_level: usize = level.map_or(0, VecDeque::len);
+        let on_side: usize = levels.order_count();
+
+        if limit.max_orders_per_level.is_some_and(|max| at_level >= max) {
+            return Some(self.oldest(level.into_iter().flatten()));
+        }
+
+        if limit.max_orders_per_side.is_some_and(|max| on_side >= max) {
+            return Some(self.oldest(levels.queues().flatten()));
+        }
+
+        if level.is_none() &&
+            limit.max_levels_per_side.is_some_and(|max| levels.len() >= max) {
+            return Some(match levels.deepest() {
+                Some((deepest, queue)) if levels.is_better(&price, deepest) =>
+                    queue.iter().copied().collect(),
+                _ => vec![]
+            });
+        }
+
+        None
+    }
+
+    /* the oldest of `ids` to rest, if any */
+    fn oldest<'a>(&self, ids: impl Iterator<Item=&'a OrderId>) ->
+        Vec<OrderId> {
+        ids.filter_map(|id| self.orders.get(id))
+            .min_by_key(|order| order.get_priority())
+            .map(Order::get_id)
+            .into_iter()
+            .collect()
+    }
+
+    /* whether an order of `order_type` at `price` would meet anything
+     * resting, hidden or not */
+    fn crosses_book(&self, order_type: OrderType, price: f64) -> bool {
+        let best: Option<&P> = self.contra(order_type).best()
+            .map(|(price, _)| price);
+
+        best.is_some_and(|best| Self::crosses(&order_type, price,
+                                              best.to_price()))
+    }
+
+    /* Submits `order` pegged on the terms of `peg`: priced from the best
+     * bid and ask of the orders that aren't pegged, and repriced whenever
+     * those move, for as long as any of it rests. Each repricing is a
+     * `Reprice` event, and costs the order its place in the queue. A
+     * repricing the book would not accept, e.g. for being outside the price
+     * band, leaves the order where it is; amending it moves it only until
+     * the reference next moves. */
+    pub fn submit_pegged(&mut self, mut order: Order, peg: Peg) ->
+        Result<(), BookError> {
+        let id: OrderId = order.get_id();
+
+        if self.seen.contains(&id) {
+            return Err(self.reject(BookError::DuplicateOrderId));
+        }
+
+        let price: f64 = match peg.price_or(&order.get_order_type(),
+                                            &self.peg_reference(),
+                                            self.reference_price(),
+                                            &self.config) {
+            Some(price) => price,
+            None => return Err(self.reject(BookError::NoPegReference))
+        };
+
+        order.amend(price, order.get_quantity(), order.get_created());
+        self.pegs.insert(id, peg);
+
+        let submitted: Result<(), BookError> = self.submit(order);
+
+        if !self.orders.contains_key(&id) {
+            self.pegs.remove(&id);
+        }
+
+        submitted
+    }
+
+    /* the terms a resting order is pegged on, if it is */
+    pub fn get_peg(&self, id: OrderId) -> Option<&Peg> {
+        self.pegs.get(&id).filter(|_| self.orders.contains_key(&id))
+            .filter(|_| self.orders.contains_key(&id))
+    }
+
+    /* the best bid and ask, and the size at each, of the orders that aren't
+     * pegged, which is what pegged orders follow: following each other
+     * instead, they could chase themselves across the spread */
+    fn peg_reference(&self) -> TopOfBook {
+        let best = |half: &HalfBook<P>| half.iter().find_map(|(price,
+                                                              queue)| {
+            let depth: Quantity = queue.iter()
+                .filter(|id| !self.pegs.contains_key(id))
+                .filter_map(|id| self.orders.get(id))
+                .filter(|order| !order.is_hidden())
+                .map(|order| order.get_quantity())
+                .sum();
+
+            if depth > ZERO {
+                Some((price.to_price(), depth))
+            } else {
+                None
+            }
+        });
+
+        TopOfBook::new(best(&self.bids), best(&self.asks))
+    }
+
+    /* Moves pegged orders after their reference, oldest first, matching
+     * them if they now cross. Matching may move the reference again, so
+     * this goes on until it stays put. */
+    fn reprice_pegs(&mut self) -> Result<(), BookError> {
+        if self.pegs.is_empty() ||
+            matches!(self.state, SessionState::Halted | SessionState::Closed) {
+            return Ok(());
+        }
+
+        /* those of filled orders are kept in case a bust puts them back */
+        let (orders, filled) = (&self.orders, &self.filled);
+        self.pegs.retain(|id, _| orders.contains_key(id) ||
+                         filled.contains_key(id));
+
+        loop {
+            let reference: TopOfBook = self.peg_reference();
+
+            if reference == self.peg_top {
+                return Ok(());
+            }
+
+            self.peg_top = reference;
+
+            let mut pegged: Vec<(u64, OrderId, Peg)> = self.pegs.iter()
+                .filter_map(|(id, peg)| self.orders.get(id)
+                            .map(|order| (order.get_priority(), *id, *peg)))
+                .collect();
+            pegged.sort_by_key(|(priority, _, _)| *priority);
+
+            for (_, id, peg) in pegged {
+                /* an earlier one may have matched it away */
+                let order: &Order = match self.orders.get(&id) {
+                    Some(order) => order,
+                    None => continue
+                };
+                let previous: f64 = order.get_price();
+                let price: f64 = match peg.price_or(&order.get_order_type(),
+                                                    &reference,
+                                                    self.reference_price(),
+                                                    &self.config) {
+                    Some(price) if price != previous => price,
+                    _ => continue
+                };
+
+                if self.check_price(price).is_err() ||
+                    self.check_buying_power(order, price,
+                                            order.get_quantity(),
+                                            Self::reservation(
+                                                order,
+                                                order.get_quantity()))
+                        .is_err() {
+                    continue;
+                }
+
+                let mut order: Order = self.take_resting(id)?;
+                let now: DateTime<Utc> = self.clock.now();
+                let event: Event = self.sequencer.stamp(EventKind::Reprice {
+                    order: id,
+                    order_type: order.get_order_type(),
+                    previous,
+                    price,
+                    quantity: order.get_quantity(),
+                    hidden: order.is_hidden()
+                }, now);
+
+                Self::amended(&mut self.amendments, &event,
+                              AmendmentKind::Reprice, &order, price,
+                              order.get_quantity());
+                self.pending.push(event);
+                order.amend(price, order.get_quantity(), now);
+                self.execute(order)?;
+            }
+        }
+    }
+
+    /* notes `order` going to `price` and `quantity`, as `event` records */
+    fn amended(amendments: &mut HashMap<OrderId, Vec<Amendment>>,
+               event: &Event, kind: AmendmentKind, order: &Order, price: f64,
+               quantity: Quantity) {
+        amendments.entry(order.get_id()).or_default().push(Amendment {
+            seq: event.get_seq(),
+            timestamp: event.get_timestamp(),
+            kind,
+            previous_price: order.get_price(),
+            previous_quantity: order.get_quantity(),
+            price,
+            quantity
+        });
+    }
+
+    /* Every change to order `id`'s price or quantity since the book
+     * accepted it, oldest first, whether modified, reduced or repriced:
+     * the order keeps its ID throughout, so this is its whole lineage.
+     * Empty for an order never amended, or one the book doesn't know. Not
+     * carried over by snapshots. */
+    pub fn order_history(&self, id: OrderId) -> &[Amendment] {
+        self.amendments.get(&id).map(Vec::as_slice).unwrap_or(&[])
+    }
+
+    /* counts `error` as the book refusing an order outright */
+    fn reject(&mut self, error: BookError) -> BookError {
+        #[cfg(feature = "metrics")]
+        self.metrics.record_reject();
+        #[cfg(feature = "tracing")]
+        tracing::debug!(error = %error, "rejected");
+        error
+    }
+
+    /* an event for each trade made since there were `since` */
+    #[cfg(feature = "tracing")]
+    fn trace_fills(&self, since: usize) {
+        for trade in self.trades.iter().skip(since) {
+            tracing::debug!(trade = trade.get_id(),
+                            aggressor = %trade.get_aggressor(),
+                            resting = %trade.get_resting(),
+                            price = trade.get_price(),
+                            quantity = %trade.get_quantity(), "fill");
+        }
+    }
+
+    /* queues `order` at the back of its level (or of its displayed orders;
+     * see `enqueue`) */
+    fn rest(&mut self, order: Order) -> Result<(), BookError> {
+        let price: P = P::from_price(order.get_price())
+            .ok_or(BookError::InvalidPrice)?;
+        let side: &mut HalfBook<P> = match order.get_order_type() {
+            OrderType::Bid => &mut self.bids,
+            OrderType::Ask => &mut self.asks
+        };
+
+        enqueue(side.level_mut(price, &mut self.pool), &self.orders, &order);
+        Self::reserve(&mut self.reserved, &order, order.get_quantity());
+        self.orders.insert(order.get_id(), order);
+        Ok(())
+    }
+
+    pub fn snapshot(&self) -> BookSnapshot {
+        BookSnapshot {
+            id: self.id,
+            name: self.name.clone(),
+            ticker: self.ticker.clone(),
+            config: self.config,
+            state: self.state,
+            ltp: self.get_ltp().ok(),
+            last_seq: self.last_seq(),
+            bids: self.iter_bids().cloned().collect(),
+            asks: self.iter_asks().cloned().collect(),
+            pegs: self.iter_bids()
+                .chain(self.iter_asks())
+                .filter_map(|order| self.get_peg(order.get_id())
+                            .map(|peg| (order.get_id(), *peg)))
+                .collect(),
+            accounts: {
+                let mut accounts: Vec<Account> =
+                    self.accounts.values().cloned().collect();
+
+                accounts.sort_by_key(Account::get_id);
+                accounts
+            }
+        }
+    }
+
+    pub fn memory_usage(&self) -> MemoryUsage {
+        let levels: usize = self.bids.len() + self.asks.len();
+        let queue_capacity: usize = self.bids.capacity() +
+            self.asks.capacity();
+        let id: usize = std::mem::size_of::<OrderId>();
+        let bytes: usize =
+            self.orders.capacity() * std::mem::size_of::<(OrderId, Order)>() +
+            self.seen.capacity() * id +
+            self.statuses.capacity() *
+                std::mem::size_of::<(OrderId, OrderStatus)>() +
+            self.labels.capacity() *
+                std::mem::size_of::<(OrderId, OrderLabel)>() +
+            self.client_ids.capacity() *
+                std::mem::size_of::<(String, OrderId)>() +
+            self.amendments.values().map(Vec::capacity).sum::<usize>() *
+                std::mem::size_of::<Amendment>() +
+            self.filled.capacity() * std::mem::size_of::<(OrderId, Order)>() +
+            levels * std::mem::size_of::<(P, VecDeque<OrderId>)>() +
+            (queue_capacity + self.pool.capacity()) * id +
+            self.trades.capacity() * std::mem::size_of::<Trade>() +
+            self.pending.capacity() * std::mem::size_of::<Event>() +
+            self.reserved.capacity() * std::mem::size_of::<(AccountId, f64)>() +
+            self.accounts.capacity() *
+                std::mem::size_of::<(AccountId, Account)>() +
+            self.pegs.capacity() * std::mem::size_of::<(OrderId, Peg)>();
+
+        MemoryUsage {
+            orders: self.orders.len(),
+            order_capacity: self.orders.capacity(),
+            levels,
+            queue_capacity,
+            pooled: self.pool.len(),
+            statuses: self.statuses.len(),
+            trades: self.trades.len(),
+            bytes
+        }
+    }
+
+    /* Gives back whatever a long session of adds and cancels has left the
+     * book holding beyond what it needs: shrinks every table and level
+     * queue to fit, drops any empty levels and the pooled queues, and, if
+     * `events`, compacts the sink to a snapshot as `compact_every` would.
+     * Returns the book's memory usage before and after. Nothing about the
+     * book's state changes, so this can be done at any quiet moment. */
+    pub fn compact(&mut self, events: bool) ->
+        Result<(MemoryUsage, MemoryUsage), BookError> {
+        let before: MemoryUsage = self.memory_usage();
+
+        for side in [&mut self.bids, &mut self.asks] {
+            side.prune(&mut self.pool);
+            side.shrink_to_fit();
+        }
+
+        self.pool.clear();
+        self.orders.shrink_to_fit();
+        self.seen.shrink_to_fit();
+        self.statuses.shrink_to_fit();
+        self.labels.shrink_to_fit();
+        self.client_ids.shrink_to_fit();
+        self.amendments.values_mut().for_each(Vec::shrink_to_fit);
+        self.amendments.shrink_to_fit();
+        self.filled.shrink_to_fit();
+        self.trades.shrink_to_fit();
+        self.pending.shrink_to_fit();
+        self.reserved.shrink_to_fit();
+        self.accounts.shrink_to_fit();
+        self.pegs.shrink_to_fit();
+
+        if events {
+            let snapshot: BookSnapshot = self.snapshot();
+            self.sink.compact(&snapshot)?;
+        }
+
+        Ok((before, self.memory_usage()))
+    }
+
+    /* Rebuilds a book from a snapshot, without matching anything: a
+     * snapshot taken in an auction restores crossed, just as it was. Only
+     * the resting orders' IDs survive a snapshot, so those of orders that
+     * had already filled or been cancelled could be reused afterwards. */
+    pub fn restore(snapshot: BookSnapshot, policy: M, sink: S, clock: C) ->
+        Result<Book<M, S, C, P>, BookError> {
+        let BookSnapshot { id, name, ticker, config, state, ltp, last_seq,
+                           bids, asks, pegs, accounts } = snapshot;
+        let mut book: Book<M, S, C, P> = Book::with_config(id, name, ticker,
+                                                           policy, sink,
+                                                           clock, config,
+                                                           state);
+
+        book.sequencer = Sequencer::starting_after(last_seq);
+
+        if let Some(ltp) = ltp {
+            book.ltp = ltp;
+            book.has_traded = true;
+            book.prior_ltp = Some(ltp);
+        }
+
+        for account in accounts {
+            book.add_account(account);
+        }
+
+        for (side, orders) in [(OrderType::Bid, bids), (OrderType::Ask, asks)] {
+            for order in orders {
+                if order.get_order_type() != side ||
+                    order.get_quantity() == ZERO {
+                    return Err(BookError::InvalidSnapshot);
+                }
+
+                if !book.seen.insert(order.get_id()) {
+                    return Err(BookError::DuplicateOrderId);
+                }
+
+                book.statuses.insert(order.get_id(),
+                                     Self::open_status(&order));
+                /* snapshots from before they kept the ledger */
+                Self::account_of_mut(&mut book.accounts, &order);
+
+                if let Some(label) = OrderLabel::of(&order) {
+                    if let Some(client_id) = &label.client_id {
+                        book.client_ids.insert(client_id.clone(),
+                                               order.get_id());
+                    }
+
+                    book.labels.insert(order.get_id(), label);
+                }
+
+                book.rest(order)?;
+            }
+        }
+
+        for (id, peg) in pegs {
+            if !book.orders.contains_key(&id) {
+                return Err(BookError::InvalidSnapshot);
+            }
+
+            book.pegs.insert(id, peg);
+        }
+
+        book.top = book.top_of_book();
+        book.peg_top = book.peg_reference();
+        Ok(book)
+    }
+
+    pub fn cancel(&mut self, id: OrderId) -> Result<CancelResult, BookError> {
+        self.cancel_with_reason(id, Reason::UserRequested)
+    }
+
+    /* as `cancel`, recording why in the `Cancel` event, e.g. for an order
+     * cancelled by the venue rather than its owner */
+    pub fn cancel_with_reason(&mut self, id: OrderId, reason: Reason) ->
+        Result<CancelResult, BookError> {
+        #[cfg(feature = "metrics")]
+        let started: Instant = Instant::now();
+        #[cfg(feature = "tracing")]
+        let _span = tracing::debug_span!("cancel", book = %self.id,
+                                         order = %id, reason = ?reason)
+            .entered();
+        let cancelled: Result<CancelResult, BookError> =
+            self.cancel_order(id, reason);
+
+        #[cfg(feature = "metrics")]
+        self.metrics.record_cancel(started.elapsed());
+        #[cfg(feature = "tracing")]
+        match &cancelled {
+            Ok(result) => tracing::debug!(
+                filled = %result.filled_quantity,
+                cancelled = %result.remaining_cancelled, "cancelled"),
+            Err(e) => tracing::debug!(error = %e, "cancel failed")
+        }
+        cancelled
+    }
+
+    fn cancel_order(&mut self, id: OrderId, reason: Reason) ->
+        Result<CancelResult, BookError> {
+        let order: Order = self.withdraw(id, reason)?;
+
+        self.publish()?;
+        Ok(CancelResult {
+            filled_quantity: order.get_filled_quantity(),
+            remaining_cancelled: order.get_quantity(),
+            order
+        })
+    }
+
+    /* takes resting order `id` off the book as cancelled for `reason`,
+     * without publishing */
+    fn withdraw(&mut self, id: OrderId, reason: Reason) ->
+        Result<Order, BookError> {
+        let mut order: Order = self.take_resting(id)?;
+
+        order.cancel_at(self.clock.now());
+        let status: OrderStatus = match reason {
+            Reason::Expired => OrderStatus::Expired,
+            _ => OrderStatus::Cancelled
+        };
+        let event: Event = self.sequencer.stamp(EventKind::Cancel {
+            order: id,
+            order_type: order.get_order_type(),
+            price: order.get_price(),
+            quantity: order.get_quantity(),
+            hidden: order.is_hidden()
+        }, self.clock.now())
+            .with_reason(reason);
+        let event: Event = Self::transition(&mut self.statuses, event, id,
+                                            status);
+        self.pending.push(event);
+        Ok(order)
+    }
+
+    /* Reverses a trade, as an exchange busts an erroneous one: the trade
+     * comes off the tape, and what it took off either order is given back,
+     * along with the money, holdings and positions that changed hands on
+     * both sides. An order still resting gets its quantity back in place,
+     * and one that filled completely while resting is put back at the back
+     * of its level, pegged as it was. A `TradeBust` event records the
+     * trade, followed by a `Post` of each restored order's terms. An order
+     * put back that would cross the book, e.g. against its own aggressor
+     * still resting, is cancelled straight away instead of trading again.
+     * An order that has since been cancelled stays cancelled, as does an
+     * aggressor that never rested. Once no trade stands, the book is as if it had
+     * never traded: `get_ltp` fails and `reference_price` falls back to the
+     * configured one. */
+    pub fn bust_trade(&mut self, id: TradeId) -> Result<Trade, BookError> {
+        let index: usize = self.trades.iter()
+            .position(|trade| trade.get_id() == id)
+            .ok_or(BookError::TradeNotFound)?;
+        let trade: Trade = self.trades.get(index).copied()
+            .ok_or(BookError::TradeNotFound)?;
+        let aggressor: OrderType = trade.get_aggressor_side();
+        let legs: [(OrderId, Option<AccountId>, OrderType); 2] = [
+            (trade.get_resting(), trade.get_resting_account(),
+             match aggressor {
+                 OrderType::Bid => OrderType::Ask,
+                 OrderType::Ask => OrderType::Bid
+             }),
+            (trade.get_aggressor(), trade.get_aggressor_account(), aggressor)
+        ];
+        let quantity: Quantity = trade.get_quantity();
+        let price: f64 = trade.get_price();
+        let now: DateTime<Utc> = self.clock.now();
+
+        /* the accounts are put right first, as that alone can fail, and
+         * the resting side's put back as it was if the aggressor's can't */
+        for (leg, (order, account, side)) in legs.iter().enumerate() {
+            let unfilled: Result<(), AccountError> =
+                match Self::trader(&mut self.accounts, &self.orders,
+                                   &self.filled, *order, *account) {
+                    Some(owner) => owner.unfill(&self.ticker, side, quantity,
+                                                price),
+                    None => Ok(())
+                };
+
+            if let Err(e) = unfilled {
+                let (order, account, side) = legs[0];
+
+                if leg > 0 {
+                    if let Some(owner) = Self::trader(&mut self.accounts,
+                                                      &self.orders,
+                                                      &self.filled, order,
+                                                      account) {
+                        let _ = owner.fill(&self.ticker, &side, quantity,
+                                           price);
+                    }
+                }
+
+                return Err(e.into());
+            }
+        }
+
+        let mut refilled: Vec<Order> = vec![];
+
+        for (order, _, _) in legs {
+            if let Some(order) = self.orders.get_mut(&order) {
+                order.unfill(quantity, now);
+                Self::reserve(&mut self.reserved, order, quantity);
+            } else if let Some(mut order) = self.filled.remove(&order) {
+                order.unfill(quantity, now);
+                refilled.push(order);
+            }
+        }
+
+        self.trades.remove(index);
+
+        /* the last traded price goes back to the trade before, or to what
+         * it was before the book's first trade */
+        if index == self.trades.len() {
+            match self.trades.last().map(Trade::get_price).or(self.prior_ltp) {
+                Some(price) => self.ltp = price,
+                None => {
+                    self.ltp = 0.00;
+                    self.has_traded = false;
+                }
+            }
+        }
+
+        self.pending.push(self.sequencer.stamp(EventKind::TradeBust(trade),
+                                               now));
+
+        for (id, _, _) in legs {
+            if let Some(position) = refilled.iter()
+                .position(|order| order.get_id() == id) {
+                let mut order: Order = refilled.swap_remove(position);
+                let event: Event = Self::repost(&mut self.sequencer,
+                                                &mut self.statuses, &order,
+                                                now);
+
+                order.set_priority(event.get_seq());
+                self.pending.push(event);
+
+                /* the other side may have been put back, or moved, since,
+                 * and the book is never left crossed */
+                let crossed: bool = self.crosses_book(order.get_order_type(),
+                                                      order.get_price());
+                self.rest(order)?;
+
+                if crossed {
+                    self.withdraw(id, Reason::Other(
+                        "crosses the book after a bust".to_string()))?;
+                }
+            } else if let Some(order) = self.orders.get(&id) {
+                let event: Event = Self::repost(&mut self.sequencer,
+                                                &mut self.statuses, order,
+                                                now);
+
+                self.pending.push(event);
+            }
+        }
+
+        self.publish()?;
+        Ok(trade)
+    }
+
+    /* a `Post` of a resting order's terms as they now stand */
+    fn repost(sequencer: &mut Sequencer,
+              statuses: &mut HashMap<OrderId, OrderStatus>, order: &Order,
+              now: DateTime<Utc>) -> Event {
+        let event: Event = sequencer.stamp(EventKind::Post {
+            order: order.get_id(),
+            order_type: order.get_order_type(),
+            price: order.get_price(),
+            quantity: order.get_quantity(),
+            hidden: order.is_hidden()
+        }, now);
+
+        Self::transition(statuses, event, order.get_id(),
+                         Self::open_status(order))
+    }
+
+    /* Amends a resting order's price and quantity. Reducing its quantity
+     * alone keeps its place in the queue; any other change sends it to the
+     * back of the queue at its new price, as if it had just been submitted,
+     * so it may match. Either way an `Amend` event records the new terms,
+     * followed in the latter case by the usual events of a submission. */
+    pub fn modify(&mut self, id: OrderId, price: f64, quantity: Quantity) ->
+        Result<(), BookError> {
+        #[cfg(feature = "tracing")]
+        let _span = tracing::debug_span!("modify", book = %self.id,
+                                         order = %id, price,
+                                         quantity = %quantity).entered();
+        let order: &Order = self.orders.get(&id)
+            .ok_or(BookError::OrderNotFound)?;
+        let (order_type, hidden) = (order.get_order_type(),
+                                    order.is_hidden());
+        let reduction: bool = price == order.get_price() &&
+            quantity <= order.get_quantity();
+
+        if quantity == ZERO {
+            return Err(BookError::InvalidQuantity);
+        }
+
+        if !reduction {
+            match self.state {
+                SessionState::Halted => return Err(BookError::MarketHalted),
+                SessionState::Closed => return Err(BookError::MarketClosed),
+                _ => self.check_price(price)?
+            }
+
+            self.check_buying_power(order, price, quantity,
+                                    Self::reservation(order,
+                                                      order.get_quantity()))?;
+        }
+
+        self.check_quantity(quantity)?;
+
+        let now: DateTime<Utc> = self.clock.now();
+        let event: Event = self.sequencer.stamp(EventKind::Amend {
+            order: id,
+            order_type,
+            price,
+            quantity,
+            hidden
+        }, now);
+
+        if let Some(order) = self.orders.get(&id) {
+            Self::amended(&mut self.amendments, &event, AmendmentKind::Modify,
+                          order, price, quantity);
+        }
+
+        self.pending.push(event);
+
+        if reduction {
+            if let Some(order) = self.orders.get_mut(&id) {
+                Self::release(&mut self.reserved, order,
+                              quantity::saturating_sub(order.get_quantity(),
+                                                       quantity));
+                order.amend(price, quantity, now);
+            }
+
+            self.publish()
+        } else {
+            let mut order: Order = self.take_resting(id)?;
+
+            order.amend(price, quantity, now);
+            self.place(order)
+        }
+    }
+
+    /* Takes `quantity` off a resting order without it losing its place in
+     * the queue, returning how much is left. Taking off all that is left
+     * cancels the order. Allowed even while the market is halted or
+     * closed. */
+    pub fn reduce(&mut self, id: OrderId, quantity: Quantity) ->
+        Result<Quantity, BookError> {
+        let order: &Order = self.orders.get(&id)
+            .ok_or(BookError::OrderNotFound)?;
+
+        let remaining: Quantity =
+            match quantity::checked_sub(order.get_quantity(), quantity) {
+                Some(remaining) if quantity > ZERO => remaining,
+                _ => return Err(BookError::InvalidQuantity)
+            };
+
+        if remaining == ZERO {
+            self.cancel(id)?;
+            return Ok(ZERO);
+        }
+
+        let (order_type, price, hidden) = (order.get_order_type(),
+                                           order.get_price(),
+                                           order.is_hidden());
+
+        self.check_quantity(remaining)?;
+
+        let now: DateTime<Utc> = self.clock.now();
+        let event: Event = self.sequencer.stamp(EventKind::Reduce {
+            order: id,
+            order_type,
+            price,
+            quantity,
+            remaining,
+            hidden
+        }, now);
+
+        if let Some(order) = self.orders.get(&id) {
+            Self::amended(&mut self.amendments, &event, AmendmentKind::Reduce,
+                          order, price, remaining);
+        }
+
+        self.pending.push(event);
+
+        if let Some(order) = self.orders.get_mut(&id) {
+            Self::release(&mut self.reserved, order, quantity);
+            order.amend(price, remaining, now);
+        }
+
+        self.publish()?;
+        Ok(remaining)
+    }
+
+    /* carries out `op` with the method for it */
+    pub fn apply(&mut self, op: BookOp) -> Result<BookOpOutcome, BookError> {
+        match op {
+            BookOp::Submit(order) => self.submit(*order)
+                .map(|_| BookOpOutcome::Submitted),
+            BookOp::Cancel(id) => self.cancel(id)
+                .map(|result| BookOpOutcome::Cancelled(Box::new(result))),
+            BookOp::Modify { id, price, quantity } =>
+                self.modify(id, price, quantity)
+                    .map(|_| BookOpOutcome::Modified),
+            BookOp::Reduce { id, quantity } => self.reduce(id, quantity)
+                .map(BookOpOutcome::Reduced)
+        }
+    }
+
+    /* Applies `ops` in order, all or nothing: if one fails, the book is put
+     * back exactly as it was before the batch and nothing is published.
+     * Otherwise their events are published together, preceded by a `Batch`
+     * event saying how many there are, and with a single top of book event
+     * at the end, so consumers never see the book between operations. A
+     * sink error while publishing them is reported against the index one
+     * past the last operation and, as elsewhere, undoes nothing. */
+    pub fn apply_batch(&mut self, ops: Vec<BookOp>) -> BatchResult {
+        let checkpoint: Checkpoint<P> = Checkpoint {
+            orders: self.orders.clone(),
+            seen: self.seen.clone(),
+            bids: self.bids.clone(),
+            asks: self.asks.clone(),
+            ltp: self.ltp,
+            has_traded: self.has_traded,
+            state: self.state,
+            sequencer: self.sequencer.clone(),
+            trades: self.trades.len(),
+            reserved: self.reserved.clone(),
+            accounts: self.accounts.clone(),
+            statuses: self.statuses.clone(),
+            labels: self.labels.clone(),
+            client_ids: self.client_ids.clone(),
+            amendments: self.amendments.clone(),
+            filled: self.filled.clone(),
+            pegs: self.pegs.clone(),
+            peg_top: self.peg_top,
+            pending: self.pending.len()
+        };
+        let operations: usize = ops.len();
+        let header: Event = self.sequencer.stamp(EventKind::Batch {
+            operations,
+            events: 0
+        }, self.clock.now());
+        let mut outcomes: Vec<BookOpOutcome> = Vec::with_capacity(operations);
+
+        self.pending.push(header.clone());
+        self.batching = true;
+
+        for (index, op) in ops.into_iter().enumerate() {
+            match self.apply(op) {
+                Ok(outcome) => outcomes.push(outcome),
+                Err(error) => {
+                    self.rollback(checkpoint);
+                    return Err(BatchError::new(index, error));
+                }
+            }
+        }
+
+        self.batching = false;
+
+        let events: u64 = self.sequencer.last_seq() - header.get_seq();
+
+        if let Some(event) = self.pending.get_mut(checkpoint.pending) {
+            *event = Event::new(header.get_seq(), header.get_timestamp(),
+                                EventKind::Batch { operations, events });
+        }
+
+        self.publish().map_err(|error| BatchError::new(operations, error))?;
+        Ok(outcomes)
+    }
+
+    /* undoes a batch that failed part way through */
+    fn rollback(&mut self, checkpoint: Checkpoint<P>) {
+        self.orders = checkpoint.orders;
+        self.seen = checkpoint.seen;
+        self.bids = checkpoint.bids;
+        self.asks = checkpoint.asks;
+        self.ltp = checkpoint.ltp;
+        self.has_traded = checkpoint.has_traded;
+        self.state = checkpoint.state;
+        self.sequencer = checkpoint.sequencer;
+        self.trades.truncate(checkpoint.trades);
+        self.reserved = checkpoint.reserved;
+        self.accounts = checkpoint.accounts;
+        self.statuses = checkpoint.statuses;
+        self.labels = checkpoint.labels;
+        self.client_ids = checkpoint.client_ids;
+        self.amendments = checkpoint.amendments;
+        self.filled = checkpoint.filled;
+        self.pegs = checkpoint.pegs;
+        self.peg_top = checkpoint.peg_top;
+        self.pending.truncate(checkpoint.pending);
+        self.batching = false;
+    }
+
+    /* removes a resting order from the book */
+    fn take_resting(&mut self, id: OrderId) -> Result<Order, BookError> {
+        let order: Order = match self.orders.remove(&id) {
+            Some(order) => order,
+            None => return Err(BookError::OrderNotFound)
+        };
+
+        Self::release(&mut self.reserved, &order, order.get_quantity());
+
+        let side: &mut HalfBook<P> = match order.get_order_type() {
+            OrderType::Bid => &mut self.bids,
+            OrderType::Ask => &mut self.asks
+        };
+
+        /* every resting order's price was representable when it rested */
+        if let Some(price) = P::from_price(order.get_price()) {
+            side.remove(&price, id, &mut self.pool);
+        }
+
+        Ok(order)
+    }
+
+    /* as `cancel`, returning just how much of the order had already
+     * executed and how much was cancelled */
+    pub fn cancel_remaining(&mut self, id: OrderId) ->
+        Result<(Quantity, Quantity), BookError> {
+        let result: CancelResult = self.cancel(id)?;
+
+        Ok((result.filled_quantity, result.remaining_cancelled))
+    }
+
+    /* checks that `quantity` of `order` can be executed without any of the
+     * arithmetic below over- or underflowing */
+    #[allow(clippy::absurd_extreme_comparisons)] /* decimals can be negative */
+    fn check_execution(accounts: &HashMap<AccountId, Account>,
+                       order: &Order, quantity: Quantity) ->
+        Result<(), BookError> {
+        if quantity <= ZERO || quantity > order.get_quantity() {
+            return Err(BookError::InvalidAllocation);
+        }
+
+        let owner: &Account = Self::account_of(accounts, order);
+
+        match order.get_order_type() {
+            OrderType::Bid =>
+                owner.can_add_holding(order.get_ticker_ref(), quantity)?,
+            OrderType::Ask =>
+                owner.can_take_holding(order.get_ticker_ref(), quantity)?
+        }
+
+        Ok(())
+    }
+
+    /* Fills `quantity` of `order` at `price`, settling it against its
+     * owner's account. Callers must have passed the order through
+     * `check_execution` first. */
+    fn partially_execute_order(accounts: &mut HashMap<AccountId, Account>,
+                               order: &mut Order, quantity: Quantity,
+                               price: f64, now: DateTime<Utc>) ->
+        Result<(), BookError> {
+        Self::account_of_mut(accounts, order)
+            .fill(order.get_ticker_ref(), &order.get_order_type(), quantity,
+                  price)?;
+        order.fill(quantity, now);
+        Ok(())
+    }
+
+    /* The single price at which the most crossed quantity would execute,
+     * and that quantity. Ties go to the price leaving the smallest
+     * imbalance between the sides, then to the one nearest the reference
+     * price (or the lowest, if there is none). Orders with a minimum
+     * fill quantity sit auctions out. */
+    fn clearing_price(&self) -> Option<(f64, Quantity)> {
+        let depth = |queue: &VecDeque<OrderId>| -> Quantity {
+            queue.iter()
+                .filter_map(|id| self.orders.get(id))
+                .filter(|order| order.get_min_quantity().is_none())
+                .map(|order| order.get_quantity())
+                .sum()
+        };
+        let nearer = |price: f64, than: f64| -> bool {
+            match self.reference_price() {
+                Some(reference) =>
+                    (price - reference).abs() < (than - reference).abs(),
+                None => price < than
+            }
+        };
+        let mut best: Option<(f64, Quantity, Quantity)> = None;
+
+        for key in self.bids.levels().keys().chain(self.asks.levels().keys()) {
+            let demand: Quantity = self.bids.at_or_better(*key)
+                .map(|(_, queue)| depth(queue))
+                .sum();
+            let supply: Quantity = self.asks.at_or_better(*key)
+                .map(|(_, queue)| depth(queue))
+                .sum();
+            let volume: Quantity = demand.min(supply);
+            let imbalance: Quantity = demand.max(supply) - volume;
+            let price: f64 = key.to_price();
+
+            if volume == ZERO {
+                continue;
+            }
+
+            let better: bool = match best {
+                None => true,
+                Some((best_price, best_volume, best_imbalance)) =>
+                    volume > best_volume ||
+                    (volume == best_volume && imbalance < best_imbalance) ||
+                    (volume == best_volume && imbalance == best_imbalance &&
+                     nearer(price, best_price))
+            };
+
+            if better {
+                best = Some((price, volume, imbalance));
+            }
+        }
+
+        best.map(|(price, volume, _)| (price, volume))
+    }
+
+    /* Executes as much crossed interest as possible at the clearing price,
+     * in price-time priority on each side regardless of the matching
+     * policy. Whatever does not execute stays resting. */
+    fn uncross(&mut self) -> Result<(), BookError> {
+        let (price, volume) = match self.clearing_price() {
+            Some(clearing) => clearing,
+            None => return Ok(())
+        };
+        let key: P = P::from_price(price).ok_or(BookError::InvalidPrice)?;
+        let orders: &HashMap<OrderId, Order> = &self.orders;
+        let eligible = |id: &OrderId| orders.get(id)
+            .is_some_and(|order| order.get_min_quantity().is_none());
+
+        let bid_ids: Vec<OrderId> = self.bids.at_or_better(key)
+            .flat_map(|(_, queue)| queue.iter().copied())
+            .filter(eligible)
+            .collect();
+        let ask_ids: Vec<OrderId> = self.asks.at_or_better(key)
+            .flat_map(|(_, queue)| queue.iter().copied())
+            .filter(eligible)
+            .collect();
+
+        let mut bid_ids = bid_ids.into_iter();
+        let mut ask_ids = ask_ids.into_iter();
+        let mut bid: Option<OrderId> = bid_ids.next();
+        let mut ask: Option<OrderId> = ask_ids.next();
+        let mut remaining: Quantity = volume;
+        let mut result: Result<(), BookError> = Ok(());
+
+        while remaining > ZERO {
+            let (bid_id, ask_id) = match (bid, ask) {
+                (Some(bid_id), Some(ask_id)) => (bid_id, ask_id),
+                _ => break
+            };
+
+            match self.execute_auction_fill(bid_id, ask_id, price, remaining) {
+                Ok(quantity) =>
+                    remaining = quantity::saturating_sub(remaining, quantity),
+                Err(e) => {
+                    result = Err(e);
+                    break;
+                }
+            }
+
+            if !self.orders.contains_key(&bid_id) {
+                bid = bid_ids.next();
+            }
+
+            if !self.orders.contains_key(&ask_id) {
+                ask = ask_ids.next();
+            }
+        }
+
+        /* fully filled orders are gone from `orders` already */
+        let orders: &HashMap<OrderId, Order> = &self.orders;
+
+        for side in [&mut self.bids, &mut self.asks] {
+            side.retain(|id| orders.contains_key(id), &mut self.pool);
+        }
+
+        result
+    }
+
+    /* one execution of the auction, between the given bid and ask, of at
+     * most `limit` */
+    fn execute_auction_fill(&mut self, bid_id: OrderId, ask_id: OrderId,
+                            price: f64, limit: Quantity) ->
+        Result<Quantity, BookError> {
+        let mut bid: Order = self.orders.remove(&bid_id)
+            .ok_or(BookError::OrderNotFound)?;
+        let mut ask: Order = match self.orders.remove(&ask_id) {
+            Some(ask) => ask,
+            None => {
+                self.orders.insert(bid_id, bid);
+                return Err(BookError::OrderNotFound);
+            }
+        };
+        let quantity: Quantity =
+            bid.get_quantity().min(ask.get_quantity()).min(limit);
+        let now: DateTime<Utc> = self.clock.now();
+
+        let accounts: &mut HashMap<AccountId, Account> = &mut self.accounts;
+        let executed: Result<(), BookError> =
+            Self::check_execution(accounts, &bid, quantity)
+                .and_then(|_| Self::check_execution(accounts, &ask, quantity))
+                .and_then(|_| Self::partially_execute_order(accounts, &mut bid,
+                                                            quantity, price,
+                                                            now))
+                .and_then(|_| Self::partially_execute_order(accounts, &mut ask,
+                                                            quantity, price,
+                                                            now));
+
+        if executed.is_ok() {
+            Self::release(&mut self.reserved, &bid, quantity);
+
+            /* neither side aggressed; attribute the trade to whichever
+             * arrived later */
+            let (aggressor, resting, aggressor_side) =
+                if ask.get_created() > bid.get_created() {
+                    (&ask, &bid, OrderType::Ask)
+                } else {
+                    (&bid, &ask, OrderType::Bid)
+                };
+            let (seq, timestamp) = self.sequencer.advance(now);
+            let trade: Trade = Trade::new(timestamp, price, quantity,
+                                          aggressor.get_id(),
+                                          resting.get_id(), aggressor_side)
+                .with_id(seq)
+                .with_accounts(aggressor.get_owner_ref().get_id(),
+                               resting.get_owner_ref().get_id());
+            let mut event: Event = Event::new(seq, timestamp,
+                                              EventKind::Match(trade));
+
+            for order in [&bid, &ask] {
+                event = Self::transition(&mut self.statuses, event,
+                                         order.get_id(),
+                                         Self::fill_status(order));
+            }
+
+            self.pending.push(event);
+            self.trades.push(trade);
+            self.ltp = price;
+            self.has_traded = true;
+        }
+
+        for order in [bid, ask] {
+            if order.get_quantity() > ZERO {
+                self.orders.insert(order.get_id(), order);
+            } else {
+                self.filled.insert(order.get_id(), order);
+            }
+        }
+
+        executed.map(|_| quantity)
+    }
+
+    fn crosses(order_type: &OrderType, order_price: f64,
+               level_price: f64) -> bool {
+        match order_type {
+            OrderType::Bid => level_price <= order_price,
+            OrderType::Ask => level_price >= order_price
+        }
+    }
+
+    /* Matches `order` against the other side for as long as it crosses,
+     * returning whether it stopped short, and why. */
+    fn match_order(&mut self, order: &mut Order) ->
+        Result<Matched, BookError> {
+        let order_type: OrderType = order.get_order_type();
+        let order_price: f64 = order.get_price();
+        let breaker: Option<CircuitBreaker> = self.config.circuit_breaker;
+        let fallback: Option<f64> = self.config.reference_price;
+        let limit: Option<SweepLimit> = self.config.sweep_limit;
+        let max_levels: Option<usize> =
+            limit.and_then(|limit| limit.max_levels);
+        let max_quantity: Option<Quantity> =
+            limit.and_then(|limit| limit.max_quantity);
+        let mut levels_traded: usize = 0;
+        let mut taken: Quantity = ZERO;
+
+        let &mut Book {
+            ref mut orders,
+            ref mut bids,
+            ref mut asks,
+            ref mut ltp,
+            ref mut has_traded,
+            ref mut state,
+            ref policy,
+            ref mut sequencer,
+            ref clock,
+            ref mut pending,
+            ref mut trades,
+            ref mut reserved,
+            ref mut accounts,
+            ref mut pool,
+            ref mut statuses,
+            ref mut filled,
+            .. } = self;
+
+        let side: &mut HalfBook<P> = match order_type {
+            OrderType::Bid => asks,
+            OrderType::Ask => bids
+        };
+
+        /* the last level visited; levels are walked from the best price
+         * outwards, and one may be left with orders that would not accept
+         * this order's remaining size, so can't just always take the best */
+        let mut visited: Option<P> = None;
+
+        while order.get_quantity() > ZERO {
+            let level_price: P = match side.next_after(visited) {
+                Some(price) if Self::crosses(&order_type, order_price,
+                                             price.to_price()) => price,
+                _ => break
+            };
+            visited = Some(level_price);
+
+            if max_levels.is_some_and(|max| levels_traded >= max) {
+                return Ok(Matched::SweepLimit);
+            }
+
+            let allowance: Quantity = match max_quantity {
+                Some(max) => quantity::saturating_sub(max, taken)
+                    .min(order.get_quantity()),
+                None => order.get_quantity()
+            };
+
+            let level: &mut VecDeque<OrderId> = match side.get_mut(&level_price) {
+                Some(level) => level,
+                None => break
+            };
+
+            let fills: Vec<(OrderId, Quantity)> =
+                policy.allocate(level, orders, allowance);
+
+            if fills.is_empty() {
+                continue;
+            }
+
+            /* incoming orders are checked against the band when they
+             * arrive, but a sweep through a thin book can still print a long
+             * way from the last trade */
+            let last: Option<f64> = if *has_traded {
+                Some(*ltp)
+            } else {
+                fallback
+            };
+
+            if let Some((breaker, reference)) = breaker
+                .and_then(|breaker| Some((breaker, breaker.centre(last)?))) {
+                let price: f64 = level_price.to_price();
+                let (lower, upper) = breaker.band(reference);
+
+                if price < lower || price > upper {
+                    pending.push(sequencer.stamp(EventKind::Halt {
+                        reference,
+                        price
+                    }, clock.now()));
+                    pending.push(sequencer.stamp(EventKind::StateChange {
+                        previous: *state,
+                        current: SessionState::Halted
+                    }, clock.now()));
+                    *state = SessionState::Halted;
+                    return Ok(Matched::Halted);
+                }
+            }
+
+            let now: DateTime<Utc> = clock.now();
+
+            for (counter_id, quantity) in fills {
+                let counter_order: &mut Order = match orders.get_mut(&counter_id) {
+                    Some(counter_order) => counter_order,
+                    None => return Err(BookError::OrderNotFound)
+                };
+
+                /* policies are pluggable, so don't trust them to have only
+                 * picked orders from this level */
+                let counter_price: Option<P> =
+                    P::from_price(counter_order.get_price());
+
+                if counter_price != Some(level_price) ||
+                    counter_order.get_order_type() == order_type {
+                    return Err(BookError::InvalidAllocation);
+                }
+
+                Self::check_execution(accounts, counter_order, quantity)?;
+                Self::check_execution(accounts, order, quantity)?;
+
+                Self::partially_execute_order(accounts, counter_order,
+                                              quantity,
+                                              level_price.to_price(), now)?;
+                Self::release(reserved, counter_order, quantity);
+
+                let counter_owner: AccountId =
+                    counter_order.get_owner_ref().get_id();
+
+                Self::partially_execute_order(accounts, order, quantity,
+                                              level_price.to_price(), now)?;
+
+                let counter_done: bool = counter_order.get_quantity() == ZERO;
+
+                if counter_done {
+                    if let Some(done) = orders.remove(&counter_id) {
+                        filled.insert(counter_id, done);
+                    }
+
+                    pool::remove_queued(level, counter_id);
+                }
+
+                *ltp = level_price.to_price();
+                *has_traded = true;
+
+                let (seq, timestamp) = sequencer.advance(now);
+                let trade: Trade = Trade::new(timestamp,
+                                              level_price.to_price(),
+                                              quantity, order.get_id(),
+                                              counter_id, order_type)
+                    .with_id(seq)
+                    .with_accounts(order.get_owner_ref().get_id(),
+                                   counter_owner);
+                let event: Event = Event::new(seq, timestamp,
+                                              EventKind::Match(trade));
+                let event: Event = Self::transition(
+                    statuses, event, counter_id,
+                    if counter_done {
+                        OrderStatus::Filled
+                    } else {
+                        OrderStatus::PartiallyFilled
+                    });
+                let event: Event = Self::transition(statuses, event,
+                                                    order.get_id(),
+                                                    Self::fill_status(order));
+
+                pending.push(event);
+                trades.push(trade);
+                taken += quantity;
+            }
+
+            levels_traded += 1;
+
+            side.close_if_empty(&level_price, pool);
+
+            if max_quantity.is_some_and(|max| taken >= max) &&
+                order.get_quantity() > ZERO {
+                return Ok(Matched::SweepLimit);
+            }
+        }
+
+        Ok(Matched::Done)
+    }
+
+}
+
+
+/* how far matching an incoming order got */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Matched {
+    /* it no longer crosses, or is filled */
+    Done,
+    /* it stopped short for the book's sweep limit */
+    SweepLimit,
+    /* it tripped the circuit breaker, halting the book */
+    Halted
+}
+
+/* Queues `order` at its level: displayed orders go ahead of every hidden
+ * order there, and hidden ones at the very back, so that hidden orders
+ * always lose time priority to displayed ones at the same price. */
+fn enqueue(queue: &mut VecDeque<OrderId>, orders: &HashMap<OrderId, Order>,
+           order: &Order) {
+    let first_hidden: Option<usize> = if order.is_hidden() {
+        None
+    } else {
+        queue.iter().position(|id| orders.get(id).is_some_and(Order::is_hidden))
+    };
+
+    match first_hidden {
+        Some(index) => queue.insert(index, order.get_id()),
+        None => queue.push_back(order.get_id())
+    }
+}
+
+#[cfg(feature = "decimal")]
+impl<M: MatchingPolicy, S: EventSink, C: Clock> Book<M, S, C, Decimal> {
+    /* as `depth`, at the exact prices the levels are keyed on */
+    pub fn decimal_depth(&self, depth: usize) -> DecimalLevels {
+        let level = |(price, queue): (&Decimal, &VecDeque<OrderId>)|
+            self.displayed(price, queue).map(|(_, depth)| (*price, depth));
+
+        DecimalLevels::new(
+            self.bids.iter().filter_map(level).take(depth).collect(),
+            self.asks.iter().filter_map(level).take(depth).collect())
+    }
+
+    /* as `levels`, at the exact prices the levels are keyed on */
+    pub fn decimal_levels(&self) -> DecimalLevels {
+        self.decimal_depth(usize::MAX)
+    }
+}
+
+impl<M: MatchingPolicy, S: EventSink, C: Clock, P: PriceType> fmt::Display
+    for Book<M, S, C, P> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.render(RenderOptions::default()))
+    }
+}
+
+/* the event log and trade tape carry timestamps from the book's clock, so
+ * two books that went through the same operations are equal regardless of
+ * them */
+impl<M: MatchingPolicy, S: EventSink, C: Clock, P: PriceType> PartialEq
+    for Book<M, S, C, P> {
+    fn eq(&self, other: &Self) -> bool {
+        self.id == other.id &&
+            self.name == other.name &&
+            self.ticker == other.ticker &&
+            self.ltp == other.ltp &&
+            self.has_traded == other.has_traded &&
+            self.state == other.state &&
+            self.orders == other.orders &&
+            self.accounts == other.accounts &&
+            self.bids == other.bids &&
+            self.asks == other.asks
+    }
+}
+
+
+#[cfg(all(test, not(feature = "decimal-quantity")))]
+#[allow(clippy::unwrap_used, clippy::indexing_slicing)]
+mod tests { 
+    use super::*;
+    use ordered_float::OrderedFloat;
+    use std::collections::HashMap;
+    use crate::builder::BandReference;
+    use crate::peg::PegReference;
+    use crate::account::*;
+
+    #[test]
+    fn test_new() -> Result<(), BookError> {
+        let id: u128 = 1;
+        let name: String = "Book".to_string();
+        let ticker: String = "BOOK".to_string();
+
+        let actual_book: Book = Book::new(id, name.clone(), ticker.clone());
+        let expected_book: Book = Book{
+            id,
+            name: name.clone(),
+            ticker: ticker.clone(),
+            orders: HashMap::new(),
+            seen: HashSet::new(),
+            bids: HalfBook::new(OrderType::Bid),
+            asks: HalfBook::new(OrderType::Ask),
+            ltp: 0.00,
+            has_traded: false,
+            prior_ltp: None,
+            state: SessionState::default(),
+            policy: PriceTime,
+            config: BookConfig::default(),
+            sequencer: Sequencer::default(),
+            top: TopOfBook::default(),
+            sink: MemorySink::new(),
+            clock: SystemClock,
+            pending: vec![],
+            trades: vec![],
+            reserved: HashMap::new(),
+            accounts: HashMap::new(),
+            violations: vec![],
+            statuses: HashMap::new(),
+            labels: HashMap::new(),
+            client_ids: HashMap::new(),
+            amendments: HashMap::new(),
+            filled: HashMap::new(),
+            pegs: HashMap::new(),
+            peg_top: TopOfBook::default(),
+            pool: LevelPool::new(DEFAULT_POOL_SIZE),
+            stats: MarketStats::default(),
+            actor: None,
+            subscribers: Subscribers::default(),
+            batching: false,
+            #[cfg(feature = "metrics")]
+            metrics: BookMetrics::new()
+        };
+
+        assert_eq!(actual_book, expected_book);
+        Ok(())
+    }
+
+    #[test]
+    fn test_submit_single_bid() -> Result<(), BookError> {
+        /* build account */
+        let account_id: AccountId = 1;
+        let account_name: String = "Account".to_string();
+        let account_balance: f64 = 12000.00;
+        let account_holdings: HashMap<String, Quantity> = HashMap::new();
+        let actual_account: Account = Account::new(account_id,
+                                                   account_name,
+                                                   account_balance,
+                                                   account_holdings);
+
+        /* build order */
+        let order_id: OrderId = 1;
+        let order_owner: Account = actual_account;
+        let order_ticker: String = "BOOK".to_string();
+        let order_type: OrderType = OrderType::Bid;
+        let order_price: f64 = 12.00;
+        let order_quantity: Quantity = 33;
+        let actual_order: Order = Order::new(order_id,
+                                                 order_owner,
+                                                 order_ticker,
+                                                 order_type,
+                                                 order_price,
+                                                 order_quantity);
+
+        /* build book */
+        let book_id: BookId = 1;
+        let book_name: String = "Book".to_string();
+        let book_ticker: String = "BOOK".to_string();
+        let mut actual_book: Book = Book::new(book_id,
+                                              book_name.clone(),
+                                              book_ticker.clone());
+
+        /* we need to build this field of the expected book due to movement
+         * of values */
+        let mut expected_orders: HashMap<OrderId, Order> = HashMap::new();
+        let mut expected_order: Order = actual_order.clone();
+        expected_order.set_priority(1);
+        let expected_owner: Account = expected_order.get_owner();
+        expected_orders.insert(order_id, expected_order);
+ 
+        /* submit order to book */
+        actual_book.submit(actual_order)?;
+
+        /* build expected fields */
+        let mut expected_bids: BTreeMap<PriceKey, VecDeque<OrderId>> =
+            BTreeMap::new();
+        expected_bids.insert(OrderedFloat::from(order_price),
+            VecDeque::from(vec![order_id]));
+
+        let expected_asks: BTreeMap<PriceKey, VecDeque<OrderId>> =
+            BTreeMap::new();
+
+        let expected_book: Book = Book {
+            id: book_id,
+            name: book_name.clone(),
+            ticker: book_ticker.clone(),
+            orders: expected_orders,
+            seen: HashSet::new(),
+            bids: HalfBook::with_levels(OrderType::Bid, expected_bids),
+            asks: HalfBook::with_levels(OrderType::Ask, expected_asks),
+            ltp: 0.00,
+            has_traded: false,
+            prior_ltp: None,
+            state: SessionState::default(),
+            policy: PriceTime,
+            config: BookConfig::default(),
+            sequencer: Sequencer::default(),
+            top: TopOfBook::default(),
+            sink: MemorySink::new(),
+            clock: SystemClock,
+            pending: vec![],
+            trades: vec![],
+            reserved: HashMap::new(),
+            accounts: HashMap::from([(account_id, expected_owner)]),
+            violations: vec![],
+            statuses: HashMap::new(),
+            labels: HashMap::new(),
+            client_ids: HashMap::new(),
+            amendments: HashMap::new(),
+            filled: HashMap::new(),
+            pegs: HashMap::new(),
+            peg_top: TopOfBook::default(),
+            pool: LevelPool::new(DEFAULT_POOL_SIZE),
+            stats: MarketStats::default(),
+            actor: None,
+            subscribers: Subscribers::default(),
+            batching: false,
+            #[cfg(feature = "metrics")]
+            metrics: BookMetrics::new()
+        };
+
+        assert_eq!(actual_book, expected_book);
+        Ok(())
+    }
+
+    #[test]
+    fn test_submit_single_ask() -> Result<(), BookError> {
+        /* build account */
+        let account_id: AccountId = 1;
+        let account_name: String = "Account".to_string();
+        let account_balance: f64 = 12000.00;
+        let account_holdings: HashMap<String, Quantity> = HashMap::new();
+        let actual_account: Account = Account::new(account_id,
+                                                   account_name,
+                                                   account_balance,
+                                                   account_holdings);
+
+        /* build order */
+        let order_id: OrderId = 1;
+        let order_owner: Account = actual_account;
+        let order_ticker: String = "BOOK".to_string();
+        let order_type: OrderType = OrderType::Ask;
+        let order_price: f64 = 12.00;
+        let order_quantity: Quantity = 33;
+        let actual_order: Order = Order::new(order_id,
+                                                 order_owner,
+                                                 order_ticker,
+                                                 order_type,
+                                                 order_price,
+                                                 order_quantity);
+
+        /* build book */
+        let book_id: BookId = 1;
+        let book_name: String = "Book".to_string();
+        let book_ticker: String = "BOOK".to_string();
+        let mut actual_book: Book = Book::new(book_id,
+                                              book_name.clone(),
+                                              book_ticker.clone());
+
+        /* we need to build this field of the expected book due to movement
+         * of values */
+        let mut expected_orders: HashMap<OrderId, Order> = HashMap::new();
+        let mut expected_order: Order = actual_order.clone();
+        expected_order.set_priority(1);
+        let expected_owner: Account = expected_order.get_owner();
+        expected_orders.insert(order_id, expected_order);
+ 
+        /* submit order to book */
+        actual_book.submit(actual_order)?;
+
+        /* build expected fields */
+        let expected_bids: BTreeMap<PriceKey, VecDeque<OrderId>> =
+            BTreeMap::new();
+
+        let mut expected_asks: BTreeMap<PriceKey, VecDeque<OrderId>> =
+            BTreeMap::new();
+        expected_asks.insert(OrderedFloat::from(order_price),
+            VecDeque::from(vec![order_id]));
+
+        let expected_book: Book = Book {
+            id: book_id,
+            name: book_name.clone(),
+            ticker: book_ticker.clone(),
+            orders: expected_orders,
+            seen: HashSet::new(),
+            bids: HalfBook::with_levels(OrderType::Bid, expected_bids),
+            asks: HalfBook::with_levels(OrderType::Ask, expected_asks),
+            ltp: 0.00,
+            has_traded: false,
+            prior_ltp: None,
+            state: SessionState::default(),
+            policy: PriceTime,
+            config: BookConfig::default(),
+            sequencer: Sequencer::default(),
+            top: TopOfBook::default(),
+            sink: MemorySink::new(),
+            clock: SystemClock,
+            pending: vec![],
+            trades: vec![],
+            reserved: HashMap::new(),
+            accounts: HashMap::from([(account_id, expected_owner)]),
+            violations: vec![],
+            statuses: HashMap::new(),
+            labels: HashMap::new(),
+            client_ids: HashMap::new(),
+            amendments: HashMap::new(),
+            filled: HashMap::new(),
+            pegs: HashMap::new(),
+            peg_top: TopOfBook::default(),
+            pool: LevelPool::new(DEFAULT_POOL_SIZE),
+            stats: MarketStats::default(),
+            actor: None,
+            subscribers: Subscribers::default(),
+            batching: false,
+            #[cfg(feature = "metrics")]
+            metrics: BookMetrics::new()
+        };
+
+        assert_eq!(actual_book, expected_book);
+        Ok(())
+    }
+
+    fn build_order(id: OrderId, order_type: OrderType, price: f64,
+                   quantity: Quantity) -> Order {
+        let mut holdings: HashMap<String, Quantity> = HashMap::new();
+        holdings.insert("BOOK".to_string(), 1000);
+
+        let owner: Account = Account::new(id, "Account".to_string(),
+                                          12000.00, holdings);
+
+        Order::new(id, owner, "BOOK".to_string(), order_type, price, quantity)
+    }
+
+    #[test]
+    fn test_submit_crossing_bid_full_match() -> Result<(), BookError> {
+        let mut actual_book: Book = Book::new(1, "Book".to_string(),
+                                              "BOOK".to_string());
+
+        actual_book.submit(build_order(1, OrderType::Ask, 12.00, 33))?;
+        actual_book.submit(build_order(2, OrderType::Bid, 12.50, 33))?;
+
+        assert!(actual_book.get_order(1).is_err());
+        assert!(actual_book.get_order(2).is_err());
+        assert!(actual_book.bids.is_empty());
+        assert!(actual_book.asks.is_empty());
+        assert_eq!(actual_book.get_ltp()?, 12.00);
+        Ok(())
+    }
+
+    #[test]
+    fn test_submit_crossing_bid_rests_remainder() -> Result<(), BookError> {
+        let mut actual_book: Book = Book::new(1, "Book".to_string(),
+                                              "BOOK".to_string());
+
+        actual_book.submit(build_order(1, OrderType::Ask, 12.00, 10))?;
+        actual_book.submit(build_order(2, OrderType::Bid, 12.00, 33))?;
+
+        let mut expected_bids: BTreeMap<PriceKey, VecDeque<OrderId>> =
+            BTreeMap::new();
+        expected_bids.insert(OrderedFloat::from(12.00),
+            VecDeque::from(vec![2]));
+
+        assert_eq!(actual_book.get_order(2)?.get_quantity(), 23);
+        assert_eq!(*actual_book.bids.levels(), expected_bids);
+        assert!(actual_book.asks.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_submit_crossing_ask_matches_best_bid() -> Result<(), BookError> {
+        let mut actual_book: Book = Book::new(1, "Book".to_string(),
+                                              "BOOK".to_string());
+
+        actual_book.submit(build_order(1, OrderType::Bid, 11.00, 10))?;
+        actual_book.submit(build_order(2, OrderType::Bid, 12.00, 10))?;
+        actual_book.submit(build_order(3, OrderType::Ask, 11.50, 15))?;
+
+        let mut expected_bids: BTreeMap<PriceKey, VecDeque<OrderId>> =
+            BTreeMap::new();
+        expected_bids.insert(OrderedFloat::from(11.00),
+            VecDeque::from(vec![1]));
+
+        let mut expected_asks: BTreeMap<PriceKey, VecDeque<OrderId>> =
+            BTreeMap::new();
+        expected_asks.insert(OrderedFloat::from(11.50),
+            VecDeque::from(vec![3]));
+
+        assert_eq!(*actual_book.bids.levels(), expected_bids);
+        assert_eq!(*actual_book.asks.levels(), expected_asks);
+        assert_eq!(actual_book.get_order(3)?.get_quantity(), 5);
+        assert_eq!(actual_book.get_ltp()?, 12.00);
+        Ok(())
+    }
+
+    #[test]
+    fn test_dyn_book_matches_like_static_book() -> Result<(), BookError> {
+        let mut static_book: Book = Book::new(1, "Book".to_string(),
+                                              "BOOK".to_string());
+        let mut dyn_book: DynBook = Book::with_policy(1, "Book".to_string(),
+                                                      "BOOK".to_string(),
+                                                      Box::new(PriceTime));
+
+        let orders: Vec<Order> = vec![
+            build_order(1, OrderType::Ask, 12.00, 10),
+            build_order(2, OrderType::Ask, 12.00, 20),
+            build_order(3, OrderType::Bid, 12.00, 15),
+        ];
+
+        for order in orders {
+            static_book.submit(order.clone())?;
+            dyn_book.submit(order)?;
+        }
+
+        assert_eq!(static_book.get_order(2)?.get_quantity(),
+                   dyn_book.get_order(2)?.get_quantity());
+        assert_eq!(static_book.asks, dyn_book.asks);
+        assert_eq!(static_book.bids, dyn_book.bids);
+        Ok(())
+    }
+
+    #[test]
+    fn test_levels() -> Result<(), BookError> {
+        let mut actual_book: Book = Book::new(1, "Book".to_string(),
+                                              "BOOK".to_string());
+
+        actual_book.submit(build_order(1, OrderType::Bid, 11.00, 10))?;
+        actual_book.submit(build_order(2, OrderType::Bid, 12.00, 10))?;
+        actual_book.submit(build_order(3, OrderType::Bid, 12.00, 5))?;
+        actual_book.submit(build_order(4, OrderType::Ask, 13.00, 7))?;
+        actual_book.submit(build_order(5, OrderType::Ask, 14.00, 8))?;
+
+        let expected_levels: Levels = Levels::new(
+            vec![(12.00, 15), (11.00, 10)],
+            vec![(13.00, 7), (14.00, 8)]);
+
+        assert_eq!(actual_book.levels(), expected_levels);
+
+        let snapshot: DepthSnapshot = actual_book.depth_snapshot(1);
+
+        assert_eq!((snapshot.book, snapshot.ticker.as_str(), snapshot.seq),
+                   (1, "BOOK", actual_book.last_seq()));
+        assert_eq!(snapshot.levels, Levels::new(vec![(12.00, 15)],
+                                                vec![(13.00, 7)]));
+        assert_eq!(actual_book.levels_snapshot().levels, expected_levels);
+        Ok(())
+    }
+
+    #[test]
+    fn test_cancel() -> Result<(), BookError> {
+        let mut actual_book: Book = Book::new(1, "Book".to_string(),
+                                              "BOOK".to_string());
+
+        actual_book.submit(build_order(1, OrderType::Bid, 12.00, 10))?;
+        actual_book.submit(build_order(2, OrderType::Bid, 12.00, 10))?;
+
+        let cancelled: Order = actual_book.cancel(1)?.order;
+
+        let mut expected_bids: BTreeMap<PriceKey, VecDeque<OrderId>> =
+            BTreeMap::new();
+        expected_bids.insert(OrderedFloat::from(12.00),
+            VecDeque::from(vec![2]));
+
+        assert!(!cancelled.active());
+        assert_eq!(*actual_book.bids.levels(), expected_bids);
+        assert!(matches!(actual_book.cancel(1),
+                         Err(BookError::OrderNotFound)));
+
+        actual_book.cancel(2)?;
+        assert!(actual_book.bids.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_unsettleable_match_is_an_error() -> Result<(), BookError> {
+        let mut actual_book: Book = Book::new(1, "Book".to_string(),
+                                              "BOOK".to_string());
+        let seller: Account = Account::new(2, "Account".to_string(), 0.00,
+                                           HashMap::new());
+
+        actual_book.submit(build_order(1, OrderType::Bid, 12.00, 10))?;
+
+        let result: Result<(), BookError> = actual_book.submit(
+            Order::new(2, seller, "BOOK".to_string(), OrderType::Ask, 12.00,
+                       5));
+
+        assert!(matches!(result, Err(BookError::Account(_))));
+        assert_eq!(actual_book.get_order(1)?.get_quantity(), 10);
+        assert!(actual_book.get_ltp().is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_random_operations_do_not_panic() {
+        let mut actual_book: Book = Book::new(1, "Book".to_string(),
+                                              "BOOK".to_string());
+
+        /* xorshift, so that failures are reproducible */
+        let mut state: u64 = 0x2545_f491_4f6c_dd1d;
+        let mut next = move |bound: u64| -> u64 {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state % bound
+        };
+
+        for id in 1..=2_000 {
+            if next(4) == 0 {
+                let _ = actual_book.cancel(next(id as u64) as OrderId);
+                continue;
+            }
+
+            let order_type: OrderType = if next(2) == 0 {
+                OrderType::Bid
+            } else {
+                OrderType::Ask
+            };
+            let mut holdings: HashMap<String, Quantity> = HashMap::new();
+
+            if next(8) != 0 {
+                holdings.insert("BOOK".to_string(), next(200) as Quantity);
+            }
+
+            let owner: Account = Account::new(id, "Account".to_string(),
+                                              0.00, holdings);
+            let _ = actual_book.submit(Order::new(
+                    id, owner, "BOOK".to_string(), order_type,
+                    90.00 + next(20) as f64, next(100) as Quantity));
+
+            /* the book must never be left crossed */
+            let levels: Levels = actual_book.levels();
+
+            if let (Some(bid), Some(ask)) = (levels.get_bids().first(),
+                                             levels.get_asks().first()) {
+                assert!(bid.0 < ask.0);
+            }
+        }
+    }
+
+    #[test]
+    fn test_events_and_trades_recorded() -> Result<(), BookError> {
+        let mut actual_book: Book = Book::new(1, "Book".to_string(),
+                                              "BOOK".to_string());
+
+        actual_book.submit(build_order(1, OrderType::Ask, 12.00, 10))?;
+        actual_book.submit(build_order(2, OrderType::Bid, 12.00, 15))?;
+        actual_book.cancel(2)?;
+
+        let actual_kinds: Vec<EventKind> = actual_book.get_events().iter()
+            .map(|event| event.get_kind().clone())
+            .collect();
+        let expected_trade: Trade = actual_book.get_trades()[0];
+        let empty: TopOfBook = TopOfBook::default();
+        let offered: TopOfBook = TopOfBook::new(None, Some((12.00, 10)));
+        let bid: TopOfBook = TopOfBook::new(Some((12.00, 5)), None);
+        let expected_kinds: Vec<EventKind> = vec![
+            EventKind::Post {order: 1, order_type: OrderType::Ask,
+                             price: 12.00, quantity: 10, hidden: false},
+            EventKind::TopOfBook {previous: empty, current: offered},
+            EventKind::Match(expected_trade),
+            EventKind::Post {order: 2, order_type: OrderType::Bid,
+                             price: 12.00, quantity: 5, hidden: false},
+            EventKind::TopOfBook {previous: offered, current: bid},
+            EventKind::Cancel {order: 2, order_type: OrderType::Bid,
+                               price: 12.00, quantity: 5, hidden: false},
+            EventKind::TopOfBook {previous: bid, current: empty},
+        ];
+
+        assert_eq!(actual_book.get_trades().len(), 1);
+        assert_eq!(expected_trade.get_aggressor(), 2);
+        assert_eq!(expected_trade.get_resting(), 1);
+        assert_eq!(expected_trade.get_quantity(), 10);
+        assert_eq!(actual_kinds, expected_kinds);
+        Ok(())
+    }
+
+    #[test]
+    fn test_events_are_sequenced() -> Result<(), BookError> {
+        let mut actual_book: Book = Book::new(1, "Book".to_string(),
+                                              "BOOK".to_string());
+
+        assert_eq!(actual_book.last_seq(), 0);
+
+        actual_book.submit(build_order(1, OrderType::Ask, 12.00, 10))?;
+        actual_book.submit(build_order(2, OrderType::Ask, 12.00, 10))?;
+        actual_book.submit(build_order(3, OrderType::Bid, 12.00, 25))?;
+        actual_book.cancel(3)?;
+
+        let actual_seqs: Vec<u64> = actual_book.get_events().iter()
+            .map(Event::get_seq)
+            .collect();
+
+        assert_eq!(actual_seqs, (1..=10).collect::<Vec<u64>>());
+        assert_eq!(actual_book.last_seq(), 10);
+        Ok(())
+    }
+
+    #[cfg(feature = "metrics")]
+    #[test]
+    fn test_metrics() -> Result<(), BookError> {
+        let mut actual_book: Book = Book::new(1, "Book".to_string(),
+                                              "BOOK".to_string());
+
+        actual_book.submit(build_order(1, OrderType::Ask, 12.00, 10))?;
+        actual_book.submit(build_order(2, OrderType::Ask, 12.50, 10))?;
+        actual_book.submit(build_order(3, OrderType::Bid, 12.50, 15))?;
+        actual_book.submit(build_order(4, OrderType::Bid, 11.00, 5))?;
+        assert!(actual_book.submit(build_order(4, OrderType::Bid, 11.00, 5))
+                .is_err());
+        actual_book.cancel(4)?;
+
+        let actual_metrics: &BookMetrics = actual_book.metrics();
+
+        assert_eq!(actual_metrics.get_submit_latencies().len(), 4);
+        assert_eq!(actual_metrics.get_match_latencies().len(), 4);
+        assert_eq!(actual_metrics.get_cancel_latencies().len(), 1);
+        assert_eq!(actual_metrics.get_orders_matched(), 1);
+        assert_eq!(actual_metrics.get_trades(), 2);
+        assert_eq!(actual_metrics.get_rejects(), 1);
+
+        actual_book.metrics_mut().reset();
+        assert_eq!(actual_book.metrics().get_submit_latencies().len(), 0);
+        Ok(())
+    }
+
+    #[test]
+    fn test_mark_price() -> crate::Result<()> {
+        use crate::analytics::{MarkPolicy, MarkSource};
+        use crate::clock::ManualClock;
+
+        let clock: ManualClock = ManualClock::new(Utc::now());
+        let mut actual_book: Book<PriceTime, MemorySink, ManualClock> =
+            Book::builder(1, "BOOK".to_string())
+                .clock(clock.clone())
+                .reference_price(10.00)
+                .mark_policy(MarkPolicy {
+                    stale_after: Some(Duration::from_secs(60)),
+                    ..MarkPolicy::default()
+                })
+                .build()?;
+        let source = |book: &Book<PriceTime, MemorySink, ManualClock>| book
+            .mark_price()
+            .map(|mark| (mark.source, mark.price, mark.stale));
+
+        assert_eq!(source(&actual_book),
+                   Some((MarkSource::Reference, 10.00, false)));
+
+        actual_book.submit(build_order(1, OrderType::Ask, 12.00, 10))?;
+        actual_book.submit(build_order(2, OrderType::Bid, 11.00, 10))?;
+        assert_eq!(source(&actual_book), Some((MarkSource::Mid, 11.50, false)));
+
+        clock.advance(chrono::Duration::seconds(120));
+        assert_eq!(source(&actual_book), Some((MarkSource::Mid, 11.50, true)));
+        assert_eq!(actual_book.mark_price().and_then(|mark| mark.age),
+                   Some(Duration::from_secs(120)));
+
+        /* a trade moves the top along with it */
+        actual_book.submit(build_order(3, OrderType::Bid, 12.00, 4))?;
+        assert_eq!(source(&actual_book), Some((MarkSource::Mid, 11.50, false)));
+        Ok(())
+    }
+
+    #[test]
+    fn test_order_history() -> Result<(), BookError> {
+        let mut actual_book: Book = Book::new(1, "Book".to_string(),
+                                              "BOOK".to_string());
+
+        actual_book.submit(build_order(1, OrderType::Bid, 11.00, 10))?;
+        assert!(actual_book.order_history(1).is_empty());
+
+        actual_book.modify(1, 11.50, 10)?;
+        actual_book.reduce(1, 4)?;
+        actual_book.modify(1, 11.75, 8)?;
+
+        let actual_history: Vec<(AmendmentKind, f64, Quantity, f64,
+                                 Quantity)> =
+            actual_book.order_history(1).iter()
+                .map(|amendment| (amendment.kind, amendment.previous_price,
+                                  amendment.previous_quantity,
+                                  amendment.price, amendment.quantity))
+                .collect();
+
+        assert_eq!(actual_history, vec![
+            (AmendmentKind::Modify, 11.00, 10, 11.50, 10),
+            (AmendmentKind::Reduce, 11.50, 10, 11.50, 6),
+            (AmendmentKind::Modify, 11.50, 6, 11.75, 8)
+        ]);
+
+        /* each names the event that recorded it */
+        let actual_seq: u64 = actual_book.order_history(1)[1].seq;
+
+        assert!(actual_book.get_events().iter().any(|event|
+            event.get_seq() == actual_seq &&
+                matches!(event.get_kind(), EventKind::Reduce { .. })));
+        Ok(())
+    }
+
+    #[test]
+    fn test_stats() -> Result<(), BookError> {
+        let mut actual_book: Book = Book::new(1, "Book".to_string(),
+                                              "BOOK".to_string());
+
+        actual_book.submit(build_order(1, OrderType::Ask, 12.00, 10))?;
+        actual_book.submit(build_order(2, OrderType::Ask, 12.50, 10))?;
+        actual_book.submit(build_order(3, OrderType::Bid, 11.00, 10))?;
+        actual_book.submit(build_order(4, OrderType::Bid, 12.00, 4))?;
+
+        let mut hidden: Order = build_order(5, OrderType::Bid, 11.50, 5);
+        hidden.set_hidden();
+        actual_book.submit(hidden)?;
+
+        let actual_stats: BookStats = actual_book.stats();
+
+        assert_eq!(actual_stats.seq, actual_book.last_seq());
+        assert_eq!(actual_stats.best_bid, Some((11.00, 10)));
+        assert_eq!(actual_stats.best_ask, Some((12.00, 6)));
+        assert_eq!(actual_stats.spread, Some(1.00));
+        assert_eq!(actual_stats.mid, Some(11.50));
+        assert_eq!(actual_stats.last_price, Some(12.00));
+        assert_eq!((actual_stats.bid_depth, actual_stats.ask_depth), (10, 16));
+        assert_eq!((actual_stats.bid_levels, actual_stats.ask_levels), (1, 2));
+        assert_eq!((actual_stats.bid_orders, actual_stats.ask_orders), (1, 2));
+        assert_eq!((actual_stats.trades, actual_stats.volume), (1, 4));
+        Ok(())
+    }
+
+    #[test]
+    fn test_client_ids() -> Result<(), BookError> {
+        let mut actual_book: Book = Book::new(1, "Book".to_string(),
+                                              "BOOK".to_string());
+        let labelled = |id: OrderId, order_type: OrderType, price: f64,
+                        client_id: &str| {
+            let mut order: Order = build_order(id, order_type, price, 10);
+
+            order.set_client_id(client_id.to_string());
+            order.set_tag("strategy".to_string(), "arb".to_string());
+            order
+        };
+
+        actual_book.submit(labelled(1, OrderType::Ask, 12.00, "ask-1"))?;
+        assert_eq!(actual_book.find_by_client_id("ask-1"), Some(1));
+        assert!(matches!(
+            actual_book.submit(labelled(2, OrderType::Ask, 12.50, "ask-1")),
+            Err(BookError::DuplicateClientId(_))));
+
+        actual_book.submit(build_order(3, OrderType::Bid, 12.00, 10))?;
+
+        /* the fill carries the labelled side's label, and not the other's */
+        let actual_fill: &Event = actual_book.get_events().iter()
+            .find(|event| matches!(event.get_kind(), EventKind::Match(_)))
+            .ok_or(BookError::OrderNotFound)?;
+        let actual_label: Option<&OrderLabel> = actual_fill.get_label(1);
+
+        assert_eq!(actual_fill.get_labels().len(), 1);
+        assert_eq!(actual_label.and_then(|label| label.client_id.as_deref()),
+                   Some("ask-1"));
+        assert_eq!(actual_label.and_then(|label| label.tags.get("strategy")),
+                   Some(&"arb".to_string()));
+        assert_eq!(actual_book.get_label(1), actual_label);
+
+        /* done with, its client ID may be used again */
+        actual_book.submit(labelled(4, OrderType::Ask, 12.50, "ask-1"))?;
+        assert_eq!(actual_book.find_by_client_id("ask-1"), Some(4));
+        assert_eq!(actual_book.get_label(4).map(|label| label.order), Some(4));
+        Ok(())
+    }
+
+    #[test]
+    fn test_manual_clock_timestamps() -> Result<(), BookError> {
+        use chrono::{Duration, TimeZone};
+        use crate::clock::ManualClock;
+
+        let open: DateTime<Utc> = Utc.with_ymd_and_hms(2024, 1, 2, 9, 30, 0)
+            .unwrap();
+        let clock: ManualClock = ManualClock::new(open);
+        let mut actual_book: Book<PriceTime, MemorySink, ManualClock> =
+            Book::builder(1, "BOOK".to_string())
+                .clock(clock.clone())
+                .build()
+                .unwrap();
+
+        actual_book.submit(build_order(1, OrderType::Ask, 12.00, 10))?;
+        clock.advance(Duration::seconds(1));
+        actual_book.submit(build_order(2, OrderType::Bid, 12.00, 4))?;
+        clock.advance(Duration::seconds(1));
+        let cancelled: Order = actual_book.cancel(1)?.order;
+
+        let actual_timestamps: Vec<DateTime<Utc>> = actual_book.get_events()
+            .iter()
+            .map(Event::get_timestamp)
+            .collect();
+        let expected_timestamps: Vec<DateTime<Utc>> = vec![
+            open, open,
+            open + Duration::seconds(1), open + Duration::seconds(1),
+            open + Duration::seconds(2), open + Duration::seconds(2)
+        ];
+
+        assert_eq!(actual_timestamps, expected_timestamps);
+        assert_eq!(actual_book.get_trades()[0].get_timestamp(),
+                   open + Duration::seconds(1));
+        assert_eq!(cancelled.get_modified(), open + Duration::seconds(1));
+        assert!(matches!(cancelled.get_cancelled(),
+                         Ok(time) if time == open + Duration::seconds(2)));
+        Ok(())
+    }
+
+    #[test]
+    fn test_tick_book_shares_levels() -> Result<(), BookError> {
+        use crate::price::Cents;
+
+        let mut actual_book: TickBook<100> =
+            Book::builder(1, "BOOK".to_string())
+            .price_type::<Cents>()
+            .build()
+            .unwrap();
+
+        actual_book.submit(build_order(1, OrderType::Bid, 0.1 + 0.2, 10))?;
+        actual_book.submit(build_order(2, OrderType::Bid, 0.3, 5))?;
+
+        assert_eq!(actual_book.levels().get_bids(), [(0.3, 15)]);
+        assert!(matches!(actual_book.submit(build_order(3, OrderType::Bid,
+                                                        0.305, 5)),
+                         Err(BookError::InvalidPrice)));
+
+        actual_book.submit(build_order(4, OrderType::Ask, 0.3, 12))?;
+
+        assert_eq!(actual_book.get_ltp()?, 0.3);
+        assert_eq!(actual_book.levels().get_bids(), [(0.3, 3)]);
+        Ok(())
+    }
+
+    #[cfg(feature = "decimal")]
+    #[test]
+    fn test_decimal_book() -> Result<(), BookError> {
+        let mut actual_book: DecimalBook =
+            Book::builder(1, "BOOK".to_string())
+            .price_type::<Decimal>()
+            .build()
+            .unwrap();
+
+        actual_book.submit(build_order(1, OrderType::Ask, 0.00000123, 10))?;
+        actual_book.submit(build_order(2, OrderType::Ask, 0.00000124, 5))?;
+        actual_book.submit(build_order(3, OrderType::Bid, 0.00000122, 7))?;
+
+        let levels: DecimalLevels = actual_book.decimal_levels();
+
+        assert_eq!(levels.get_asks(), [(Decimal::new(123, 8), 10),
+                                       (Decimal::new(124, 8), 5)]);
+        assert_eq!(levels.get_bids(), [(Decimal::new(122, 8), 7)]);
+        assert_eq!(levels.to_levels(), actual_book.levels());
+        assert_eq!(actual_book.levels().to_decimal(), Some(levels));
+        assert_eq!(actual_book.decimal_depth(1).get_asks().len(), 1);
+        assert!(serde_json::to_string(&actual_book.decimal_levels())
+                .map_err(|_| BookError::InvalidPrice)?
+                .contains("[\"0.00000123\",10]"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_reduce() -> Result<(), BookError> {
+        let mut actual_book: Book = Book::new(1, "Book".to_string(),
+                                              "BOOK".to_string());
+
+        actual_book.submit(build_order(1, OrderType::Bid, 12.00, 10))?;
+        actual_book.submit(build_order(2, OrderType::Bid, 12.00, 10))?;
+
+        assert_eq!(actual_book.reduce(1, 4)?, 6);
+        assert_eq!(actual_book.queue_position(1), Some(0));
+        assert_eq!(actual_book.get_order(1)?.get_quantity(), 6);
+        assert_eq!(actual_book.levels().get_bids(), [(12.00, 16)]);
+        assert!(matches!(actual_book.get_events().last().map(Event::get_kind),
+                         Some(EventKind::TopOfBook { .. })));
+        assert!(matches!(actual_book.reduce(1, 7),
+                         Err(BookError::InvalidQuantity)));
+
+        assert_eq!(actual_book.reduce(1, 6)?, 0);
+        assert!(actual_book.get_order(1).is_err());
+        assert_eq!(actual_book.queue_position(2), Some(0));
+        Ok(())
+    }
+
+    #[test]
+    fn test_apply_batch() -> Result<(), BookError> {
+        let mut actual_book: Book = Book::new(1, "Book".to_string(),
+                                              "BOOK".to_string());
+
+        actual_book.submit(build_order(1, OrderType::Bid, 11.00, 10))?;
+        actual_book.submit(build_order(2, OrderType::Ask, 12.00, 10))?;
+
+        let last_seq: u64 = actual_book.last_seq();
+        let outcomes: Vec<BookOpOutcome> = actual_book.apply_batch(vec![
+            BookOp::Cancel(1),
+            BookOp::Cancel(2),
+            BookOp::Submit(Box::new(build_order(3, OrderType::Bid, 11.50,
+                                                10))),
+            BookOp::Submit(Box::new(build_order(4, OrderType::Ask, 11.75,
+                                                10)))
+        ]).map_err(BatchError::into_error)?;
+
+        assert_eq!(outcomes.len(), 4);
+        assert_eq!(actual_book.top_of_book(),
+                   TopOfBook::new(Some((11.50, 10)), Some((11.75, 10))));
+
+        let events: &[Event] = actual_book.get_events();
+        let batch: Vec<&EventKind> = events.iter()
+            .filter(|event| event.get_seq() > last_seq)
+            .map(Event::get_kind)
+            .collect();
+
+        assert_eq!(batch.first(),
+                   Some(&&EventKind::Batch { operations: 4, events: 4 }));
+        assert_eq!(batch.iter()
+                       .filter(|kind| matches!(kind,
+                                               EventKind::TopOfBook { .. }))
+                       .count(),
+                   1);
+
+        /* the last operation fails, so none of them happen */
+        let levels: Levels = actual_book.levels();
+        let last_seq: u64 = actual_book.last_seq();
+        let published: usize = actual_book.get_events().len();
+        let failed: BatchResult = actual_book.apply_batch(vec![
+            BookOp::Reduce { id: 3, quantity: 5 },
+            BookOp::Submit(Box::new(build_order(5, OrderType::Bid, 11.75,
+                                                5))),
+            BookOp::Cancel(99)
+        ]);
+
+        assert!(matches!(failed.as_ref().map_err(BatchError::get_error),
+                         Err(BookError::OrderNotFound)));
+        assert_eq!(failed.map_err(|e| e.get_index()).err(), Some(2));
+        assert_eq!(actual_book.levels(), levels);
+        assert_eq!(actual_book.last_seq(), last_seq);
+        assert_eq!(actual_book.get_events().len(), published);
+        assert!(actual_book.get_trades().is_empty());
+        assert!(actual_book.get_order(5).is_err());
+
+        /* nor was order 5's ID used up */
+        actual_book.submit(build_order(5, OrderType::Bid, 11.00, 5))?;
+
+        /* an order filled by a batch that fails is back as it was */
+        let failed: BatchResult = actual_book.apply_batch(vec![
+            BookOp::Submit(Box::new(build_order(6, OrderType::Bid, 11.75,
+                                                10))),
+            BookOp::Cancel(99)
+        ]);
+
+        assert!(failed.is_err());
+        assert_eq!(actual_book.get_order(4)?.get_quantity(), 10);
+        assert_eq!(actual_book.status(4), Some(OrderStatus::New));
+        assert!(actual_book.filled.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_fills_update_positions() -> Result<(), BookError> {
+        use crate::position::{Pnl, PositionSide};
+
+        let mut actual_book: Book = Book::new(1, "Book".to_string(),
+                                              "BOOK".to_string());
+
+        actual_book.submit(build_order(1, OrderType::Ask, 12.00, 10))?;
+        actual_book.submit(build_order(2, OrderType::Bid, 12.00, 4))?;
+        actual_book.submit(build_order(3, OrderType::Bid, 11.00, 1))?;
+
+        let seller: &Account = actual_book.get_account(1).unwrap();
+
+        assert_eq!(seller.get_position("BOOK").map(|p| p.get_side()),
+                   Some(PositionSide::Short));
+        assert_eq!(seller.get_position("BOOK").map(|p| p.get_quantity()),
+                   Some(4));
+        /* marked at the mid, 11.50 */
+        assert_eq!(seller.pnl(&actual_book), Pnl::new(0.00, 2.00));
+        Ok(())
+    }
+
+    #[test]
+    fn test_positions_across_orders() -> Result<(), BookError> {
+        use crate::position::{Pnl, PositionSide};
+
+        let mut actual_book: Book = Book::new(1, "Book".to_string(),
+                                              "BOOK".to_string());
+        let from = |account: AccountId, id: OrderId, order_type: OrderType,
+                    price: f64, quantity: Quantity| {
+            let mut order: Order = build_order(id, order_type, price,
+                                               quantity);
+            order.get_owner_mut().set_id(account);
+            order
+        };
+
+        /* account 1 sells 8 over two orders, both of which fill */
+        actual_book.submit(from(1, 1, OrderType::Ask, 12.00, 4))?;
+        actual_book.submit(from(1, 2, OrderType::Ask, 13.00, 4))?;
+        actual_book.submit(from(3, 3, OrderType::Bid, 13.00, 8))?;
+
+        /* and buys 2 back with a third */
+        actual_book.submit(from(4, 4, OrderType::Ask, 12.00, 2))?;
+        actual_book.submit(from(1, 5, OrderType::Bid, 12.00, 2))?;
+
+        /* leaving the mid at 12.00 */
+        actual_book.submit(from(6, 6, OrderType::Bid, 11.00, 1))?;
+        actual_book.submit(from(7, 7, OrderType::Ask, 13.00, 1))?;
+
+        assert!(actual_book.get_order(1).is_err());
+        assert!(actual_book.get_order(2).is_err());
+
+        let seller: &Account = actual_book.get_account(1).unwrap();
+
+        assert_eq!(seller.positions().len(), 1);
+        assert_eq!(seller.get_position("BOOK").map(|p| p.get_side()),
+                   Some(PositionSide::Short));
+        assert_eq!(seller.get_position("BOOK").map(|p| p.get_quantity()),
+                   Some(6));
+        assert_eq!(seller.get_position("BOOK").map(|p| p.get_average_price()),
+                   Some(12.50));
+        assert_eq!(seller.get_holding("BOOK".to_string()), Ok(994));
+        assert_eq!(seller.pnl(&actual_book), Pnl::new(1.00, 3.00));
+        Ok(())
+    }
+
+    #[test]
+    fn test_buying_power() -> Result<(), BookError> {
+        use crate::margin::MarginModel;
+
+        let mut actual_book: Book = Book::builder(1, "BOOK".to_string())
+            .margin(MarginModel::Leverage(2.0))
+            .build()
+            .unwrap();
+        /* every order from `build_order` has a balance of 12000.00 */
+        let bid = |id: OrderId, price: f64, quantity: Quantity| {
+            let mut order: Order = build_order(id, OrderType::Bid, price,
+                                               quantity);
+            order.get_owner_mut().set_id(1);
+            order
+        };
+
+        actual_book.submit(bid(1, 10.00, 1500))?;
+        assert_eq!(actual_book.get_reserved(1), 15000.00);
+        assert!(matches!(
+            actual_book.submit(bid(2, 10.00, 1000)),
+            Err(BookError::InsufficientBuyingPower { required, available })
+                if required == 10000.00 && available == 9000.00));
+        assert!(matches!(actual_book.get_events().last().map(Event::get_kind),
+                         Some(EventKind::BuyingPowerReject { order: 2, .. })));
+
+        /* filling spends it, out of the account's balance */
+        actual_book.submit(build_order(3, OrderType::Ask, 10.00, 600))?;
+        assert_eq!(actual_book.get_reserved(1), 9000.00);
+        assert_eq!(actual_book.get_account(1).map(Account::get_balance),
+                   Some(6000.00));
+
+        /* cancelling frees what is left */
+        actual_book.cancel(1)?;
+        assert_eq!(actual_book.get_reserved(1), 0.00);
+        assert!(matches!(
+            actual_book.submit(bid(4, 10.00, 1201)),
+            Err(BookError::InsufficientBuyingPower { .. })));
+        actual_book.submit(bid(5, 10.00, 1200))?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_buying_power_across_orders() -> Result<(), BookError> {
+        use crate::margin::MarginModel;
+
+        let mut actual_book: Book = Book::builder(1, "BOOK".to_string())
+            .margin(MarginModel::Cash)
+            .build()
+            .unwrap();
+        let bid = |id: OrderId, price: f64, quantity: Quantity| {
+            let mut order: Order = build_order(id, OrderType::Bid, price,
+                                               quantity);
+            order.get_owner_mut().set_id(1);
+            order
+        };
+
+        /* 10000.00 of the account's 12000.00 goes on the first, which
+         * fills straight away, so holds nothing back */
+        actual_book.submit(build_order(2, OrderType::Ask, 10.00, 1000))?;
+        actual_book.submit(bid(3, 10.00, 1000))?;
+        assert_eq!(actual_book.get_reserved(1), 0.00);
+
+        /* the second carries a copy of the account from before that */
+        assert!(matches!(
+            actual_book.submit(bid(4, 10.00, 500)),
+            Err(BookError::InsufficientBuyingPower { required, available })
+                if required == 5000.00 && available == 2000.00));
+        actual_book.submit(bid(5, 10.00, 200))?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_short_selling() -> Result<(), BookError> {
+        use crate::position::PositionSide;
+
+        let mut actual_book: Book = Book::new(1, "Book".to_string(),
+                                              "BOOK".to_string());
+        let mut short_seller: Order = build_order(1, OrderType::Ask, 12.00,
+                                                  1500);
+        short_seller.get_owner_mut().allow_short("BOOK".to_string(),
+                                                 Some(400));
+
+        actual_book.submit(short_seller)?;
+        actual_book.submit(build_order(2, OrderType::Bid, 12.00, 1200))?;
+
+        let seller: &Account = actual_book.get_account(1).unwrap();
+
+        assert_eq!(seller.get_holding("BOOK".to_string()), Ok(0));
+        assert_eq!(seller.get_borrowed("BOOK"), 200);
+        assert_eq!(seller.get_position("BOOK").map(|p| p.get_side()),
+                   Some(PositionSide::Short));
+
+        /* the last 300 would take it 500 short, past its limit */
+        assert!(matches!(
+            actual_book.submit(build_order(3, OrderType::Bid, 12.00, 400)),
+            Err(BookError::Account(AccountError::BorrowLimitExceeded))));
+        Ok(())
+    }
+
+    #[test]
+    fn test_borrow_limit_across_orders() -> Result<(), BookError> {
+        let mut actual_book: Book = Book::new(1, "Book".to_string(),
+                                              "BOOK".to_string());
+        let from = |account: AccountId, id: OrderId, order_type: OrderType,
+                    price: f64, quantity: Quantity| {
+            let mut order: Order = build_order(id, order_type, price,
+                                               quantity);
+            order.get_owner_mut().set_id(account);
+            order
+        };
+        let mut short_seller: Account = from(1, 0, OrderType::Ask, 0.00, 1)
+            .get_owner();
+
+        short_seller.allow_short("BOOK".to_string(), Some(400));
+        actual_book.add_account(short_seller);
+        actual_book.submit(from(2, 1, OrderType::Bid, 12.00, 1700))?;
+
+        /* 1200 out of 1000 held leaves it 200 short */
+        actual_book.submit(from(1, 2, OrderType::Ask, 12.00, 1200))?;
+        assert_eq!(actual_book.get_account(1).map(|a| a.get_borrowed("BOOK")),
+                   Some(200));
+
+        /* another 300 would take it 500 short, past its limit */
+        assert!(matches!(
+            actual_book.submit(from(1, 3, OrderType::Ask, 12.00, 300)),
+            Err(BookError::Account(AccountError::BorrowLimitExceeded))));
+        assert_eq!(actual_book.get_order(1)?.get_quantity(), 500);
+
+        actual_book.submit(from(1, 4, OrderType::Ask, 12.00, 200))?;
+        assert_eq!(actual_book.get_account(1).map(|a| a.get_borrowed("BOOK")),
+                   Some(400));
+        Ok(())
+    }
+
+    #[test]
+    fn test_cancel_after_partial_fill() -> Result<(), BookError> {
+        let mut actual_book: Book = Book::new(1, "Book".to_string(),
+                                              "BOOK".to_string());
+
+        actual_book.submit(build_order(1, OrderType::Ask, 12.00, 30))?;
+        actual_book.submit(build_order(2, OrderType::Ask, 12.00, 5))?;
+        actual_book.submit(build_order(3, OrderType::Bid, 12.00, 12))?;
+        actual_book.submit(build_order(4, OrderType::Bid, 11.00, 10))?;
+        actual_book.submit(build_order(5, OrderType::Ask, 11.00, 4))?;
+
+        let actual_ask: CancelResult = actual_book.cancel(1)?;
+
+        assert_eq!((actual_ask.filled_quantity,
+                    actual_ask.remaining_cancelled), (12, 18));
+        assert_eq!(actual_ask.order.get_quantity(), 18);
+        assert!(actual_book.get_events().iter().any(|event| matches!(
+            event.get_kind(),
+            EventKind::Cancel { order: 1, quantity: 18, .. })));
+        assert!(matches!(actual_book.cancel(1),
+                         Err(BookError::OrderNotFound)));
+
+        /* only what was left comes off the depth and buying power */
+        let actual_bid: CancelResult = actual_book.cancel(4)?;
+
+        assert_eq!((actual_bid.filled_quantity,
+                    actual_bid.remaining_cancelled), (4, 6));
+        assert_eq!(actual_book.levels(),
+                   Levels::new(vec![], vec![(12.00, 5)]));
+        assert_eq!(actual_book.get_reserved(4), 0.0);
+        assert!(actual_book.check_integrity().is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_cancel_remaining_after_partial_fill() -> Result<(), BookError> {
+        let mut actual_book: Book = Book::new(1, "Book".to_string(),
+                                              "BOOK".to_string());
+
+        actual_book.submit(build_order(1, OrderType::Ask, 12.00, 30))?;
+        actual_book.submit(build_order(2, OrderType::Bid, 12.00, 12))?;
+
+        assert_eq!(actual_book.cancel_remaining(1)?, (12, 18));
+        assert!(actual_book.levels().get_asks().is_empty());
+        assert!(matches!(actual_book.cancel_remaining(1),
+                         Err(BookError::OrderNotFound)));
+        Ok(())
+    }
+
+    #[test]
+    fn test_hidden_orders() -> Result<(), BookError> {
+        let mut actual_book: Book = Book::new(1, "Book".to_string(),
+                                              "BOOK".to_string());
+        let mut hidden: Order = build_order(1, OrderType::Ask, 12.00, 10);
+        hidden.set_hidden();
+        actual_book.submit(hidden)?;
+        actual_book.submit(build_order(2, OrderType::Ask, 12.00, 5))?;
+
+        let mut hidden: Order = build_order(3, OrderType::Ask, 11.90, 3);
+        hidden.set_hidden();
+        actual_book.submit(hidden)?;
+
+        /* only the displayed order shows, and it is ahead of the hidden
+         * one that arrived first */
+        assert_eq!(actual_book.levels(),
+                   Levels::new(vec![], vec![(12.00, 5)]));
+        assert_eq!(actual_book.top_of_book().get_ask(),
+                   Some((12.00, 5)));
+        assert!(!actual_book.to_string().contains("11.9"));
+        assert_eq!(actual_book.queue_position(1), Some(1));
+
+        actual_book.submit(build_order(4, OrderType::Bid, 12.00, 10))?;
+
+        let actual_fills: Vec<(OrderId, Quantity)> = actual_book.get_trades()
+            .iter()
+            .map(|trade| (trade.get_resting(), trade.get_quantity()))
+            .collect();
+
+        assert_eq!(actual_fills, vec![(3, 3), (2, 5), (1, 2)]);
+        assert!(actual_book.levels().is_empty());
+        assert_eq!(actual_book.get_order(1)?.get_quantity(), 8);
+        assert!(actual_book.check_integrity().is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_all_or_none_keeps_priority() -> Result<(), BookError> {
+        let mut actual_book: Book = Book::new(1, "Book".to_string(),
+                                              "BOOK".to_string());
+        let mut all_or_none: Order = build_order(1, OrderType::Ask, 12.00, 20);
+        all_or_none.set_all_or_none();
+
+        actual_book.submit(all_or_none)?;
+        actual_book.submit(build_order(2, OrderType::Ask, 12.00, 20))?;
+        actual_book.submit(build_order(3, OrderType::Bid, 12.00, 15))?;
+
+        let mut expected_asks: BTreeMap<PriceKey, VecDeque<OrderId>> =
+            BTreeMap::new();
+        expected_asks.insert(OrderedFloat::from(12.00),
+            VecDeque::from(vec![1, 2]));
+
+        assert_eq!(*actual_book.asks.levels(), expected_asks);
+        assert_eq!(actual_book.get_order(1)?.get_quantity(), 20);
+        assert_eq!(actual_book.get_order(2)?.get_quantity(), 5);
+
+        actual_book.submit(build_order(4, OrderType::Bid, 12.00, 25))?;
+
+        assert!(actual_book.asks.is_empty());
+        assert!(actual_book.bids.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_min_quantity_skips_to_next_level() -> Result<(), BookError> {
+        let mut actual_book: Book = Book::new(1, "Book".to_string(),
+                                              "BOOK".to_string());
+        let mut minimum: Order = build_order(1, OrderType::Bid, 12.00, 50);
+        minimum.set_min_quantity(Some(30));
+
+        actual_book.submit(minimum)?;
+        actual_book.submit(build_order(2, OrderType::Bid, 11.00, 50))?;
+        actual_book.submit(build_order(3, OrderType::Ask, 11.00, 10))?;
+
+        assert_eq!(actual_book.get_order(1)?.get_quantity(), 50);
+        assert_eq!(actual_book.get_order(2)?.get_quantity(), 40);
+        assert_eq!(actual_book.get_ltp()?, 11.00);
+        Ok(())
+    }
+
+    #[test]
+    fn test_configured_book_rejects_invalid_orders() -> Result<(), BookError> {
+        let mut actual_book: Book = Book::builder(1, "BOOK".to_string())
+            .tick_size(0.05)
+            .lot_size(10)
+            .price_band(10.00, 14.00)
+            .build()
+            .unwrap();
+
+        assert!(matches!(
+            actual_book.submit(build_order(1, OrderType::Bid, 12.01, 10)),
+            Err(BookError::InvalidPrice)));
+        assert!(matches!(
+            actual_book.submit(build_order(2, OrderType::Bid, 14.05, 10)),
+            Err(BookError::InvalidPrice)));
+        assert!(matches!(
+            actual_book.submit(build_order(3, OrderType::Bid, 12.05, 15)),
+            Err(BookError::InvalidQuantity)));
+
+        actual_book.submit(build_order(4, OrderType::Bid, 12.05, 20))?;
+
+        assert_eq!(actual_book.levels().get_bids(), &[(12.05, 20)]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_event_capacity() -> Result<(), BookError> {
+        let mut actual_book: Book = Book::builder(1, "BOOK".to_string())
+            .event_capacity(4)
+            .build()
+            .unwrap();
+
+        for id in 1..=3 {
+            actual_book.submit(build_order(id, OrderType::Bid, 12.00, 10))?;
+        }
+
+        let actual_orders: Vec<OrderId> = actual_book.get_events().iter()
+            .filter_map(|event| match event.get_kind() {
+                EventKind::Post { order, .. } => Some(*order),
+                _ => None
+            })
+            .collect();
+
+        assert_eq!(actual_orders, vec![2, 3]);
+        Ok(())
+    }
+
+    #[derive(Debug)]
+    struct FailingSink;
+
+    impl EventSink for FailingSink {
+        fn write(&mut self, _event: &Event) -> Result<(), SinkError> {
+            Err(SinkError::Encoding("unwritable".to_string()))
+        }
+
+        fn flush(&mut self) -> Result<(), SinkError> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_event_age_retention() -> Result<(), BookError> {
+        use chrono::{Duration, TimeZone};
+        use crate::clock::ManualClock;
+        use crate::sink::Retention;
+
+        let clock: ManualClock =
+            ManualClock::new(Utc.with_ymd_and_hms(2024, 1, 2, 9, 30, 0)
+                             .unwrap());
+        let mut actual_book: Book<PriceTime, MemorySink, ManualClock> =
+            Book::builder(1, "BOOK".to_string())
+                .event_retention(Retention::Age(
+                    std::time::Duration::from_secs(60)))
+                .clock(clock.clone())
+                .build()
+                .unwrap();
+
+        for id in 1..=5 {
+            actual_book.submit(build_order(id, OrderType::Bid, 12.00, 10))?;
+            clock.advance(Duration::seconds(30));
+        }
+
+        let actual_orders: Vec<OrderId> = actual_book.get_events().iter()
+            .filter_map(|event| match event.get_kind() {
+                EventKind::Post { order, .. } => Some(*order),
+                _ => None
+            })
+            .collect();
+
+        assert_eq!(actual_orders, vec![3, 4, 5]);
+        assert!(matches!(actual_book.events_since(0),
+                         Err(BookError::EventsEvicted)));
+        Ok(())
+    }
+
+    #[test]
+    fn test_expiry() -> Result<(), BookError> {
+        use chrono::{Duration, TimeZone};
+        use crate::clock::ManualClock;
+
+        let open: DateTime<Utc> =
+            Utc.with_ymd_and_hms(2026, 12, 18, 9, 30, 0).unwrap();
+        let clock: ManualClock = ManualClock::new(open);
+        let mut actual_book: Book<PriceTime, MemorySink, ManualClock> =
+            Book::builder(1, "BOOK".to_string())
+                .expiry(open + Duration::hours(1))
+                .clock(clock.clone())
+                .build()
+                .unwrap();
+
+        actual_book.submit(build_order(1, OrderType::Bid, 11.50, 10))?;
+        actual_book.submit(build_order(2, OrderType::Ask, 12.00, 10))?;
+        assert!(!actual_book.is_expired());
+
+        clock.advance(Duration::hours(1));
+        assert!(actual_book.is_expired());
+        assert!(matches!(
+            actual_book.submit(build_order(3, OrderType::Bid, 11.75, 10)),
+            Err(BookError::Expired)));
+
+        let cancelled: Vec<OrderId> = actual_book.expire(Some(11.80))?
+            .iter()
+            .map(|result| result.order.get_id())
+            .collect();
+        let kinds: Vec<&EventKind> = actual_book.get_events().iter()
+            .map(Event::get_kind)
+            .collect();
+
+        assert_eq!(cancelled, vec![1, 2]);
+        assert!(actual_book.iter_all().next().is_none());
+        assert_eq!(actual_book.status(1), Some(OrderStatus::Expired));
+        assert_eq!(actual_book.get_state(), SessionState::Closed);
+        assert!(kinds.contains(&&EventKind::Settlement { price: 11.80 }));
+        assert!(matches!(kinds.last(), Some(EventKind::StateChange {
+            current: SessionState::Closed, ..
+        })));
+        Ok(())
+    }
+
+    #[test]
+    fn test_bust_trade() -> Result<(), BookError> {
+        use crate::position::Position;
+
+        let mut actual_book: Book = Book::new(1, "Book".to_string(),
+                                              "BOOK".to_string());
+
+        actual_book.submit(build_order(1, OrderType::Ask, 12.00, 10))?;
+        actual_book.submit(build_order(2, OrderType::Ask, 12.00, 5))?;
+        actual_book.submit(build_order(3, OrderType::Bid, 12.00, 12))?;
+
+        let trades: Vec<TradeId> = actual_book.get_trades().iter()
+            .map(Trade::get_id)
+            .collect();
+
+        assert_eq!(actual_book.status(1), Some(OrderStatus::Filled));
+
+        /* the filled order comes back, behind the one still resting */
+        let busted: Trade = actual_book.bust_trade(trades[0])?;
+
+        assert_eq!(busted.get_resting(), 1);
+        assert_eq!(actual_book.get_trades().len(), 1);
+        assert_eq!(actual_book.iter_level(OrderType::Ask, 12.00)
+                       .map(Order::get_id)
+                       .collect::<Vec<OrderId>>(),
+                   vec![2, 1]);
+        assert_eq!(actual_book.get_account(1).map(Account::get_balance),
+                   Some(12000.00));
+        assert_eq!(actual_book.status(1), Some(OrderStatus::New));
+
+        /* the aggressor is put right too */
+        let buyer: &Account = actual_book.get_account(3).unwrap();
+
+        assert_eq!(buyer.get_balance(), 12000.00 - 24.00);
+        assert_eq!(buyer.get_holding("BOOK".to_string()).ok(), Some(1002));
+        assert_eq!(buyer.get_position("BOOK").map(Position::get_quantity),
+                   Some(2));
+        assert!(actual_book.get_events().iter().any(|event| {
+            event.get_kind() == &EventKind::TradeBust(busted)
+        }));
+        assert!(matches!(actual_book.bust_trade(trades[0]),
+                         Err(BookError::TradeNotFound)));
+
+        /* one still resting gets its quantity back in place */
+        actual_book.bust_trade(trades[1])?;
+        assert_eq!(actual_book.get_order(2)?.get_quantity(), 5);
+        assert_eq!(actual_book.status(2), Some(OrderStatus::New));
+        assert!(actual_book.get_trades().is_empty());
+        assert_eq!(actual_book.levels(), Levels::new(vec![],
+                                                     vec![(12.00, 15)]));
+        assert_eq!(actual_book.get_account(3).map(Account::get_balance),
+                   Some(12000.00));
+        assert_eq!(actual_book.get_account(3).unwrap()
+                       .get_position("BOOK").map(Position::get_quantity),
+                   Some(0));
+
+        /* with no trade left standing, there is no last traded price */
+        assert!(matches!(actual_book.get_ltp(), Err(BookError::NoTrades)));
+        assert_eq!(actual_book.reference_price(), None);
+
+        let mut actual_book: Book = Book::builder(1, "BOOK".to_string())
+            .reference_price(11.00)
+            .build()
+            .unwrap();
+
+        actual_book.submit(build_order(1, OrderType::Ask, 12.00, 10))?;
+        actual_book.submit(build_order(2, OrderType::Bid, 12.00, 5))?;
+        assert_eq!(actual_book.reference_price(), Some(12.00));
+
+        let trade: TradeId = actual_book.get_trades()[0].get_id();
+
+        actual_book.bust_trade(trade)?;
+        assert!(matches!(actual_book.get_ltp(), Err(BookError::NoTrades)));
+        assert_eq!(actual_book.reference_price(), Some(11.00));
+        Ok(())
+    }
+
+    #[test]
+    fn test_bust_trade_restores_aggressor() -> Result<(), BookError> {
+        use crate::position::Position;
+
+        let mut actual_book: Book = Book::new(1, "Book".to_string(),
+                                              "BOOK".to_string());
+
+        actual_book.submit(build_order(1, OrderType::Ask, 12.00, 5))?;
+        actual_book.submit(build_order(2, OrderType::Bid, 12.00, 8))?;
+
+        let trade: TradeId = actual_book.get_trades()[0].get_id();
+
+        actual_book.bust_trade(trade)?;
+
+        /* the aggressor gets back what it bought, in place, and the ask it
+         * bought from is not put back across it */
+        assert_eq!(actual_book.get_order(2)?.get_quantity(), 8);
+        assert_eq!(actual_book.status(1), Some(OrderStatus::Cancelled));
+        assert_eq!(actual_book.levels(), Levels::new(vec![(12.00, 8)],
+                                                     vec![]));
+
+        for id in [1, 2] {
+            let account: &Account = actual_book.get_account(id).unwrap();
+
+            assert_eq!(account.get_balance(), 12000.00);
+            assert_eq!(account.get_holding("BOOK".to_string()).ok(), Some(1000));
+            assert_eq!(account.get_position("BOOK")
+                           .map(Position::get_quantity), Some(0));
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_bust_trade_keeps_peg() -> Result<(), BookError> {
+        let mut actual_book: Book = Book::new(1, "Book".to_string(),
+                                              "BOOK".to_string());
+        let peg: Peg = Peg::new(PegReference::BestAsk).offset(-0.50);
+
+        actual_book.submit(build_order(1, OrderType::Bid, 11.00, 10))?;
+        actual_book.submit(build_order(2, OrderType::Ask, 13.00, 10))?;
+        actual_book.submit_pegged(build_order(3, OrderType::Ask, 0.00, 5),
+                                  peg)?;
+        actual_book.submit(build_order(4, OrderType::Bid, 12.50, 5))?;
+        assert_eq!(actual_book.status(3), Some(OrderStatus::Filled));
+
+        let trade: TradeId = actual_book.get_trades()[0].get_id();
+
+        actual_book.bust_trade(trade)?;
+        assert_eq!(actual_book.get_order(3)?.get_price(), 12.50);
+        assert_eq!(actual_book.get_peg(3), Some(&peg));
+
+        /* and it goes on following its reference */
+        actual_book.submit(build_order(5, OrderType::Ask, 12.80, 10))?;
+        assert_eq!(actual_book.get_order(3)?.get_price(), 12.30);
+        Ok(())
+    }
+
+    #[test]
+    fn test_from_levels() -> crate::Result<()> {
+        use crate::id::MonotonicIdGenerator;
+        use crate::metadata::{AssetClass, Metadata};
+
+        let metadata: Metadata = Metadata::new("BOOK".to_string(),
+                                               AssetClass::Equity,
+                                               "USD".to_string())
+            .tick_size(0.5);
+        let levels: Levels = Levels::new(vec![(11.50, 10), (11.00, 7)],
+                                         vec![(12.00, 5)]);
+        let owner: Account = build_order(1, OrderType::Bid, 0.00, 1)
+            .get_owner_ref()
+            .clone();
+        let mut ids: MonotonicIdGenerator = MonotonicIdGenerator::new();
+        let mut actual_book: Book = Book::from_levels(1, &metadata, &levels,
+                                                      &owner, 2, &mut ids)?;
+
+        assert_eq!(actual_book.levels(), levels);
+        assert_eq!(actual_book.iter_level(OrderType::Bid, 11.00)
+                       .map(Order::get_quantity)
+                       .collect::<Vec<Quantity>>(),
+                   vec![4, 3]);
+        assert!(actual_book.get_events().is_empty());
+
+        /* and trading carries on from there */
+        actual_book.submit(build_order(ids.next_id(), OrderType::Ask, 11.50,
+                                       12))?;
+        assert_eq!(actual_book.levels(),
+                   Levels::new(vec![(11.00, 7)], vec![(11.50, 2),
+                                                      (12.00, 5)]));
+
+        let crossed: Levels = Levels::new(vec![(12.00, 1)], vec![(12.00, 1)]);
+        let off_tick: Levels = Levels::new(vec![(11.25, 1)], vec![]);
+
+        assert!(matches!(Book::from_levels(1, &metadata, &crossed, &owner, 1,
+                                           &mut ids),
+                         Err(crate::Error::Book(BookError::InvalidSnapshot))));
+        assert!(matches!(Book::from_levels(1, &metadata, &off_tick, &owner, 1,
+                                           &mut ids),
+                         Err(crate::Error::Book(BookError::InvalidPrice))));
+        Ok(())
+    }
+
+    #[test]
+    fn test_notional() -> crate::Result<()> {
+        use crate::metadata::{AssetClass, Metadata};
+        use crate::notional::Notional;
+
+        let metadata: Metadata = Metadata::new("BOOK".to_string(),
+                                               AssetClass::Equity,
+                                               "USD".to_string());
+        let mut actual_book: Book = Book::builder(1, "BOOK".to_string())
+            .lot_size(5)
+            .build()?;
+        let budget: Notional = metadata.notional(100.00);
+        let owner: Account = build_order(1, OrderType::Bid, 0.00, 1)
+            .get_owner();
+        let order: Order = Order::from_notional(1, owner, "BOOK".to_string(),
+                                                OrderType::Bid, 12.00,
+                                                &budget);
+
+        assert_eq!(order.get_quantity(), 8);
+        assert_eq!(actual_book.quantity_for(12.00, budget.get_amount()), 5);
+
+        actual_book.submit(build_order(1, OrderType::Bid, 12.00, 5))?;
+        actual_book.submit(build_order(2, OrderType::Bid, 11.00, 10))?;
+        actual_book.submit(build_order(3, OrderType::Ask, 13.00, 5))?;
+
+        assert_eq!(actual_book.notional(OrderType::Bid), 170.00);
+        assert_eq!(actual_book.notional(OrderType::Ask), 65.00);
+        Ok(())
+    }
+
+    #[test]
+    fn test_sweep_limit() -> crate::Result<()> {
+        let build = |max_levels: Option<usize>,
+                     max_quantity: Option<Quantity>,
+                     remainder: SweepRemainder| -> crate::Result<Book> {
+            let mut book: Book = Book::builder(1, "BOOK".to_string())
+                .sweep_limit(SweepLimit {max_levels, max_quantity, remainder})
+                .build()?;
+
+            for (id, price) in [(1, 12.00), (2, 12.50), (3, 13.00)] {
+                book.submit(build_order(id, OrderType::Ask, price, 5))?;
+            }
+
+            Ok(book)
+        };
+
+        /* two levels at most, and the rest is cancelled */
+        let mut actual_book: Book = build(Some(2), None,
+                                          SweepRemainder::Cancel)?;
+
+        actual_book.submit(build_order(4, OrderType::Bid, 13.00, 20))?;
+        assert_eq!(actual_book.levels(), Levels::new(vec![],
+                                                     vec![(13.00, 5)]));
+        assert_eq!(actual_book.status(4), Some(OrderStatus::Cancelled));
+        assert!(actual_book.get_events().iter()
+                    .any(|event| event.get_reason() ==
+                                 Some(&Reason::SweepLimit)));
+
+        /* ten at most, and the rest rests where it stopped */
+        let mut actual_book: Book = build(None, Some(10),
+                                          SweepRemainder::Post)?;
+
+        actual_book.submit(build_order(4, OrderType::Bid, 13.00, 20))?;
+        assert_eq!(actual_book.levels(), Levels::new(vec![(12.50, 10)],
+                                                     vec![(13.00, 5)]));
+        assert_eq!(actual_book.get_order(4)?.get_filled_quantity(), 10);
+
+        /* at its last fill's price, not its limit, which would cross */
+        assert_eq!(actual_book.get_order(4)?.get_price(), 12.50);
+        assert!(actual_book.get_events().iter()
+                    .any(|event| matches!(event.get_kind(),
+                                          EventKind::Post { order: 4, price,
+                                                            .. }
+                                          if *price == 12.50)));
+
+        /* unless it stopped part way through a level */
+        let mut actual_book: Book = build(None, Some(7),
+                                          SweepRemainder::Post)?;
+
+        actual_book.submit(build_order(4, OrderType::Bid, 13.00, 20))?;
+        assert_eq!(actual_book.levels(), Levels::new(vec![],
+                                                     vec![(12.50, 3),
+                                                          (13.00, 5)]));
+        assert_eq!(actual_book.status(4), Some(OrderStatus::Cancelled));
+        Ok(())
+    }
+
+    #[test]
+    fn test_compaction() -> Result<(), BookError> {
+        use crate::replica::BookReplica;
+
+        let mut actual_book: Book = Book::builder(1, "BOOK".to_string())
+            .compact_every(5)
+            .build()
+            .unwrap();
+
+        for id in 1..=6 {
+            actual_book.submit(build_order(id, OrderType::Bid,
+                                           10.00 + id as f64, 10))?;
+        }
+
+        actual_book.cancel(2)?;
+
+        let snapshot: &BookSnapshot = actual_book.get_compacted().unwrap();
+        let mut replica: BookReplica = BookReplica::from_snapshot(snapshot);
+
+        assert!(snapshot.last_seq >= 10);
+        assert!(actual_book.get_events().len() < 5);
+        assert!(matches!(actual_book.events_since(0),
+                         Err(BookError::EventsEvicted)));
+
+        replica.apply_delta(actual_book.events_since(snapshot.last_seq)?)
+            .unwrap();
+        assert_eq!(replica.levels(), actual_book.levels());
+        Ok(())
+    }
+
+    #[test]
+    fn test_compact() -> Result<(), BookError> {
+        let mut actual_book: Book = Book::new(1, "Book".to_string(),
+                                              "BOOK".to_string());
+
+        for id in 1..=1000 {
+            actual_book.submit(build_order(id, OrderType::Bid,
+                                           10.00 + (id % 50) as f64, 10))?;
+        }
+
+        for id in 1..=990 {
+            actual_book.cancel(id)?;
+        }
+
+        let levels: Levels = actual_book.levels();
+        let (before, after) = actual_book.compact(true)?;
+
+        assert_eq!(before.orders, 10);
+        assert_eq!(after.orders, 10);
+        assert_eq!(after.levels, 10);
+        assert!(before.pooled > 0);
+        assert_eq!(after.pooled, 0);
+        assert!(after.order_capacity < before.order_capacity);
+        assert!(after.bytes < before.bytes);
+        assert_eq!(after, actual_book.memory_usage());
+        assert_eq!(actual_book.levels(), levels);
+        assert!(actual_book.get_events().is_empty());
+        assert_eq!(actual_book.get_compacted()
+                       .map(|snapshot| snapshot.last_seq),
+                   Some(actual_book.last_seq()));
+        Ok(())
+    }
+
+    #[test]
+    fn test_audit_trail() -> Result<(), BookError> {
+        let mut actual_book: Book = Book::new(1, "Book".to_string(),
+                                              "BOOK".to_string());
+        let actor: Actor = Actor::account(1)
+            .session("FIX-1".to_string())
+            .source("gateway".to_string());
+
+        actual_book.set_actor(Some(actor.clone()));
+        actual_book.submit(build_order(1, OrderType::Bid, 12.00, 10))?;
+        actual_book.set_actor(None);
+        actual_book.cancel_with_reason(1, Reason::Expired)?;
+
+        let events: &[Event] = actual_book.get_events();
+        let cancel: Option<&Event> = events.iter()
+            .find(|event| matches!(event.get_kind(),
+                                    EventKind::Cancel { .. }));
+
+        assert_eq!(events.first().and_then(Event::get_actor), Some(&actor));
+        assert_eq!(cancel.and_then(Event::get_actor), None);
+        assert_eq!(cancel.and_then(Event::get_reason), Some(&Reason::Expired));
+        Ok(())
+    }
+
+    #[test]
+    fn test_market_stats() -> Result<(), BookError> {
+        let mut actual_book: Book = Book::new(1, "Book".to_string(),
+                                              "BOOK".to_string());
+
+        actual_book.submit(build_order(1, OrderType::Ask, 12.00, 10))?;
+        actual_book.submit(build_order(2, OrderType::Bid, 11.00, 10))?;
+        actual_book.submit(build_order(3, OrderType::Bid, 12.00, 4))?;
+
+        let stats: &MarketStats = actual_book.market_stats();
+
+        assert_eq!(stats.get_spread(), Some(1.00));
+        assert_eq!(stats.get_mid(), Some(11.50));
+        assert_eq!(stats.get_last_price(), Some(12.00));
+        assert_eq!(stats.windows()[0].trades, 1);
+        assert_eq!(stats.windows()[0].volume, 4);
+        Ok(())
+    }
+
+    #[test]
+    fn test_capacity_limits() -> Result<(), BookError> {
+        let build = |policy: CapacityPolicy| -> Result<Book, BookError> {
+            let mut book: Book = Book::new(1, "Book".to_string(),
+                                           "BOOK".to_string());
+
+            book.config.capacity = Some(CapacityLimit {
+                max_orders_per_level: Some(2),
+                max_orders_per_side: Some(5),
+                max_levels_per_side: Some(3),
+                policy
+            });
+            book.submit(build_order(1, OrderType::Ask, 12.00, 10))?;
+            book.submit(build_order(2, OrderType::Ask, 12.00, 10))?;
+            book.submit(build_order(3, OrderType::Ask, 13.00, 10))?;
+            book.submit(build_order(4, OrderType::Ask, 14.00, 10))?;
+            Ok(book)
+        };
+        let ids = |book: &Book| -> Vec<OrderId> {
+            book.iter_asks().map(Order::get_id).collect()
+        };
+
+        /* a full level and a level too many are refused */
+        let mut rejecting: Book = build(CapacityPolicy::RejectNew)?;
+
+        rejecting.submit(build_order(5, OrderType::Ask, 12.00, 10))?;
+        rejecting.submit(build_order(6, OrderType::Ask, 15.00, 10))?;
+        assert_eq!(ids(&rejecting), vec![1, 2, 3, 4]);
+        assert_eq!(rejecting.status(5), Some(OrderStatus::Cancelled));
+
+        /* but an order that only trades is fine */
+        rejecting.submit(build_order(7, OrderType::Bid, 12.00, 5))?;
+        assert_eq!(rejecting.get_trades().len(), 1);
+
+        /* or the oldest make way, at the level and then on the side */
+        let mut evicting: Book = build(CapacityPolicy::RejectOldest)?;
+
+        evicting.submit(build_order(5, OrderType::Ask, 12.00, 10))?;
+        assert_eq!(ids(&evicting), vec![2, 5, 3, 4]);
+        evicting.submit(build_order(6, OrderType::Ask, 13.00, 10))?;
+        evicting.submit(build_order(7, OrderType::Ask, 14.00, 10))?;
+        assert_eq!(ids(&evicting), vec![5, 3, 6, 4, 7]);
+        assert_eq!(evicting.status(2), Some(OrderStatus::Cancelled));
+
+        /* a level too many costs the one furthest from the top, unless the
+         * new one would be further still */
+        evicting.cancel(3)?;
+        evicting.submit(build_order(8, OrderType::Ask, 15.00, 10))?;
+        assert_eq!(ids(&evicting), vec![5, 6, 4, 7]);
+        evicting.submit(build_order(9, OrderType::Ask, 11.00, 10))?;
+        assert_eq!(ids(&evicting), vec![9, 5, 6]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_seed_history() -> Result<(), BookError> {
+        let mut actual_book: Book = Book::new(1, "Book".to_string(),
+                                              "BOOK".to_string());
+        let now: DateTime<Utc> = Utc::now();
+        let history: Vec<Trade> = vec![
+            Trade::new(now - chrono::Duration::seconds(30), 11.00, 5, 2, 1,
+                       OrderType::Bid),
+            Trade::new(now - chrono::Duration::seconds(10), 11.50, 3, 4, 3,
+                       OrderType::Ask)
+        ];
+
+        actual_book.submit(build_order(1, OrderType::Ask, 12.00, 10))?;
+        actual_book.seed_history(&history);
+
+        assert_eq!(actual_book.get_ltp()?, 11.50);
+        assert_eq!(actual_book.market_stats().windows()[0].volume, 8);
+        assert!(actual_book.market_stats().candles().next().is_some());
+        assert_eq!(actual_book.mark_price().map(|mark| mark.updated),
+                   Some(history.last().map(Trade::get_timestamp)));
+
+        /* the resting book and the book's own tape are as they were */
+        assert!(actual_book.get_trades().is_empty());
+        assert_eq!(actual_book.levels().get_asks(), &[(12.00, 10)]);
+
+        /* and once the book has traded, history is ignored */
+        actual_book.submit(build_order(2, OrderType::Bid, 12.00, 4))?;
+        actual_book.seed_history(&history);
+        assert_eq!(actual_book.get_ltp()?, 12.00);
+        Ok(())
+    }
+
+    #[test]
+    fn test_pegged_orders() -> Result<(), BookError> {
+        let mut actual_book: Book = Book::new(1, "Book".to_string(),
+                                              "BOOK".to_string());
+
+        assert!(matches!(actual_book.submit_pegged(
+                             build_order(3, OrderType::Bid, 0.00, 5),
+                             Peg::new(PegReference::BestBid)),
+                         Err(BookError::NoPegReference)));
+
+        actual_book.submit(build_order(1, OrderType::Bid, 11.00, 10))?;
+        actual_book.submit(build_order(2, OrderType::Ask, 13.00, 10))?;
+        actual_book.submit_pegged(build_order(3, OrderType::Bid, 0.00, 5),
+                                  Peg::new(PegReference::BestBid)
+                                      .offset(0.50)
+                                      .limit(11.90))?;
+        actual_book.submit_pegged(build_order(4, OrderType::Ask, 0.00, 5),
+                                  Peg::new(PegReference::Mid))?;
+
+        assert_eq!(actual_book.get_order(3)?.get_price(), 11.50);
+        assert_eq!(actual_book.get_order(4)?.get_price(), 12.00);
+
+        /* the bid peg is capped, and the ask follows the unpegged mid */
+        actual_book.submit(build_order(5, OrderType::Bid, 11.80, 10))?;
+
+        assert_eq!(actual_book.get_order(3)?.get_price(), 11.90);
+        assert_eq!(actual_book.get_order(4)?.get_price(), 12.40);
+        assert!(matches!(actual_book.get_events().last().map(Event::get_kind),
+                         Some(EventKind::TopOfBook { .. })));
+
+        let reprices: usize = actual_book.get_events()
+            .iter()
+            .filter(|event| matches!(event.get_kind(),
+                                     EventKind::Reprice { order: 3,
+                                                          previous, .. }
+                                     if *previous == 11.50))
+            .count();
+
+        assert_eq!(reprices, 1);
+        assert_eq!(actual_book.order_history(3).iter()
+                       .map(|amendment| (amendment.kind, amendment.price))
+                       .collect::<Vec<(AmendmentKind, f64)>>(),
+                   vec![(AmendmentKind::Reprice, 11.90)]);
+
+        /* pegs survive a snapshot */
+        let restored: Book = Book::from_snapshot(actual_book.snapshot())?;
+
+        assert_eq!(restored.get_peg(4), actual_book.get_peg(4));
+
+        /* and go once the order does */
+        actual_book.cancel(5)?;
+        actual_book.cancel(3)?;
+
+        assert_eq!(actual_book.get_order(4)?.get_price(), 12.00);
+        assert_eq!(actual_book.get_peg(3), None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_order_status() -> Result<(), BookError> {
+        let mut actual_book: Book = Book::new(1, "Book".to_string(),
+                                              "BOOK".to_string());
+
+        actual_book.submit(build_order(1, OrderType::Ask, 12.00, 10))?;
+        assert_eq!(actual_book.status(1), Some(OrderStatus::New));
+
+        actual_book.submit(build_order(2, OrderType::Bid, 12.00, 4))?;
+        assert_eq!(actual_book.status(1), Some(OrderStatus::PartiallyFilled));
+        assert_eq!(actual_book.status(2), Some(OrderStatus::Filled));
+        assert_eq!(actual_book.open_quantity(1), Some(6));
+        assert_eq!(actual_book.open_quantity(2), Some(0));
+
+        actual_book.cancel(1)?;
+        actual_book.submit(build_order(3, OrderType::Bid, 11.00, 5))?;
+        actual_book.cancel_with_reason(3, Reason::Expired)?;
+        assert!(actual_book
+                .submit(build_order(4, OrderType::Bid, f64::NAN, 5))
+                .is_err());
+
+        assert_eq!(actual_book.status(1), Some(OrderStatus::Cancelled));
+        assert_eq!(actual_book.status(3), Some(OrderStatus::Expired));
+        assert_eq!(actual_book.status(4), Some(OrderStatus::Rejected));
+        assert_eq!(actual_book.status(5), None);
+        assert_eq!(actual_book.open_quantity(5), None);
+
+        /* the order refused outright never made an event */
+        let transitions: Vec<(OrderId, Option<OrderStatus>, OrderStatus)> =
+            actual_book.get_events()
+            .iter()
+            .flat_map(|event| event.get_transitions().iter())
+            .map(|change| (change.order, change.previous, change.current))
+            .collect();
+
+        assert_eq!(transitions, vec![
+            (1, None, OrderStatus::New),
+            (1, Some(OrderStatus::New), OrderStatus::PartiallyFilled),
+            (2, None, OrderStatus::Filled),
+            (1, Some(OrderStatus::PartiallyFilled), OrderStatus::Cancelled),
+            (3, None, OrderStatus::New),
+            (3, Some(OrderStatus::New), OrderStatus::Expired)
+        ]);
+        Ok(())
+    }
+
+    /* loses bid 1's queue entry and reservation, and queues an order that
+     * doesn't exist among the asks */
+    fn corrupt(book: &mut Book) {
+        book.bids.clear();
+        book.reserved.clear();
+
+        if let Some((_, queue)) = book.asks.queues_mut().next() {
+            queue.push_back(9);
+        }
+    }
+
+    #[test]
+    fn test_integrity_repair() -> crate::Result<()> {
+        let mut actual_book: Book = BookBuilder::new(1, "BOOK".to_string())
+            .integrity(IntegrityMode::Repair)
+            .build()?;
+
+        actual_book.submit(build_order(1, OrderType::Bid, 11.00, 10))?;
+        actual_book.submit(build_order(2, OrderType::Ask, 12.00, 10))?;
+        assert!(actual_book.check_integrity().is_empty());
+
+        corrupt(&mut actual_book);
+
+        let expected_violations: Vec<Violation> = vec![
+            Violation::Depth {
+                side: OrderType::Bid,
+                price: 11.00,
+                cached: 0,
+                recomputed: 10
+            },
+            Violation::UnknownOrder {
+                side: OrderType::Ask,
+                price: 12.00,
+                id: 9
+            },
+            Violation::Reserved {
+                account: 1,
+                cached: 0.0,
+                recomputed: 110.0
+            }
+        ];
+
+        assert_eq!(actual_book.check_integrity(), expected_violations);
+
+        /* the next operation finds and repairs it */
+        actual_book.submit(build_order(3, OrderType::Bid, 10.00, 5))?;
+
+        assert_eq!(actual_book.get_violations(), &expected_violations[..]);
+        assert!(actual_book.check_integrity().is_empty());
+        assert_eq!(actual_book.levels().get_bids(), &[(11.00, 10),
+                                                      (10.00, 5)]);
+        assert_eq!(actual_book.get_reserved(1), 110.0);
+        Ok(())
+    }
+
+    #[test]
+    #[should_panic(expected = "book BOOK is inconsistent")]
+    fn test_integrity_strict() {
+        let mut actual_book: Book = Book::new(1, "Book".to_string(),
+                                              "BOOK".to_string());
+
+        actual_book.config.integrity = Some(IntegrityMode::Strict);
+        let _ = actual_book.submit(build_order(1, OrderType::Bid, 11.00, 10));
+        let _ = actual_book.submit(build_order(2, OrderType::Ask, 12.00, 10));
+        corrupt(&mut actual_book);
+        let _ = actual_book.cancel(2);
+    }
+
+    #[test]
+    fn test_sink_error_does_not_undo_submission() {
+        let mut actual_book: Book<PriceTime, FailingSink> =
+            Book::with_sink(1, "Book".to_string(), "BOOK".to_string(),
+                            PriceTime, FailingSink);
+
+        let result: Result<(), BookError> =
+            actual_book.submit(build_order(1, OrderType::Bid, 12.00, 10));
+
+        assert!(matches!(result, Err(BookError::Sink(_))));
+        assert_eq!(actual_book.levels().get_bids(), &[(12.00, 10)]);
+        assert_eq!(actual_book.last_seq(), 2);
+    }
+
+    #[test]
+    fn test_top_of_book_events_only_on_change() -> Result<(), BookError> {
+        let mut actual_book: Book = Book::new(1, "Book".to_string(),
+                                              "BOOK".to_string());
+
+        actual_book.submit(build_order(1, OrderType::Bid, 12.00, 10))?;
+        actual_book.submit(build_order(2, OrderType::Bid, 11.00, 10))?;
+        actual_book.submit(build_order(3, OrderType::Ask, 13.00, 10))?;
+
+        let actual_changes: Vec<(TopOfBook, TopOfBook)> =
+            actual_book.get_events().iter()
+            .filter_map(|event| match event.get_kind() {
+                EventKind::TopOfBook { previous, current } =>
+                    Some((*previous, *current)),
+                _ => None
+            })
+            .collect();
+        let expected_changes: Vec<(TopOfBook, TopOfBook)> = vec![
+            (TopOfBook::default(), TopOfBook::new(Some((12.00, 10)), None)),
+            (TopOfBook::new(Some((12.00, 10)), None),
+             TopOfBook::new(Some((12.00, 10)), Some((13.00, 10)))),
+        ];
+
+        assert_eq!(actual_changes, expected_changes);
+        assert_eq!(actual_book.top_of_book(),
+                   TopOfBook::new(Some((12.00, 10)), Some((13.00, 10))));
+        Ok(())
+    }
+
+    #[test]
+    fn test_duplicate_order_id_rejected() -> Result<(), BookError> {
+        let mut actual_book: Book = Book::new(1, "Book".to_string(),
+                                              "BOOK".to_string());
+
+        actual_book.submit(build_order(1, OrderType::Bid, 12.00, 10))?;
+        actual_book.submit(build_order(2, OrderType::Ask, 13.00, 10))?;
+        actual_book.submit(build_order(3, OrderType::Ask, 12.00, 10))?;
+
+        /* resting and already filled IDs alike */
+        assert!(matches!(
+            actual_book.submit(build_order(2, OrderType::Bid, 11.00, 10)),
+            Err(BookError::DuplicateOrderId)));
+        assert!(matches!(
+            actual_book.submit(build_order(1, OrderType::Bid, 11.00, 10)),
+            Err(BookError::DuplicateOrderId)));
+        assert_eq!(actual_book.levels().get_asks(), &[(13.00, 10)]);
+        assert!(actual_book.levels().get_bids().is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_circuit_breaker() -> Result<(), BookError> {
+        let mut actual_book: Book = Book::builder(1, "BOOK".to_string())
+            .circuit_breaker(0.10, BandReference::Fixed(10.00))
+            .build()
+            .unwrap();
+
+        assert!(matches!(
+            actual_book.submit(build_order(1, OrderType::Ask, 11.50, 10)),
+            Err(BookError::InvalidPrice)));
+
+        actual_book.submit(build_order(2, OrderType::Ask, 10.50, 10))?;
+        actual_book.submit(build_order(3, OrderType::Bid, 10.50, 5))?;
+
+        /* a fixed band stays where it is however far a sweep goes */
+        actual_book.submit(build_order(4, OrderType::Ask, 9.50, 5))?;
+        actual_book.submit(build_order(5, OrderType::Ask, 10.90, 5))?;
+        actual_book.submit(build_order(6, OrderType::Bid, 10.90, 15))?;
+        assert!(!actual_book.is_halted());
+        assert_eq!(actual_book.get_ltp()?, 10.90);
+        assert_eq!(actual_book.status(6), Some(OrderStatus::Filled));
+
+        /* a sweep through a thin book gaps too far from the last trade */
+        let mut actual_book: Book = Book::builder(1, "BOOK".to_string())
+            .circuit_breaker(0.05, BandReference::LastTrade)
+            .build()
+            .unwrap();
+
+        actual_book.submit(build_order(1, OrderType::Ask, 10.00, 10))?;
+        actual_book.submit(build_order(2, OrderType::Bid, 10.00, 10))?;
+        actual_book.submit(build_order(3, OrderType::Ask, 9.60, 5))?;
+        actual_book.submit(build_order(4, OrderType::Ask, 10.45, 10))?;
+
+        /* it traded, so it went in, but what's left of it is cancelled */
+        actual_book.submit(build_order(5, OrderType::Bid, 10.45, 20))?;
+        assert!(actual_book.is_halted());
+        assert_eq!(actual_book.status(5), Some(OrderStatus::Cancelled));
+        assert!(matches!(actual_book.get_order(5),
+                         Err(BookError::OrderNotFound)));
+        assert!(actual_book.get_events().iter().any(|event|
+            event.get_reason() == Some(&Reason::CircuitBreaker) &&
+            *event.get_kind() == EventKind::Cancel {
+                order: 5,
+                order_type: OrderType::Bid,
+                price: 10.45,
+                quantity: 15,
+                hidden: false
+            }));
+        assert!(matches!(
+            actual_book.submit(build_order(6, OrderType::Bid, 9.60, 1)),
+            Err(BookError::MarketHalted)));
+        assert_eq!(actual_book.get_ltp()?, 9.60);
+        assert_eq!(actual_book.get_order(4)?.get_quantity(), 10);
+
+        actual_book.resume()?;
+
+        let actual_kinds: Vec<EventKind> = actual_book.get_events().iter()
+            .map(|event| event.get_kind().clone())
+            .filter(|kind| matches!(kind, EventKind::Halt { .. } |
+                                          EventKind::StateChange { .. }))
+            .collect();
+
+        assert_eq!(actual_kinds, vec![
+            EventKind::Halt {reference: 9.60, price: 10.45},
+            EventKind::StateChange {previous: SessionState::Continuous,
+                                    current: SessionState::Halted},
+            EventKind::StateChange {previous: SessionState::Halted,
+                                    current: SessionState::Continuous}
+        ]);
+        assert!(!actual_book.is_halted());
+        actual_book.submit(build_order(7, OrderType::Bid, 9.60, 1))?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_session_states() -> Result<(), BookError> {
+        let mut actual_book: Book = Book::builder(1, "BOOK".to_string())
+            .initial_state(SessionState::PreOpen)
+            .build()
+            .unwrap();
+
+        /* crossed interest rests until the auction uncrosses it */
+        actual_book.submit(build_order(1, OrderType::Bid, 12.00, 10))?;
+        actual_book.submit(build_order(2, OrderType::Ask, 11.00, 5))?;
+        actual_book.submit(build_order(3, OrderType::Ask, 11.50, 10))?;
+        actual_book.submit(build_order(4, OrderType::Bid, 11.50, 10))?;
+
+        assert!(actual_book.get_trades().is_empty());
+
+        actual_book.set_state(SessionState::OpeningAuction)?;
+        actual_book.set_state(SessionState::Continuous)?;
+
+        /* 15 can execute at 11.50, but only 10 at 11.00 or 12.00 */
+        let actual_trades: Vec<(f64, Quantity)> = actual_book.get_trades()
+            .iter()
+            .map(|trade| (trade.get_price(), trade.get_quantity()))
+            .collect();
+
+        assert_eq!(actual_trades, vec![(11.50, 5), (11.50, 5), (11.50, 5)]);
+        assert_eq!(actual_book.levels().get_bids(), &[(11.50, 5)]);
+        assert!(actual_book.levels().get_asks().is_empty());
+
+        actual_book.set_state(SessionState::Closed)?;
+
+        assert!(matches!(
+            actual_book.submit(build_order(5, OrderType::Bid, 11.00, 1)),
+            Err(BookError::MarketClosed)));
+        assert!(matches!(
+            actual_book.set_state(SessionState::Continuous),
+            Err(BookError::InvalidStateTransition(SessionState::Closed,
+                                                  SessionState::Continuous))));
+        actual_book.cancel(4)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_indicative_price() -> Result<(), BookError> {
+        let mut actual_book: Book = Book::builder(1, "BOOK".to_string())
+            .initial_state(SessionState::PreOpen)
+            .circuit_breaker(0.10, BandReference::LastTrade)
+            .build()
+            .unwrap();
+
+        actual_book.submit(build_order(1, OrderType::Bid, 12.00, 10))?;
+        actual_book.submit(build_order(2, OrderType::Ask, 11.00, 10))?;
+
+        /* 10 executes at either price; the lower wins without a reference */
+        assert_eq!(actual_book.reference_price(), None);
+        assert_eq!(actual_book.indicative_price(), Some(11.00));
+        assert_eq!(actual_book.indicative_volume(), 10);
+
+        actual_book.set_reference_price(Some(11.80));
+        assert_eq!(actual_book.indicative_price(), Some(12.00));
+
+        /* the band is centred on the reference until there is a trade */
+        assert!(matches!(
+            actual_book.submit(build_order(3, OrderType::Bid, 13.50, 10)),
+            Err(BookError::InvalidPrice)));
+
+        actual_book.set_state(SessionState::Continuous)?;
+        assert_eq!(actual_book.get_ltp()?, 12.00);
+        assert_eq!(actual_book.indicative_price(), None);
+        assert_eq!(actual_book.indicative_volume(), 0);
+
+        /* pegs follow the reference while there is nothing to peg to */
+        let mut pegged_book: Book = Book::builder(2, "BOOK".to_string())
+            .reference_price(11.80)
+            .build()
+            .unwrap();
+
+        pegged_book.submit_pegged(build_order(1, OrderType::Bid, 0.00, 5),
+                                  Peg::new(PegReference::Mid).offset(-0.30))?;
+        assert_eq!(pegged_book.get_order(1)?.get_price(), 11.50);
+        Ok(())
+    }
+
+    #[test]
+    fn test_iterators() -> Result<(), BookError> {
+        let mut actual_book: Book = Book::new(1, "Book".to_string(),
+                                              "BOOK".to_string());
+
+        actual_book.submit(build_order(1, OrderType::Bid, 11.00, 10))?;
+        actual_book.submit(build_order(2, OrderType::Bid, 12.00, 10))?;
+        actual_book.submit(build_order(3, OrderType::Bid, 11.00, 10))?;
+        actual_book.submit(build_order(4, OrderType::Ask, 14.00, 10))?;
+        actual_book.submit(build_order(5, OrderType::Ask, 13.00, 10))?;
+
+        let ids = |orders: &mut dyn Iterator<Item=&Order>| -> Vec<OrderId> {
+            orders.map(|order| order.get_id()).collect()
+        };
+
+        assert_eq!(ids(&mut actual_book.iter_bids()), vec![2, 1, 3]);
+        assert_eq!(ids(&mut actual_book.iter_asks()), vec![5, 4]);
+        assert_eq!(ids(&mut actual_book.iter_level(OrderType::Bid, 11.00)),
+                   vec![1, 3]);
+        assert_eq!(actual_book.iter_level(OrderType::Ask, 11.00).len(), 0);
+        assert_eq!(actual_book.iter_bids().len(), 3);
+        assert_eq!(actual_book.iter_all().len(), 5);
+
+        let mut bids: SideIter = actual_book.iter_bids();
+        bids.next();
+        assert_eq!(bids.len(), 2);
+        Ok(())
+    }
+
+    #[test]
+    fn test_queue_position() -> Result<(), BookError> {
+        let mut actual_book: Book = Book::new(1, "Book".to_string(),
+                                              "BOOK".to_string());
+
+        actual_book.submit(build_order(1, OrderType::Bid, 12.00, 10))?;
+        actual_book.submit(build_order(2, OrderType::Bid, 12.00, 20))?;
+        actual_book.submit(build_order(3, OrderType::Bid, 11.00, 5))?;
+        actual_book.submit(build_order(4, OrderType::Bid, 12.00, 30))?;
+        actual_book.submit(build_order(5, OrderType::Ask, 12.00, 4))?;
+
+        assert_eq!(actual_book.queue_position(1), Some(0));
+        assert_eq!(actual_book.queue_position(4), Some(2));
+        assert_eq!(actual_book.queue_position(3), Some(0));
+        assert_eq!(actual_book.queue_position(5), None);
+        assert_eq!(actual_book.orders_ahead(1), Some(0));
+        assert_eq!(actual_book.orders_ahead(4), Some(26));
+        assert_eq!(actual_book.orders_ahead(5), None);
+
+        let priorities: Vec<u64> = actual_book.iter_level(OrderType::Bid,
+                                                          12.00)
+            .map(Order::get_priority)
+            .collect();
+        assert!(priorities.windows(2).all(|pair| pair[0] < pair[1]));
+
+        /* priority survives a partial fill */
+        assert_eq!(actual_book.get_order(1)?.get_priority(), priorities[0]);
+
+        let queued: Vec<OrderRef> = actual_book.level_orders(OrderType::Bid,
+                                                             12.00);
+
+        assert_eq!(queued.iter().map(|order| (order.id, order.quantity))
+                       .collect::<Vec<(OrderId, Quantity)>>(),
+                   vec![(1, 6), (2, 20), (4, 30)]);
+        assert_eq!(queued.iter().map(|order| order.priority)
+                       .collect::<Vec<u64>>(), priorities);
+        assert!(actual_book.level_orders(OrderType::Ask, 12.00).is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_modify() -> Result<(), BookError> {
+        let mut actual_book: Book = Book::new(1, "Book".to_string(),
+                                              "BOOK".to_string());
+
+        actual_book.submit(build_order(1, OrderType::Bid, 12.00, 10))?;
+        actual_book.submit(build_order(2, OrderType::Bid, 12.00, 20))?;
+        actual_book.submit(build_order(3, OrderType::Ask, 13.00, 15))?;
+
+        /* reducing keeps priority */
+        actual_book.modify(1, 12.00, 5)?;
+        assert_eq!(actual_book.queue_position(1), Some(0));
+        assert_eq!(actual_book.get_order(1)?.get_original_quantity(), 5);
+
+        /* increasing loses it */
+        actual_book.modify(1, 12.00, 8)?;
+        assert_eq!(actual_book.queue_position(1), Some(1));
+
+        /* repricing through the other side matches */
+        actual_book.modify(2, 13.00, 20)?;
+        assert!(actual_book.get_order(3).is_err());
+        assert_eq!(actual_book.get_order(2)?.get_quantity(), 5);
+        assert_eq!(actual_book.get_order(2)?.get_filled_quantity(), 15);
+        assert_eq!(actual_book.levels().get_bids(),
+                   &[(13.00, 5), (12.00, 8)]);
+
+        assert!(matches!(actual_book.modify(3, 13.00, 5),
+                         Err(BookError::OrderNotFound)));
+        assert!(matches!(actual_book.modify(1, 12.00, 0),
+                         Err(BookError::InvalidQuantity)));
+        assert!(matches!(actual_book.modify(1, f64::NAN, 8),
+                         Err(BookError::InvalidPrice)));
+        assert_eq!(BookError::OrderNotFound.to_string(),
+                   "no such resting order");
+        Ok(())
+    }
+
+    #[test]
+    fn test_quote() -> Result<(), BookError> {
+        let mut actual_book: Book = Book::new(1, "Book".to_string(),
+                                              "BOOK".to_string());
+
+        assert_eq!(actual_book.quote(OrderType::Bid, 10), None);
+
+        actual_book.submit(build_order(1, OrderType::Ask, 10.00, 10))?;
+        actual_book.submit(build_order(2, OrderType::Ask, 11.00, 10))?;
+        actual_book.submit(build_order(3, OrderType::Ask, 12.00, 10))?;
+        actual_book.submit(build_order(4, OrderType::Bid, 9.00, 10))?;
+
+        let actual_quote: QuoteResult =
+            actual_book.quote(OrderType::Bid, 25).unwrap();
+
+        assert_eq!(actual_quote, QuoteResult::new(25, 10.80, 12.00, 3));
+        assert!(actual_quote.is_complete(25));
+
+        let actual_quote: QuoteResult =
+            actual_book.quote(OrderType::Ask, 15).unwrap();
+
+        assert_eq!(actual_quote, QuoteResult::new(10, 9.00, 9.00, 1));
+        assert!(!actual_quote.is_complete(15));
+        assert_eq!(actual_book.levels().get_asks().len(), 3);
+        Ok(())
+    }
+
+    #[test]
+    fn test_snapshot_round_trip() -> Result<(), BookError> {
+        let mut expected_book: Book = Book::new(1, "Book".to_string(),
+                                                "BOOK".to_string());
+
+        expected_book.submit(build_order(1, OrderType::Bid, 11.00, 10))?;
+        expected_book.submit(build_order(2, OrderType::Bid, 12.00, 10))?;
+        expected_book.submit(build_order(3, OrderType::Bid, 11.00, 10))?;
+        expected_book.submit(build_order(4, OrderType::Ask, 13.00, 10))?;
+        expected_book.submit(build_order(5, OrderType::Ask, 12.00, 5))?;
+
+        let snapshot: BookSnapshot = expected_book.snapshot();
+        let bytes: Vec<u8> = crate::binary::encode(&snapshot).unwrap();
+        let mut actual_book: Book =
+            Book::from_snapshot(crate::binary::decode(&bytes).unwrap())?;
+
+        assert_eq!(actual_book, expected_book);
+        assert_eq!(actual_book.last_seq(), expected_book.last_seq());
+        assert_eq!(actual_book.top_of_book(), expected_book.top_of_book());
+
+        actual_book.submit(build_order(6, OrderType::Ask, 11.00, 20))?;
+
+        let actual_resting: Vec<OrderId> = actual_book.iter_bids()
+            .map(|order| order.get_id())
+            .collect();
+
+        assert_eq!(actual_resting, vec![3]);
+        assert_eq!(actual_book.get_events()[0].get_seq(),
+                   expected_book.last_seq() + 1);
+        assert!(matches!(actual_book.submit(
+                    build_order(3, OrderType::Ask, 11.00, 1)),
+                         Err(BookError::DuplicateOrderId)));
+        Ok(())
+    }
+}
+
+/* the tests above are written with integer sizes; these are for the
+ * `decimal-quantity` backend's fractional ones */
+#[cfg(all(test, feature = "decimal-quantity"))]
+mod decimal_quantity_tests {
+    use super::*;
+    use std::collections::HashMap;
+    use crate::account::*;
+
+    fn build_order(id: OrderId, order_type: OrderType, price: f64,
+                   quantity: Quantity) -> Order {
+        let mut holdings: HashMap<String, Quantity> = HashMap::new();
+        holdings.insert("BOOK".to_string(), Quantity::from(1000));
+
+        let owner: Account = Account::new(id, "Account".to_string(),
+                                          12000.00, holdings);
+
+        Order::new(id, owner, "BOOK".to_string(), order_type, price, quantity)
+    }
+
+    #[test]
+    fn test_fractional_fills() -> Result<(), BookError> {
+        let mut actual_book: Book = Book::new(1, "Book".to_string(),
+                                              "BOOK".to_string());
+
+        actual_book.submit(build_order(1, OrderType::Ask, 12.00,
+                                       Quantity::new(1, 2)))?;
+        actual_book.submit(build_order(2, OrderType::Ask, 12.00,
+                                       Quantity::new(5, 3)))?;
+        assert_eq!(actual_book.levels().get_asks(),
+                   &[(12.00, Quantity::new(15, 3))]);
+
+        actual_book.submit(build_order(3, OrderType::Bid, 12.00,
+                                       Quantity::new(15, 3)))?;
+
+        let actual_fills: Vec<Quantity> = actual_book.get_trades().iter()
+            .map(Trade::get_quantity)
+            .collect();
+
+        assert_eq!(actual_fills, vec![Quantity::new(1, 2),
+                                      Quantity::new(5, 3)]);
+        assert_eq!(actual_book.levels(), Levels::new(vec![], vec![]));
+
+        for id in 1..=3 {
+            assert_eq!(actual_book.status(id), Some(OrderStatus::Filled));
+        }
+
+        Ok(())
+    }
+}
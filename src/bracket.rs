@@ -0,0 +1,331 @@
+use serde::{Serialize, Deserialize};
+
+use crate::book::{Book, BookError};
+use crate::builder::RoundingMode;
+use crate::clock::Clock;
+use crate::event::{EventKind, Reason, Trade, TradeId};
+use crate::id::OrderIdGenerator;
+use crate::matching::MatchingPolicy;
+use crate::order::{Order, OrderId, OrderStatus, OrderType};
+use crate::price::PriceType;
+use crate::quantity::Quantity;
+use crate::sink::EventSink;
+
+/* Bracket orders: a parent order whose every fill is protected by a take
+ * profit and a stop loss on the other side, one cancelling the other. The
+ * take profit rests in the book like any other order; the stop is held
+ * off the book until the last trade reaches it, then goes in as a limit at
+ * its trigger price, so it never fills worse than that but may rest if the
+ * market gaps through it. */
+
+/* how far from each of the parent's fills its children go: the take
+ * profit `take_profit` better, the stop `stop_loss` worse */
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Bracket {
+    pub take_profit: f64,
+    pub stop_loss: f64
+}
+
+impl Bracket {
+    pub fn new(take_profit: f64, stop_loss: f64) -> Bracket {
+        Bracket {take_profit, stop_loss}
+    }
+}
+
+/* a stop waiting for the last trade to reach `trigger` */
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Stop {
+    pub id: OrderId,
+    pub side: OrderType,
+    pub trigger: f64,
+    pub quantity: Quantity
+}
+
+impl Stop {
+    /* sell stops fall, buy stops rise */
+    pub fn is_triggered(&self, last: f64) -> bool {
+        match self.side {
+            OrderType::Ask => last <= self.trigger,
+            OrderType::Bid => last >= self.trigger
+        }
+    }
+}
+
+/* one fill's children: whichever of them trades first cancels the other */
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct OcoPair {
+    pub take_profit: OrderId,
+    pub stop_loss: OrderId,
+    /* until it triggers or is cancelled */
+    pub stop: Option<Stop>
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct BracketOrder {
+    parent: Order,
+    bracket: Bracket,
+    /* trades up to this one have been looked at */
+    last_trade: Option<TradeId>,
+    pairs: Vec<OcoPair>
+}
+
+impl BracketOrder {
+    /* Submits `parent`, and arms children for whatever it fills straight
+     * away. Call `step` after anything else changes the book to arm
+     * children for later fills and to work the ones already armed. */
+    pub fn submit<M, S, C, P, G>(book: &mut Book<M, S, C, P>, parent: Order,
+                                 bracket: Bracket, ids: &mut G) ->
+        Result<BracketOrder, BookError>
+    where M: MatchingPolicy, S: EventSink, C: Clock, P: PriceType,
+          G: OrderIdGenerator {
+        let mut order: BracketOrder = BracketOrder {
+            parent: parent.clone(),
+            bracket,
+            last_trade: book.get_trades().iter().map(Trade::get_id).max(),
+            pairs: vec![]
+        };
+
+        book.submit(parent)?;
+        order.step(book, ids)?;
+        Ok(order)
+    }
+
+    pub fn get_parent(&self) -> OrderId {
+        self.parent.get_id()
+    }
+
+    pub fn get_bracket(&self) -> Bracket {
+        self.bracket
+    }
+
+    /* a pair for each of the parent's fills so far, oldest first */
+    pub fn get_pairs(&self) -> &[OcoPair] {
+        &self.pairs
+    }
+
+    /* stops still waiting to trigger */
+    pub fn armed(&self) -> impl Iterator<Item=&Stop> {
+        self.pairs.iter().filter_map(|pair| pair.stop.as_ref())
+    }
+
+    /* the parent is done and no stop is waiting */
+    pub fn is_done<M, S, C, P>(&self, book: &Book<M, S, C, P>) -> bool
+    where M: MatchingPolicy, S: EventSink, C: Clock, P: PriceType {
+        !book.status(self.get_parent()).is_some_and(|s| s.is_open()) &&
+            self.armed().next().is_none()
+    }
+
+    /* Arms children for the parent's new fills, cancels the stops whose
+     * take profits have traded or gone, and triggers the stops the last
+     * trade has reached, until there is nothing more to do. */
+    pub fn step<M, S, C, P, G>(&mut self, book: &mut Book<M, S, C, P>,
+                               ids: &mut G) -> Result<(), BookError>
+    where M: MatchingPolicy, S: EventSink, C: Clock, P: PriceType,
+          G: OrderIdGenerator {
+        while self.arm(book, ids)? | self.work(book)? {}
+
+        Ok(())
+    }
+
+    /* whether there were any new fills to arm children for */
+    fn arm<M, S, C, P, G>(&mut self, book: &mut Book<M, S, C, P>,
+                          ids: &mut G) -> Result<bool, BookError>
+    where M: MatchingPolicy, S: EventSink, C: Clock, P: PriceType,
+          G: OrderIdGenerator {
+        let parent: OrderId = self.get_parent();
+        let last_trade: Option<TradeId> = self.last_trade;
+        let new: Vec<Trade> = book.get_trades().iter()
+            .filter(|trade| Some(trade.get_id()) > last_trade)
+            .copied()
+            .collect();
+
+        self.last_trade = new.iter().map(Trade::get_id).max()
+            .or(self.last_trade);
+
+        let fills: Vec<&Trade> = new.iter()
+            .filter(|trade| trade.get_resting() == parent ||
+                            trade.get_aggressor() == parent)
+            .collect();
+
+        for fill in fills.iter() {
+            self.arm_fill(book, fill, ids)?;
+        }
+
+        Ok(!fills.is_empty())
+    }
+
+    fn arm_fill<M, S, C, P, G>(&mut self, book: &mut Book<M, S, C, P>,
+                               fill: &Trade, ids: &mut G) ->
+        Result<(), BookError>
+    where M: MatchingPolicy, S: EventSink, C: Clock, P: PriceType,
+          G: OrderIdGenerator {
+        let (side, direction): (OrderType, f64) =
+            match self.parent.get_order_type() {
+                OrderType::Bid => (OrderType::Ask, 1.0),
+                OrderType::Ask => (OrderType::Bid, -1.0)
+            };
+        let price = |offset: f64| book.get_config()
+            .round_to_tick(fill.get_price() + direction * offset,
+                           RoundingMode::Nearest);
+        let (take_profit, trigger): (f64, f64) =
+            (price(self.bracket.take_profit), price(-self.bracket.stop_loss));
+        let stop: Stop = Stop {
+            id: ids.next_id(),
+            side,
+            trigger,
+            quantity: fill.get_quantity()
+        };
+        let pair: OcoPair = OcoPair {
+            take_profit: ids.next_id(),
+            stop_loss: stop.id,
+            stop: Some(stop)
+        };
+
+        book.record(EventKind::BracketArmed {
+            parent: self.get_parent(),
+            take_profit: pair.take_profit,
+            stop_loss: pair.stop_loss,
+            order_type: side,
+            price: take_profit,
+            trigger,
+            quantity: stop.quantity
+        })?;
+        self.pairs.push(pair);
+        book.submit(self.child(pair.take_profit, side, take_profit,
+                               stop.quantity))
+    }
+
+    /* whether any stop was cancelled or triggered */
+    fn work<M, S, C, P>(&mut self, book: &mut Book<M, S, C, P>) ->
+        Result<bool, BookError>
+    where M: MatchingPolicy, S: EventSink, C: Clock, P: PriceType {
+        let last: Option<f64> = book.get_ltp().ok();
+        let mut worked: bool = false;
+
+        for index in 0..self.pairs.len() {
+            let (take_profit, stop): (OrderId, Stop) =
+                match self.pairs.get(index) {
+                    Some(OcoPair { take_profit, stop: Some(stop), .. }) =>
+                        (*take_profit, *stop),
+                    _ => continue
+                };
+
+            if book.status(take_profit) != Some(OrderStatus::New) {
+                book.record_with_reason(EventKind::StopCancelled {
+                    order: stop.id,
+                    order_type: stop.side,
+                    trigger: stop.trigger,
+                    quantity: stop.quantity
+                }, Reason::OneCancelsOther)?;
+            } else if last.is_some_and(|last| stop.is_triggered(last)) {
+                book.record(EventKind::StopTriggered {
+                    order: stop.id,
+                    order_type: stop.side,
+                    trigger: stop.trigger,
+                    quantity: stop.quantity
+                })?;
+                book.cancel_with_reason(take_profit,
+                                        Reason::OneCancelsOther)?;
+                book.submit(self.child(stop.id, stop.side, stop.trigger,
+                                       stop.quantity))?;
+            } else {
+                continue;
+            }
+
+            if let Some(pair) = self.pairs.get_mut(index) {
+                pair.stop = None;
+            }
+
+            worked = true;
+        }
+
+        Ok(worked)
+    }
+
+    fn child(&self, id: OrderId, side: OrderType, price: f64,
+             quantity: Quantity) -> Order {
+        Order::new(id, self.parent.get_owner(), self.parent.get_ticker(),
+                   side, price, quantity)
+    }
+}
+
+#[cfg(all(test, not(feature = "decimal-quantity")))]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use crate::account::Account;
+    use crate::event::Event;
+    use crate::id::MonotonicIdGenerator;
+
+    fn account(id: u128) -> Account {
+        let mut holdings: HashMap<String, Quantity> = HashMap::new();
+        holdings.insert("BOOK".to_string(), 1000);
+
+        Account::new(id, "Account".to_string(), 100000.00, holdings)
+    }
+
+    fn order(ids: &mut MonotonicIdGenerator, owner: u128, side: OrderType,
+             price: f64, quantity: Quantity) -> Order {
+        Order::new(ids.next_id(), account(owner), "BOOK".to_string(), side,
+                   price, quantity)
+    }
+
+    #[test]
+    fn test_bracket() -> Result<(), BookError> {
+        let mut book: Book = Book::new(1, "Book".to_string(),
+                                       "BOOK".to_string());
+        let mut ids: MonotonicIdGenerator = MonotonicIdGenerator::new();
+
+        book.submit(order(&mut ids, 1, OrderType::Ask, 10.00, 5))?;
+
+        let parent: Order = order(&mut ids, 2, OrderType::Bid, 10.00, 8);
+        let mut bracket: BracketOrder =
+            BracketOrder::submit(&mut book, parent, Bracket::new(1.00, 0.50),
+                                 &mut ids)?;
+
+        /* the parent's first fill is protected straight away */
+        let first: OcoPair = bracket.get_pairs()[0];
+
+        assert_eq!(book.get_order(first.take_profit)?.get_price(), 11.00);
+        assert_eq!(first.stop.map(|stop| (stop.trigger, stop.quantity)),
+                   Some((9.50, 5)));
+
+        /* the rest fills later, and the take profit trades */
+        book.submit(order(&mut ids, 3, OrderType::Ask, 10.00, 3))?;
+        bracket.step(&mut book, &mut ids)?;
+        book.submit(order(&mut ids, 4, OrderType::Bid, 11.00, 5))?;
+        bracket.step(&mut book, &mut ids)?;
+
+        let second: OcoPair = bracket.get_pairs()[1];
+
+        assert_eq!(bracket.get_pairs().len(), 2);
+        assert_eq!(bracket.get_pairs()[0].stop, None);
+        assert_eq!(bracket.armed().count(), 1);
+
+        /* then the market falls through the other stop */
+        book.submit(order(&mut ids, 5, OrderType::Bid, 9.50, 10))?;
+        book.submit(order(&mut ids, 6, OrderType::Ask, 9.50, 1))?;
+        bracket.step(&mut book, &mut ids)?;
+
+        assert!(bracket.is_done(&book));
+        assert_eq!(book.status(second.take_profit),
+                   Some(OrderStatus::Cancelled));
+        assert_eq!(book.status(second.stop_loss), Some(OrderStatus::Filled));
+
+        let kinds: Vec<&EventKind> = book.get_events().iter()
+            .map(Event::get_kind)
+            .collect();
+
+        assert_eq!(kinds.iter()
+                       .filter(|kind| matches!(kind,
+                                               EventKind::BracketArmed { .. }))
+                       .count(), 2);
+        assert!(kinds.iter().any(|kind| matches!(kind,
+            EventKind::StopCancelled { order, .. }
+                if *order == first.stop_loss)));
+        assert!(kinds.iter().any(|kind| matches!(kind,
+            EventKind::StopTriggered { order, .. }
+                if *order == second.stop_loss)));
+        Ok(())
+    }
+}
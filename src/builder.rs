@@ -0,0 +1,661 @@
+use std::marker::PhantomData;
+
+use chrono::{DateTime, Utc};
+use serde::{Serialize, Deserialize};
+
+use crate::analytics::MarkPolicy;
+use crate::book::*;
+use crate::clock::{Clock, SystemClock};
+use crate::integrity::IntegrityMode;
+use crate::margin::MarginModel;
+use crate::matching::*;
+use crate::price::{F64Price, PriceType};
+use crate::quantity::{Quantity, ZERO};
+use crate::session::SessionState;
+use crate::sink::{EventSink, MemorySink, Retention};
+
+const MAX_TICKER_LENGTH: usize = 16;
+
+#[derive(Debug, Clone, PartialEq, thiserror::Error)]
+pub enum BuildError {
+    #[error("invalid ticker {0:?}")]
+    InvalidTicker(String),
+    #[error("invalid tick size {0}")]
+    InvalidTickSize(f64),
+    #[error("invalid lot size {0}")]
+    InvalidLotSize(Quantity),
+    #[error("invalid price band from {0} to {1}")]
+    InvalidPriceBand(f64, f64),
+    #[error("price band from {0} to {1} is off the tick grid")]
+    PriceBandOffTick(f64, f64),
+    #[error("invalid circuit breaker")]
+    InvalidCircuitBreaker,
+    #[error("invalid event capacity")]
+    InvalidEventCapacity,
+    #[error("invalid margin model")]
+    InvalidMargin,
+    #[error("events cannot be compacted every zero events")]
+    InvalidCompaction,
+    #[error("invalid sweep limit")]
+    InvalidSweepLimit,
+    #[error("invalid capacity limit")]
+    InvalidCapacityLimit,
+}
+
+/* what a circuit breaker's band is centred on */
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum BandReference {
+    Fixed(f64),
+    /* no band applies until the book has traded, or has a reference
+     * price; see `Book::reference_price` */
+    LastTrade
+}
+
+/* Limit-up/limit-down protection: orders priced more than `width` (as a
+ * fraction, so 0.05 is 5%) either side of the reference are rejected, and a
+ * trade that would print that far from the last trade halts the book until
+ * it is resumed. */
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct CircuitBreaker {
+    pub width: f64,
+    pub reference: BandReference
+}
+
+impl CircuitBreaker {
+    pub fn new(width: f64, reference: BandReference) -> CircuitBreaker {
+        CircuitBreaker {width, reference}
+    }
+
+    /* the band's centre, given the last traded price or, before there has
+     * been a trade, the reference price; `None` if there isn't one */
+    pub fn centre(&self, last: Option<f64>) -> Option<f64> {
+        match self.reference {
+            BandReference::Fixed(price) => Some(price),
+            BandReference::LastTrade => last
+        }
+    }
+
+    /* the band around `reference`, as inclusive lower and upper bounds */
+    pub fn band(&self, reference: f64) -> (f64, f64) {
+        (reference * (1.0 - self.width), reference * (1.0 + self.width))
+    }
+}
+
+/* what becomes of what an order could not take for its `SweepLimit` */
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SweepRemainder {
+    Cancel,
+    /* rests at the last price it traded at, as at exchanges with
+     * protection points, unless that would leave the book crossed, in which
+     * case it is cancelled */
+    Post
+}
+
+/* Bounds how much a single incoming order may take, as exchanges do to
+ * protect against orders sweeping a thin book, and to bound how long
+ * matching one order can take. */
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct SweepLimit {
+    /* how many price levels it may trade at */
+    pub max_levels: Option<usize>,
+    /* how much it may take across them all */
+    pub max_quantity: Option<Quantity>,
+    pub remainder: SweepRemainder
+}
+
+/* what gives way when an order would rest beyond a `CapacityLimit` */
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CapacityPolicy {
+    /* what is left of the incoming order is cancelled instead of resting */
+    #[default]
+    RejectNew,
+    /* resting orders are cancelled to make room for it: the oldest at its
+     * level, or on its side, or, for want of a level, every order at the
+     * level furthest from the top of the book. If the incoming order's
+     * level would itself be the furthest, it is cancelled instead. */
+    RejectOldest
+}
+
+/* Bounds on what may rest in a book, so that a long-running simulation
+ * fed pathological flow uses a bounded amount of memory. Orders put back
+ * by busting a trade, or restored from a snapshot, are not limited. */
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct CapacityLimit {
+    pub max_orders_per_level: Option<usize>,
+    pub max_orders_per_side: Option<usize>,
+    pub max_levels_per_side: Option<usize>,
+    pub policy: CapacityPolicy
+}
+
+/* Market parameters a book enforces on every submission. Anything left as
+ * `None` is unconstrained, which is what `Book::new` gives you. */
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct BookConfig {
+    /* every price must be a whole multiple of this */
+    pub tick_size: Option<f64>,
+    /* every quantity must be a whole multiple of this */
+    pub lot_size: Option<Quantity>,
+    /* inclusive lower and upper bounds on acceptable prices */
+    pub price_band: Option<(f64, f64)>,
+    pub circuit_breaker: Option<CircuitBreaker>,
+    /* checked against every bid's owner */
+    #[serde(default)]
+    pub margin: Option<MarginModel>,
+    /* every this many events, the book offers its sink a snapshot to keep
+     * in place of the events before it; see `EventSink::compact` */
+    #[serde(default)]
+    pub compact_every: Option<u64>,
+    /* checks the book is consistent with itself after every operation,
+     * and what to do if it isn't; see `IntegrityMode` */
+    #[serde(default)]
+    pub integrity: Option<IntegrityMode>,
+    /* when the instrument expires, after which no more orders are
+     * accepted; see `Book::expire` */
+    #[serde(default)]
+    pub expiry: Option<DateTime<Utc>>,
+    /* stands in for the last trade until there has been one; see
+     * `Book::reference_price` */
+    #[serde(default)]
+    pub reference_price: Option<f64>,
+    #[serde(default)]
+    pub sweep_limit: Option<SweepLimit>,
+    /* see `Book::mark_price` */
+    #[serde(default)]
+    pub mark: MarkPolicy,
+    #[serde(default)]
+    pub capacity: Option<CapacityLimit>
+}
+
+/* tolerates the representation error of prices that are on tick but not
+ * exactly representable, like 0.1 */
+pub(crate) fn on_tick(price: f64, tick_size: f64) -> bool {
+    let ticks: f64 = price / tick_size;
+
+    (ticks - ticks.round()).abs() <= 1e-9 * ticks.abs().max(1.0)
+}
+
+impl BookConfig {
+    /* `price` on this book's tick grid, if it has one; see `round_to_tick` */
+    pub fn round_to_tick(&self, price: f64, mode: RoundingMode) -> f64 {
+        self.tick_size
+            .map_or(price, |tick_size| round_to_tick(price, tick_size, mode))
+    }
+
+    /* `quantity` in this book's whole lots, if it has them; see
+     * `round_to_lot` */
+    pub fn round_to_lot(&self, quantity: Quantity) -> Quantity {
+        self.lot_size
+            .map_or(quantity, |lot_size| round_to_lot(quantity, lot_size))
+    }
+}
+
+/* which way to move a price that is between ticks */
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RoundingMode {
+    Down,
+    Up,
+    /* halfway rounds away from zero */
+    Nearest
+}
+
+/* The multiple of `tick_size` that `mode` picks for `price`. A price that
+ * `on_tick` already accepts is only tidied up, never moved a whole tick. */
+pub fn round_to_tick(price: f64, tick_size: f64, mode: RoundingMode) ->
+    f64 {
+    let ticks: f64 = price / tick_size;
+    let ticks: f64 = if on_tick(price, tick_size) {
+        ticks.round()
+    } else {
+        match mode {
+            RoundingMode::Down => ticks.floor(),
+            RoundingMode::Up => ticks.ceil(),
+            RoundingMode::Nearest => ticks.round()
+        }
+    };
+    let per_unit: f64 = 1.0 / tick_size;
+
+    /* dividing by a whole number of ticks per unit gives e.g. 10.3 for 103
+     * ticks of 0.1, where multiplying gives 10.300000000000001 */
+    if (per_unit - per_unit.round()).abs() <= 1e-9 * per_unit {
+        ticks / per_unit.round()
+    } else {
+        ticks * tick_size
+    }
+}
+
+/* `quantity` rounded down to a whole number of lots */
+pub fn round_to_lot(quantity: Quantity, lot_size: Quantity) -> Quantity {
+    if lot_size == ZERO {
+        quantity
+    } else {
+        quantity - quantity % lot_size
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct BookBuilder<M: MatchingPolicy = PriceTime,
+                       S: EventSink = MemorySink,
+                       C: Clock = SystemClock,
+                       P: PriceType = F64Price> {
+    id: BookId,
+    name: Option<String>,
+    ticker: String,
+    policy: M,
+    sink: S,
+    clock: C,
+    event_retention: Option<Retention>,
+    initial_state: SessionState,
+    config: BookConfig,
+    prices: PhantomData<P>
+}
+
+impl BookBuilder {
+    pub fn new(id: BookId, ticker: String) -> BookBuilder {
+        BookBuilder {
+            id,
+            name: None,
+            ticker,
+            policy: PriceTime,
+            sink: MemorySink::new(),
+            clock: SystemClock,
+            event_retention: None,
+            initial_state: SessionState::default(),
+            config: BookConfig::default(),
+            prices: PhantomData
+        }
+    }
+}
+
+impl<M: MatchingPolicy, C: Clock, P: PriceType>
+    BookBuilder<M, MemorySink, C, P> {
+    /* how many of the most recent events the book keeps in memory */
+    pub fn event_capacity(self, capacity: usize) ->
+        BookBuilder<M, MemorySink, C, P> {
+        self.event_retention(Retention::Count(capacity))
+    }
+
+    /* which events the book keeps in memory */
+    pub fn event_retention(mut self, retention: Retention) ->
+        BookBuilder<M, MemorySink, C, P> {
+        self.event_retention = Some(retention);
+        self.sink = match retention {
+            Retention::Count(capacity) => MemorySink::with_capacity(capacity),
+            _ => MemorySink::with_retention(retention)
+        };
+        self
+    }
+}
+
+impl<M: MatchingPolicy, S: EventSink, C: Clock, P: PriceType>
+    BookBuilder<M, S, C, P> {
+    /* defaults to the ticker */
+    pub fn name(mut self, name: String) -> BookBuilder<M, S, C, P> {
+        self.name = Some(name);
+        self
+    }
+
+    pub fn tick_size(mut self, tick_size: f64) -> BookBuilder<M, S, C, P> {
+        self.config.tick_size = Some(tick_size);
+        self
+    }
+
+    pub fn lot_size(mut self, lot_size: Quantity) -> BookBuilder<M, S, C, P> {
+        self.config.lot_size = Some(lot_size);
+        self
+    }
+
+    pub fn price_band(mut self, lower: f64, upper: f64) ->
+        BookBuilder<M, S, C, P> {
+        self.config.price_band = Some((lower, upper));
+        self
+    }
+
+    pub fn circuit_breaker(mut self, width: f64, reference: BandReference) ->
+        BookBuilder<M, S, C, P> {
+        self.config.circuit_breaker =
+            Some(CircuitBreaker::new(width, reference));
+        self
+    }
+
+    pub fn margin(mut self, margin: MarginModel) -> BookBuilder<M, S, C, P> {
+        self.config.margin = Some(margin);
+        self
+    }
+
+    /* see `BookConfig::compact_every` */
+    pub fn compact_every(mut self, events: u64) -> BookBuilder<M, S, C, P> {
+        self.config.compact_every = Some(events);
+        self
+    }
+
+    /* see `BookConfig::expiry` */
+    pub fn expiry(mut self, expiry: DateTime<Utc>) -> BookBuilder<M, S, C, P> {
+        self.config.expiry = Some(expiry);
+        self
+    }
+
+    /* see `BookConfig::reference_price` */
+    pub fn reference_price(mut self, price: f64) -> BookBuilder<M, S, C, P> {
+        self.config.reference_price = Some(price);
+        self
+    }
+
+    /* see `MarkPolicy` */
+    pub fn mark_policy(mut self, policy: MarkPolicy) ->
+        BookBuilder<M, S, C, P> {
+        self.config.mark = policy;
+        self
+    }
+
+    /* see `SweepLimit` */
+    pub fn sweep_limit(mut self, limit: SweepLimit) ->
+        BookBuilder<M, S, C, P> {
+        self.config.sweep_limit = Some(limit);
+        self
+    }
+
+    /* see `CapacityLimit` */
+    pub fn capacity_limit(mut self, limit: CapacityLimit) ->
+        BookBuilder<M, S, C, P> {
+        self.config.capacity = Some(limit);
+        self
+    }
+
+    /* see `BookConfig::integrity` */
+    pub fn integrity(mut self, mode: IntegrityMode) ->
+        BookBuilder<M, S, C, P> {
+        self.config.integrity = Some(mode);
+        self
+    }
+
+    /* e.g. `PreOpen`, for a book that opens with an auction */
+    pub fn initial_state(mut self, state: SessionState) ->
+        BookBuilder<M, S, C, P> {
+        self.initial_state = state;
+        self
+    }
+
+    pub fn policy<N: MatchingPolicy>(self, policy: N) ->
+        BookBuilder<N, S, C, P> {
+        BookBuilder {
+            id: self.id,
+            name: self.name,
+            ticker: self.ticker,
+            policy,
+            sink: self.sink,
+            clock: self.clock,
+            event_retention: self.event_retention,
+            initial_state: self.initial_state,
+            config: self.config,
+            prices: PhantomData
+        }
+    }
+
+    pub fn sink<T: EventSink>(self, sink: T) -> BookBuilder<M, T, C, P> {
+        BookBuilder {
+            id: self.id,
+            name: self.name,
+            ticker: self.ticker,
+            policy: self.policy,
+            sink,
+            clock: self.clock,
+            event_retention: None,
+            initial_state: self.initial_state,
+            config: self.config,
+            prices: PhantomData
+        }
+    }
+
+    /* e.g. a `ManualClock`, for deterministic timestamps */
+    pub fn clock<K: Clock>(self, clock: K) -> BookBuilder<M, S, K, P> {
+        BookBuilder {
+            id: self.id,
+            name: self.name,
+            ticker: self.ticker,
+            policy: self.policy,
+            sink: self.sink,
+            clock,
+            event_retention: self.event_retention,
+            initial_state: self.initial_state,
+            config: self.config,
+            prices: PhantomData
+        }
+    }
+
+    /* e.g. `Cents`, to key levels on whole ticks rather than floats */
+    pub fn price_type<Q: PriceType>(self) -> BookBuilder<M, S, C, Q> {
+        BookBuilder {
+            id: self.id,
+            name: self.name,
+            ticker: self.ticker,
+            policy: self.policy,
+            sink: self.sink,
+            clock: self.clock,
+            event_retention: self.event_retention,
+            initial_state: self.initial_state,
+            config: self.config,
+            prices: PhantomData
+        }
+    }
+
+    fn validate(&self) -> Result<(), BuildError> {
+        let valid_ticker: bool = !self.ticker.is_empty() &&
+            self.ticker.len() <= MAX_TICKER_LENGTH &&
+            self.ticker.chars().all(|c| c.is_ascii_uppercase() ||
+                                        c.is_ascii_digit() ||
+                                        c == '.' || c == '-' || c == '/');
+
+        if !valid_ticker {
+            return Err(BuildError::InvalidTicker(self.ticker.clone()));
+        }
+
+        if let Some(tick_size) = self.config.tick_size {
+            if !tick_size.is_finite() || tick_size <= 0.0 {
+                return Err(BuildError::InvalidTickSize(tick_size));
+            }
+        }
+
+        if let Some(lot_size) = self.config.lot_size {
+            #[allow(clippy::absurd_extreme_comparisons)] /* decimals can be
+                                                           * negative */
+            if lot_size <= ZERO {
+                return Err(BuildError::InvalidLotSize(lot_size));
+            }
+        }
+
+        if let Some((lower, upper)) = self.config.price_band {
+            if !lower.is_finite() || !upper.is_finite() || lower < 0.0 ||
+                lower >= upper {
+                return Err(BuildError::InvalidPriceBand(lower, upper));
+            }
+
+            /* a band edge between ticks would be unreachable */
+            if let Some(tick_size) = self.config.tick_size {
+                if !on_tick(lower, tick_size) || !on_tick(upper, tick_size) {
+                    return Err(BuildError::PriceBandOffTick(lower, upper));
+                }
+            }
+        }
+
+        if let Some(breaker) = self.config.circuit_breaker {
+            let valid_reference: bool = match breaker.reference {
+                BandReference::Fixed(price) => price.is_finite() && price > 0.0,
+                BandReference::LastTrade => true
+            };
+
+            if !breaker.width.is_finite() || breaker.width <= 0.0 ||
+                !valid_reference {
+                return Err(BuildError::InvalidCircuitBreaker);
+            }
+        }
+
+        if let Some(margin) = self.config.margin {
+            if !margin.is_valid() {
+                return Err(BuildError::InvalidMargin);
+            }
+        }
+
+        if matches!(self.event_retention, Some(Retention::Count(0))) {
+            return Err(BuildError::InvalidEventCapacity);
+        }
+
+        if self.config.compact_every == Some(0) {
+            return Err(BuildError::InvalidCompaction);
+        }
+
+        /* a limit that lets nothing trade is a mistake */
+        if let Some(limit) = self.config.sweep_limit {
+            #[allow(clippy::absurd_extreme_comparisons)] /* decimals can be
+                                                           * negative */
+            if limit.max_levels == Some(0) ||
+                limit.max_quantity.is_some_and(|max| max <= ZERO) {
+                return Err(BuildError::InvalidSweepLimit);
+            }
+        }
+
+        /* as is one that lets nothing rest */
+        if let Some(limit) = self.config.capacity {
+            if [limit.max_orders_per_level, limit.max_orders_per_side,
+                limit.max_levels_per_side].contains(&Some(0)) {
+                return Err(BuildError::InvalidCapacityLimit);
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn build(self) -> Result<Book<M, S, C, P>, BuildError> {
+        self.validate()?;
+
+        let BookBuilder { id, name, ticker, policy, sink, clock,
+                          initial_state, config, .. } = self;
+        let name: String = name.unwrap_or_else(|| ticker.clone());
+
+        Ok(Book::with_config(id, name, ticker, policy, sink, clock, config,
+                             initial_state))
+    }
+}
+
+#[cfg(all(test, not(feature = "decimal-quantity")))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_build_defaults() -> Result<(), BuildError> {
+        let actual_book: Book = BookBuilder::new(1, "BOOK".to_string())
+            .build()?;
+        let expected_book: Book = Book::new(1, "BOOK".to_string(),
+                                            "BOOK".to_string());
+
+        assert_eq!(actual_book, expected_book);
+        assert_eq!(*actual_book.get_config(), BookConfig::default());
+        Ok(())
+    }
+
+    #[test]
+    fn test_build_configured() -> Result<(), BuildError> {
+        let actual_book: Book<ProRata> =
+            BookBuilder::new(1, "BOOK".to_string())
+            .name("Book".to_string())
+            .tick_size(0.05)
+            .lot_size(100)
+            .price_band(10.00, 20.00)
+            .margin(MarginModel::Cash)
+            .event_capacity(1000)
+            .policy(ProRata)
+            .build()?;
+        let expected_config: BookConfig = BookConfig {
+            tick_size: Some(0.05),
+            lot_size: Some(100),
+            price_band: Some((10.00, 20.00)),
+            circuit_breaker: None,
+            margin: Some(MarginModel::Cash),
+            compact_every: None,
+            integrity: None,
+            expiry: None,
+            reference_price: None,
+            sweep_limit: None,
+            mark: MarkPolicy::default(),
+            capacity: None
+        };
+
+        assert_eq!(actual_book.get_name(), "Book".to_string());
+        assert_eq!(*actual_book.get_config(), expected_config);
+        assert_eq!(actual_book.get_sink().get_capacity(), Some(1000));
+        Ok(())
+    }
+
+    #[test]
+    fn test_rounding() {
+        let config: BookConfig = BookConfig {
+            tick_size: Some(0.05),
+            lot_size: Some(100),
+            ..BookConfig::default()
+        };
+
+        assert_eq!(config.round_to_tick(10.0000001, RoundingMode::Nearest),
+                   10.00);
+        assert_eq!(config.round_to_tick(10.0000001, RoundingMode::Up),
+                   10.05);
+        assert_eq!(config.round_to_tick(10.1, RoundingMode::Up), 10.1);
+        assert_eq!(config.round_to_tick(10.01, RoundingMode::Up), 10.05);
+        assert_eq!(config.round_to_tick(10.04, RoundingMode::Down), 10.00);
+        assert_eq!(config.round_to_tick(10.03, RoundingMode::Nearest),
+                   10.05);
+        assert_eq!(round_to_tick(10.29, 0.1, RoundingMode::Up), 10.3);
+        assert_eq!(config.round_to_lot(250), 200);
+        assert_eq!(BookConfig::default().round_to_lot(250), 250);
+        assert_eq!(BookConfig::default()
+                       .round_to_tick(10.0000001, RoundingMode::Down),
+                   10.0000001);
+    }
+
+    #[test]
+    fn test_build_rejects_misconfiguration() {
+        let builder = || BookBuilder::new(1, "BOOK".to_string());
+
+        assert_eq!(BookBuilder::new(1, "".to_string()).build().err(),
+                   Some(BuildError::InvalidTicker("".to_string())));
+        assert_eq!(BookBuilder::new(1, "bo ok".to_string()).build().err(),
+                   Some(BuildError::InvalidTicker("bo ok".to_string())));
+        assert_eq!(builder().tick_size(0.0).build().err(),
+                   Some(BuildError::InvalidTickSize(0.0)));
+        assert_eq!(builder().lot_size(0).build().err(),
+                   Some(BuildError::InvalidLotSize(0)));
+        assert_eq!(builder().price_band(20.00, 10.00).build().err(),
+                   Some(BuildError::InvalidPriceBand(20.00, 10.00)));
+        assert_eq!(builder().tick_size(0.5).price_band(10.00, 20.25)
+                       .build().err(),
+                   Some(BuildError::PriceBandOffTick(10.00, 20.25)));
+        assert_eq!(builder().circuit_breaker(0.0, BandReference::LastTrade)
+                       .build().err(),
+                   Some(BuildError::InvalidCircuitBreaker));
+        assert_eq!(builder().circuit_breaker(0.1, BandReference::Fixed(-1.0))
+                       .build().err(),
+                   Some(BuildError::InvalidCircuitBreaker));
+        assert_eq!(builder().event_capacity(0).build().err(),
+                   Some(BuildError::InvalidEventCapacity));
+        assert_eq!(builder().compact_every(0).build().err(),
+                   Some(BuildError::InvalidCompaction));
+        assert_eq!(builder().margin(MarginModel::Leverage(0.0)).build().err(),
+                   Some(BuildError::InvalidMargin));
+        assert_eq!(builder().sweep_limit(SweepLimit {
+                       max_levels: Some(0),
+                       max_quantity: None,
+                       remainder: SweepRemainder::Cancel
+                   }).build().err(),
+                   Some(BuildError::InvalidSweepLimit));
+        assert_eq!(builder().capacity_limit(CapacityLimit {
+                       max_orders_per_side: Some(0),
+                       ..CapacityLimit::default()
+                   }).build().err(),
+                   Some(BuildError::InvalidCapacityLimit));
+    }
+
+    #[test]
+    fn test_on_tick() {
+        assert!(on_tick(0.3, 0.1));
+        assert!(on_tick(12.35, 0.05));
+        assert!(!on_tick(10.0000001, 0.01));
+    }
+}
@@ -0,0 +1,129 @@
+use crate::levels::{Level, Levels};
+use crate::quantity;
+
+/* Checksums over the top of a book, computed the way venues compute the
+ * ones they send alongside their depth feeds, so that a book mirrored from
+ * a feed can be checked against the venue's. Both schemes are a CRC32 of
+ * the top `depth` levels written out as text, prices and quantities to the
+ * number of decimal places the venue quotes them to. */
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ChecksumScheme {
+    /* Kraken: the asks, best first, then the bids, best first, each level
+     * as its price then its quantity with the decimal point and any leading
+     * zeros removed, all concatenated:
+     *
+     *     5541.3 @ 2.50000000  ->  "55413" + "250000000" */
+    Kraken,
+    /* OKX: bids and asks interleaved level by level, each level as
+     * `price:quantity`, all joined with `:`, carrying on with just one side
+     * once the other runs out. OKX compares the CRC as a signed 32-bit
+     * integer; reinterpret the result with `as i32` to do the same. */
+    Okx
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ChecksumFormat {
+    pub scheme: ChecksumScheme,
+    /* levels per side, e.g. 10 for Kraken and 25 for OKX */
+    pub depth: usize,
+    pub price_decimals: usize,
+    pub quantity_decimals: usize
+}
+
+impl ChecksumFormat {
+    pub fn kraken(price_decimals: usize, quantity_decimals: usize) ->
+        ChecksumFormat {
+        ChecksumFormat {
+            scheme: ChecksumScheme::Kraken,
+            depth: 10,
+            price_decimals,
+            quantity_decimals
+        }
+    }
+
+    pub fn okx(price_decimals: usize, quantity_decimals: usize) ->
+        ChecksumFormat {
+        ChecksumFormat {
+            scheme: ChecksumScheme::Okx,
+            depth: 25,
+            price_decimals,
+            quantity_decimals
+        }
+    }
+}
+
+fn price_text(price: f64, format: &ChecksumFormat) -> String {
+    format!("{:.*}", format.price_decimals, price)
+}
+
+fn quantity_text(level: &Level, format: &ChecksumFormat) -> String {
+    quantity::format_fixed(level.1, format.quantity_decimals)
+}
+
+/* Kraken's treatment of a number: no decimal point, no leading zeros */
+fn digits(text: &str) -> String {
+    let digits: String = text.chars().filter(|c| *c != '.').collect();
+
+    digits.trim_start_matches('0').to_string()
+}
+
+/* the text the checksum is computed over */
+pub fn canonical(levels: &Levels, format: &ChecksumFormat) -> String {
+    let bids: &[Level] = levels.get_bids();
+    let asks: &[Level] = levels.get_asks();
+    let depth: usize = format.depth;
+
+    match format.scheme {
+        ChecksumScheme::Kraken => asks.iter()
+            .take(depth)
+            .chain(bids.iter().take(depth))
+            .map(|level| digits(&price_text(level.0, format)) +
+                 &digits(&quantity_text(level, format)))
+            .collect(),
+        ChecksumScheme::Okx => (0..depth)
+            .flat_map(|i| [bids.get(i), asks.get(i)])
+            .flatten()
+            .map(|level| format!("{}:{}", price_text(level.0, format),
+                                 quantity_text(level, format)))
+            .collect::<Vec<String>>()
+            .join(":")
+    }
+}
+
+pub fn checksum(levels: &Levels, format: &ChecksumFormat) -> u32 {
+    crc32fast::hash(canonical(levels, format).as_bytes())
+}
+
+#[cfg(all(test, not(feature = "decimal-quantity")))]
+mod tests {
+    use super::*;
+
+    fn levels() -> Levels {
+        Levels::new(vec![(5541.20, 1), (5539.90, 3)],
+                    vec![(5541.30, 2), (5541.80, 12), (5542.70, 5)])
+    }
+
+    #[test]
+    fn test_kraken() {
+        let format: ChecksumFormat = ChecksumFormat::kraken(1, 8);
+
+        assert_eq!(canonical(&levels(), &format),
+                   "55413200000000\
+                    554181200000000\
+                    55427500000000\
+                    55412100000000\
+                    55399300000000");
+        assert_eq!(checksum(&levels(), &format), 2_140_111_981);
+    }
+
+    #[test]
+    fn test_okx() {
+        let format: ChecksumFormat = ChecksumFormat::okx(1, 0);
+
+        assert_eq!(canonical(&levels(), &format),
+                   "5541.2:1:5541.3:2:5539.9:3:5541.8:12:5542.7:5");
+        assert_eq!(checksum(&levels(), &format), 2_715_356_059);
+        assert_eq!(checksum(&Levels::default(), &format), 0);
+    }
+}
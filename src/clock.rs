@@ -0,0 +1,135 @@
+use std::fmt::Debug;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use chrono::{DateTime, Duration, Utc};
+
+/* Where a book gets the time from, for its events and for the orders it
+ * modifies or cancels. Clocks can be used either as a generic parameter or
+ * boxed as `Box<dyn Clock>`. */
+pub trait Clock: Debug {
+    fn now(&self) -> DateTime<Utc>;
+}
+
+impl Clock for Box<dyn Clock> {
+    fn now(&self) -> DateTime<Utc> {
+        (**self).now()
+    }
+}
+
+/* the wall clock */
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> DateTime<Utc> {
+        Utc::now()
+    }
+}
+
+/* a time shared between clones, so that whoever keeps one clone can move
+ * the time seen by the book that was given another */
+#[derive(Debug, Default, Clone)]
+struct SharedTime(Arc<Mutex<DateTime<Utc>>>);
+
+impl SharedTime {
+    fn new(time: DateTime<Utc>) -> SharedTime {
+        SharedTime(Arc::new(Mutex::new(time)))
+    }
+
+    /* nothing is ever left half-written, so a poisoned lock is still
+     * good */
+    fn lock(&self) -> MutexGuard<'_, DateTime<Utc>> {
+        self.0.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+/* Stands still until it is set or advanced, e.g. in tests. Clones share
+ * the same time. */
+#[derive(Debug, Default, Clone)]
+pub struct ManualClock {
+    time: SharedTime
+}
+
+impl ManualClock {
+    pub fn new(time: DateTime<Utc>) -> ManualClock {
+        ManualClock {
+            time: SharedTime::new(time)
+        }
+    }
+
+    /* may go backwards; the book's event timestamps still will not */
+    pub fn set(&self, time: DateTime<Utc>) {
+        *self.time.lock() = time;
+    }
+
+    pub fn advance(&self, duration: Duration) {
+        *self.time.lock() += duration;
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> DateTime<Utc> {
+        *self.time.lock()
+    }
+}
+
+/* Follows the timestamps of whatever is being replayed, e.g. historical
+ * orders fed through a book, so that a simulation reports the times the
+ * data did rather than when it happened to run. Never goes backwards.
+ * Clones share the same time. */
+#[derive(Debug, Default, Clone)]
+pub struct SimulatedClock {
+    time: SharedTime
+}
+
+impl SimulatedClock {
+    pub fn new(start: DateTime<Utc>) -> SimulatedClock {
+        SimulatedClock {
+            time: SharedTime::new(start)
+        }
+    }
+
+    /* moves the clock on to `time`, unless it is already past it */
+    pub fn advance_to(&self, time: DateTime<Utc>) {
+        let mut current: MutexGuard<'_, DateTime<Utc>> = self.time.lock();
+        *current = (*current).max(time);
+    }
+}
+
+impl Clock for SimulatedClock {
+    fn now(&self) -> DateTime<Utc> {
+        *self.time.lock()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn test_manual_clock_shared_between_clones() {
+        let start: DateTime<Utc> = Utc.with_ymd_and_hms(2024, 1, 2, 9, 30, 0)
+            .unwrap();
+        let clock: ManualClock = ManualClock::new(start);
+        let book_clock: ManualClock = clock.clone();
+
+        clock.advance(Duration::seconds(5));
+        assert_eq!(book_clock.now(), start + Duration::seconds(5));
+
+        clock.set(start);
+        assert_eq!(book_clock.now(), start);
+    }
+
+    #[test]
+    fn test_simulated_clock_never_goes_backwards() {
+        let start: DateTime<Utc> = Utc.with_ymd_and_hms(2024, 1, 2, 9, 30, 0)
+            .unwrap();
+        let clock: SimulatedClock = SimulatedClock::new(start);
+
+        clock.advance_to(start + Duration::seconds(10));
+        clock.advance_to(start + Duration::seconds(3));
+
+        assert_eq!(clock.now(), start + Duration::seconds(10));
+    }
+}
@@ -0,0 +1,178 @@
+use std::str::FromStr;
+
+use crate::io::{self, IoError, OrderRecord, Schema};
+use crate::order::{OrderId, OrderType};
+use crate::quantity::{self, Quantity};
+
+pub const USAGE: &str = "\
+buy QUANTITY @ PRICE     submit a bid (also: bid)
+sell QUANTITY @ PRICE    submit an ask (also: ask)
+modify ID QUANTITY @ PRICE
+                         amend a resting order (also: amend)
+cancel ID                cancel a resting order
+book [LEVELS]            show the book
+trades                   show the trades so far
+help                     show this
+quit                     stop (also: exit)
+{...}                    an order as a line of JSON, with its own ID";
+
+#[derive(Debug, thiserror::Error)]
+pub enum CommandError {
+    #[error("unknown command {0:?}; try \"help\"")]
+    Unknown(String),
+    #[error("usage: {0}")]
+    Usage(&'static str),
+    #[error("invalid {0}: {1:?}")]
+    Invalid(&'static str, String),
+    #[error(transparent)]
+    Io(#[from] IoError),
+}
+
+/* What a user typed at an interactive prompt. Orders typed in by hand are
+ * given IDs by whoever carries them out; those given as JSON bring their
+ * own. */
+#[derive(Debug, Clone, PartialEq)]
+pub enum Command {
+    Submit {
+        side: OrderType,
+        quantity: Quantity,
+        price: f64
+    },
+    Record(OrderRecord),
+    Modify {
+        id: OrderId,
+        quantity: Quantity,
+        price: f64
+    },
+    Cancel(OrderId),
+    Book(Option<usize>),
+    Trades,
+    Help,
+    Quit
+}
+
+/* `QUANTITY @ PRICE`, with or without the `@` or the spaces around it */
+fn terms(words: &[&str], usage: &'static str) ->
+    Result<(Quantity, f64), CommandError> {
+    let joined: String = words.join(" ");
+    let (quantity, price) = match joined.split_once('@') {
+        Some((quantity, price)) => (quantity.trim(), price.trim()),
+        None => match words {
+            [quantity, price] => (*quantity, *price),
+            _ => return Err(CommandError::Usage(usage))
+        }
+    };
+
+    if quantity.is_empty() || price.is_empty() {
+        return Err(CommandError::Usage(usage));
+    }
+
+    Ok((quantity::parse(quantity)
+            .ok_or_else(|| CommandError::Invalid("quantity",
+                                                 quantity.to_string()))?,
+        parse_number("price", price)?))
+}
+
+fn parse_number<T: FromStr>(what: &'static str, text: &str) ->
+    Result<T, CommandError> {
+    text.parse()
+        .map_err(|_| CommandError::Invalid(what, text.to_string()))
+}
+
+/* Parses one line of input, which is either a command (see `USAGE`) or an
+ * order as JSON in the default `Schema`. Blank lines and `#` comments are
+ * `None`. */
+pub fn parse(line: &str) -> Result<Option<Command>, CommandError> {
+    let line: &str = line.trim();
+
+    if line.is_empty() || line.starts_with('#') {
+        return Ok(None);
+    }
+
+    if line.starts_with('{') {
+        return Ok(Some(Command::Record(
+            io::parse_json_record(line, &Schema::default())?)));
+    }
+
+    let words: Vec<&str> = line.split_whitespace().collect();
+    let (verb, rest) = match words.split_first() {
+        Some((verb, rest)) => (verb.to_lowercase(), rest),
+        None => return Ok(None)
+    };
+
+    let command: Command = match (verb.as_str(), rest) {
+        ("buy" | "bid" | "sell" | "ask", _) => {
+            let (quantity, price) = terms(rest, "buy QUANTITY @ PRICE")?;
+
+            Command::Submit {
+                side: io::parse_side(&verb)?,
+                quantity,
+                price
+            }
+        },
+        ("modify" | "amend", [id, terms_words @ ..]) => {
+            let (quantity, price) = terms(terms_words,
+                                          "modify ID QUANTITY @ PRICE")?;
+
+            Command::Modify {
+                id: parse_number("order ID", id)?,
+                quantity,
+                price
+            }
+        },
+        ("modify" | "amend", []) =>
+            return Err(CommandError::Usage("modify ID QUANTITY @ PRICE")),
+        ("cancel", [id]) => Command::Cancel(parse_number("order ID", id)?),
+        ("cancel", _) => return Err(CommandError::Usage("cancel ID")),
+        ("book", []) => Command::Book(None),
+        ("book", [levels]) =>
+            Command::Book(Some(parse_number("number of levels", levels)?)),
+        ("book", _) => return Err(CommandError::Usage("book [LEVELS]")),
+        ("trades", []) => Command::Trades,
+        ("help" | "?", []) => Command::Help,
+        ("quit" | "exit", []) => Command::Quit,
+        _ => return Err(CommandError::Unknown(line.to_string()))
+    };
+
+    Ok(Some(command))
+}
+
+#[cfg(all(test, not(feature = "decimal-quantity")))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() -> Result<(), CommandError> {
+        assert_eq!(parse("buy 100 @ 12.5")?, Some(Command::Submit {
+            side: OrderType::Bid,
+            quantity: 100,
+            price: 12.5
+        }));
+        assert_eq!(parse("  SELL 50@13 ")?, Some(Command::Submit {
+            side: OrderType::Ask,
+            quantity: 50,
+            price: 13.0
+        }));
+        assert_eq!(parse("amend 7 20 12.75")?, Some(Command::Modify {
+            id: 7,
+            quantity: 20,
+            price: 12.75
+        }));
+        assert_eq!(parse("cancel 42")?, Some(Command::Cancel(42)));
+        assert_eq!(parse("book 5")?, Some(Command::Book(Some(5))));
+        assert_eq!(parse("trades")?, Some(Command::Trades));
+        assert_eq!(parse("# a comment")?, None);
+        assert_eq!(parse("{\"id\": 3, \"side\": \"ask\", \"price\": 12.5, \
+                          \"quantity\": 10}")?,
+                   Some(Command::Record(OrderRecord::new(3, OrderType::Ask,
+                                                         12.5, 10, None))));
+
+        assert!(matches!(parse("buy 100"), Err(CommandError::Usage(_))));
+        assert!(matches!(parse("buy ten @ 12"),
+                         Err(CommandError::Invalid("quantity", _))));
+        assert!(matches!(parse("sell 5 @ 12 @ 13"),
+                         Err(CommandError::Invalid("price", _))));
+        assert!(matches!(parse("dance"), Err(CommandError::Unknown(_))));
+        Ok(())
+    }
+}
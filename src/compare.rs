@@ -0,0 +1,344 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+use ordered_float::OrderedFloat;
+use serde::{Serialize, Deserialize};
+
+use crate::book::Book;
+use crate::clock::Clock;
+use crate::levels::{Level, Levels};
+use crate::matching::MatchingPolicy;
+use crate::order::{OrderId, OrderType};
+use crate::price::PriceType;
+use crate::quantity::{Quantity, ZERO};
+use crate::sink::EventSink;
+
+/* set to regenerate golden files rather than check against them */
+pub const BLESS_VARIABLE: &str = "IRONLOBE_BLESS";
+
+/* an order's side, price and size, as compared */
+pub type Terms = (OrderType, f64, Quantity);
+
+/* one way in which the book under test differs from what was expected */
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Difference {
+    /* the size at `price`, zero where there is no level */
+    Level {
+        side: OrderType,
+        price: f64,
+        expected: Quantity,
+        actual: Quantity
+    },
+    /* the number of price levels */
+    Depth {
+        side: OrderType,
+        expected: usize,
+        actual: usize
+    },
+    /* expected to be resting but isn't */
+    MissingOrder(OrderId),
+    /* resting but not expected to be */
+    UnexpectedOrder(OrderId),
+    /* resting in both, on different terms */
+    Order {
+        id: OrderId,
+        expected: Terms,
+        actual: Terms
+    },
+    LastTradedPrice {
+        expected: Option<f64>,
+        actual: Option<f64>
+    },
+}
+
+impl fmt::Display for Difference {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Difference::Level { side, price, expected, actual } =>
+                write!(f, "{:?} level {}: expected {} but found {}", side,
+                       price, expected, actual),
+            Difference::Depth { side, expected, actual } =>
+                write!(f, "{:?} depth: expected {} levels but found {}", side,
+                       expected, actual),
+            Difference::MissingOrder(id) => write!(f, "order {} is missing",
+                                                   id),
+            Difference::UnexpectedOrder(id) =>
+                write!(f, "order {} is not expected", id),
+            Difference::Order { id, expected, actual } =>
+                write!(f, "order {}: expected {:?} but found {:?}", id,
+                       expected, actual),
+            Difference::LastTradedPrice { expected, actual } =>
+                write!(f, "last traded price: expected {:?} but found {:?}",
+                       expected, actual)
+        }
+    }
+}
+
+/* Everything that differs between two books or two sets of levels, bids
+ * before asks, each best price first, then orders by ID. Empty when they
+ * agree. */
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BookDiff {
+    differences: Vec<Difference>
+}
+
+impl BookDiff {
+    pub fn get_differences(&self) -> &[Difference] {
+        &self.differences
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.differences.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.differences.len()
+    }
+}
+
+impl fmt::Display for BookDiff {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for difference in self.differences.iter() {
+            writeln!(f, "{}", difference)?;
+        }
+
+        Ok(())
+    }
+}
+
+fn compare_side(side: OrderType, expected: &[Level], actual: &[Level],
+                differences: &mut Vec<Difference>) {
+    if expected.len() != actual.len() {
+        differences.push(Difference::Depth {
+            side,
+            expected: expected.len(),
+            actual: actual.len()
+        });
+    }
+
+    let mut sizes: BTreeMap<OrderedFloat<f64>, (Quantity, Quantity)> =
+        BTreeMap::new();
+
+    for (price, quantity) in expected {
+        sizes.entry(OrderedFloat(*price)).or_insert((ZERO, ZERO)).0 =
+            *quantity;
+    }
+
+    for (price, quantity) in actual {
+        sizes.entry(OrderedFloat(*price)).or_insert((ZERO, ZERO)).1 =
+            *quantity;
+    }
+
+    let changed = sizes.into_iter()
+        .filter(|(_, (expected, actual))| expected != actual)
+        .map(|(price, (expected, actual))| Difference::Level {
+            side,
+            price: price.into_inner(),
+            expected,
+            actual
+        });
+
+    match side {
+        OrderType::Bid => differences.extend(changed.rev()),
+        OrderType::Ask => differences.extend(changed)
+    }
+}
+
+/* how `actual`'s depth differs from `expected`'s */
+pub fn compare_levels(expected: &Levels, actual: &Levels) -> BookDiff {
+    let mut differences: Vec<Difference> = vec![];
+
+    compare_side(OrderType::Bid, expected.get_bids(), actual.get_bids(),
+                 &mut differences);
+    compare_side(OrderType::Ask, expected.get_asks(), actual.get_asks(),
+                 &mut differences);
+    BookDiff {differences}
+}
+
+fn terms<M, S, C, P>(book: &Book<M, S, C, P>) -> BTreeMap<OrderId, Terms>
+where M: MatchingPolicy, S: EventSink, C: Clock, P: PriceType {
+    book.iter_all()
+        .map(|order| (order.get_id(), (order.get_order_type(),
+                                       order.get_price(),
+                                       order.get_quantity())))
+        .collect()
+}
+
+/* How `actual` differs from `expected`: in depth, in the orders resting on
+ * it, and in the last traded price. Timestamps, owners and queue order are
+ * not compared. The books needn't be of the same type. */
+pub fn compare<M, S, C, P, N, T, K, Q>(expected: &Book<M, S, C, P>,
+                                       actual: &Book<N, T, K, Q>) -> BookDiff
+where M: MatchingPolicy, S: EventSink, C: Clock, P: PriceType,
+      N: MatchingPolicy, T: EventSink, K: Clock, Q: PriceType {
+    let mut diff: BookDiff = compare_levels(&expected.levels(),
+                                            &actual.levels());
+    let expected_orders: BTreeMap<OrderId, Terms> = terms(expected);
+    let actual_orders: BTreeMap<OrderId, Terms> = terms(actual);
+
+    for (id, terms) in expected_orders.iter() {
+        match actual_orders.get(id) {
+            None => diff.differences.push(Difference::MissingOrder(*id)),
+            Some(actual) if actual != terms =>
+                diff.differences.push(Difference::Order {
+                    id: *id,
+                    expected: *terms,
+                    actual: *actual
+                }),
+            Some(_) => {}
+        }
+    }
+
+    diff.differences.extend(actual_orders.keys()
+                            .filter(|id| !expected_orders.contains_key(id))
+                            .map(|id| Difference::UnexpectedOrder(*id)));
+
+    let (expected_ltp, actual_ltp) = (expected.get_ltp().ok(),
+                                      actual.get_ltp().ok());
+
+    if expected_ltp != actual_ltp {
+        diff.differences.push(Difference::LastTradedPrice {
+            expected: expected_ltp,
+            actual: actual_ltp
+        });
+    }
+
+    diff
+}
+
+/* Assertions on a book for test suites, each of which panics with the
+ * whole diff if it fails, e.g.
+ *
+ *     BookAssert::new(&book)
+ *         .matches(&expected)
+ *         .matches_golden("tests/golden/sweep.json");
+ */
+pub struct BookAssert<'a, M: MatchingPolicy, S: EventSink, C: Clock,
+                      P: PriceType> {
+    book: &'a Book<M, S, C, P>
+}
+
+impl<'a, M: MatchingPolicy, S: EventSink, C: Clock, P: PriceType>
+    BookAssert<'a, M, S, C, P> {
+    pub fn new(book: &'a Book<M, S, C, P>) -> BookAssert<'a, M, S, C, P> {
+        BookAssert {book}
+    }
+
+    fn check(self, diff: BookDiff, against: &str) -> Self {
+        if !diff.is_empty() {
+            panic!("book {} differs from {}:\n{}", self.book.get_ticker(),
+                   against, diff);
+        }
+
+        self
+    }
+
+    /* see `compare` */
+    #[track_caller]
+    pub fn matches<N, T, K, Q>(self, expected: &Book<N, T, K, Q>) -> Self
+    where N: MatchingPolicy, T: EventSink, K: Clock, Q: PriceType {
+        let diff: BookDiff = compare(expected, self.book);
+        self.check(diff, "the expected book")
+    }
+
+    #[track_caller]
+    pub fn has_levels(self, expected: &Levels) -> Self {
+        let diff: BookDiff = compare_levels(expected, &self.book.levels());
+        self.check(diff, "the expected levels")
+    }
+
+    /* Checks the book's depth against that saved as JSON at `path`, or
+     * saves it there if there is nothing yet, or if `BLESS_VARIABLE` is
+     * set. */
+    #[track_caller]
+    pub fn matches_golden<Q: AsRef<Path>>(self, path: Q) -> Self {
+        let path: &Path = path.as_ref();
+        let levels: Levels = self.book.levels();
+
+        if std::env::var_os(BLESS_VARIABLE).is_some() || !path.exists() {
+            let json: String = serde_json::to_string_pretty(&levels)
+                .unwrap_or_default();
+
+            if let Err(error) = fs::write(path, json + "\n") {
+                panic!("could not write {}: {}", path.display(), error);
+            }
+
+            return self;
+        }
+
+        let expected: Levels = match fs::read_to_string(path).map_err(
+            |error| error.to_string())
+            .and_then(|json| serde_json::from_str(&json)
+                      .map_err(|error| error.to_string())) {
+            Ok(expected) => expected,
+            Err(error) => panic!("could not read {}: {}", path.display(),
+                                 error)
+        };
+        let diff: BookDiff = compare_levels(&expected, &levels);
+
+        self.check(diff, &path.display().to_string())
+    }
+}
+
+#[cfg(all(test, not(feature = "decimal-quantity")))]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use crate::account::Account;
+    use crate::book::BookError;
+    use crate::order::Order;
+
+    fn build_order(id: OrderId, order_type: OrderType, price: f64,
+                   quantity: Quantity) -> Order {
+        let mut holdings: HashMap<String, Quantity> = HashMap::new();
+        holdings.insert("BOOK".to_string(), 1000);
+
+        let owner: Account = Account::new(id, "Account".to_string(),
+                                          12000.00, holdings);
+
+        Order::new(id, owner, "BOOK".to_string(), order_type, price, quantity)
+    }
+
+    #[test]
+    fn test_compare() -> Result<(), BookError> {
+        let mut expected: Book = Book::new(1, "Book".to_string(),
+                                           "BOOK".to_string());
+        let mut actual: Book = Book::new(1, "Book".to_string(),
+                                         "BOOK".to_string());
+
+        for book in [&mut expected, &mut actual].iter_mut() {
+            book.submit(build_order(1, OrderType::Bid, 11.00, 10))?;
+            book.submit(build_order(2, OrderType::Ask, 12.00, 10))?;
+        }
+
+        BookAssert::new(&actual).matches(&expected);
+
+        expected.submit(build_order(3, OrderType::Bid, 10.00, 5))?;
+        actual.submit(build_order(4, OrderType::Ask, 12.00, 5))?;
+
+        assert_eq!(compare(&expected, &actual).get_differences(), &[
+            Difference::Depth {
+                side: OrderType::Bid,
+                expected: 2,
+                actual: 1
+            },
+            Difference::Level {
+                side: OrderType::Bid,
+                price: 10.00,
+                expected: 5,
+                actual: 0
+            },
+            Difference::Level {
+                side: OrderType::Ask,
+                price: 12.00,
+                expected: 10,
+                actual: 15
+            },
+            Difference::MissingOrder(3),
+            Difference::UnexpectedOrder(4)
+        ]);
+        Ok(())
+    }
+}
@@ -0,0 +1,227 @@
+use std::collections::BTreeMap;
+
+use ordered_float::OrderedFloat;
+use serde::{Serialize, Deserialize};
+
+use crate::book::{Book, BookId};
+use crate::clock::Clock;
+use crate::event::Trade;
+use crate::levels::{Level, Levels, TopOfBook};
+use crate::matching::MatchingPolicy;
+use crate::price::PriceType;
+use crate::quantity::{Quantity, ZERO};
+use crate::sink::EventSink;
+
+#[derive(Debug, Clone, PartialEq, thiserror::Error)]
+pub enum ConsolidatedError {
+    /* every book has to be for the consolidated book's ticker */
+    #[error("book is for {0}, not the consolidated ticker")]
+    TickerMismatch(String),
+    #[error("book {0} has already been added")]
+    DuplicateBook(BookId)
+}
+
+/* the size at one price across every venue, and how much of it each
+ * contributes, in the order the venues were added */
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConsolidatedLevel {
+    price: f64,
+    venues: Vec<(BookId, Quantity)>
+}
+
+impl ConsolidatedLevel {
+    pub fn get_price(&self) -> f64 {
+        self.price
+    }
+
+    pub fn get_quantity(&self) -> Quantity {
+        self.venues.iter().map(|(_, quantity)| *quantity).sum()
+    }
+
+    pub fn get_venues(&self) -> &[(BookId, Quantity)] {
+        &self.venues
+    }
+}
+
+/* A trade, and the book it happened in */
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct VenueTrade {
+    book: BookId,
+    trade: Trade
+}
+
+impl VenueTrade {
+    pub fn get_book(&self) -> BookId {
+        self.book
+    }
+
+    pub fn get_trade(&self) -> &Trade {
+        &self.trade
+    }
+}
+
+/* The depth of several books for the same instrument, e.g. on different
+ * venues, merged price by price, along with all their trades in time
+ * order. A copy as of when each book was added, rather than a live view,
+ * so books of different types can be consolidated together. */
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ConsolidatedBook {
+    ticker: String,
+    books: Vec<BookId>,
+    bids: BTreeMap<OrderedFloat<f64>, Vec<(BookId, Quantity)>>,
+    asks: BTreeMap<OrderedFloat<f64>, Vec<(BookId, Quantity)>>,
+    tape: Vec<VenueTrade>
+}
+
+impl ConsolidatedBook {
+    pub fn new(ticker: String) -> ConsolidatedBook {
+        ConsolidatedBook {
+            ticker,
+            ..ConsolidatedBook::default()
+        }
+    }
+
+    pub fn get_ticker(&self) -> String {
+        self.ticker.clone()
+    }
+
+    pub fn get_books(&self) -> &[BookId] {
+        &self.books
+    }
+
+    pub fn add_book<M, S, C, P>(&mut self, book: &Book<M, S, C, P>) ->
+        Result<(), ConsolidatedError>
+        where M: MatchingPolicy, S: EventSink, C: Clock, P: PriceType {
+        if book.get_ticker() != self.ticker {
+            return Err(ConsolidatedError::TickerMismatch(book.get_ticker()));
+        }
+
+        self.add_venue(book.get_id(), &book.levels(), book.get_trades())
+    }
+
+    /* for depth and trades from somewhere other than a `Book`, e.g. a
+     * feed */
+    pub fn add_venue(&mut self, book: BookId, levels: &Levels,
+                     trades: &[Trade]) -> Result<(), ConsolidatedError> {
+        if self.books.contains(&book) {
+            return Err(ConsolidatedError::DuplicateBook(book));
+        }
+
+        self.books.push(book);
+
+        for (side, levels) in [(&mut self.bids, levels.get_bids()),
+                               (&mut self.asks, levels.get_asks())] {
+            for (price, quantity) in levels {
+                side.entry(OrderedFloat::from(*price))
+                    .or_default()
+                    .push((book, *quantity));
+            }
+        }
+
+        self.tape.extend(trades.iter().map(|trade| VenueTrade {
+            book,
+            trade: *trade
+        }));
+        /* stable, so each venue's trades keep their order */
+        self.tape.sort_by_key(|trade| trade.trade.get_timestamp());
+        Ok(())
+    }
+
+    /* best first */
+    pub fn bids(&self) -> Vec<ConsolidatedLevel> {
+        self.bids.iter().rev().map(Self::level).collect()
+    }
+
+    /* best first */
+    pub fn asks(&self) -> Vec<ConsolidatedLevel> {
+        self.asks.iter().map(Self::level).collect()
+    }
+
+    fn level((price, venues): (&OrderedFloat<f64>, &Vec<(BookId, Quantity)>))
+        -> ConsolidatedLevel {
+        ConsolidatedLevel {
+            price: price.into_inner(),
+            venues: venues.clone()
+        }
+    }
+
+    /* the merged depth, without attribution */
+    pub fn levels(&self) -> Levels {
+        let total = |level: ConsolidatedLevel| -> Level {
+            (level.get_price(), level.get_quantity())
+        };
+
+        Levels::new(self.bids().into_iter().map(total).collect(),
+                    self.asks().into_iter().map(total).collect())
+    }
+
+    /* the best bid and ask across every venue */
+    pub fn top_of_book(&self) -> TopOfBook {
+        let best = |level: Option<&ConsolidatedLevel>| {
+            level.map(|level| (level.get_price(), level.get_quantity()))
+                .filter(|(_, quantity)| *quantity > ZERO)
+        };
+
+        TopOfBook::new(best(self.bids().first()), best(self.asks().first()))
+    }
+
+    /* every venue's trades, oldest first */
+    pub fn tape(&self) -> &[VenueTrade] {
+        &self.tape
+    }
+}
+
+#[cfg(all(test, not(feature = "decimal-quantity")))]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use crate::account::Account;
+    use crate::book::BookError;
+    use crate::order::*;
+
+    fn build_order(id: OrderId, order_type: OrderType, price: f64,
+                   quantity: Quantity) -> Order {
+        let mut holdings: HashMap<String, Quantity> = HashMap::new();
+        holdings.insert("BOOK".to_string(), 1000);
+
+        let owner: Account = Account::new(id, "Account".to_string(),
+                                          12000.00, holdings);
+
+        Order::new(id, owner, "BOOK".to_string(), order_type, price, quantity)
+    }
+
+    #[test]
+    fn test_consolidated_book() -> Result<(), BookError> {
+        let mut first: Book = Book::new(1, "First".to_string(),
+                                        "BOOK".to_string());
+        let mut second: Book = Book::new(2, "Second".to_string(),
+                                         "BOOK".to_string());
+
+        first.submit(build_order(1, OrderType::Bid, 11.00, 10))?;
+        first.submit(build_order(2, OrderType::Ask, 12.00, 10))?;
+        second.submit(build_order(3, OrderType::Bid, 11.00, 5))?;
+        second.submit(build_order(4, OrderType::Bid, 11.50, 5))?;
+        second.submit(build_order(5, OrderType::Ask, 11.50, 2))?;
+        first.submit(build_order(6, OrderType::Bid, 12.00, 1))?;
+
+        let mut actual_book: ConsolidatedBook =
+            ConsolidatedBook::new("BOOK".to_string());
+        actual_book.add_book(&first).unwrap();
+        actual_book.add_book(&second).unwrap();
+
+        assert_eq!(actual_book.levels(),
+                   Levels::new(vec![(11.50, 3), (11.00, 15)],
+                               vec![(12.00, 9)]));
+        assert_eq!(actual_book.bids().get(1).map(|l| l.get_venues()),
+                   Some(&[(1, 10), (2, 5)][..]));
+        assert_eq!(actual_book.top_of_book(),
+                   TopOfBook::new(Some((11.50, 3)), Some((12.00, 9))));
+        assert_eq!(actual_book.tape().iter()
+                       .map(VenueTrade::get_book)
+                       .collect::<Vec<BookId>>(),
+                   vec![2, 1]);
+        assert_eq!(actual_book.add_book(&first),
+                   Err(ConsolidatedError::DuplicateBook(1)));
+        Ok(())
+    }
+}
@@ -0,0 +1,462 @@
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, HashSet};
+use std::hash::{Hash, Hasher};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, SyncSender,
+                      TryRecvError, TrySendError};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use crate::book::{Book, BookError};
+use crate::clock::{Clock, SystemClock};
+use crate::event::Event;
+use crate::matching::{MatchingPolicy, PriceTime};
+use crate::order::{Order, OrderId};
+use crate::price::{F64Price, PriceType};
+use crate::quantity::Quantity;
+use crate::sink::MemorySink;
+
+#[derive(Debug, thiserror::Error)]
+pub enum EngineError {
+    #[error("shard {0}'s queue is full")]
+    Full(usize),
+    #[error("the engine has shut down")]
+    ShutDown,
+    #[error("a book for {0} has already been added")]
+    DuplicateBook(String),
+    #[error("shard {0}'s worker panicked")]
+    WorkerPanicked(usize),
+}
+
+/* what a shard is asked to do; everything but adding a book names the
+ * ticker of the book it is for */
+#[derive(Debug)]
+pub enum Command<M: MatchingPolicy = PriceTime, C: Clock = SystemClock,
+                 P: PriceType = F64Price> {
+    AddBook(Box<Book<M, MemorySink, C, P>>),
+    Submit(Box<Order>),
+    Cancel {
+        ticker: String,
+        id: OrderId
+    },
+    Modify {
+        ticker: String,
+        id: OrderId,
+        price: f64,
+        quantity: Quantity
+    },
+    Reduce {
+        ticker: String,
+        id: OrderId,
+        quantity: Quantity
+    },
+    /* finish the queue, then stop */
+    Shutdown
+}
+
+impl<M: MatchingPolicy, C: Clock, P: PriceType> Command<M, C, P> {
+    pub fn get_ticker(&self) -> Option<String> {
+        match self {
+            Command::AddBook(book) => Some(book.get_ticker()),
+            Command::Submit(order) => Some(order.get_ticker()),
+            Command::Cancel { ticker, .. } | Command::Modify { ticker, .. } |
+            Command::Reduce { ticker, .. } => Some(ticker.clone()),
+            Command::Shutdown => None
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum EngineEventKind {
+    Book(Box<Event>),
+    /* a command the shard could not carry out; `order` is the order it
+     * concerned, if any */
+    Rejected {
+        order: Option<OrderId>,
+        error: BookError
+    },
+    /* a command for a ticker no shard has a book for */
+    UnknownSymbol {
+        order: Option<OrderId>
+    }
+}
+
+/* One entry in the engine's merged outbound stream. `seq` numbers every
+ * entry across all shards, starting from 1, in the order they appear in
+ * the stream; each book's own events keep their own sequence numbers
+ * too. */
+#[derive(Debug)]
+pub struct EngineEvent {
+    seq: u64,
+    ticker: String,
+    kind: EngineEventKind
+}
+
+impl EngineEvent {
+    pub fn get_seq(&self) -> u64 {
+        self.seq
+    }
+
+    pub fn get_ticker(&self) -> String {
+        self.ticker.clone()
+    }
+
+    pub fn get_kind(&self) -> &EngineEventKind {
+        &self.kind
+    }
+
+    pub fn into_kind(self) -> EngineEventKind {
+        self.kind
+    }
+}
+
+/* stamps outbound entries and sends them, under one lock so that the
+ * stream is in sequence order */
+#[derive(Debug)]
+struct Outbound {
+    seq: u64,
+    sender: SyncSender<EngineEvent>
+}
+
+type SharedOutbound = Arc<Mutex<Outbound>>;
+
+fn emit(outbound: &SharedOutbound, ticker: &str, kind: EngineEventKind) {
+    if let Ok(mut outbound) = outbound.lock() {
+        outbound.seq += 1;
+
+        let event: EngineEvent = EngineEvent {
+            seq: outbound.seq,
+            ticker: ticker.to_string(),
+            kind
+        };
+
+        /* nobody listening is not the shard's problem */
+        let _ = outbound.sender.send(event);
+    }
+}
+
+fn order_of<M: MatchingPolicy, C: Clock, P: PriceType>(
+    command: &Command<M, C, P>) -> Option<OrderId> {
+    match command {
+        Command::Submit(order) => Some(order.get_id()),
+        Command::Cancel { id, .. } | Command::Modify { id, .. } |
+        Command::Reduce { id, .. } => Some(*id),
+        Command::AddBook(_) | Command::Shutdown => None
+    }
+}
+
+/* a shard's worker: applies commands to its books in arrival order until
+ * told to stop, and hands the books back */
+fn run_shard<M, C, P>(commands: Receiver<Command<M, C, P>>,
+                      outbound: SharedOutbound) -> ShardBooks<M, C, P>
+where
+    M: MatchingPolicy,
+    C: Clock,
+    P: PriceType
+{
+    let mut books: ShardBooks<M, C, P> = HashMap::new();
+
+    for command in commands.iter() {
+        let order: Option<OrderId> = order_of(&command);
+        let ticker: String = match command.get_ticker() {
+            Some(ticker) => ticker,
+            None => break
+        };
+
+        if let Command::AddBook(book) = command {
+            books.insert(ticker, *book);
+            continue;
+        }
+
+        let book: &mut Book<M, MemorySink, C, P> =
+            match books.get_mut(&ticker) {
+                Some(book) => book,
+                None => {
+                    emit(&outbound, &ticker,
+                         EngineEventKind::UnknownSymbol { order });
+                    continue;
+                }
+            };
+        let last: u64 = book.last_seq();
+
+        let result: Result<(), BookError> = match command {
+            Command::Submit(order) => book.submit(*order),
+            Command::Cancel { id, .. } => book.cancel(id).map(|_| ()),
+            Command::Modify { id, price, quantity, .. } =>
+                book.modify(id, price, quantity),
+            Command::Reduce { id, quantity, .. } =>
+                book.reduce(id, quantity).map(|_| ()),
+            Command::AddBook(_) | Command::Shutdown => Ok(())
+        };
+
+        for event in book.events_since(last).unwrap_or_default() {
+            emit(&outbound, &ticker,
+                 EngineEventKind::Book(Box::new(event.clone())));
+        }
+
+        if let Err(error) = result {
+            emit(&outbound, &ticker,
+                 EngineEventKind::Rejected { order, error });
+        }
+    }
+
+    books
+}
+
+type ShardBooks<M, C, P> = HashMap<String, Book<M, MemorySink, C, P>>;
+
+/* a shut-down engine's books, in ticker order, and the rest of its
+ * stream */
+pub type Drained<M, C, P> = (Vec<Book<M, MemorySink, C, P>>, Vec<EngineEvent>);
+
+#[derive(Debug)]
+struct Shard<M: MatchingPolicy, C: Clock, P: PriceType> {
+    sender: SyncSender<Command<M, C, P>>,
+    worker: JoinHandle<ShardBooks<M, C, P>>
+}
+
+/* Runs books on worker threads, each ticker's book on the shard its ticker
+ * hashes to, so that instruments match in parallel while each book still
+ * sees its commands in order. Every shard takes commands from a bounded
+ * queue: `send` blocks while the queue is full, and `try_send` hands the
+ * command back instead. Everything the books do comes out of one merged
+ * stream (see `EngineEvent`), which is also bounded, so a consumer that
+ * falls behind slows the shards down rather than letting the stream
+ * grow. */
+#[derive(Debug)]
+pub struct ShardedEngine<M: MatchingPolicy = PriceTime,
+                         C: Clock = SystemClock,
+                         P: PriceType = F64Price> {
+    shards: Vec<Shard<M, C, P>>,
+    events: Receiver<EngineEvent>,
+    /* tickers with books, so that duplicates are caught up front */
+    tickers: HashSet<String>
+}
+
+impl<M, C, P> ShardedEngine<M, C, P>
+where
+    M: MatchingPolicy + Send + 'static,
+    C: Clock + Send + 'static,
+    P: PriceType + Send + 'static
+{
+    /* `shards` worker threads (at least one), each queueing up to
+     * `capacity` commands; the outbound stream holds up to `capacity`
+     * entries per shard */
+    pub fn new(shards: usize, capacity: usize) -> ShardedEngine<M, C, P> {
+        let count: usize = shards.max(1);
+        let (sender, events) = mpsc::sync_channel(capacity * count);
+        let outbound: SharedOutbound = Arc::new(Mutex::new(Outbound {
+            seq: 0,
+            sender
+        }));
+
+        let shards: Vec<Shard<M, C, P>> = (0..count)
+            .map(|_| {
+                let (sender, commands) = mpsc::sync_channel(capacity);
+                let outbound: SharedOutbound = Arc::clone(&outbound);
+                let worker = thread::spawn(move || run_shard(commands,
+                                                             outbound));
+
+                Shard { sender, worker }
+            })
+            .collect();
+
+        ShardedEngine {
+            shards,
+            events,
+            tickers: HashSet::new()
+        }
+    }
+
+    pub fn get_shard_count(&self) -> usize {
+        self.shards.len()
+    }
+
+    /* which shard `ticker`'s book runs on */
+    pub fn shard_of(&self, ticker: &str) -> usize {
+        let mut hasher: DefaultHasher = DefaultHasher::new();
+        ticker.hash(&mut hasher);
+
+        (hasher.finish() % self.shards.len() as u64) as usize
+    }
+
+    pub fn add_book(&mut self, book: Book<M, MemorySink, C, P>) ->
+        Result<(), EngineError> {
+        let ticker: String = book.get_ticker();
+
+        if self.tickers.contains(&ticker) {
+            return Err(EngineError::DuplicateBook(ticker));
+        }
+
+        self.send(Command::AddBook(Box::new(book)))?;
+        self.tickers.insert(ticker);
+        Ok(())
+    }
+
+    /* queues `command` on its shard, waiting for room if need be */
+    pub fn send(&self, command: Command<M, C, P>) -> Result<(), EngineError> {
+        let shard: usize = self.route(&command);
+
+        self.shards.get(shard)
+            .ok_or(EngineError::ShutDown)?
+            .sender
+            .send(command)
+            .map_err(|_| EngineError::ShutDown)
+    }
+
+    /* as `send`, but never waits: a command that does not fit is handed
+     * back along with the error */
+    pub fn try_send(&self, command: Command<M, C, P>) ->
+        Result<(), (EngineError, Command<M, C, P>)> {
+        let shard: usize = self.route(&command);
+        let sender: &SyncSender<Command<M, C, P>> =
+            match self.shards.get(shard) {
+                Some(shard) => &shard.sender,
+                None => return Err((EngineError::ShutDown, command))
+            };
+
+        sender.try_send(command).map_err(|e| match e {
+            TrySendError::Full(command) =>
+                (EngineError::Full(shard), command),
+            TrySendError::Disconnected(command) =>
+                (EngineError::ShutDown, command)
+        })
+    }
+
+    pub fn submit(&self, order: Order) -> Result<(), EngineError> {
+        self.send(Command::Submit(Box::new(order)))
+    }
+
+    pub fn cancel(&self, ticker: String, id: OrderId) ->
+        Result<(), EngineError> {
+        self.send(Command::Cancel { ticker, id })
+    }
+
+    /* the next entry of the merged stream, waiting for one */
+    pub fn recv(&self) -> Result<EngineEvent, EngineError> {
+        self.events.recv().map_err(|_| EngineError::ShutDown)
+    }
+
+    pub fn recv_timeout(&self, timeout: Duration) ->
+        Result<Option<EngineEvent>, EngineError> {
+        match self.events.recv_timeout(timeout) {
+            Ok(event) => Ok(Some(event)),
+            Err(RecvTimeoutError::Timeout) => Ok(None),
+            Err(RecvTimeoutError::Disconnected) => Err(EngineError::ShutDown)
+        }
+    }
+
+    /* whatever entries are ready, without waiting */
+    pub fn drain(&self) -> Vec<EngineEvent> {
+        let mut events: Vec<EngineEvent> = vec![];
+
+        loop {
+            match self.events.try_recv() {
+                Ok(event) => events.push(event),
+                Err(TryRecvError::Empty) | Err(TryRecvError::Disconnected) =>
+                    return events
+            }
+        }
+    }
+
+    /* Stops taking commands, lets every shard finish what it has queued,
+     * and hands back the books along with whatever is left of the stream.
+     * Entries are drained while waiting, so a full stream can't keep a
+     * shard from finishing. */
+    pub fn shutdown(self) -> Result<Drained<M, C, P>, EngineError> {
+        let ShardedEngine { shards, events, .. } = self;
+        let mut remaining: Vec<EngineEvent> = vec![];
+        let mut books: Vec<Book<M, MemorySink, C, P>> = vec![];
+        let wait: Duration = Duration::from_millis(1);
+
+        for shard in shards.iter() {
+            let mut command: Command<M, C, P> = Command::Shutdown;
+
+            /* a shard that has gone already will show up when joined */
+            while let Err(TrySendError::Full(returned)) =
+                shard.sender.try_send(command) {
+                command = returned;
+                remaining.extend(events.recv_timeout(wait).ok());
+            }
+        }
+
+        for (index, shard) in shards.into_iter().enumerate() {
+            while !shard.worker.is_finished() {
+                remaining.extend(events.recv_timeout(wait).ok());
+            }
+
+            let shard_books: ShardBooks<M, C, P> = shard.worker.join()
+                .map_err(|_| EngineError::WorkerPanicked(index))?;
+
+            books.extend(shard_books.into_values());
+        }
+
+        remaining.extend(events.try_iter());
+        books.sort_by_key(|book| book.get_ticker());
+        Ok((books, remaining))
+    }
+
+    fn route(&self, command: &Command<M, C, P>) -> usize {
+        command.get_ticker()
+            .map(|ticker| self.shard_of(&ticker))
+            .unwrap_or(0)
+    }
+}
+
+#[cfg(all(test, not(feature = "decimal-quantity")))]
+mod tests {
+    use super::*;
+    use crate::account::Account;
+    use crate::event::EventKind;
+    use crate::order::OrderType;
+
+    fn order(id: OrderId, ticker: &str, order_type: OrderType, price: f64,
+             quantity: Quantity) -> Order {
+        let mut holdings: HashMap<String, Quantity> = HashMap::new();
+        holdings.insert(ticker.to_string(), 1_000);
+
+        Order::new(id, Account::new(id, "Trader".to_string(), 1e9, holdings),
+                   ticker.to_string(), order_type, price, quantity)
+    }
+
+    #[test]
+    fn test_sharded_engine() -> Result<(), EngineError> {
+        let tickers: [&str; 4] = ["AAA", "BBB", "CCC", "DDD"];
+        let mut engine: ShardedEngine = ShardedEngine::new(2, 16);
+
+        for (id, ticker) in tickers.iter().enumerate() {
+            engine.add_book(Book::new(id as u128, ticker.to_string(),
+                                      ticker.to_string()))?;
+        }
+
+        assert!(matches!(engine.add_book(Book::new(9, "AAA".to_string(),
+                                                   "AAA".to_string())),
+                         Err(EngineError::DuplicateBook(_))));
+
+        for (i, ticker) in tickers.iter().enumerate() {
+            let id: OrderId = 10 * i as u128;
+
+            engine.submit(order(id + 1, ticker, OrderType::Ask, 10.0, 5))?;
+            engine.submit(order(id + 2, ticker, OrderType::Bid, 10.0, 3))?;
+        }
+
+        engine.cancel("ZZZ".to_string(), 1)?;
+
+        let (books, events) = engine.shutdown()?;
+        let trades: usize = events.iter()
+            .filter(|event| matches!(event.get_kind(),
+                                     EngineEventKind::Book(event)
+                                     if matches!(event.get_kind(),
+                                                 EventKind::Match(_))))
+            .count();
+
+        assert_eq!(books.len(), 4);
+        assert!(books.iter().all(|book| book.get_trades().len() == 1));
+        assert_eq!(trades, 4);
+        assert!(events.iter().any(|event| matches!(
+            event.get_kind(), EngineEventKind::UnknownSymbol { .. })));
+        assert!(events.windows(2)
+                .all(|pair| pair[0].get_seq() + 1 == pair[1].get_seq()));
+        Ok(())
+    }
+}
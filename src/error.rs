@@ -0,0 +1,98 @@
+use crate::account::AccountError;
+use crate::batch::BatchError;
+use crate::binary::BinaryError;
+use crate::book::BookError;
+use crate::builder::BuildError;
+use crate::command::CommandError;
+use crate::consolidated::ConsolidatedError;
+use crate::engine::EngineError;
+use crate::exchange::ExchangeError;
+use crate::external::ExternalError;
+use crate::io::IoError;
+use crate::journal::JournalError;
+use crate::order::OrderError;
+use crate::replica::ReplicaError;
+use crate::router::RouterError;
+use crate::sink::SinkError;
+use crate::versioned::VersionError;
+#[cfg(feature = "storage")]
+use crate::storage::StorageError;
+#[cfg(feature = "async")]
+use crate::stream::StreamError;
+#[cfg(feature = "viz")]
+use crate::viz::VizError;
+
+/* Every error in the crate, for callers that use several modules at once
+ * and would rather `?` them all into one type. Each module still returns its
+ * own error, which converts into this. */
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error(transparent)]
+    Book(#[from] BookError),
+    #[error(transparent)]
+    Batch(#[from] BatchError),
+    #[error(transparent)]
+    Order(#[from] OrderError),
+    #[error(transparent)]
+    Account(#[from] AccountError),
+    #[error(transparent)]
+    Build(#[from] BuildError),
+    #[error(transparent)]
+    Router(#[from] RouterError),
+    #[error(transparent)]
+    Command(#[from] CommandError),
+    #[error(transparent)]
+    Consolidated(#[from] ConsolidatedError),
+    #[error(transparent)]
+    Engine(#[from] EngineError),
+    #[error(transparent)]
+    Exchange(#[from] ExchangeError),
+    #[error(transparent)]
+    Io(#[from] IoError),
+    #[error(transparent)]
+    Journal(#[from] JournalError),
+    #[error(transparent)]
+    Binary(#[from] BinaryError),
+    #[error(transparent)]
+    Version(#[from] VersionError),
+    #[error(transparent)]
+    External(#[from] ExternalError),
+    #[error(transparent)]
+    Sink(#[from] SinkError),
+    /* from replicas and the feeds and recorders built on them */
+    #[error(transparent)]
+    Replica(#[from] ReplicaError),
+    #[cfg(feature = "viz")]
+    #[error(transparent)]
+    Viz(#[from] VizError),
+    #[cfg(feature = "storage")]
+    #[error(transparent)]
+    Storage(#[from] StorageError),
+    #[cfg(feature = "async")]
+    #[error(transparent)]
+    Stream(#[from] StreamError),
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fails() -> Result<()> {
+        Err(AccountError::InsufficientHoldings)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_conversion() {
+        let actual: Result<()> = fails();
+
+        assert!(matches!(actual,
+                         Err(Error::Account(
+                             AccountError::InsufficientHoldings))));
+        assert_eq!(Error::from(BookError::Account(AccountError::AssetNotFound))
+                       .to_string(),
+                   "account error: account holds none of that asset");
+    }
+}
@@ -0,0 +1,490 @@
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, Utc};
+use serde::{Serialize, Deserialize};
+
+use crate::account::AccountId;
+use crate::levels::TopOfBook;
+use crate::book::BookId;
+use crate::order::*;
+use crate::quantity::Quantity;
+use crate::router::RoutingStrategy;
+use crate::session::SessionState;
+
+/* the sequence number of the `Match` event a book recorded a trade with,
+ * which stays the same across snapshots and replays */
+pub type TradeId = u64;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Trade {
+    #[serde(default)]
+    id: TradeId,
+    timestamp: DateTime<Utc>,
+    price: f64,
+    quantity: Quantity,
+    aggressor: OrderId,
+    resting: OrderId,
+    aggressor_side: OrderType,
+    /* the owners of each side, where the book knew them */
+    #[serde(default)]
+    aggressor_account: Option<AccountId>,
+    #[serde(default)]
+    resting_account: Option<AccountId>
+}
+
+impl Trade {
+    pub fn new(timestamp: DateTime<Utc>, price: f64, quantity: Quantity,
+               aggressor: OrderId, resting: OrderId,
+               aggressor_side: OrderType) -> Trade {
+        Trade {
+            id: 0,
+            timestamp,
+            price,
+            quantity,
+            aggressor,
+            resting,
+            aggressor_side,
+            aggressor_account: None,
+            resting_account: None
+        }
+    }
+
+    pub fn with_id(mut self, id: TradeId) -> Trade {
+        self.id = id;
+        self
+    }
+
+    pub fn with_accounts(mut self, aggressor: AccountId, resting: AccountId) ->
+        Trade {
+        self.aggressor_account = Some(aggressor);
+        self.resting_account = Some(resting);
+        self
+    }
+
+    /* zero for trades that no book recorded */
+    pub fn get_id(&self) -> TradeId {
+        self.id
+    }
+
+    pub fn get_timestamp(&self) -> DateTime<Utc> {
+        self.timestamp
+    }
+
+    pub fn get_price(&self) -> f64 {
+        self.price
+    }
+
+    pub fn get_quantity(&self) -> Quantity {
+        self.quantity
+    }
+
+    pub fn get_aggressor(&self) -> OrderId {
+        self.aggressor
+    }
+
+    pub fn get_resting(&self) -> OrderId {
+        self.resting
+    }
+
+    pub fn get_aggressor_side(&self) -> OrderType {
+        self.aggressor_side
+    }
+
+    pub fn get_aggressor_account(&self) -> Option<AccountId> {
+        self.aggressor_account
+    }
+
+    pub fn get_resting_account(&self) -> Option<AccountId> {
+        self.resting_account
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum EventKind {
+    /* An order (or what was left of it after matching) now rests. A
+     * hidden order's events say so, and are left out of anything showing
+     * the book's depth; see `Order::set_hidden`. */
+    Post {
+        order: OrderId,
+        order_type: OrderType,
+        price: f64,
+        quantity: Quantity,
+        #[serde(default)]
+        hidden: bool
+    },
+    Match(Trade),
+    Cancel {
+        order: OrderId,
+        order_type: OrderType,
+        price: f64,
+        quantity: Quantity,
+        #[serde(default)]
+        hidden: bool
+    },
+    /* matching stopped rather than trade at `price`, outside the circuit
+     * breaker's band around `reference`; the book is now halted */
+    Halt {
+        reference: f64,
+        price: f64
+    },
+    StateChange {
+        previous: SessionState,
+        current: SessionState
+    },
+    /* the best bid or ask, or the size at either, changed */
+    TopOfBook {
+        previous: TopOfBook,
+        current: TopOfBook
+    },
+    /* a resting order's new terms; see `Book::modify` */
+    Amend {
+        order: OrderId,
+        order_type: OrderType,
+        price: f64,
+        quantity: Quantity,
+        #[serde(default)]
+        hidden: bool
+    },
+    /* a router sent `quantity` of `order` to this book */
+    Route {
+        order: OrderId,
+        book: BookId,
+        quantity: Quantity,
+        strategy: RoutingStrategy
+    },
+    /* a bid was refused for want of buying power; see `MarginModel` */
+    BuyingPowerReject {
+        order: OrderId,
+        account: AccountId,
+        required: f64,
+        available: f64
+    },
+    /* `quantity` was taken off a resting order, leaving `remaining` in
+     * place; see `Book::reduce` */
+    Reduce {
+        order: OrderId,
+        order_type: OrderType,
+        price: f64,
+        quantity: Quantity,
+        remaining: Quantity,
+        #[serde(default)]
+        hidden: bool
+    },
+    /* a pegged order followed its reference from `previous` to `price`,
+     * going to the back of the queue there; followed, as an amendment is,
+     * by any trades and a post of what is left. See `Book::submit_pegged` */
+    Reprice {
+        order: OrderId,
+        order_type: OrderType,
+        previous: f64,
+        price: f64,
+        quantity: Quantity,
+        #[serde(default)]
+        hidden: bool
+    },
+    /* the next `events` events are the effects of a batch of `operations`
+     * operations, applied atomically; see `Book::apply_batch` */
+    Batch {
+        operations: usize,
+        events: u64
+    },
+    /* the instrument expired and open positions in it settle at `price`;
+     * see `Book::expire` */
+    Settlement {
+        price: f64
+    },
+    /* an earlier trade was busted, and stands no longer; what it took off
+     * the resting order is given back in a `Post` that follows. See
+     * `Book::bust_trade` */
+    TradeBust(Trade),
+    /* one of a bracket order's parent's fills is now protected by a take
+     * profit at `price` and a stop triggered at `trigger`, each for
+     * `quantity`; see `BracketOrder` */
+    BracketArmed {
+        parent: OrderId,
+        take_profit: OrderId,
+        stop_loss: OrderId,
+        order_type: OrderType,
+        price: f64,
+        trigger: f64,
+        quantity: Quantity
+    },
+    /* the last trade reached a stop, which goes in as a limit at `trigger`
+     * next */
+    StopTriggered {
+        order: OrderId,
+        order_type: OrderType,
+        trigger: f64,
+        quantity: Quantity
+    },
+    /* a stop that never triggered was cancelled */
+    StopCancelled {
+        order: OrderId,
+        order_type: OrderType,
+        trigger: f64,
+        quantity: Quantity
+    },
+}
+
+impl EventKind {
+    /* events that buffering sinks should pass on without delay */
+    pub fn is_critical(&self) -> bool {
+        matches!(self, EventKind::Match(_) | EventKind::Halt { .. } |
+                 EventKind::StateChange { .. } |
+                 EventKind::Settlement { .. })
+    }
+}
+
+/* who an event happened on behalf of, for audit trails; see
+ * `Book::set_actor` */
+#[derive(Debug, Default, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Actor {
+    pub account: Option<AccountId>,
+    pub session: Option<String>,
+    /* the feed or gateway the instruction came in through */
+    pub source: Option<String>
+}
+
+impl Actor {
+    pub fn account(account: AccountId) -> Actor {
+        Actor {
+            account: Some(account),
+            ..Actor::default()
+        }
+    }
+
+    pub fn session(mut self, session: String) -> Actor {
+        self.session = Some(session);
+        self
+    }
+
+    pub fn source(mut self, source: String) -> Actor {
+        self.source = Some(source);
+        self
+    }
+}
+
+/* why an order was cancelled or refused */
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Reason {
+    UserRequested,
+    Expired,
+    SelfTradePrevention,
+    RiskReject,
+    /* the other order of a one-cancels-other pair traded */
+    OneCancelsOther,
+    /* what an incoming order could not take for the book's sweep limit;
+     * see `SweepLimit` */
+    SweepLimit,
+    /* to keep within the book's capacity limit; see `CapacityLimit` */
+    CapacityLimit,
+    /* what an incoming order had left when its sweep tripped the circuit
+     * breaker; see `CircuitBreaker` */
+    CircuitBreaker,
+    Other(String)
+}
+
+/* an order's status changing as a result of the event it is carried on,
+ * from nothing the first time the book records one */
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct StatusChange {
+    pub order: OrderId,
+    pub previous: Option<OrderStatus>,
+    pub current: OrderStatus
+}
+
+/* what an order's owner knows it by, carried on the events about it; see
+ * `Order::set_client_id` and `Order::set_tag` */
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OrderLabel {
+    pub order: OrderId,
+    pub client_id: Option<String>,
+    pub tags: BTreeMap<String, String>
+}
+
+impl OrderLabel {
+    /* nothing, for an order with neither a client ID nor tags */
+    pub fn of(order: &Order) -> Option<OrderLabel> {
+        if order.get_client_id().is_none() && order.get_tags().is_empty() {
+            return None;
+        }
+
+        Some(OrderLabel {
+            order: order.get_id(),
+            client_id: order.get_client_id().map(str::to_string),
+            tags: order.get_tags().clone()
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Event {
+    seq: u64,
+    timestamp: DateTime<Utc>,
+    kind: EventKind,
+    #[serde(default)]
+    actor: Option<Actor>,
+    #[serde(default)]
+    reason: Option<Reason>,
+    #[serde(default)]
+    transitions: Vec<StatusChange>,
+    #[serde(default)]
+    labels: Vec<OrderLabel>
+}
+
+impl Event {
+    pub fn new(seq: u64, timestamp: DateTime<Utc>, kind: EventKind) -> Event {
+        Event {
+            seq,
+            timestamp,
+            kind,
+            actor: None,
+            reason: None,
+            transitions: vec![],
+            labels: vec![]
+        }
+    }
+
+    pub fn with_actor(mut self, actor: Actor) -> Event {
+        self.actor = Some(actor);
+        self
+    }
+
+    pub fn with_reason(mut self, reason: Reason) -> Event {
+        self.reason = Some(reason);
+        self
+    }
+
+    pub fn with_transition(mut self, transition: StatusChange) -> Event {
+        self.transitions.push(transition);
+        self
+    }
+
+    pub fn with_label(mut self, label: OrderLabel) -> Event {
+        self.labels.push(label);
+        self
+    }
+
+    pub fn get_seq(&self) -> u64 {
+        self.seq
+    }
+
+    pub fn get_timestamp(&self) -> DateTime<Utc> {
+        self.timestamp
+    }
+
+    pub fn get_kind(&self) -> &EventKind {
+        &self.kind
+    }
+
+    pub fn get_actor(&self) -> Option<&Actor> {
+        self.actor.as_ref()
+    }
+
+    pub fn get_reason(&self) -> Option<&Reason> {
+        self.reason.as_ref()
+    }
+
+    /* the order statuses this event changed, e.g. both sides of a match */
+    pub fn get_transitions(&self) -> &[StatusChange] {
+        &self.transitions
+    }
+
+    /* the labels of the orders whose statuses this event changed, for
+     * those that have any */
+    pub fn get_labels(&self) -> &[OrderLabel] {
+        &self.labels
+    }
+
+    pub fn get_label(&self, order: OrderId) -> Option<&OrderLabel> {
+        self.labels.iter().find(|label| label.order == order)
+    }
+}
+
+/* Hands out a book's event sequence numbers, starting from 1, along with
+ * timestamps that never go backwards even if the wall clock does. Order
+ * events by sequence number; timestamps may repeat. */
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Sequencer {
+    seq: u64,
+    timestamp: DateTime<Utc>
+}
+
+impl Sequencer {
+    /* carries on numbering from `seq`, e.g. when restoring a snapshot */
+    pub fn starting_after(seq: u64) -> Sequencer {
+        Sequencer {
+            seq,
+            timestamp: DateTime::<Utc>::default()
+        }
+    }
+
+    pub fn last_seq(&self) -> u64 {
+        self.seq
+    }
+
+    pub fn advance(&mut self, now: DateTime<Utc>) -> (u64, DateTime<Utc>) {
+        self.seq += 1;
+        self.timestamp = self.timestamp.max(now);
+
+        (self.seq, self.timestamp)
+    }
+
+    pub fn stamp(&mut self, kind: EventKind, now: DateTime<Utc>) -> Event {
+        let (seq, timestamp) = self.advance(now);
+
+        Event::new(seq, timestamp, kind)
+    }
+}
+
+/* the sequence numbers missing from `events`, which are expected to be in
+ * order; a consumer that sees any has lost events */
+pub fn find_gaps(events: &[Event]) -> Vec<u64> {
+    events.windows(2)
+        .flat_map(|pair| match pair {
+            [first, second] => (first.seq + 1)..second.seq,
+            _ => 0..0
+        })
+        .collect()
+}
+
+#[cfg(all(test, not(feature = "decimal-quantity")))]
+mod tests {
+    use super::*;
+
+    fn cancel() -> EventKind {
+        EventKind::Cancel {
+            order: 1,
+            order_type: OrderType::Bid,
+            price: 12.00,
+            quantity: 10,
+            hidden: false
+        }
+    }
+
+    #[test]
+    fn test_sequencer_is_monotonic() {
+        let mut sequencer: Sequencer = Sequencer::default();
+        let events: Vec<Event> = (0..100)
+            .map(|_| sequencer.stamp(cancel(), Utc::now()))
+            .collect();
+
+        let actual_seqs: Vec<u64> = events.iter().map(Event::get_seq).collect();
+        let expected_seqs: Vec<u64> = (1..=100).collect();
+
+        assert_eq!(actual_seqs, expected_seqs);
+        assert_eq!(sequencer.last_seq(), 100);
+        assert!(events.windows(2)
+            .all(|pair| pair[0].get_timestamp() <= pair[1].get_timestamp()));
+    }
+
+    #[test]
+    fn test_find_gaps() {
+        let events: Vec<Event> = [1, 2, 5, 6, 8].iter()
+            .map(|seq| Event::new(*seq, Utc::now(), cancel()))
+            .collect();
+
+        assert_eq!(find_gaps(&events), vec![3, 4, 7]);
+    }
+}
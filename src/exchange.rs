@@ -0,0 +1,576 @@
+use std::collections::{BTreeMap, HashMap, VecDeque};
+
+use chrono::{DateTime, Utc};
+use serde::{Serialize, Deserialize};
+
+use crate::account::{Account, AccountId};
+use crate::analytics::BookStats;
+use crate::book::{Book, BookError, BookId, CancelResult};
+use crate::clock::{Clock, SystemClock};
+use crate::consolidated::VenueTrade;
+use crate::event::{Trade, TradeId};
+use crate::id::{MonotonicIdGenerator, OrderIdGenerator};
+use crate::levels::Levels;
+use crate::matching::{MatchingPolicy, PriceTime};
+use crate::order::{Order, OrderId, OrderType};
+use crate::price::{F64Price, PriceType};
+use crate::quantity::Quantity;
+use crate::router::{Allocation, OrderRouter, RouterError};
+use crate::settlement::{Settlement, SettlementCycle, SettlementReport};
+use crate::sink::{EventSink, MemorySink};
+
+#[derive(Debug, thiserror::Error)]
+pub enum ExchangeError {
+    #[error(transparent)]
+    Router(#[from] RouterError),
+    #[error("no such account {0}")]
+    UnknownAccount(AccountId),
+    #[error("no such order {0}")]
+    OrderNotFound(OrderId),
+    /* the key was last used for a different request */
+    #[error("idempotency key {0:?} reused for a different order")]
+    IdempotencyConflict(String),
+}
+
+impl From<BookError> for ExchangeError {
+    fn from(error: BookError) -> ExchangeError {
+        ExchangeError::Router(RouterError::Book(error))
+    }
+}
+
+/* an order as a client places it: the exchange gives it its ID, and its
+ * owner is the registered account */
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OrderRequest {
+    pub ticker: String,
+    pub account: AccountId,
+    pub side: OrderType,
+    pub price: f64,
+    pub quantity: Quantity,
+    #[serde(default)]
+    pub client_id: Option<String>,
+    #[serde(default)]
+    pub tags: BTreeMap<String, String>,
+    /* Resubmitting with the same key, e.g. when retrying a request whose
+     * response was lost, gives back what the first submission placed
+     * rather than placing it again. Keys are the account's own. */
+    #[serde(default)]
+    pub idempotency_key: Option<String>
+}
+
+/* what placing an order came to: its ID, where it went and what it
+ * traded on the way */
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Placed {
+    pub id: OrderId,
+    pub allocations: Vec<Allocation>,
+    pub trades: Vec<Trade>
+}
+
+/* what cancelling an order took off one of the books it went to */
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Cancelled {
+    pub book: BookId,
+    pub result: CancelResult
+}
+
+/* how many idempotency keys an exchange remembers unless told otherwise */
+pub const DEFAULT_IDEMPOTENCY_RETENTION: usize = 10_000;
+
+/* the most recent keyed submissions, and what each placed */
+#[derive(Debug)]
+struct Submitted {
+    placed: HashMap<(AccountId, String), (OrderRequest, Placed)>,
+    /* oldest first */
+    keys: VecDeque<(AccountId, String)>,
+    retention: usize
+}
+
+impl Submitted {
+    fn new(retention: usize) -> Submitted {
+        Submitted {
+            placed: HashMap::new(),
+            keys: VecDeque::new(),
+            retention
+        }
+    }
+
+    fn get(&self, account: AccountId, key: &str) ->
+        Option<&(OrderRequest, Placed)> {
+        self.placed.get(&(account, key.to_string()))
+    }
+
+    fn remember(&mut self, key: String, request: OrderRequest,
+                placed: Placed) {
+        let key: (AccountId, String) = (request.account, key);
+
+        self.keys.push_back(key.clone());
+        self.placed.insert(key, (request, placed));
+
+        while self.keys.len() > self.retention {
+            if let Some(oldest) = self.keys.pop_front() {
+                self.placed.remove(&oldest);
+            }
+        }
+    }
+}
+
+/* The books of an `OrderRouter` along with the accounts allowed to trade
+ * on them, taking orders the way a venue does: from registered accounts,
+ * with IDs of its own choosing, by ticker rather than by book. What the
+ * `rest` feature serves; usable directly for an in-process venue. */
+#[derive(Debug)]
+pub struct Exchange<M: MatchingPolicy = PriceTime, S: EventSink = MemorySink,
+                    C: Clock = SystemClock, P: PriceType = F64Price,
+                    G: OrderIdGenerator = MonotonicIdGenerator> {
+    router: OrderRouter<M, S, C, P>,
+    accounts: HashMap<AccountId, Account>,
+    ids: G,
+    /* the ticker of every order placed */
+    placed: HashMap<OrderId, String>,
+    submitted: Submitted,
+    settlement: Settlement,
+    /* the last trade of each book handed to settlement */
+    settled: HashMap<BookId, TradeId>
+}
+
+impl<M: MatchingPolicy, S: EventSink, C: Clock, P: PriceType>
+    Exchange<M, S, C, P> {
+    /* Trades the router's books made before now are not the exchange's to
+     * settle; everything they trade from here on is, however it came
+     * about. */
+    pub fn new(router: OrderRouter<M, S, C, P>) -> Exchange<M, S, C, P> {
+        let settled: HashMap<BookId, TradeId> = router.iter_books()
+            .filter_map(|book| book.get_trades().last()
+                        .map(|trade| (book.get_id(), trade.get_id())))
+            .collect();
+
+        Exchange {
+            router,
+            accounts: HashMap::new(),
+            ids: MonotonicIdGenerator::new(),
+            placed: HashMap::new(),
+            submitted: Submitted::new(DEFAULT_IDEMPOTENCY_RETENTION),
+            settlement: Settlement::default(),
+            settled
+        }
+    }
+}
+
+impl<M: MatchingPolicy, S: EventSink, C: Clock, P: PriceType,
+     G: OrderIdGenerator> Exchange<M, S, C, P, G> {
+    /* gives orders IDs from `ids` rather than counting from one */
+    pub fn with_id_generator<H: OrderIdGenerator>(self, ids: H) ->
+        Exchange<M, S, C, P, H> {
+        Exchange {
+            router: self.router,
+            accounts: self.accounts,
+            ids,
+            placed: self.placed,
+            submitted: self.submitted,
+            settlement: self.settlement,
+            settled: self.settled
+        }
+    }
+
+    /* when trades move the accounts' cash and holdings; immediately by
+     * default */
+    pub fn settlement_cycle(mut self, cycle: SettlementCycle) ->
+        Exchange<M, S, C, P, G> {
+        self.settlement = Settlement::new(cycle);
+        self
+    }
+
+    pub fn get_settlement(&self) -> &Settlement {
+        &self.settlement
+    }
+
+    /* Settles the trades made since the last settlement, e.g. at the end
+     * of the day; see `Settlement::settle`. */
+    pub fn settle(&mut self, at: DateTime<Utc>) -> SettlementReport {
+        self.record_trades();
+        self.settlement.settle(at, &mut self.accounts)
+    }
+
+    /* Hands settlement every trade the books have made since it was last
+     * called, not just those of orders submitted here: resting orders hit
+     * by orders given to a book directly, an auction uncrossing, pegs
+     * repricing into the market. Books are taken in ID order, each book's
+     * trades in the order it made them. */
+    fn record_trades(&mut self) {
+        let mut books: Vec<&Book<M, S, C, P>> =
+            self.router.iter_books().collect();
+        books.sort_by_key(|book| book.get_id());
+
+        for book in books {
+            let last: TradeId = self.settled.get(&book.get_id()).copied()
+                .unwrap_or(0);
+            let ticker: String = book.get_ticker();
+
+            for trade in book.get_trades().iter()
+                .filter(|trade| trade.get_id() > last) {
+                self.settlement.record(&ticker, trade, &mut self.accounts);
+                self.settled.insert(book.get_id(), trade.get_id());
+            }
+        }
+    }
+
+    /* how many idempotency keys to remember, most recent first; a key
+     * older than that places its order again */
+    pub fn idempotency_retention(mut self, retention: usize) ->
+        Exchange<M, S, C, P, G> {
+        self.submitted.retention = retention;
+        self
+    }
+
+    /* Replaces any account with the same ID. Orders carry a copy of their
+     * owner as it was when they were placed; the exchange's own copy is
+     * the one trades settle against. */
+    pub fn add_account(&mut self, account: Account) {
+        self.accounts.insert(account.get_id(), account);
+    }
+
+    pub fn get_account(&self, id: AccountId) -> Option<&Account> {
+        self.accounts.get(&id)
+    }
+
+    pub fn get_router(&self) -> &OrderRouter<M, S, C, P> {
+        &self.router
+    }
+
+    pub fn get_router_mut(&mut self) -> &mut OrderRouter<M, S, C, P> {
+        &mut self.router
+    }
+
+    /* Routes `request` to its ticker's books; see `OrderRouter::route`.
+     * The trades are those it made, in the order the books made them. A
+     * request with an idempotency key already used for the same request
+     * gives back what that placed. One that failed before placing anything
+     * is not remembered, so may be retried; one that failed part way is
+     * remembered with the shares it did place, so that a retry gives those
+     * back rather than placing the order again. */
+    pub fn submit(&mut self, request: OrderRequest) ->
+        Result<Placed, ExchangeError> {
+        let keyed: Option<(String, OrderRequest)> =
+            match &request.idempotency_key {
+                Some(key) => match self.submitted.get(request.account, key) {
+                    Some((original, placed)) if *original == request =>
+                        return Ok(placed.clone()),
+                    Some(_) => return Err(
+                        ExchangeError::IdempotencyConflict(key.clone())),
+                    None => Some((key.clone(), request.clone()))
+                },
+                None => None
+            };
+        /* so that the owner is placed as of every trade so far */
+        self.record_trades();
+
+        let owner: Account = self.accounts.get(&request.account)
+            .cloned()
+            .ok_or(ExchangeError::UnknownAccount(request.account))?;
+        let id: OrderId = self.ids.next_id();
+        let traded: HashMap<BookId, usize> =
+            self.router.get_books(&request.ticker).into_iter()
+                .map(|book| (book.get_id(), book.get_trades().len()))
+                .collect();
+        let mut order: Order = Order::new(id, owner, request.ticker.clone(),
+                                          request.side, request.price,
+                                          request.quantity);
+
+        if let Some(client_id) = request.client_id {
+            order.set_client_id(client_id);
+        }
+
+        for (key, value) in request.tags {
+            order.set_tag(key, value);
+        }
+
+        /* shares routed before any refusal stand, and may be cancelled */
+        self.placed.insert(id, request.ticker.clone());
+
+        let (allocations, routed) = self.router.route_partial(order);
+        let trades: Vec<Trade> = allocations.iter()
+            .filter_map(|(book, _)| self.router.get_book(*book).ok())
+            .flat_map(|book| book.get_trades().iter()
+                      .skip(traded.get(&book.get_id()).copied()
+                            .unwrap_or(0)))
+            .filter(|trade| trade.get_aggressor() == id)
+            .copied()
+            .collect();
+
+        /* including those of a book that traded some of its share before
+         * refusing the rest */
+        self.record_trades();
+
+        let placed: Placed = Placed {id, allocations, trades};
+
+        if let Some((key, request)) = keyed {
+            if routed.is_ok() || !placed.allocations.is_empty() {
+                self.submitted.remember(key, request, placed.clone());
+            }
+        }
+
+        routed?;
+        Ok(placed)
+    }
+
+    /* cancels what is left of order `id` on every book it rests on */
+    pub fn cancel(&mut self, id: OrderId) ->
+        Result<Vec<Cancelled>, ExchangeError> {
+        let ticker: &String = self.placed.get(&id)
+            .ok_or(ExchangeError::OrderNotFound(id))?;
+        let books: Vec<BookId> = self.router.get_books(ticker).into_iter()
+            .filter(|book| book.get_order(id).is_ok())
+            .map(Book::get_id)
+            .collect();
+        let mut cancelled: Vec<Cancelled> = vec![];
+
+        for book in books {
+            let result: CancelResult = self.router.get_book_mut(book)?
+                .cancel(id)?;
+
+            cancelled.push(Cancelled {book, result});
+        }
+
+        if cancelled.is_empty() {
+            return Err(ExchangeError::OrderNotFound(id));
+        }
+
+        self.record_trades();
+        Ok(cancelled)
+    }
+
+    fn check_symbol(&self, ticker: &str) -> Result<(), ExchangeError> {
+        if !self.router.has_symbol(ticker) {
+            return Err(RouterError::UnknownSymbol(ticker.to_string()).into());
+        }
+
+        Ok(())
+    }
+
+    /* the depth of every book for `ticker`, merged */
+    pub fn levels(&self, ticker: &str) -> Result<Levels, ExchangeError> {
+        self.check_symbol(ticker)?;
+        Ok(self.router.consolidated(ticker).levels())
+    }
+
+    /* every trade in `ticker`, oldest first */
+    pub fn trades(&self, ticker: &str) ->
+        Result<Vec<VenueTrade>, ExchangeError> {
+        self.check_symbol(ticker)?;
+        Ok(self.router.consolidated(ticker).tape().to_vec())
+    }
+
+    /* each of `ticker`'s books at a glance, first added first */
+    pub fn stats(&self, ticker: &str) ->
+        Result<Vec<BookStats>, ExchangeError> {
+        self.check_symbol(ticker)?;
+        Ok(self.router.get_books(ticker).into_iter()
+           .map(Book::stats)
+           .collect())
+    }
+}
+
+#[cfg(all(test, not(feature = "decimal-quantity")))]
+mod tests {
+    use super::*;
+    use crate::router::RoutingStrategy;
+
+    fn build_exchange() -> Result<Exchange, ExchangeError> {
+        let mut router: OrderRouter = OrderRouter::new(
+            RoutingStrategy::Primary);
+
+        router.add_book(Book::new(1, "Book".to_string(),
+                                  "BOOK".to_string()))?;
+
+        let mut exchange: Exchange = Exchange::new(router);
+
+        for id in 1..=2 {
+            let mut holdings: HashMap<String, Quantity> = HashMap::new();
+            holdings.insert("BOOK".to_string(), 1000);
+
+            exchange.add_account(Account::new(id, "Account".to_string(),
+                                              12000.00, holdings));
+        }
+
+        Ok(exchange)
+    }
+
+    fn request(account: AccountId, side: OrderType, price: f64,
+               quantity: Quantity) -> OrderRequest {
+        OrderRequest {
+            ticker: "BOOK".to_string(),
+            account,
+            side,
+            price,
+            quantity,
+            client_id: None,
+            tags: BTreeMap::new(),
+            idempotency_key: None
+        }
+    }
+
+    #[test]
+    fn test_exchange() -> Result<(), ExchangeError> {
+        let mut exchange: Exchange = build_exchange()?;
+
+        let ask: Placed = exchange.submit(request(1, OrderType::Ask, 12.00,
+                                                  10))?;
+        let bid: Placed = exchange.submit(request(2, OrderType::Bid, 12.00,
+                                                  4))?;
+
+        assert_eq!((ask.id, bid.id), (1, 2));
+        assert_eq!(bid.allocations, vec![(1, 4)]);
+        assert_eq!(bid.trades.iter().map(Trade::get_resting)
+                       .collect::<Vec<OrderId>>(), vec![1]);
+        assert_eq!(exchange.levels("BOOK")?.get_asks(), &[(12.00, 6)]);
+        assert_eq!(exchange.trades("BOOK")?.len(), 1);
+        assert_eq!(exchange.stats("BOOK")?[0].volume, 4);
+
+        /* trades settle against the exchange's accounts as they happen */
+        assert_eq!(exchange.get_account(2).map(Account::get_balance),
+                   Some(12000.00 - 48.00));
+        assert_eq!(exchange.settle(Utc::now()).get_obligation(1, "BOOK")
+                       .map(|obligation| obligation.sold), Some(4));
+
+        assert!(matches!(exchange.submit(request(3, OrderType::Bid, 12.00,
+                                                 4)),
+                         Err(ExchangeError::UnknownAccount(3))));
+        assert!(matches!(exchange.levels("NONE"),
+                         Err(ExchangeError::Router(
+                             RouterError::UnknownSymbol(_)))));
+
+        let cancelled: Vec<Cancelled> = exchange.cancel(ask.id)?;
+
+        assert_eq!(cancelled[0].result.remaining_cancelled, 6);
+        assert!(matches!(exchange.cancel(ask.id),
+                         Err(ExchangeError::OrderNotFound(1))));
+        Ok(())
+    }
+
+    #[test]
+    fn test_id_generator() -> Result<(), ExchangeError> {
+        use crate::id::SnowflakeIdGenerator;
+
+        let mut exchange: Exchange<PriceTime, MemorySink, SystemClock,
+                                   F64Price, SnowflakeIdGenerator> =
+            build_exchange()?.with_id_generator(SnowflakeIdGenerator::new(7));
+        let first: OrderId = exchange.submit(request(1, OrderType::Ask, 12.00,
+                                                     10))?.id;
+        let second: OrderId = exchange.submit(request(1, OrderType::Ask,
+                                                      12.00, 10))?.id;
+
+        assert!(second > first);
+        assert_eq!(SnowflakeIdGenerator::decode(first).0, 7);
+        Ok(())
+    }
+
+    #[test]
+    fn test_settles_trades_made_outside_submit() -> Result<(), ExchangeError> {
+        let mut exchange: Exchange = build_exchange()?;
+        let ask: Placed = exchange.submit(request(1, OrderType::Ask, 12.00,
+                                                  10))?;
+        let buyer: Account = exchange.get_account(2).unwrap().clone();
+
+        /* the resting ask is hit by an order the exchange never saw */
+        exchange.get_router_mut().get_book_mut(1)?
+            .submit(Order::new(100, buyer, "BOOK".to_string(), OrderType::Bid,
+                               12.00, 4))?;
+
+        let report: SettlementReport = exchange.settle(Utc::now());
+
+        assert!(report.failures.is_empty());
+        assert_eq!(report.get_obligation(1, "BOOK")
+                       .map(|obligation| obligation.sold), Some(4));
+        assert_eq!(exchange.get_account(1).map(Account::get_balance),
+                   Some(12000.00 + 48.00));
+        assert_eq!(exchange.get_account(2).map(Account::get_balance),
+                   Some(12000.00 - 48.00));
+
+        /* and only once */
+        exchange.cancel(ask.id)?;
+        assert!(exchange.settle(Utc::now()).obligations.is_empty());
+        assert_eq!(exchange.get_account(1).map(Account::get_balance),
+                   Some(12000.00 + 48.00));
+        Ok(())
+    }
+
+    #[test]
+    fn test_idempotency_keys() -> Result<(), ExchangeError> {
+        let mut exchange: Exchange = build_exchange()?
+            .idempotency_retention(1);
+        let keyed = |account: AccountId, key: &str, quantity: Quantity| {
+            OrderRequest {
+                idempotency_key: Some(key.to_string()),
+                ..request(account, OrderType::Ask, 12.00, quantity)
+            }
+        };
+
+        let first: Placed = exchange.submit(keyed(1, "a", 10))?;
+
+        /* a retry places nothing new */
+        assert_eq!(exchange.submit(keyed(1, "a", 10))?, first);
+        assert_eq!(exchange.levels("BOOK")?.get_asks(), &[(12.00, 10)]);
+        assert!(matches!(exchange.submit(keyed(1, "a", 5)),
+                         Err(ExchangeError::IdempotencyConflict(_))));
+
+        /* keys are per account, and only so many are remembered */
+        let other: Placed = exchange.submit(keyed(2, "a", 10))?;
+
+        assert_ne!(other.id, first.id);
+        assert_ne!(exchange.submit(keyed(1, "a", 10))?.id, first.id);
+        assert_eq!(exchange.levels("BOOK")?.get_asks(), &[(12.00, 30)]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_idempotent_retry_after_partial_routing() ->
+        Result<(), ExchangeError> {
+        use crate::margin::MarginModel;
+
+        let mut router: OrderRouter = OrderRouter::new(RoutingStrategy::Split);
+
+        router.add_book(Book::new(1, "Book".to_string(), "BOOK".to_string()))?;
+        router.add_book(Book::builder(2, "BOOK".to_string())
+                        .margin(MarginModel::Cash)
+                        .build()
+                        .unwrap())?;
+
+        let mut exchange: Exchange = Exchange::new(router);
+        let mut holdings: HashMap<String, Quantity> = HashMap::new();
+        holdings.insert("BOOK".to_string(), 1000);
+
+        exchange.add_account(Account::new(1, "Account".to_string(), 12000.00,
+                                          holdings));
+        /* can pay for one book's share, but not both */
+        exchange.add_account(Account::new(2, "Account".to_string(), 100.00,
+                                          HashMap::new()));
+        exchange.submit(request(1, OrderType::Ask, 12.00, 5))?;
+
+        let seller: Account = exchange.get_account(1).unwrap().clone();
+        exchange.get_router_mut().get_book_mut(2)?
+            .submit(Order::new(100, seller, "BOOK".to_string(), OrderType::Ask,
+                               12.00, 10))?;
+
+        let keyed: OrderRequest = OrderRequest {
+            idempotency_key: Some("a".to_string()),
+            ..request(2, OrderType::Bid, 12.00, 15)
+        };
+
+        /* the first book's share trades, the second book's is refused */
+        assert!(matches!(exchange.submit(keyed.clone()),
+                         Err(ExchangeError::Router(RouterError::Book(
+                             BookError::InsufficientBuyingPower { .. })))));
+        assert_eq!(exchange.trades("BOOK")?.len(), 1);
+
+        /* retrying gives back what was placed, without placing it again */
+        let retried: Placed = exchange.submit(keyed)?;
+
+        assert_eq!(retried.allocations, vec![(1, 5)]);
+        assert_eq!(retried.trades.len(), 1);
+        assert_eq!(exchange.trades("BOOK")?.len(), 1);
+        assert_eq!(exchange.get_account(2).map(Account::get_balance),
+                   Some(100.00 - 60.00));
+        Ok(())
+    }
+}
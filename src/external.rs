@@ -0,0 +1,194 @@
+use serde::{Serialize, Deserialize};
+use serde_json::Number;
+
+use crate::levels::*;
+use crate::quantity::{self, Quantity};
+
+/* Converters between `Levels` and the depth snapshots served by exchange
+ * REST APIs, so that a book can be seeded from (and published back as) the
+ * shape most market data tooling already understands. */
+
+#[derive(Debug, thiserror::Error)]
+pub enum ExternalError {
+    #[error("malformed JSON: {0}")]
+    MalformedJson(#[from] serde_json::Error),
+    #[error("invalid price {0:?}")]
+    InvalidPrice(String),
+    #[error("invalid quantity {0:?}")]
+    InvalidQuantity(String),
+    #[error("invalid {0} {1:?}")]
+    InvalidField(&'static str, String),
+}
+
+/* string-encoded pairs, as served by Binance and friends:
+ *
+ *     {"lastUpdateId": 1027024, "bids": [["4.00000000", "431.00000000"]],
+ *      "asks": [["4.00000200", "12.00000000"]]}
+ */
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StringPairDepth {
+    #[serde(rename = "lastUpdateId", default)]
+    last_update_id: u64,
+    bids: Vec<(String, String)>,
+    asks: Vec<(String, String)>
+}
+
+/* plain numeric pairs: {"bids": [[4.0, 431.0]], "asks": [[4.000002, 12.0]]} */
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct NumberPairDepth {
+    bids: Vec<(Number, Number)>,
+    asks: Vec<(Number, Number)>
+}
+
+pub(crate) fn parse_price(text: &str) -> Result<f64, ExternalError> {
+    match text.trim().parse::<f64>() {
+        Ok(price) if price.is_finite() => Ok(price),
+        _ => Err(ExternalError::InvalidPrice(text.to_string()))
+    }
+}
+
+pub(crate) fn parse_quantity(text: &str) -> Result<Quantity, ExternalError> {
+    quantity::parse(text)
+        .ok_or_else(|| ExternalError::InvalidQuantity(text.to_string()))
+}
+
+fn parse_side<I>(pairs: I) -> Result<Vec<Level>, ExternalError>
+    where I: Iterator<Item=(String, String)> {
+    pairs.map(|(price, quantity)| Ok((parse_price(&price)?,
+                                      parse_quantity(&quantity)?)))
+        .collect()
+}
+
+impl StringPairDepth {
+    pub fn get_last_update_id(&self) -> u64 {
+        self.last_update_id
+    }
+
+    pub fn from_levels(levels: &Levels, last_update_id: u64) ->
+        StringPairDepth {
+        let format = |side: &[Level]| side.iter()
+            .map(|(price, quantity)| (price.to_string(), quantity.to_string()))
+            .collect();
+
+        StringPairDepth {
+            last_update_id,
+            bids: format(levels.get_bids()),
+            asks: format(levels.get_asks())
+        }
+    }
+
+    pub fn to_levels(&self) -> Result<Levels, ExternalError> {
+        Ok(Levels::new(parse_side(self.bids.iter().cloned())?,
+                       parse_side(self.asks.iter().cloned())?))
+    }
+
+    pub fn from_json(json: &str) -> Result<StringPairDepth, ExternalError> {
+        Ok(serde_json::from_str(json)?)
+    }
+
+    pub fn to_json(&self) -> Result<String, ExternalError> {
+        Ok(serde_json::to_string(self)?)
+    }
+}
+
+impl NumberPairDepth {
+    pub fn from_levels(levels: &Levels) -> Result<NumberPairDepth,
+                                                   ExternalError> {
+        let format = |side: &[Level]| side.iter()
+            .map(|(price, quantity)| {
+                let price: Number = Number::from_f64(*price)
+                    .ok_or_else(|| ExternalError::InvalidPrice(
+                            price.to_string()))?;
+                let quantity: Number = quantity.to_string().parse()
+                    .map_err(|_| ExternalError::InvalidQuantity(
+                            quantity.to_string()))?;
+
+                Ok((price, quantity))
+            })
+            .collect::<Result<Vec<(Number, Number)>, ExternalError>>();
+
+        Ok(NumberPairDepth {
+            bids: format(levels.get_bids())?,
+            asks: format(levels.get_asks())?
+        })
+    }
+
+    pub fn to_levels(&self) -> Result<Levels, ExternalError> {
+        let stringify = |side: &[(Number, Number)]| side.iter()
+            .map(|(p, q)| (p.to_string(), q.to_string()))
+            .collect::<Vec<(String, String)>>()
+            .into_iter();
+
+        Ok(Levels::new(parse_side(stringify(&self.bids))?,
+                       parse_side(stringify(&self.asks))?))
+    }
+
+    pub fn from_json(json: &str) -> Result<NumberPairDepth, ExternalError> {
+        Ok(serde_json::from_str(json)?)
+    }
+
+    pub fn to_json(&self) -> Result<String, ExternalError> {
+        Ok(serde_json::to_string(self)?)
+    }
+}
+
+#[cfg(all(test, not(feature = "decimal-quantity")))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_string_pairs_from_json() -> Result<(), ExternalError> {
+        let json: &str = r#"{"lastUpdateId": 1027024,
+            "bids": [["4.00000000", "431.00000000"], ["3.5", "12"]],
+            "asks": [["4.00000200", "12.00000000"]]}"#;
+
+        let actual_depth: StringPairDepth = StringPairDepth::from_json(json)?;
+        let expected_levels: Levels = Levels::new(
+            vec![(4.00, 431), (3.50, 12)],
+            vec![(4.000002, 12)]);
+
+        assert_eq!(actual_depth.get_last_update_id(), 1027024);
+        assert_eq!(actual_depth.to_levels()?, expected_levels);
+        Ok(())
+    }
+
+    #[test]
+    fn test_string_pairs_round_trip() -> Result<(), ExternalError> {
+        let expected_levels: Levels = Levels::new(
+            vec![(12.50, 100), (12.00, 3)],
+            vec![(13.25, 7)]);
+
+        let json: String =
+            StringPairDepth::from_levels(&expected_levels, 7).to_json()?;
+        let actual_levels: Levels =
+            StringPairDepth::from_json(&json)?.to_levels()?;
+
+        assert_eq!(actual_levels, expected_levels);
+        Ok(())
+    }
+
+    #[test]
+    fn test_number_pairs_round_trip() -> Result<(), ExternalError> {
+        let expected_levels: Levels = Levels::new(
+            vec![(12.50, 100)],
+            vec![(13.25, 7), (14.00, 1)]);
+
+        let json: String =
+            NumberPairDepth::from_levels(&expected_levels)?.to_json()?;
+        let actual_levels: Levels =
+            NumberPairDepth::from_json(&json)?.to_levels()?;
+
+        assert_eq!(actual_levels, expected_levels);
+        Ok(())
+    }
+
+    #[test]
+    fn test_fractional_quantity_rejected() {
+        let json: &str = r#"{"bids": [["4.0", "0.5"]], "asks": []}"#;
+
+        let result: Result<Levels, ExternalError> =
+            StringPairDepth::from_json(json).and_then(|d| d.to_levels());
+
+        assert!(matches!(result, Err(ExternalError::InvalidQuantity(_))));
+    }
+}
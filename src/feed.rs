@@ -0,0 +1,600 @@
+use std::collections::{BTreeMap, HashMap, VecDeque};
+
+use chrono::{DateTime, Utc};
+use ordered_float::OrderedFloat;
+use serde::{Serialize, Deserialize};
+
+use crate::event::{Event, EventKind};
+use crate::integrity::Violation;
+use crate::order::*;
+use crate::quantity::{self, Quantity, ZERO};
+use crate::sink::{EventSink, SinkError};
+use crate::snapshot::BookSnapshot;
+
+pub mod normalize;
+
+/* Exchange style market data, derived from a book's events: market by
+ * order (MBO), every change to every resting order, and market by price
+ * (MBP), every change to the aggregate size at each level. Both need the
+ * book's resting orders to start from, so begin either from a snapshot or
+ * from the book's very first event. Hidden orders are left out of both. */
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum MboAction {
+    Add,
+    Modify,
+    Delete,
+    /* a resting order traded; one that traded all it had is gone */
+    Fill
+}
+
+/* For adds and modifies, `price` and `quantity` are the order's terms
+ * afterwards; for deletes, what was removed; for fills, what traded, and at
+ * what price. */
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MboMessage {
+    seq: u64,
+    timestamp: DateTime<Utc>,
+    action: MboAction,
+    order: OrderId,
+    side: OrderType,
+    price: f64,
+    quantity: Quantity
+}
+
+impl MboMessage {
+    pub fn get_seq(&self) -> u64 {
+        self.seq
+    }
+
+    pub fn get_timestamp(&self) -> DateTime<Utc> {
+        self.timestamp
+    }
+
+    pub fn get_action(&self) -> MboAction {
+        self.action
+    }
+
+    pub fn get_order(&self) -> OrderId {
+        self.order
+    }
+
+    pub fn get_side(&self) -> OrderType {
+        self.side
+    }
+
+    pub fn get_price(&self) -> f64 {
+        self.price
+    }
+
+    pub fn get_quantity(&self) -> Quantity {
+        self.quantity
+    }
+}
+
+/* the new aggregate size at a level; zero once the level is empty */
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MbpUpdate {
+    seq: u64,
+    timestamp: DateTime<Utc>,
+    side: OrderType,
+    price: f64,
+    quantity: Quantity
+}
+
+impl MbpUpdate {
+    pub fn get_seq(&self) -> u64 {
+        self.seq
+    }
+
+    pub fn get_timestamp(&self) -> DateTime<Utc> {
+        self.timestamp
+    }
+
+    pub fn get_side(&self) -> OrderType {
+        self.side
+    }
+
+    pub fn get_price(&self) -> f64 {
+        self.price
+    }
+
+    pub fn get_quantity(&self) -> Quantity {
+        self.quantity
+    }
+}
+
+/* the resting orders and levels as consumers of the feeds see them */
+#[derive(Debug, Clone, Default, PartialEq)]
+struct FeedState {
+    orders: HashMap<OrderId, (OrderType, f64, Quantity)>,
+    bids: BTreeMap<OrderedFloat<f64>, Quantity>,
+    asks: BTreeMap<OrderedFloat<f64>, Quantity>,
+    /* where the events took off more than the feed thought was there */
+    violations: Vec<Violation>
+}
+
+impl FeedState {
+    fn from_snapshot(snapshot: &BookSnapshot) -> FeedState {
+        let mut state: FeedState = FeedState::default();
+
+        for order in snapshot.bids.iter().chain(snapshot.asks.iter())
+            .filter(|order| !order.is_hidden()) {
+            state.insert(order.get_id(), order.get_order_type(),
+                         order.get_price(), order.get_quantity());
+        }
+
+        state
+    }
+
+    fn level_mut(&mut self, side: &OrderType, price: f64) -> &mut Quantity {
+        let levels: &mut BTreeMap<OrderedFloat<f64>, Quantity> = match side {
+            OrderType::Bid => &mut self.bids,
+            OrderType::Ask => &mut self.asks
+        };
+
+        levels.entry(OrderedFloat::from(price)).or_insert(ZERO)
+    }
+
+    fn level(&self, side: &OrderType, price: f64) -> Quantity {
+        let levels: &BTreeMap<OrderedFloat<f64>, Quantity> = match side {
+            OrderType::Bid => &self.bids,
+            OrderType::Ask => &self.asks
+        };
+
+        levels.get(&OrderedFloat::from(price)).copied().unwrap_or(ZERO)
+    }
+
+    fn insert(&mut self, id: OrderId, side: OrderType, price: f64,
+              quantity: Quantity) {
+        *self.level_mut(&side, price) += quantity;
+        self.orders.insert(id, (side, price, quantity));
+    }
+
+    /* Takes `quantity` off a resting order, removing it if none is left.
+     * Taking off more than is left means the feed has drifted from the
+     * book; that is recorded, and the order and its level go no lower than
+     * nothing. */
+    fn reduce(&mut self, id: OrderId, quantity: Quantity) {
+        let (side, price, available) = match self.orders.get(&id) {
+            Some(order) => *order,
+            None => return
+        };
+        let remaining: Quantity = match quantity::checked_sub(available,
+                                                              quantity) {
+            Some(remaining) => remaining,
+            None => {
+                self.violations.push(Violation::Overdrawn {
+                    side,
+                    price,
+                    id,
+                    available,
+                    taken: quantity
+                });
+                ZERO
+            }
+        };
+        let level: &mut Quantity = self.level_mut(&side, price);
+
+        *level = quantity::saturating_sub(*level, available - remaining);
+
+        if *level == ZERO {
+            self.remove_level(&side, price);
+        }
+
+        if remaining == ZERO {
+            self.orders.remove(&id);
+        } else {
+            self.orders.insert(id, (side, price, remaining));
+        }
+    }
+
+    fn remove_level(&mut self, side: &OrderType, price: f64) {
+        match side {
+            OrderType::Bid => self.bids.remove(&OrderedFloat::from(price)),
+            OrderType::Ask => self.asks.remove(&OrderedFloat::from(price))
+        };
+    }
+
+    /* the MBO messages for `event`, and the levels they touched */
+    fn apply(&mut self, event: &Event) ->
+        (Vec<MboMessage>, Vec<(OrderType, f64)>) {
+        let mut messages: Vec<MboMessage> = vec![];
+        let mut touched: Vec<(OrderType, f64)> = vec![];
+        let mut message = |action: MboAction, order: OrderId,
+                           side: &OrderType, price: f64,
+                           quantity: Quantity| {
+            messages.push(MboMessage {
+                seq: event.get_seq(),
+                timestamp: event.get_timestamp(),
+                action,
+                order,
+                side: *side,
+                price,
+                quantity
+            });
+        };
+
+        match event.get_kind() {
+            /* hidden orders are never published, so nothing that happens
+             * to them is either */
+            EventKind::Post { hidden: true, .. } |
+            EventKind::Amend { hidden: true, .. } |
+            EventKind::Reprice { hidden: true, .. } => {},
+            /* an amended order is posted again once it has been through
+             * matching, by which time it is already known, and may well be
+             * unchanged */
+            EventKind::Post { order, order_type, price, quantity, .. } |
+            EventKind::Amend { order, order_type, price, quantity, .. } |
+            EventKind::Reprice { order, order_type, price, quantity, .. } => {
+                let action: MboAction = match self.orders.get(order) {
+                    Some(known) if *known == (*order_type, *price,
+                                              *quantity) =>
+                        return (messages, touched),
+                    Some((side, previous, remaining)) => {
+                        touched.push((*side, *previous));
+                        self.reduce(*order, *remaining);
+                        MboAction::Modify
+                    },
+                    None => MboAction::Add
+                };
+
+                message(action, *order, order_type, *price, *quantity);
+                self.insert(*order, *order_type, *price, *quantity);
+                touched.push((*order_type, *price));
+            },
+            EventKind::Cancel { order, .. } => {
+                if let Some((side, price, remaining)) =
+                    self.orders.get(order).cloned() {
+                    message(MboAction::Delete, *order, &side, price,
+                            remaining);
+                    self.reduce(*order, remaining);
+                    touched.push((side, price));
+                }
+            },
+            EventKind::Reduce { order, quantity, remaining, .. } => {
+                if let Some((side, price, _)) =
+                    self.orders.get(order).cloned() {
+                    message(MboAction::Modify, *order, &side, price,
+                            *remaining);
+                    self.reduce(*order, *quantity);
+                    touched.push((side, price));
+                }
+            },
+            /* in continuous trading the aggressor never rested, so only
+             * the resting order is known; in an auction both are */
+            EventKind::Match(trade) => {
+                for id in [trade.get_resting(), trade.get_aggressor()] {
+                    if let Some((side, price, _)) =
+                        self.orders.get(&id).cloned() {
+                        message(MboAction::Fill, id, &side, trade.get_price(),
+                                trade.get_quantity());
+                        self.reduce(id, trade.get_quantity());
+                        touched.push((side, price));
+                    }
+                }
+            },
+            _ => {}
+        }
+
+        (messages, touched)
+    }
+
+    fn updates(&self, event: &Event, touched: Vec<(OrderType, f64)>) ->
+        Vec<MbpUpdate> {
+        let mut updates: Vec<MbpUpdate> = vec![];
+
+        for (side, price) in touched {
+            if updates.iter().any(|u| u.side == side && u.price == price) {
+                continue;
+            }
+
+            updates.push(MbpUpdate {
+                seq: event.get_seq(),
+                timestamp: event.get_timestamp(),
+                quantity: self.level(&side, price),
+                side,
+                price
+            });
+        }
+
+        updates
+    }
+}
+
+/* Turns events into MBO messages. As a sink, it holds on to the messages
+ * until they are taken. */
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MboFeed {
+    state: FeedState,
+    messages: Vec<MboMessage>
+}
+
+impl MboFeed {
+    /* for a book that has not had any events yet */
+    pub fn new() -> MboFeed {
+        MboFeed::default()
+    }
+
+    pub fn from_snapshot(snapshot: &BookSnapshot) -> MboFeed {
+        MboFeed {
+            state: FeedState::from_snapshot(snapshot),
+            messages: vec![]
+        }
+    }
+
+    pub fn translate(&mut self, event: &Event) -> Vec<MboMessage> {
+        self.state.apply(event).0
+    }
+
+    pub fn take_messages(&mut self) -> Vec<MboMessage> {
+        std::mem::take(&mut self.messages)
+    }
+
+    /* every inconsistency between the events and the feed so far */
+    pub fn get_violations(&self) -> &[Violation] {
+        &self.state.violations
+    }
+
+    pub fn take_violations(&mut self) -> Vec<Violation> {
+        std::mem::take(&mut self.state.violations)
+    }
+}
+
+impl EventSink for MboFeed {
+    fn write(&mut self, event: &Event) -> Result<(), SinkError> {
+        let messages: Vec<MboMessage> = self.translate(event);
+
+        self.messages.extend(messages);
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), SinkError> {
+        Ok(())
+    }
+}
+
+/* Turns events into MBP updates, one per level an event changed. As a
+ * sink, it holds on to the updates until they are taken. */
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MbpFeed {
+    state: FeedState,
+    updates: Vec<MbpUpdate>
+}
+
+impl MbpFeed {
+    /* for a book that has not had any events yet */
+    pub fn new() -> MbpFeed {
+        MbpFeed::default()
+    }
+
+    pub fn from_snapshot(snapshot: &BookSnapshot) -> MbpFeed {
+        MbpFeed {
+            state: FeedState::from_snapshot(snapshot),
+            updates: vec![]
+        }
+    }
+
+    pub fn translate(&mut self, event: &Event) -> Vec<MbpUpdate> {
+        let (_, touched) = self.state.apply(event);
+
+        self.state.updates(event, touched)
+    }
+
+    pub fn take_updates(&mut self) -> Vec<MbpUpdate> {
+        std::mem::take(&mut self.updates)
+    }
+
+    /* every inconsistency between the events and the feed so far */
+    pub fn get_violations(&self) -> &[Violation] {
+        &self.state.violations
+    }
+
+    pub fn take_violations(&mut self) -> Vec<Violation> {
+        std::mem::take(&mut self.state.violations)
+    }
+}
+
+impl EventSink for MbpFeed {
+    fn write(&mut self, event: &Event) -> Result<(), SinkError> {
+        let updates: Vec<MbpUpdate> = self.translate(event);
+
+        self.updates.extend(updates);
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), SinkError> {
+        Ok(())
+    }
+}
+
+/* the MBO messages for a stream of events; see `mbo` */
+#[derive(Debug, Clone)]
+pub struct MboIter<I> {
+    events: I,
+    feed: MboFeed,
+    ready: VecDeque<MboMessage>
+}
+
+impl<I: Iterator<Item=Event>> Iterator for MboIter<I> {
+    type Item = MboMessage;
+
+    fn next(&mut self) -> Option<MboMessage> {
+        while self.ready.is_empty() {
+            let event: Event = self.events.next()?;
+            self.ready.extend(self.feed.translate(&event));
+        }
+
+        self.ready.pop_front()
+    }
+}
+
+/* the MBP updates for a stream of events; see `mbp` */
+#[derive(Debug, Clone)]
+pub struct MbpIter<I> {
+    events: I,
+    feed: MbpFeed,
+    ready: VecDeque<MbpUpdate>
+}
+
+impl<I: Iterator<Item=Event>> Iterator for MbpIter<I> {
+    type Item = MbpUpdate;
+
+    fn next(&mut self) -> Option<MbpUpdate> {
+        while self.ready.is_empty() {
+            let event: Event = self.events.next()?;
+            self.ready.extend(self.feed.translate(&event));
+        }
+
+        self.ready.pop_front()
+    }
+}
+
+/* MBO messages for every event of a book from its first */
+pub fn mbo<I: IntoIterator<Item=Event>>(events: I) -> MboIter<I::IntoIter> {
+    MboIter {
+        events: events.into_iter(),
+        feed: MboFeed::new(),
+        ready: VecDeque::new()
+    }
+}
+
+/* MBP updates for every event of a book from its first */
+pub fn mbp<I: IntoIterator<Item=Event>>(events: I) -> MbpIter<I::IntoIter> {
+    MbpIter {
+        events: events.into_iter(),
+        feed: MbpFeed::new(),
+        ready: VecDeque::new()
+    }
+}
+
+#[cfg(all(test, not(feature = "decimal-quantity")))]
+mod tests {
+    use super::*;
+    use crate::account::Account;
+    use crate::book::{Book, BookError};
+
+    fn build_order(id: OrderId, order_type: OrderType, price: f64,
+                   quantity: Quantity) -> Order {
+        let mut holdings: HashMap<String, Quantity> = HashMap::new();
+        holdings.insert("BOOK".to_string(), 1000);
+
+        let owner: Account = Account::new(id, "Account".to_string(),
+                                          12000.00, holdings);
+
+        Order::new(id, owner, "BOOK".to_string(), order_type, price, quantity)
+    }
+
+    fn build_book() -> Result<Book, BookError> {
+        let mut book: Book = Book::new(1, "Book".to_string(),
+                                       "BOOK".to_string());
+
+        book.submit(build_order(1, OrderType::Ask, 12.00, 10))?;
+        book.submit(build_order(2, OrderType::Ask, 12.00, 5))?;
+        book.submit(build_order(3, OrderType::Bid, 12.00, 12))?;
+        book.modify(2, 12.50, 3)?;
+        book.cancel(2)?;
+
+        Ok(book)
+    }
+
+    #[test]
+    fn test_mbo() -> Result<(), BookError> {
+        let book: Book = build_book()?;
+        let actual: Vec<(MboAction, OrderId, f64, Quantity)> =
+            mbo(book.get_events().to_vec())
+                .map(|m| (m.get_action(), m.get_order(), m.get_price(),
+                          m.get_quantity()))
+                .collect();
+        let expected: Vec<(MboAction, OrderId, f64, Quantity)> = vec![
+            (MboAction::Add, 1, 12.00, 10),
+            (MboAction::Add, 2, 12.00, 5),
+            (MboAction::Fill, 1, 12.00, 10),
+            (MboAction::Fill, 2, 12.00, 2),
+            (MboAction::Modify, 2, 12.50, 3),
+            (MboAction::Delete, 2, 12.50, 3)
+        ];
+
+        assert_eq!(actual, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn test_mbp() -> Result<(), BookError> {
+        let book: Book = build_book()?;
+        let actual: Vec<(OrderType, f64, Quantity)> =
+            mbp(book.get_events().to_vec())
+                .map(|u| (u.get_side(), u.get_price(), u.get_quantity()))
+                .collect();
+        let expected: Vec<(OrderType, f64, Quantity)> = vec![
+            (OrderType::Ask, 12.00, 10),
+            (OrderType::Ask, 12.00, 15),
+            (OrderType::Ask, 12.00, 5),
+            (OrderType::Ask, 12.00, 3),
+            (OrderType::Ask, 12.00, 0),
+            (OrderType::Ask, 12.50, 3),
+            (OrderType::Ask, 12.50, 0)
+        ];
+
+        assert_eq!(actual, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn test_feeds_as_sinks() -> Result<(), BookError> {
+        let events: Vec<Event> = build_book()?.get_events().to_vec();
+        let mut mbo_feed: MboFeed = MboFeed::new();
+        let mut mbp_feed: MbpFeed = MbpFeed::new();
+
+        for event in &events {
+            mbo_feed.write(event)?;
+            mbp_feed.write(event)?;
+        }
+
+        let expected_messages: Vec<MboMessage> = mbo(events.clone()).collect();
+        let expected_updates: Vec<MbpUpdate> = mbp(events).collect();
+
+        assert_eq!(mbo_feed.take_messages(), expected_messages);
+        assert_eq!(mbp_feed.take_updates(), expected_updates);
+        assert!(mbo_feed.take_messages().is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_drift() -> Result<(), BookError> {
+        let mut book: Book = Book::new(1, "Book".to_string(),
+                                       "BOOK".to_string());
+        book.submit(build_order(1, OrderType::Ask, 12.00, 10))?;
+        book.submit(build_order(2, OrderType::Ask, 12.00, 5))?;
+
+        let mut feed: MbpFeed = MbpFeed::new();
+
+        for event in book.get_events() {
+            feed.translate(event);
+        }
+
+        /* an event taking off more of order 2 than the feed has */
+        let reduce: Event = Event::new(book.last_seq() + 1, Utc::now(),
+                                       EventKind::Reduce {
+                                           order: 2,
+                                           order_type: OrderType::Ask,
+                                           price: 12.00,
+                                           quantity: 8,
+                                           remaining: 0,
+                                           hidden: false
+                                       });
+        let updates: Vec<MbpUpdate> = feed.translate(&reduce);
+
+        assert_eq!(updates.iter().map(MbpUpdate::get_quantity)
+                       .collect::<Vec<Quantity>>(), vec![10]);
+        assert_eq!(feed.take_violations(), vec![Violation::Overdrawn {
+            side: OrderType::Ask,
+            price: 12.00,
+            id: 2,
+            available: 5,
+            taken: 8
+        }]);
+        assert!(feed.get_violations().is_empty());
+        Ok(())
+    }
+}
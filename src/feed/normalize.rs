@@ -0,0 +1,629 @@
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, TimeZone, Utc};
+use ordered_float::OrderedFloat;
+use serde::{Serialize, Deserialize};
+use serde::de::DeserializeOwned;
+use serde_json::{Number, Value};
+
+use crate::account::{Account, AccountId};
+use crate::batch::{BatchResult, BookOp};
+use crate::book::Book;
+use crate::clock::Clock;
+use crate::external::{self, ExternalError};
+use crate::id::OrderIdGenerator;
+use crate::levels::{Level, Levels, LevelsDelta};
+use crate::matching::MatchingPolicy;
+use crate::order::{Order, OrderId, OrderType};
+use crate::price::PriceType;
+use crate::quantity::{self, Quantity, ZERO};
+use crate::sink::EventSink;
+
+/* Adapters from the depth and trade messages of crypto exchanges' public
+ * WebSocket feeds to ironlobe's own types: full depth as `Levels`,
+ * changes to it as a `LevelsDelta` and trades as `MarketTrade`s. A
+ * `LevelMirror` then keeps a book's levels in step with them.
+ *
+ * Quantities go through `quantity::parse`, so fractional sizes need the
+ * `decimal-quantity` feature. Messages of other kinds (subscription
+ * acknowledgements, heartbeats, tickers) normalize to nothing. */
+
+/* whose message schema to read */
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Venue {
+    /* `<symbol>@depth` diffs, `/api/v3/depth` and `<symbol>@depth<n>`
+     * snapshots, and `trade` and `aggTrade` streams */
+    Binance,
+    /* the Exchange `level2` (`snapshot`, `l2update`) and `matches` (`match`,
+     * `last_match`) channels */
+    Coinbase,
+    /* the v2 `book` and `trade` channels */
+    Kraken
+}
+
+/* a trade reported by a venue, which (unlike a `Trade`) has no orders of
+ * this book's on either side */
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct MarketTrade {
+    timestamp: DateTime<Utc>,
+    price: f64,
+    quantity: Quantity,
+    aggressor_side: Option<OrderType>
+}
+
+impl MarketTrade {
+    pub fn new(timestamp: DateTime<Utc>, price: f64, quantity: Quantity,
+               aggressor_side: Option<OrderType>) -> MarketTrade {
+        MarketTrade {timestamp, price, quantity, aggressor_side}
+    }
+
+    pub fn get_timestamp(&self) -> DateTime<Utc> {
+        self.timestamp
+    }
+
+    pub fn get_price(&self) -> f64 {
+        self.price
+    }
+
+    pub fn get_quantity(&self) -> Quantity {
+        self.quantity
+    }
+
+    /* the side that took liquidity, where the venue says */
+    pub fn get_aggressor_side(&self) -> Option<OrderType> {
+        self.aggressor_side
+    }
+}
+
+/* one thing a venue's message said */
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Normalized {
+    /* the whole book, replacing whatever came before */
+    Snapshot(Levels),
+    /* the new size at each level that changed; zero where one has gone */
+    Delta(LevelsDelta),
+    Trade(MarketTrade)
+}
+
+/* everything in one message from `venue`, in the order it says it */
+pub fn normalize(venue: Venue, json: &str) ->
+    Result<Vec<Normalized>, ExternalError> {
+    let message: Value = serde_json::from_str(json)?;
+
+    match venue {
+        Venue::Binance => binance(message),
+        Venue::Coinbase => coinbase(message),
+        Venue::Kraken => kraken(message)
+    }
+}
+
+fn field(message: &Value, name: &str) -> Option<String> {
+    message.get(name).and_then(Value::as_str).map(str::to_string)
+}
+
+fn parse<T: DeserializeOwned>(message: Value) -> Result<T, ExternalError> {
+    Ok(serde_json::from_value(message)?)
+}
+
+fn millis(millis: i64) -> Result<DateTime<Utc>, ExternalError> {
+    Utc.timestamp_millis_opt(millis).single()
+        .ok_or_else(|| ExternalError::InvalidField("timestamp",
+                                                   millis.to_string()))
+}
+
+fn rfc3339(text: &str) -> Result<DateTime<Utc>, ExternalError> {
+    DateTime::parse_from_rfc3339(text)
+        .map(|timestamp| timestamp.with_timezone(&Utc))
+        .map_err(|_| ExternalError::InvalidField("timestamp",
+                                                 text.to_string()))
+}
+
+fn side(text: &str) -> Result<OrderType, ExternalError> {
+    match text {
+        "buy" => Ok(OrderType::Bid),
+        "sell" => Ok(OrderType::Ask),
+        _ => Err(ExternalError::InvalidField("side", text.to_string()))
+    }
+}
+
+fn opposite(side: OrderType) -> OrderType {
+    match side {
+        OrderType::Bid => OrderType::Ask,
+        OrderType::Ask => OrderType::Bid
+    }
+}
+
+fn level(price: &str, quantity: &str) -> Result<Level, ExternalError> {
+    Ok((external::parse_price(price)?, external::parse_quantity(quantity)?))
+}
+
+/* best price first: highest for bids, lowest for asks */
+fn sorted(mut levels: Vec<Level>, side: OrderType) -> Vec<Level> {
+    levels.sort_by(|(a, _), (b, _)| match side {
+        OrderType::Bid => b.total_cmp(a),
+        OrderType::Ask => a.total_cmp(b)
+    });
+    levels
+}
+
+fn string_pairs(pairs: &[(String, String)], side: OrderType) ->
+    Result<Vec<Level>, ExternalError> {
+    let levels: Vec<Level> = pairs.iter()
+        .map(|(price, quantity)| level(price, quantity))
+        .collect::<Result<Vec<Level>, ExternalError>>()?;
+
+    Ok(sorted(levels, side))
+}
+
+#[derive(Debug, Deserialize)]
+struct PairDepth {
+    #[serde(alias = "b")]
+    bids: Vec<(String, String)>,
+    #[serde(alias = "a")]
+    asks: Vec<(String, String)>
+}
+
+impl PairDepth {
+    fn levels(&self) -> Result<(Vec<Level>, Vec<Level>), ExternalError> {
+        Ok((string_pairs(&self.bids, OrderType::Bid)?,
+            string_pairs(&self.asks, OrderType::Ask)?))
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct BinanceTrade {
+    #[serde(rename = "T")]
+    time: i64,
+    #[serde(rename = "p")]
+    price: String,
+    #[serde(rename = "q")]
+    quantity: String,
+    /* whether the buyer was the maker, i.e. the seller aggressed */
+    #[serde(rename = "m")]
+    buyer_maker: bool
+}
+
+fn binance(message: Value) -> Result<Vec<Normalized>, ExternalError> {
+    match field(&message, "e").as_deref() {
+        Some("depthUpdate") => {
+            let (bids, asks) = parse::<PairDepth>(message)?.levels()?;
+
+            Ok(vec![Normalized::Delta(LevelsDelta::new(bids, asks))])
+        },
+        Some("trade") | Some("aggTrade") => {
+            let trade: BinanceTrade = parse(message)?;
+            let aggressor_side: OrderType = if trade.buyer_maker {
+                OrderType::Ask
+            } else {
+                OrderType::Bid
+            };
+
+            Ok(vec![Normalized::Trade(MarketTrade::new(
+                millis(trade.time)?,
+                external::parse_price(&trade.price)?,
+                external::parse_quantity(&trade.quantity)?,
+                Some(aggressor_side)))])
+        },
+        Some(_) => Ok(vec![]),
+        /* depth snapshots are the only messages without an event type */
+        None if message.get("lastUpdateId").is_some() => {
+            let (bids, asks) = parse::<PairDepth>(message)?.levels()?;
+
+            Ok(vec![Normalized::Snapshot(Levels::new(bids, asks))])
+        },
+        None => Ok(vec![])
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct CoinbaseUpdate {
+    changes: Vec<(String, String, String)>
+}
+
+#[derive(Debug, Deserialize)]
+struct CoinbaseMatch {
+    time: String,
+    price: String,
+    size: String,
+    /* the maker's side */
+    side: String
+}
+
+fn coinbase(message: Value) -> Result<Vec<Normalized>, ExternalError> {
+    match field(&message, "type").as_deref() {
+        Some("snapshot") => {
+            let (bids, asks) = parse::<PairDepth>(message)?.levels()?;
+
+            Ok(vec![Normalized::Snapshot(Levels::new(bids, asks))])
+        },
+        Some("l2update") => {
+            let update: CoinbaseUpdate = parse(message)?;
+            let mut bids: Vec<Level> = vec![];
+            let mut asks: Vec<Level> = vec![];
+
+            for (change, price, quantity) in update.changes.iter() {
+                match side(change)? {
+                    OrderType::Bid => bids.push(level(price, quantity)?),
+                    OrderType::Ask => asks.push(level(price, quantity)?)
+                }
+            }
+
+            Ok(vec![Normalized::Delta(LevelsDelta::new(
+                sorted(bids, OrderType::Bid),
+                sorted(asks, OrderType::Ask)))])
+        },
+        Some("match") | Some("last_match") => {
+            let trade: CoinbaseMatch = parse(message)?;
+
+            Ok(vec![Normalized::Trade(MarketTrade::new(
+                rfc3339(&trade.time)?,
+                external::parse_price(&trade.price)?,
+                external::parse_quantity(&trade.size)?,
+                Some(opposite(side(&trade.side)?))))])
+        },
+        _ => Ok(vec![])
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct KrakenLevel {
+    price: Number,
+    qty: Number
+}
+
+#[derive(Debug, Deserialize)]
+struct KrakenBook {
+    #[serde(default)]
+    bids: Vec<KrakenLevel>,
+    #[serde(default)]
+    asks: Vec<KrakenLevel>
+}
+
+impl KrakenBook {
+    fn levels(&self) -> Result<(Vec<Level>, Vec<Level>), ExternalError> {
+        let side = |levels: &[KrakenLevel], side: OrderType| levels.iter()
+            .map(|l| level(&l.price.to_string(), &l.qty.to_string()))
+            .collect::<Result<Vec<Level>, ExternalError>>()
+            .map(|levels| sorted(levels, side));
+
+        Ok((side(&self.bids, OrderType::Bid)?,
+            side(&self.asks, OrderType::Ask)?))
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct KrakenTrade {
+    /* the taker's side */
+    side: String,
+    price: Number,
+    qty: Number,
+    timestamp: String
+}
+
+#[derive(Debug, Deserialize)]
+struct KrakenMessage<T> {
+    data: Vec<T>
+}
+
+fn kraken(message: Value) -> Result<Vec<Normalized>, ExternalError> {
+    let snapshot: bool = field(&message, "type").as_deref() ==
+        Some("snapshot");
+
+    match field(&message, "channel").as_deref() {
+        Some("book") => parse::<KrakenMessage<KrakenBook>>(message)?.data
+            .iter()
+            .map(|book| {
+                let (bids, asks) = book.levels()?;
+
+                Ok(if snapshot {
+                    Normalized::Snapshot(Levels::new(bids, asks))
+                } else {
+                    Normalized::Delta(LevelsDelta::new(bids, asks))
+                })
+            })
+            .collect(),
+        Some("trade") => parse::<KrakenMessage<KrakenTrade>>(message)?.data
+            .iter()
+            .map(|trade| Ok(Normalized::Trade(MarketTrade::new(
+                rfc3339(&trade.timestamp)?,
+                external::parse_price(&trade.price.to_string())?,
+                external::parse_quantity(&trade.qty.to_string())?,
+                Some(side(&trade.side)?)))))
+            .collect(),
+        _ => Ok(vec![])
+    }
+}
+
+/* Keeps a book's levels in step with a venue's depth, with one order per
+ * level from `owner`, which needs the balance and holdings to back all of
+ * them. A level that shrinks is reduced, keeping its place in the queue;
+ * one that grows is amended, losing it. Each update goes in as a single
+ * batch, shrinking levels first so that a book which briefly looks crossed
+ * mid-update doesn't trade with itself. Trades leave the book as it is;
+ * the venue's depth updates show what they took. */
+#[derive(Debug, Clone, PartialEq)]
+pub struct LevelMirror {
+    owner: Account,
+    ticker: String,
+    bids: BTreeMap<OrderedFloat<f64>, OrderId>,
+    asks: BTreeMap<OrderedFloat<f64>, OrderId>
+}
+
+impl LevelMirror {
+    pub fn new(owner: Account, ticker: String) -> LevelMirror {
+        LevelMirror {
+            owner,
+            ticker,
+            bids: BTreeMap::new(),
+            asks: BTreeMap::new()
+        }
+    }
+
+    /* the order standing for a level, if the mirror has placed one */
+    pub fn get_order(&self, side: OrderType, price: f64) -> Option<OrderId> {
+        self.side(side).get(&OrderedFloat::from(price)).copied()
+    }
+
+    fn side(&self, side: OrderType) -> &BTreeMap<OrderedFloat<f64>, OrderId> {
+        match side {
+            OrderType::Bid => &self.bids,
+            OrderType::Ask => &self.asks
+        }
+    }
+
+    fn side_mut(&mut self, side: OrderType) ->
+        &mut BTreeMap<OrderedFloat<f64>, OrderId> {
+        match side {
+            OrderType::Bid => &mut self.bids,
+            OrderType::Ask => &mut self.asks
+        }
+    }
+
+    /* Takes over the orders `owner` already has resting in `book`, one
+     * per level, e.g. those of a book seeded with `Book::from_levels`. */
+    pub fn adopt<M, S, C, P>(&mut self, book: &Book<M, S, C, P>)
+    where M: MatchingPolicy, S: EventSink, C: Clock, P: PriceType {
+        let owner: AccountId = self.owner.get_id();
+
+        for order in book.iter_bids().chain(book.iter_asks())
+            .filter(|order| order.get_owner_ref().get_id() == owner) {
+            self.side_mut(order.get_order_type())
+                .entry(OrderedFloat::from(order.get_price()))
+                .or_insert(order.get_id());
+        }
+    }
+
+    /* Applies what a venue said to `book`. A snapshot also removes every
+     * level the mirror placed that it doesn't have. */
+    pub fn apply<M, S, C, P, G>(&mut self, book: &mut Book<M, S, C, P>,
+                                ids: &mut G, data: &Normalized) -> BatchResult
+    where M: MatchingPolicy, S: EventSink, C: Clock, P: PriceType,
+          G: OrderIdGenerator {
+        let mut changes: Vec<(OrderType, f64, Quantity)> = vec![];
+
+        match data {
+            Normalized::Snapshot(levels) => {
+                for (side, levels) in [(OrderType::Bid, levels.get_bids()),
+                                       (OrderType::Ask, levels.get_asks())] {
+                    changes.extend(self.side(side).keys()
+                        .filter(|price| !levels.iter()
+                                .any(|(p, _)| **price == OrderedFloat(*p)))
+                        .map(|price| (side, price.into_inner(), ZERO)));
+                    changes.extend(levels.iter()
+                        .map(|(price, quantity)| (side, *price, *quantity)));
+                }
+            },
+            Normalized::Delta(delta) => {
+                for (side, levels) in [(OrderType::Bid, delta.get_bids()),
+                                       (OrderType::Ask, delta.get_asks())] {
+                    changes.extend(levels.iter()
+                        .map(|(price, quantity)| (side, *price, *quantity)));
+                }
+            },
+            Normalized::Trade(_) => return Ok(vec![])
+        }
+
+        let mut shrinking: Vec<BookOp> = vec![];
+        let mut growing: Vec<BookOp> = vec![];
+        let mut placed: Vec<(OrderType, f64, Option<OrderId>)> = vec![];
+
+        for (side, price, quantity) in changes {
+            let resting: Option<&Order> = self.get_order(side, price)
+                .and_then(|id| book.get_order(id).ok());
+
+            match resting {
+                Some(order) if quantity == ZERO => {
+                    shrinking.push(BookOp::Cancel(order.get_id()));
+                    placed.push((side, price, None));
+                },
+                Some(order) => match quantity::checked_sub(
+                    order.get_quantity(), quantity) {
+                    Some(by) if by == ZERO => {},
+                    Some(by) => shrinking.push(BookOp::Reduce {
+                        id: order.get_id(),
+                        quantity: by
+                    }),
+                    None => growing.push(BookOp::Modify {
+                        id: order.get_id(),
+                        price,
+                        quantity
+                    })
+                },
+                None if quantity == ZERO => placed.push((side, price, None)),
+                None => {
+                    let id: OrderId = ids.next_id();
+
+                    growing.push(BookOp::Submit(Box::new(Order::new(
+                        id, self.owner.clone(), self.ticker.clone(), side,
+                        price, quantity))));
+                    placed.push((side, price, Some(id)));
+                }
+            }
+        }
+
+        shrinking.append(&mut growing);
+
+        let outcomes = if shrinking.is_empty() {
+            vec![]
+        } else {
+            book.apply_batch(shrinking)?
+        };
+
+        for (side, price, id) in placed {
+            let key: OrderedFloat<f64> = OrderedFloat::from(price);
+
+            match id {
+                Some(id) => self.side_mut(side).insert(key, id),
+                None => self.side_mut(side).remove(&key)
+            };
+        }
+
+        Ok(outcomes)
+    }
+}
+
+#[cfg(all(test, not(feature = "decimal-quantity")))]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use crate::id::MonotonicIdGenerator;
+    use crate::metadata::{AssetClass, Metadata};
+
+    fn account() -> Account {
+        let mut holdings: HashMap<String, Quantity> = HashMap::new();
+        holdings.insert("BTC".to_string(), 1_000_000);
+
+        Account::new(1, "Venue".to_string(), 1e12, holdings)
+    }
+
+    #[test]
+    fn test_binance() -> Result<(), ExternalError> {
+        let snapshot: &str = r#"{"lastUpdateId": 160,
+            "bids": [["0.0024", "10"]], "asks": [["0.0026", "100"]]}"#;
+        let update: &str = r#"{"e": "depthUpdate", "E": 123456789,
+            "s": "BNBBTC", "U": 157, "u": 160,
+            "b": [["0.0024", "0"], ["0.0023", "5"]],
+            "a": [["0.0026", "100"]]}"#;
+        let trade: &str = r#"{"e": "trade", "E": 123456789, "s": "BNBBTC",
+            "t": 12345, "p": "0.001", "q": "100", "T": 123456785,
+            "m": true, "M": true}"#;
+
+        assert_eq!(normalize(Venue::Binance, snapshot)?,
+                   vec![Normalized::Snapshot(Levels::new(
+                       vec![(0.0024, 10)], vec![(0.0026, 100)]))]);
+        assert_eq!(normalize(Venue::Binance, update)?,
+                   vec![Normalized::Delta(LevelsDelta::new(
+                       vec![(0.0024, 0), (0.0023, 5)],
+                       vec![(0.0026, 100)]))]);
+        assert_eq!(normalize(Venue::Binance, trade)?,
+                   vec![Normalized::Trade(MarketTrade::new(
+                       millis(123456785)?, 0.001, 100,
+                       Some(OrderType::Ask)))]);
+        assert!(normalize(Venue::Binance, r#"{"result": null, "id": 1}"#)?
+                .is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_coinbase() -> Result<(), ExternalError> {
+        let update: &str = r#"{"type": "l2update", "product_id": "BTC-USD",
+            "time": "2019-08-14T20:42:27.265Z",
+            "changes": [["buy", "10101.80", "162"], ["sell", "10102.5", "0"],
+                        ["buy", "10101.90", "3"]]}"#;
+        let trade: &str = r#"{"type": "match", "trade_id": 10,
+            "sequence": 50, "time": "2014-11-07T08:19:27.028459Z",
+            "product_id": "BTC-USD", "size": "5", "price": "400.23",
+            "side": "sell"}"#;
+
+        assert_eq!(normalize(Venue::Coinbase, update)?,
+                   vec![Normalized::Delta(LevelsDelta::new(
+                       vec![(10101.90, 3), (10101.80, 162)],
+                       vec![(10102.5, 0)]))]);
+        assert_eq!(normalize(Venue::Coinbase, trade)?,
+                   vec![Normalized::Trade(MarketTrade::new(
+                       rfc3339("2014-11-07T08:19:27.028459Z")?, 400.23, 5,
+                       Some(OrderType::Bid)))]);
+        let unknown: &str =
+            r#"{"type": "l2update", "changes": [["hold", "1", "1"]]}"#;
+
+        assert!(matches!(normalize(Venue::Coinbase, unknown),
+                         Err(ExternalError::InvalidField("side", _))));
+        Ok(())
+    }
+
+    #[test]
+    fn test_kraken() -> Result<(), ExternalError> {
+        let snapshot: &str = r#"{"channel": "book", "type": "snapshot",
+            "data": [{"symbol": "BTC/USD",
+                      "bids": [{"price": 45283.5, "qty": 1},
+                               {"price": 45283.6, "qty": 2}],
+                      "asks": [{"price": 45285.2, "qty": 3}],
+                      "checksum": 3310070434}]}"#;
+        let trade: &str = r#"{"channel": "trade", "type": "update",
+            "data": [{"symbol": "BTC/USD", "side": "sell", "price": 45280.1,
+                      "qty": 2.0, "ord_type": "market", "trade_id": 4665906,
+                      "timestamp": "2023-09-25T07:49:37.708706Z"}]}"#;
+
+        assert_eq!(normalize(Venue::Kraken, snapshot)?,
+                   vec![Normalized::Snapshot(Levels::new(
+                       vec![(45283.6, 2), (45283.5, 1)],
+                       vec![(45285.2, 3)]))]);
+        assert_eq!(normalize(Venue::Kraken, trade)?,
+                   vec![Normalized::Trade(MarketTrade::new(
+                       rfc3339("2023-09-25T07:49:37.708706Z")?, 45280.1, 2,
+                       Some(OrderType::Ask)))]);
+        assert!(normalize(Venue::Kraken, r#"{"channel": "heartbeat"}"#)?
+                .is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_mirror() -> crate::Result<()> {
+        let mut book: Book = Book::builder(1, "BTC".to_string()).build()?;
+        let mut ids: MonotonicIdGenerator = MonotonicIdGenerator::new();
+        let mut mirror: LevelMirror = LevelMirror::new(account(),
+                                                       "BTC".to_string());
+        let messages: Vec<&str> = vec![
+            r#"{"type": "snapshot", "product_id": "BTC-USD",
+                "bids": [["100.0", "5"], ["99.0", "7"]],
+                "asks": [["101.0", "4"]]}"#,
+            r#"{"type": "l2update", "product_id": "BTC-USD",
+                "time": "2019-08-14T20:42:27.265Z",
+                "changes": [["buy", "100.0", "3"], ["buy", "99.0", "0"],
+                            ["sell", "101.0", "6"], ["sell", "102.0", "1"]]}"#
+        ];
+
+        for message in messages {
+            for data in normalize(Venue::Coinbase, message)? {
+                mirror.apply(&mut book, &mut ids, &data)?;
+            }
+        }
+
+        assert_eq!(book.levels(), Levels::new(vec![(100.0, 3)],
+                                              vec![(101.0, 6), (102.0, 1)]));
+        assert_eq!(mirror.get_order(OrderType::Bid, 99.0), None);
+        assert!(book.get_trades().is_empty());
+
+        /* a snapshot replaces everything */
+        mirror.apply(&mut book, &mut ids, &Normalized::Snapshot(
+            Levels::new(vec![(98.0, 2)], vec![])))?;
+        assert_eq!(book.levels(), Levels::new(vec![(98.0, 2)], vec![]));
+
+        /* a book seeded from a venue's snapshot is taken over as it is */
+        let metadata: Metadata = Metadata::new("BTC".to_string(),
+                                               AssetClass::Fx,
+                                               "USD".to_string());
+        let mut seeded: Book = Book::from_levels(
+            2, &metadata, &Levels::new(vec![(100.0, 5)], vec![(101.0, 4)]),
+            &account(), 1, &mut ids)?;
+        let mut adopted: LevelMirror = LevelMirror::new(account(),
+                                                        "BTC".to_string());
+
+        adopted.adopt(&seeded);
+        adopted.apply(&mut seeded, &mut ids, &Normalized::Delta(
+            LevelsDelta::new(vec![(100.0, 2)], vec![])))?;
+        assert_eq!(seeded.levels(), Levels::new(vec![(100.0, 2)],
+                                                vec![(101.0, 4)]));
+        Ok(())
+    }
+}
@@ -0,0 +1,391 @@
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use serde::{Serialize, Deserialize};
+
+use crate::account::AccountId;
+use crate::event::{Event, EventKind, Trade, TradeId};
+use crate::price;
+use crate::sink::{EventSink, SinkError};
+
+/* Exchange fees: what each side of a trade pays, or is paid, as a fraction
+ * of its notional. Makers (the resting side) typically earn a rebate for
+ * the liquidity they provide and takers pay for removing it, both at rates
+ * that improve with how much the account has traded lately. */
+
+const DAY: Duration = Duration::from_secs(24 * 60 * 60);
+
+/* the rates for an account that has traded at least `min_volume` of
+ * notional over the schedule's window; a negative rate is a rebate */
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct FeeTier {
+    pub min_volume: f64,
+    pub maker_rate: f64,
+    pub taker_rate: f64
+}
+
+/* Tiers by volume, each account charged at the best tier its volume over
+ * the trailing `window` (before the trade being charged) reaches. */
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FeeSchedule {
+    /* in order of `min_volume`, the first from zero */
+    tiers: Vec<FeeTier>,
+    window: Duration
+}
+
+impl Default for FeeSchedule {
+    fn default() -> FeeSchedule {
+        FeeSchedule::flat(0.0, 0.0)
+    }
+}
+
+impl FeeSchedule {
+    /* the same rates for everyone, over a thirty day window */
+    pub fn flat(maker_rate: f64, taker_rate: f64) -> FeeSchedule {
+        FeeSchedule {
+            tiers: vec![FeeTier {
+                min_volume: 0.0,
+                maker_rate,
+                taker_rate
+            }],
+            window: DAY * 30
+        }
+    }
+
+    /* replaces any tier starting at the same volume */
+    pub fn tier(mut self, tier: FeeTier) -> FeeSchedule {
+        self.tiers.retain(|existing| existing.min_volume != tier.min_volume);
+        self.tiers.push(tier);
+        self.tiers.sort_by(|a, b| a.min_volume.total_cmp(&b.min_volume));
+        self
+    }
+
+    pub fn window(mut self, window: Duration) -> FeeSchedule {
+        self.window = window;
+        self
+    }
+
+    pub fn get_tiers(&self) -> &[FeeTier] {
+        &self.tiers
+    }
+
+    pub fn get_window(&self) -> Duration {
+        self.window
+    }
+
+    /* the tier for an account with `volume` over the window */
+    pub fn tier_for(&self, volume: f64) -> FeeTier {
+        self.tiers.iter()
+            .rev()
+            .find(|tier| tier.min_volume <= volume)
+            .or_else(|| self.tiers.first())
+            .copied()
+            .unwrap_or(FeeTier {
+                min_volume: 0.0,
+                maker_rate: 0.0,
+                taker_rate: 0.0
+            })
+    }
+}
+
+/* What an account has paid and been paid, fees and rebates both counted
+ * as positive amounts */
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct AccountFees {
+    pub fees: f64,
+    pub rebates: f64,
+    /* notional traded on each side */
+    pub maker_volume: f64,
+    pub taker_volume: f64,
+    pub trades: usize
+}
+
+impl AccountFees {
+    /* what the account paid overall; negative if it came out ahead */
+    pub fn net(&self) -> f64 {
+        self.fees - self.rebates
+    }
+
+    /* adds `charge` to the totals, or takes it away again if `sign` is
+     * negative */
+    fn apply(&mut self, charge: &Charge, sign: f64) {
+        if charge.amount >= 0.0 {
+            self.fees += sign * charge.amount;
+        } else {
+            self.rebates -= sign * charge.amount;
+        }
+
+        if charge.maker {
+            self.maker_volume += sign * charge.notional;
+        } else {
+            self.taker_volume += sign * charge.notional;
+        }
+
+        if sign > 0.0 {
+            self.trades += 1;
+        } else {
+            self.trades = self.trades.saturating_sub(1);
+        }
+    }
+}
+
+/* every account's fees as of the last event seen */
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct FeeReport {
+    pub accounts: BTreeMap<AccountId, AccountFees>,
+    pub fees: f64,
+    pub rebates: f64
+}
+
+impl FeeReport {
+    pub fn net(&self) -> f64 {
+        self.fees - self.rebates
+    }
+}
+
+/* one side's charge for a trade, kept so that a bust can undo it */
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Charge {
+    account: AccountId,
+    maker: bool,
+    notional: f64,
+    amount: f64
+}
+
+/* Charges the trades of a book's events to the accounts on either side of
+ * them, fed either as the book's sink or from `Book::events_since` after a
+ * backtest, and reverses them when a trade is busted. Trades that don't
+ * say whose they were (e.g. from before books recorded it) are skipped. */
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FeeLedger {
+    schedule: FeeSchedule,
+    accounts: HashMap<AccountId, AccountFees>,
+    /* each account's trades within the window, oldest first */
+    volumes: HashMap<AccountId, VecDeque<(DateTime<Utc>, TradeId, f64)>>,
+    charges: HashMap<TradeId, Vec<Charge>>
+}
+
+impl FeeLedger {
+    pub fn new(schedule: FeeSchedule) -> FeeLedger {
+        FeeLedger {
+            schedule,
+            ..FeeLedger::default()
+        }
+    }
+
+    pub fn get_schedule(&self) -> &FeeSchedule {
+        &self.schedule
+    }
+
+    pub fn get_account(&self, account: AccountId) -> Option<&AccountFees> {
+        self.accounts.get(&account)
+    }
+
+    /* the account's notional within the window as of its last trade */
+    pub fn volume(&self, account: AccountId) -> f64 {
+        self.volumes.get(&account)
+            .map(|trades| trades.iter().map(|(_, _, notional)| notional).sum())
+            .unwrap_or(0.0)
+    }
+
+    pub fn report(&self) -> FeeReport {
+        let accounts: BTreeMap<AccountId, AccountFees> = self.accounts.iter()
+            .map(|(account, fees)| (*account, *fees))
+            .collect();
+
+        FeeReport {
+            fees: accounts.values().map(|fees| fees.fees).sum(),
+            rebates: accounts.values().map(|fees| fees.rebates).sum(),
+            accounts
+        }
+    }
+
+    pub fn observe(&mut self, event: &Event) {
+        match event.get_kind() {
+            EventKind::Match(trade) => self.charge(trade),
+            EventKind::TradeBust(trade) => self.bust(trade),
+            _ => {}
+        }
+    }
+
+    pub fn observe_all(&mut self, events: &[Event]) {
+        events.iter().for_each(|event| self.observe(event));
+    }
+
+    fn charge(&mut self, trade: &Trade) {
+        let notional: f64 = price::notional(trade.get_price(),
+                                            trade.get_quantity());
+        let sides: [(Option<AccountId>, bool); 2] = [
+            (trade.get_aggressor_account(), false),
+            (trade.get_resting_account(), true)
+        ];
+        let mut charges: Vec<Charge> = vec![];
+
+        for (account, maker) in sides {
+            let account: AccountId = match account {
+                Some(account) => account,
+                None => continue
+            };
+            let volume: f64 = self.trailing(account, trade.get_timestamp());
+            let tier: FeeTier = self.schedule.tier_for(volume);
+            let rate: f64 = if maker {
+                tier.maker_rate
+            } else {
+                tier.taker_rate
+            };
+            let charge: Charge = Charge {
+                account,
+                maker,
+                notional,
+                amount: price::fee(notional, rate)
+            };
+
+            self.accounts.entry(account).or_default().apply(&charge, 1.0);
+            self.volumes.entry(account).or_default()
+                .push_back((trade.get_timestamp(), trade.get_id(), notional));
+            charges.push(charge);
+        }
+
+        self.charges.insert(trade.get_id(), charges);
+    }
+
+    fn bust(&mut self, trade: &Trade) {
+        for charge in self.charges.remove(&trade.get_id()).unwrap_or_default() {
+            if let Some(fees) = self.accounts.get_mut(&charge.account) {
+                fees.apply(&charge, -1.0);
+            }
+
+            if let Some(trades) = self.volumes.get_mut(&charge.account) {
+                trades.retain(|(_, id, _)| *id != trade.get_id());
+            }
+        }
+    }
+
+    /* the account's volume over the window up to `now`, forgetting what
+     * has fallen out of it */
+    fn trailing(&mut self, account: AccountId, now: DateTime<Utc>) -> f64 {
+        let window: chrono::Duration =
+            chrono::Duration::from_std(self.schedule.window)
+                .unwrap_or(chrono::Duration::MAX);
+        let trades = match self.volumes.get_mut(&account) {
+            Some(trades) => trades,
+            None => return 0.0
+        };
+
+        while let Some((at, _, _)) = trades.front() {
+            if now.signed_duration_since(*at) <= window {
+                break;
+            }
+
+            trades.pop_front();
+        }
+
+        trades.iter().map(|(_, _, notional)| notional).sum()
+    }
+}
+
+impl EventSink for FeeLedger {
+    fn write(&mut self, event: &Event) -> Result<(), SinkError> {
+        self.observe(event);
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), SinkError> {
+        Ok(())
+    }
+}
+
+#[cfg(all(test, not(feature = "decimal-quantity")))]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use crate::account::Account;
+    use crate::book::{Book, BookError};
+    use crate::order::{Order, OrderType};
+    use crate::quantity::Quantity;
+
+    fn trade(seq: u64, days: i64, price: f64, quantity: Quantity,
+             taker: AccountId, maker: AccountId) -> Trade {
+        let timestamp: DateTime<Utc> = DateTime::<Utc>::default() +
+            chrono::Duration::days(days);
+
+        Trade::new(timestamp, price, quantity, 2, 1, OrderType::Bid)
+            .with_id(seq)
+            .with_accounts(taker, maker)
+    }
+
+    fn matched(trade: Trade) -> Event {
+        Event::new(trade.get_id(), trade.get_timestamp(),
+                   EventKind::Match(trade))
+    }
+
+    #[test]
+    fn test_tiered_fees() {
+        let schedule: FeeSchedule = FeeSchedule::flat(-0.0002, 0.0030)
+            .tier(FeeTier {
+                min_volume: 1000.0,
+                maker_rate: -0.0003,
+                taker_rate: 0.0020
+            })
+            .window(DAY * 7);
+        let mut ledger: FeeLedger = FeeLedger::new(schedule);
+
+        /* 1000 of notional puts both accounts in the second tier... */
+        ledger.observe_all(&[matched(trade(1, 0, 10.0, 100, 1, 2)),
+                             matched(trade(2, 1, 10.0, 100, 1, 2))]);
+
+        let taker: AccountFees = ledger.report().accounts[&1];
+        let maker: AccountFees = ledger.report().accounts[&2];
+
+        assert!((taker.fees - (3.0 + 2.0)).abs() < 1e-9);
+        assert!((maker.rebates - (0.2 + 0.3)).abs() < 1e-9);
+        assert_eq!((taker.taker_volume, maker.maker_volume),
+                   (2000.0, 2000.0));
+        assert!((ledger.report().net() - 4.5).abs() < 1e-9);
+
+        /* ...until it falls out of the window */
+        let late: Trade = trade(3, 9, 10.0, 100, 1, 2);
+
+        ledger.observe(&matched(late));
+        assert!((ledger.report().accounts[&1].fees - 8.0).abs() < 1e-9);
+        assert_eq!(ledger.volume(1), 1000.0);
+
+        /* busting a trade gives back what it charged */
+        let bust: Event = Event::new(4, late.get_timestamp(),
+                                     EventKind::TradeBust(late));
+
+        ledger.observe(&bust);
+        assert!((ledger.report().accounts[&1].fees - 5.0).abs() < 1e-9);
+        assert_eq!(ledger.report().accounts[&1].trades, 2);
+        assert_eq!(ledger.volume(1), 0.0);
+    }
+
+    #[test]
+    fn test_book_trades() -> Result<(), BookError> {
+        let mut book: Book = Book::new(1, "Book".to_string(),
+                                       "BOOK".to_string());
+        let mut ledger: FeeLedger =
+            FeeLedger::new(FeeSchedule::flat(-0.001, 0.002));
+
+        for (id, order_type) in [(1, OrderType::Ask), (2, OrderType::Bid)] {
+            let mut holdings: HashMap<String, Quantity> = HashMap::new();
+            holdings.insert("BOOK".to_string(), 1000);
+
+            let owner: Account = Account::new(id * 10, "Account".to_string(),
+                                              12000.00, holdings);
+
+            book.submit(Order::new(id, owner, "BOOK".to_string(), order_type,
+                                   10.00, 50))?;
+        }
+
+        ledger.observe_all(book.get_events());
+
+        let report: FeeReport = ledger.report();
+
+        assert!((report.accounts[&20].fees - 1.0).abs() < 1e-9);
+        assert!((report.accounts[&10].rebates - 0.5).abs() < 1e-9);
+        assert!((report.net() - 0.5).abs() < 1e-9);
+        Ok(())
+    }
+}
@@ -0,0 +1,554 @@
+/* `Status` is what tonic handlers must fail with, large as it is */
+#![allow(clippy::result_large_err)]
+
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::fmt::Display;
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use tokio::sync::broadcast;
+use tokio_stream::wrappers::BroadcastStream;
+use tokio_stream::wrappers::errors::BroadcastStreamRecvError;
+use tokio_stream::{Stream, StreamExt};
+use tonic::{Request, Response, Status};
+
+use crate::account::{Account, AccountId};
+use crate::book::{Book, BookError, BookId};
+use crate::clock::Clock;
+use crate::event::{Event, EventKind, Trade};
+use crate::id::{MonotonicIdGenerator, OrderIdGenerator};
+use crate::levels::{Level, Levels};
+use crate::matching::MatchingPolicy;
+use crate::order::{Order, OrderId, OrderType};
+use crate::price::PriceType;
+use crate::quantity::Quantity;
+use crate::router::{OrderRouter, RouterError};
+use crate::sink::MemorySink;
+
+pub use proto::matching_engine_client::MatchingEngineClient;
+pub use proto::matching_engine_server::{MatchingEngine, MatchingEngineServer};
+
+/* the messages and service generated from `proto/ironlobe.proto` */
+pub mod proto {
+    #![allow(clippy::all)]
+
+    tonic::include_proto!("ironlobe.v1");
+}
+
+/* how many events a slow `StreamEvents` subscriber may fall behind by
+ * before its stream is ended */
+pub const STREAM_CAPACITY: usize = 1024;
+
+type EventStream =
+    Pin<Box<dyn Stream<Item = Result<proto::Event, Status>> + Send>>;
+
+/* everything behind the service's lock */
+#[derive(Debug)]
+struct Exchange<M: MatchingPolicy, C: Clock, P: PriceType> {
+    router: OrderRouter<M, MemorySink, C, P>,
+    accounts: HashMap<AccountId, Account>,
+    ids: Box<dyn OrderIdGenerator + Send>,
+    /* the last event of each book sent to subscribers */
+    published: HashMap<BookId, u64>
+}
+
+/* Serves the books of an `OrderRouter` over gRPC, as the `MatchingEngine`
+ * service of `proto/ironlobe.proto`. Orders are placed on behalf of
+ * accounts registered with `add_account`, and are given IDs by the service
+ * rather than by clients. Books need a `MemorySink`, which `StreamEvents`
+ * replays a subscriber's backlog from. */
+#[derive(Debug)]
+pub struct MatchingService<M: MatchingPolicy, C: Clock, P: PriceType> {
+    exchange: Arc<Mutex<Exchange<M, C, P>>>,
+    events: broadcast::Sender<(BookId, Event)>
+}
+
+impl<M, C, P> Clone for MatchingService<M, C, P>
+where
+    M: MatchingPolicy,
+    C: Clock,
+    P: PriceType
+{
+    fn clone(&self) -> MatchingService<M, C, P> {
+        MatchingService {
+            exchange: Arc::clone(&self.exchange),
+            events: self.events.clone()
+        }
+    }
+}
+
+impl<M, C, P> MatchingService<M, C, P>
+where
+    M: MatchingPolicy + Send + 'static,
+    C: Clock + Send + 'static,
+    P: PriceType + Send + 'static
+{
+    pub fn new(router: OrderRouter<M, MemorySink, C, P>) ->
+        MatchingService<M, C, P> {
+        let (events, _) = broadcast::channel(STREAM_CAPACITY);
+
+        MatchingService {
+            exchange: Arc::new(Mutex::new(Exchange {
+                router,
+                accounts: HashMap::new(),
+                ids: Box::new(MonotonicIdGenerator::new()),
+                published: HashMap::new()
+            })),
+            events
+        }
+    }
+
+    pub fn with_id_generator<G>(self, ids: G) -> MatchingService<M, C, P>
+    where
+        G: OrderIdGenerator + Send + 'static
+    {
+        if let Ok(mut exchange) = self.exchange.lock() {
+            exchange.ids = Box::new(ids);
+        }
+
+        self
+    }
+
+    /* replaces any account with the same ID */
+    pub fn add_account(&self, account: Account) {
+        if let Ok(mut exchange) = self.exchange.lock() {
+            exchange.accounts.insert(account.get_id(), account);
+        }
+    }
+
+    pub fn into_server(self) ->
+        MatchingEngineServer<MatchingService<M, C, P>> {
+        MatchingEngineServer::new(self)
+    }
+
+    /* serves on `address` until the server fails */
+    pub async fn serve(self, address: SocketAddr) ->
+        Result<(), tonic::transport::Error> {
+        tonic::transport::Server::builder()
+            .add_service(self.into_server())
+            .serve(address)
+            .await
+    }
+
+    fn lock(&self) -> Result<MutexGuard<'_, Exchange<M, C, P>>, Status> {
+        self.exchange.lock()
+            .map_err(|_| Status::internal("exchange lock poisoned"))
+    }
+
+    /* Runs `action` against book `id`, then sends subscribers whatever
+     * events it raised, returning its result along with the trades it
+     * made */
+    fn with_book<T, F>(&self, id: BookId, action: F) ->
+        Result<(T, Vec<Trade>), Status>
+    where
+        F: FnOnce(&mut Book<M, MemorySink, C, P>) -> Result<T, Status>
+    {
+        let mut exchange: MutexGuard<'_, Exchange<M, C, P>> = self.lock()?;
+        let book: &mut Book<M, MemorySink, C, P> =
+            exchange.router.get_book_mut(id).map_err(router_status)?;
+        let traded: usize = book.get_trades().len();
+        let outcome: Result<T, Status> = action(book);
+        let trades: Vec<Trade> = book.get_trades()
+            .get(traded..)
+            .unwrap_or_default()
+            .to_vec();
+
+        self.publish(&mut exchange, id);
+        outcome.map(|result| (result, trades))
+    }
+
+    fn publish(&self, exchange: &mut Exchange<M, C, P>, id: BookId) {
+        let book: &Book<M, MemorySink, C, P> =
+            match exchange.router.get_book(id) {
+                Ok(book) => book,
+                Err(_) => return
+            };
+        let last: u64 = exchange.published.get(&id).copied().unwrap_or(0);
+
+        /* with no subscribers there is nobody to tell, and a new one
+         * catches up from the book itself */
+        for event in book.events_since(last).unwrap_or_default() {
+            let _ = self.events.send((id, event.clone()));
+        }
+
+        exchange.published.insert(id, book.last_seq());
+    }
+}
+
+fn parse<T: FromStr>(field: &str, value: &str) -> Result<T, Status> {
+    value.parse()
+        .map_err(|_| Status::invalid_argument(format!("invalid {}: {:?}",
+                                                      field, value)))
+}
+
+fn side(value: i32) -> Result<OrderType, Status> {
+    match proto::Side::try_from(value) {
+        Ok(proto::Side::Bid) => Ok(OrderType::Bid),
+        Ok(proto::Side::Ask) => Ok(OrderType::Ask),
+        _ => Err(Status::invalid_argument("side must be bid or ask"))
+    }
+}
+
+fn proto_side(order_type: &OrderType) -> i32 {
+    match order_type {
+        OrderType::Bid => proto::Side::Bid as i32,
+        OrderType::Ask => proto::Side::Ask as i32
+    }
+}
+
+fn string<T: Display>(value: T) -> String {
+    value.to_string()
+}
+
+fn book_status(error: BookError) -> Status {
+    match error {
+        BookError::OrderNotFound => Status::not_found(error.to_string()),
+        BookError::DuplicateOrderId | BookError::DuplicateClientId(_) => {
+            Status::already_exists(error.to_string())
+        },
+        BookError::MarketHalted | BookError::MarketClosed |
+        BookError::Expired | BookError::Account(_) |
+        BookError::InsufficientBuyingPower { .. } => {
+            Status::failed_precondition(error.to_string())
+        },
+        BookError::InvalidPrice | BookError::InvalidQuantity => {
+            Status::invalid_argument(error.to_string())
+        },
+        BookError::EventsEvicted => Status::out_of_range(error.to_string()),
+        _ => Status::internal(error.to_string())
+    }
+}
+
+fn router_status(error: RouterError) -> Status {
+    match error {
+        RouterError::Book(error) => book_status(error),
+        RouterError::BookNotFound(_) | RouterError::UnknownSymbol(_) => {
+            Status::not_found(error.to_string())
+        },
+        RouterError::DuplicateBook(_) => {
+            Status::already_exists(error.to_string())
+        }
+    }
+}
+
+fn proto_trade(trade: &Trade) -> proto::Trade {
+    proto::Trade {
+        timestamp: trade.get_timestamp().to_rfc3339(),
+        price: trade.get_price(),
+        quantity: string(trade.get_quantity()),
+        aggressor: string(trade.get_aggressor()),
+        resting: string(trade.get_resting()),
+        aggressor_side: proto_side(&trade.get_aggressor_side())
+    }
+}
+
+fn proto_levels(levels: &[Level]) -> Vec<proto::Level> {
+    levels.iter()
+        .map(|(price, quantity)| proto::Level {
+            price: *price,
+            quantity: string(quantity)
+        })
+        .collect()
+}
+
+fn update(order: &OrderId, order_type: &OrderType, price: f64,
+          quantity: Quantity, hidden: bool) -> proto::OrderUpdate {
+    proto::OrderUpdate {
+        id: string(order),
+        side: proto_side(order_type),
+        price,
+        quantity: string(quantity),
+        hidden
+    }
+}
+
+/* the typed form of the commonest events, and JSON for the rest */
+pub fn proto_event(book: BookId, event: &Event) -> proto::Event {
+    use proto::event::Kind;
+
+    let kind: Kind = match event.get_kind() {
+        EventKind::Post { order, order_type, price, quantity, hidden } => {
+            Kind::Post(update(order, order_type, *price, *quantity, *hidden))
+        },
+        EventKind::Match(trade) => Kind::Match(proto_trade(trade)),
+        EventKind::Cancel { order, order_type, price, quantity, hidden } => {
+            Kind::Cancel(update(order, order_type, *price, *quantity,
+                                *hidden))
+        },
+        /* to a client, a repricing is just an amendment by the venue */
+        EventKind::Amend { order, order_type, price, quantity, hidden } |
+        EventKind::Reprice { order, order_type, price, quantity, hidden,
+                             .. } => {
+            Kind::Amend(update(order, order_type, *price, *quantity, *hidden))
+        },
+        /* the quantity left, as with the other updates */
+        EventKind::Reduce { order, order_type, price, remaining, hidden,
+                            .. } => {
+            Kind::Reduce(update(order, order_type, *price, *remaining,
+                                *hidden))
+        },
+        other => Kind::Other(serde_json::to_string(other)
+                             .unwrap_or_default())
+    };
+
+    proto::Event {
+        book: string(book),
+        seq: event.get_seq(),
+        timestamp: event.get_timestamp().to_rfc3339(),
+        kind: Some(kind)
+    }
+}
+
+#[tonic::async_trait]
+impl<M, C, P> MatchingEngine for MatchingService<M, C, P>
+where
+    M: MatchingPolicy + Send + 'static,
+    C: Clock + Send + 'static,
+    P: PriceType + Send + 'static
+{
+    async fn submit_order(&self, request: Request<proto::SubmitOrderRequest>)
+        -> Result<Response<proto::SubmitOrderResponse>, Status> {
+        let request: proto::SubmitOrderRequest = request.into_inner();
+        let book: BookId = parse("book", &request.book)?;
+        let account: AccountId = parse("account", &request.account)?;
+        let order_type: OrderType = side(request.side)?;
+        let quantity: Quantity = parse("quantity", &request.quantity)?;
+
+        let (owner, id): (Account, OrderId) = {
+            let mut exchange = self.lock()?;
+            let owner: Account = exchange.accounts.get(&account)
+                .cloned()
+                .ok_or_else(|| Status::not_found(
+                    format!("no such account {}", account)))?;
+
+            (owner, exchange.ids.next_id())
+        };
+
+        let (id, trades) = self.with_book(book, |book| {
+            let mut order: Order = Order::new(id, owner, book.get_ticker(),
+                                              order_type, request.price,
+                                              quantity);
+
+            if let Some(client_id) = request.client_order_id {
+                order.set_client_id(client_id);
+            }
+
+            for (key, value) in request.tags {
+                order.set_tag(key, value);
+            }
+
+            book.submit(order).map_err(book_status)?;
+            Ok(id)
+        })?;
+
+        Ok(Response::new(proto::SubmitOrderResponse {
+            id: string(id),
+            trades: trades.iter().map(proto_trade).collect()
+        }))
+    }
+
+    async fn cancel_order(&self, request: Request<proto::CancelOrderRequest>)
+        -> Result<Response<proto::CancelOrderResponse>, Status> {
+        let request: proto::CancelOrderRequest = request.into_inner();
+        let book: BookId = parse("book", &request.book)?;
+        let id: OrderId = parse("id", &request.id)?;
+
+        let (order, _) = self.with_book(book, |book| {
+            book.cancel(id).map(|result| result.order).map_err(book_status)
+        })?;
+
+        Ok(Response::new(proto::CancelOrderResponse {
+            order: Some(proto::Order {
+                id: string(order.get_id()),
+                account: string(order.get_owner_ref().get_id()),
+                side: proto_side(&order.get_order_type()),
+                price: order.get_price(),
+                quantity: string(order.get_quantity())
+            })
+        }))
+    }
+
+    async fn modify_order(&self, request: Request<proto::ModifyOrderRequest>)
+        -> Result<Response<proto::ModifyOrderResponse>, Status> {
+        let request: proto::ModifyOrderRequest = request.into_inner();
+        let book: BookId = parse("book", &request.book)?;
+        let id: OrderId = parse("id", &request.id)?;
+        let quantity: Quantity = parse("quantity", &request.quantity)?;
+
+        let (_, trades) = self.with_book(book, |book| {
+            book.modify(id, request.price, quantity).map_err(book_status)
+        })?;
+
+        Ok(Response::new(proto::ModifyOrderResponse {
+            trades: trades.iter().map(proto_trade).collect()
+        }))
+    }
+
+    async fn get_book(&self, request: Request<proto::GetBookRequest>) ->
+        Result<Response<proto::GetBookResponse>, Status> {
+        let request: proto::GetBookRequest = request.into_inner();
+        let id: BookId = parse("book", &request.book)?;
+        let exchange = self.lock()?;
+        let book: &Book<M, MemorySink, C, P> = exchange.router.get_book(id)
+            .map_err(router_status)?;
+        let levels: Levels = match request.depth {
+            0 => book.levels(),
+            depth => book.depth(depth as usize)
+        };
+
+        Ok(Response::new(proto::GetBookResponse {
+            ticker: book.get_ticker(),
+            bids: proto_levels(levels.get_bids()),
+            asks: proto_levels(levels.get_asks()),
+            last_price: book.get_ltp().ok(),
+            last_seq: book.last_seq()
+        }))
+    }
+
+    type StreamEventsStream = EventStream;
+
+    async fn stream_events(&self,
+                           request: Request<proto::StreamEventsRequest>) ->
+        Result<Response<EventStream>, Status> {
+        let request: proto::StreamEventsRequest = request.into_inner();
+        let id: BookId = parse("book", &request.book)?;
+
+        /* subscribing under the lock means nothing published can fall
+         * between the backlog and the live events */
+        let (backlog, receiver) = {
+            let exchange = self.lock()?;
+            let book: &Book<M, MemorySink, C, P> =
+                exchange.router.get_book(id).map_err(router_status)?;
+            let backlog: Vec<Event> = book.delta_since(request.from_seq)
+                .map_err(book_status)?;
+
+            (backlog, self.events.subscribe())
+        };
+        let after: u64 = backlog.last()
+            .map(Event::get_seq)
+            .unwrap_or(request.from_seq);
+
+        let backlog = tokio_stream::iter(backlog)
+            .map(move |event| Ok(proto_event(id, &event)));
+        let live = BroadcastStream::new(receiver)
+            .filter_map(move |received| match received {
+                Ok((book, event)) if book == id && event.get_seq() > after => {
+                    Some(Ok(proto_event(id, &event)))
+                },
+                Ok(_) => None,
+                Err(BroadcastStreamRecvError::Lagged(missed)) => {
+                    Some(Err(Status::data_loss(format!(
+                        "fell {} events behind", missed))))
+                }
+            });
+
+        Ok(Response::new(Box::pin(backlog.chain(live)) as EventStream))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::SystemClock;
+    use crate::matching::PriceTime;
+    use crate::price::F64Price;
+    use crate::router::RoutingStrategy;
+
+    fn service() ->
+        Result<MatchingService<PriceTime, SystemClock, F64Price>,
+               RouterError> {
+        let mut router: OrderRouter =
+            OrderRouter::new(RoutingStrategy::default());
+        let mut holdings: HashMap<String, Quantity> = HashMap::new();
+        holdings.insert("BOOK".to_string(), 100);
+
+        router.add_book(Book::new(1, "Book".to_string(), "BOOK".to_string()))?;
+
+        let service = MatchingService::new(router);
+        service.add_account(Account::new(1, "Trader".to_string(), 10_000.0,
+                                         holdings));
+        Ok(service)
+    }
+
+    fn submit(side: proto::Side, price: f64, quantity: &str) ->
+        Request<proto::SubmitOrderRequest> {
+        Request::new(proto::SubmitOrderRequest {
+            book: "1".to_string(),
+            account: "1".to_string(),
+            side: side as i32,
+            price,
+            quantity: quantity.to_string(),
+            ..proto::SubmitOrderRequest::default()
+        })
+    }
+
+    #[tokio::test]
+    async fn test_service() -> Result<(), Status> {
+        let service = service().map_err(router_status)?;
+
+        let ask = service.submit_order(submit(proto::Side::Ask, 10.0, "5"))
+            .await?
+            .into_inner();
+        let bid = service.submit_order(submit(proto::Side::Bid, 10.0, "2"))
+            .await?
+            .into_inner();
+
+        assert_eq!(ask.id, "1");
+        assert!(ask.trades.is_empty());
+        assert_eq!(bid.trades.len(), 1);
+        assert_eq!(bid.trades[0].quantity, "2");
+        assert_eq!(bid.trades[0].resting, "1");
+
+        let depth = service.get_book(Request::new(proto::GetBookRequest {
+            book: "1".to_string(),
+            depth: 0
+        })).await?.into_inner();
+
+        assert_eq!(depth.asks, vec![proto::Level {
+            price: 10.0,
+            quantity: "3".to_string()
+        }]);
+        assert_eq!(depth.last_price, Some(10.0));
+
+        let mut events = service.stream_events(
+            Request::new(proto::StreamEventsRequest {
+                book: "1".to_string(),
+                from_seq: 0
+            })).await?.into_inner();
+        let mut seen: u64 = 0;
+
+        while seen < depth.last_seq {
+            seen = events.next().await
+                .ok_or_else(|| Status::internal("stream ended"))??
+                .seq;
+        }
+
+        let cancelled = service.cancel_order(
+            Request::new(proto::CancelOrderRequest {
+                book: "1".to_string(),
+                id: "1".to_string()
+            })).await?.into_inner();
+
+        assert_eq!(cancelled.order.map(|order| order.quantity),
+                   Some("3".to_string()));
+
+        let live = events.next().await
+            .ok_or_else(|| Status::internal("stream ended"))??;
+
+        assert_eq!(live.seq, depth.last_seq + 1);
+        assert!(matches!(live.kind, Some(proto::event::Kind::Cancel(_))));
+
+        let missing = service.cancel_order(
+            Request::new(proto::CancelOrderRequest {
+                book: "1".to_string(),
+                id: "1".to_string()
+            })).await;
+
+        assert_eq!(missing.map(|_| ()).map_err(|e| e.code()),
+                   Err(tonic::Code::NotFound));
+        Ok(())
+    }
+}
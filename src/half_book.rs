@@ -0,0 +1,293 @@
+use std::collections::{BTreeMap, VecDeque};
+use std::collections::btree_map;
+use std::ops::Bound;
+
+use crate::order::{OrderId, OrderType};
+use crate::pool::{self, LevelPool};
+use crate::price::PriceType;
+
+/* One side of a book: its price levels, each a queue of resting order IDs
+ * in time priority. Which way is best depends on the side, the highest
+ * price for bids and the lowest for asks, and everything that walks the
+ * levels does so best first, so that `Book` (or any other book built on
+ * these) needn't say which side it means at every turn. Emptied levels are
+ * handed to a `LevelPool` rather than dropped. */
+#[derive(Debug, Clone, PartialEq)]
+pub struct HalfBook<P: PriceType> {
+    side: OrderType,
+    levels: BTreeMap<P, VecDeque<OrderId>>
+}
+
+/* a side's levels, best price first */
+#[derive(Debug, Clone)]
+pub struct BestFirst<I> {
+    levels: I,
+    side: OrderType
+}
+
+impl<I: DoubleEndedIterator> Iterator for BestFirst<I> {
+    type Item = I::Item;
+
+    fn next(&mut self) -> Option<I::Item> {
+        match self.side {
+            OrderType::Bid => self.levels.next_back(),
+            OrderType::Ask => self.levels.next()
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.levels.size_hint()
+    }
+}
+
+impl<I: DoubleEndedIterator> DoubleEndedIterator for BestFirst<I> {
+    fn next_back(&mut self) -> Option<I::Item> {
+        match self.side {
+            OrderType::Bid => self.levels.next(),
+            OrderType::Ask => self.levels.next_back()
+        }
+    }
+}
+
+impl<I: DoubleEndedIterator + ExactSizeIterator> ExactSizeIterator
+    for BestFirst<I> {}
+
+pub type LevelsIter<'a, P> =
+    BestFirst<btree_map::Iter<'a, P, VecDeque<OrderId>>>;
+pub type LevelsRange<'a, P> =
+    BestFirst<btree_map::Range<'a, P, VecDeque<OrderId>>>;
+
+impl<P: PriceType> HalfBook<P> {
+    pub fn new(side: OrderType) -> HalfBook<P> {
+        HalfBook {
+            side,
+            levels: BTreeMap::new()
+        }
+    }
+
+    /* a side already holding `levels`, e.g. from another book */
+    pub fn with_levels(side: OrderType,
+                       levels: BTreeMap<P, VecDeque<OrderId>>) ->
+        HalfBook<P> {
+        HalfBook {side, levels}
+    }
+
+    pub fn get_side(&self) -> OrderType {
+        self.side
+    }
+
+    /* the number of price levels */
+    pub fn len(&self) -> usize {
+        self.levels.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.levels.is_empty()
+    }
+
+    /* the number of orders queued across every level */
+    pub fn order_count(&self) -> usize {
+        self.levels.values().map(VecDeque::len).sum()
+    }
+
+    /* how many order IDs the level queues have room for */
+    pub fn capacity(&self) -> usize {
+        self.levels.values().map(VecDeque::capacity).sum()
+    }
+
+    pub fn get(&self, price: &P) -> Option<&VecDeque<OrderId>> {
+        self.levels.get(price)
+    }
+
+    pub fn get_mut(&mut self, price: &P) -> Option<&mut VecDeque<OrderId>> {
+        self.levels.get_mut(price)
+    }
+
+    pub fn contains(&self, price: &P) -> bool {
+        self.levels.contains_key(price)
+    }
+
+    /* every level, best first */
+    pub fn iter(&self) -> LevelsIter<'_, P> {
+        BestFirst {
+            levels: self.levels.iter(),
+            side: self.side
+        }
+    }
+
+    /* every level's queue, best first */
+    pub fn queues(&self) ->
+        impl DoubleEndedIterator<Item=&VecDeque<OrderId>> + Clone + '_ {
+        self.iter().map(|(_, queue)| queue)
+    }
+
+    /* every level's queue, in no particular order */
+    pub fn queues_mut(&mut self) ->
+        impl Iterator<Item=(&P, &mut VecDeque<OrderId>)> {
+        self.levels.iter_mut()
+    }
+
+    pub fn best(&self) -> Option<(&P, &VecDeque<OrderId>)> {
+        self.iter().next()
+    }
+
+    /* the level furthest from the top of the book */
+    pub fn deepest(&self) -> Option<(&P, &VecDeque<OrderId>)> {
+        self.iter().next_back()
+    }
+
+    /* whether `price` is a better price than `than` on this side */
+    pub fn is_better(&self, price: &P, than: &P) -> bool {
+        match self.side {
+            OrderType::Bid => price > than,
+            OrderType::Ask => price < than
+        }
+    }
+
+    /* the levels at `price` or better, best first */
+    pub fn at_or_better(&self, price: P) -> LevelsRange<'_, P> {
+        let range: (Bound<P>, Bound<P>) = match self.side {
+            OrderType::Bid => (Bound::Included(price), Bound::Unbounded),
+            OrderType::Ask => (Bound::Unbounded, Bound::Included(price))
+        };
+
+        BestFirst {
+            levels: self.levels.range(range),
+            side: self.side
+        }
+    }
+
+    /* the best price worse than `after`, or the best of all without it,
+     * for walking the levels while changing them */
+    pub fn next_after(&self, after: Option<P>) -> Option<P> {
+        let range: (Bound<P>, Bound<P>) = match (self.side, after) {
+            (_, None) => (Bound::Unbounded, Bound::Unbounded),
+            (OrderType::Bid, Some(after)) =>
+                (Bound::Unbounded, Bound::Excluded(after)),
+            (OrderType::Ask, Some(after)) =>
+                (Bound::Excluded(after), Bound::Unbounded)
+        };
+
+        BestFirst {
+            levels: self.levels.range(range),
+            side: self.side
+        }.next().map(|(price, _)| *price)
+    }
+
+    /* the queue at `price`, opened (from `pool`) if there isn't one */
+    pub fn level_mut(&mut self, price: P, pool: &mut LevelPool) ->
+        &mut VecDeque<OrderId> {
+        self.levels.entry(price).or_insert_with(|| pool.take())
+    }
+
+    /* takes `id` out of the queue at `price`, closing the level if that
+     * empties it */
+    pub fn remove(&mut self, price: &P, id: OrderId, pool: &mut LevelPool) {
+        if let Some(level) = self.levels.get_mut(price) {
+            pool::remove_queued(level, id);
+            self.close_if_empty(price, pool);
+        }
+    }
+
+    /* closes the level at `price` if nothing is queued there */
+    pub fn close_if_empty(&mut self, price: &P, pool: &mut LevelPool) {
+        if self.levels.get(price).is_some_and(VecDeque::is_empty) {
+            if let Some(level) = self.levels.remove(price) {
+                pool.give(level);
+            }
+        }
+    }
+
+    /* keeps only the queued IDs `keep` accepts, then `prune`s */
+    pub fn retain<F>(&mut self, mut keep: F, pool: &mut LevelPool)
+        where F: FnMut(&OrderId) -> bool {
+        for queue in self.levels.values_mut() {
+            queue.retain(&mut keep);
+        }
+
+        self.prune(pool);
+    }
+
+    /* closes every empty level */
+    pub fn prune(&mut self, pool: &mut LevelPool) {
+        let empty: Vec<P> = self.levels.iter()
+            .filter(|(_, queue)| queue.is_empty())
+            .map(|(price, _)| *price)
+            .collect();
+
+        for price in empty {
+            if let Some(level) = self.levels.remove(&price) {
+                pool.give(level);
+            }
+        }
+    }
+
+    /* shrinks every level queue to fit */
+    pub fn shrink_to_fit(&mut self) {
+        self.levels.values_mut().for_each(VecDeque::shrink_to_fit);
+    }
+
+    pub fn clear(&mut self) {
+        self.levels.clear();
+    }
+
+    pub fn levels(&self) -> &BTreeMap<P, VecDeque<OrderId>> {
+        &self.levels
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::price::F64Price;
+
+    fn side(side: OrderType, pool: &mut LevelPool) -> HalfBook<F64Price> {
+        let mut half: HalfBook<F64Price> = HalfBook::new(side);
+
+        for (id, price) in [(1, 10.0), (2, 11.0), (3, 12.0), (4, 11.0)] {
+            if let Some(price) = F64Price::from_price(price) {
+                half.level_mut(price, pool).push_back(id);
+            }
+        }
+
+        half
+    }
+
+    #[test]
+    fn test_best_first() {
+        let mut pool: LevelPool = LevelPool::new(4);
+        let prices = |levels: &mut dyn Iterator<Item=(&F64Price,
+                                                      &VecDeque<OrderId>)>|
+            levels.map(|(price, _)| price.to_price()).collect::<Vec<f64>>();
+        let eleven: Option<F64Price> = F64Price::from_price(11.0);
+        let mut bids: HalfBook<F64Price> = side(OrderType::Bid, &mut pool);
+        let asks: HalfBook<F64Price> = side(OrderType::Ask, &mut pool);
+
+        /* the same levels, walked from either end */
+        assert_eq!(prices(&mut bids.iter()), vec![12.0, 11.0, 10.0]);
+        assert_eq!(prices(&mut asks.iter()), vec![10.0, 11.0, 12.0]);
+        assert_eq!(eleven.map(|price| prices(&mut bids.at_or_better(price))),
+                   Some(vec![12.0, 11.0]));
+        assert_eq!(eleven.map(|price| prices(&mut asks.at_or_better(price))),
+                   Some(vec![10.0, 11.0]));
+        assert_eq!(bids.next_after(eleven).map(|price| price.to_price()),
+                   Some(10.0));
+        assert_eq!(asks.next_after(eleven).map(|price| price.to_price()),
+                   Some(12.0));
+        assert_eq!(bids.deepest().map(|(price, _)| price.to_price()),
+                   Some(10.0));
+
+        /* emptied levels close, and go back to the pool */
+        if let Some(price) = eleven {
+            bids.remove(&price, 2, &mut pool);
+            assert_eq!(bids.order_count(), 3);
+            bids.remove(&price, 4, &mut pool);
+        }
+
+        assert_eq!(prices(&mut bids.iter()), vec![12.0, 10.0]);
+        assert_eq!(pool.len(), 1);
+
+        bids.retain(|id| *id != 3, &mut pool);
+        assert_eq!(prices(&mut bids.iter()), vec![10.0]);
+    }
+}
@@ -0,0 +1,288 @@
+use std::io::Write;
+use std::slice;
+
+use chrono::{DateTime, Utc};
+use serde::{Serialize, Deserialize};
+
+use crate::event::Event;
+use crate::io::IoError;
+use crate::levels::{Level, Levels};
+use crate::order::OrderType;
+use crate::quantity::Quantity;
+use crate::recorder::RecordingInterval;
+use crate::replica::{BookReplica, ReplicaError};
+use crate::sink::{EventSink, SinkError};
+use crate::snapshot::BookSnapshot;
+
+/* one level of one sample, in the long format that heatmap plots take:
+ * one row per timestamp, side and price */
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HeatmapRow {
+    pub timestamp: DateTime<Utc>,
+    pub side: OrderType,
+    pub price: f64,
+    pub quantity: Quantity
+}
+
+/* Samples a book's depth as its events go by, at the given interval, into
+ * rows for a bookmap-style heatmap. Counting events, a sample is skipped if
+ * the depth hasn't changed since the last one; sampling by time, every
+ * sample is kept, so that each column of the heatmap is filled in. Use as
+ * a book's sink, or feed it events from wherever they were stored, then
+ * write the rows out with `write_heatmap_csv` (or `write_heatmap_parquet`,
+ * with the `parquet` feature). */
+#[derive(Debug, Clone, PartialEq)]
+pub struct HeatmapSampler {
+    interval: RecordingInterval,
+    /* the levels sampled on each side, or all of them */
+    depth: Option<usize>,
+    book: BookReplica,
+    /* as of the last sample */
+    sampled: Option<(u64, DateTime<Utc>, Levels)>,
+    rows: Vec<HeatmapRow>
+}
+
+impl HeatmapSampler {
+    /* for a book that has not had any events yet */
+    pub fn new(interval: RecordingInterval) -> HeatmapSampler {
+        HeatmapSampler::starting_from(BookReplica::default(), interval)
+    }
+
+    pub fn from_snapshot(snapshot: &BookSnapshot,
+                         interval: RecordingInterval) -> HeatmapSampler {
+        HeatmapSampler::starting_from(BookReplica::from_snapshot(snapshot),
+                                      interval)
+    }
+
+    fn starting_from(book: BookReplica, interval: RecordingInterval) ->
+        HeatmapSampler {
+        HeatmapSampler {
+            interval,
+            depth: None,
+            book,
+            sampled: None,
+            rows: vec![]
+        }
+    }
+
+    /* samples only the best `depth` levels on each side */
+    pub fn depth(mut self, depth: usize) -> HeatmapSampler {
+        self.depth = Some(depth);
+        self
+    }
+
+    pub fn get_rows(&self) -> &[HeatmapRow] {
+        &self.rows
+    }
+
+    /* the rows sampled so far, e.g. to write out a chunk of a long run */
+    pub fn take_rows(&mut self) -> Vec<HeatmapRow> {
+        std::mem::take(&mut self.rows)
+    }
+
+    pub fn observe(&mut self, event: &Event) -> Result<(), ReplicaError> {
+        let previous: u64 = self.book.get_last_seq();
+
+        self.book.apply_delta(slice::from_ref(event))?;
+
+        if self.book.get_last_seq() == previous {
+            return Ok(());
+        }
+
+        let levels: Levels = match (self.book.levels(), self.depth) {
+            (levels, Some(depth)) => Levels::new(
+                levels.get_bids().iter().take(depth).copied().collect(),
+                levels.get_asks().iter().take(depth).copied().collect()),
+            (levels, None) => levels
+        };
+        let due: bool = match (&self.sampled, self.interval) {
+            (None, _) => true,
+            (Some((seq, _, last)), RecordingInterval::Events(events)) =>
+                event.get_seq() - seq >= events && *last != levels,
+            (Some((_, _, last)), RecordingInterval::EveryEvent) =>
+                *last != levels,
+            (Some((_, at, _)), RecordingInterval::Every(duration)) =>
+                event.get_timestamp() - *at >= duration
+        };
+
+        if due {
+            let timestamp: DateTime<Utc> = event.get_timestamp();
+            let rows = |side: OrderType, levels: &[Level]| ->
+                Vec<HeatmapRow> {
+                levels.iter()
+                    .map(|(price, quantity)| HeatmapRow {
+                        timestamp,
+                        side,
+                        price: *price,
+                        quantity: *quantity
+                    })
+                    .collect()
+            };
+
+            self.rows.extend(rows(OrderType::Bid, levels.get_bids()));
+            self.rows.extend(rows(OrderType::Ask, levels.get_asks()));
+            self.sampled = Some((event.get_seq(), timestamp, levels));
+        }
+
+        Ok(())
+    }
+}
+
+impl EventSink for HeatmapSampler {
+    fn write(&mut self, event: &Event) -> Result<(), SinkError> {
+        Ok(self.observe(event)?)
+    }
+
+    fn flush(&mut self) -> Result<(), SinkError> {
+        Ok(())
+    }
+}
+
+fn side_name(side: &OrderType) -> &'static str {
+    match side {
+        OrderType::Bid => "bid",
+        OrderType::Ask => "ask"
+    }
+}
+
+/* as `timestamp,side,price,quantity`, with a header */
+pub fn write_heatmap_csv<W: Write>(rows: &[HeatmapRow], writer: W) ->
+    Result<(), IoError> {
+    let mut writer: csv::Writer<W> = csv::Writer::from_writer(writer);
+
+    writer.write_record(["timestamp", "side", "price", "quantity"])?;
+
+    for row in rows {
+        writer.write_record(&[row.timestamp.to_rfc3339(),
+                              side_name(&row.side).to_string(),
+                              row.price.to_string(),
+                              row.quantity.to_string()])?;
+    }
+
+    writer.flush()?;
+    Ok(())
+}
+
+/* As a single row group of a Parquet file with the same columns as the
+ * CSV: the timestamp in microseconds since the epoch, and the quantity as a
+ * double, which is what dataframe libraries will make of it anyway. */
+#[cfg(feature = "parquet")]
+pub fn write_heatmap_parquet<W: Write + Send>(rows: &[HeatmapRow],
+                                              writer: W) ->
+    Result<(), parquet::errors::ParquetError> {
+    use std::sync::Arc;
+
+    use parquet::data_type::{ByteArray, ByteArrayType, DoubleType,
+                             Int64Type};
+    use parquet::file::properties::WriterProperties;
+    use parquet::file::writer::{SerializedColumnWriter, SerializedFileWriter};
+    use parquet::schema::parser::parse_message_type;
+
+    const SCHEMA: &str = "message heatmap {
+        REQUIRED INT64 timestamp (TIMESTAMP(MICROS, true));
+        REQUIRED BYTE_ARRAY side (UTF8);
+        REQUIRED DOUBLE price;
+        REQUIRED DOUBLE quantity;
+    }";
+
+    let mut file: SerializedFileWriter<W> = SerializedFileWriter::new(
+        writer, Arc::new(parse_message_type(SCHEMA)?),
+        Arc::new(WriterProperties::builder().build()))?;
+    let mut group = file.next_row_group()?;
+    let mut column = |write: &dyn Fn(&mut SerializedColumnWriter<'_>) ->
+                      parquet::errors::Result<usize>| ->
+        parquet::errors::Result<()> {
+        if let Some(mut column) = group.next_column()? {
+            write(&mut column)?;
+            column.close()?;
+        }
+
+        Ok(())
+    };
+
+    column(&|column| column.typed::<Int64Type>().write_batch(
+        &rows.iter().map(|row| row.timestamp.timestamp_micros())
+            .collect::<Vec<i64>>(), None, None))?;
+    column(&|column| column.typed::<ByteArrayType>().write_batch(
+        &rows.iter().map(|row| ByteArray::from(side_name(&row.side)))
+            .collect::<Vec<ByteArray>>(), None, None))?;
+    column(&|column| column.typed::<DoubleType>().write_batch(
+        &rows.iter().map(|row| row.price).collect::<Vec<f64>>(), None,
+        None))?;
+    column(&|column| column.typed::<DoubleType>().write_batch(
+        &rows.iter().map(|row| crate::quantity::to_f64(row.quantity))
+            .collect::<Vec<f64>>(), None, None))?;
+
+    group.close()?;
+    file.close()?;
+    Ok(())
+}
+
+#[cfg(all(test, not(feature = "decimal-quantity")))]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use crate::account::Account;
+    use crate::book::Book;
+    use crate::matching::PriceTime;
+    use crate::order::{Order, OrderId};
+
+    fn build_order(id: OrderId, order_type: OrderType, price: f64,
+                   quantity: Quantity) -> Order {
+        let mut holdings: HashMap<String, Quantity> = HashMap::new();
+        holdings.insert("BOOK".to_string(), 1000);
+
+        let owner: Account = Account::new(id, "Account".to_string(),
+                                          12000.00, holdings);
+
+        Order::new(id, owner, "BOOK".to_string(), order_type, price, quantity)
+    }
+
+    #[test]
+    fn test_heatmap() -> crate::Result<()> {
+        let mut book: Book<PriceTime, HeatmapSampler> =
+            Book::with_sink(1, "Book".to_string(), "BOOK".to_string(),
+                            PriceTime,
+                            HeatmapSampler::new(RecordingInterval::EveryEvent)
+                            .depth(1));
+
+        book.submit(build_order(1, OrderType::Bid, 10.00, 5))?;
+        book.submit(build_order(2, OrderType::Bid, 9.50, 5))?;
+        book.submit(build_order(3, OrderType::Ask, 10.50, 7))?;
+
+        let rows: Vec<(OrderType, f64, Quantity)> = book.get_sink()
+            .get_rows()
+            .iter()
+            .map(|row| (row.side, row.price, row.quantity))
+            .collect();
+
+        /* the second bid is outside the depth sampled, so changes nothing */
+        assert_eq!(rows, vec![(OrderType::Bid, 10.00, 5),
+                              (OrderType::Bid, 10.00, 5),
+                              (OrderType::Ask, 10.50, 7)]);
+
+        let mut csv: Vec<u8> = vec![];
+
+        write_heatmap_csv(book.get_sink().get_rows(), &mut csv)?;
+        assert_eq!(String::from_utf8_lossy(&csv).lines().nth(3)
+                       .map(|line| line.ends_with(",ask,10.5,7")),
+                   Some(true));
+        Ok(())
+    }
+
+    #[cfg(feature = "parquet")]
+    #[test]
+    fn test_parquet() -> Result<(), parquet::errors::ParquetError> {
+        let rows: Vec<HeatmapRow> = vec![HeatmapRow {
+            timestamp: DateTime::<Utc>::default(),
+            side: OrderType::Bid,
+            price: 10.00,
+            quantity: 5
+        }];
+        let mut file: Vec<u8> = vec![];
+
+        write_heatmap_parquet(&rows, &mut file)?;
+        assert!(file.starts_with(b"PAR1") && file.ends_with(b"PAR1"));
+        Ok(())
+    }
+}
@@ -0,0 +1,148 @@
+use std::convert::TryFrom;
+use std::fmt::Debug;
+
+use chrono::Utc;
+
+use crate::order::OrderId;
+
+/* Hands out order IDs. Books reject IDs they have already seen, so an
+ * exchange fronting several clients needs one of these rather than trusting
+ * client-supplied IDs. Generators can be used either as a generic parameter
+ * or boxed as `Box<dyn OrderIdGenerator>`. */
+pub trait OrderIdGenerator: Debug {
+    fn next_id(&mut self) -> OrderId;
+}
+
+impl OrderIdGenerator for Box<dyn OrderIdGenerator> {
+    fn next_id(&mut self) -> OrderId {
+        (**self).next_id()
+    }
+}
+
+/* 1, 2, 3, ... */
+#[derive(Debug, Clone, PartialEq)]
+pub struct MonotonicIdGenerator {
+    next: OrderId
+}
+
+impl MonotonicIdGenerator {
+    pub fn new() -> MonotonicIdGenerator {
+        MonotonicIdGenerator::starting_at(1)
+    }
+
+    /* for resuming after IDs up to `next - 1` have been issued */
+    pub fn starting_at(next: OrderId) -> MonotonicIdGenerator {
+        MonotonicIdGenerator {next}
+    }
+}
+
+impl Default for MonotonicIdGenerator {
+    fn default() -> MonotonicIdGenerator {
+        MonotonicIdGenerator::new()
+    }
+}
+
+impl OrderIdGenerator for MonotonicIdGenerator {
+    fn next_id(&mut self) -> OrderId {
+        let id: OrderId = self.next;
+        self.next += 1;
+        id
+    }
+}
+
+/* Snowflake-style IDs, unique across generators with distinct node numbers
+ * without any coordination between them:
+ *
+ *     | milliseconds since the epoch (64) | node (32) | sequence (32) |
+ *
+ * IDs from one generator always increase, even if the wall clock goes
+ * backwards or more than 2^32 are issued in a millisecond; in both cases
+ * the generator carries on from its own notion of the time. */
+#[derive(Debug, Clone, PartialEq)]
+pub struct SnowflakeIdGenerator {
+    node: u32,
+    millis: u64,
+    sequence: u32
+}
+
+impl SnowflakeIdGenerator {
+    pub fn new(node: u32) -> SnowflakeIdGenerator {
+        SnowflakeIdGenerator {
+            node,
+            millis: 0,
+            sequence: 0
+        }
+    }
+
+    pub fn get_node(&self) -> u32 {
+        self.node
+    }
+
+    /* the node and time encoded in an ID this kind of generator issued */
+    pub fn decode(id: OrderId) -> (u32, u64) {
+        ((id >> 32) as u32, (id >> 64) as u64)
+    }
+
+    fn now() -> u64 {
+        u64::try_from(Utc::now().timestamp_millis()).unwrap_or(0)
+    }
+}
+
+impl OrderIdGenerator for SnowflakeIdGenerator {
+    fn next_id(&mut self) -> OrderId {
+        let now: u64 = SnowflakeIdGenerator::now();
+
+        if now > self.millis {
+            self.millis = now;
+            self.sequence = 0;
+        } else if self.sequence == u32::MAX {
+            self.millis += 1;
+            self.sequence = 0;
+        } else {
+            self.sequence += 1;
+        }
+
+        ((self.millis as u128) << 64) | ((self.node as u128) << 32) |
+            self.sequence as u128
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_monotonic() {
+        let mut generator: MonotonicIdGenerator =
+            MonotonicIdGenerator::starting_at(41);
+
+        let actual_ids: Vec<OrderId> =
+            (0..3).map(|_| generator.next_id()).collect();
+
+        assert_eq!(actual_ids, vec![41, 42, 43]);
+    }
+
+    #[test]
+    fn test_snowflake_increasing_and_tagged() {
+        let mut generator: SnowflakeIdGenerator = SnowflakeIdGenerator::new(7);
+
+        let ids: Vec<OrderId> =
+            (0..10_000).map(|_| generator.next_id()).collect();
+
+        assert!(ids.windows(2).all(|pair| pair[0] < pair[1]));
+        assert!(ids.iter()
+            .all(|id| SnowflakeIdGenerator::decode(*id).0 == 7));
+    }
+
+    #[test]
+    fn test_snowflake_survives_clock_going_backwards() {
+        let mut generator: SnowflakeIdGenerator = SnowflakeIdGenerator::new(1);
+        generator.millis = u64::MAX / 2;
+
+        let first: OrderId = generator.next_id();
+        let second: OrderId = generator.next_id();
+
+        assert!(first < second);
+        assert_eq!(SnowflakeIdGenerator::decode(second).1, u64::MAX / 2);
+    }
+}
@@ -0,0 +1,91 @@
+use std::fmt;
+
+use serde::{Serialize, Deserialize};
+
+use crate::account::AccountId;
+use crate::order::{OrderId, OrderType};
+use crate::quantity::Quantity;
+
+/* What a book does when it finds itself inconsistent after a change; see
+ * `BookConfig::integrity`. Checking walks the whole book after every
+ * operation, so is meant for debugging and soak tests rather than
+ * production. */
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum IntegrityMode {
+    /* panics, with every violation found */
+    Strict,
+    /* records the violations for `Book::get_violations` and carries on */
+    Log,
+    /* records the violations, then rebuilds the book from its orders and
+     * uncrosses it */
+    Repair
+}
+
+/* one way in which a book is inconsistent with itself */
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Violation {
+    /* the best bid is at or above the best ask while matching */
+    Crossed {
+        bid: f64,
+        ask: f64
+    },
+    /* a price level with nothing queued in it */
+    EmptyLevel {
+        side: OrderType,
+        price: f64
+    },
+    /* queued at a level, but not resting */
+    UnknownOrder {
+        side: OrderType,
+        price: f64,
+        id: OrderId
+    },
+    /* the size at a level, summed over its queue, against that summed over
+     * the resting orders at its price */
+    Depth {
+        side: OrderType,
+        price: f64,
+        cached: Quantity,
+        recomputed: Quantity
+    },
+    /* the buying power held for an account, against that of its resting
+     * bids */
+    Reserved {
+        account: AccountId,
+        cached: f64,
+        recomputed: f64
+    },
+    /* more taken off an order (e.g. by a fill) than was left of it; the
+     * order is taken to be gone */
+    Overdrawn {
+        side: OrderType,
+        price: f64,
+        id: OrderId,
+        available: Quantity,
+        taken: Quantity
+    }
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Violation::Crossed { bid, ask } =>
+                write!(f, "crossed: best bid {} against best ask {}", bid,
+                       ask),
+            Violation::EmptyLevel { side, price } =>
+                write!(f, "{:?} level {} is empty", side, price),
+            Violation::UnknownOrder { side, price, id } =>
+                write!(f, "{:?} level {}: order {} is not resting", side,
+                       price, id),
+            Violation::Depth { side, price, cached, recomputed } =>
+                write!(f, "{:?} level {}: queued {} but orders sum to {}",
+                       side, price, cached, recomputed),
+            Violation::Reserved { account, cached, recomputed } =>
+                write!(f, "account {}: reserved {} but bids hold {}",
+                       account, cached, recomputed),
+            Violation::Overdrawn { side, price, id, available, taken } =>
+                write!(f, "{:?} level {}: took {} off order {} but only {} \
+                           was left", side, price, taken, id, available)
+        }
+    }
+}
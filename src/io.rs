@@ -0,0 +1,405 @@
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Lines, Read, Write};
+use std::path::Path;
+
+use chrono::{DateTime, TimeZone, Utc};
+use serde_json::Value;
+
+use crate::account::Account;
+use crate::event::*;
+use crate::order::*;
+use crate::quantity::{self, Quantity};
+
+/* Streaming readers for order datasets and writers for what a book produces
+ * from them. Input columns (or JSON fields) are located by name via a
+ * `Schema`, so datasets don't need to be massaged into one fixed layout. */
+
+#[derive(Debug, thiserror::Error)]
+pub enum IoError {
+    #[error("I/O error: {0}")]
+    Io(#[from] io::Error),
+    #[error("CSV error: {0}")]
+    Csv(#[from] csv::Error),
+    #[error("JSON error: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("missing field {0:?}")]
+    MissingField(String),
+    #[error("invalid value {1:?} for field {0:?}")]
+    InvalidField(String, String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Schema {
+    pub id: String,
+    pub side: String,
+    pub price: String,
+    pub quantity: String,
+    /* datasets without timestamps leave orders stamped on creation */
+    pub timestamp: Option<String>
+}
+
+impl Default for Schema {
+    fn default() -> Schema {
+        Schema {
+            id: "id".to_string(),
+            side: "side".to_string(),
+            price: "price".to_string(),
+            quantity: "quantity".to_string(),
+            timestamp: Some("timestamp".to_string())
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct OrderRecord {
+    id: OrderId,
+    side: OrderType,
+    price: f64,
+    quantity: Quantity,
+    timestamp: Option<DateTime<Utc>>
+}
+
+impl OrderRecord {
+    pub fn new(id: OrderId, side: OrderType, price: f64, quantity: Quantity,
+               timestamp: Option<DateTime<Utc>>) -> OrderRecord {
+        OrderRecord {id, side, price, quantity, timestamp}
+    }
+
+    pub fn get_id(&self) -> OrderId {
+        self.id
+    }
+
+    pub fn get_side(&self) -> OrderType {
+        self.side
+    }
+
+    pub fn get_price(&self) -> f64 {
+        self.price
+    }
+
+    pub fn get_quantity(&self) -> Quantity {
+        self.quantity
+    }
+
+    pub fn get_timestamp(&self) -> Option<DateTime<Utc>> {
+        self.timestamp
+    }
+
+    pub fn into_order(self, owner: Account, ticker: String) -> Order {
+        let mut order: Order = Order::new(self.id, owner, ticker, self.side,
+                                          self.price, self.quantity);
+
+        if let Some(timestamp) = self.timestamp {
+            order.set_created(timestamp);
+        }
+
+        order
+    }
+}
+
+fn invalid(field: &str, value: &str) -> IoError {
+    IoError::InvalidField(field.to_string(), value.to_string())
+}
+
+pub(crate) fn parse_side(text: &str) -> Result<OrderType, IoError> {
+    match text.trim().to_lowercase().as_str() {
+        "bid" | "buy" | "b" => Ok(OrderType::Bid),
+        "ask" | "sell" | "offer" | "s" | "a" => Ok(OrderType::Ask),
+        _ => Err(invalid("side", text))
+    }
+}
+
+/* RFC 3339, or an integer number of milliseconds since the epoch */
+fn parse_timestamp(text: &str) -> Result<DateTime<Utc>, IoError> {
+    let text: &str = text.trim();
+
+    if let Ok(timestamp) = DateTime::parse_from_rfc3339(text) {
+        return Ok(timestamp.with_timezone(&Utc));
+    }
+
+    text.parse::<i64>().ok()
+        .and_then(|millis| Utc.timestamp_millis_opt(millis).single())
+        .ok_or_else(|| invalid("timestamp", text))
+}
+
+fn parse_record<F>(schema: &Schema, field: F) -> Result<OrderRecord, IoError>
+    where F: Fn(&str) -> Option<String> {
+    let get = |name: &str| field(name)
+        .ok_or_else(|| IoError::MissingField(name.to_string()));
+
+    let id: String = get(&schema.id)?;
+    let price: String = get(&schema.price)?;
+    let quantity: String = get(&schema.quantity)?;
+    let timestamp: Option<DateTime<Utc>> = match &schema.timestamp {
+        Some(name) => match field(name) {
+            Some(text) if !text.trim().is_empty() =>
+                Some(parse_timestamp(&text)?),
+            _ => None
+        },
+        None => None
+    };
+
+    Ok(OrderRecord {
+        id: id.trim().parse().map_err(|_| invalid(&schema.id, &id))?,
+        side: parse_side(&get(&schema.side)?)?,
+        price: price.trim().parse().map_err(|_| invalid(&schema.price,
+                                                        &price))?,
+        quantity: quantity::parse(&quantity)
+            .ok_or_else(|| invalid(&schema.quantity, &quantity))?,
+        timestamp
+    })
+}
+
+pub struct CsvOrderReader<R: Read> {
+    schema: Schema,
+    headers: csv::StringRecord,
+    records: csv::StringRecordsIntoIter<R>
+}
+
+impl<R: Read> CsvOrderReader<R> {
+    pub fn new(reader: R, schema: Schema) -> Result<CsvOrderReader<R>,
+                                                    IoError> {
+        let mut reader: csv::Reader<R> = csv::ReaderBuilder::new()
+            .trim(csv::Trim::All)
+            .from_reader(reader);
+        let headers: csv::StringRecord = reader.headers()?.clone();
+
+        Ok(CsvOrderReader {schema, headers, records: reader.into_records()})
+    }
+}
+
+impl CsvOrderReader<File> {
+    pub fn open<P: AsRef<Path>>(path: P, schema: Schema) ->
+        Result<CsvOrderReader<File>, IoError> {
+        CsvOrderReader::new(File::open(path)?, schema)
+    }
+}
+
+impl<R: Read> Iterator for CsvOrderReader<R> {
+    type Item = Result<OrderRecord, IoError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let record: csv::StringRecord = match self.records.next()? {
+            Ok(record) => record,
+            Err(e) => return Some(Err(e.into()))
+        };
+        let headers: &csv::StringRecord = &self.headers;
+
+        Some(parse_record(&self.schema, |name| {
+            headers.iter()
+                .position(|header| header == name)
+                .and_then(|i| record.get(i))
+                .map(|value| value.to_string())
+        }))
+    }
+}
+
+/* one order from a line of JSON, e.g. as read by `JsonLinesOrderReader` */
+pub fn parse_json_record(line: &str, schema: &Schema) ->
+    Result<OrderRecord, IoError> {
+    let value: Value = serde_json::from_str(line)?;
+
+    parse_record(schema, |name| {
+        match value.get(name)? {
+            Value::String(text) => Some(text.clone()),
+            Value::Null => None,
+            other => Some(other.to_string())
+        }
+    })
+}
+
+pub struct JsonLinesOrderReader<R: BufRead> {
+    schema: Schema,
+    lines: Lines<R>
+}
+
+impl<R: BufRead> JsonLinesOrderReader<R> {
+    pub fn new(reader: R, schema: Schema) -> JsonLinesOrderReader<R> {
+        JsonLinesOrderReader {schema, lines: reader.lines()}
+    }
+}
+
+impl JsonLinesOrderReader<BufReader<File>> {
+    pub fn open<P: AsRef<Path>>(path: P, schema: Schema) ->
+        Result<JsonLinesOrderReader<BufReader<File>>, IoError> {
+        Ok(JsonLinesOrderReader::new(BufReader::new(File::open(path)?),
+                                     schema))
+    }
+}
+
+impl<R: BufRead> Iterator for JsonLinesOrderReader<R> {
+    type Item = Result<OrderRecord, IoError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let line: String = match self.lines.next()? {
+                Ok(line) => line,
+                Err(e) => return Some(Err(e.into()))
+            };
+
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            return Some(parse_json_record(&line, &self.schema));
+        }
+    }
+}
+
+pub fn write_events_jsonl<W: Write>(events: &[Event], mut writer: W) ->
+    Result<(), IoError> {
+    for event in events {
+        serde_json::to_writer(&mut writer, event)?;
+        writer.write_all(b"\n")?;
+    }
+
+    writer.flush()?;
+    Ok(())
+}
+
+pub fn write_trades_jsonl<W: Write>(trades: &[Trade], mut writer: W) ->
+    Result<(), IoError> {
+    for trade in trades {
+        serde_json::to_writer(&mut writer, trade)?;
+        writer.write_all(b"\n")?;
+    }
+
+    writer.flush()?;
+    Ok(())
+}
+
+pub fn write_trades_csv<W: Write>(trades: &[Trade], writer: W) ->
+    Result<(), IoError> {
+    let mut writer: csv::Writer<W> = csv::Writer::from_writer(writer);
+
+    writer.write_record(["timestamp", "price", "quantity", "aggressor",
+                         "resting", "aggressor_side"])?;
+
+    for trade in trades {
+        let side: &str = match trade.get_aggressor_side() {
+            OrderType::Bid => "bid",
+            OrderType::Ask => "ask"
+        };
+
+        writer.write_record(&[trade.get_timestamp().to_rfc3339(),
+                              trade.get_price().to_string(),
+                              trade.get_quantity().to_string(),
+                              trade.get_aggressor().to_string(),
+                              trade.get_resting().to_string(),
+                              side.to_string()])?;
+    }
+
+    writer.flush()?;
+    Ok(())
+}
+
+#[cfg(all(test, not(feature = "decimal-quantity")))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_csv_reader_with_schema() -> Result<(), IoError> {
+        let data: &str = "order_id,direction,px,size,ts\n\
+                          1,buy,12.5,100,2020-01-01T00:00:00Z\n\
+                          2,SELL,13,50,\n";
+        let schema: Schema = Schema {
+            id: "order_id".to_string(),
+            side: "direction".to_string(),
+            price: "px".to_string(),
+            quantity: "size".to_string(),
+            timestamp: Some("ts".to_string())
+        };
+
+        let actual_records: Vec<OrderRecord> =
+            CsvOrderReader::new(data.as_bytes(), schema)?
+                .collect::<Result<Vec<OrderRecord>, IoError>>()?;
+        let expected_records: Vec<OrderRecord> = vec![
+            OrderRecord::new(1, OrderType::Bid, 12.5, 100,
+                             Some(Utc.with_ymd_and_hms(2020, 1, 1, 0, 0, 0)
+                                  .unwrap())),
+            OrderRecord::new(2, OrderType::Ask, 13.0, 50, None),
+        ];
+
+        assert_eq!(actual_records, expected_records);
+        Ok(())
+    }
+
+    #[test]
+    fn test_jsonl_reader() -> Result<(), IoError> {
+        let data: &str = "{\"id\": 1, \"side\": \"bid\", \"price\": 12.5, \
+                          \"quantity\": 100, \"timestamp\": 1577836800000}\n\
+                          \n\
+                          {\"id\": \"2\", \"side\": \"ask\", \"price\": \"13\", \
+                          \"quantity\": \"50\"}\n";
+
+        let actual_records: Vec<OrderRecord> =
+            JsonLinesOrderReader::new(data.as_bytes(), Schema::default())
+                .collect::<Result<Vec<OrderRecord>, IoError>>()?;
+        let expected_records: Vec<OrderRecord> = vec![
+            OrderRecord::new(1, OrderType::Bid, 12.5, 100,
+                             Some(Utc.with_ymd_and_hms(2020, 1, 1, 0, 0, 0)
+                                  .unwrap())),
+            OrderRecord::new(2, OrderType::Ask, 13.0, 50, None),
+        ];
+
+        assert_eq!(actual_records, expected_records);
+        Ok(())
+    }
+
+    #[test]
+    fn test_reader_reports_bad_fields() {
+        let data: &str = "id,side,price\n1,sideways,12\n";
+
+        let actual_result: Option<Result<OrderRecord, IoError>> =
+            CsvOrderReader::new(data.as_bytes(), Schema::default())
+                .ok()
+                .and_then(|mut reader| reader.next());
+
+        assert!(matches!(actual_result, Some(Err(IoError::MissingField(_)))));
+    }
+
+    #[test]
+    fn test_write_trades_csv() -> Result<(), IoError> {
+        let timestamp: DateTime<Utc> =
+            Utc.with_ymd_and_hms(2020, 1, 1, 0, 0, 0).unwrap();
+        let trades: Vec<Trade> = vec![
+            Trade::new(timestamp, 12.5, 10, 2, 1, OrderType::Bid)
+        ];
+        let mut buffer: Vec<u8> = vec![];
+
+        write_trades_csv(&trades, &mut buffer)?;
+
+        assert_eq!(String::from_utf8_lossy(&buffer),
+                   "timestamp,price,quantity,aggressor,resting,aggressor_side\n\
+                    2020-01-01T00:00:00+00:00,12.5,10,2,1,bid\n");
+        Ok(())
+    }
+
+    #[test]
+    fn test_write_events_jsonl_round_trip() -> Result<(), IoError> {
+        let timestamp: DateTime<Utc> =
+            Utc.with_ymd_and_hms(2020, 1, 1, 0, 0, 0).unwrap();
+        let events: Vec<Event> = vec![
+            Event::new(1, timestamp, EventKind::Post {
+                order: 1,
+                order_type: OrderType::Ask,
+                price: 12.5,
+                quantity: 10,
+                hidden: false
+            }),
+            Event::new(2, timestamp, EventKind::Match(
+                Trade::new(timestamp, 12.5, 10, 2, 1, OrderType::Bid))),
+        ];
+        let mut buffer: Vec<u8> = vec![];
+
+        write_events_jsonl(&events, &mut buffer)?;
+
+        let actual_events: Vec<Event> = buffer.split(|b| *b == b'\n')
+            .filter(|line| !line.is_empty())
+            .map(serde_json::from_slice)
+            .collect::<Result<Vec<Event>, serde_json::Error>>()?;
+
+        assert_eq!(actual_events, events);
+        Ok(())
+    }
+}
@@ -0,0 +1,111 @@
+use std::collections::{HashMap, VecDeque};
+use std::collections::vec_deque;
+
+use serde::{Serialize, Deserialize};
+
+use crate::order::*;
+use crate::quantity::Quantity;
+
+/* Iterators over a book's resting orders. Every ID queued at a level is a
+ * resting order, which is what lets these report their exact length. */
+
+/* A resting order as its place in a queue: see `Book::level_orders` */
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OrderRef {
+    pub id: OrderId,
+    pub quantity: Quantity,
+    /* the sequence number of the event that queued it; see
+     * `Order::get_priority` */
+    pub priority: u64,
+    pub hidden: bool
+}
+
+impl From<&Order> for OrderRef {
+    fn from(order: &Order) -> OrderRef {
+        OrderRef {
+            id: order.get_id(),
+            quantity: order.get_quantity(),
+            priority: order.get_priority(),
+            hidden: order.is_hidden()
+        }
+    }
+}
+
+/* the orders at a single price level, in time priority */
+#[derive(Debug, Clone)]
+pub struct LevelIter<'a> {
+    ids: vec_deque::Iter<'a, OrderId>,
+    orders: &'a HashMap<OrderId, Order>
+}
+
+impl<'a> LevelIter<'a> {
+    pub(crate) fn new(queue: &'a VecDeque<OrderId>,
+                      orders: &'a HashMap<OrderId, Order>) -> LevelIter<'a> {
+        LevelIter {
+            ids: queue.iter(),
+            orders
+        }
+    }
+}
+
+impl<'a> Iterator for LevelIter<'a> {
+    type Item = &'a Order;
+
+    fn next(&mut self) -> Option<&'a Order> {
+        let orders: &'a HashMap<OrderId, Order> = self.orders;
+
+        self.ids.by_ref().find_map(|id| orders.get(id))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.ids.len(), Some(self.ids.len()))
+    }
+}
+
+impl<'a> ExactSizeIterator for LevelIter<'a> {}
+
+/* every order on one side of the book, best price first and in time
+ * priority within each level */
+pub struct SideIter<'a> {
+    levels: Box<dyn Iterator<Item=&'a VecDeque<OrderId>> + 'a>,
+    level: Option<LevelIter<'a>>,
+    orders: &'a HashMap<OrderId, Order>,
+    remaining: usize
+}
+
+impl<'a> SideIter<'a> {
+    pub(crate) fn new<I>(levels: I, orders: &'a HashMap<OrderId, Order>) ->
+        SideIter<'a>
+        where I: Iterator<Item=&'a VecDeque<OrderId>> + Clone + 'a {
+        let remaining: usize = levels.clone().map(|queue| queue.len()).sum();
+
+        SideIter {
+            levels: Box::new(levels),
+            level: None,
+            orders,
+            remaining
+        }
+    }
+}
+
+impl<'a> Iterator for SideIter<'a> {
+    type Item = &'a Order;
+
+    fn next(&mut self) -> Option<&'a Order> {
+        loop {
+            if let Some(order) = self.level.as_mut().and_then(|l| l.next()) {
+                self.remaining = self.remaining.saturating_sub(1);
+                return Some(order);
+            }
+
+            let queue: &'a VecDeque<OrderId> = self.levels.next()?;
+            self.level = Some(LevelIter::new(queue, self.orders));
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<'a> ExactSizeIterator for SideIter<'a> {}
@@ -0,0 +1,247 @@
+use std::io::{BufRead, Write};
+
+use serde::{Serialize, Deserialize};
+use serde_json::Value;
+
+use crate::batch::{BookOp, BookOpOutcome};
+use crate::book::{Book, BookError};
+use crate::clock::Clock;
+use crate::event::Event;
+use crate::io::IoError;
+use crate::matching::MatchingPolicy;
+use crate::price::PriceType;
+use crate::replica::{BookReplica, ReplicaError};
+use crate::sink::MemorySink;
+use crate::snapshot::BookSnapshot;
+
+/* A recording of a session on a book, one JSON entry per line: a snapshot
+ * of the book to start from, then each operation applied to it followed by
+ * the events that came of it. Replaying one into a fresh book checks that
+ * it does exactly what the original did, timestamps aside. */
+
+#[derive(Debug, thiserror::Error)]
+pub enum JournalError {
+    #[error(transparent)]
+    Io(#[from] IoError),
+    #[error(transparent)]
+    Book(#[from] BookError),
+    #[error(transparent)]
+    Replica(#[from] ReplicaError),
+    #[error("line {0}: the recording does not start with a snapshot")]
+    MissingSnapshot(usize),
+    /* replaying did something other than what was recorded */
+    #[error("event {0} differs from the recording")]
+    Diverged(u64),
+    #[error("replaying left the book in a different state")]
+    StateMismatch,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Entry {
+    Snapshot(Box<BookSnapshot>),
+    Op(BookOp),
+    Event(Box<Event>)
+}
+
+/* Applies operations to a book, recording each and what came of it */
+#[derive(Debug)]
+pub struct JournalWriter<W: Write> {
+    writer: W
+}
+
+impl<W: Write> JournalWriter<W> {
+    /* starts a recording of `book` as it stands */
+    pub fn start<M, C, P>(book: &Book<M, MemorySink, C, P>, writer: W) ->
+        Result<JournalWriter<W>, JournalError>
+    where M: MatchingPolicy, C: Clock, P: PriceType {
+        let mut journal: JournalWriter<W> = JournalWriter {writer};
+
+        journal.write(&Entry::Snapshot(Box::new(book.snapshot())))?;
+        Ok(journal)
+    }
+
+    /* Applies `op` to `book` and records it, and its events, whether or
+     * not the book accepts it; the outcome is the book's. */
+    pub fn apply<M, C, P>(&mut self, book: &mut Book<M, MemorySink, C, P>,
+                          op: BookOp) ->
+        Result<Result<BookOpOutcome, BookError>, JournalError>
+    where M: MatchingPolicy, C: Clock, P: PriceType {
+        let last_seq: u64 = book.last_seq();
+
+        self.write(&Entry::Op(op.clone()))?;
+
+        let outcome: Result<BookOpOutcome, BookError> = book.apply(op);
+
+        for event in book.events_since(last_seq).unwrap_or_default() {
+            self.write(&Entry::Event(Box::new(event.clone())))?;
+        }
+
+        self.writer.flush().map_err(IoError::from)?;
+        Ok(outcome)
+    }
+
+    fn write(&mut self, entry: &Entry) -> Result<(), JournalError> {
+        serde_json::to_writer(&mut self.writer, entry)
+            .map_err(IoError::from)?;
+        writeln!(self.writer).map_err(IoError::from)?;
+        Ok(())
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+/* what replaying a recording came to */
+#[derive(Debug)]
+pub struct Replay {
+    pub book: Book,
+    pub ops: usize,
+    pub events: usize
+}
+
+/* events compare equal on replay if they differ only in when they were
+ * stamped */
+fn untimed(event: &Event) -> Result<Value, IoError> {
+    fn strip(value: &mut Value) {
+        match value {
+            Value::Object(fields) => {
+                fields.remove("timestamp");
+                fields.values_mut().for_each(strip);
+            },
+            Value::Array(values) => values.iter_mut().for_each(strip),
+            _ => {}
+        }
+    }
+
+    let mut value: Value = serde_json::to_value(event)?;
+
+    strip(&mut value);
+    Ok(value)
+}
+
+/* Replays a recording into a fresh book built from its snapshot, checking
+ * every event it publishes against the one recorded, and that the book
+ * ends up as the recorded events left it. */
+pub fn replay<R: BufRead>(reader: R) -> Result<Replay, JournalError> {
+    let mut lines = reader.lines().enumerate();
+    let (book, mut replica): (Book, BookReplica) = match lines.next() {
+        Some((_, line)) => match serde_json::from_str(
+            &line.map_err(IoError::from)?).map_err(IoError::from)? {
+            Entry::Snapshot(snapshot) => (
+                Book::from_snapshot(*snapshot.clone())?,
+                BookReplica::from_snapshot(&snapshot)),
+            _ => return Err(JournalError::MissingSnapshot(1))
+        },
+        None => return Err(JournalError::MissingSnapshot(1))
+    };
+    let mut replay: Replay = Replay {book, ops: 0, events: 0};
+
+    for (number, line) in lines {
+        let line: String = line.map_err(IoError::from)?;
+
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        match serde_json::from_str(&line).map_err(IoError::from)? {
+            Entry::Snapshot(_) =>
+                return Err(JournalError::MissingSnapshot(number + 1)),
+            Entry::Op(op) => {
+                /* refusals were recorded along with everything else */
+                let _ = replay.book.apply(op);
+                replay.ops += 1;
+            },
+            Entry::Event(recorded) => {
+                let seq: u64 = recorded.get_seq();
+                let replayed: Option<&Event> = replay.book
+                    .events_since(seq.saturating_sub(1))
+                    .ok()
+                    .and_then(<[Event]>::first)
+                    .filter(|event| event.get_seq() == seq);
+
+                let agrees: bool = match replayed {
+                    Some(event) => untimed(event)? == untimed(&recorded)?,
+                    None => false
+                };
+
+                if !agrees {
+                    return Err(JournalError::Diverged(seq));
+                }
+
+                replica.apply_delta(&[*recorded])?;
+                replay.events += 1;
+            }
+        }
+    }
+
+    if replay.book.last_seq() != replica.get_last_seq() {
+        return Err(JournalError::Diverged(replica.get_last_seq() + 1));
+    }
+
+    /* hidden orders included */
+    if BookReplica::from_snapshot(&replay.book.snapshot()).levels() !=
+        replica.levels() {
+        return Err(JournalError::StateMismatch);
+    }
+
+    Ok(replay)
+}
+
+#[cfg(all(test, not(feature = "decimal-quantity")))]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use crate::account::Account;
+    use crate::order::{Order, OrderId, OrderType};
+    use crate::quantity::Quantity;
+
+    fn submit(id: OrderId, order_type: OrderType, price: f64,
+              quantity: Quantity) -> BookOp {
+        let mut holdings: HashMap<String, Quantity> = HashMap::new();
+        holdings.insert("BOOK".to_string(), 1000);
+
+        let owner: Account = Account::new(id, "Account".to_string(),
+                                          12000.00, holdings);
+
+        BookOp::Submit(Box::new(Order::new(id, owner, "BOOK".to_string(),
+                                           order_type, price, quantity)))
+    }
+
+    #[test]
+    fn test_record_and_replay() -> crate::Result<()> {
+        let mut book: Book = Book::new(1, "Book".to_string(),
+                                       "BOOK".to_string());
+
+        book.apply(submit(1, OrderType::Ask, 12.50, 10))?;
+
+        let mut journal: JournalWriter<Vec<u8>> =
+            JournalWriter::start(&book, vec![])?;
+        let ops: Vec<BookOp> = vec![
+            submit(2, OrderType::Ask, 12.00, 10),
+            submit(3, OrderType::Bid, 12.25, 15),
+            BookOp::Modify { id: 1, price: 12.75, quantity: 5 },
+            BookOp::Cancel(42)
+        ];
+
+        for op in ops {
+            journal.apply(&mut book, op)?.ok();
+        }
+
+        let recording: Vec<u8> = journal.into_inner();
+        let replayed: Replay = replay(recording.as_slice())?;
+
+        assert_eq!(replayed.ops, 4);
+        assert_eq!(replayed.book.last_seq(), book.last_seq());
+        assert_eq!(replayed.book.levels(), book.levels());
+
+        /* a recording that the book no longer agrees with */
+        let tampered: String = String::from_utf8_lossy(&recording)
+            .replacen("\"quantity\":15", "\"quantity\":16", 1);
+
+        assert!(matches!(replay(tampered.as_bytes()),
+                         Err(JournalError::Diverged(_))));
+        Ok(())
+    }
+}
@@ -0,0 +1,330 @@
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, Utc};
+use ordered_float::OrderedFloat;
+use serde::{Serialize, Deserialize};
+#[cfg(feature = "decimal")]
+use rust_decimal::Decimal;
+
+use crate::book::BookId;
+use crate::checksum::{self, ChecksumFormat};
+#[cfg(feature = "decimal")]
+use crate::price;
+use crate::order::OrderType;
+use crate::quantity::{Quantity, ZERO};
+
+pub type Level = (f64, Quantity);
+
+/* aggregated depth: total resting quantity per price, best price first on
+ * each side */
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Levels {
+    bids: Vec<Level>,
+    asks: Vec<Level>
+}
+
+impl Levels {
+    pub fn new(bids: Vec<Level>, asks: Vec<Level>) -> Levels {
+        Levels {bids, asks}
+    }
+
+    pub fn get_bids(&self) -> &[Level] {
+        &self.bids
+    }
+
+    pub fn get_asks(&self) -> &[Level] {
+        &self.asks
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bids.is_empty() && self.asks.is_empty()
+    }
+
+    /* see `checksum` */
+    pub fn checksum(&self, format: &ChecksumFormat) -> u32 {
+        checksum::checksum(self, format)
+    }
+
+    /* what would have to change to turn these levels into `other` */
+    pub fn diff(&self, other: &Levels) -> LevelsDelta {
+        LevelsDelta {
+            bids: diff_side(&self.bids, &other.bids, true),
+            asks: diff_side(&self.asks, &other.asks, false)
+        }
+    }
+
+    pub fn apply_delta(&mut self, delta: &LevelsDelta) {
+        self.bids = apply_side(&self.bids, &delta.bids, true);
+        self.asks = apply_side(&self.asks, &delta.asks, false);
+    }
+
+    /* These levels summed into bins `bucket_width` wide, each priced at the
+     * edge nearest the other side: bids at the bottom of their bin, asks at
+     * the top, so bins never cross when the levels don't. Bins with nothing
+     * in them are left out. A width that isn't positive and finite leaves
+     * the levels as they are. */
+    pub fn histogram(&self, bucket_width: f64) -> Levels {
+        if !(bucket_width.is_finite() && bucket_width > 0.0) {
+            return self.clone();
+        }
+
+        Levels {
+            bids: bucket_side(&self.bids, bucket_width, f64::floor, true),
+            asks: bucket_side(&self.asks, bucket_width, f64::ceil, false)
+        }
+    }
+
+    /* these levels at decimal prices; `None` if any has no decimal form */
+    #[cfg(feature = "decimal")]
+    pub fn to_decimal(&self) -> Option<DecimalLevels> {
+        let side = |levels: &[Level]| levels.iter()
+            .map(|(price, quantity)| Some((price::to_decimal(*price)?,
+                                           *quantity)))
+            .collect::<Option<Vec<DecimalLevel>>>();
+
+        Some(DecimalLevels::new(side(&self.bids)?, side(&self.asks)?))
+    }
+}
+
+#[cfg(feature = "decimal")]
+pub type DecimalLevel = (Decimal, Quantity);
+
+/* `Levels` at exact decimal prices, as a `DecimalBook` keys them (see
+ * `Book::decimal_levels`). Prices serialize as strings, so nothing is lost
+ * on the wire however small they are. */
+#[cfg(feature = "decimal")]
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DecimalLevels {
+    bids: Vec<DecimalLevel>,
+    asks: Vec<DecimalLevel>
+}
+
+#[cfg(feature = "decimal")]
+impl DecimalLevels {
+    /* each side best price first, as for `Levels` */
+    pub fn new(bids: Vec<DecimalLevel>, asks: Vec<DecimalLevel>) ->
+        DecimalLevels {
+        DecimalLevels {bids, asks}
+    }
+
+    pub fn get_bids(&self) -> &[DecimalLevel] {
+        &self.bids
+    }
+
+    pub fn get_asks(&self) -> &[DecimalLevel] {
+        &self.asks
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bids.is_empty() && self.asks.is_empty()
+    }
+
+    /* at the nearest `f64` prices */
+    pub fn to_levels(&self) -> Levels {
+        let side = |levels: &[DecimalLevel]| levels.iter()
+            .map(|(price, quantity)| (price::from_decimal(*price), *quantity))
+            .collect::<Vec<Level>>();
+
+        Levels::new(side(&self.bids), side(&self.asks))
+    }
+}
+
+/* The levels that changed between two depth snapshots, each with its new
+ * quantity: zero where a level has gone. Best price first on each side. */
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LevelsDelta {
+    bids: Vec<Level>,
+    asks: Vec<Level>
+}
+
+impl LevelsDelta {
+    /* each side best price first, as for `Levels` */
+    pub fn new(bids: Vec<Level>, asks: Vec<Level>) -> LevelsDelta {
+        LevelsDelta {bids, asks}
+    }
+
+    pub fn get_bids(&self) -> &[Level] {
+        &self.bids
+    }
+
+    pub fn get_asks(&self) -> &[Level] {
+        &self.asks
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bids.is_empty() && self.asks.is_empty()
+    }
+}
+
+/* One level's aggregate quantity changing with event `seq`, from
+ * `old_quantity` to `new_quantity`: zero where the level was empty before
+ * or is now. See `BookReplica::level_deltas`. */
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct LevelDelta {
+    pub seq: u64,
+    pub timestamp: DateTime<Utc>,
+    pub side: OrderType,
+    pub price: f64,
+    pub old_quantity: Quantity,
+    pub new_quantity: Quantity
+}
+
+fn to_map(levels: &[Level]) -> BTreeMap<OrderedFloat<f64>, Quantity> {
+    levels.iter()
+        .map(|(price, quantity)| (OrderedFloat::from(*price), *quantity))
+        .collect()
+}
+
+/* best price first: highest for bids, lowest for asks */
+fn from_map<I>(levels: I, descending: bool) -> Vec<Level>
+    where I: DoubleEndedIterator<Item=(OrderedFloat<f64>, Quantity)> {
+    let levels = levels.map(|(price, quantity)| (price.into_inner(),
+                                                  quantity));
+
+    if descending {
+        levels.rev().collect()
+    } else {
+        levels.collect()
+    }
+}
+
+fn diff_side(from: &[Level], to: &[Level], descending: bool) -> Vec<Level> {
+    let from: BTreeMap<OrderedFloat<f64>, Quantity> = to_map(from);
+    let to: BTreeMap<OrderedFloat<f64>, Quantity> = to_map(to);
+    let mut changes: BTreeMap<OrderedFloat<f64>, Quantity> = from.keys()
+        .filter(|price| !to.contains_key(price))
+        .map(|price| (*price, ZERO))
+        .collect();
+
+    changes.extend(to.iter()
+        .filter(|(price, quantity)| from.get(price) != Some(quantity))
+        .map(|(price, quantity)| (*price, *quantity)));
+
+    from_map(changes.into_iter(), descending)
+}
+
+fn apply_side(levels: &[Level], changes: &[Level], descending: bool) ->
+    Vec<Level> {
+    let mut levels: BTreeMap<OrderedFloat<f64>, Quantity> = to_map(levels);
+
+    for (price, quantity) in changes {
+        if *quantity == ZERO {
+            levels.remove(&OrderedFloat::from(*price));
+        } else {
+            levels.insert(OrderedFloat::from(*price), *quantity);
+        }
+    }
+
+    from_map(levels.into_iter(), descending)
+}
+
+fn bucket_side(levels: &[Level], width: f64, edge: fn(f64) -> f64,
+               descending: bool) -> Vec<Level> {
+    let mut buckets: BTreeMap<OrderedFloat<f64>, Quantity> = BTreeMap::new();
+
+    for (price, quantity) in levels {
+        /* nudged so prices already on an edge stay there despite rounding,
+         * and the edge rounded for the same reason */
+        let nudge: f64 = if descending { 1e-9 } else { -1e-9 };
+        let bucket: f64 = edge(price / width + nudge) * width;
+        let bucket: f64 = (bucket * 1e9).round() / 1e9;
+
+        *buckets.entry(OrderedFloat::from(bucket)).or_insert(ZERO) +=
+            *quantity;
+    }
+
+    from_map(buckets.into_iter(), descending)
+}
+
+/* the best level on each side, if there is one */
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct TopOfBook {
+    bid: Option<Level>,
+    ask: Option<Level>
+}
+
+impl TopOfBook {
+    pub fn new(bid: Option<Level>, ask: Option<Level>) -> TopOfBook {
+        TopOfBook {bid, ask}
+    }
+
+    pub fn get_bid(&self) -> Option<Level> {
+        self.bid
+    }
+
+    pub fn get_ask(&self) -> Option<Level> {
+        self.ask
+    }
+
+    /* halfway between the best bid and ask, if there are both */
+    pub fn get_mid(&self) -> Option<f64> {
+        match (self.bid, self.ask) {
+            (Some((bid, _)), Some((ask, _))) => Some((bid + ask) / 2.0),
+            _ => None
+        }
+    }
+
+    /* best ask less best bid, if there are both */
+    pub fn get_spread(&self) -> Option<f64> {
+        match (self.bid, self.ask) {
+            (Some((bid, _)), Some((ask, _))) => Some(ask - bid),
+            _ => None
+        }
+    }
+}
+
+/* A book's depth along with which book it is and where in the book's event
+ * stream it was taken, so that a consumer receiving snapshots of several
+ * markets over the wire can tell them apart and put them in order against
+ * the events. See `Book::depth_snapshot`. */
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DepthSnapshot {
+    pub book: BookId,
+    pub ticker: String,
+    /* of the last event the depth reflects */
+    pub seq: u64,
+    /* by the book's clock, when the snapshot was taken */
+    pub timestamp: DateTime<Utc>,
+    pub levels: Levels
+}
+
+#[cfg(all(test, not(feature = "decimal-quantity")))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_histogram() {
+        let levels: Levels = Levels::new(
+            vec![(10.30, 5), (10.20, 1), (10.19, 2), (9.99, 4)],
+            vec![(10.40, 3), (10.41, 6), (10.60, 1)]);
+
+        assert_eq!(levels.histogram(0.10),
+                   Levels::new(vec![(10.30, 5), (10.20, 1), (10.10, 2),
+                                    (9.90, 4)],
+                               vec![(10.40, 3), (10.50, 6), (10.60, 1)]));
+        assert_eq!(levels.histogram(0.50),
+                   Levels::new(vec![(10.00, 8), (9.50, 4)],
+                               vec![(10.50, 9), (11.00, 1)]));
+        assert_eq!(levels.histogram(0.0), levels);
+    }
+
+    #[test]
+    fn test_diff_and_apply_delta() {
+        let mut actual_levels: Levels =
+            Levels::new(vec![(12.00, 10), (11.50, 5), (11.00, 20)],
+                        vec![(12.50, 10), (13.00, 5)]);
+        let expected_levels: Levels =
+            Levels::new(vec![(12.00, 10), (11.50, 8), (10.50, 1)],
+                        vec![(12.25, 4), (12.50, 10), (13.00, 5)]);
+
+        let delta: LevelsDelta = actual_levels.diff(&expected_levels);
+
+        assert_eq!(delta.get_bids(), &[(11.50, 8), (11.00, 0), (10.50, 1)]);
+        assert_eq!(delta.get_asks(), &[(12.25, 4)]);
+
+        actual_levels.apply_delta(&delta);
+
+        assert_eq!(actual_levels, expected_levels);
+        assert!(actual_levels.diff(&expected_levels).is_empty());
+    }
+}
@@ -0,0 +1,73 @@
+pub mod error;
+pub mod account;
+pub mod algo;
+pub mod analytics;
+pub mod fees;
+pub mod position;
+pub mod margin;
+pub mod order;
+pub mod book;
+pub mod half_book;
+pub mod bracket;
+pub mod metadata;
+pub mod batch;
+pub mod builder;
+pub mod command;
+pub mod matching;
+pub mod quantity;
+pub mod notional;
+pub mod levels;
+pub mod peg;
+pub mod pool;
+pub mod external;
+pub mod render;
+pub mod quote;
+pub mod quoter;
+pub mod event;
+pub mod io;
+pub mod journal;
+pub mod binary;
+pub mod versioned;
+pub mod snapshot;
+pub mod iter;
+pub mod id;
+pub mod sink;
+pub mod session;
+pub mod subscription;
+pub mod router;
+pub mod exchange;
+pub mod settlement;
+pub mod consolidated;
+pub mod engine;
+pub mod clock;
+pub mod replica;
+pub mod recorder;
+pub mod feed;
+pub mod heatmap;
+pub mod paper;
+pub mod reference;
+pub mod sim;
+pub mod checksum;
+pub mod compare;
+pub mod integrity;
+pub mod price;
+pub mod publish;
+pub mod prelude;
+#[cfg(feature = "grpc")]
+pub mod grpc;
+#[cfg(feature = "rest")]
+pub mod rest;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+#[cfg(feature = "viz")]
+pub mod viz;
+#[cfg(feature = "storage")]
+pub mod storage;
+#[cfg(feature = "metrics")]
+pub mod metrics;
+#[cfg(feature = "persistent")]
+pub mod persistent;
+#[cfg(feature = "async")]
+pub mod stream;
+
+pub use error::{Error, Result};
@@ -1,7 +0,0 @@
-mod account;
-mod order;
-mod book;
-
-fn main() {
-    println!("Hello, world!");
-}
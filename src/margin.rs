@@ -0,0 +1,42 @@
+use serde::{Serialize, Deserialize};
+
+/* How much an account may have bid for at once, across everything it has
+ * resting in a book and the order it is submitting. A resting bid holds on
+ * to its price times its remaining quantity until it fills, which spends
+ * it, or is cancelled, which frees it. */
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum MarginModel {
+    /* only the account's balance */
+    Cash,
+    /* the account's balance times this, which is at least one */
+    Leverage(f64)
+}
+
+impl MarginModel {
+    pub fn buying_power(&self, balance: f64) -> f64 {
+        match *self {
+            MarginModel::Cash => balance,
+            MarginModel::Leverage(multiplier) => balance * multiplier
+        }
+    }
+
+    pub(crate) fn is_valid(&self) -> bool {
+        match *self {
+            MarginModel::Cash => true,
+            MarginModel::Leverage(multiplier) =>
+                multiplier.is_finite() && multiplier >= 1.0
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_buying_power() {
+        assert_eq!(MarginModel::Cash.buying_power(1000.00), 1000.00);
+        assert_eq!(MarginModel::Leverage(4.0).buying_power(1000.00), 4000.00);
+        assert!(!MarginModel::Leverage(0.5).is_valid());
+    }
+}
@@ -0,0 +1,194 @@
+#![deny(clippy::unwrap_used, clippy::expect_used, clippy::panic,
+        clippy::indexing_slicing)]
+
+use std::collections::{HashMap, VecDeque};
+use std::fmt::Debug;
+
+use crate::order::*;
+use crate::quantity::{self, Quantity, ZERO};
+
+/* A matching policy decides how an incoming quantity is shared out amongst
+ * the resting orders at a single price level. Policies must not allocate a
+ * resting order less than it accepts (see `Order::accepts_fill`); such
+ * orders are passed over but keep their place in the queue. Books take their policy as a
+ * generic parameter so that the common case is statically dispatched, but
+ * `Box<dyn MatchingPolicy>` is itself a policy for when the choice needs to
+ * be made at runtime. */
+pub trait MatchingPolicy: Debug {
+    fn allocate(&self, queue: &VecDeque<OrderId>,
+                orders: &HashMap<OrderId, Order>,
+                quantity: Quantity) -> Vec<(OrderId, Quantity)>;
+}
+
+impl MatchingPolicy for Box<dyn MatchingPolicy> {
+    fn allocate(&self, queue: &VecDeque<OrderId>,
+                orders: &HashMap<OrderId, Order>,
+                quantity: Quantity) -> Vec<(OrderId, Quantity)> {
+        (**self).allocate(queue, orders, quantity)
+    }
+}
+
+/* strict first-in, first-out within a level */
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct PriceTime;
+
+impl MatchingPolicy for PriceTime {
+    fn allocate(&self, queue: &VecDeque<OrderId>,
+                orders: &HashMap<OrderId, Order>,
+                quantity: Quantity) -> Vec<(OrderId, Quantity)> {
+        let mut remaining: Quantity = quantity;
+        let mut fills: Vec<(OrderId, Quantity)> = vec![];
+
+        for id in queue.iter() {
+            if remaining == ZERO {
+                break;
+            }
+
+            if let Some(order) = orders.get(id) {
+                let fill: Quantity = order.get_quantity().min(remaining);
+
+                if fill > ZERO && order.accepts_fill(fill) {
+                    fills.push((*id, fill));
+                    remaining -= fill;
+                }
+            }
+        }
+
+        fills
+    }
+}
+
+/* shares the incoming quantity in proportion to resting size, with any
+ * rounding remainder handed out in time priority */
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct ProRata;
+
+impl MatchingPolicy for ProRata {
+    fn allocate(&self, queue: &VecDeque<OrderId>,
+                orders: &HashMap<OrderId, Order>,
+                quantity: Quantity) -> Vec<(OrderId, Quantity)> {
+        let resting: Vec<&Order> = queue.iter()
+            .filter_map(|id| orders.get(id))
+            .filter(|o| o.get_quantity() > ZERO)
+            .collect();
+        let total: Quantity = resting.iter().map(|o| o.get_quantity()).sum();
+
+        let mut fills: Vec<(OrderId, Quantity)> = resting.iter()
+            .map(|o| {
+                let share: Quantity = if total <= quantity {
+                    o.get_quantity()
+                } else {
+                    quantity::share(o.get_quantity(), total, quantity)
+                };
+
+                if o.accepts_fill(share) {
+                    (o.get_id(), share)
+                } else {
+                    (o.get_id(), ZERO)
+                }
+            })
+            .collect();
+
+        /* shares round down, so they never sum to more than `quantity` and
+         * never exceed what is available at each order */
+        let mut leftover: Quantity =
+            quantity - fills.iter().map(|(_, q)| *q).sum::<Quantity>();
+
+        for (fill, order) in fills.iter_mut().zip(resting.iter()) {
+            if leftover == ZERO {
+                break;
+            }
+
+            let extra: Quantity =
+                (order.get_quantity() - fill.1).min(leftover);
+
+            if extra > ZERO && order.accepts_fill(fill.1 + extra) {
+                fill.1 += extra;
+                leftover -= extra;
+            }
+        }
+
+        fills.into_iter().filter(|(_, q)| *q > ZERO).collect()
+    }
+}
+
+#[cfg(all(test, not(feature = "decimal-quantity")))]
+#[allow(clippy::unwrap_used, clippy::indexing_slicing)]
+mod tests {
+    use super::*;
+    use crate::account::*;
+
+    fn build_orders(quantities: &[Quantity]) ->
+        (VecDeque<OrderId>, HashMap<OrderId, Order>) {
+        let mut queue: VecDeque<OrderId> = VecDeque::new();
+        let mut orders: HashMap<OrderId, Order> = HashMap::new();
+
+        for (i, quantity) in quantities.iter().enumerate() {
+            let id: OrderId = i as OrderId + 1;
+            queue.push_back(id);
+            orders.insert(id, Order::new(id, Account::default(),
+                                         "BOOK".to_string(), OrderType::Ask,
+                                         12.00, *quantity));
+        }
+
+        (queue, orders)
+    }
+
+    #[test]
+    fn test_price_time_fills_in_queue_order() {
+        let (queue, orders) = build_orders(&[10, 20, 30]);
+
+        let actual_fills: Vec<(OrderId, Quantity)> =
+            PriceTime.allocate(&queue, &orders, 25);
+        let expected_fills: Vec<(OrderId, Quantity)> = vec![(1, 10), (2, 15)];
+
+        assert_eq!(actual_fills, expected_fills);
+    }
+
+    #[test]
+    fn test_pro_rata_shares_proportionally() {
+        let (queue, orders) = build_orders(&[10, 30, 60]);
+
+        let actual_fills: Vec<(OrderId, Quantity)> =
+            ProRata.allocate(&queue, &orders, 51);
+        let expected_fills: Vec<(OrderId, Quantity)> =
+            vec![(1, 6), (2, 15), (3, 30)];
+
+        assert_eq!(actual_fills, expected_fills);
+    }
+
+    #[test]
+    fn test_boxed_policy_matches_static_policy() {
+        let (queue, orders) = build_orders(&[10, 20, 30]);
+        let boxed: Box<dyn MatchingPolicy> = Box::new(PriceTime);
+
+        assert_eq!(boxed.allocate(&queue, &orders, 25),
+                   PriceTime.allocate(&queue, &orders, 25));
+    }
+
+    #[test]
+    fn test_price_time_skips_unacceptable_fills() {
+        let (queue, mut orders) = build_orders(&[10, 20, 30]);
+        orders.get_mut(&1).unwrap().set_all_or_none();
+        orders.get_mut(&2).unwrap().set_min_quantity(Some(15));
+
+        let actual_fills: Vec<(OrderId, Quantity)> =
+            PriceTime.allocate(&queue, &orders, 8);
+        let expected_fills: Vec<(OrderId, Quantity)> = vec![(3, 8)];
+
+        assert_eq!(actual_fills, expected_fills);
+    }
+
+    #[test]
+    fn test_pro_rata_skips_unacceptable_fills() {
+        let (queue, mut orders) = build_orders(&[10, 30, 60]);
+        orders.get_mut(&1).unwrap().set_all_or_none();
+
+        let actual_fills: Vec<(OrderId, Quantity)> =
+            ProRata.allocate(&queue, &orders, 51);
+        let expected_fills: Vec<(OrderId, Quantity)> =
+            vec![(2, 21), (3, 30)];
+
+        assert_eq!(actual_fills, expected_fills);
+    }
+}
@@ -0,0 +1,241 @@
+use std::fmt;
+
+use chrono::{DateTime, Utc};
+use serde::{Serialize, Deserialize};
+
+use crate::book::BookId;
+use crate::builder::BookBuilder;
+use crate::notional::Notional;
+use crate::quantity::Quantity;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AssetClass {
+    Equity,
+    Future,
+    Option,
+    Fx,
+    Crypto,
+    FixedIncome,
+    Commodity,
+    Other(String)
+}
+
+impl fmt::Display for AssetClass {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AssetClass::Equity => write!(f, "equity"),
+            AssetClass::Future => write!(f, "future"),
+            AssetClass::Option => write!(f, "option"),
+            AssetClass::Fx => write!(f, "fx"),
+            AssetClass::Crypto => write!(f, "crypto"),
+            AssetClass::FixedIncome => write!(f, "fixed income"),
+            AssetClass::Commodity => write!(f, "commodity"),
+            AssetClass::Other(class) => write!(f, "{}", class)
+        }
+    }
+}
+
+/* Describes the instrument a book trades. Fields are serialized under
+ * these names, with optional ones left out when unset, and new fields will
+ * only ever be added as optional, so stored descriptors keep loading. */
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Metadata {
+    ticker: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    name: Option<String>,
+    asset_class: AssetClass,
+    /* what prices are quoted in, e.g. "USD" */
+    currency: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    tick_size: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    lot_size: Option<Quantity>,
+    /* for derivatives */
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    expiry: Option<DateTime<Utc>>
+}
+
+impl Metadata {
+    pub fn new(ticker: String, asset_class: AssetClass, currency: String) ->
+        Metadata {
+        Metadata {
+            ticker,
+            name: None,
+            asset_class,
+            currency,
+            tick_size: None,
+            lot_size: None,
+            expiry: None
+        }
+    }
+
+    pub fn name(mut self, name: String) -> Metadata {
+        self.name = Some(name);
+        self
+    }
+
+    pub fn tick_size(mut self, tick_size: f64) -> Metadata {
+        self.tick_size = Some(tick_size);
+        self
+    }
+
+    pub fn lot_size(mut self, lot_size: Quantity) -> Metadata {
+        self.lot_size = Some(lot_size);
+        self
+    }
+
+    pub fn expiry(mut self, expiry: DateTime<Utc>) -> Metadata {
+        self.expiry = Some(expiry);
+        self
+    }
+
+    pub fn get_ticker(&self) -> String {
+        self.ticker.clone()
+    }
+
+    pub fn get_name(&self) -> Option<String> {
+        self.name.clone()
+    }
+
+    pub fn get_asset_class(&self) -> &AssetClass {
+        &self.asset_class
+    }
+
+    pub fn get_currency(&self) -> String {
+        self.currency.clone()
+    }
+
+    /* `amount` of this instrument's currency */
+    pub fn notional(&self, amount: f64) -> Notional {
+        Notional::new(amount, self.currency.clone())
+    }
+
+    pub fn get_tick_size(&self) -> Option<f64> {
+        self.tick_size
+    }
+
+    pub fn get_lot_size(&self) -> Option<Quantity> {
+        self.lot_size
+    }
+
+    pub fn get_expiry(&self) -> Option<DateTime<Utc>> {
+        self.expiry
+    }
+
+    pub fn is_expired(&self, now: DateTime<Utc>) -> bool {
+        self.expiry.is_some_and(|expiry| expiry <= now)
+    }
+
+    /* a builder for a book trading this instrument, with its name, tick
+     * size and lot size */
+    pub fn builder(&self, id: BookId) -> BookBuilder {
+        let mut builder: BookBuilder = BookBuilder::new(id,
+                                                        self.ticker.clone());
+
+        if let Some(name) = &self.name {
+            builder = builder.name(name.clone());
+        }
+
+        if let Some(tick_size) = self.tick_size {
+            builder = builder.tick_size(tick_size);
+        }
+
+        if let Some(lot_size) = self.lot_size {
+            builder = builder.lot_size(lot_size);
+        }
+
+        if let Some(expiry) = self.expiry {
+            builder = builder.expiry(expiry);
+        }
+
+        builder
+    }
+}
+
+/* e.g. "ESZ6 (E-mini S&P, future, USD, tick 0.25, expires 2026-12-18)" */
+impl fmt::Display for Metadata {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} (", self.ticker)?;
+
+        if let Some(name) = &self.name {
+            write!(f, "{}, ", name)?;
+        }
+
+        write!(f, "{}, {}", self.asset_class, self.currency)?;
+
+        if let Some(tick_size) = self.tick_size {
+            write!(f, ", tick {}", tick_size)?;
+        }
+
+        if let Some(lot_size) = self.lot_size {
+            write!(f, ", lot {}", lot_size)?;
+        }
+
+        if let Some(expiry) = self.expiry {
+            write!(f, ", expires {}", expiry.format("%Y-%m-%d"))?;
+        }
+
+        write!(f, ")")
+    }
+}
+
+#[cfg(all(test, not(feature = "decimal-quantity")))]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use crate::book::Book;
+    use crate::builder::BuildError;
+
+    fn future() -> Metadata {
+        Metadata::new("ESZ6".to_string(), AssetClass::Future,
+                      "USD".to_string())
+            .name("E-mini S&P".to_string())
+            .tick_size(0.25)
+            .lot_size(1)
+            .expiry(Utc.with_ymd_and_hms(2026, 12, 18, 14, 30, 0).unwrap())
+    }
+
+    #[test]
+    fn test_serde_schema() {
+        let actual_json: String = serde_json::to_string(&future()).unwrap();
+        let expected_json: &str = "{\"ticker\":\"ESZ6\",\
+                                   \"name\":\"E-mini S&P\",\
+                                   \"asset_class\":\"future\",\
+                                   \"currency\":\"USD\",\
+                                   \"tick_size\":0.25,\
+                                   \"lot_size\":1,\
+                                   \"expiry\":\"2026-12-18T14:30:00Z\"}";
+
+        assert_eq!(actual_json, expected_json);
+        assert_eq!(serde_json::from_str::<Metadata>(expected_json).unwrap(),
+                   future());
+
+        let bare: Metadata = serde_json::from_str(
+            "{\"ticker\":\"BOOK\",\"asset_class\":{\"other\":\"index\"},\
+             \"currency\":\"AUD\"}").unwrap();
+
+        assert_eq!(bare.get_asset_class(),
+                   &AssetClass::Other("index".to_string()));
+        assert_eq!(bare.get_tick_size(), None);
+    }
+
+    #[test]
+    fn test_display() {
+        assert_eq!(future().to_string(),
+                   "ESZ6 (E-mini S&P, future, USD, tick 0.25, lot 1, \
+                    expires 2026-12-18)");
+    }
+
+    #[test]
+    fn test_builder() -> Result<(), BuildError> {
+        let book: Book = future().builder(1).build()?;
+
+        assert_eq!(book.get_ticker(), "ESZ6".to_string());
+        assert_eq!(book.get_name(), "E-mini S&P".to_string());
+        assert_eq!(book.get_config().tick_size, Some(0.25));
+        assert!(future().is_expired(
+            Utc.with_ymd_and_hms(2027, 1, 1, 0, 0, 0).unwrap()));
+        Ok(())
+    }
+}
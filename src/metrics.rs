@@ -0,0 +1,105 @@
+use std::convert::TryFrom;
+use std::time::Duration;
+
+use hdrhistogram::Histogram;
+
+/* how many significant figures latencies are recorded to */
+const PRECISION: u8 = 3;
+
+/* Latencies, in nanoseconds, and counts of what a book has done since it
+ * was created or its metrics last reset. Submission latencies cover the
+ * whole of `Book::submit`, including matching and writing out events;
+ * rejected orders are counted but not timed. */
+#[derive(Debug, Clone)]
+pub struct BookMetrics {
+    submit: Histogram<u64>,
+    matching: Histogram<u64>,
+    cancel: Histogram<u64>,
+    /* incoming orders that traded at least once on arrival */
+    orders_matched: u64,
+    trades: u64,
+    rejects: u64
+}
+
+fn histogram() -> Histogram<u64> {
+    /* only fails for more than five significant figures */
+    #[allow(clippy::expect_used)]
+    Histogram::new(PRECISION).expect("precision is supported")
+}
+
+fn nanos(elapsed: Duration) -> u64 {
+    u64::try_from(elapsed.as_nanos()).unwrap_or(u64::MAX)
+}
+
+impl BookMetrics {
+    pub fn new() -> BookMetrics {
+        BookMetrics {
+            submit: histogram(),
+            matching: histogram(),
+            cancel: histogram(),
+            orders_matched: 0,
+            trades: 0,
+            rejects: 0
+        }
+    }
+
+    pub fn get_submit_latencies(&self) -> &Histogram<u64> {
+        &self.submit
+    }
+
+    pub fn get_match_latencies(&self) -> &Histogram<u64> {
+        &self.matching
+    }
+
+    pub fn get_cancel_latencies(&self) -> &Histogram<u64> {
+        &self.cancel
+    }
+
+    pub fn get_orders_matched(&self) -> u64 {
+        self.orders_matched
+    }
+
+    pub fn get_trades(&self) -> u64 {
+        self.trades
+    }
+
+    pub fn get_rejects(&self) -> u64 {
+        self.rejects
+    }
+
+    pub fn reset(&mut self) {
+        *self = BookMetrics::new();
+    }
+
+    pub(crate) fn record_submit(&mut self, elapsed: Duration) {
+        self.submit.saturating_record(nanos(elapsed));
+    }
+
+    pub(crate) fn record_match(&mut self, elapsed: Duration, trades: usize) {
+        self.matching.saturating_record(nanos(elapsed));
+
+        if trades > 0 {
+            self.orders_matched += 1;
+        }
+
+        self.record_trades(trades);
+    }
+
+    pub(crate) fn record_trades(&mut self, trades: usize) {
+        self.trades += trades as u64;
+    }
+
+    pub(crate) fn record_cancel(&mut self, elapsed: Duration) {
+        self.cancel.saturating_record(nanos(elapsed));
+    }
+
+    pub(crate) fn record_reject(&mut self) {
+        self.rejects += 1;
+    }
+}
+
+impl Default for BookMetrics {
+    fn default() -> BookMetrics {
+        BookMetrics::new()
+    }
+}
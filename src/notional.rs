@@ -0,0 +1,84 @@
+use std::fmt;
+
+use serde::{Serialize, Deserialize};
+
+use crate::quantity::{self, Quantity};
+
+/* An amount of money in some currency, e.g. what an order is worth: its
+ * price times its quantity, in whatever its book's prices are quoted in
+ * (see `Metadata::get_currency`). For strategies and risk checks that
+ * think in dollars rather than shares. */
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Notional {
+    amount: f64,
+    currency: String
+}
+
+impl Notional {
+    pub fn new(amount: f64, currency: String) -> Notional {
+        Notional {amount, currency}
+    }
+
+    /* what `quantity` is worth at `price` */
+    pub fn of(price: f64, quantity: Quantity, currency: String) -> Notional {
+        Notional::new(price * quantity::to_f64(quantity), currency)
+    }
+
+    pub fn get_amount(&self) -> f64 {
+        self.amount
+    }
+
+    pub fn get_currency(&self) -> &str {
+        &self.currency
+    }
+
+    /* The most this buys at `price`, rounded down so as never to spend
+     * more than the amount; nothing for a price that isn't positive. Books
+     * with lots round this down further; see `Book::quantity_for`. */
+    pub fn quantity_at(&self, price: f64) -> Quantity {
+        if price > 0.0 {
+            quantity::from_f64(self.amount / price)
+        } else {
+            quantity::ZERO
+        }
+    }
+
+    /* the sum of the two, if they are in the same currency */
+    pub fn checked_add(&self, other: &Notional) -> Option<Notional> {
+        if self.currency != other.currency {
+            return None;
+        }
+
+        Some(Notional::new(self.amount + other.amount,
+                           self.currency.clone()))
+    }
+}
+
+/* e.g. "1234.50 USD" */
+impl fmt::Display for Notional {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:.2} {}", self.amount, self.currency)
+    }
+}
+
+#[cfg(all(test, not(feature = "decimal-quantity")))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_notional() {
+        let notional: Notional = Notional::of(12.50, 8, "USD".to_string());
+
+        assert_eq!(notional.get_amount(), 100.0);
+        assert_eq!(notional.to_string(), "100.00 USD");
+        assert_eq!(notional.quantity_at(12.50), 8);
+        assert_eq!(notional.quantity_at(12.51), 7);
+        assert_eq!(notional.quantity_at(0.0), 0);
+
+        assert_eq!(notional.checked_add(&notional),
+                   Some(Notional::new(200.0, "USD".to_string())));
+        assert_eq!(notional.checked_add(&Notional::new(1.0,
+                                                       "AUD".to_string())),
+                   None);
+    }
+}
@@ -1,30 +1,73 @@
 extern crate chrono;
 
+use std::collections::BTreeMap;
+
 use chrono::{DateTime, Utc};
+use serde::{Serialize, Deserialize};
 
 use crate::account;
+use crate::notional::Notional;
+use crate::quantity::{self, Quantity};
 
+#[derive(Debug, Clone, PartialEq, thiserror::Error)]
 pub enum OrderError {
+    #[error("order is still active")]
     OrderStillActive
 }
 
 pub type OrderId = u128;
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
 #[allow(dead_code)]
 pub enum OrderType {
     Bid,
     Ask
 }
 
-#[derive(Debug, Clone, PartialEq)]
+/* where an order is in its life, as of the last event about it; see
+ * `Book::status` */
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum OrderStatus {
+    /* resting, with nothing filled yet */
+    New,
+    /* resting, or being matched, with some filled */
+    PartiallyFilled,
+    Filled,
+    Cancelled,
+    /* cancelled for having outlived its time in force */
+    Expired,
+    /* refused by the book */
+    Rejected
+}
+
+impl OrderStatus {
+    /* whether any of the order can still trade */
+    pub fn is_open(&self) -> bool {
+        matches!(self, OrderStatus::New | OrderStatus::PartiallyFilled)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Order {
     id: u128,
     owner: account::Account,
     ticker: String,
     order_type: OrderType,
     price: f64,
-    quantity: u128,
+    quantity: Quantity,
+    original_quantity: Quantity,
+    min_quantity: Option<Quantity>,
+    /* never displayed; see `set_hidden` */
+    #[serde(default)]
+    hidden: bool,
+    /* where the order stands in its level's queue; see `get_priority` */
+    #[serde(default)]
+    priority: u64,
+    /* the ID its owner knows it by; see `set_client_id` */
+    #[serde(default)]
+    client_id: Option<String>,
+    #[serde(default)]
+    tags: BTreeMap<String, String>,
     created: DateTime<Utc>,
     modified: DateTime<Utc>,
     cancelled: DateTime<Utc>,
@@ -34,21 +77,44 @@ pub struct Order {
 #[allow(dead_code)]
 impl Order {
     pub fn new(id: u128, owner: account::Account, ticker: String,
-               order_type: OrderType, price: f64, quantity: u128) -> Order {
+               order_type: OrderType, price: f64, quantity: Quantity) -> Order {
+        Order::new_at(id, owner, ticker, order_type, price, quantity,
+                      Utc::now())
+    }
+
+    /* as `new`, but created at `created` rather than now */
+    pub fn new_at(id: u128, owner: account::Account, ticker: String,
+                  order_type: OrderType, price: f64, quantity: Quantity,
+                  created: DateTime<Utc>) -> Order {
         Order {
-            id: id,
-            owner: owner,
-            ticker: ticker.clone(),
-            order_type: order_type,
-            price: price,
-            quantity: quantity,
-            created: Utc::now(),
-            modified: Utc::now(),
-            cancelled: Utc::now(),
+            id,
+            owner,
+            ticker,
+            order_type,
+            price,
+            quantity,
+            original_quantity: quantity,
+            min_quantity: None,
+            hidden: false,
+            priority: 0,
+            client_id: None,
+            tags: BTreeMap::new(),
+            created,
+            modified: created,
+            cancelled: created,
             active: true
         }
     }
 
+    /* as `new`, for as much as `notional` buys at `price`; see
+     * `Notional::quantity_at` */
+    pub fn from_notional(id: u128, owner: account::Account, ticker: String,
+                         order_type: OrderType, price: f64,
+                         notional: &Notional) -> Order {
+        Order::new(id, owner, ticker, order_type, price,
+                   notional.quantity_at(price))
+    }
+
     pub fn get_id(&self) -> u128 {
         self.id
     }
@@ -57,6 +123,10 @@ impl Order {
         self.owner.clone()
     }
 
+    pub fn get_owner_ref(&self) -> &account::Account {
+        &self.owner
+    }
+
     pub fn get_owner_mut(&mut self) -> &mut account::Account {
         &mut self.owner
     }
@@ -65,37 +135,177 @@ impl Order {
         self.ticker.clone()
     }
 
+    pub fn get_ticker_ref(&self) -> &str {
+        &self.ticker
+    }
+
     pub fn get_order_type(&self) -> OrderType {
-        self.order_type.clone()
+        self.order_type
     }
 
     pub fn get_price(&self) -> f64 {
         self.price
     }
 
-    pub fn get_quantity(&self) -> u128 {
+    pub fn get_quantity(&self) -> Quantity {
         self.quantity
     }
 
+    pub fn set_quantity(&mut self, quantity: Quantity) {
+        self.quantity = quantity;
+        self.modified = Utc::now();
+    }
+
+    /* a copy of this order for `quantity` alone, e.g. one of the pieces a
+     * router splits it into */
+    pub fn with_quantity(&self, quantity: Quantity) -> Order {
+        Order {
+            quantity,
+            original_quantity: quantity,
+            ..self.clone()
+        }
+    }
+
+    /* new terms for the order, keeping what has already been filled */
+    pub(crate) fn amend(&mut self, price: f64, quantity: Quantity,
+                        at: DateTime<Utc>) {
+        self.original_quantity = self.get_filled_quantity() + quantity;
+        self.price = price;
+        self.quantity = quantity;
+        self.modified = at;
+    }
+
+    /* Takes `quantity` off what is left of the order. The money and
+     * holdings that change hands are settled against the owner's account
+     * in the book's ledger (see `Book::get_account`), not against the copy
+     * the order carries. */
+    pub(crate) fn fill(&mut self, quantity: Quantity, at: DateTime<Utc>) {
+        self.quantity = quantity::saturating_sub(self.quantity, quantity);
+        self.modified = at;
+    }
+
+    /* gives back `quantity` of the order, as when one of its trades is
+     * busted */
+    pub(crate) fn unfill(&mut self, quantity: Quantity, at: DateTime<Utc>) {
+        self.quantity += quantity;
+        self.modified = at;
+    }
+
+    pub fn get_original_quantity(&self) -> Quantity {
+        self.original_quantity
+    }
+
+    /* how much of the order has executed so far */
+    pub fn get_filled_quantity(&self) -> Quantity {
+        quantity::saturating_sub(self.original_quantity, self.quantity)
+    }
+
+    pub fn get_min_quantity(&self) -> Option<Quantity> {
+        self.min_quantity
+    }
+
+    /* the smallest single execution this order will accept while resting;
+     * once less than the minimum remains, only the whole remainder will do */
+    pub fn set_min_quantity(&mut self, min_quantity: Option<Quantity>) {
+        self.min_quantity = min_quantity;
+    }
+
+    pub fn set_all_or_none(&mut self) {
+        self.min_quantity = Some(self.quantity);
+    }
+
+    pub fn is_hidden(&self) -> bool {
+        self.hidden
+    }
+
+    /* Makes the order fully hidden: it rests and matches as any other, but
+     * never shows in the book's depth, top of book or rendering. At each
+     * price, hidden orders queue behind every displayed order, even those
+     * that arrive after them. */
+    pub fn set_hidden(&mut self) {
+        self.hidden = true;
+    }
+
+    pub fn get_client_id(&self) -> Option<&str> {
+        self.client_id.as_deref()
+    }
+
+    /* The ID the order's owner gave it, which the book and router will
+     * find it by (see `Book::find_by_client_id`) and which events about it
+     * carry. The book's own ID is still what identifies it to the book. */
+    pub fn set_client_id(&mut self, client_id: String) {
+        self.client_id = Some(client_id);
+    }
+
+    pub fn get_tags(&self) -> &BTreeMap<String, String> {
+        &self.tags
+    }
+
+    pub fn get_tag(&self, key: &str) -> Option<&str> {
+        self.tags.get(key).map(String::as_str)
+    }
+
+    /* Labels the order, e.g. with the strategy, desk or batch it came
+     * from; replaces any tag with the same key. Carried on the events
+     * about it, as for the client ID. */
+    pub fn set_tag(&mut self, key: String, value: String) {
+        self.tags.insert(key, value);
+    }
+
+    pub fn accepts_fill(&self, quantity: Quantity) -> bool {
+        match self.min_quantity {
+            Some(min_quantity) => quantity >= min_quantity.min(self.quantity),
+            None => true
+        }
+    }
+
+    /* Orders at the same price are queued in increasing priority, which a
+     * book assigns when the order comes to rest: the sequence number of the
+     * event recording it. Zero until then. */
+    pub fn get_priority(&self) -> u64 {
+        self.priority
+    }
+
+    pub(crate) fn set_priority(&mut self, priority: u64) {
+        self.priority = priority;
+    }
+
     pub fn get_created(&self) -> DateTime<Utc> {
         self.created
     }
 
+    pub fn set_created(&mut self, created: DateTime<Utc>) {
+        self.created = created;
+    }
+
     pub fn get_modified(&self) -> DateTime<Utc> {
         self.modified
     }
 
+    pub fn set_modified(&mut self, modified: DateTime<Utc>) {
+        self.modified = modified;
+    }
+
     pub fn get_cancelled(&self) -> Result<DateTime<Utc>, OrderError> {
         if self.active {
-            Ok(self.cancelled)
-        } else {
             Err(OrderError::OrderStillActive)
+        } else {
+            Ok(self.cancelled)
         }
     }
 
     pub fn active(&self) -> bool {
         self.active
     }
+
+    pub fn cancel(&mut self) {
+        self.cancel_at(Utc::now());
+    }
+
+    pub fn cancel_at(&mut self, cancelled: DateTime<Utc>) {
+        self.active = false;
+        self.cancelled = cancelled;
+    }
 }
 
 
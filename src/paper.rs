@@ -0,0 +1,399 @@
+use std::collections::HashMap;
+
+use chrono::{DateTime, Utc};
+
+use crate::event::{Event, EventKind, Trade};
+use crate::levels::{Level, Levels};
+use crate::order::{OrderId, OrderType};
+use crate::quantity::{self, Quantity, ZERO};
+use crate::replica::{BookReplica, ReplicaError};
+use crate::sink::{EventSink, SinkError};
+use crate::snapshot::BookSnapshot;
+
+/* Paper trading: simulated orders kept alongside a copy of a real book,
+ * filled as the real book's events suggest they would have been, without
+ * ever touching the real book.
+ *
+ * A simulated order joins the back of the queue at its price, behind every
+ * real order resting there. Those orders leave the queue ahead of it as they
+ * trade, are cancelled or lose their priority, and only trades beyond them
+ * fill it: trades against real orders that joined the level after it, or at
+ * prices worse than its own, i.e. trades the real book would not have made
+ * had the simulated order really been there. Simulated orders never take
+ * liquidity away from the real book, so a trade may fill several of them,
+ * up to its quantity between them. */
+
+/* a simulated order; IDs are the trader's own, separate from the book's */
+#[derive(Debug, Clone, PartialEq)]
+pub struct PaperOrder {
+    id: OrderId,
+    order_type: OrderType,
+    price: f64,
+    quantity: Quantity,
+    filled: Quantity,
+    /* the real orders queued in front of this one, and how much of each */
+    ahead: HashMap<OrderId, Quantity>
+}
+
+impl PaperOrder {
+    pub fn get_id(&self) -> OrderId {
+        self.id
+    }
+
+    pub fn get_order_type(&self) -> OrderType {
+        self.order_type
+    }
+
+    pub fn get_price(&self) -> f64 {
+        self.price
+    }
+
+    /* what is left to fill */
+    pub fn get_quantity(&self) -> Quantity {
+        self.quantity
+    }
+
+    pub fn get_filled_quantity(&self) -> Quantity {
+        self.filled
+    }
+
+    /* how much real quantity is queued in front of this order */
+    pub fn get_queue_ahead(&self) -> Quantity {
+        quantity::total(self.ahead.values().copied())
+    }
+
+    /* whether a trade with a resting order at `price` went through this
+     * order's price */
+    fn traded_through(&self, price: f64) -> bool {
+        match self.order_type {
+            OrderType::Bid => price < self.price,
+            OrderType::Ask => price > self.price
+        }
+    }
+
+    fn fill(&mut self, quantity: Quantity) {
+        self.quantity = quantity::saturating_sub(self.quantity, quantity);
+        self.filled += quantity;
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PaperFill {
+    order: OrderId,
+    price: f64,
+    quantity: Quantity,
+    timestamp: DateTime<Utc>
+}
+
+impl PaperFill {
+    pub fn get_order(&self) -> OrderId {
+        self.order
+    }
+
+    pub fn get_price(&self) -> f64 {
+        self.price
+    }
+
+    pub fn get_quantity(&self) -> Quantity {
+        self.quantity
+    }
+
+    pub fn get_timestamp(&self) -> DateTime<Utc> {
+        self.timestamp
+    }
+}
+
+/* Simulated orders against a real book, fed with that book's events, e.g.
+ * as its sink or from `Book::delta_since`. Orders that are filled in full
+ * are dropped; their fills are kept until taken. */
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PaperTrader {
+    book: BookReplica,
+    /* in the order they were submitted */
+    orders: Vec<PaperOrder>,
+    fills: Vec<PaperFill>,
+    next_id: OrderId
+}
+
+impl PaperTrader {
+    pub fn new(book: BookReplica) -> PaperTrader {
+        PaperTrader {
+            book,
+            orders: vec![],
+            fills: vec![],
+            next_id: 1
+        }
+    }
+
+    pub fn from_snapshot(snapshot: &BookSnapshot) -> PaperTrader {
+        PaperTrader::new(BookReplica::from_snapshot(snapshot))
+    }
+
+    /* the trader's copy of the real book */
+    pub fn get_book(&self) -> &BookReplica {
+        &self.book
+    }
+
+    pub fn get_orders(&self) -> &[PaperOrder] {
+        &self.orders
+    }
+
+    pub fn get_order(&self, id: OrderId) -> Option<&PaperOrder> {
+        self.orders.iter().find(|order| order.id == id)
+    }
+
+    pub fn get_fills(&self) -> &[PaperFill] {
+        &self.fills
+    }
+
+    pub fn take_fills(&mut self) -> Vec<PaperFill> {
+        std::mem::take(&mut self.fills)
+    }
+
+    /* Places a simulated order, returning its ID. Whatever of it crosses the
+     * real book fills straight away against the depth there, as of
+     * `timestamp`, and the rest joins the queue at its price. */
+    pub fn submit(&mut self, order_type: OrderType, price: f64,
+                  quantity: Quantity, timestamp: DateTime<Utc>) -> OrderId {
+        let id: OrderId = self.next_id;
+        let mut order: PaperOrder = PaperOrder {
+            id,
+            order_type,
+            price,
+            quantity,
+            filled: ZERO,
+            ahead: HashMap::new()
+        };
+        let levels: Levels = self.book.levels();
+        let opposite: &[Level] = match order_type {
+            OrderType::Bid => levels.get_asks(),
+            OrderType::Ask => levels.get_bids()
+        };
+
+        for (level_price, level_quantity) in opposite {
+            if order.quantity == ZERO ||
+                (!order.traded_through(*level_price) &&
+                 *level_price != price) {
+                break;
+            }
+
+            let filled: Quantity = order.quantity.min(*level_quantity);
+
+            order.fill(filled);
+            self.fills.push(PaperFill {
+                order: id,
+                price: *level_price,
+                quantity: filled,
+                timestamp
+            });
+        }
+
+        self.next_id += 1;
+
+        if order.quantity > ZERO {
+            order.ahead = self.book.orders_at(&order_type, price)
+                .into_iter()
+                .collect();
+            self.orders.push(order);
+        }
+
+        id
+    }
+
+    pub fn cancel(&mut self, id: OrderId) -> Option<PaperOrder> {
+        let index: usize = self.orders.iter()
+            .position(|order| order.id == id)?;
+
+        Some(self.orders.remove(index))
+    }
+
+    /* Brings the copy of the real book up to date with `event`, filling
+     * simulated orders as it implies. Events already seen are skipped. */
+    pub fn observe(&mut self, event: &Event) -> Result<(), ReplicaError> {
+        if event.get_seq() <= self.book.get_last_seq() {
+            return Ok(());
+        }
+
+        /* the real order the event is about, as it was before the event;
+         * the replica checks the sequence before anything changes */
+        let subject: Option<OrderId> = match event.get_kind() {
+            EventKind::Match(trade) => Some(trade.get_resting()),
+            EventKind::Amend { order, .. } => Some(*order),
+            _ => None
+        };
+        let before: Option<(OrderType, f64, Quantity)> =
+            subject.and_then(|id| self.book.get_order(id));
+
+        self.book.apply_delta(std::slice::from_ref(event))?;
+
+        match (event.get_kind(), before) {
+            (EventKind::Match(trade), Some((side, price, _))) =>
+                self.trade(trade, side, price, event.get_timestamp()),
+            (EventKind::Cancel { order, .. }, _) => self.leave(*order),
+            (EventKind::Reduce { order, quantity, .. }, _) =>
+                self.shrink(*order, *quantity),
+            /* an amendment keeps its priority only if it just reduces the
+             * order */
+            (EventKind::Amend { order, price, quantity, .. },
+             Some((_, old_price, old_quantity)))
+                if old_price == *price && *quantity <= old_quantity =>
+                self.shrink(*order, quantity::saturating_sub(old_quantity,
+                                                             *quantity)),
+            (EventKind::Amend { order, .. }, _) |
+            (EventKind::Reprice { order, .. }, _) => self.leave(*order),
+            _ => {}
+        }
+
+        Ok(())
+    }
+
+    pub fn observe_all(&mut self, events: &[Event]) ->
+        Result<(), ReplicaError> {
+        events.iter().try_for_each(|event| self.observe(event))
+    }
+
+    /* a real order has gone from every queue it was in */
+    fn leave(&mut self, id: OrderId) {
+        for order in self.orders.iter_mut() {
+            order.ahead.remove(&id);
+        }
+    }
+
+    /* a real order has less in front of simulated orders than it did */
+    fn shrink(&mut self, id: OrderId, quantity: Quantity) {
+        for order in self.orders.iter_mut() {
+            if let Some(ahead) = order.ahead.get_mut(&id) {
+                *ahead = quantity::saturating_sub(*ahead, quantity);
+
+                if *ahead == ZERO {
+                    order.ahead.remove(&id);
+                }
+            }
+        }
+    }
+
+    /* a trade with a real order resting on `side` at `price` */
+    fn trade(&mut self, trade: &Trade, side: OrderType, price: f64,
+             timestamp: DateTime<Utc>) {
+        let resting: OrderId = trade.get_resting();
+        let mut left: Quantity = trade.get_quantity();
+
+        for order in self.orders.iter_mut() {
+            /* it could only have traded ahead of this order */
+            if order.order_type != side || left == ZERO ||
+                order.ahead.contains_key(&resting) ||
+                !(price == order.price || order.traded_through(price)) {
+                continue;
+            }
+
+            let filled: Quantity = left.min(order.quantity);
+
+            order.fill(filled);
+            left = quantity::saturating_sub(left, filled);
+            self.fills.push(PaperFill {
+                order: order.id,
+                price: order.price,
+                quantity: filled,
+                timestamp
+            });
+        }
+
+        self.shrink(resting, trade.get_quantity());
+        self.orders.retain(|order| order.quantity > ZERO);
+    }
+}
+
+impl EventSink for PaperTrader {
+    fn write(&mut self, event: &Event) -> Result<(), SinkError> {
+        Ok(self.observe(event)?)
+    }
+
+    fn flush(&mut self) -> Result<(), SinkError> {
+        Ok(())
+    }
+}
+
+#[cfg(all(test, not(feature = "decimal-quantity")))]
+mod tests {
+    use super::*;
+    use crate::account::Account;
+    use crate::book::{Book, BookError};
+    use crate::order::Order;
+
+    fn build_order(id: OrderId, order_type: OrderType, price: f64,
+                   quantity: Quantity) -> Order {
+        let mut holdings: HashMap<String, Quantity> = HashMap::new();
+        holdings.insert("BOOK".to_string(), 1000);
+
+        let owner: Account = Account::new(id, "Account".to_string(),
+                                          12000.00, holdings);
+
+        Order::new(id, owner, "BOOK".to_string(), order_type, price, quantity)
+    }
+
+    #[test]
+    fn test_queue_position_fills() -> Result<(), BookError> {
+        let mut book: Book = Book::new(1, "Book".to_string(),
+                                       "BOOK".to_string());
+
+        book.submit(build_order(1, OrderType::Bid, 11.00, 10))?;
+        book.submit(build_order(2, OrderType::Bid, 11.00, 5))?;
+        book.submit(build_order(3, OrderType::Ask, 12.00, 10))?;
+
+        let mut actual_trader: PaperTrader =
+            PaperTrader::from_snapshot(&book.snapshot());
+        let seq: u64 = book.last_seq();
+        let mine: OrderId = actual_trader.submit(OrderType::Bid, 11.00, 8,
+                                                 Utc::now());
+
+        assert_eq!(actual_trader.get_order(mine).map(|o| o.get_queue_ahead()),
+                   Some(15));
+
+        /* someone behind us, then trades that only reach the orders ahead */
+        book.submit(build_order(4, OrderType::Bid, 11.00, 20))?;
+        book.cancel(2)?;
+        book.submit(build_order(5, OrderType::Ask, 11.00, 6))?;
+        actual_trader.observe_all(&book.delta_since(seq)?).unwrap();
+
+        assert!(actual_trader.get_fills().is_empty());
+        assert_eq!(actual_trader.get_order(mine).map(|o| o.get_queue_ahead()),
+                   Some(4));
+
+        /* this one reaches past the queue ahead, into order 4 */
+        let seq: u64 = book.last_seq();
+        book.submit(build_order(6, OrderType::Ask, 11.00, 9))?;
+        actual_trader.observe_all(&book.delta_since(seq)?).unwrap();
+
+        let fills: Vec<PaperFill> = actual_trader.take_fills();
+
+        assert_eq!(fills.iter().map(PaperFill::get_quantity).sum::<Quantity>(),
+                   5);
+        assert_eq!(actual_trader.get_order(mine).map(|o| o.get_quantity()),
+                   Some(3));
+
+        /* the real book is untouched */
+        assert_eq!(actual_trader.get_book().levels(), book.levels());
+        Ok(())
+    }
+
+    #[test]
+    fn test_marketable_paper_order() {
+        let mut book: Book = Book::new(1, "Book".to_string(),
+                                       "BOOK".to_string());
+
+        book.submit(build_order(1, OrderType::Ask, 12.00, 10)).unwrap();
+        book.submit(build_order(2, OrderType::Ask, 12.50, 10)).unwrap();
+
+        let mut actual_trader: PaperTrader =
+            PaperTrader::from_snapshot(&book.snapshot());
+        let mine: OrderId = actual_trader.submit(OrderType::Bid, 12.50, 25,
+                                                 Utc::now());
+
+        assert_eq!(actual_trader.get_fills().iter()
+                       .map(|fill| (fill.get_price(), fill.get_quantity()))
+                       .collect::<Vec<(f64, Quantity)>>(),
+                   vec![(12.00, 10), (12.50, 10)]);
+        assert_eq!(actual_trader.get_order(mine).map(|o| o.get_quantity()),
+                   Some(5));
+    }
+}
@@ -0,0 +1,117 @@
+use serde::{Serialize, Deserialize};
+
+use crate::builder::{BookConfig, RoundingMode};
+use crate::levels::TopOfBook;
+use crate::order::OrderType;
+
+/* the price a pegged order follows */
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum PegReference {
+    BestBid,
+    BestAsk,
+    Mid
+}
+
+/* Terms on which an order's price follows the market: `offset` from the
+ * reference, but never beyond `limit` (above it for a bid, below it for an
+ * ask). See `Book::submit_pegged`. */
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Peg {
+    reference: PegReference,
+    offset: f64,
+    limit: Option<f64>
+}
+
+impl Peg {
+    pub fn new(reference: PegReference) -> Peg {
+        Peg {
+            reference,
+            offset: 0.0,
+            limit: None
+        }
+    }
+
+    pub fn offset(mut self, offset: f64) -> Peg {
+        self.offset = offset;
+        self
+    }
+
+    pub fn limit(mut self, limit: f64) -> Peg {
+        self.limit = Some(limit);
+        self
+    }
+
+    pub fn get_reference(&self) -> PegReference {
+        self.reference
+    }
+
+    pub fn get_offset(&self) -> f64 {
+        self.offset
+    }
+
+    pub fn get_limit(&self) -> Option<f64> {
+        self.limit
+    }
+
+    /* Where an order on `side` pegged on these terms belongs, given the
+     * market `top`, if the reference exists. Prices between ticks are
+     * rounded away from the other side, so a peg never becomes more
+     * aggressive than its terms. */
+    pub fn price(&self, side: &OrderType, top: &TopOfBook,
+                 config: &BookConfig) -> Option<f64> {
+        self.price_or(side, top, None, config)
+    }
+
+    /* as `price`, but following `fallback` (e.g. `Book::reference_price`)
+     * while the reference doesn't exist */
+    pub fn price_or(&self, side: &OrderType, top: &TopOfBook,
+                    fallback: Option<f64>, config: &BookConfig) ->
+        Option<f64> {
+        let reference: f64 = match self.reference {
+            PegReference::BestBid => top.get_bid().map(|(price, _)| price),
+            PegReference::BestAsk => top.get_ask().map(|(price, _)| price),
+            PegReference::Mid => top.get_mid()
+        }.or(fallback)?;
+        let price: f64 = reference + self.offset;
+
+        Some(match side {
+            OrderType::Bid => config.round_to_tick(
+                self.limit.map_or(price, |limit| price.min(limit)),
+                RoundingMode::Down),
+            OrderType::Ask => config.round_to_tick(
+                self.limit.map_or(price, |limit| price.max(limit)),
+                RoundingMode::Up)
+        })
+    }
+}
+
+#[cfg(all(test, not(feature = "decimal-quantity")))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_price() {
+        let top: TopOfBook = TopOfBook::new(Some((10.0, 5)), Some((10.5, 5)));
+        let config: BookConfig = BookConfig {
+            tick_size: Some(0.1),
+            ..BookConfig::default()
+        };
+
+        assert_eq!(Peg::new(PegReference::BestBid).offset(0.1)
+                       .price(&OrderType::Bid, &top, &config),
+                   Some(10.1));
+        assert_eq!(Peg::new(PegReference::Mid)
+                       .price(&OrderType::Bid, &top, &config),
+                   Some(10.2));
+        assert_eq!(Peg::new(PegReference::Mid)
+                       .price(&OrderType::Ask, &top, &config),
+                   Some(10.3));
+        assert_eq!(Peg::new(PegReference::BestAsk).offset(-1.0).limit(10.0)
+                       .price(&OrderType::Ask, &top, &config),
+                   Some(10.0));
+        assert_eq!(Peg::new(PegReference::BestAsk)
+                       .price(&OrderType::Bid, &TopOfBook::default(),
+                              &config),
+                   None);
+    }
+}
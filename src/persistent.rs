@@ -0,0 +1,360 @@
+use im::{HashMap, HashSet, OrdMap, Vector};
+use ordered_float::OrderedFloat;
+
+use crate::event::{Event, EventKind};
+use crate::levels::{Level, Levels, TopOfBook};
+use crate::order::{OrderId, OrderType};
+use crate::quantity::{self, Quantity, ZERO};
+use crate::replica::ReplicaError;
+use crate::sink::{EventSink, SinkError};
+use crate::snapshot::BookSnapshot;
+
+type Side = OrdMap<OrderedFloat<f64>, Vector<OrderId>>;
+
+/* A consistent, read-only view of a book as of one event. Views share
+ * whatever hasn't changed between them, so taking one is O(1) and holding
+ * on to it costs only what the book has changed since; they can be sent to
+ * other threads and read there while the book carries on matching. Hidden
+ * orders are in their queues, behind the displayed ones, but not in the
+ * depth. */
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct BookView {
+    orders: HashMap<OrderId, (OrderType, f64, Quantity)>,
+    hidden: HashSet<OrderId>,
+    bids: Side,
+    asks: Side,
+    last_seq: u64
+}
+
+impl BookView {
+    /* the sequence number of the last event seen */
+    pub fn get_last_seq(&self) -> u64 {
+        self.last_seq
+    }
+
+    /* a resting order's side, price and remaining quantity */
+    pub fn get_order(&self, id: OrderId) ->
+        Option<(OrderType, f64, Quantity)> {
+        self.orders.get(&id).copied()
+    }
+
+    fn side(&self, side: OrderType) -> &Side {
+        match side {
+            OrderType::Bid => &self.bids,
+            OrderType::Ask => &self.asks
+        }
+    }
+
+    /* the orders resting on `side` at `price`, in time priority */
+    pub fn queue(&self, side: OrderType, price: f64) -> Vec<OrderId> {
+        self.side(side).get(&OrderedFloat::from(price))
+            .map(|queue| queue.iter().copied().collect())
+            .unwrap_or_default()
+    }
+
+    /* the level's displayed depth; `None` if it only has hidden orders */
+    fn level(&self, price: &OrderedFloat<f64>, queue: &Vector<OrderId>) ->
+        Option<Level> {
+        let depth: Quantity = quantity::total(queue.iter()
+            .filter(|id| !self.hidden.contains(id))
+            .filter_map(|id| self.orders.get(id))
+            .map(|(_, _, quantity)| *quantity));
+
+        if depth > ZERO {
+            Some((price.into_inner(), depth))
+        } else {
+            None
+        }
+    }
+
+    pub fn best_bid(&self) -> Option<Level> {
+        self.bids.iter().rev()
+            .find_map(|(price, queue)| self.level(price, queue))
+    }
+
+    pub fn best_ask(&self) -> Option<Level> {
+        self.asks.iter().find_map(|(price, queue)| self.level(price, queue))
+    }
+
+    pub fn top_of_book(&self) -> TopOfBook {
+        TopOfBook::new(self.best_bid(), self.best_ask())
+    }
+
+    /* aggregated depth, as `Book::levels` would report it */
+    pub fn levels(&self) -> Levels {
+        Levels::new(self.bids.iter().rev()
+                        .filter_map(|(price, queue)| self.level(price, queue))
+                        .collect(),
+                    self.asks.iter()
+                        .filter_map(|(price, queue)| self.level(price, queue))
+                        .collect())
+    }
+}
+
+/* A book's resting orders and queues kept in persistent maps, seeded from a
+ * snapshot (or from nothing, for a new book) and kept up to date from its
+ * events, as a `BookReplica` is. Use it as the book's sink and hand the
+ * views it takes to readers, which then need neither locks nor copies of
+ * the book. */
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PersistentBook {
+    current: BookView
+}
+
+impl PersistentBook {
+    /* for a book that has not had any events yet */
+    pub fn new() -> PersistentBook {
+        PersistentBook::default()
+    }
+
+    pub fn from_snapshot(snapshot: &BookSnapshot) -> PersistentBook {
+        let current: BookView = BookView {
+            last_seq: snapshot.last_seq,
+            ..BookView::default()
+        };
+
+        let mut book: PersistentBook = PersistentBook {current};
+
+        /* best first, and in time priority within each level */
+        for order in snapshot.bids.iter().chain(snapshot.asks.iter()) {
+            book.insert(order.get_id(), order.get_order_type(),
+                        order.get_price(), order.get_quantity(),
+                        order.is_hidden());
+        }
+
+        book
+    }
+
+    /* the book as it stands, in O(1) */
+    pub fn view(&self) -> BookView {
+        self.current.clone()
+    }
+
+    pub fn get_last_seq(&self) -> u64 {
+        self.current.last_seq
+    }
+
+    /* Applies events in sequence order, skipping any already seen; a
+     * missing one is an error, as for `BookReplica::apply_delta`. */
+    pub fn apply_delta(&mut self, events: &[Event]) ->
+        Result<(), ReplicaError> {
+        events.iter().try_for_each(|event| self.apply_event(event))
+    }
+
+    fn apply_event(&mut self, event: &Event) -> Result<(), ReplicaError> {
+        let last_seq: u64 = self.current.last_seq;
+
+        if event.get_seq() <= last_seq {
+            return Ok(());
+        }
+
+        if event.get_seq() != last_seq + 1 {
+            return Err(ReplicaError::Gap {
+                expected: last_seq + 1,
+                found: event.get_seq()
+            });
+        }
+
+        self.apply(event.get_kind());
+        self.current.last_seq = event.get_seq();
+        Ok(())
+    }
+
+    fn apply(&mut self, kind: &EventKind) {
+        match kind {
+            EventKind::Post { order, order_type, price, quantity,
+                              hidden } => {
+                if self.current.orders.contains_key(order) {
+                    self.current.orders.insert(*order, (*order_type, *price,
+                                                        *quantity));
+                } else {
+                    self.insert(*order, *order_type, *price, *quantity,
+                                *hidden);
+                }
+            },
+            /* an amendment keeps its place only if it just reduces the
+             * order; otherwise it leaves the book, to be posted again once
+             * it has been through matching */
+            EventKind::Amend { order, price, quantity, .. } => {
+                match self.current.orders.get(order).copied() {
+                    Some((side, old_price, old_quantity))
+                        if old_price == *price && *quantity <= old_quantity =>
+                    {
+                        self.current.orders.insert(*order, (side, *price,
+                                                            *quantity));
+                    },
+                    _ => self.remove(*order)
+                }
+            },
+            EventKind::Reprice { order, .. } => self.remove(*order),
+            EventKind::Cancel { order, .. } => self.remove(*order),
+            EventKind::Reduce { order, quantity, .. } =>
+                self.fill(*order, *quantity),
+            /* in continuous trading the aggressor has not rested yet, so
+             * only the resting order is known; in an auction both are */
+            EventKind::Match(trade) => {
+                for id in [trade.get_resting(), trade.get_aggressor()] {
+                    self.fill(id, trade.get_quantity());
+                }
+            },
+            _ => {}
+        }
+    }
+
+    /* queues an order as the book does: displayed orders ahead of every
+     * hidden one at their level, and hidden ones at the very back */
+    fn insert(&mut self, id: OrderId, side: OrderType, price: f64,
+              quantity: Quantity, hidden: bool) {
+        let view: &mut BookView = &mut self.current;
+
+        view.orders.insert(id, (side, price, quantity));
+
+        if hidden {
+            view.hidden.insert(id);
+        }
+
+        let hidden_orders: &HashSet<OrderId> = &view.hidden;
+        let levels: &mut Side = match side {
+            OrderType::Bid => &mut view.bids,
+            OrderType::Ask => &mut view.asks
+        };
+        let queue: &mut Vector<OrderId> = levels
+            .entry(OrderedFloat::from(price))
+            .or_default();
+        let first_hidden: Option<usize> = if hidden {
+            None
+        } else {
+            queue.iter().position(|queued| hidden_orders.contains(queued))
+        };
+
+        match first_hidden {
+            Some(index) => queue.insert(index, id),
+            None => queue.push_back(id)
+        }
+    }
+
+    fn remove(&mut self, id: OrderId) {
+        let (side, price, _) = match self.current.orders.remove(&id) {
+            Some(order) => order,
+            None => return
+        };
+        self.current.hidden.remove(&id);
+        let levels: &mut Side = side_mut(&mut self.current, side);
+        let key: OrderedFloat<f64> = OrderedFloat::from(price);
+
+        if let Some(queue) = levels.get_mut(&key) {
+            if let Some(index) = queue.index_of(&id) {
+                queue.remove(index);
+            }
+
+            if queue.is_empty() {
+                levels.remove(&key);
+            }
+        }
+    }
+
+    fn fill(&mut self, id: OrderId, quantity: Quantity) {
+        let remaining: Quantity = match self.current.orders.get_mut(&id) {
+            Some((_, _, remaining)) => {
+                *remaining = quantity::saturating_sub(*remaining, quantity);
+                *remaining
+            },
+            None => return
+        };
+
+        if remaining == ZERO {
+            self.remove(id);
+        }
+    }
+}
+
+fn side_mut(view: &mut BookView, side: OrderType) -> &mut Side {
+    match side {
+        OrderType::Bid => &mut view.bids,
+        OrderType::Ask => &mut view.asks
+    }
+}
+
+impl EventSink for PersistentBook {
+    fn write(&mut self, event: &Event) -> Result<(), SinkError> {
+        Ok(self.apply_event(event)?)
+    }
+
+    fn flush(&mut self) -> Result<(), SinkError> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+    use crate::account::Account;
+    use crate::book::Book;
+    use crate::matching::PriceTime;
+    use crate::order::Order;
+
+    fn build_order(id: OrderId, order_type: OrderType, price: f64,
+                   quantity: Quantity) -> Order {
+        let mut holdings: std::collections::HashMap<String, Quantity> =
+            std::collections::HashMap::new();
+        holdings.insert("BOOK".to_string(), 1000);
+
+        let owner: Account = Account::new(id, "Account".to_string(),
+                                          12000.00, holdings);
+
+        Order::new(id, owner, "BOOK".to_string(), order_type, price, quantity)
+    }
+
+    #[test]
+    fn test_views() -> crate::Result<()> {
+        let mut book: Book<PriceTime, PersistentBook> =
+            Book::builder(1, "BOOK".to_string())
+            .sink(PersistentBook::new())
+            .build()?;
+
+        book.submit(build_order(1, OrderType::Ask, 12.00, 10))?;
+        book.submit(build_order(2, OrderType::Ask, 12.00, 5))?;
+        book.submit(build_order(3, OrderType::Bid, 11.50, 4))?;
+
+        let before: BookView = book.get_sink().view();
+
+        /* the book moves on while a reader holds the view elsewhere */
+        let reader = thread::spawn(move || (before.levels(),
+                                            before.queue(OrderType::Ask,
+                                                         12.00)));
+
+        book.submit(build_order(4, OrderType::Bid, 12.00, 12))?;
+        book.modify(3, 11.75, 4)?;
+
+        let (levels, queue) = reader.join().unwrap();
+
+        assert_eq!(levels, Levels::new(vec![(11.50, 4)],
+                                       vec![(12.00, 15)]));
+        assert_eq!(queue, vec![1, 2]);
+
+        let after: BookView = book.get_sink().view();
+
+        assert_eq!(after.levels(), book.levels());
+        assert_eq!(after.queue(OrderType::Ask, 12.00), vec![2]);
+        assert_eq!(after.get_last_seq(), book.last_seq());
+        assert_eq!(PersistentBook::from_snapshot(&book.snapshot()).view()
+                       .levels(), book.levels());
+
+        /* hidden orders queue behind displayed ones, out of the depth */
+        let mut hidden: Order = build_order(5, OrderType::Ask, 12.50, 10);
+
+        hidden.set_hidden();
+        book.submit(hidden)?;
+        book.submit(build_order(6, OrderType::Ask, 12.50, 3))?;
+
+        let after: BookView = book.get_sink().view();
+
+        assert_eq!(after.levels(), book.levels());
+        assert_eq!(after.best_ask(), Some((12.00, 3)));
+        assert_eq!(after.queue(OrderType::Ask, 12.50), vec![6, 5]);
+        assert_eq!(PersistentBook::from_snapshot(&book.snapshot()).view(),
+                   after);
+        Ok(())
+    }
+}
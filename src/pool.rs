@@ -0,0 +1,99 @@
+use std::collections::VecDeque;
+
+use crate::order::OrderId;
+
+/* how many emptied level queues a book keeps for reuse by default */
+pub const DEFAULT_POOL_SIZE: usize = 64;
+
+/* Emptied level queues, kept with their buffers for the next level to
+ * open, so that levels emptying and refilling (as at the touch, all day
+ * long) don't allocate and free a queue each time. At most `limit` are
+ * kept, so a book that once had very many levels doesn't hold on to them
+ * all. */
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LevelPool {
+    spare: Vec<VecDeque<OrderId>>,
+    limit: usize
+}
+
+impl LevelPool {
+    pub fn new(limit: usize) -> LevelPool {
+        LevelPool {
+            spare: Vec::with_capacity(limit),
+            limit
+        }
+    }
+
+    pub fn get_limit(&self) -> usize {
+        self.limit
+    }
+
+    /* number of queues waiting to be reused */
+    pub fn len(&self) -> usize {
+        self.spare.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.spare.is_empty()
+    }
+
+    /* how many order IDs the queues waiting to be reused have room for */
+    pub fn capacity(&self) -> usize {
+        self.spare.iter().map(VecDeque::capacity).sum()
+    }
+
+    /* lets go of every queue waiting to be reused */
+    pub fn clear(&mut self) {
+        self.spare.clear();
+        self.spare.shrink_to_fit();
+    }
+
+    /* an empty queue, reused if there is one */
+    pub fn take(&mut self) -> VecDeque<OrderId> {
+        self.spare.pop().unwrap_or_default()
+    }
+
+    pub fn give(&mut self, mut queue: VecDeque<OrderId>) {
+        if self.spare.len() < self.limit && queue.capacity() > 0 {
+            queue.clear();
+            self.spare.push(queue);
+        }
+    }
+}
+
+/* removes `id` from a level queue; fills and cancels mostly take the order
+ * at the front, so that is tried before searching */
+pub fn remove_queued(queue: &mut VecDeque<OrderId>, id: OrderId) {
+    if queue.front() == Some(&id) {
+        queue.pop_front();
+    } else if let Some(index) = queue.iter().position(|queued| *queued == id) {
+        queue.remove(index);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reuse() {
+        let mut pool: LevelPool = LevelPool::new(1);
+        let mut queue: VecDeque<OrderId> = pool.take();
+
+        queue.extend([1, 2, 3]);
+        remove_queued(&mut queue, 1);
+        remove_queued(&mut queue, 3);
+        assert_eq!(queue, VecDeque::from(vec![2]));
+
+        let capacity: usize = queue.capacity();
+
+        pool.give(queue);
+        pool.give(VecDeque::with_capacity(8));
+        assert_eq!(pool.len(), 1);
+
+        let reused: VecDeque<OrderId> = pool.take();
+
+        assert!(reused.is_empty());
+        assert_eq!(reused.capacity(), capacity);
+    }
+}
@@ -0,0 +1,172 @@
+use serde::{Serialize, Deserialize};
+
+use crate::order::OrderType;
+use crate::quantity::{self, Quantity, ZERO};
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PositionSide {
+    #[default]
+    Flat,
+    Long,
+    Short
+}
+
+/* profit and loss on a position, in units of price */
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Pnl {
+    realized: f64,
+    unrealized: f64
+}
+
+impl Pnl {
+    pub fn new(realized: f64, unrealized: f64) -> Pnl {
+        Pnl {realized, unrealized}
+    }
+
+    pub fn get_realized(&self) -> f64 {
+        self.realized
+    }
+
+    pub fn get_unrealized(&self) -> f64 {
+        self.unrealized
+    }
+
+    pub fn total(&self) -> f64 {
+        self.realized + self.unrealized
+    }
+}
+
+/* An account's exposure to one ticker, built up fill by fill. Fills in the
+ * direction of the position move its average entry price; fills against it
+ * close it out first, realizing the difference from that price, and
+ * whatever is left over opens a position the other way at the fill's
+ * price. */
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Position {
+    side: PositionSide,
+    quantity: Quantity,
+    average_price: f64,
+    realized: f64
+}
+
+impl Position {
+    pub fn new() -> Position {
+        Position::default()
+    }
+
+    pub fn get_side(&self) -> PositionSide {
+        self.side
+    }
+
+    pub fn get_quantity(&self) -> Quantity {
+        self.quantity
+    }
+
+    /* zero while flat */
+    pub fn get_average_price(&self) -> f64 {
+        self.average_price
+    }
+
+    pub fn get_realized(&self) -> f64 {
+        self.realized
+    }
+
+    /* what closing the whole position at `mark` would realize */
+    pub fn unrealized(&self, mark: f64) -> f64 {
+        let size: f64 = quantity::to_f64(self.quantity);
+
+        match self.side {
+            PositionSide::Flat => 0.0,
+            PositionSide::Long => (mark - self.average_price) * size,
+            PositionSide::Short => (self.average_price - mark) * size
+        }
+    }
+
+    /* against `mark`, or with nothing unrealized if there is none */
+    pub fn pnl(&self, mark: Option<f64>) -> Pnl {
+        Pnl::new(self.realized, mark.map_or(0.0, |mark| self.unrealized(mark)))
+    }
+
+    /* closes the whole position out at `price`, as an expiring instrument
+     * does at its settlement price, returning what that realized */
+    pub fn settle(&mut self, price: f64) -> f64 {
+        let realized: f64 = self.unrealized(price);
+
+        self.realized += realized;
+        self.side = PositionSide::Flat;
+        self.quantity = ZERO;
+        self.average_price = 0.0;
+        realized
+    }
+
+    /* a buy (bid) or sell (ask) of `quantity` at `price` */
+    pub fn apply_fill(&mut self, side: &OrderType, quantity: Quantity,
+                      price: f64) {
+        let direction: PositionSide = match side {
+            OrderType::Bid => PositionSide::Long,
+            OrderType::Ask => PositionSide::Short
+        };
+
+        if self.side == PositionSide::Flat || self.side == direction {
+            let held: f64 = quantity::to_f64(self.quantity);
+            let added: f64 = quantity::to_f64(quantity);
+
+            self.average_price = (self.average_price * held + price * added) /
+                (held + added);
+            self.quantity += quantity;
+            self.side = direction;
+            return;
+        }
+
+        let closed: Quantity = quantity.min(self.quantity);
+        let opened: Quantity = quantity - closed;
+
+        self.realized += match self.side {
+            PositionSide::Long => price - self.average_price,
+            _ => self.average_price - price
+        } * quantity::to_f64(closed);
+        self.quantity -= closed;
+
+        if self.quantity == ZERO {
+            self.side = PositionSide::Flat;
+            self.average_price = 0.0;
+        }
+
+        if opened > ZERO {
+            self.side = direction;
+            self.quantity = opened;
+            self.average_price = price;
+        }
+    }
+}
+
+#[cfg(all(test, not(feature = "decimal-quantity")))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_position() {
+        let mut actual_position: Position = Position::new();
+
+        actual_position.apply_fill(&OrderType::Bid, 10, 10.00);
+        actual_position.apply_fill(&OrderType::Bid, 10, 12.00);
+        assert_eq!(actual_position.get_average_price(), 11.00);
+        assert_eq!(actual_position.unrealized(12.50), 30.00);
+
+        actual_position.apply_fill(&OrderType::Ask, 5, 13.00);
+        assert_eq!(actual_position.get_realized(), 10.00);
+        assert_eq!(actual_position.get_quantity(), 15);
+
+        /* through flat and out the other side */
+        actual_position.apply_fill(&OrderType::Ask, 20, 10.00);
+        assert_eq!(actual_position.get_realized(), -5.00);
+        assert_eq!(actual_position.get_side(), PositionSide::Short);
+        assert_eq!(actual_position.get_quantity(), 5);
+        assert_eq!(actual_position.pnl(Some(9.00)), Pnl::new(-5.00, 5.00));
+        assert_eq!(actual_position.pnl(None).total(), -5.00);
+
+        assert_eq!(actual_position.settle(9.50), 2.50);
+        assert_eq!(actual_position.get_side(), PositionSide::Flat);
+        assert_eq!(actual_position.get_realized(), -2.50);
+    }
+}
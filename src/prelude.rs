@@ -0,0 +1,18 @@
+/* The types most programs using a book need, for importing in one go:
+ *
+ *     use ironlobe::prelude::*;
+ */
+
+pub use crate::account::Account;
+pub use crate::book::{Book, BookError, BookId, CancelResult};
+pub use crate::builder::{BookBuilder, BookConfig, RoundingMode};
+pub use crate::clock::{Clock, ManualClock, SystemClock};
+pub use crate::event::{Event, EventKind, OrderLabel, Reason, Trade};
+pub use crate::levels::{Level, LevelDelta, Levels, TopOfBook};
+pub use crate::matching::{MatchingPolicy, PriceTime, ProRata};
+pub use crate::metadata::{AssetClass, Metadata};
+pub use crate::notional::Notional;
+pub use crate::order::{Order, OrderId, OrderStatus, OrderType};
+pub use crate::quantity::Quantity;
+pub use crate::sink::{EventSink, MemorySink, NullSink};
+pub use crate::subscription::EventFilter;
@@ -0,0 +1,183 @@
+use std::fmt::Debug;
+
+use ordered_float::OrderedFloat;
+
+use crate::quantity::{self, Quantity};
+#[cfg(feature = "decimal")]
+use rust_decimal::Decimal;
+#[cfg(feature = "decimal")]
+use rust_decimal::prelude::{FromPrimitive, ToPrimitive};
+
+/* How a book keys its price levels, its fourth generic parameter. Orders,
+ * events and levels still carry `f64` prices; a book converts them into
+ * its price type on the way in and back out again on the way out, so only
+ * the ordering of levels, and which prices land on the same level, depend
+ * on the choice. Money worked out from prices, i.e. `notional` and `fee`,
+ * is done in decimal under the `decimal` feature whatever the book's price
+ * type. */
+pub trait PriceType: Ord + Copy + Debug {
+    /* `None` if `price` has no exact representation, e.g. is between two
+     * ticks, in which case the book rejects it */
+    fn from_price(price: f64) -> Option<Self>;
+
+    fn to_price(self) -> f64;
+}
+
+/* the default: levels keyed on the prices exactly as given */
+pub type F64Price = OrderedFloat<f64>;
+
+impl PriceType for OrderedFloat<f64> {
+    fn from_price(price: f64) -> Option<OrderedFloat<f64>> {
+        if price.is_finite() {
+            Some(OrderedFloat::from(price))
+        } else {
+            None
+        }
+    }
+
+    fn to_price(self) -> f64 {
+        self.into_inner()
+    }
+}
+
+/* A whole number of ticks, each `1 / PER_UNIT` of a unit of price. Prices
+ * within floating point error of a tick, like 0.1 in `Ticks<100>`, are
+ * taken as being on it, so they all share the one level. */
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Ticks<const PER_UNIT: u64>(i64);
+
+/* hundredths, e.g. for equities quoted in dollars and cents */
+pub type Cents = Ticks<100>;
+
+impl<const PER_UNIT: u64> Ticks<PER_UNIT> {
+    pub fn new(ticks: i64) -> Ticks<PER_UNIT> {
+        Ticks(ticks)
+    }
+
+    pub fn get_ticks(&self) -> i64 {
+        self.0
+    }
+}
+
+impl<const PER_UNIT: u64> PriceType for Ticks<PER_UNIT> {
+    fn from_price(price: f64) -> Option<Ticks<PER_UNIT>> {
+        let ticks: f64 = price * PER_UNIT as f64;
+        let rounded: f64 = ticks.round();
+
+        if !ticks.is_finite() ||
+            (ticks - rounded).abs() > 1e-9 * ticks.abs().max(1.0) ||
+            rounded < i64::MIN as f64 || rounded > i64::MAX as f64 {
+            return None;
+        }
+
+        Some(Ticks(rounded as i64))
+    }
+
+    fn to_price(self) -> f64 {
+        self.0 as f64 / PER_UNIT as f64
+    }
+}
+
+/* The shortest decimal that round trips to the same `f64`, so that 0.1 is
+ * keyed as exactly 0.1 rather than its binary approximation. */
+#[cfg(feature = "decimal")]
+impl PriceType for Decimal {
+    fn from_price(price: f64) -> Option<Decimal> {
+        to_decimal(price)
+    }
+
+    fn to_price(self) -> f64 {
+        from_decimal(self)
+    }
+}
+
+/* exact decimal prices, for markets quoted far below a cent */
+#[cfg(feature = "decimal")]
+pub type DecimalPrice = Decimal;
+
+/* a price as the shortest decimal that round trips to it; `None` for
+ * infinities, NaN and anything too large */
+#[cfg(feature = "decimal")]
+pub fn to_decimal(price: f64) -> Option<Decimal> {
+    Decimal::from_f64(price).map(|price| price.normalize())
+}
+
+/* the nearest `f64`, e.g. for handing a decimal price to an order */
+#[cfg(feature = "decimal")]
+pub fn from_decimal(price: Decimal) -> f64 {
+    price.to_f64().unwrap_or(f64::NAN)
+}
+
+/* what `quantity` is worth at `price` */
+#[cfg(not(feature = "decimal"))]
+pub fn notional(price: f64, quantity: Quantity) -> f64 {
+    price * quantity::to_f64(quantity)
+}
+
+/* Worked in decimal, so that e.g. 3 at 0.1 is worth 0.3 rather than
+ * 0.30000000000000004; in `f64` if either side has no decimal form. */
+#[cfg(feature = "decimal")]
+pub fn notional(price: f64, quantity: Quantity) -> f64 {
+    match (to_decimal(price), to_decimal(quantity::to_f64(quantity))) {
+        (Some(price), Some(quantity)) => price.checked_mul(quantity)
+            .map(from_decimal)
+            .unwrap_or(f64::NAN),
+        _ => price * quantity::to_f64(quantity)
+    }
+}
+
+/* `rate` of `notional`, e.g. a fee or rebate */
+#[cfg(not(feature = "decimal"))]
+pub fn fee(notional: f64, rate: f64) -> f64 {
+    notional * rate
+}
+
+#[cfg(feature = "decimal")]
+pub fn fee(notional: f64, rate: f64) -> f64 {
+    match (to_decimal(notional), to_decimal(rate)) {
+        (Some(notional), Some(rate)) => notional.checked_mul(rate)
+            .map(from_decimal)
+            .unwrap_or(f64::NAN),
+        _ => notional * rate
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ticks() {
+        assert_eq!(Cents::from_price(12.34), Some(Ticks::new(1234)));
+        assert_eq!(Cents::from_price(0.1), Some(Ticks::new(10)));
+        assert_eq!(Cents::from_price(12.345), None);
+        assert_eq!(Cents::from_price(f64::NAN), None);
+        assert_eq!(Ticks::<100>::new(1234).to_price(), 12.34);
+    }
+
+    #[test]
+    fn test_f64_price() {
+        assert_eq!(F64Price::from_price(12.345).map(PriceType::to_price),
+                   Some(12.345));
+        assert_eq!(F64Price::from_price(f64::INFINITY), None);
+    }
+
+    #[cfg(feature = "decimal")]
+    #[test]
+    fn test_decimal_money() {
+        assert_eq!(notional(0.1, quantity::from_f64(3.0)), 0.3);
+        assert_eq!(fee(0.3, 0.001), 0.0003);
+        assert_eq!(fee(notional(0.00000001, quantity::from_f64(7.0)), -0.0002),
+                   -0.000000000014);
+    }
+
+    #[cfg(feature = "decimal")]
+    #[test]
+    fn test_decimal_price() {
+        let tiny: Decimal = Decimal::new(123, 10);
+
+        assert_eq!(to_decimal(0.0000000123), Some(tiny));
+        assert_eq!(from_decimal(tiny), 0.0000000123);
+        assert_eq!(to_decimal(f64::NAN), None);
+    }
+}
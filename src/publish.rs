@@ -0,0 +1,434 @@
+use std::fmt::Debug;
+#[cfg(feature = "nats")]
+use std::io::{BufRead, BufReader, BufWriter, Write};
+#[cfg(feature = "nats")]
+use std::net::{TcpStream, ToSocketAddrs};
+
+use crate::binary;
+use crate::event::*;
+use crate::sink::{EventSink, SinkError};
+
+/* Publishes a book's events, and separately its trades, to a message broker,
+ * one topic each per ticker. The broker is reached through a `Transport`;
+ * ones for NATS and Kafka are available behind the `nats` and `kafka`
+ * features respectively. */
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Message {
+    pub topic: String,
+    pub payload: Vec<u8>
+}
+
+/* Sends messages to a broker. Like sinks, transports can be used either as
+ * a generic parameter or boxed as `Box<dyn Transport>`. */
+pub trait Transport: Debug {
+    fn send(&mut self, messages: &[Message]) -> Result<(), SinkError>;
+    fn flush(&mut self) -> Result<(), SinkError>;
+}
+
+impl Transport for Box<dyn Transport> {
+    fn send(&mut self, messages: &[Message]) -> Result<(), SinkError> {
+        (**self).send(messages)
+    }
+
+    fn flush(&mut self) -> Result<(), SinkError> {
+        (**self).flush()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PayloadFormat {
+    Json,
+    /* see `binary`; events and trades are tagged with their kind */
+    Binary
+}
+
+/* `<prefix>.<ticker>.<stream>`, with anything in the ticker that brokers
+ * treat specially (e.g. the `.` NATS uses to separate subject tokens)
+ * replaced by `_` */
+pub fn topic(prefix: &str, ticker: &str, stream: &str) -> String {
+    let ticker: String = ticker.chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '-' { c } else { '_' })
+        .collect();
+
+    format!("{}.{}.{}", prefix, ticker, stream)
+}
+
+/* Stages each event as a message for the ticker's `events` topic, along with
+ * one for its `trades` topic if the event is a trade, and sends them in
+ * batches of `batch_size` events. Critical events (see
+ * `EventKind::is_critical`) are sent straight away with whatever is
+ * staged ahead of them, as is everything when the publisher is flushed. */
+#[derive(Debug)]
+pub struct TopicPublisher<T: Transport> {
+    transport: T,
+    format: PayloadFormat,
+    events_topic: String,
+    trades_topic: String,
+    batch_size: usize,
+    staged: Vec<Message>,
+    staged_events: usize
+}
+
+impl<T: Transport> TopicPublisher<T> {
+    pub fn new(transport: T, prefix: &str, ticker: &str,
+               format: PayloadFormat) -> TopicPublisher<T> {
+        TopicPublisher {
+            transport,
+            format,
+            events_topic: topic(prefix, ticker, "events"),
+            trades_topic: topic(prefix, ticker, "trades"),
+            batch_size: 1,
+            staged: vec![],
+            staged_events: 0
+        }
+    }
+
+    pub fn get_transport(&self) -> &T {
+        &self.transport
+    }
+
+    pub fn get_format(&self) -> PayloadFormat {
+        self.format
+    }
+
+    pub fn get_events_topic(&self) -> &str {
+        &self.events_topic
+    }
+
+    pub fn get_trades_topic(&self) -> &str {
+        &self.trades_topic
+    }
+
+    pub fn get_batch_size(&self) -> usize {
+        self.batch_size
+    }
+
+    /* at least one */
+    pub fn set_batch_size(&mut self, batch_size: usize) {
+        self.batch_size = batch_size.max(1);
+    }
+
+    fn encode_event(&self, event: &Event) -> Result<Vec<u8>, SinkError> {
+        match self.format {
+            PayloadFormat::Json => Ok(serde_json::to_vec(event)?),
+            PayloadFormat::Binary => Ok(binary::encode(event)?)
+        }
+    }
+
+    fn encode_trade(&self, trade: &Trade) -> Result<Vec<u8>, SinkError> {
+        match self.format {
+            PayloadFormat::Json => Ok(serde_json::to_vec(trade)?),
+            PayloadFormat::Binary => Ok(binary::encode(trade)?)
+        }
+    }
+
+    /* staged messages are only dropped once the transport has accepted
+     * them, so a failed send is retried on the next write or flush */
+    fn send_staged(&mut self) -> Result<(), SinkError> {
+        if !self.staged.is_empty() {
+            self.transport.send(&self.staged)?;
+            self.staged.clear();
+            self.staged_events = 0;
+        }
+
+        Ok(())
+    }
+}
+
+impl<T: Transport> EventSink for TopicPublisher<T> {
+    fn write(&mut self, event: &Event) -> Result<(), SinkError> {
+        let payload: Vec<u8> = self.encode_event(event)?;
+        let trade: Option<Vec<u8>> = match event.get_kind() {
+            EventKind::Match(trade) => Some(self.encode_trade(trade)?),
+            _ => None
+        };
+
+        self.staged.push(Message {
+            topic: self.events_topic.clone(),
+            payload
+        });
+
+        if let Some(payload) = trade {
+            self.staged.push(Message {
+                topic: self.trades_topic.clone(),
+                payload
+            });
+        }
+
+        self.staged_events += 1;
+
+        if event.get_kind().is_critical() ||
+            self.staged_events >= self.batch_size {
+            self.send_staged()?;
+        }
+
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), SinkError> {
+        self.send_staged()?;
+        self.transport.flush()
+    }
+}
+
+/* A minimal NATS client, speaking just enough of the protocol to publish:
+ * no TLS, authentication or reconnection. The server's PINGs are only
+ * answered while flushing, so flush at least every couple of minutes (see
+ * `Book::flush_events`) to stay connected. */
+#[cfg(feature = "nats")]
+#[derive(Debug)]
+pub struct NatsTransport {
+    reader: BufReader<TcpStream>,
+    writer: BufWriter<TcpStream>
+}
+
+#[cfg(feature = "nats")]
+impl NatsTransport {
+    /* e.g. `"localhost:4222"` */
+    pub fn connect<A: ToSocketAddrs>(address: A) ->
+        Result<NatsTransport, SinkError> {
+        let stream: TcpStream = TcpStream::connect(address)?;
+        let mut reader: BufReader<TcpStream> =
+            BufReader::new(stream.try_clone()?);
+        let mut writer: BufWriter<TcpStream> = BufWriter::new(stream);
+        let mut info: String = String::new();
+
+        reader.read_line(&mut info)?;
+
+        if !info.starts_with("INFO") {
+            return Err(SinkError::Transport(info.trim_end().to_string()));
+        }
+
+        writer.write_all(b"CONNECT {\"verbose\":false,\"pedantic\":false,\
+                           \"name\":\"ironlobe\",\"lang\":\"rust\"}\r\n")?;
+        writer.flush()?;
+
+        Ok(NatsTransport {reader, writer})
+    }
+}
+
+#[cfg(feature = "nats")]
+impl Transport for NatsTransport {
+    fn send(&mut self, messages: &[Message]) -> Result<(), SinkError> {
+        for message in messages {
+            write!(self.writer, "PUB {} {}\r\n", message.topic,
+                   message.payload.len())?;
+            self.writer.write_all(&message.payload)?;
+            self.writer.write_all(b"\r\n")?;
+        }
+
+        self.writer.flush()?;
+        Ok(())
+    }
+
+    /* waits for the server to answer a PING, by which time it has
+     * processed everything sent before it */
+    fn flush(&mut self) -> Result<(), SinkError> {
+        self.writer.write_all(b"PING\r\n")?;
+        self.writer.flush()?;
+
+        loop {
+            let mut line: String = String::new();
+
+            if self.reader.read_line(&mut line)? == 0 {
+                return Err(SinkError::Transport(
+                    "connection closed".to_string()));
+            }
+
+            match line.trim_end() {
+                "PONG" => return Ok(()),
+                "PING" => {
+                    self.writer.write_all(b"PONG\r\n")?;
+                    self.writer.flush()?;
+                },
+                error if error.starts_with("-ERR") =>
+                    return Err(SinkError::Transport(error.to_string())),
+                _ => {}
+            }
+        }
+    }
+}
+
+#[cfg(feature = "kafka")]
+pub struct KafkaTransport {
+    producer: kafka::producer::Producer
+}
+
+#[cfg(feature = "kafka")]
+impl Debug for KafkaTransport {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("KafkaTransport").finish_non_exhaustive()
+    }
+}
+
+#[cfg(feature = "kafka")]
+impl KafkaTransport {
+    pub fn connect(hosts: Vec<String>) -> Result<KafkaTransport, SinkError> {
+        let producer: kafka::producer::Producer =
+            kafka::producer::Producer::from_hosts(hosts)
+                .with_required_acks(kafka::producer::RequiredAcks::One)
+                .create()
+                .map_err(|e| SinkError::Transport(e.to_string()))?;
+
+        Ok(KafkaTransport {producer})
+    }
+
+    pub fn new(producer: kafka::producer::Producer) -> KafkaTransport {
+        KafkaTransport {producer}
+    }
+}
+
+#[cfg(feature = "kafka")]
+impl Transport for KafkaTransport {
+    fn send(&mut self, messages: &[Message]) -> Result<(), SinkError> {
+        let records: Vec<kafka::producer::Record<(), &[u8]>> = messages.iter()
+            .map(|message| kafka::producer::Record::from_value(
+                &message.topic, message.payload.as_slice()))
+            .collect();
+
+        self.producer.send_all(&records)
+            .map_err(|e| SinkError::Transport(e.to_string()))?;
+        Ok(())
+    }
+
+    /* `send` waits for the broker to acknowledge every batch */
+    fn flush(&mut self) -> Result<(), SinkError> {
+        Ok(())
+    }
+}
+
+#[cfg(all(test, not(feature = "decimal-quantity")))]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+    use crate::order::OrderType;
+
+    #[derive(Debug, Default)]
+    struct RecordingTransport {
+        batches: Vec<Vec<Message>>,
+        flushes: usize
+    }
+
+    impl Transport for RecordingTransport {
+        fn send(&mut self, messages: &[Message]) -> Result<(), SinkError> {
+            self.batches.push(messages.to_vec());
+            Ok(())
+        }
+
+        fn flush(&mut self) -> Result<(), SinkError> {
+            self.flushes += 1;
+            Ok(())
+        }
+    }
+
+    fn post(seq: u64) -> Event {
+        Event::new(seq, Utc::now(), EventKind::Post {
+            order: seq as u128,
+            order_type: OrderType::Bid,
+            price: 12.00,
+            quantity: 10,
+            hidden: false
+        })
+    }
+
+    #[test]
+    fn test_topic_naming() {
+        assert_eq!(topic("ironlobe", "BTC/USD", "events"),
+                   "ironlobe.BTC_USD.events");
+        assert_eq!(topic("ironlobe", "BRK.B", "trades"),
+                   "ironlobe.BRK_B.trades");
+    }
+
+    #[test]
+    fn test_batching() -> Result<(), SinkError> {
+        let mut publisher: TopicPublisher<RecordingTransport> =
+            TopicPublisher::new(RecordingTransport::default(), "lob", "BOOK",
+                                PayloadFormat::Json);
+        publisher.set_batch_size(3);
+
+        publisher.write(&post(1))?;
+        publisher.write(&post(2))?;
+        assert!(publisher.get_transport().batches.is_empty());
+
+        publisher.write(&post(3))?;
+        publisher.write(&post(4))?;
+        assert_eq!(publisher.get_transport().batches.len(), 1);
+
+        /* trades are critical, so go straight away */
+        let trade: Trade = Trade::new(Utc::now(), 12.00, 10, 5, 1,
+                                      OrderType::Ask);
+        publisher.write(&Event::new(5, Utc::now(),
+                                    EventKind::Match(trade)))?;
+
+        let batches: &[Vec<Message>] = &publisher.get_transport().batches;
+        let actual_topics: Vec<&str> = batches[1].iter()
+            .map(|message| message.topic.as_str())
+            .collect();
+
+        assert_eq!(actual_topics,
+                   vec!["lob.BOOK.events", "lob.BOOK.events",
+                        "lob.BOOK.trades"]);
+        assert_eq!(serde_json::from_slice::<Trade>(&batches[1][2].payload)
+                       .unwrap(),
+                   trade);
+
+        publisher.flush()?;
+        assert_eq!(publisher.get_transport().batches.len(), 2);
+        assert_eq!(publisher.get_transport().flushes, 1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_binary_payloads() -> Result<(), SinkError> {
+        let mut publisher: TopicPublisher<RecordingTransport> =
+            TopicPublisher::new(RecordingTransport::default(), "lob", "BOOK",
+                                PayloadFormat::Binary);
+
+        let event: Event = post(1);
+        publisher.write(&event)?;
+
+        let payload: &[u8] = &publisher.get_transport().batches[0][0].payload;
+        assert_eq!(binary::decode::<Event>(payload)?, event);
+        Ok(())
+    }
+
+    #[cfg(feature = "nats")]
+    #[test]
+    fn test_nats_protocol() -> Result<(), SinkError> {
+        use std::io::Read;
+        use std::net::TcpListener;
+        use std::thread;
+
+        let listener: TcpListener = TcpListener::bind("127.0.0.1:0")?;
+        let address = listener.local_addr()?;
+        let server = thread::spawn(move || -> std::io::Result<String> {
+            let (mut stream, _) = listener.accept()?;
+            stream.write_all(b"INFO {}\r\n")?;
+
+            let mut received: Vec<u8> = vec![];
+            let mut buffer: [u8; 256] = [0; 256];
+
+            while !received.ends_with(b"PING\r\n") {
+                let read: usize = stream.read(&mut buffer)?;
+                received.extend_from_slice(&buffer[..read]);
+            }
+
+            stream.write_all(b"PONG\r\n")?;
+            Ok(String::from_utf8_lossy(&received).into_owned())
+        });
+
+        let mut transport: NatsTransport = NatsTransport::connect(address)?;
+        transport.send(&[Message {
+            topic: "lob.BOOK.events".to_string(),
+            payload: b"hello".to_vec()
+        }])?;
+        transport.flush()?;
+
+        let received: String = server.join().unwrap()?;
+        assert!(received.starts_with("CONNECT {"));
+        assert!(received.ends_with("PUB lob.BOOK.events 5\r\nhello\r\n\
+                                    PING\r\n"));
+        Ok(())
+    }
+}
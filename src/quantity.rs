@@ -0,0 +1,207 @@
+/* Order sizes are integral by default. Building with the `decimal-quantity`
+ * feature swaps in a decimal type so that fractional sizes (e.g. 0.015 BTC)
+ * can be traded; everything else in the crate is written against the
+ * `Quantity` alias and the helpers below, so it works with either backend. */
+
+#[cfg(feature = "decimal-quantity")]
+use std::convert::TryFrom;
+
+#[cfg(not(feature = "decimal-quantity"))]
+pub type Quantity = u128;
+
+#[cfg(feature = "decimal-quantity")]
+pub type Quantity = rust_decimal::Decimal;
+
+#[cfg(not(feature = "decimal-quantity"))]
+pub const ZERO: Quantity = 0;
+
+#[cfg(feature = "decimal-quantity")]
+pub const ZERO: Quantity = rust_decimal::Decimal::ZERO;
+
+#[cfg(not(feature = "decimal-quantity"))]
+pub fn to_f64(quantity: Quantity) -> f64 {
+    quantity as f64
+}
+
+#[cfg(feature = "decimal-quantity")]
+pub fn to_f64(quantity: Quantity) -> f64 {
+    use rust_decimal::prelude::ToPrimitive;
+
+    quantity.to_f64().unwrap_or(f64::NAN)
+}
+
+/* the most of `value` that is a quantity: rounded towards zero to what
+ * the backend can hold, and nothing for negative or non-finite values */
+#[cfg(not(feature = "decimal-quantity"))]
+pub fn from_f64(value: f64) -> Quantity {
+    if value.is_finite() && value > 0.0 {
+        value.trunc() as Quantity
+    } else {
+        ZERO
+    }
+}
+
+#[cfg(feature = "decimal-quantity")]
+pub fn from_f64(value: f64) -> Quantity {
+    use rust_decimal::prelude::FromPrimitive;
+
+    if value.is_finite() && value > 0.0 {
+        Quantity::from_f64(value).unwrap_or(ZERO)
+    } else {
+        ZERO
+    }
+}
+
+/* `part / whole` of `amount`, rounded towards zero so that the shares of a
+ * whole never sum to more than `amount` */
+#[cfg(not(feature = "decimal-quantity"))]
+pub fn share(part: Quantity, whole: Quantity, amount: Quantity) -> Quantity {
+    if whole == ZERO {
+        return ZERO;
+    }
+
+    match part.checked_mul(amount) {
+        Some(product) => product / whole,
+        None => (part / whole).saturating_mul(amount)
+    }
+}
+
+#[cfg(feature = "decimal-quantity")]
+pub fn share(part: Quantity, whole: Quantity, amount: Quantity) -> Quantity {
+    use rust_decimal::RoundingStrategy;
+
+    if whole == ZERO {
+        return ZERO;
+    }
+
+    let scale: u32 = part.scale().max(amount.scale());
+
+    match part.checked_mul(amount).and_then(|p| p.checked_div(whole)) {
+        Some(share) => share.round_dp_with_strategy(scale,
+                                                    RoundingStrategy::ToZero),
+        None => ZERO
+    }
+}
+
+/* `quantity` less `by`, or `None` if that would leave less than nothing
+ * (or overflow) */
+pub fn checked_sub(quantity: Quantity, by: Quantity) -> Option<Quantity> {
+    if by > quantity {
+        return None;
+    }
+
+    quantity.checked_sub(by)
+}
+
+/* `quantity` less `by`, but never less than nothing; the decimal type's own
+ * `saturating_sub` only stops at its minimum, which is negative */
+pub fn saturating_sub(quantity: Quantity, by: Quantity) -> Quantity {
+    checked_sub(quantity, by).unwrap_or(ZERO)
+}
+
+/* the sum of `quantities`, stopping at the largest representable quantity
+ * rather than overflowing */
+pub fn total<I: IntoIterator<Item=Quantity>>(quantities: I) -> Quantity {
+    quantities.into_iter()
+        .fold(ZERO, |total, quantity| total.saturating_add(quantity))
+}
+
+/* written out to exactly `decimals` decimal places */
+#[cfg(not(feature = "decimal-quantity"))]
+pub fn format_fixed(quantity: Quantity, decimals: usize) -> String {
+    if decimals == 0 {
+        quantity.to_string()
+    } else {
+        format!("{}.{}", quantity, "0".repeat(decimals))
+    }
+}
+
+#[cfg(feature = "decimal-quantity")]
+pub fn format_fixed(quantity: Quantity, decimals: usize) -> String {
+    let rounded: Quantity = u32::try_from(decimals)
+        .map(|decimals| quantity.round_dp(decimals))
+        .unwrap_or(quantity);
+
+    format!("{:.*}", decimals, rounded)
+}
+
+/* parses a quantity as written by an external source; the integer backend
+ * accepts decimal notation so long as there is no fractional part */
+#[cfg(not(feature = "decimal-quantity"))]
+pub fn parse(text: &str) -> Option<Quantity> {
+    let text: &str = text.trim();
+
+    match text.split_once('.') {
+        Some((whole, fraction)) if fraction.chars().all(|c| c == '0') =>
+            whole.parse().ok(),
+        Some(_) => None,
+        None => text.parse().ok()
+    }
+}
+
+/* as with integers, negative sizes are refused */
+#[cfg(feature = "decimal-quantity")]
+pub fn parse(text: &str) -> Option<Quantity> {
+    use std::str::FromStr;
+
+    Quantity::from_str(text.trim()).ok()
+        .filter(|quantity| *quantity >= ZERO)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_checked_arithmetic() {
+        let (five, eight): (Quantity, Quantity) =
+            (Quantity::from(5u8), Quantity::from(8u8));
+
+        assert_eq!(checked_sub(eight, five), Some(Quantity::from(3u8)));
+        assert_eq!(checked_sub(five, eight), None);
+        assert_eq!(saturating_sub(five, eight), ZERO);
+        assert_eq!(total(vec![five, eight]), Quantity::from(13u8));
+        assert_eq!(total(vec![Quantity::MAX, five]), Quantity::MAX);
+        assert_eq!(from_f64(-1.0), ZERO);
+        assert_eq!(from_f64(f64::NAN), ZERO);
+    }
+
+    #[cfg(feature = "decimal-quantity")]
+    #[test]
+    fn test_fractional_quantities() {
+        let (tenth, hundredth): (Quantity, Quantity) =
+            (Quantity::new(1, 1), Quantity::new(1, 2));
+
+        assert_eq!(parse(" 0.015 "), Some(Quantity::new(15, 3)));
+        assert_eq!(checked_sub(tenth, hundredth), Some(Quantity::new(9, 2)));
+        assert_eq!(total(vec![tenth, hundredth]), Quantity::new(11, 2));
+        assert_eq!(from_f64(0.25), Quantity::new(25, 2));
+        assert_eq!(format_fixed(Quantity::new(15, 3), 4), "0.0150");
+        assert!((to_f64(Quantity::new(15, 3)) - 0.015).abs() < 1e-12);
+    }
+
+    #[cfg(feature = "decimal-quantity")]
+    #[test]
+    fn test_decimal_share() {
+        let amount: Quantity = Quantity::new(10, 3);
+        let three: Quantity = Quantity::from(3);
+
+        /* a third of 0.010 is rounded down to 0.003, at the amount's scale */
+        assert_eq!(share(Quantity::from(1), three, amount),
+                   Quantity::new(3, 3));
+        assert!(total((0..3).map(|_| share(Quantity::from(1), three,
+                                           amount))) <= amount);
+        assert_eq!(share(Quantity::from(1), ZERO, amount), ZERO);
+    }
+
+    #[cfg(feature = "decimal-quantity")]
+    #[test]
+    fn test_negative_decimals() {
+        assert_eq!(parse("-0.5"), None);
+        assert_eq!(from_f64(-0.5), ZERO);
+        assert_eq!(checked_sub(Quantity::new(1, 2), Quantity::new(15, 3)),
+                   None);
+        assert_eq!(saturating_sub(Quantity::new(1, 2), Quantity::new(15, 3)),
+                   ZERO);
+    }
+}
@@ -0,0 +1,79 @@
+use crate::quantity::{self, Quantity, ZERO};
+
+/* What an order taking liquidity would achieve against the book as it
+ * stands; see `Book::quote`. */
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct QuoteResult {
+    filled: Quantity,
+    average_price: f64,
+    worst_price: f64,
+    levels: usize
+}
+
+impl QuoteResult {
+    pub fn new(filled: Quantity, average_price: f64, worst_price: f64,
+               levels: usize) -> QuoteResult {
+        QuoteResult {filled, average_price, worst_price, levels}
+    }
+
+    pub fn get_filled(&self) -> Quantity {
+        self.filled
+    }
+
+    /* volume-weighted over every fill */
+    pub fn get_average_price(&self) -> f64 {
+        self.average_price
+    }
+
+    /* the price of the deepest level reached */
+    pub fn get_worst_price(&self) -> f64 {
+        self.worst_price
+    }
+
+    /* how many price levels the order would touch */
+    pub fn get_levels(&self) -> usize {
+        self.levels
+    }
+
+    pub fn is_complete(&self, quantity: Quantity) -> bool {
+        self.filled >= quantity
+    }
+
+    /* the average price's distance from `reference` (e.g. the mid), as a
+     * fraction of it */
+    pub fn slippage(&self, reference: f64) -> f64 {
+        (self.average_price - reference).abs() / reference
+    }
+}
+
+/* Accumulates fills, best level first, into a `QuoteResult`. */
+#[derive(Debug, Default)]
+pub(crate) struct QuoteBuilder {
+    filled: Quantity,
+    notional: f64,
+    worst_price: f64,
+    levels: usize
+}
+
+impl QuoteBuilder {
+    pub(crate) fn add(&mut self, price: f64, quantity: Quantity) {
+        if quantity == ZERO {
+            return;
+        }
+
+        self.filled += quantity;
+        self.notional += price * quantity::to_f64(quantity);
+        self.worst_price = price;
+        self.levels += 1;
+    }
+
+    pub(crate) fn build(self) -> Option<QuoteResult> {
+        if self.filled == ZERO {
+            return None;
+        }
+
+        Some(QuoteResult::new(self.filled,
+                              self.notional / quantity::to_f64(self.filled),
+                              self.worst_price, self.levels))
+    }
+}
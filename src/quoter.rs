@@ -0,0 +1,254 @@
+use crate::account::Account;
+use crate::batch::{BatchResult, BookOp, BookOpOutcome};
+use crate::book::Book;
+use crate::builder::RoundingMode;
+use crate::clock::Clock;
+use crate::id::OrderIdGenerator;
+use crate::matching::MatchingPolicy;
+use crate::order::{Order, OrderId, OrderType};
+use crate::price::PriceType;
+use crate::quantity::Quantity;
+use crate::sink::EventSink;
+
+/* Keeps a two-sided quote `spread` wide and `size` deep on each side in a
+ * book, centred on the mid of everyone else's best bid and ask (or their
+ * one side, or the last trade). Call `update` whenever the book may have
+ * moved: each side that is off target is amended back onto it, and each
+ * that has filled or gone is replaced, all in a single batch so nobody
+ * sees the quote half moved. */
+#[derive(Debug, Clone, PartialEq)]
+pub struct Quoter {
+    owner: Account,
+    ticker: String,
+    spread: f64,
+    size: Quantity,
+    bid: Option<OrderId>,
+    ask: Option<OrderId>
+}
+
+impl Quoter {
+    pub fn new(owner: Account, ticker: String, spread: f64,
+               size: Quantity) -> Quoter {
+        Quoter {
+            owner,
+            ticker,
+            spread,
+            size,
+            bid: None,
+            ask: None
+        }
+    }
+
+    pub fn get_spread(&self) -> f64 {
+        self.spread
+    }
+
+    /* takes effect at the next `update` */
+    pub fn set_spread(&mut self, spread: f64) {
+        self.spread = spread;
+    }
+
+    pub fn get_size(&self) -> Quantity {
+        self.size
+    }
+
+    /* takes effect at the next `update` */
+    pub fn set_size(&mut self, size: Quantity) {
+        self.size = size;
+    }
+
+    /* the quote's current bid, if it has one */
+    pub fn get_bid(&self) -> Option<OrderId> {
+        self.bid
+    }
+
+    pub fn get_ask(&self) -> Option<OrderId> {
+        self.ask
+    }
+
+    fn owns(&self, id: OrderId) -> bool {
+        self.bid == Some(id) || self.ask == Some(id)
+    }
+
+    /* the price to quote around, ignoring the quote itself */
+    fn reference<M, S, C, P>(&self, book: &Book<M, S, C, P>) -> Option<f64>
+    where M: MatchingPolicy, S: EventSink, C: Clock, P: PriceType {
+        let bid: Option<f64> = book.iter_bids()
+            .find(|order| !self.owns(order.get_id()))
+            .map(Order::get_price);
+        let ask: Option<f64> = book.iter_asks()
+            .find(|order| !self.owns(order.get_id()))
+            .map(Order::get_price);
+
+        match (bid, ask) {
+            (Some(bid), Some(ask)) => Some((bid + ask) / 2.0),
+            (Some(price), None) | (None, Some(price)) => Some(price),
+            (None, None) => book.get_ltp().ok()
+        }
+    }
+
+    /* what, if anything, brings one side of the quote onto target */
+    fn side_op<M, S, C, P, G>(&self, book: &Book<M, S, C, P>, ids: &mut G,
+                              side: OrderType, quoted: Option<OrderId>,
+                              price: f64) -> (Option<OrderId>, Option<BookOp>)
+    where M: MatchingPolicy, S: EventSink, C: Clock, P: PriceType,
+          G: OrderIdGenerator {
+        match quoted.and_then(|id| book.get_order(id).ok()) {
+            Some(order) if order.get_price() == price &&
+                order.get_quantity() == self.size => (quoted, None),
+            Some(order) => (quoted, Some(BookOp::Modify {
+                id: order.get_id(),
+                price,
+                quantity: self.size
+            })),
+            None => {
+                let id: OrderId = ids.next_id();
+                let order: Order = Order::new(id, self.owner.clone(),
+                                              self.ticker.clone(), side,
+                                              price, self.size);
+
+                (Some(id), Some(BookOp::Submit(Box::new(order))))
+            }
+        }
+    }
+
+    /* Moves the quote onto target, placing, repricing or resizing either
+     * side as needed. A side whose new price would reach the quote's own
+     * resting order on the other side goes second, once that order has
+     * moved out of the way, so the quote never trades with itself. Does
+     * nothing if there is nothing to quote around. */
+    pub fn update<M, S, C, P, G>(&mut self, book: &mut Book<M, S, C, P>,
+                                 ids: &mut G) -> BatchResult
+    where M: MatchingPolicy, S: EventSink, C: Clock, P: PriceType,
+          G: OrderIdGenerator {
+        let mid: f64 = match self.reference(book) {
+            Some(mid) => mid,
+            None => return Ok(vec![])
+        };
+        let bid_price: f64 = book.get_config()
+            .round_to_tick(mid - self.spread / 2.0, RoundingMode::Down);
+        let ask_price: f64 = book.get_config()
+            .round_to_tick(mid + self.spread / 2.0, RoundingMode::Up);
+        let resting_ask: Option<f64> = self.ask
+            .and_then(|id| book.get_order(id).ok())
+            .map(Order::get_price);
+
+        let (bid, bid_op) = self.side_op(book, ids, OrderType::Bid, self.bid,
+                                         bid_price);
+        let (ask, ask_op) = self.side_op(book, ids, OrderType::Ask, self.ask,
+                                         ask_price);
+        let ask_first: bool =
+            resting_ask.is_some_and(|resting| bid_price >= resting);
+        let ops: Vec<BookOp> = if ask_first {
+            ask_op.into_iter().chain(bid_op).collect()
+        } else {
+            bid_op.into_iter().chain(ask_op).collect()
+        };
+
+        if ops.is_empty() {
+            return Ok(vec![]);
+        }
+
+        let outcomes: Vec<BookOpOutcome> = book.apply_batch(ops)?;
+
+        self.bid = bid;
+        self.ask = ask;
+        Ok(outcomes)
+    }
+
+    /* cancels whatever is left of the quote */
+    pub fn pull<M, S, C, P>(&mut self, book: &mut Book<M, S, C, P>) ->
+        BatchResult
+    where M: MatchingPolicy, S: EventSink, C: Clock, P: PriceType {
+        let ops: Vec<BookOp> = self.bid.take().into_iter()
+            .chain(self.ask.take())
+            .filter(|id| book.get_order(*id).is_ok())
+            .map(BookOp::Cancel)
+            .collect();
+
+        if ops.is_empty() {
+            return Ok(vec![]);
+        }
+
+        book.apply_batch(ops)
+    }
+}
+
+#[cfg(all(test, not(feature = "decimal-quantity")))]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use crate::id::MonotonicIdGenerator;
+
+    fn account(id: u128) -> Account {
+        let mut holdings: HashMap<String, Quantity> = HashMap::new();
+        holdings.insert("BOOK".to_string(), 1000);
+
+        Account::new(id, "Account".to_string(), 100000.00, holdings)
+    }
+
+    fn submit(book: &mut Book, ids: &mut MonotonicIdGenerator,
+              order_type: OrderType, price: f64, quantity: Quantity) ->
+        crate::Result<OrderId> {
+        let id: OrderId = ids.next_id();
+
+        book.submit(Order::new(id, account(1), "BOOK".to_string(),
+                               order_type, price, quantity))?;
+        Ok(id)
+    }
+
+    fn quote(book: &Book, quoter: &Quoter) -> Option<(f64, Quantity, f64,
+                                                      Quantity)> {
+        let bid: &Order = book.get_order(quoter.get_bid()?).ok()?;
+        let ask: &Order = book.get_order(quoter.get_ask()?).ok()?;
+
+        Some((bid.get_price(), bid.get_quantity(), ask.get_price(),
+              ask.get_quantity()))
+    }
+
+    #[test]
+    fn test_quoter() -> crate::Result<()> {
+        let mut book: Book = Book::builder(1, "BOOK".to_string())
+            .tick_size(0.01)
+            .build()?;
+        let mut ids: MonotonicIdGenerator = MonotonicIdGenerator::new();
+        let mut quoter: Quoter = Quoter::new(account(2), "BOOK".to_string(),
+                                             0.50, 5);
+
+        /* nothing to quote around yet */
+        quoter.update(&mut book, &mut ids)?;
+        assert_eq!(quoter.get_bid(), None);
+
+        let others: Vec<OrderId> =
+            vec![submit(&mut book, &mut ids, OrderType::Bid, 10.00, 10)?,
+                 submit(&mut book, &mut ids, OrderType::Ask, 11.00, 10)?];
+
+        quoter.update(&mut book, &mut ids)?;
+        assert_eq!(quote(&book, &quoter), Some((10.25, 5, 10.75, 5)));
+
+        /* a partial fill is topped back up, losing its place */
+        submit(&mut book, &mut ids, OrderType::Ask, 10.25, 2)?;
+        assert_eq!(quote(&book, &quoter), Some((10.25, 3, 10.75, 5)));
+
+        quoter.update(&mut book, &mut ids)?;
+        assert_eq!(quote(&book, &quoter), Some((10.25, 5, 10.75, 5)));
+
+        /* the market jumps past the quote's own ask, which moves first */
+        for id in others {
+            book.cancel(id)?;
+        }
+
+        submit(&mut book, &mut ids, OrderType::Bid, 10.70, 10)?;
+        submit(&mut book, &mut ids, OrderType::Ask, 15.00, 10)?;
+
+        let trades: usize = book.get_trades().len();
+
+        quoter.update(&mut book, &mut ids)?;
+        assert_eq!(quote(&book, &quoter), Some((12.60, 5, 13.10, 5)));
+        assert_eq!(book.get_trades().len(), trades);
+
+        quoter.pull(&mut book)?;
+        assert_eq!(book.iter_all().count(), 2);
+        Ok(())
+    }
+}
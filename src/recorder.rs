@@ -0,0 +1,218 @@
+use std::slice;
+
+use chrono::{DateTime, Duration, Utc};
+
+use crate::event::Event;
+use crate::levels::Levels;
+use crate::replica::{BookReplica, ReplicaError};
+use crate::sink::{EventSink, SinkError};
+use crate::snapshot::BookSnapshot;
+
+/* how often a `DepthRecorder` keeps a full copy of the book */
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RecordingInterval {
+    EveryEvent,
+    Events(u64),
+    /* the first event at least this long after the last copy taken */
+    Every(Duration)
+}
+
+/* a copy of the book as of an event */
+#[derive(Debug, Clone, PartialEq)]
+struct Keyframe {
+    timestamp: Option<DateTime<Utc>>,
+    book: BookReplica
+}
+
+/* Keeps a book's history, so that its depth as of any event since
+ * recording started can be looked up again: full copies of the book at
+ * the configured interval, and every event in between to replay forward
+ * from the nearest one. Use as a book's sink, or feed it events from
+ * wherever they were stored. Events must arrive in sequence. */
+#[derive(Debug, Clone, PartialEq)]
+pub struct DepthRecorder {
+    interval: RecordingInterval,
+    current: BookReplica,
+    /* in sequence order; the first is where recording started */
+    keyframes: Vec<Keyframe>,
+    events: Vec<Event>
+}
+
+impl DepthRecorder {
+    /* for a book that has not had any events yet */
+    pub fn new(interval: RecordingInterval) -> DepthRecorder {
+        DepthRecorder::starting_from(BookReplica::default(), interval)
+    }
+
+    pub fn from_snapshot(snapshot: &BookSnapshot,
+                         interval: RecordingInterval) -> DepthRecorder {
+        DepthRecorder::starting_from(BookReplica::from_snapshot(snapshot),
+                                     interval)
+    }
+
+    fn starting_from(book: BookReplica, interval: RecordingInterval) ->
+        DepthRecorder {
+        DepthRecorder {
+            interval,
+            current: book.clone(),
+            keyframes: vec![Keyframe { timestamp: None, book }],
+            events: vec![]
+        }
+    }
+
+    pub fn get_interval(&self) -> RecordingInterval {
+        self.interval
+    }
+
+    /* the book as of the latest event recorded */
+    pub fn get_current(&self) -> &BookReplica {
+        &self.current
+    }
+
+    pub fn get_events(&self) -> &[Event] {
+        &self.events
+    }
+
+    pub fn record(&mut self, event: &Event) -> Result<(), ReplicaError> {
+        let previous: u64 = self.current.get_last_seq();
+
+        self.current.apply_delta(slice::from_ref(event))?;
+
+        /* already recorded */
+        if self.current.get_last_seq() == previous {
+            return Ok(());
+        }
+
+        self.events.push(event.clone());
+
+        if self.keyframe_due(event) {
+            self.keyframes.push(Keyframe {
+                timestamp: Some(event.get_timestamp()),
+                book: self.current.clone()
+            });
+        }
+
+        Ok(())
+    }
+
+    fn keyframe_due(&self, event: &Event) -> bool {
+        let last: &Keyframe = match self.keyframes.last() {
+            Some(last) => last,
+            None => return true
+        };
+
+        match self.interval {
+            RecordingInterval::EveryEvent => true,
+            RecordingInterval::Events(events) =>
+                event.get_seq() - last.book.get_last_seq() >= events,
+            RecordingInterval::Every(duration) => last.timestamp
+                .is_none_or(|at| event.get_timestamp() - at >= duration)
+        }
+    }
+
+    /* The book as it was just after the event numbered `seq`, or where
+     * recording started if that is `seq`. `None` if that is before
+     * recording started or after the latest event recorded. */
+    pub fn book_as_of(&self, seq: u64) -> Option<BookReplica> {
+        if seq > self.current.get_last_seq() {
+            return None;
+        }
+
+        let nearest: usize = self.keyframes
+            .partition_point(|keyframe| keyframe.book.get_last_seq() <= seq)
+            .checked_sub(1)?;
+        let mut book: BookReplica = self.keyframes.get(nearest)?.book.clone();
+        let start: usize = self.events
+            .partition_point(|event| event.get_seq() <= book.get_last_seq());
+        let end: usize = self.events
+            .partition_point(|event| event.get_seq() <= seq);
+
+        book.apply_delta(self.events.get(start..end)?).ok()?;
+        Some(book)
+    }
+
+    /* the book as it was at `timestamp`, if any event had been recorded by
+     * then */
+    pub fn book_at(&self, timestamp: DateTime<Utc>) -> Option<BookReplica> {
+        let recorded: usize = self.events
+            .partition_point(|event| event.get_timestamp() <= timestamp);
+        let last: &Event = self.events.get(recorded.checked_sub(1)?)?;
+
+        self.book_as_of(last.get_seq())
+    }
+
+    pub fn levels_at(&self, seq: u64) -> Option<Levels> {
+        self.book_as_of(seq).map(|book| book.levels())
+    }
+}
+
+impl EventSink for DepthRecorder {
+    fn write(&mut self, event: &Event) -> Result<(), SinkError> {
+        Ok(self.record(event)?)
+    }
+
+    fn flush(&mut self) -> Result<(), SinkError> {
+        Ok(())
+    }
+}
+
+#[cfg(all(test, not(feature = "decimal-quantity")))]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use chrono::TimeZone;
+    use crate::account::Account;
+    use crate::book::{Book, BookError};
+    use crate::clock::ManualClock;
+    use crate::matching::PriceTime;
+    use crate::order::*;
+    use crate::quantity::Quantity;
+
+    fn build_order(id: OrderId, order_type: OrderType, price: f64,
+                   quantity: Quantity) -> Order {
+        let mut holdings: HashMap<String, Quantity> = HashMap::new();
+        holdings.insert("BOOK".to_string(), 1000);
+
+        let owner: Account = Account::new(id, "Account".to_string(),
+                                          12000.00, holdings);
+
+        Order::new(id, owner, "BOOK".to_string(), order_type, price, quantity)
+    }
+
+    #[test]
+    fn test_levels_at() -> Result<(), BookError> {
+        let open: DateTime<Utc> = Utc.with_ymd_and_hms(2024, 1, 2, 9, 30, 0)
+            .unwrap();
+        let clock: ManualClock = ManualClock::new(open);
+        let mut book: Book<PriceTime, DepthRecorder, ManualClock> =
+            Book::builder(1, "BOOK".to_string())
+                .sink(DepthRecorder::new(RecordingInterval::Events(3)))
+                .clock(clock.clone())
+                .build()
+                .unwrap();
+        let mut expected: Vec<(u64, Levels)> =
+            vec![(book.last_seq(), book.levels())];
+
+        book.submit(build_order(1, OrderType::Bid, 10.00, 5))?;
+        expected.push((book.last_seq(), book.levels()));
+        book.submit(build_order(2, OrderType::Ask, 10.50, 5))?;
+        expected.push((book.last_seq(), book.levels()));
+        clock.advance(Duration::seconds(1));
+        book.submit(build_order(3, OrderType::Ask, 10.00, 2))?;
+        expected.push((book.last_seq(), book.levels()));
+        book.cancel(2)?;
+        expected.push((book.last_seq(), book.levels()));
+
+        let recorder: &DepthRecorder = book.get_sink();
+
+        for (seq, levels) in &expected {
+            assert_eq!(recorder.levels_at(*seq).as_ref(), Some(levels));
+        }
+
+        assert_eq!(recorder.levels_at(book.last_seq() + 1), None);
+        assert_eq!(recorder.book_at(open - Duration::seconds(1)), None);
+        assert_eq!(recorder.book_at(open).map(|book| book.levels()),
+                   expected.get(2).map(|(_, levels)| levels.clone()));
+        Ok(())
+    }
+}
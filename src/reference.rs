@@ -0,0 +1,356 @@
+use std::collections::{BTreeMap, HashMap};
+
+use ordered_float::OrderedFloat;
+
+use crate::account::Account;
+use crate::book::Book;
+use crate::compare::{compare_levels, BookDiff};
+use crate::event::Trade;
+use crate::levels::{Level, Levels};
+use crate::order::{Order, OrderId, OrderType};
+use crate::quantity::{Quantity, ZERO};
+
+/* A deliberately naive price-time matcher, as an oracle for differential
+ * testing `Book` against: resting orders sit in one unsorted list, and
+ * every match scans the whole of it for the best price and earliest
+ * arrival. There are no accounts, sessions or configuration; it only
+ * agrees with a `Book` with the default `PriceTime` policy whose orders'
+ * owners can always settle. */
+
+#[derive(Debug, Clone, PartialEq)]
+struct Resting {
+    id: OrderId,
+    order_type: OrderType,
+    price: f64,
+    quantity: Quantity,
+    /* lower is earlier */
+    priority: u64
+}
+
+/* a fill, as (price, quantity, aggressor, resting) */
+pub type ReferenceFill = (f64, Quantity, OrderId, OrderId);
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ReferenceBook {
+    resting: Vec<Resting>,
+    next_priority: u64
+}
+
+impl ReferenceBook {
+    pub fn new() -> ReferenceBook {
+        ReferenceBook::default()
+    }
+
+    pub fn contains(&self, id: OrderId) -> bool {
+        self.resting.iter().any(|order| order.id == id)
+    }
+
+    /* a resting order's remaining quantity */
+    pub fn get_quantity(&self, id: OrderId) -> Option<Quantity> {
+        self.resting.iter()
+            .find(|order| order.id == id)
+            .map(|order| order.quantity)
+    }
+
+    /* matches, then rests whatever is left */
+    pub fn submit(&mut self, id: OrderId, order_type: OrderType, price: f64,
+                  quantity: Quantity) -> Vec<ReferenceFill> {
+        let mut remaining: Quantity = quantity;
+        let mut fills: Vec<ReferenceFill> = vec![];
+
+        while remaining > ZERO {
+            let best: Option<usize> = (0..self.resting.len())
+                .filter(|i| {
+                    let other: &Resting = &self.resting[*i];
+
+                    other.order_type != order_type && match order_type {
+                        OrderType::Bid => other.price <= price,
+                        OrderType::Ask => other.price >= price
+                    }
+                })
+                .min_by(|a, b| {
+                    let (a, b) = (&self.resting[*a], &self.resting[*b]);
+                    let by_price = match order_type {
+                        OrderType::Bid => a.price.partial_cmp(&b.price),
+                        OrderType::Ask => b.price.partial_cmp(&a.price)
+                    };
+
+                    by_price.unwrap_or(std::cmp::Ordering::Equal)
+                        .then(a.priority.cmp(&b.priority))
+                });
+
+            let index: usize = match best {
+                Some(index) => index,
+                None => break
+            };
+            let other: &mut Resting = &mut self.resting[index];
+            let filled: Quantity = remaining.min(other.quantity);
+
+            fills.push((other.price, filled, id, other.id));
+            other.quantity -= filled;
+            remaining -= filled;
+
+            if other.quantity == ZERO {
+                self.resting.remove(index);
+            }
+        }
+
+        if remaining > ZERO {
+            self.rest(id, order_type, price, remaining);
+        }
+
+        fills
+    }
+
+    fn rest(&mut self, id: OrderId, order_type: OrderType, price: f64,
+            quantity: Quantity) {
+        self.resting.push(Resting {
+            id,
+            order_type,
+            price,
+            quantity,
+            priority: self.next_priority
+        });
+        self.next_priority += 1;
+    }
+
+    pub fn cancel(&mut self, id: OrderId) -> bool {
+        let before: usize = self.resting.len();
+
+        self.resting.retain(|order| order.id != id);
+        self.resting.len() != before
+    }
+
+    /* As `Book::modify`: a reduction keeps its priority, anything else is
+     * resubmitted. `None` if there is no such order or `quantity` is
+     * zero. */
+    pub fn modify(&mut self, id: OrderId, price: f64, quantity: Quantity) ->
+        Option<Vec<ReferenceFill>> {
+        let index: usize = self.resting.iter()
+            .position(|order| order.id == id)?;
+
+        if quantity == ZERO {
+            return None;
+        }
+
+        let order: &mut Resting = &mut self.resting[index];
+
+        if price == order.price && quantity <= order.quantity {
+            order.quantity = quantity;
+            return Some(vec![]);
+        }
+
+        let order: Resting = self.resting.remove(index);
+
+        Some(self.submit(id, order.order_type, price, quantity))
+    }
+
+    /* As `Book::reduce`: what is left, or `None` if there is no such order
+     * or it has less than `quantity` left */
+    pub fn reduce(&mut self, id: OrderId, quantity: Quantity) ->
+        Option<Quantity> {
+        let index: usize = self.resting.iter()
+            .position(|order| order.id == id)?;
+        let order: &mut Resting = &mut self.resting[index];
+
+        if quantity == ZERO || quantity > order.quantity {
+            return None;
+        }
+
+        order.quantity -= quantity;
+
+        if order.quantity == ZERO {
+            self.resting.remove(index);
+            return Some(ZERO);
+        }
+
+        Some(order.quantity)
+    }
+
+    pub fn levels(&self) -> Levels {
+        let mut bids: BTreeMap<OrderedFloat<f64>, Quantity> = BTreeMap::new();
+        let mut asks: BTreeMap<OrderedFloat<f64>, Quantity> = BTreeMap::new();
+
+        for order in self.resting.iter() {
+            let side: &mut BTreeMap<OrderedFloat<f64>, Quantity> =
+                match order.order_type {
+                    OrderType::Bid => &mut bids,
+                    OrderType::Ask => &mut asks
+                };
+
+            *side.entry(OrderedFloat::from(order.price)).or_insert(ZERO) +=
+                order.quantity;
+        }
+
+        let level = |(price, quantity): (&OrderedFloat<f64>, &Quantity)| ->
+            Level { (price.into_inner(), *quantity) };
+
+        Levels::new(bids.iter().rev().map(level).collect(),
+                    asks.iter().map(level).collect())
+    }
+}
+
+/* one step of a differential run */
+#[derive(Debug, Clone, PartialEq)]
+pub enum DiffOp {
+    Submit {
+        id: OrderId,
+        order_type: OrderType,
+        price: f64,
+        quantity: Quantity
+    },
+    Cancel(OrderId),
+    Modify {
+        id: OrderId,
+        price: f64,
+        quantity: Quantity
+    },
+    Reduce {
+        id: OrderId,
+        quantity: Quantity
+    }
+}
+
+/* where a `Book` and the reference first disagreed */
+#[derive(Debug, Clone, PartialEq)]
+pub struct Divergence {
+    index: usize,
+    op: DiffOp,
+    reason: String
+}
+
+impl Divergence {
+    pub fn get_index(&self) -> usize {
+        self.index
+    }
+
+    pub fn get_op(&self) -> &DiffOp {
+        &self.op
+    }
+
+    pub fn get_reason(&self) -> &str {
+        &self.reason
+    }
+}
+
+fn unlimited_order(id: OrderId, ticker: &str, order_type: OrderType,
+                   price: f64, quantity: Quantity) -> Order {
+    let mut holdings: HashMap<String, Quantity> = HashMap::new();
+    holdings.insert(ticker.to_string(), Quantity::from(u64::MAX));
+
+    let owner: Account = Account::new(id, "Reference".to_string(),
+                                      f64::MAX / 4.0, holdings);
+
+    Order::new(id, owner, ticker.to_string(), order_type, price, quantity)
+}
+
+fn fills(trades: &[Trade]) -> Vec<ReferenceFill> {
+    trades.iter()
+        .map(|trade| (trade.get_price(), trade.get_quantity(),
+                      trade.get_aggressor(), trade.get_resting()))
+        .collect()
+}
+
+/* Applies `ops` to a fresh `Book` and a fresh `ReferenceBook` alike,
+ * checking after every one that they accepted or refused it alike, made the
+ * same fills and were left with the same depth. Submissions should have
+ * distinct IDs, positive quantities and positive prices. */
+pub fn differential(ops: &[DiffOp]) -> Result<(), Divergence> {
+    let mut book: Book = Book::new(1, "Differential".to_string(),
+                                   "BOOK".to_string());
+    let mut reference: ReferenceBook = ReferenceBook::new();
+
+    for (index, op) in ops.iter().enumerate() {
+        let diverged = |reason: String| Divergence {
+            index,
+            op: op.clone(),
+            reason
+        };
+        let traded: usize = book.get_trades().len();
+        let expected: Option<Vec<ReferenceFill>> = match op {
+            DiffOp::Submit { id, order_type, price, quantity } => {
+                let order: Order = unlimited_order(*id, "BOOK",
+                                                   *order_type,
+                                                   *price, *quantity);
+
+                book.submit(order)
+                    .map_err(|e| diverged(format!("book refused: {}", e)))?;
+                Some(reference.submit(*id, *order_type, *price,
+                                      *quantity))
+            },
+            DiffOp::Cancel(id) => {
+                let (actual, expected) = (book.cancel(*id).is_ok(),
+                                          reference.cancel(*id));
+
+                if actual != expected {
+                    return Err(diverged(format!(
+                        "book cancelled: {}, reference cancelled: {}",
+                        actual, expected)));
+                }
+
+                None
+            },
+            DiffOp::Modify { id, price, quantity } => {
+                let actual: bool = book.modify(*id, *price, *quantity).is_ok();
+                let expected: Option<Vec<ReferenceFill>> =
+                    reference.modify(*id, *price, *quantity);
+
+                if actual != expected.is_some() {
+                    return Err(diverged(format!(
+                        "book modified: {}, reference modified: {}",
+                        actual, expected.is_some())));
+                }
+
+                expected
+            },
+            DiffOp::Reduce { id, quantity } => {
+                let (actual, expected) = (book.reduce(*id, *quantity).ok(),
+                                          reference.reduce(*id, *quantity));
+
+                if actual != expected {
+                    return Err(diverged(format!(
+                        "book left {:?}, reference left {:?}",
+                        actual, expected)));
+                }
+
+                None
+            }
+        };
+        let actual: Vec<ReferenceFill> =
+            fills(book.get_trades().get(traded..).unwrap_or_default());
+
+        if actual != expected.unwrap_or_default() {
+            return Err(diverged(format!("book filled {:?}", actual)));
+        }
+
+        let diff: BookDiff = compare_levels(&reference.levels(),
+                                            &book.levels());
+
+        if !diff.is_empty() {
+            return Err(diverged(format!("book differs from reference:\n{}",
+                                        diff)));
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(all(test, not(feature = "decimal-quantity")))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reference_matches() {
+        let mut actual_book: ReferenceBook = ReferenceBook::new();
+
+        actual_book.submit(1, OrderType::Ask, 12.00, 10);
+        actual_book.submit(2, OrderType::Ask, 11.00, 5);
+        actual_book.submit(3, OrderType::Ask, 11.00, 5);
+
+        assert_eq!(actual_book.submit(4, OrderType::Bid, 12.00, 12),
+                   vec![(11.00, 5, 4, 2), (11.00, 5, 4, 3),
+                        (12.00, 2, 4, 1)]);
+        assert_eq!(actual_book.levels(),
+                   Levels::new(vec![], vec![(12.00, 8)]));
+    }
+}
@@ -0,0 +1,228 @@
+use crate::levels::*;
+use crate::quantity::{self, Quantity};
+
+const BAR_WIDTH: usize = 20;
+const BAR_EIGHTHS: [char; 8] = ['▏', '▎', '▍', '▌', '▋', '▊', '▉', '█'];
+
+const GREEN: &str = "\x1b[32m";
+const RED: &str = "\x1b[31m";
+const RESET: &str = "\x1b[0m";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RenderOptions {
+    /* number of price levels shown on each side */
+    pub levels: usize,
+    /* show sizes accumulated outwards from the touch rather than per level */
+    pub cumulative: bool,
+    /* colour bids green and asks red using ANSI escapes */
+    pub color: bool,
+    /* draw a unicode bar proportional to each size */
+    pub bars: bool
+}
+
+impl Default for RenderOptions {
+    fn default() -> RenderOptions {
+        RenderOptions {
+            levels: 10,
+            cumulative: false,
+            color: false,
+            bars: false
+        }
+    }
+}
+
+/* bars for the bid side grow leftwards, and unicode only has right-aligned
+ * blocks for halves, so they are drawn at a coarser resolution */
+fn bar(size: f64, max: f64, leftwards: bool) -> String {
+    if max <= 0.0 || size <= 0.0 {
+        return String::new();
+    }
+
+    let eighths: usize =
+        ((size / max) * (BAR_WIDTH * 8) as f64).round().max(1.0) as usize;
+    let partial: usize = eighths % 8;
+    let full: String = "█".repeat(eighths / 8);
+
+    if leftwards {
+        match partial {
+            0 => full,
+            1..=3 if !full.is_empty() => full,
+            _ => format!("▐{}", full)
+        }
+    } else if partial == 0 {
+        full
+    } else {
+        format!("{}{}", full, BAR_EIGHTHS[partial - 1])
+    }
+}
+
+fn sizes(side: &[Level], options: &RenderOptions) -> Vec<(f64, Quantity)> {
+    let mut running: Quantity = quantity::ZERO;
+
+    side.iter()
+        .take(options.levels)
+        .map(|(price, size)| {
+            running += *size;
+
+            if options.cumulative {
+                (*price, running)
+            } else {
+                (*price, *size)
+            }
+        })
+        .collect()
+}
+
+/* how many decimal places `price` is shown with on the ladder */
+fn decimals(price: f64) -> usize {
+    price.to_string()
+        .split_once('.')
+        .map_or(0, |(_, fraction)| fraction.len())
+}
+
+/* `value` without the representation error that arithmetic on prices
+ * leaves, e.g. the difference between 12.2 and 12.1 */
+fn round_to(value: f64, decimals: usize) -> f64 {
+    let scale: f64 = 10f64.powi(decimals as i32);
+
+    (value * scale).round() / scale
+}
+
+/* Renders a depth-of-market ladder: asks above bids, highest price at the
+ * top, with a line marking the mid and the spread in between. */
+pub fn render(levels: &Levels, options: &RenderOptions) -> String {
+    let bids: Vec<(f64, Quantity)> = sizes(levels.get_bids(), options);
+    let asks: Vec<(f64, Quantity)> = sizes(levels.get_asks(), options);
+
+    let max: f64 = bids.iter()
+        .chain(asks.iter())
+        .map(|(_, size)| quantity::to_f64(*size))
+        .fold(0.0, f64::max);
+
+    let paint = |text: String, colour: &str| -> String {
+        if options.color && !text.trim().is_empty() {
+            format!("{}{}{}", colour, text, RESET)
+        } else {
+            text
+        }
+    };
+
+    let mut lines: Vec<String> = vec![
+        format!("{:>20} {:>12} | {:^12} | {:<12}", "", "BID", "PRICE", "ASK")
+    ];
+
+    for (price, size) in asks.iter().rev() {
+        let histogram: String = if options.bars {
+            bar(quantity::to_f64(*size), max, false)
+        } else {
+            String::new()
+        };
+
+        lines.push(format!("{:>20} {:>12} | {:^12} | {} {}", "", "", price,
+                           paint(format!("{:<12}", size), RED),
+                           paint(histogram, RED)));
+    }
+
+    /* the mid can be half way between two of the ladder's prices, so takes
+     * one more place than they do */
+    let precision: usize = bids.iter()
+        .chain(asks.iter())
+        .map(|(price, _)| decimals(*price))
+        .max()
+        .unwrap_or(0);
+    let spread: String = match (bids.first(), asks.first()) {
+        (Some((bid, _)), Some((ask, _))) =>
+            format!(" mid {} spread {} ",
+                    round_to((bid + ask) / 2.0, precision + 1),
+                    round_to(ask - bid, precision)),
+        _ => " no spread ".to_string()
+    };
+    lines.push(format!("{:-^66}", spread));
+
+    for (price, size) in bids.iter() {
+        let histogram: String = if options.bars {
+            bar(quantity::to_f64(*size), max, true)
+        } else {
+            String::new()
+        };
+
+        lines.push(format!("{} {} | {:^12} |",
+                           paint(format!("{:>20}", histogram), GREEN),
+                           paint(format!("{:>12}", size), GREEN),
+                           price));
+    }
+
+    lines.iter()
+        .map(|line| line.trim_end())
+        .collect::<Vec<&str>>()
+        .join("\n")
+}
+
+#[cfg(all(test, not(feature = "decimal-quantity")))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_plain() {
+        let levels: Levels = Levels::new(vec![(12.0, 15), (11.0, 10)],
+                                         vec![(13.0, 7), (14.0, 8)]);
+
+        let actual_render: String = render(&levels, &RenderOptions::default());
+        let expected_render: String = [
+            "                              BID |    PRICE     | ASK",
+            "                                  |      14      | 8",
+            "                                  |      13      | 7",
+            "----------------------- mid 12.5 spread 1 ------------------------",
+            "                               15 |      12      |",
+            "                               10 |      11      |",
+        ].join("\n");
+
+        assert_eq!(actual_render, expected_render);
+    }
+
+    #[test]
+    fn test_render_fractional_spread() {
+        let levels: Levels = Levels::new(vec![(12.10, 15)],
+                                         vec![(12.20, 7)]);
+
+        let actual_render: String = render(&levels, &RenderOptions::default());
+        let actual_rule: &str = actual_render.lines().nth(2).unwrap();
+        let expected_rule: &str =
+            "---------------------- mid 12.15 spread 0.1 ----------------------";
+
+        assert_eq!(actual_rule, expected_rule);
+        assert_eq!(actual_rule.chars().count(), 66);
+    }
+
+    #[test]
+    fn test_render_cumulative_with_bars() {
+        let levels: Levels = Levels::new(vec![(12.0, 10), (11.0, 10)],
+                                         vec![(13.0, 5)]);
+        let options: RenderOptions = RenderOptions {
+            levels: 10,
+            cumulative: true,
+            color: false,
+            bars: true
+        };
+
+        let actual_render: String = render(&levels, &options);
+
+        assert!(actual_render.contains(&"█".repeat(BAR_WIDTH)));
+        assert!(actual_render.contains(&format!("{:>12} |", 20)));
+    }
+
+    #[test]
+    fn test_render_truncates_levels() {
+        let levels: Levels = Levels::new(vec![(12.0, 1), (11.0, 1)],
+                                         vec![(13.0, 1), (14.0, 1)]);
+        let options: RenderOptions = RenderOptions {
+            levels: 1,
+            ..RenderOptions::default()
+        };
+
+        let actual_render: String = render(&levels, &options);
+
+        assert!(!actual_render.contains("14"));
+        assert!(!actual_render.contains("11"));
+    }
+}
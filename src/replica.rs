@@ -0,0 +1,455 @@
+use std::collections::{BTreeMap, HashMap};
+
+use ordered_float::OrderedFloat;
+
+use crate::event::{Event, EventKind};
+use crate::levels::{Level, LevelDelta, Levels};
+use crate::order::*;
+use crate::quantity::{self, Quantity, ZERO};
+use crate::snapshot::BookSnapshot;
+
+#[derive(Debug, Clone, PartialEq, thiserror::Error)]
+pub enum ReplicaError {
+    /* an event was missed; the replica needs a fresh snapshot */
+    #[error("expected event {expected} but got {found}")]
+    Gap {
+        expected: u64,
+        found: u64
+    }
+}
+
+/* A client's copy of a book's depth, seeded from a snapshot and kept up to
+ * date with the events that follow it (see `Book::delta_since`), so that a
+ * publisher need only send full snapshots to clients that fall behind.
+ * Resting orders are tracked individually, as an auction's trades print at
+ * the clearing price rather than at the prices the orders rested at, and
+ * aggregated by level as they change. Hidden orders are left out, as they
+ * are from `Book::levels`. */
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct BookReplica {
+    orders: HashMap<OrderId, (OrderType, f64, Quantity)>,
+    bids: BTreeMap<OrderedFloat<f64>, Quantity>,
+    asks: BTreeMap<OrderedFloat<f64>, Quantity>,
+    last_seq: u64
+}
+
+impl BookReplica {
+    pub fn from_snapshot(snapshot: &BookSnapshot) -> BookReplica {
+        let mut replica: BookReplica = BookReplica {
+            last_seq: snapshot.last_seq,
+            ..BookReplica::default()
+        };
+
+        for order in snapshot.bids.iter().chain(snapshot.asks.iter())
+            .filter(|order| !order.is_hidden()) {
+            replica.place(order.get_id(), order.get_order_type(),
+                          order.get_price(), order.get_quantity());
+        }
+
+        replica
+    }
+
+    /* the sequence number of the last event applied */
+    pub fn get_last_seq(&self) -> u64 {
+        self.last_seq
+    }
+
+    /* Applies events in sequence order. Any the replica has already seen
+     * are skipped, so deltas may overlap; a missing one is an error, and
+     * leaves the replica as it was after the event before it. */
+    pub fn apply_delta(&mut self, events: &[Event]) ->
+        Result<(), ReplicaError> {
+        self.step(events, None)
+    }
+
+    /* Applies events as `apply_delta` does, giving how each changed the
+     * aggregated depth: a `LevelDelta` for every level an event left with a
+     * different quantity, in the order of the events. */
+    pub fn level_deltas(&mut self, events: &[Event]) ->
+        Result<Vec<LevelDelta>, ReplicaError> {
+        let mut deltas: Vec<LevelDelta> = vec![];
+
+        self.step(events, Some(&mut deltas)).map(|_| deltas)
+    }
+
+    fn step(&mut self, events: &[Event],
+            mut deltas: Option<&mut Vec<LevelDelta>>) ->
+        Result<(), ReplicaError> {
+        for event in events {
+            if event.get_seq() <= self.last_seq {
+                continue;
+            }
+
+            if event.get_seq() != self.last_seq + 1 {
+                return Err(ReplicaError::Gap {
+                    expected: self.last_seq + 1,
+                    found: event.get_seq()
+                });
+            }
+
+            match deltas.as_deref_mut() {
+                Some(deltas) => {
+                    let before: Vec<(OrderType, f64, Quantity)> =
+                        self.touches(event.get_kind()).into_iter()
+                            .map(|(side, price)|
+                                 (side, price, self.level(&side, price)))
+                            .collect();
+
+                    self.apply(event.get_kind());
+                    deltas.extend(before.into_iter()
+                        .map(|(side, price, old_quantity)| LevelDelta {
+                            seq: event.get_seq(),
+                            timestamp: event.get_timestamp(),
+                            side,
+                            price,
+                            old_quantity,
+                            new_quantity: self.level(&side, price)
+                        })
+                        .filter(|delta|
+                                delta.old_quantity != delta.new_quantity));
+                },
+                None => self.apply(event.get_kind())
+            }
+
+            self.last_seq = event.get_seq();
+        }
+
+        Ok(())
+    }
+
+    /* the levels an event may change, each once */
+    fn touches(&self, kind: &EventKind) -> Vec<(OrderType, f64)> {
+        let mut levels: Vec<(OrderType, f64)> = vec![];
+        let mut touch = |level: (OrderType, f64)| {
+            if !levels.contains(&level) {
+                levels.push(level);
+            }
+        };
+
+        match kind {
+            EventKind::Post { order, order_type, price, .. } |
+            EventKind::Amend { order, order_type, price, .. } |
+            EventKind::Reprice { order, order_type, price, .. } |
+            EventKind::Cancel { order, order_type, price, .. } |
+            EventKind::Reduce { order, order_type, price, .. } => {
+                if let Some((side, at, _)) = self.orders.get(order) {
+                    touch((*side, *at));
+                }
+
+                touch((*order_type, *price));
+            },
+            EventKind::Match(trade) => {
+                for id in [trade.get_resting(), trade.get_aggressor()] {
+                    if let Some((side, at, _)) = self.orders.get(&id) {
+                        touch((*side, *at));
+                    }
+                }
+            },
+            _ => {}
+        }
+
+        levels
+    }
+
+    fn apply(&mut self, kind: &EventKind) {
+        match kind {
+            /* never known, so nothing that happens to them shows either */
+            EventKind::Post { hidden: true, .. } |
+            EventKind::Amend { hidden: true, .. } |
+            EventKind::Reprice { hidden: true, .. } => {},
+            /* an amendment that goes on to match is followed by its
+             * trades, then by a post of what is left */
+            EventKind::Post { order, order_type, price, quantity, .. } |
+            EventKind::Amend { order, order_type, price, quantity, .. } |
+            EventKind::Reprice { order, order_type, price, quantity, .. } => {
+                self.place(*order, *order_type, *price, *quantity);
+            },
+            EventKind::Cancel { order, .. } => {
+                self.remove(*order);
+            },
+            EventKind::Reduce { order, quantity, .. } => {
+                self.fill(*order, *quantity);
+            },
+            /* in continuous trading the aggressor has not rested yet, so
+             * only the resting order is known; in an auction both are */
+            EventKind::Match(trade) => {
+                for id in [trade.get_resting(), trade.get_aggressor()] {
+                    self.fill(id, trade.get_quantity());
+                }
+            },
+            _ => {}
+        }
+    }
+
+    fn side_mut(&mut self, side: &OrderType) ->
+        &mut BTreeMap<OrderedFloat<f64>, Quantity> {
+        match side {
+            OrderType::Bid => &mut self.bids,
+            OrderType::Ask => &mut self.asks
+        }
+    }
+
+    /* takes `quantity` off the level, forgetting it once it is empty */
+    fn take(&mut self, side: &OrderType, price: f64, quantity: Quantity) {
+        let side: &mut BTreeMap<OrderedFloat<f64>, Quantity> =
+            self.side_mut(side);
+        let key: OrderedFloat<f64> = OrderedFloat::from(price);
+
+        if let Some(level) = side.get_mut(&key) {
+            *level = quantity::saturating_sub(*level, quantity);
+
+            if *level == ZERO {
+                side.remove(&key);
+            }
+        }
+    }
+
+    fn place(&mut self, id: OrderId, side: OrderType, price: f64,
+             quantity: Quantity) {
+        self.remove(id);
+
+        if quantity == ZERO {
+            return;
+        }
+
+        self.orders.insert(id, (side, price, quantity));
+        *self.side_mut(&side).entry(OrderedFloat::from(price))
+            .or_insert(ZERO) += quantity;
+    }
+
+    fn remove(&mut self, id: OrderId) {
+        if let Some((side, price, quantity)) = self.orders.remove(&id) {
+            self.take(&side, price, quantity);
+        }
+    }
+
+    fn fill(&mut self, id: OrderId, quantity: Quantity) {
+        let (side, price, filled) = match self.orders.get_mut(&id) {
+            Some((side, price, remaining)) => {
+                let filled: Quantity = if quantity < *remaining {
+                    quantity
+                } else {
+                    *remaining
+                };
+
+                *remaining = quantity::saturating_sub(*remaining, quantity);
+                (*side, *price, filled)
+            },
+            None => return
+        };
+
+        if self.orders.get(&id).map(|(_, _, remaining)| *remaining) ==
+            Some(ZERO) {
+            self.orders.remove(&id);
+        }
+
+        self.take(&side, price, filled);
+    }
+
+    /* a resting order's side, price and remaining quantity */
+    pub fn get_order(&self, id: OrderId) ->
+        Option<(OrderType, f64, Quantity)> {
+        self.orders.get(&id).cloned()
+    }
+
+    /* the orders resting on `side` at `price`, in no particular order */
+    pub fn orders_at(&self, side: &OrderType, price: f64) ->
+        Vec<(OrderId, Quantity)> {
+        self.orders.iter()
+            .filter(|(_, (order_type, order_price, _))| {
+                order_type == side && *order_price == price
+            })
+            .map(|(id, (_, _, quantity))| (*id, *quantity))
+            .collect()
+    }
+
+    /* the total quantity resting on `side` at `price` */
+    pub fn level(&self, side: &OrderType, price: f64) -> Quantity {
+        let levels: &BTreeMap<OrderedFloat<f64>, Quantity> = match side {
+            OrderType::Bid => &self.bids,
+            OrderType::Ask => &self.asks
+        };
+
+        levels.get(&OrderedFloat::from(price)).copied().unwrap_or(ZERO)
+    }
+
+    /* aggregated depth, as `Book::levels` would report it */
+    pub fn levels(&self) -> Levels {
+        let level = |(price, quantity): (&OrderedFloat<f64>, &Quantity)| ->
+            Level { (price.into_inner(), *quantity) };
+
+        Levels::new(self.bids.iter().rev().map(level).collect(),
+                    self.asks.iter().map(level).collect())
+    }
+}
+
+#[cfg(all(test, not(feature = "decimal-quantity")))]
+mod tests {
+    use super::*;
+    use crate::account::Account;
+    use crate::book::{Book, BookError};
+    use crate::session::SessionState;
+
+    fn build_order(id: OrderId, order_type: OrderType, price: f64,
+                   quantity: Quantity) -> Order {
+        let mut holdings: HashMap<String, Quantity> = HashMap::new();
+        holdings.insert("BOOK".to_string(), 1000);
+
+        let owner: Account = Account::new(id, "Account".to_string(),
+                                          12000.00, holdings);
+
+        Order::new(id, owner, "BOOK".to_string(), order_type, price, quantity)
+    }
+
+    #[test]
+    fn test_snapshot_and_deltas() -> Result<(), BookError> {
+        let mut book: Book = Book::new(1, "Book".to_string(),
+                                       "BOOK".to_string());
+
+        book.submit(build_order(1, OrderType::Bid, 11.00, 10))?;
+        book.submit(build_order(2, OrderType::Ask, 12.00, 10))?;
+
+        let mut actual_replica: BookReplica =
+            BookReplica::from_snapshot(&book.snapshot());
+        let seq: u64 = actual_replica.get_last_seq();
+
+        book.submit(build_order(3, OrderType::Ask, 12.00, 5))?;
+        book.submit(build_order(4, OrderType::Bid, 12.50, 12))?;
+        book.submit(build_order(5, OrderType::Bid, 11.00, 7))?;
+        book.cancel(1)?;
+        book.modify(5, 11.00, 3)?;
+        book.submit(build_order(6, OrderType::Ask, 10.00, 20))?;
+        book.submit(build_order(7, OrderType::Bid, 9.00, 10))?;
+        book.modify(7, 10.00, 30)?;
+
+        actual_replica.apply_delta(&book.delta_since(seq)?).unwrap();
+
+        let expected_replica: BookReplica =
+            BookReplica::from_snapshot(&book.snapshot());
+
+        assert_eq!(actual_replica, expected_replica);
+        assert_eq!(actual_replica.levels(), book.levels());
+
+        /* deltas may overlap what has already been applied */
+        actual_replica.apply_delta(&book.delta_since(seq)?).unwrap();
+        assert_eq!(actual_replica, expected_replica);
+        Ok(())
+    }
+
+    #[test]
+    fn test_deltas_across_auction() -> Result<(), BookError> {
+        let mut book: Book = Book::builder(1, "BOOK".to_string())
+            .initial_state(SessionState::PreOpen)
+            .build()
+            .unwrap();
+        let mut actual_replica: BookReplica =
+            BookReplica::from_snapshot(&book.snapshot());
+
+        book.submit(build_order(1, OrderType::Bid, 12.50, 10))?;
+        book.submit(build_order(2, OrderType::Bid, 12.00, 10))?;
+        book.submit(build_order(3, OrderType::Ask, 11.50, 15))?;
+        book.set_state(SessionState::Continuous)?;
+
+        actual_replica.apply_delta(&book.delta_since(0)?).unwrap();
+
+        assert_eq!(actual_replica.levels(), book.levels());
+        assert_eq!(actual_replica.get_last_seq(), book.last_seq());
+        Ok(())
+    }
+
+    #[test]
+    fn test_level_deltas() -> Result<(), BookError> {
+        let mut book: Book = Book::new(1, "Book".to_string(),
+                                       "BOOK".to_string());
+
+        book.submit(build_order(1, OrderType::Ask, 12.00, 10))?;
+
+        let mut replica: BookReplica =
+            BookReplica::from_snapshot(&book.snapshot());
+        let seq: u64 = replica.get_last_seq();
+
+        book.submit(build_order(2, OrderType::Ask, 12.00, 5))?;
+        book.submit(build_order(3, OrderType::Bid, 12.00, 12))?;
+        book.modify(2, 12.50, 3)?;
+        book.cancel(2)?;
+
+        let deltas: Vec<(OrderType, f64, Quantity, Quantity)> = replica
+            .level_deltas(&book.delta_since(seq)?)
+            .unwrap()
+            .iter()
+            .map(|delta| (delta.side, delta.price, delta.old_quantity,
+                          delta.new_quantity))
+            .collect();
+
+        assert_eq!(deltas, vec![
+            (OrderType::Ask, 12.00, 10, 15),
+            /* the sweep takes the level down an order at a time */
+            (OrderType::Ask, 12.00, 15, 5),
+            (OrderType::Ask, 12.00, 5, 3),
+            /* the amendment moves what is left to a new level */
+            (OrderType::Ask, 12.00, 3, 0),
+            (OrderType::Ask, 12.50, 0, 3),
+            (OrderType::Ask, 12.50, 3, 0)
+        ]);
+        assert_eq!(replica.levels(), book.levels());
+        Ok(())
+    }
+
+    #[test]
+    fn test_hidden_orders() -> Result<(), BookError> {
+        let mut book: Book = Book::new(1, "Book".to_string(),
+                                       "BOOK".to_string());
+        let mut actual_replica: BookReplica =
+            BookReplica::from_snapshot(&book.snapshot());
+        let mut hidden: Order = build_order(1, OrderType::Ask, 12.00, 10);
+
+        hidden.set_hidden();
+        book.submit(hidden)?;
+
+        let deltas: Vec<LevelDelta> = actual_replica
+            .level_deltas(&book.delta_since(0)?)
+            .unwrap();
+
+        assert!(book.levels().get_asks().is_empty());
+        assert_eq!(actual_replica.levels(), book.levels());
+        assert!(deltas.is_empty());
+
+        /* the displayed order trades first, then the hidden one */
+        book.submit(build_order(2, OrderType::Ask, 12.00, 5))?;
+        book.submit(build_order(3, OrderType::Bid, 12.00, 8))?;
+        book.modify(1, 12.50, 7)?;
+        actual_replica.apply_delta(&book.delta_since(0)?).unwrap();
+        assert_eq!(actual_replica.levels(), book.levels());
+        assert_eq!(BookReplica::from_snapshot(&book.snapshot()),
+                   actual_replica);
+
+        book.cancel(1)?;
+        actual_replica.apply_delta(&book.delta_since(0)?).unwrap();
+        assert_eq!(actual_replica.levels(), book.levels());
+        Ok(())
+    }
+
+    #[test]
+    fn test_gaps_and_evictions() -> Result<(), BookError> {
+        let mut book: Book = Book::builder(1, "BOOK".to_string())
+            .event_capacity(2)
+            .build()
+            .unwrap();
+        let mut actual_replica: BookReplica =
+            BookReplica::from_snapshot(&book.snapshot());
+
+        for id in 1..=4 {
+            book.submit(build_order(id, OrderType::Bid, 11.00, 10))?;
+        }
+
+        assert!(matches!(book.delta_since(0),
+                         Err(BookError::EventsEvicted)));
+        assert!(book.delta_since(book.last_seq())?.is_empty());
+        assert_eq!(actual_replica.apply_delta(book.get_events()),
+                   Err(ReplicaError::Gap {
+                       expected: 1,
+                       found: book.last_seq() - 1
+                   }));
+        Ok(())
+    }
+}
@@ -0,0 +1,294 @@
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post, delete};
+use axum::{Json, Router};
+
+use crate::analytics::BookStats;
+use crate::book::BookError;
+use crate::clock::Clock;
+use crate::consolidated::VenueTrade;
+use crate::exchange::{Cancelled, Exchange, ExchangeError, OrderRequest,
+                      Placed};
+use crate::id::{MonotonicIdGenerator, OrderIdGenerator};
+use crate::levels::Levels;
+use crate::matching::MatchingPolicy;
+use crate::order::OrderId;
+use crate::price::PriceType;
+use crate::router::RouterError;
+use crate::sink::EventSink;
+
+type Shared<M, S, C, P, G> = Arc<Mutex<Exchange<M, S, C, P, G>>>;
+type Locked<'a, M, S, C, P, G> = MutexGuard<'a, Exchange<M, S, C, P, G>>;
+
+/* a request that failed, as its status and a JSON body saying why */
+#[derive(Debug, Clone, PartialEq)]
+pub struct Failure {
+    status: StatusCode,
+    message: String
+}
+
+impl Failure {
+    fn new(status: StatusCode, message: String) -> Failure {
+        Failure {status, message}
+    }
+
+    pub fn get_status(&self) -> StatusCode {
+        self.status
+    }
+
+    pub fn get_message(&self) -> &str {
+        &self.message
+    }
+}
+
+impl From<ExchangeError> for Failure {
+    fn from(error: ExchangeError) -> Failure {
+        let status: StatusCode = match &error {
+            ExchangeError::UnknownAccount(_) |
+            ExchangeError::OrderNotFound(_) |
+            ExchangeError::Router(RouterError::UnknownSymbol(_)) |
+            ExchangeError::Router(RouterError::BookNotFound(_)) |
+            ExchangeError::Router(RouterError::Book(
+                BookError::OrderNotFound)) => StatusCode::NOT_FOUND,
+            ExchangeError::IdempotencyConflict(_) |
+            ExchangeError::Router(RouterError::Book(
+                BookError::DuplicateOrderId |
+                BookError::DuplicateClientId(_))) => StatusCode::CONFLICT,
+            ExchangeError::Router(RouterError::Book(
+                BookError::InvalidPrice | BookError::InvalidQuantity)) =>
+                StatusCode::BAD_REQUEST,
+            ExchangeError::Router(RouterError::Book(
+                BookError::MarketHalted | BookError::MarketClosed |
+                BookError::Expired | BookError::Account(_) |
+                BookError::InsufficientBuyingPower { .. })) =>
+                StatusCode::UNPROCESSABLE_ENTITY,
+            _ => StatusCode::INTERNAL_SERVER_ERROR
+        };
+
+        Failure::new(status, error.to_string())
+    }
+}
+
+impl IntoResponse for Failure {
+    fn into_response(self) -> Response {
+        (self.status, Json(serde_json::json!({ "error": self.message })))
+            .into_response()
+    }
+}
+
+/* Serves an `Exchange` over HTTP, as JSON in the shape of the crate's own
+ * serde types:
+ *
+ *     POST   /orders                 an `OrderRequest`, giving `Placed`
+ *     DELETE /orders/{id}            giving a `Cancelled` for each book
+ *     GET    /books/{ticker}/levels  the merged `Levels` of its books
+ *     GET    /books/{ticker}/trades  its `VenueTrade`s, oldest first
+ *     GET    /books/{ticker}/stats   a `BookStats` for each of its books
+ *
+ * Failures come back as `{"error": "..."}` with a status to match. */
+#[derive(Debug)]
+pub struct RestService<M, S, C, P, G = MonotonicIdGenerator>
+where M: MatchingPolicy, S: EventSink, C: Clock, P: PriceType,
+      G: OrderIdGenerator {
+    exchange: Shared<M, S, C, P, G>
+}
+
+impl<M, S, C, P, G> Clone for RestService<M, S, C, P, G>
+where M: MatchingPolicy, S: EventSink, C: Clock, P: PriceType,
+      G: OrderIdGenerator {
+    fn clone(&self) -> RestService<M, S, C, P, G> {
+        RestService {
+            exchange: Arc::clone(&self.exchange)
+        }
+    }
+}
+
+impl<M, S, C, P, G> RestService<M, S, C, P, G>
+where
+    M: MatchingPolicy + Send + 'static,
+    S: EventSink + Send + 'static,
+    C: Clock + Send + 'static,
+    P: PriceType + Send + 'static,
+    G: OrderIdGenerator + Send + 'static
+{
+    pub fn new(exchange: Exchange<M, S, C, P, G>) ->
+        RestService<M, S, C, P, G> {
+        RestService {
+            exchange: Arc::new(Mutex::new(exchange))
+        }
+    }
+
+    /* the exchange being served, e.g. for adding accounts while it is */
+    pub fn get_exchange(&self) -> Shared<M, S, C, P, G> {
+        Arc::clone(&self.exchange)
+    }
+
+    pub fn into_router(self) -> Router {
+        Router::new()
+            .route("/orders", post(submit::<M, S, C, P, G>))
+            .route("/orders/:id", delete(cancel::<M, S, C, P, G>))
+            .route("/books/:ticker/levels", get(levels::<M, S, C, P, G>))
+            .route("/books/:ticker/trades", get(trades::<M, S, C, P, G>))
+            .route("/books/:ticker/stats", get(stats::<M, S, C, P, G>))
+            .with_state(self.exchange)
+    }
+
+    /* serves on `address` until the server fails */
+    pub async fn serve(self, address: SocketAddr) -> std::io::Result<()> {
+        let listener: tokio::net::TcpListener =
+            tokio::net::TcpListener::bind(address).await?;
+
+        axum::serve(listener, self.into_router()).await
+    }
+}
+
+fn lock<M, S, C, P, G>(exchange: &Shared<M, S, C, P, G>) ->
+    Result<Locked<'_, M, S, C, P, G>, Failure>
+where M: MatchingPolicy, S: EventSink, C: Clock, P: PriceType,
+      G: OrderIdGenerator {
+    exchange.lock()
+        .map_err(|_| Failure::new(StatusCode::INTERNAL_SERVER_ERROR,
+                                  "exchange lock poisoned".to_string()))
+}
+
+async fn submit<M, S, C, P, G>(
+    State(exchange): State<Shared<M, S, C, P, G>>,
+    Json(request): Json<OrderRequest>) ->
+    Result<(StatusCode, Json<Placed>), Failure>
+where M: MatchingPolicy, S: EventSink, C: Clock, P: PriceType,
+      G: OrderIdGenerator {
+    let placed: Placed = lock(&exchange)?.submit(request)?;
+
+    Ok((StatusCode::CREATED, Json(placed)))
+}
+
+async fn cancel<M, S, C, P, G>(
+    State(exchange): State<Shared<M, S, C, P, G>>,
+    Path(id): Path<String>) ->
+    Result<Json<Vec<Cancelled>>, Failure>
+where M: MatchingPolicy, S: EventSink, C: Clock, P: PriceType,
+      G: OrderIdGenerator {
+    let id: OrderId = id.parse()
+        .map_err(|_| Failure::new(StatusCode::BAD_REQUEST,
+                                  format!("invalid order ID: {:?}", id)))?;
+
+    Ok(Json(lock(&exchange)?.cancel(id)?))
+}
+
+async fn levels<M, S, C, P, G>(
+    State(exchange): State<Shared<M, S, C, P, G>>,
+    Path(ticker): Path<String>) ->
+    Result<Json<Levels>, Failure>
+where M: MatchingPolicy, S: EventSink, C: Clock, P: PriceType,
+      G: OrderIdGenerator {
+    Ok(Json(lock(&exchange)?.levels(&ticker)?))
+}
+
+async fn trades<M, S, C, P, G>(
+    State(exchange): State<Shared<M, S, C, P, G>>,
+    Path(ticker): Path<String>) ->
+    Result<Json<Vec<VenueTrade>>, Failure>
+where M: MatchingPolicy, S: EventSink, C: Clock, P: PriceType,
+      G: OrderIdGenerator {
+    Ok(Json(lock(&exchange)?.trades(&ticker)?))
+}
+
+async fn stats<M, S, C, P, G>(
+    State(exchange): State<Shared<M, S, C, P, G>>,
+    Path(ticker): Path<String>) ->
+    Result<Json<Vec<BookStats>>, Failure>
+where M: MatchingPolicy, S: EventSink, C: Clock, P: PriceType,
+      G: OrderIdGenerator {
+    Ok(Json(lock(&exchange)?.stats(&ticker)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::{BTreeMap, HashMap};
+    use crate::account::Account;
+    use crate::book::Book;
+    use crate::matching::PriceTime;
+    use crate::order::OrderType;
+    use crate::price::F64Price;
+    use crate::quantity::Quantity;
+    use crate::router::{OrderRouter, RoutingStrategy};
+    use crate::sink::MemorySink;
+    use crate::clock::SystemClock;
+
+    type Service = RestService<PriceTime, MemorySink, SystemClock, F64Price>;
+
+    fn service() -> Result<Service, RouterError> {
+        let mut router: OrderRouter = OrderRouter::new(
+            RoutingStrategy::Primary);
+        let mut holdings: HashMap<String, Quantity> = HashMap::new();
+
+        router.add_book(Book::new(1, "Book".to_string(),
+                                  "BOOK".to_string()))?;
+        holdings.insert("BOOK".to_string(), 100);
+
+        let mut exchange: Exchange = Exchange::new(router);
+        exchange.add_account(Account::new(1, "Trader".to_string(), 10_000.0,
+                                          holdings));
+        Ok(RestService::new(exchange))
+    }
+
+    fn order(side: OrderType, price: f64, quantity: Quantity) ->
+        Json<OrderRequest> {
+        Json(OrderRequest {
+            ticker: "BOOK".to_string(),
+            account: 1,
+            side,
+            price,
+            quantity,
+            client_id: None,
+            tags: BTreeMap::new(),
+            idempotency_key: None
+        })
+    }
+
+    #[tokio::test]
+    async fn test_endpoints() -> Result<(), Failure> {
+        let service: Service = service().map_err(ExchangeError::from)?;
+        let state = || State(service.get_exchange());
+        let ticker = |ticker: &str| Path(ticker.to_string());
+
+        let (status, Json(ask)) =
+            submit(state(), order(OrderType::Ask, 10.0, 5)).await?;
+        let (_, Json(bid)) =
+            submit(state(), order(OrderType::Bid, 10.0, 2)).await?;
+
+        assert_eq!(status, StatusCode::CREATED);
+        assert_eq!(bid.trades.len(), 1);
+
+        let Json(depth) = levels(state(), ticker("BOOK")).await?;
+        let Json(tape) = trades(state(), ticker("BOOK")).await?;
+        let Json(books) = stats(state(), ticker("BOOK")).await?;
+
+        assert_eq!(depth.get_asks(), &[(10.0, 3)]);
+        assert_eq!(tape.len(), 1);
+        assert_eq!(books[0].last_price, Some(10.0));
+
+        let Json(cancelled) =
+            cancel(state(), Path(ask.id.to_string())).await?;
+
+        assert_eq!(cancelled[0].result.remaining_cancelled, 3);
+        assert_eq!(cancel(state(), Path(ask.id.to_string())).await
+                       .map(|_| ()).map_err(|e| e.get_status()),
+                   Err(StatusCode::NOT_FOUND));
+        assert_eq!(cancel(state(), Path("one".to_string())).await
+                       .map(|_| ()).map_err(|e| e.get_status()),
+                   Err(StatusCode::BAD_REQUEST));
+        assert_eq!(levels(state(), ticker("NONE")).await
+                       .map(|_| ()).map_err(|e| e.get_status()),
+                   Err(StatusCode::NOT_FOUND));
+
+        /* and the whole thing assembles into a router */
+        let _: Router = service.into_router();
+        Ok(())
+    }
+}
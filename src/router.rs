@@ -0,0 +1,445 @@
+use std::cmp::Ordering;
+use std::collections::HashMap;
+
+use serde::{Serialize, Deserialize};
+
+use crate::book::{Book, BookError, BookId};
+use crate::clock::{Clock, SystemClock};
+use crate::consolidated::ConsolidatedBook;
+use crate::event::EventKind;
+use crate::iter::SideIter;
+use crate::levels::{Level, TopOfBook};
+use crate::matching::{MatchingPolicy, PriceTime};
+use crate::order::*;
+use crate::price::{F64Price, PriceType};
+use crate::quantity::{Quantity, ZERO};
+use crate::sink::{EventSink, MemorySink};
+
+#[derive(Debug, thiserror::Error)]
+pub enum RouterError {
+    #[error("no books are listed for {0}")]
+    UnknownSymbol(String),
+    #[error("book {0} has already been added")]
+    DuplicateBook(BookId),
+    #[error("no such book {0}")]
+    BookNotFound(BookId),
+    #[error("book error: {0}")]
+    Book(#[from] BookError)
+}
+
+/* How an order is shared out when its instrument trades on more than one
+ * book. Whatever a book cannot execute on arrival rests there as usual. */
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash, Serialize,
+         Deserialize)]
+pub enum RoutingStrategy {
+    /* all of it to the instrument's first book */
+    Primary,
+    /* all of it to the book with the best opposite price, if any crosses,
+     * and to the first book otherwise */
+    #[default]
+    BestPrice,
+    /* across every book, best prices first; the remainder goes wherever
+     * the best price was */
+    Split
+}
+
+/* how much of an order went to which book */
+pub type Allocation = (BookId, Quantity);
+
+/* Holds the books for any number of instruments and routes each order to
+ * the ones listed under its ticker. Every piece of an order a book receives
+ * is preceded in that book's event stream by a `Route` event. */
+#[derive(Debug)]
+pub struct OrderRouter<M: MatchingPolicy = PriceTime,
+                       S: EventSink = MemorySink,
+                       C: Clock = SystemClock,
+                       P: PriceType = F64Price> {
+    books: HashMap<BookId, Book<M, S, C, P>>,
+    /* each ticker's books, in the order they were added */
+    venues: HashMap<String, Vec<BookId>>,
+    strategy: RoutingStrategy
+}
+
+impl<M: MatchingPolicy, S: EventSink, C: Clock, P: PriceType>
+    OrderRouter<M, S, C, P> {
+    pub fn new(strategy: RoutingStrategy) -> OrderRouter<M, S, C, P> {
+        OrderRouter {
+            books: HashMap::new(),
+            venues: HashMap::new(),
+            strategy
+        }
+    }
+
+    pub fn get_strategy(&self) -> RoutingStrategy {
+        self.strategy
+    }
+
+    pub fn set_strategy(&mut self, strategy: RoutingStrategy) {
+        self.strategy = strategy;
+    }
+
+    /* lists `book` under its ticker, after any books already there */
+    pub fn add_book(&mut self, book: Book<M, S, C, P>) ->
+        Result<(), RouterError> {
+        let id: BookId = book.get_id();
+
+        if self.books.contains_key(&id) {
+            return Err(RouterError::DuplicateBook(id));
+        }
+
+        self.venues.entry(book.get_ticker()).or_default().push(id);
+        self.books.insert(id, book);
+        Ok(())
+    }
+
+    pub fn remove_book(&mut self, id: BookId) ->
+        Result<Book<M, S, C, P>, RouterError> {
+        let book: Book<M, S, C, P> = self.books.remove(&id)
+            .ok_or(RouterError::BookNotFound(id))?;
+        let ticker: String = book.get_ticker();
+
+        if let Some(venues) = self.venues.get_mut(&ticker) {
+            venues.retain(|venue| *venue != id);
+
+            if venues.is_empty() {
+                self.venues.remove(&ticker);
+            }
+        }
+
+        Ok(book)
+    }
+
+    pub fn get_book(&self, id: BookId) ->
+        Result<&Book<M, S, C, P>, RouterError> {
+        self.books.get(&id).ok_or(RouterError::BookNotFound(id))
+    }
+
+    pub fn get_book_mut(&mut self, id: BookId) ->
+        Result<&mut Book<M, S, C, P>, RouterError> {
+        self.books.get_mut(&id).ok_or(RouterError::BookNotFound(id))
+    }
+
+    /* the books listed under `ticker`, first added first */
+    pub fn get_books(&self, ticker: &str) -> Vec<&Book<M, S, C, P>> {
+        self.venues.get(ticker)
+            .map(|venues| venues.iter()
+                 .filter_map(|id| self.books.get(id))
+                 .collect())
+            .unwrap_or_default()
+    }
+
+    /* every book, whatever its ticker, in no particular order */
+    pub fn iter_books(&self) -> impl Iterator<Item=&Book<M, S, C, P>> {
+        self.books.values()
+    }
+
+    /* the depth and trades of every book listed under `ticker`, merged */
+    pub fn consolidated(&self, ticker: &str) -> ConsolidatedBook {
+        let mut consolidated: ConsolidatedBook =
+            ConsolidatedBook::new(ticker.to_string());
+
+        for book in self.get_books(ticker) {
+            /* listed once each, under their own ticker */
+            let _ = consolidated.add_book(book);
+        }
+
+        consolidated
+    }
+
+    pub fn has_symbol(&self, ticker: &str) -> bool {
+        self.venues.contains_key(ticker)
+    }
+
+    /* Where the last order given `client_id` under `ticker` went: each
+     * book that accepted a share of it, first added first, and the ID it
+     * was accepted under there. See `Order::set_client_id`. */
+    pub fn find_by_client_id(&self, ticker: &str, client_id: &str) ->
+        Vec<(BookId, OrderId)> {
+        self.get_books(ticker).into_iter()
+            .filter_map(|book| book.find_by_client_id(client_id)
+                        .map(|id| (book.get_id(), id)))
+            .collect()
+    }
+
+    /* Submits `order` to the books listed under its ticker, according to
+     * the routing strategy, and returns where it went. Each book receives a
+     * copy of the order, with the same ID, for its share of the quantity.
+     * If a book rejects its share, routing stops there: shares already
+     * submitted stand; see `route_partial`. */
+    pub fn route(&mut self, order: Order) ->
+        Result<Vec<Allocation>, RouterError> {
+        let (allocations, routed) = self.route_partial(order);
+
+        routed.map(|_| allocations)
+    }
+
+    /* As `route`, but gives the shares placed even if routing stopped part
+     * way: those submitted before the book that failed, and that book's
+     * too if it accepted its share before failing, e.g. after trading
+     * some of it. */
+    pub fn route_partial(&mut self, order: Order) ->
+        (Vec<Allocation>, Result<(), RouterError>) {
+        let ticker: String = order.get_ticker();
+        let venues: Vec<BookId> = match self.venues.get(&ticker) {
+            Some(venues) => venues.clone(),
+            None => return (vec![], Err(RouterError::UnknownSymbol(ticker)))
+        };
+        let allocations: Vec<Allocation> = self.allocate(&order, &venues);
+        let strategy: RoutingStrategy = self.strategy;
+        let mut placed: Vec<Allocation> = vec![];
+
+        for (id, quantity) in allocations {
+            let book: &mut Book<M, S, C, P> = match self.books.get_mut(&id) {
+                Some(book) => book,
+                None => return (placed, Err(RouterError::BookNotFound(id)))
+            };
+
+            /* as with the book's own events, failing to record the
+             * decision does not stop it being carried out */
+            let recorded: Result<(), BookError> =
+                book.record(EventKind::Route {
+                    order: order.get_id(),
+                    book: id,
+                    quantity,
+                    strategy
+                });
+            let submitted: Result<(), BookError> =
+                book.submit(order.with_quantity(quantity));
+
+            if submitted.is_ok() || book.status(order.get_id())
+                .is_some_and(|status| status != OrderStatus::Rejected) {
+                placed.push((id, quantity));
+            }
+
+            if let Err(e) = submitted.and(recorded) {
+                return (placed, Err(e.into()));
+            }
+        }
+
+        (placed, Ok(()))
+    }
+
+    fn allocate(&self, order: &Order, venues: &[BookId]) -> Vec<Allocation> {
+        let order_type: OrderType = order.get_order_type();
+        let limit: f64 = order.get_price();
+        let quantity: Quantity = order.get_quantity();
+        let primary: Vec<Allocation> = venues.first()
+            .map(|id| vec![(*id, quantity)])
+            .unwrap_or_default();
+
+        match self.strategy {
+            RoutingStrategy::Primary => primary,
+            RoutingStrategy::BestPrice => {
+                let best: Option<(f64, BookId)> = venues.iter()
+                    .filter_map(|id| {
+                        let book: &Book<M, S, C, P> = self.books.get(id)?;
+                        let top: TopOfBook = book.top_of_book();
+                        let (price, _): Level = match order_type {
+                            OrderType::Bid => top.get_ask(),
+                            OrderType::Ask => top.get_bid()
+                        }?;
+
+                        if crosses(&order_type, price, limit) {
+                            Some((price, *id))
+                        } else {
+                            None
+                        }
+                    })
+                    .fold(None, |best, (price, id)| match best {
+                        Some((best_price, _)) if
+                            compare(&order_type, best_price, price) !=
+                            Ordering::Greater => best,
+                        _ => Some((price, id))
+                    });
+
+                match best {
+                    Some((_, id)) => vec![(id, quantity)],
+                    None => primary
+                }
+            },
+            RoutingStrategy::Split => {
+                let mut liquidity: Vec<(f64, Quantity, BookId)> = venues
+                    .iter()
+                    .filter_map(|id| self.books.get(id).map(|b| (*id, b)))
+                    .flat_map(|(id, book)| {
+                        let side: SideIter = match order_type {
+                            OrderType::Bid => book.iter_asks(),
+                            OrderType::Ask => book.iter_bids()
+                        };
+
+                        side.take_while(|resting| crosses(&order_type,
+                                                          resting.get_price(),
+                                                          limit))
+                            .map(|resting| (resting.get_price(),
+                                            resting.get_quantity(), id))
+                            .collect::<Vec<(f64, Quantity, BookId)>>()
+                    })
+                    .collect();
+
+                /* stable, so ties go to the book added first */
+                liquidity.sort_by(|a, b| compare(&order_type, a.0, b.0));
+
+                let mut allocations: Vec<Allocation> = vec![];
+                let mut remaining: Quantity = quantity;
+
+                for (_, available, id) in liquidity {
+                    if remaining == ZERO {
+                        break;
+                    }
+
+                    let taken: Quantity = available.min(remaining);
+                    remaining -= taken;
+
+                    match allocations.iter_mut().find(|(book, _)| *book == id) {
+                        Some(allocation) => allocation.1 += taken,
+                        None => allocations.push((id, taken))
+                    }
+                }
+
+                if remaining > ZERO {
+                    match allocations.first_mut() {
+                        Some(allocation) => allocation.1 += remaining,
+                        None => return primary
+                    }
+                }
+
+                allocations
+            }
+        }
+    }
+}
+
+/* whether a resting order at `price` would trade with an incoming order of
+ * type `order_type` limited at `limit` */
+fn crosses(order_type: &OrderType, price: f64, limit: f64) -> bool {
+    match order_type {
+        OrderType::Bid => price <= limit,
+        OrderType::Ask => price >= limit
+    }
+}
+
+/* orders resting prices from best to worst for an incoming order of type
+ * `order_type` */
+fn compare(order_type: &OrderType, a: f64, b: f64) -> Ordering {
+    let ordering: Ordering = a.partial_cmp(&b).unwrap_or(Ordering::Equal);
+
+    match order_type {
+        OrderType::Bid => ordering,
+        OrderType::Ask => ordering.reverse()
+    }
+}
+
+#[cfg(all(test, not(feature = "decimal-quantity")))]
+mod tests {
+    use super::*;
+    use crate::account::Account;
+    use crate::event::Event;
+
+    fn build_order(id: OrderId, ticker: &str, order_type: OrderType,
+                   price: f64, quantity: Quantity) -> Order {
+        let mut holdings: HashMap<String, Quantity> = HashMap::new();
+        holdings.insert(ticker.to_string(), 1000);
+
+        let owner: Account = Account::new(id, "Account".to_string(),
+                                          12000.00, holdings);
+
+        Order::new(id, owner, ticker.to_string(), order_type, price, quantity)
+    }
+
+    fn build_router(strategy: RoutingStrategy) ->
+        Result<OrderRouter, RouterError> {
+        let mut router: OrderRouter = OrderRouter::new(strategy);
+
+        router.add_book(Book::new(1, "Venue A".to_string(),
+                                  "BOOK".to_string()))?;
+        router.add_book(Book::new(2, "Venue B".to_string(),
+                                  "BOOK".to_string()))?;
+        router.add_book(Book::new(3, "Other".to_string(),
+                                  "OTHER".to_string()))?;
+        Ok(router)
+    }
+
+    #[test]
+    fn test_registry() -> Result<(), RouterError> {
+        let mut actual_router: OrderRouter =
+            build_router(RoutingStrategy::Primary)?;
+
+        assert!(matches!(
+            actual_router.add_book(Book::new(2, "Venue B".to_string(),
+                                             "BOOK".to_string())),
+            Err(RouterError::DuplicateBook(2))));
+        assert!(matches!(
+            actual_router.route(build_order(1, "NONE", OrderType::Bid,
+                                            10.00, 10)),
+            Err(RouterError::UnknownSymbol(_))));
+        assert_eq!(actual_router.get_books("BOOK").len(), 2);
+
+        actual_router.remove_book(3)?;
+
+        assert!(!actual_router.has_symbol("OTHER"));
+        assert_eq!(actual_router.route(build_order(1, "BOOK", OrderType::Bid,
+                                                   10.00, 10))?,
+                   vec![(1, 10)]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_best_price() -> Result<(), RouterError> {
+        let mut actual_router: OrderRouter =
+            build_router(RoutingStrategy::BestPrice)?;
+
+        actual_router.get_book_mut(1)?
+            .submit(build_order(1, "BOOK", OrderType::Ask, 10.10, 10))?;
+        actual_router.get_book_mut(2)?
+            .submit(build_order(2, "BOOK", OrderType::Ask, 10.00, 10))?;
+
+        assert_eq!(actual_router.route(build_order(3, "BOOK", OrderType::Bid,
+                                                   10.05, 5))?,
+                   vec![(2, 5)]);
+        assert_eq!(actual_router.get_book(2)?.get_order(2)?.get_quantity(),
+                   5);
+
+        let events: &[Event] = actual_router.get_book(2)?.get_events();
+        assert!(events.iter().any(|event| *event.get_kind() ==
+                                  EventKind::Route {
+                                      order: 3,
+                                      book: 2,
+                                      quantity: 5,
+                                      strategy: RoutingStrategy::BestPrice
+                                  }));
+
+        /* nothing crosses, so the order rests on the first book */
+        assert_eq!(actual_router.route(build_order(4, "BOOK", OrderType::Bid,
+                                                   9.00, 5))?,
+                   vec![(1, 5)]);
+        assert!(actual_router.get_book(1)?.get_order(4).is_ok());
+        Ok(())
+    }
+
+    #[test]
+    fn test_split() -> Result<(), RouterError> {
+        let mut actual_router: OrderRouter =
+            build_router(RoutingStrategy::Split)?;
+
+        actual_router.get_book_mut(1)?
+            .submit(build_order(1, "BOOK", OrderType::Ask, 10.00, 5))?;
+        actual_router.get_book_mut(1)?
+            .submit(build_order(2, "BOOK", OrderType::Ask, 10.20, 5))?;
+        actual_router.get_book_mut(2)?
+            .submit(build_order(3, "BOOK", OrderType::Ask, 10.10, 5))?;
+
+        let mut order: Order = build_order(4, "BOOK", OrderType::Bid, 10.15,
+                                           20);
+        order.set_client_id("client-4".to_string());
+
+        assert_eq!(actual_router.route(order)?, vec![(1, 15), (2, 5)]);
+        assert_eq!(actual_router.get_book(1)?.get_order(4)?.get_quantity(),
+                   10);
+        assert!(actual_router.get_book(2)?.iter_asks().next().is_none());
+        assert_eq!(actual_router.find_by_client_id("BOOK", "client-4"),
+                   vec![(1, 4), (2, 4)]);
+        assert!(actual_router.find_by_client_id("BOOK", "client-5")
+                .is_empty());
+        Ok(())
+    }
+}
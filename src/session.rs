@@ -0,0 +1,63 @@
+use serde::{Serialize, Deserialize};
+
+/* Where a book is in its trading day. Books start out `Continuous`; ones
+ * built to open with an auction start out `PreOpen`. */
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash, Serialize,
+         Deserialize)]
+pub enum SessionState {
+    /* orders are accepted and rest, but nothing matches */
+    PreOpen,
+    /* as `PreOpen`; leaving it uncrosses the book at a single price */
+    OpeningAuction,
+    #[default]
+    Continuous,
+    /* no new orders, but resting ones can still be cancelled */
+    Halted,
+    /* as `Halted`, until the next day's `PreOpen` */
+    Closed
+}
+
+impl SessionState {
+    pub fn can_transition(&self, to: SessionState) -> bool {
+        use SessionState::*;
+
+        matches!((self, to),
+                 (PreOpen, OpeningAuction) | (PreOpen, Continuous) |
+                 (PreOpen, Closed) |
+                 (OpeningAuction, Continuous) | (OpeningAuction, Halted) |
+                 (OpeningAuction, Closed) |
+                 (Continuous, Halted) | (Continuous, Closed) |
+                 (Halted, OpeningAuction) | (Halted, Continuous) |
+                 (Halted, Closed) |
+                 (Closed, PreOpen))
+    }
+
+    pub fn accepts_orders(&self) -> bool {
+        matches!(self, SessionState::PreOpen | SessionState::OpeningAuction |
+                       SessionState::Continuous)
+    }
+
+    /* whether incoming orders match on arrival */
+    pub fn matches(&self) -> bool {
+        *self == SessionState::Continuous
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_transitions() {
+        assert!(SessionState::PreOpen
+            .can_transition(SessionState::OpeningAuction));
+        assert!(SessionState::Halted.can_transition(SessionState::Continuous));
+        assert!(SessionState::Closed.can_transition(SessionState::PreOpen));
+        assert!(!SessionState::Closed
+            .can_transition(SessionState::Continuous));
+        assert!(!SessionState::Continuous
+            .can_transition(SessionState::PreOpen));
+        assert!(!SessionState::Continuous
+            .can_transition(SessionState::Continuous));
+    }
+}
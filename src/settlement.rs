@@ -0,0 +1,297 @@
+use std::collections::{BTreeMap, HashMap};
+
+use chrono::{DateTime, Utc};
+use serde::{Serialize, Deserialize};
+
+use crate::account::{Account, AccountError, AccountId};
+use crate::event::Trade;
+use crate::order::OrderType;
+use crate::price;
+use crate::quantity::{self, Quantity, ZERO};
+
+/* Clearing and settlement: moving the cash and holdings that trades owe
+ * between the accounts that made them. A book only settles against the
+ * copies of accounts its orders carry; this settles against the accounts
+ * themselves, as kept by whoever registered them (e.g. an `Exchange`). */
+
+/* when trades move cash and holdings */
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum SettlementCycle {
+    /* T+0: each trade as it is recorded */
+    #[default]
+    Immediate,
+    /* each account's trades in each ticker netted into one movement of
+     * cash and one of holdings, made by `Settlement::settle` */
+    Netted
+}
+
+/* What an account's trades in a ticker come to since the last settlement:
+ * how much it bought and sold, and for how much. */
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Obligation {
+    pub account: AccountId,
+    pub ticker: String,
+    pub bought: Quantity,
+    pub sold: Quantity,
+    pub paid: f64,
+    pub received: f64,
+    pub trades: usize
+}
+
+impl Obligation {
+    fn new(account: AccountId, ticker: String) -> Obligation {
+        Obligation {
+            account,
+            ticker,
+            bought: ZERO,
+            sold: ZERO,
+            paid: 0.0,
+            received: 0.0,
+            trades: 0
+        }
+    }
+
+    fn add(&mut self, side: OrderType, quantity: Quantity, value: f64) {
+        match side {
+            OrderType::Bid => {
+                self.bought += quantity;
+                self.paid += value;
+            },
+            OrderType::Ask => {
+                self.sold += quantity;
+                self.received += value;
+            }
+        }
+
+        self.trades += 1;
+    }
+
+    /* the cash the account is owed; negative if it owes */
+    pub fn net_cash(&self) -> f64 {
+        self.received - self.paid
+    }
+
+    /* whether the account takes delivery on balance, and of how much */
+    pub fn net_delivery(&self) -> (OrderType, Quantity) {
+        if self.bought >= self.sold {
+            (OrderType::Bid, quantity::saturating_sub(self.bought, self.sold))
+        } else {
+            (OrderType::Ask, quantity::saturating_sub(self.sold, self.bought))
+        }
+    }
+
+    /* moves the cash and holdings, or nothing if the holdings can't */
+    fn apply(&self, account: &mut Account) -> Result<(), AccountError> {
+        match self.net_delivery() {
+            (_, quantity) if quantity == ZERO => {},
+            (OrderType::Bid, quantity) =>
+                account.add_holding(&self.ticker, quantity)?,
+            (OrderType::Ask, quantity) =>
+                account.take_holding(&self.ticker, quantity)?
+        }
+
+        account.add_balance(self.net_cash());
+        Ok(())
+    }
+}
+
+/* an obligation that could not be settled, and why */
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SettlementFailure {
+    pub account: AccountId,
+    pub ticker: String,
+    pub reason: String
+}
+
+/* what a settlement came to, by account and then ticker */
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SettlementReport {
+    pub timestamp: DateTime<Utc>,
+    pub cycle: SettlementCycle,
+    pub obligations: Vec<Obligation>,
+    pub failures: Vec<SettlementFailure>
+}
+
+impl SettlementReport {
+    pub fn get_obligation(&self, account: AccountId, ticker: &str) ->
+        Option<&Obligation> {
+        self.obligations.iter()
+            .find(|obligation| obligation.account == account &&
+                  obligation.ticker == ticker)
+    }
+}
+
+/* Records trades against the accounts on either side of them and settles
+ * them by its `SettlementCycle`. Positions are updated as trades are
+ * recorded, whatever the cycle, as trading is what changes them. Trades
+ * that don't say whose they were are left out. */
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Settlement {
+    cycle: SettlementCycle,
+    pending: BTreeMap<(AccountId, String), Obligation>,
+    failures: Vec<SettlementFailure>
+}
+
+impl Settlement {
+    pub fn new(cycle: SettlementCycle) -> Settlement {
+        Settlement {
+            cycle,
+            ..Settlement::default()
+        }
+    }
+
+    pub fn get_cycle(&self) -> SettlementCycle {
+        self.cycle
+    }
+
+    /* what has been recorded since the last settlement */
+    pub fn pending(&self) -> impl Iterator<Item=&Obligation> {
+        self.pending.values()
+    }
+
+    pub fn record(&mut self, ticker: &str, trade: &Trade,
+                  accounts: &mut HashMap<AccountId, Account>) {
+        let aggressor: OrderType = trade.get_aggressor_side();
+        let resting: OrderType = match aggressor {
+            OrderType::Bid => OrderType::Ask,
+            OrderType::Ask => OrderType::Bid
+        };
+        let legs: [(Option<AccountId>, OrderType); 2] = [
+            (trade.get_aggressor_account(), aggressor),
+            (trade.get_resting_account(), resting)
+        ];
+        let quantity: Quantity = trade.get_quantity();
+        let value: f64 = price::notional(trade.get_price(), quantity);
+
+        for (account, side) in legs {
+            let account: AccountId = match account {
+                Some(account) => account,
+                None => continue
+            };
+
+            self.pending.entry((account, ticker.to_string()))
+                .or_insert_with(|| Obligation::new(account,
+                                                   ticker.to_string()))
+                .add(side, quantity, value);
+
+            match accounts.get_mut(&account) {
+                Some(owner) => {
+                    owner.record_fill(ticker, &side, quantity,
+                                      trade.get_price());
+
+                    if self.cycle == SettlementCycle::Immediate {
+                        let mut leg: Obligation =
+                            Obligation::new(account, ticker.to_string());
+
+                        leg.add(side, quantity, value);
+                        self.apply(&leg, owner);
+                    }
+                },
+                None => self.failures.push(SettlementFailure {
+                    account,
+                    ticker: ticker.to_string(),
+                    reason: format!("no such account {}", account)
+                })
+            }
+        }
+    }
+
+    fn apply(&mut self, obligation: &Obligation, account: &mut Account) {
+        if let Err(e) = obligation.apply(account) {
+            self.failures.push(SettlementFailure {
+                account: obligation.account,
+                ticker: obligation.ticker.clone(),
+                reason: e.to_string()
+            });
+        }
+    }
+
+    /* Settles everything recorded since the last settlement (if the cycle
+     * hasn't already), and reports it. */
+    pub fn settle(&mut self, at: DateTime<Utc>,
+                  accounts: &mut HashMap<AccountId, Account>) ->
+        SettlementReport {
+        let obligations: Vec<Obligation> = std::mem::take(&mut self.pending)
+            .into_values()
+            .collect();
+
+        if self.cycle == SettlementCycle::Netted {
+            for obligation in &obligations {
+                if let Some(account) = accounts.get_mut(&obligation.account) {
+                    self.apply(obligation, account);
+                }
+            }
+        }
+
+        SettlementReport {
+            timestamp: at,
+            cycle: self.cycle,
+            obligations,
+            failures: std::mem::take(&mut self.failures)
+        }
+    }
+}
+
+#[cfg(all(test, not(feature = "decimal-quantity")))]
+mod tests {
+    use super::*;
+
+    fn accounts() -> HashMap<AccountId, Account> {
+        (1..=2).map(|id| {
+            let mut holdings: HashMap<String, Quantity> = HashMap::new();
+            holdings.insert("BOOK".to_string(), 100);
+
+            (id, Account::new(id, "Account".to_string(), 1000.00, holdings))
+        }).collect()
+    }
+
+    fn trade(price: f64, quantity: Quantity, buyer: AccountId,
+             seller: AccountId) -> Trade {
+        Trade::new(DateTime::<Utc>::default(), price, quantity, 2, 1,
+                   OrderType::Bid)
+            .with_accounts(buyer, seller)
+    }
+
+    #[test]
+    fn test_settlement_cycles() -> Result<(), AccountError> {
+        let trades: Vec<Trade> = vec![trade(10.00, 10, 1, 2),
+                                      trade(11.00, 4, 2, 1)];
+
+        /* immediately, each trade moves cash and holdings as it happens */
+        let mut immediate: Settlement =
+            Settlement::new(SettlementCycle::Immediate);
+        let mut settled: HashMap<AccountId, Account> = accounts();
+
+        immediate.record("BOOK", &trades[0], &mut settled);
+        assert_eq!(settled[&1].get_holding("BOOK".to_string())?, 110);
+        immediate.record("BOOK", &trades[1], &mut settled);
+
+        /* netted, nothing moves until the end of the day */
+        let mut netted: Settlement = Settlement::new(SettlementCycle::Netted);
+        let mut deferred: HashMap<AccountId, Account> = accounts();
+
+        for trade in &trades {
+            netted.record("BOOK", trade, &mut deferred);
+        }
+
+        assert_eq!(deferred[&1].get_holding("BOOK".to_string())?, 100);
+        assert_eq!(deferred[&1].get_position("BOOK")
+                       .map(|position| position.get_quantity()), Some(6));
+
+        let report: SettlementReport =
+            netted.settle(DateTime::<Utc>::default(), &mut deferred);
+        let buyer: Option<&Obligation> = report.get_obligation(1, "BOOK");
+
+        assert_eq!(buyer.map(Obligation::net_delivery),
+                   Some((OrderType::Bid, 6)));
+        assert_eq!(buyer.map(Obligation::net_cash), Some(-56.00));
+        assert!(report.failures.is_empty());
+
+        /* either way the accounts end up in the same place */
+        assert_eq!(deferred, settled);
+        assert_eq!(deferred[&1].get_balance(), 944.00);
+        assert_eq!(deferred[&2].get_holding("BOOK".to_string())?, 94);
+        assert!(netted.pending().next().is_none());
+        Ok(())
+    }
+}
@@ -0,0 +1,84 @@
+/* Deterministic simulation: everything here is driven by a `SimRng`, so
+ * the same seed always produces the same run. */
+pub mod flow;
+pub mod latency;
+pub mod replay;
+
+/* xorshift64*, seeded through splitmix64 so that nearby seeds still give
+ * unrelated streams. Not cryptographic, just fast and reproducible. */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SimRng {
+    state: u64
+}
+
+impl SimRng {
+    pub fn new(seed: u64) -> SimRng {
+        let mut z: u64 = seed.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^= z >> 31;
+
+        /* xorshift never leaves zero */
+        SimRng {
+            state: if z == 0 { 1 } else { z }
+        }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state ^= self.state >> 12;
+        self.state ^= self.state << 25;
+        self.state ^= self.state >> 27;
+        self.state.wrapping_mul(0x2545_f491_4f6c_dd1d)
+    }
+
+    /* uniform on [0, bound), or zero if `bound` is */
+    pub fn below(&mut self, bound: u64) -> u64 {
+        if bound == 0 {
+            0
+        } else {
+            self.next_u64() % bound
+        }
+    }
+
+    /* uniform on (0, 1] */
+    pub fn unit(&mut self) -> f64 {
+        ((self.next_u64() >> 11) + 1) as f64 / (1u64 << 53) as f64
+    }
+
+    pub fn chance(&mut self, probability: f64) -> bool {
+        self.unit() <= probability
+    }
+
+    /* exponentially distributed, e.g. the wait for the next arrival of a
+     * Poisson process with the given rate */
+    pub fn exponential(&mut self, rate: f64) -> f64 {
+        -self.unit().ln() / rate
+    }
+
+    /* standard normal, by Box-Muller */
+    pub fn normal(&mut self) -> f64 {
+        let radius: f64 = (-2.0 * self.unit().ln()).sqrt();
+
+        radius * (std::f64::consts::TAU * self.unit()).cos()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rng_is_deterministic() {
+        let mut first: SimRng = SimRng::new(42);
+        let mut second: SimRng = SimRng::new(42);
+        let mut other: SimRng = SimRng::new(43);
+
+        let actual: Vec<u64> = (0..8).map(|_| first.next_u64()).collect();
+        let expected: Vec<u64> = (0..8).map(|_| second.next_u64()).collect();
+
+        assert_eq!(actual, expected);
+        assert_ne!(actual.first(), Some(&other.next_u64()));
+        assert!((0..1000).all(|_| first.below(10) < 10));
+        assert!((0..1000).all(|_| (0.0..=1.0).contains(&first.unit())));
+    }
+}
@@ -0,0 +1,306 @@
+use std::collections::HashMap;
+
+use chrono::{DateTime, Duration, Utc};
+use serde::{Serialize, Deserialize};
+
+use crate::account::Account;
+use crate::order::*;
+use crate::quantity::Quantity;
+use crate::sim::SimRng;
+
+/* Synthetic order flow: passive limit orders, marketable orders and
+ * cancels arriving as independent Poisson processes around a mid price
+ * that wanders a tick at a time. */
+
+/* how big orders are, in whole units */
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum SizeDistribution {
+    Fixed(u64),
+    /* inclusive */
+    Uniform {
+        min: u64,
+        max: u64
+    },
+    /* at least one, with the given mean */
+    Geometric {
+        mean: f64
+    }
+}
+
+impl SizeDistribution {
+    fn sample(&self, rng: &mut SimRng) -> u64 {
+        match *self {
+            SizeDistribution::Fixed(size) => size,
+            SizeDistribution::Uniform { min, max } =>
+                min + rng.below(max.saturating_sub(min).saturating_add(1)),
+            SizeDistribution::Geometric { mean } => {
+                let success: f64 = 1.0 / mean.max(1.0);
+
+                if success >= 1.0 {
+                    1
+                } else {
+                    1 + (rng.unit().ln() / (1.0 - success).ln()) as u64
+                }
+            }
+        }
+    }
+}
+
+/* Rates are arrivals per second of simulated time, so their ratios set the
+ * mix, e.g. `cancel_rate` against `limit_rate` for the cancel ratio. */
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FlowConfig {
+    pub ticker: String,
+    /* the time of the first arrival is some way after this */
+    pub start: DateTime<Utc>,
+    pub mid: f64,
+    pub tick_size: f64,
+    pub limit_rate: f64,
+    pub market_rate: f64,
+    pub cancel_rate: f64,
+    /* mean distance of passive orders behind the touch, in ticks */
+    pub mean_depth: f64,
+    /* how many levels into the other side marketable orders may reach */
+    pub max_cross: u64,
+    /* the chance the mid moves a tick, either way, at each arrival */
+    pub volatility: f64,
+    pub limit_size: SizeDistribution,
+    pub market_size: SizeDistribution,
+    pub first_id: OrderId
+}
+
+impl Default for FlowConfig {
+    fn default() -> FlowConfig {
+        FlowConfig {
+            ticker: "BOOK".to_string(),
+            start: DateTime::<Utc>::default(),
+            mid: 100.00,
+            tick_size: 0.01,
+            limit_rate: 10.0,
+            market_rate: 1.0,
+            cancel_rate: 8.0,
+            mean_depth: 5.0,
+            max_cross: 4,
+            volatility: 0.05,
+            limit_size: SizeDistribution::Uniform { min: 1, max: 10 },
+            market_size: SizeDistribution::Geometric { mean: 20.0 },
+            first_id: 1
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum FlowAction {
+    Submit(Box<Order>),
+    /* a passive order generated earlier, which may well have filled by
+     * now, so the cancel may fail */
+    Cancel(OrderId)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FlowEvent {
+    timestamp: DateTime<Utc>,
+    action: FlowAction
+}
+
+impl FlowEvent {
+    pub fn get_timestamp(&self) -> DateTime<Utc> {
+        self.timestamp
+    }
+
+    pub fn get_action(&self) -> &FlowAction {
+        &self.action
+    }
+
+    pub fn into_action(self) -> FlowAction {
+        self.action
+    }
+}
+
+/* An endless stream of arrivals, the same for the same configuration and
+ * seed. Every order has its own owner, with more than enough cash and
+ * holdings for whatever it trades. */
+#[derive(Debug, Clone)]
+pub struct OrderFlow {
+    config: FlowConfig,
+    rng: SimRng,
+    now: DateTime<Utc>,
+    /* in ticks, so it never drifts off the grid */
+    mid: i64,
+    next_id: OrderId,
+    /* passive orders that have not been cancelled yet */
+    resting: Vec<OrderId>
+}
+
+impl OrderFlow {
+    pub fn new(config: FlowConfig, seed: u64) -> OrderFlow {
+        let mid: i64 = (config.mid / config.tick_size).round() as i64;
+
+        OrderFlow {
+            rng: SimRng::new(seed),
+            now: config.start,
+            mid,
+            next_id: config.first_id,
+            resting: vec![],
+            config
+        }
+    }
+
+    pub fn get_config(&self) -> &FlowConfig {
+        &self.config
+    }
+
+    pub fn get_mid(&self) -> f64 {
+        self.price(self.mid)
+    }
+
+    fn price(&self, ticks: i64) -> f64 {
+        ticks as f64 * self.config.tick_size
+    }
+
+    fn side(&mut self) -> OrderType {
+        if self.rng.below(2) == 0 {
+            OrderType::Bid
+        } else {
+            OrderType::Ask
+        }
+    }
+
+    fn order(&mut self, order_type: OrderType, price: f64, size: u64) ->
+        Order {
+        let id: OrderId = self.next_id;
+        let mut holdings: HashMap<String, Quantity> = HashMap::new();
+        holdings.insert(self.config.ticker.clone(), Quantity::from(u64::MAX));
+
+        let owner: Account = Account::new(id, "Simulated".to_string(),
+                                          f64::MAX / 2.0, holdings);
+        let order: Order = Order::new_at(id, owner,
+                                         self.config.ticker.clone(),
+                                         order_type, price,
+                                         Quantity::from(size.max(1)),
+                                         self.now);
+
+        self.next_id += 1;
+        order
+    }
+
+    fn passive(&mut self) -> Order {
+        let order_type: OrderType = self.side();
+        let behind: i64 =
+            1 + self.rng.exponential(1.0 / self.config.mean_depth.max(1e-9))
+                as i64;
+        let ticks: i64 = match order_type {
+            OrderType::Bid => self.mid - behind,
+            OrderType::Ask => self.mid + behind
+        };
+        let size: u64 = self.config.limit_size.sample(&mut self.rng);
+        let order: Order = self.order(order_type, self.price(ticks), size);
+
+        self.resting.push(order.get_id());
+        order
+    }
+
+    fn marketable(&mut self) -> Order {
+        let order_type: OrderType = self.side();
+        let reach: i64 = 1 + self.rng.below(self.config.max_cross + 1) as i64;
+        let ticks: i64 = match order_type {
+            OrderType::Bid => self.mid + reach,
+            OrderType::Ask => self.mid - reach
+        };
+        let size: u64 = self.config.market_size.sample(&mut self.rng);
+
+        self.order(order_type, self.price(ticks), size)
+    }
+}
+
+impl Iterator for OrderFlow {
+    type Item = FlowEvent;
+
+    /* `None` only if every rate is zero */
+    fn next(&mut self) -> Option<FlowEvent> {
+        let cancel_rate: f64 = if self.resting.is_empty() {
+            0.0
+        } else {
+            self.config.cancel_rate
+        };
+        let total: f64 = self.config.limit_rate + self.config.market_rate +
+            cancel_rate;
+
+        if total <= 0.0 || !total.is_finite() {
+            return None;
+        }
+
+        let wait: f64 = self.rng.exponential(total);
+        self.now += Duration::nanoseconds((wait * 1e9) as i64);
+
+        if self.rng.chance(self.config.volatility) {
+            self.mid += if self.rng.below(2) == 0 { 1 } else { -1 };
+        }
+
+        let pick: f64 = self.rng.unit() * total;
+        let action: FlowAction = if pick <= self.config.limit_rate {
+            FlowAction::Submit(Box::new(self.passive()))
+        } else if pick <= self.config.limit_rate + self.config.market_rate {
+            FlowAction::Submit(Box::new(self.marketable()))
+        } else {
+            let index: usize = self.rng.below(self.resting.len() as u64)
+                as usize;
+            FlowAction::Cancel(self.resting.swap_remove(index))
+        };
+
+        Some(FlowEvent {
+            timestamp: self.now,
+            action
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::book::{Book, BookError};
+
+    #[test]
+    fn test_flow_is_deterministic() {
+        let actual: Vec<FlowEvent> = OrderFlow::new(FlowConfig::default(), 7)
+            .take(500)
+            .collect();
+        let expected: Vec<FlowEvent> =
+            OrderFlow::new(FlowConfig::default(), 7).take(500).collect();
+        let other: Vec<FlowEvent> = OrderFlow::new(FlowConfig::default(), 8)
+            .take(500)
+            .collect();
+
+        assert_eq!(actual, expected);
+        assert_ne!(actual, other);
+        assert!(actual.windows(2).all(|pair| {
+            pair[0].get_timestamp() <= pair[1].get_timestamp()
+        }));
+    }
+
+    #[test]
+    fn test_flow_drives_book() -> Result<(), BookError> {
+        let mut book: Book = Book::new(1, "Book".to_string(),
+                                       "BOOK".to_string());
+        let (mut submitted, mut cancelled) = (0, 0);
+
+        for event in OrderFlow::new(FlowConfig::default(), 1).take(2000) {
+            match event.into_action() {
+                FlowAction::Submit(order) => {
+                    book.submit(*order)?;
+                    submitted += 1;
+                },
+                FlowAction::Cancel(id) => {
+                    if book.cancel(id).is_ok() {
+                        cancelled += 1;
+                    }
+                }
+            }
+        }
+
+        assert!(submitted > 1000);
+        assert!(cancelled > 0);
+        assert!(!book.get_trades().is_empty());
+        Ok(())
+    }
+}
@@ -0,0 +1,356 @@
+use std::collections::HashMap;
+
+use chrono::{DateTime, Duration, Utc};
+use serde::{Serialize, Deserialize};
+
+use crate::account::AccountId;
+use crate::book::{Book, BookError};
+use crate::clock::SimulatedClock;
+use crate::event::{Event, EventKind, Trade};
+use crate::matching::MatchingPolicy;
+use crate::order::{Order, OrderId};
+use crate::price::PriceType;
+use crate::sim::SimRng;
+use crate::sim::replay::Timeline;
+use crate::sink::MemorySink;
+
+/* Network and gateway latency between the participants of a simulation and
+ * the book they trade on: orders reach the book some time after they are
+ * sent, and participants hear of their fills some time after they happen,
+ * so that a backtest can see how much its results depend on either. */
+
+/* how long one leg takes */
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum Latency {
+    Fixed(std::time::Duration),
+    /* never less than zero */
+    Normal {
+        mean: std::time::Duration,
+        std_dev: std::time::Duration
+    }
+}
+
+impl Default for Latency {
+    fn default() -> Latency {
+        Latency::Fixed(std::time::Duration::ZERO)
+    }
+}
+
+impl Latency {
+    pub fn sample(&self, rng: &mut SimRng) -> Duration {
+        let seconds: f64 = match *self {
+            Latency::Fixed(latency) => latency.as_secs_f64(),
+            Latency::Normal { mean, std_dev } =>
+                mean.as_secs_f64() + rng.normal() * std_dev.as_secs_f64()
+        };
+
+        Duration::nanoseconds((seconds.max(0.0) * 1e9) as i64)
+    }
+}
+
+/* a participant's latency each way */
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct LatencyProfile {
+    /* from sending an order or cancel to the book acting on it */
+    pub inbound: Latency,
+    /* from a fill or rejection to hearing of it */
+    pub outbound: Latency
+}
+
+/* Everyone's latency: the same profile for all, unless a participant has
+ * one of their own. Sampled from its own `SimRng`, so the same seed gives
+ * the same delays. */
+#[derive(Debug, Clone)]
+pub struct LatencyModel {
+    default: LatencyProfile,
+    participants: HashMap<AccountId, LatencyProfile>,
+    rng: SimRng
+}
+
+impl LatencyModel {
+    pub fn new(default: LatencyProfile, seed: u64) -> LatencyModel {
+        LatencyModel {
+            default,
+            participants: HashMap::new(),
+            rng: SimRng::new(seed)
+        }
+    }
+
+    /* the same delay every time, both ways */
+    pub fn fixed(latency: std::time::Duration) -> LatencyModel {
+        LatencyModel::new(LatencyProfile {
+            inbound: Latency::Fixed(latency),
+            outbound: Latency::Fixed(latency)
+        }, 0)
+    }
+
+    pub fn participant(mut self, account: AccountId,
+                       profile: LatencyProfile) -> LatencyModel {
+        self.participants.insert(account, profile);
+        self
+    }
+
+    pub fn profile(&self, account: AccountId) -> &LatencyProfile {
+        self.participants.get(&account).unwrap_or(&self.default)
+    }
+
+    pub fn inbound(&mut self, account: AccountId) -> Duration {
+        let latency: Latency = self.profile(account).inbound;
+        latency.sample(&mut self.rng)
+    }
+
+    pub fn outbound(&mut self, account: AccountId) -> Duration {
+        let latency: Latency = self.profile(account).outbound;
+        latency.sample(&mut self.rng)
+    }
+}
+
+/* what a participant hears back */
+#[derive(Debug, Clone, PartialEq)]
+pub enum Notice {
+    Fill(Trade),
+    Rejected {
+        order: OrderId,
+        reason: String
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Notification {
+    pub account: AccountId,
+    pub notice: Notice,
+    /* when the book did it, and when the participant heard */
+    pub sent: DateTime<Utc>,
+    pub received: DateTime<Utc>
+}
+
+#[derive(Debug)]
+enum Message {
+    Arrive(Box<Order>),
+    Cancel {
+        account: AccountId,
+        order: OrderId
+    },
+    Deliver(Box<Notification>)
+}
+
+/* A book on a simulated timeline with a `LatencyModel` between it and its
+ * participants. Orders and cancels are sent now and acted on once they
+ * arrive; fills and rejections are notified once they have travelled back.
+ * Everything happens in time order as the timeline is moved on. Cancels
+ * of orders that are no longer there are dropped silently, as they would
+ * be if a fill won the race. */
+#[derive(Debug)]
+pub struct LatencySim<M: MatchingPolicy, P: PriceType> {
+    book: Book<M, MemorySink, SimulatedClock, P>,
+    timeline: Timeline<Message>,
+    model: LatencyModel,
+    notifications: Vec<Notification>
+}
+
+impl<M: MatchingPolicy, P: PriceType> LatencySim<M, P> {
+    pub fn new(book: Book<M, MemorySink, SimulatedClock, P>,
+               model: LatencyModel) -> LatencySim<M, P> {
+        let timeline: Timeline<Message> =
+            Timeline::new(book.get_clock().clone());
+
+        LatencySim {
+            book,
+            timeline,
+            model,
+            notifications: vec![]
+        }
+    }
+
+    pub fn get_book(&self) -> &Book<M, MemorySink, SimulatedClock, P> {
+        &self.book
+    }
+
+    pub fn get_model(&self) -> &LatencyModel {
+        &self.model
+    }
+
+    pub fn now(&self) -> DateTime<Utc> {
+        self.timeline.now()
+    }
+
+    /* sends `order` now, giving when it will reach the book */
+    pub fn submit(&mut self, order: Order) -> DateTime<Utc> {
+        let owner: AccountId = order.get_owner_ref().get_id();
+        let at: DateTime<Utc> = self.now() + self.model.inbound(owner);
+
+        self.timeline.schedule(at, Message::Arrive(Box::new(order)));
+        at
+    }
+
+    /* sends a cancel of `order` now, giving when it will reach the book */
+    pub fn cancel(&mut self, account: AccountId, order: OrderId) ->
+        DateTime<Utc> {
+        let at: DateTime<Utc> = self.now() + self.model.inbound(account);
+
+        self.timeline.schedule(at, Message::Cancel {account, order});
+        at
+    }
+
+    /* Moves time on to `at`, delivering everything due by then. */
+    pub fn advance_to(&mut self, at: DateTime<Utc>) -> Result<(), BookError> {
+        while self.timeline.next_due().is_some_and(|due| due <= at) {
+            if let Some((_, message)) = self.timeline.pop() {
+                self.deliver(message)?;
+            }
+        }
+
+        self.timeline.advance_to(at);
+        Ok(())
+    }
+
+    /* delivers everything still in flight */
+    pub fn run(&mut self) -> Result<(), BookError> {
+        while let Some((_, message)) = self.timeline.pop() {
+            self.deliver(message)?;
+        }
+
+        Ok(())
+    }
+
+    /* what participants have heard so far, in the order they heard it */
+    pub fn take_notifications(&mut self) -> Vec<Notification> {
+        std::mem::take(&mut self.notifications)
+    }
+
+    fn deliver(&mut self, message: Message) -> Result<(), BookError> {
+        let last_seq: u64 = self.book.last_seq();
+
+        match message {
+            Message::Arrive(order) => {
+                let owner: AccountId = order.get_owner_ref().get_id();
+                let id: OrderId = order.get_id();
+
+                if let Err(e) = self.book.submit(*order) {
+                    self.notify(owner, Notice::Rejected {
+                        order: id,
+                        reason: e.to_string()
+                    });
+                }
+            },
+            Message::Cancel { account, order } => {
+                let owned: bool = self.book.get_order(order)
+                    .map(|resting| resting.get_owner_ref().get_id() ==
+                         account)
+                    .unwrap_or(false);
+
+                if owned {
+                    self.book.cancel(order)?;
+                }
+            },
+            Message::Deliver(notification) =>
+                self.notifications.push(*notification)
+        }
+
+        let trades: Vec<Trade> = self.book.events_since(last_seq)?.iter()
+            .filter_map(|event: &Event| match event.get_kind() {
+                EventKind::Match(trade) => Some(*trade),
+                _ => None
+            })
+            .collect();
+
+        for trade in trades {
+            let accounts = [trade.get_aggressor_account(),
+                            trade.get_resting_account()];
+
+            for account in accounts.iter().flatten() {
+                self.notify(*account, Notice::Fill(trade));
+            }
+        }
+
+        Ok(())
+    }
+
+    fn notify(&mut self, account: AccountId, notice: Notice) {
+        let sent: DateTime<Utc> = self.now();
+        let received: DateTime<Utc> = sent + self.model.outbound(account);
+
+        self.timeline.schedule(received, Message::Deliver(Box::new(
+            Notification {account, notice, sent, received})));
+    }
+}
+
+#[cfg(all(test, not(feature = "decimal-quantity")))]
+mod tests {
+    use super::*;
+    use crate::account::Account;
+    use crate::book::Book;
+    use crate::builder::BookBuilder;
+    use crate::matching::PriceTime;
+    use crate::order::OrderType;
+    use crate::price::F64Price;
+    use crate::quantity::Quantity;
+
+    fn build_order(id: OrderId, owner: AccountId, order_type: OrderType,
+                   price: f64, quantity: Quantity) -> Order {
+        let mut holdings: HashMap<String, Quantity> = HashMap::new();
+        holdings.insert("BOOK".to_string(), 1000);
+
+        let owner: Account = Account::new(owner, "Account".to_string(),
+                                          12000.00, holdings);
+
+        Order::new(id, owner, "BOOK".to_string(), order_type, price, quantity)
+    }
+
+    #[test]
+    fn test_latency() -> crate::Result<()> {
+        let start: DateTime<Utc> = DateTime::<Utc>::default();
+        let book: Book<PriceTime, MemorySink, SimulatedClock, F64Price> =
+            BookBuilder::new(1, "BOOK".to_string())
+                .clock(SimulatedClock::new(start))
+                .build()?;
+        let millis = |n: u64| std::time::Duration::from_millis(n);
+        /* the fast participant is colocated */
+        let model: LatencyModel = LatencyModel::fixed(millis(10))
+            .participant(2, LatencyProfile {
+                inbound: Latency::Fixed(millis(1)),
+                outbound: Latency::Fixed(millis(1))
+            });
+        let mut sim: LatencySim<PriceTime, F64Price> =
+            LatencySim::new(book, model);
+
+        sim.advance_to(start + Duration::milliseconds(100))?;
+
+        /* the slow participant rests an ask and tries to pull it, but the
+         * fast one's bid, sent later, gets there first */
+        sim.submit(build_order(1, 1, OrderType::Ask, 12.00, 10));
+        sim.advance_to(start + Duration::milliseconds(120))?;
+        sim.cancel(1, 1);
+        sim.advance_to(start + Duration::milliseconds(125))?;
+        sim.submit(build_order(2, 2, OrderType::Bid, 12.00, 10));
+        sim.advance_to(start + Duration::milliseconds(126))?;
+
+        /* no one has heard of the fill yet */
+        assert_eq!(sim.get_book().get_trades().len(), 1);
+        assert!(sim.take_notifications().is_empty());
+
+        sim.run()?;
+
+        let heard: Vec<(AccountId, i64)> = sim.take_notifications().iter()
+            .map(|notification| (notification.account,
+                                 (notification.received - start)
+                                 .num_milliseconds()))
+            .collect();
+
+        assert_eq!(heard, vec![(2, 127), (1, 136)]);
+
+        /* normal latencies are reproducible and never negative */
+        let normal: Latency = Latency::Normal {
+            mean: millis(1),
+            std_dev: millis(5)
+        };
+        let mut first: SimRng = SimRng::new(7);
+        let mut second: SimRng = SimRng::new(7);
+
+        assert!((0..100).all(|_| {
+            let sample: Duration = normal.sample(&mut first);
+            sample == normal.sample(&mut second) && sample >= Duration::zero()
+        }));
+        Ok(())
+    }
+}
@@ -0,0 +1,295 @@
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+use std::thread;
+use std::time::Instant;
+
+use chrono::{DateTime, Duration, Utc};
+use serde::{Serialize, Deserialize};
+
+use crate::clock::{Clock, SimulatedClock};
+
+/* Replaying on a simulated timeline: recorded or generated actions, and
+ * whatever is scheduled along the way (order expiries, a strategy's timed
+ * actions), delivered in time order on a `SimulatedClock`, as fast as
+ * possible or paced against the wall clock. */
+
+/* how fast simulated time passes against the wall clock */
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum Pace {
+    /* never waits */
+    #[default]
+    AsFastAsPossible,
+    RealTime,
+    /* e.g. `Scaled(10.0)` for ten simulated seconds to every real one */
+    Scaled(f64)
+}
+
+impl Pace {
+    /* how long after the start of a replay, by the wall clock, something
+     * `elapsed` into it by simulated time is due; `None` if whenever */
+    pub fn wall_time(&self, elapsed: Duration) ->
+        Option<std::time::Duration> {
+        let speed: f64 = match *self {
+            Pace::AsFastAsPossible => return None,
+            Pace::RealTime => 1.0,
+            Pace::Scaled(speed) if speed > 0.0 && speed.is_finite() => speed,
+            Pace::Scaled(_) => return None
+        };
+        let elapsed: std::time::Duration = elapsed.to_std()
+            .unwrap_or_default();
+
+        Some(elapsed.div_f64(speed))
+    }
+}
+
+/* ordered on time, then on the order of scheduling, soonest first */
+#[derive(Debug)]
+struct Scheduled<T> {
+    at: DateTime<Utc>,
+    seq: u64,
+    item: T
+}
+
+impl<T> PartialEq for Scheduled<T> {
+    fn eq(&self, other: &Scheduled<T>) -> bool {
+        (self.at, self.seq) == (other.at, other.seq)
+    }
+}
+
+impl<T> Eq for Scheduled<T> {}
+
+impl<T> PartialOrd for Scheduled<T> {
+    fn partial_cmp(&self, other: &Scheduled<T>) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<T> Ord for Scheduled<T> {
+    /* reversed, as `BinaryHeap` is a max-heap */
+    fn cmp(&self, other: &Scheduled<T>) -> Ordering {
+        (other.at, other.seq).cmp(&(self.at, self.seq))
+    }
+}
+
+/* Items of `T` due at given times, handed back in time order (and in the
+ * order they were scheduled at the same time) as the clock is moved on to
+ * each. Anything scheduled in the clock's past is due at once. */
+#[derive(Debug)]
+pub struct Timeline<T> {
+    clock: SimulatedClock,
+    pace: Pace,
+    queue: BinaryHeap<Scheduled<T>>,
+    next_seq: u64,
+    /* when pacing started, by the wall clock and the simulated one */
+    anchor: Option<(Instant, DateTime<Utc>)>
+}
+
+impl<T> Timeline<T> {
+    /* on `clock`, which the books being driven should share */
+    pub fn new(clock: SimulatedClock) -> Timeline<T> {
+        Timeline {
+            clock,
+            pace: Pace::default(),
+            queue: BinaryHeap::new(),
+            next_seq: 0,
+            anchor: None
+        }
+    }
+
+    pub fn pace(mut self, pace: Pace) -> Timeline<T> {
+        self.pace = pace;
+        self
+    }
+
+    pub fn get_clock(&self) -> &SimulatedClock {
+        &self.clock
+    }
+
+    pub fn get_pace(&self) -> Pace {
+        self.pace
+    }
+
+    pub fn now(&self) -> DateTime<Utc> {
+        self.clock.now()
+    }
+
+    pub fn schedule(&mut self, at: DateTime<Utc>, item: T) {
+        self.queue.push(Scheduled {
+            at,
+            seq: self.next_seq,
+            item
+        });
+        self.next_seq += 1;
+    }
+
+    /* `after` from now, by the clock */
+    pub fn schedule_in(&mut self, after: Duration, item: T) {
+        let at: DateTime<Utc> = self.clock.now() + after;
+        self.schedule(at, item);
+    }
+
+    pub fn next_due(&self) -> Option<DateTime<Utc>> {
+        self.queue.peek().map(|scheduled| scheduled.at)
+    }
+
+    /* how many items are still to come */
+    pub fn len(&self) -> usize {
+        self.queue.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.queue.is_empty()
+    }
+
+    /* Moves the clock on to `at`, first waiting for as long as the pace
+     * says it should take by the wall clock. */
+    pub fn advance_to(&mut self, at: DateTime<Utc>) {
+        let clock: &SimulatedClock = &self.clock;
+        let (started, from) = *self.anchor
+            .get_or_insert_with(|| (Instant::now(), clock.now()));
+
+        if let Some(due) = self.pace.wall_time(at - from) {
+            let waited: std::time::Duration = started.elapsed();
+
+            if due > waited {
+                thread::sleep(due - waited);
+            }
+        }
+
+        self.clock.advance_to(at);
+    }
+
+    /* the next item due, once the clock has been moved on to it */
+    pub fn pop(&mut self) -> Option<(DateTime<Utc>, T)> {
+        let scheduled: Scheduled<T> = self.queue.pop()?;
+
+        self.advance_to(scheduled.at);
+        Some((scheduled.at, scheduled.item))
+    }
+
+    /* Delivers every item of `source`, which must be in time order, along
+     * with everything scheduled, to `handler` in time order, moving the
+     * clock on to each. Scheduled items go first at the same time. The
+     * handler may schedule more; those due after the source runs out are
+     * still delivered. Stops at the handler's first error. */
+    pub fn run<I, F, E>(&mut self, source: I, mut handler: F) ->
+        Result<(), E>
+    where I: IntoIterator<Item=(DateTime<Utc>, T)>,
+          F: FnMut(&mut Timeline<T>, DateTime<Utc>, T) -> Result<(), E> {
+        let mut source = source.into_iter().peekable();
+
+        loop {
+            let scheduled: bool = match (self.next_due(), source.peek()) {
+                (Some(due), Some((at, _))) => due <= *at,
+                (Some(_), None) => true,
+                (None, Some(_)) => false,
+                (None, None) => return Ok(())
+            };
+            let next: Option<(DateTime<Utc>, T)> = if scheduled {
+                self.pop()
+            } else {
+                source.next().map(|(at, item)| {
+                    self.advance_to(at);
+                    (at, item)
+                })
+            };
+
+            if let Some((at, item)) = next {
+                handler(self, at, item)?;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::book::{Book, BookError};
+    use crate::builder::BookBuilder;
+    use crate::event::Reason;
+    use crate::order::{OrderId, OrderStatus};
+    use crate::sim::flow::{FlowAction, FlowConfig, OrderFlow};
+
+    #[derive(Debug)]
+    enum Action {
+        Flow(FlowAction),
+        Expire(OrderId)
+    }
+
+    #[test]
+    fn test_pace() {
+        let second: Duration = Duration::seconds(1);
+
+        assert_eq!(Pace::AsFastAsPossible.wall_time(second), None);
+        assert_eq!(Pace::RealTime.wall_time(second),
+                   Some(std::time::Duration::from_secs(1)));
+        assert_eq!(Pace::Scaled(10.0).wall_time(second),
+                   Some(std::time::Duration::from_millis(100)));
+        assert_eq!(Pace::Scaled(0.0).wall_time(second), None);
+    }
+
+    #[test]
+    fn test_scheduled_in_time_order() {
+        let start: DateTime<Utc> = DateTime::<Utc>::default();
+        let mut timeline: Timeline<&str> =
+            Timeline::new(SimulatedClock::new(start));
+
+        timeline.schedule(start + Duration::seconds(2), "third");
+        timeline.schedule(start + Duration::seconds(1), "first");
+        timeline.schedule(start + Duration::seconds(1), "second");
+
+        let items: Vec<&str> = std::iter::from_fn(|| timeline.pop())
+            .map(|(_, item)| item)
+            .collect();
+
+        assert_eq!(items, vec!["first", "second", "third"]);
+        assert_eq!(timeline.now(), start + Duration::seconds(2));
+    }
+
+    #[test]
+    fn test_replay_with_expiries() -> crate::Result<()> {
+        let config: FlowConfig = FlowConfig::default();
+        let clock: SimulatedClock = SimulatedClock::new(config.start);
+        let mut book: Book<_, _, SimulatedClock> =
+            BookBuilder::new(1, "BOOK".to_string())
+            .clock(clock.clone())
+            .build()?;
+        let mut timeline: Timeline<Action> =
+            Timeline::new(clock).pace(Pace::Scaled(1e6));
+        let flow = OrderFlow::new(config, 3)
+            .take(500)
+            .map(|event| (event.get_timestamp(),
+                          Action::Flow(event.into_action())));
+        let mut last: DateTime<Utc> = DateTime::<Utc>::default();
+
+        timeline.run(flow, |timeline, at, action| -> Result<(), BookError> {
+            assert!(at >= last && book.get_clock().now() == at);
+            last = at;
+
+            match action {
+                Action::Flow(FlowAction::Submit(order)) => {
+                    let id: OrderId = order.get_id();
+
+                    book.submit(*order)?;
+                    timeline.schedule_in(Duration::milliseconds(500),
+                                         Action::Expire(id));
+                },
+                Action::Flow(FlowAction::Cancel(id)) => {
+                    let _ = book.cancel(id);
+                },
+                Action::Expire(id) => {
+                    let _ = book.cancel_with_reason(id, Reason::Expired);
+                }
+            }
+
+            Ok(())
+        })?;
+
+        /* every order still resting has been expired */
+        assert!(timeline.is_empty());
+        assert_eq!(book.iter_all().count(), 0);
+        assert!((1..=500).any(|id| book.status(id) ==
+                              Some(OrderStatus::Expired)));
+        Ok(())
+    }
+}
@@ -0,0 +1,783 @@
+use std::convert::TryFrom;
+use std::fmt::Debug;
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufRead, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, Utc};
+use serde::{Serialize, Deserialize};
+
+use crate::binary::BinaryError;
+use crate::event::*;
+use crate::replica::ReplicaError;
+use crate::snapshot::BookSnapshot;
+
+#[derive(Debug, thiserror::Error)]
+pub enum SinkError {
+    #[error("I/O error: {0}")]
+    Io(#[from] io::Error),
+    #[error("encoding error: {0}")]
+    Encoding(String),
+    /* a broker or other remote end refused or failed to take events */
+    #[error("transport error: {0}")]
+    Transport(String),
+    /* e.g. a `DepthRecorder` was given events out of sequence */
+    #[error("replica error: {0}")]
+    Replica(#[from] ReplicaError),
+}
+
+impl From<serde_json::Error> for SinkError {
+    fn from(error: serde_json::Error) -> SinkError {
+        SinkError::Encoding(error.to_string())
+    }
+}
+
+impl From<BinaryError> for SinkError {
+    fn from(error: BinaryError) -> SinkError {
+        SinkError::Encoding(error.to_string())
+    }
+}
+
+/* Somewhere for a book's events to go. Like matching policies, sinks can be
+ * used either as a generic parameter or boxed as `Box<dyn EventSink>`. */
+pub trait EventSink: Debug {
+    fn write(&mut self, event: &Event) -> Result<(), SinkError>;
+    fn flush(&mut self) -> Result<(), SinkError>;
+
+    /* Offered a snapshot of the book as of `snapshot.last_seq` by books
+     * that compact (see `BookConfig::compact_every`), so that a sink that
+     * keeps events can keep that instead of every event up to it. Most
+     * sinks have no use for it. */
+    fn compact(&mut self, snapshot: &BookSnapshot) -> Result<(), SinkError> {
+        let _ = snapshot;
+        Ok(())
+    }
+}
+
+impl EventSink for Box<dyn EventSink> {
+    fn write(&mut self, event: &Event) -> Result<(), SinkError> {
+        (**self).write(event)
+    }
+
+    fn compact(&mut self, snapshot: &BookSnapshot) -> Result<(), SinkError> {
+        (**self).compact(snapshot)
+    }
+
+    fn flush(&mut self) -> Result<(), SinkError> {
+        (**self).flush()
+    }
+}
+
+/* which events a `MemorySink` holds on to */
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum Retention {
+    #[default]
+    Unbounded,
+    /* the most recent this many, as a ring buffer */
+    Count(usize),
+    /* those no older than this, by event timestamp, than the newest */
+    Age(Duration)
+}
+
+/* Keeps events in memory, as many as its `Retention` allows. A book that
+ * compacts also leaves it a snapshot, and the events before the snapshot
+ * are dropped in favour of it. */
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct MemorySink {
+    events: Vec<Event>,
+    retention: Retention,
+    compacted: Option<BookSnapshot>
+}
+
+impl MemorySink {
+    pub fn new() -> MemorySink {
+        MemorySink::default()
+    }
+
+    pub fn with_capacity(capacity: usize) -> MemorySink {
+        MemorySink {
+            events: Vec::with_capacity(capacity),
+            ..MemorySink::with_retention(Retention::Count(capacity))
+        }
+    }
+
+    pub fn with_retention(retention: Retention) -> MemorySink {
+        MemorySink {
+            retention,
+            ..MemorySink::default()
+        }
+    }
+
+    pub fn get_retention(&self) -> Retention {
+        self.retention
+    }
+
+    /* only for `Retention::Count` */
+    pub fn get_capacity(&self) -> Option<usize> {
+        match self.retention {
+            Retention::Count(capacity) => Some(capacity),
+            _ => None
+        }
+    }
+
+    /* the latest snapshot a book compacted into this sink, if any */
+    pub fn get_compacted(&self) -> Option<&BookSnapshot> {
+        self.compacted.as_ref()
+    }
+
+    /* where the retained events start in `events` */
+    fn start(&self) -> usize {
+        match self.retention {
+            Retention::Unbounded => 0,
+            Retention::Count(capacity) =>
+                self.events.len().saturating_sub(capacity),
+            Retention::Age(age) => match (self.events.last(),
+                                          chrono::Duration::from_std(age)) {
+                (Some(newest), Ok(age)) => {
+                    let oldest: DateTime<Utc> = newest.get_timestamp() - age;
+
+                    self.events.partition_point(|event| {
+                        event.get_timestamp() < oldest
+                    })
+                },
+                _ => 0
+            }
+        }
+    }
+
+    pub fn get_events(&self) -> &[Event] {
+        self.events.get(self.start()..).unwrap_or_default()
+    }
+
+    /* The retained events after `seq`, or `None` if some of them have
+     * already been dropped. */
+    pub fn events_since(&self, seq: u64) -> Option<&[Event]> {
+        let events: &[Event] = self.get_events();
+
+        if let Some(first) = events.first() {
+            if seq.saturating_add(1) < first.get_seq() {
+                return None;
+            }
+        } else if self.compacted.as_ref()
+            .is_some_and(|snapshot| seq < snapshot.last_seq) {
+            return None;
+        }
+
+        let start: usize =
+            events.partition_point(|event| event.get_seq() <= seq);
+
+        events.get(start..)
+    }
+}
+
+impl EventSink for MemorySink {
+    fn write(&mut self, event: &Event) -> Result<(), SinkError> {
+        self.events.push(event.clone());
+
+        /* evicting in bulk once at least as many events are due to go as
+         * are to stay keeps writes amortised constant time while still
+         * serving a contiguous slice */
+        let start: usize = self.start();
+
+        if start > 0 && start >= self.events.len() - start {
+            self.events.drain(..start);
+        }
+
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), SinkError> {
+        Ok(())
+    }
+
+    fn compact(&mut self, snapshot: &BookSnapshot) -> Result<(), SinkError> {
+        let start: usize = self.events
+            .partition_point(|event| event.get_seq() <= snapshot.last_seq);
+
+        self.events.drain(..start);
+        self.compacted = Some(snapshot.clone());
+        Ok(())
+    }
+}
+
+/* Discards everything, for books whose history nobody needs. */
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct NullSink;
+
+impl EventSink for NullSink {
+    fn write(&mut self, _event: &Event) -> Result<(), SinkError> {
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), SinkError> {
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum WalFormat {
+    /* one JSON-encoded event per line */
+    JsonLines,
+    /* each JSON-encoded event preceded by its length as a little-endian
+     * u32, so a torn final record can be told apart from a corrupt one */
+    Framed,
+    /* as `Framed`, but each event is bincode rather than JSON */
+    Binary
+}
+
+/* when a `WalSink` asks the operating system to put its writes on disk */
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SyncPolicy {
+    Never,
+    OnFlush,
+    EveryWrite
+}
+
+/* An append-only file of events, suitable as a write-ahead log. */
+#[derive(Debug)]
+pub struct WalSink {
+    writer: BufWriter<File>,
+    format: WalFormat,
+    sync: SyncPolicy
+}
+
+impl WalSink {
+    /* appends to the file at `path`, creating it if need be */
+    pub fn open<P: AsRef<Path>>(path: P, format: WalFormat,
+                                sync: SyncPolicy) -> Result<WalSink,
+                                                            SinkError> {
+        let file: File = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)?;
+
+        Ok(WalSink {
+            writer: BufWriter::new(file),
+            format,
+            sync
+        })
+    }
+
+    pub fn get_format(&self) -> WalFormat {
+        self.format
+    }
+
+    pub fn get_sync_policy(&self) -> SyncPolicy {
+        self.sync
+    }
+
+    fn sync(&mut self) -> Result<(), SinkError> {
+        self.writer.flush()?;
+        self.writer.get_ref().sync_data()?;
+        Ok(())
+    }
+}
+
+impl EventSink for WalSink {
+    fn write(&mut self, event: &Event) -> Result<(), SinkError> {
+        let encoded: Vec<u8> = match self.format {
+            WalFormat::Binary => bincode::serialize(event)
+                .map_err(|e| SinkError::Encoding(e.to_string()))?,
+            WalFormat::JsonLines | WalFormat::Framed =>
+                serde_json::to_vec(event)?
+        };
+
+        match self.format {
+            WalFormat::JsonLines => {
+                self.writer.write_all(&encoded)?;
+                self.writer.write_all(b"\n")?;
+            },
+            WalFormat::Framed | WalFormat::Binary => {
+                let length: u32 = u32::try_from(encoded.len())
+                    .map_err(|e| SinkError::Encoding(e.to_string()))?;
+
+                self.writer.write_all(&length.to_le_bytes())?;
+                self.writer.write_all(&encoded)?;
+            }
+        }
+
+        if self.sync == SyncPolicy::EveryWrite {
+            self.sync()?;
+        }
+
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), SinkError> {
+        match self.sync {
+            SyncPolicy::Never => Ok(self.writer.flush()?),
+            SyncPolicy::OnFlush | SyncPolicy::EveryWrite => self.sync()
+        }
+    }
+}
+
+/* Reads back a log written by `WalSink`. A final record cut short by a
+ * crash mid-write is ignored; anything else that fails to decode is an
+ * error. */
+pub fn read_wal<P: AsRef<Path>>(path: P, format: WalFormat) ->
+    Result<Vec<Event>, SinkError> {
+    let mut reader: BufReader<File> = BufReader::new(File::open(path)?);
+    let mut events: Vec<Event> = vec![];
+
+    match format {
+        WalFormat::JsonLines => {
+            let mut line: String = String::new();
+
+            while reader.read_line(&mut line)? > 0 {
+                if !line.ends_with('\n') {
+                    break;
+                }
+
+                events.push(serde_json::from_str(&line)?);
+                line.clear();
+            }
+        },
+        WalFormat::Framed | WalFormat::Binary => {
+            let mut header: [u8; 4] = [0; 4];
+
+            loop {
+                match reader.read_exact(&mut header) {
+                    Ok(()) => {},
+                    Err(e) if e.kind() == io::ErrorKind::UnexpectedEof =>
+                        break,
+                    Err(e) => return Err(e.into())
+                }
+
+                /* read no more than is there, so that a torn or corrupt
+                 * length can't have a huge buffer allocated for it */
+                let length: u64 = u64::from(u32::from_le_bytes(header));
+                let mut encoded: Vec<u8> = vec![];
+
+                if reader.by_ref().take(length).read_to_end(&mut encoded)? as
+                    u64 != length {
+                    break;
+                }
+
+                if format == WalFormat::Binary {
+                    events.push(bincode::deserialize(&encoded)
+                        .map_err(|e| SinkError::Encoding(e.to_string()))?);
+                } else {
+                    events.push(serde_json::from_slice(&encoded)?);
+                }
+            }
+        }
+    }
+
+    Ok(events)
+}
+
+/* When a `BufferedSink` pushes its buffer downstream. Every limit is
+ * optional; with none set, events are only written on an explicit flush. */
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct FlushPolicy {
+    pub max_events: Option<usize>,
+    /* measured as the size of each event's JSON encoding */
+    pub max_bytes: Option<usize>,
+    pub max_interval: Option<Duration>,
+    pub on_critical: bool
+}
+
+impl FlushPolicy {
+    pub fn immediate() -> FlushPolicy {
+        FlushPolicy {
+            max_events: Some(1),
+            ..FlushPolicy::default()
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct SinkMetrics {
+    pub buffered_events: usize,
+    pub buffered_bytes: usize,
+    pub flushed_events: u64,
+    pub flushes: u64,
+    /* how long the oldest buffered event has been waiting */
+    pub lag: Duration
+}
+
+#[derive(Debug)]
+pub struct BufferedSink<S: EventSink> {
+    inner: S,
+    policy: FlushPolicy,
+    buffer: Vec<Event>,
+    /* how many of the buffer's first events the inner sink has taken,
+     * though not yet flushed */
+    written: usize,
+    buffered_bytes: usize,
+    oldest: Option<Instant>,
+    last_flush: Instant,
+    flushed_events: u64,
+    flushes: u64
+}
+
+impl<S: EventSink> BufferedSink<S> {
+    pub fn new(inner: S, policy: FlushPolicy) -> BufferedSink<S> {
+        BufferedSink {
+            inner,
+            policy,
+            buffer: vec![],
+            written: 0,
+            buffered_bytes: 0,
+            oldest: None,
+            last_flush: Instant::now(),
+            flushed_events: 0,
+            flushes: 0
+        }
+    }
+
+    pub fn get_inner(&self) -> &S {
+        &self.inner
+    }
+
+    pub fn get_policy(&self) -> FlushPolicy {
+        self.policy
+    }
+
+    pub fn set_policy(&mut self, policy: FlushPolicy) {
+        self.policy = policy;
+    }
+
+    pub fn metrics(&self) -> SinkMetrics {
+        SinkMetrics {
+            buffered_events: self.buffer.len(),
+            buffered_bytes: self.buffered_bytes,
+            flushed_events: self.flushed_events,
+            flushes: self.flushes,
+            lag: self.oldest.map(|t| t.elapsed()).unwrap_or_default()
+        }
+    }
+
+    /* flushes if the interval has elapsed; for callers that want
+     * time-based flushing to happen even when no events are arriving */
+    pub fn poll(&mut self) -> Result<(), SinkError> {
+        if self.interval_elapsed() {
+            self.flush()?;
+        }
+
+        Ok(())
+    }
+
+    fn interval_elapsed(&self) -> bool {
+        match self.policy.max_interval {
+            Some(interval) => !self.buffer.is_empty() &&
+                self.last_flush.elapsed() >= interval,
+            None => false
+        }
+    }
+
+    fn should_flush(&self, latest: &Event) -> bool {
+        (self.policy.on_critical && latest.get_kind().is_critical()) ||
+            self.policy.max_events
+                .is_some_and(|max| self.buffer.len() >= max) ||
+            self.policy.max_bytes
+                .is_some_and(|max| self.buffered_bytes >= max) ||
+            self.interval_elapsed()
+    }
+}
+
+impl<S: EventSink> EventSink for BufferedSink<S> {
+    fn write(&mut self, event: &Event) -> Result<(), SinkError> {
+        if self.policy.max_bytes.is_some() {
+            self.buffered_bytes += serde_json::to_vec(event)?.len();
+        }
+
+        self.buffer.push(event.clone());
+        self.oldest.get_or_insert_with(Instant::now);
+
+        if self.should_flush(event) {
+            self.flush()?;
+        }
+
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), SinkError> {
+        /* events are only dropped from the buffer once the inner sink has
+         * flushed them, so a failed flush can be retried; those it already
+         * took are not written to it again */
+        while let Some(event) = self.buffer.get(self.written) {
+            self.inner.write(event)?;
+            self.written += 1;
+        }
+
+        self.inner.flush()?;
+
+        self.flushed_events += self.buffer.len() as u64;
+        self.flushes += 1;
+        self.buffer.clear();
+        self.written = 0;
+        self.buffered_bytes = 0;
+        self.oldest = None;
+        self.last_flush = Instant::now();
+
+        Ok(())
+    }
+}
+
+#[cfg(all(test, not(feature = "decimal-quantity")))]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+    use crate::order::OrderType;
+
+    #[derive(Debug, Default)]
+    struct RecordingSink {
+        written: Vec<Event>,
+        flushes: usize,
+        /* how many more writes to take before refusing them */
+        capacity: Option<usize>,
+        refuse_flushes: bool
+    }
+
+    impl EventSink for RecordingSink {
+        fn write(&mut self, event: &Event) -> Result<(), SinkError> {
+            if self.capacity == Some(0) {
+                return Err(SinkError::Transport("full".to_string()));
+            }
+
+            self.capacity = self.capacity.map(|capacity| capacity - 1);
+            self.written.push(event.clone());
+            Ok(())
+        }
+
+        fn flush(&mut self) -> Result<(), SinkError> {
+            if self.refuse_flushes {
+                return Err(SinkError::Transport("unreachable".to_string()));
+            }
+
+            self.flushes += 1;
+            Ok(())
+        }
+    }
+
+    fn post(id: u128) -> Event {
+        Event::new(id as u64, Utc::now(), EventKind::Post {
+            order: id,
+            order_type: OrderType::Bid,
+            price: 12.00,
+            quantity: 10,
+            hidden: false
+        })
+    }
+
+    fn trade() -> Event {
+        Event::new(0, Utc::now(), EventKind::Match(
+            Trade::new(Utc::now(), 12.00, 10, 2, 1, OrderType::Ask)))
+    }
+
+    #[test]
+    fn test_flush_by_count() -> Result<(), SinkError> {
+        let policy: FlushPolicy = FlushPolicy {
+            max_events: Some(3),
+            ..FlushPolicy::default()
+        };
+        let mut sink: BufferedSink<RecordingSink> =
+            BufferedSink::new(RecordingSink::default(), policy);
+
+        sink.write(&post(1))?;
+        sink.write(&post(2))?;
+        assert!(sink.get_inner().written.is_empty());
+        assert_eq!(sink.metrics().buffered_events, 2);
+
+        sink.write(&post(3))?;
+        assert_eq!(sink.get_inner().written.len(), 3);
+        assert_eq!(sink.metrics().flushed_events, 3);
+        assert_eq!(sink.metrics().buffered_events, 0);
+        Ok(())
+    }
+
+    #[test]
+    fn test_flush_by_bytes() -> Result<(), SinkError> {
+        let event: Event = post(1);
+        let size: usize = serde_json::to_vec(&event)?.len();
+        let policy: FlushPolicy = FlushPolicy {
+            max_bytes: Some(size * 2),
+            ..FlushPolicy::default()
+        };
+        let mut sink: BufferedSink<RecordingSink> =
+            BufferedSink::new(RecordingSink::default(), policy);
+
+        sink.write(&event)?;
+        assert_eq!(sink.metrics().buffered_bytes, size);
+
+        sink.write(&event)?;
+        assert_eq!(sink.get_inner().written.len(), 2);
+        Ok(())
+    }
+
+    #[test]
+    fn test_flush_on_critical() -> Result<(), SinkError> {
+        let policy: FlushPolicy = FlushPolicy {
+            on_critical: true,
+            ..FlushPolicy::default()
+        };
+        let mut sink: BufferedSink<RecordingSink> =
+            BufferedSink::new(RecordingSink::default(), policy);
+
+        sink.write(&post(1))?;
+        assert!(sink.get_inner().written.is_empty());
+
+        sink.write(&trade())?;
+        assert_eq!(sink.get_inner().written.len(), 2);
+        assert_eq!(sink.get_inner().flushes, 1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_flush_by_interval() -> Result<(), SinkError> {
+        let policy: FlushPolicy = FlushPolicy {
+            max_interval: Some(Duration::from_millis(0)),
+            ..FlushPolicy::default()
+        };
+        let mut sink: BufferedSink<RecordingSink> =
+            BufferedSink::new(RecordingSink::default(), policy);
+
+        sink.write(&post(1))?;
+        assert_eq!(sink.get_inner().written.len(), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_explicit_flush() -> Result<(), SinkError> {
+        let mut sink: BufferedSink<RecordingSink> =
+            BufferedSink::new(RecordingSink::default(),
+                              FlushPolicy::default());
+
+        sink.write(&post(1))?;
+        sink.write(&trade())?;
+        assert!(sink.get_inner().written.is_empty());
+
+        sink.flush()?;
+        assert_eq!(sink.get_inner().written.len(), 2);
+        assert_eq!(sink.metrics().flushes, 1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_retry_failed_flush() -> Result<(), SinkError> {
+        let mut sink: BufferedSink<RecordingSink> =
+            BufferedSink::new(RecordingSink::default(),
+                              FlushPolicy {
+                                  max_bytes: Some(usize::MAX),
+                                  ..FlushPolicy::default()
+                              });
+
+        for id in 1..=3 {
+            sink.write(&post(id))?;
+        }
+
+        let size: usize = sink.metrics().buffered_bytes;
+
+        /* the inner sink takes two events, then refuses the third */
+        sink.inner.capacity = Some(2);
+        assert!(sink.flush().is_err());
+        assert_eq!(sink.get_inner().written.len(), 2);
+
+        /* then takes the third, but cannot flush */
+        sink.inner.capacity = None;
+        sink.inner.refuse_flushes = true;
+        assert!(sink.flush().is_err());
+        assert_eq!(sink.get_inner().written.len(), 3);
+        assert_eq!(sink.metrics().buffered_events, 3);
+        assert_eq!(sink.metrics().buffered_bytes, size);
+
+        /* nothing is written twice once it can */
+        sink.inner.refuse_flushes = false;
+        sink.flush()?;
+        assert_eq!(sink.get_inner().written.iter().map(Event::get_seq)
+                       .collect::<Vec<u64>>(), vec![1, 2, 3]);
+        assert_eq!(sink.metrics().buffered_events, 0);
+        assert_eq!(sink.metrics().buffered_bytes, 0);
+        assert_eq!(sink.metrics().flushed_events, 3);
+        assert!(sink.metrics().lag.is_zero());
+        Ok(())
+    }
+
+    #[test]
+    fn test_memory_sink_capacity() -> Result<(), SinkError> {
+        let mut sink: MemorySink = MemorySink::with_capacity(3);
+
+        for id in 1..=10 {
+            sink.write(&post(id))?;
+        }
+
+        let actual_seqs: Vec<u64> = sink.get_events().iter()
+            .map(Event::get_seq)
+            .collect();
+
+        assert_eq!(actual_seqs, vec![8, 9, 10]);
+        Ok(())
+    }
+
+    fn wal_path(name: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("ironlobe-{}-{}.wal", name,
+                                          std::process::id()))
+    }
+
+    fn wal_round_trip(format: WalFormat) -> Result<(), SinkError> {
+        let path = wal_path(&format!("{:?}", format));
+        let expected_events: Vec<Event> = vec![post(1), trade(), post(2)];
+
+        {
+            let mut sink: WalSink = WalSink::open(&path, format,
+                                                  SyncPolicy::OnFlush)?;
+
+            for event in expected_events.iter() {
+                sink.write(event)?;
+            }
+
+            sink.flush()?;
+        }
+
+        /* simulate a crash part way through appending another event */
+        let mut file: File = OpenOptions::new().append(true).open(&path)?;
+        file.write_all(&[8, 0, 0, 0, b'{'])?;
+
+        let actual_events: Vec<Event> = read_wal(&path, format)?;
+        std::fs::remove_file(&path)?;
+
+        assert_eq!(actual_events, expected_events);
+        Ok(())
+    }
+
+    #[test]
+    fn test_wal_json_lines_round_trip() -> Result<(), SinkError> {
+        wal_round_trip(WalFormat::JsonLines)
+    }
+
+    #[test]
+    fn test_wal_framed_round_trip() -> Result<(), SinkError> {
+        wal_round_trip(WalFormat::Framed)
+    }
+
+    #[test]
+    fn test_wal_binary_round_trip() -> Result<(), SinkError> {
+        wal_round_trip(WalFormat::Binary)
+    }
+
+    #[test]
+    fn test_wal_corrupt_length() -> Result<(), SinkError> {
+        let path = wal_path("corrupt");
+        let expected_event: Event = post(1);
+
+        {
+            let mut sink: WalSink = WalSink::open(&path, WalFormat::Framed,
+                                                  SyncPolicy::OnFlush)?;
+
+            sink.write(&expected_event)?;
+            sink.flush()?;
+        }
+
+        /* a header claiming almost 4 GiB, with next to nothing after it */
+        let mut file: File = OpenOptions::new().append(true).open(&path)?;
+        file.write_all(&[0xff, 0xff, 0xff, 0xff, b'{'])?;
+
+        let actual_events: Vec<Event> = read_wal(&path, WalFormat::Framed)?;
+        std::fs::remove_file(&path)?;
+
+        assert_eq!(actual_events, vec![expected_event]);
+        Ok(())
+    }
+}
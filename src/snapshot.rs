@@ -0,0 +1,31 @@
+use serde::{Serialize, Deserialize};
+
+use crate::account::Account;
+use crate::book::BookId;
+use crate::builder::BookConfig;
+use crate::order::{Order, OrderId};
+use crate::peg::Peg;
+use crate::session::SessionState;
+
+/* Everything needed to rebuild a book as it stood: its configuration and
+ * resting orders, each side best price first and in time priority. The
+ * event log and trade tape are not included. */
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BookSnapshot {
+    pub id: BookId,
+    pub name: String,
+    pub ticker: String,
+    pub config: BookConfig,
+    pub state: SessionState,
+    pub ltp: Option<f64>,
+    /* the sequence number of the last event before the snapshot */
+    pub last_seq: u64,
+    pub bids: Vec<Order>,
+    pub asks: Vec<Order>,
+    /* the terms of those orders that are pegged */
+    #[serde(default)]
+    pub pegs: Vec<(OrderId, Peg)>,
+    /* the book's ledger, by account ID; see `Book::get_account` */
+    #[serde(default)]
+    pub accounts: Vec<Account>
+}
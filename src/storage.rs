@@ -0,0 +1,365 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+use thiserror::Error;
+
+use crate::batch::{BookOp, BookOpOutcome};
+use crate::binary::{self, BinaryError};
+use crate::book::{Book, BookError};
+use crate::builder::{BookBuilder, BuildError};
+use crate::clock::SystemClock;
+use crate::event::{Event, EventKind, Trade};
+use crate::matching::PriceTime;
+use crate::order::Order;
+use crate::sink::{EventSink, SinkError};
+use crate::snapshot::BookSnapshot;
+
+#[derive(Debug, Error)]
+pub enum StorageError {
+    #[error("database error: {0}")]
+    Database(#[from] sled::Error),
+    #[error("encoding error: {0}")]
+    Encoding(#[from] serde_json::Error),
+    #[error(transparent)]
+    Binary(#[from] BinaryError),
+    #[error(transparent)]
+    Book(#[from] BookError),
+    #[error(transparent)]
+    Build(#[from] BuildError),
+    #[error("there is already a book for {0}")]
+    DuplicateBook(String),
+    #[error("no book for {0}")]
+    UnknownBook(String),
+}
+
+/* the books an `Exchange` keeps */
+pub type StoredBook = Book<PriceTime, StoreSink>;
+
+/* Everything is keyed on the book's ticker, then a NUL, then big-endian
+ * numbers, so that each book's entries sort together and in order:
+ *
+ *  - snapshots: ticker => the book's latest snapshot
+ *  - ops:       ticker, last sequence number before it, ID => operation
+ *  - events:    ticker, sequence number => event
+ *  - trades:    ticker, sequence number of its event => trade */
+#[derive(Debug, Clone)]
+struct Trees {
+    snapshots: sled::Tree,
+    ops: sled::Tree,
+    events: sled::Tree,
+    trades: sled::Tree
+}
+
+impl Trees {
+    fn open(db: &sled::Db) -> Result<Trees, sled::Error> {
+        Ok(Trees {
+            snapshots: db.open_tree("snapshots")?,
+            ops: db.open_tree("ops")?,
+            events: db.open_tree("events")?,
+            trades: db.open_tree("trades")?
+        })
+    }
+}
+
+fn prefix(ticker: &str) -> Vec<u8> {
+    let mut key: Vec<u8> = ticker.as_bytes().to_vec();
+    key.push(0);
+    key
+}
+
+/* just past every key of `ticker`'s */
+fn prefix_end(ticker: &str) -> Vec<u8> {
+    let mut key: Vec<u8> = ticker.as_bytes().to_vec();
+    key.push(1);
+    key
+}
+
+fn key(ticker: &str, numbers: &[u64]) -> Vec<u8> {
+    let mut key: Vec<u8> = prefix(ticker);
+
+    for number in numbers {
+        key.extend_from_slice(&number.to_be_bytes());
+    }
+
+    key
+}
+
+/* the sequence number at the end of an event key */
+fn seq_of(key: &[u8]) -> u64 {
+    let mut seq: [u8; 8] = [0; 8];
+
+    if let Some(tail) = key.len().checked_sub(8).and_then(|start|
+                                                          key.get(start..)) {
+        seq.copy_from_slice(tail);
+    }
+
+    u64::from_be_bytes(seq)
+}
+
+/* keeps `snapshot` as its book's latest, dropping the operations it makes
+ * redundant */
+fn save_snapshot(trees: &Trees, snapshot: &BookSnapshot) ->
+    Result<(), StorageError> {
+    let ticker: &str = &snapshot.ticker;
+
+    trees.snapshots.insert(ticker.as_bytes(), binary::encode(snapshot)?)?;
+
+    for entry in trees.ops.range(prefix(ticker)..key(ticker,
+                                                     &[snapshot.last_seq])) {
+        let (op, _) = entry?;
+        trees.ops.remove(op)?;
+    }
+
+    Ok(())
+}
+
+fn database(error: sled::Error) -> SinkError {
+    SinkError::Io(error.into())
+}
+
+/* Writes a book's events, and the trades among them, to an `Exchange`'s
+ * database, and keeps the snapshots the book offers when it compacts.
+ * Events it already has, as when a book is being rebuilt by replaying its
+ * operations, are not written again. */
+#[derive(Debug, Clone)]
+pub struct StoreSink {
+    trees: Trees,
+    ticker: String,
+    /* the last sequence number written */
+    stored: u64
+}
+
+impl StoreSink {
+    fn new(trees: Trees, ticker: String) -> Result<StoreSink, StorageError> {
+        let stored: u64 = match trees.events.scan_prefix(prefix(&ticker))
+            .next_back() {
+            Some(entry) => seq_of(&entry?.0),
+            None => 0
+        };
+
+        Ok(StoreSink {trees, ticker, stored})
+    }
+
+    pub fn get_ticker(&self) -> &str {
+        &self.ticker
+    }
+}
+
+impl EventSink for StoreSink {
+    fn write(&mut self, event: &Event) -> Result<(), SinkError> {
+        if event.get_seq() <= self.stored {
+            return Ok(());
+        }
+
+        let key: Vec<u8> = key(&self.ticker, &[event.get_seq()]);
+
+        if let EventKind::Match(trade) = event.get_kind() {
+            self.trees.trades.insert(key.as_slice(), binary::encode(trade)?)
+                .map_err(database)?;
+        }
+
+        self.trees.events.insert(key, binary::encode(event)?)
+            .map_err(database)?;
+        self.stored = event.get_seq();
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), SinkError> {
+        self.trees.events.flush().map_err(database)?;
+        Ok(())
+    }
+
+    fn compact(&mut self, snapshot: &BookSnapshot) -> Result<(), SinkError> {
+        save_snapshot(&self.trees, snapshot).map_err(|error| match error {
+            StorageError::Database(error) => database(error),
+            error => SinkError::Encoding(error.to_string())
+        })
+    }
+}
+
+/* Books whose state survives a restart. Every operation is written to an
+ * embedded database before it is carried out, and every event and trade
+ * as it happens, along with each book's snapshots; `open` rebuilds the
+ * books from their latest snapshots and the operations since. Books that
+ * compact (see `BookConfig::compact_every`), or calls to `checkpoint`, keep
+ * that replay short. */
+#[derive(Debug)]
+pub struct Exchange {
+    db: sled::Db,
+    trees: Trees,
+    books: HashMap<String, StoredBook>
+}
+
+impl Exchange {
+    /* opens the database at `path`, creating it if there is none, and
+     * restores every book in it */
+    pub fn open<Q: AsRef<Path>>(path: Q) -> Result<Exchange, StorageError> {
+        let db: sled::Db = sled::open(path)?;
+        let trees: Trees = Trees::open(&db)?;
+        let mut books: HashMap<String, StoredBook> = HashMap::new();
+
+        for entry in trees.snapshots.iter() {
+            let snapshot: BookSnapshot = binary::decode(&entry?.1)?;
+            let ticker: String = snapshot.ticker.clone();
+            let start: Vec<u8> = key(&ticker, &[snapshot.last_seq]);
+            let ops: Vec<BookOp> = trees.ops.range(start..prefix_end(&ticker))
+                .map(|entry| Ok(serde_json::from_slice(&entry?.1)?))
+                .collect::<Result<Vec<BookOp>, StorageError>>()?;
+            let mut book: StoredBook = Exchange::restore(&trees, snapshot)?;
+
+            for op in ops {
+                /* an operation that failed fails again, just the same */
+                let _ = book.apply(op);
+            }
+
+            books.insert(ticker, book);
+        }
+
+        Ok(Exchange {db, trees, books})
+    }
+
+    fn restore(trees: &Trees, snapshot: BookSnapshot) ->
+        Result<StoredBook, StorageError> {
+        let sink: StoreSink = StoreSink::new(trees.clone(),
+                                             snapshot.ticker.clone())?;
+
+        Ok(Book::restore(snapshot, PriceTime, sink, SystemClock)?)
+    }
+
+    /* builds a new, empty book to keep */
+    pub fn add_book(&mut self, builder: BookBuilder) ->
+        Result<&StoredBook, StorageError> {
+        let snapshot: BookSnapshot = builder.build()?.snapshot();
+        let ticker: String = snapshot.ticker.clone();
+
+        if self.books.contains_key(&ticker) {
+            return Err(StorageError::DuplicateBook(ticker));
+        }
+
+        save_snapshot(&self.trees, &snapshot)?;
+
+        let book: StoredBook = Exchange::restore(&self.trees, snapshot)?;
+
+        Ok(self.books.entry(ticker).or_insert(book))
+    }
+
+    pub fn get_book(&self, ticker: &str) -> Option<&StoredBook> {
+        self.books.get(ticker)
+    }
+
+    pub fn tickers(&self) -> Vec<String> {
+        let mut tickers: Vec<String> = self.books.keys().cloned().collect();
+        tickers.sort();
+        tickers
+    }
+
+    /* records `op`, then carries it out on the book for `ticker` */
+    pub fn apply(&mut self, ticker: &str, op: BookOp) ->
+        Result<BookOpOutcome, StorageError> {
+        let book: &mut StoredBook = self.books.get_mut(ticker)
+            .ok_or_else(|| StorageError::UnknownBook(ticker.to_string()))?;
+        let key: Vec<u8> = key(ticker, &[book.last_seq(),
+                                         self.db.generate_id()?]);
+
+        self.trees.ops.insert(key, serde_json::to_vec(&op)?)?;
+        Ok(book.apply(op)?)
+    }
+
+    /* submits `order` to the book for its ticker */
+    pub fn submit(&mut self, order: Order) -> Result<(), StorageError> {
+        let ticker: String = order.get_ticker();
+
+        self.apply(&ticker, BookOp::Submit(Box::new(order)))?;
+        Ok(())
+    }
+
+    /* snapshots every book, so that the next `open` need replay nothing
+     * before now, and makes sure it is all on disk */
+    pub fn checkpoint(&mut self) -> Result<(), StorageError> {
+        for book in self.books.values() {
+            save_snapshot(&self.trees, &book.snapshot())?;
+        }
+
+        self.flush()
+    }
+
+    pub fn flush(&self) -> Result<(), StorageError> {
+        self.db.flush()?;
+        Ok(())
+    }
+
+    /* the events of the book for `ticker` after `seq`, in order */
+    pub fn events_since(&self, ticker: &str, seq: u64) ->
+        Result<Vec<Event>, StorageError> {
+        self.trees.events.range(key(ticker, &[seq + 1])..prefix_end(ticker))
+            .map(|entry| Ok(binary::decode(&entry?.1)?))
+            .collect()
+    }
+
+    /* every trade in the book for `ticker`, in order */
+    pub fn trades(&self, ticker: &str) -> Result<Vec<Trade>, StorageError> {
+        self.trees.trades.scan_prefix(prefix(ticker))
+            .map(|entry| Ok(binary::decode(&entry?.1)?))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+    use crate::account::Account;
+    use crate::levels::Levels;
+    use crate::order::{OrderId, OrderType};
+    use crate::quantity::Quantity;
+
+    fn build_order(id: OrderId, order_type: OrderType, price: f64,
+                   quantity: Quantity) -> Order {
+        let mut holdings: HashMap<String, Quantity> = HashMap::new();
+        holdings.insert("BOOK".to_string(), 1000);
+
+        let owner: Account = Account::new(id, "Account".to_string(),
+                                          12000.00, holdings);
+
+        Order::new(id, owner, "BOOK".to_string(), order_type, price, quantity)
+    }
+
+    #[test]
+    fn test_reopen() -> Result<(), StorageError> {
+        let path: PathBuf = std::env::temp_dir()
+            .join(format!("ironlobe-storage-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&path);
+
+        let expected: Levels = {
+            let mut exchange: Exchange = Exchange::open(&path)?;
+
+            exchange.add_book(BookBuilder::new(1, "BOOK".to_string()))?;
+            exchange.submit(build_order(1, OrderType::Ask, 12.00, 10))?;
+            exchange.submit(build_order(2, OrderType::Bid, 12.00, 4))?;
+            exchange.checkpoint()?;
+            exchange.submit(build_order(3, OrderType::Bid, 11.00, 10))?;
+            exchange.apply("BOOK", BookOp::Cancel(1))?;
+
+            assert!(matches!(exchange.submit(build_order(3, OrderType::Bid,
+                                                         11.00, 10)),
+                             Err(StorageError::Book(
+                                 BookError::DuplicateOrderId))));
+            exchange.flush()?;
+            exchange.get_book("BOOK").map(Book::levels).unwrap_or_default()
+        };
+
+        let exchange: Exchange = Exchange::open(&path)?;
+        let book: Option<&StoredBook> = exchange.get_book("BOOK");
+
+        assert_eq!(book.map(Book::levels), Some(expected));
+        assert_eq!(book.map(Book::last_seq),
+                   exchange.events_since("BOOK", 0)?.last()
+                       .map(Event::get_seq));
+        assert_eq!(exchange.trades("BOOK")?.len(), 1);
+        assert!(exchange.get_book("NONE").is_none());
+
+        drop(exchange);
+        std::fs::remove_dir_all(&path).ok();
+        Ok(())
+    }
+}
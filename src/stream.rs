@@ -0,0 +1,189 @@
+use std::pin::Pin;
+
+use tokio::sync::broadcast;
+use tokio_stream::wrappers::BroadcastStream;
+use tokio_stream::wrappers::errors::BroadcastStreamRecvError;
+use tokio_stream::{Stream, StreamExt};
+
+use crate::book::Book;
+use crate::clock::Clock;
+use crate::event::{Event, EventKind, Trade};
+use crate::levels::TopOfBook;
+use crate::matching::MatchingPolicy;
+use crate::price::PriceType;
+use crate::sink::{EventSink, MemorySink, SinkError};
+use crate::snapshot::BookSnapshot;
+
+/* how many events a subscriber may fall behind by default */
+pub const DEFAULT_STREAM_CAPACITY: usize = 1024;
+
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum StreamError {
+    /* the subscriber fell so far behind that these were dropped */
+    #[error("fell {0} events behind")]
+    Lagged(u64),
+}
+
+/* what the book's streams yield; a subscriber that lags gets an error in
+ * place of what it missed, and carries on from there */
+pub type BookStream<T> =
+    Pin<Box<dyn Stream<Item = Result<T, StreamError>> + Send>>;
+
+/* Passes each event on to `inner` and to every stream subscribed to it
+ * at the time. Streams are fed from a broadcast channel, so a slow one
+ * doesn't hold up the book or the others, but misses events once it falls
+ * more than the channel's capacity behind. */
+#[derive(Debug)]
+pub struct BroadcastSink<S: EventSink = MemorySink> {
+    inner: S,
+    sender: broadcast::Sender<Event>
+}
+
+impl<S: EventSink> BroadcastSink<S> {
+    pub fn new(inner: S, capacity: usize) -> BroadcastSink<S> {
+        let (sender, _) = broadcast::channel(capacity.max(1));
+
+        BroadcastSink {inner, sender}
+    }
+
+    pub fn get_inner(&self) -> &S {
+        &self.inner
+    }
+
+    pub fn get_inner_mut(&mut self) -> &mut S {
+        &mut self.inner
+    }
+
+    /* number of streams currently subscribed */
+    pub fn subscribers(&self) -> usize {
+        self.sender.receiver_count()
+    }
+
+    /* every event from now on */
+    pub fn event_stream(&self) -> BookStream<Event> {
+        self.filtered(Some)
+    }
+
+    /* the events from now on that `select` picks something out of */
+    pub fn filtered<T, F>(&self, select: F) -> BookStream<T>
+    where T: Send + 'static, F: Fn(Event) -> Option<T> + Send + 'static {
+        Box::pin(BroadcastStream::new(self.sender.subscribe())
+            .filter_map(move |received| match received {
+                Ok(event) => select(event).map(Ok),
+                Err(BroadcastStreamRecvError::Lagged(missed)) =>
+                    Some(Err(StreamError::Lagged(missed)))
+            }))
+    }
+}
+
+impl<S: EventSink + Default> Default for BroadcastSink<S> {
+    fn default() -> BroadcastSink<S> {
+        BroadcastSink::new(S::default(), DEFAULT_STREAM_CAPACITY)
+    }
+}
+
+impl<S: EventSink> EventSink for BroadcastSink<S> {
+    fn write(&mut self, event: &Event) -> Result<(), SinkError> {
+        self.inner.write(event)?;
+
+        /* nobody listening is not an error */
+        let _ = self.sender.send(event.clone());
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), SinkError> {
+        self.inner.flush()
+    }
+
+    fn compact(&mut self, snapshot: &BookSnapshot) -> Result<(), SinkError> {
+        self.inner.compact(snapshot)
+    }
+}
+
+/* Books whose sink is a `BroadcastSink` can be subscribed to directly,
+ * e.g. to fan market data out to async consumers. */
+impl<M, S, C, P> Book<M, BroadcastSink<S>, C, P>
+where M: MatchingPolicy, S: EventSink, C: Clock, P: PriceType {
+    /* every event the book publishes from now on */
+    pub fn event_stream(&self) -> BookStream<Event> {
+        self.get_sink().event_stream()
+    }
+
+    /* the book's trades from now on */
+    pub fn trade_stream(&self) -> BookStream<Trade> {
+        self.get_sink().filtered(|event| match event.get_kind() {
+            EventKind::Match(trade) => Some(*trade),
+            _ => None
+        })
+    }
+
+    /* the best bid and offer, each time either changes */
+    pub fn bbo_stream(&self) -> BookStream<TopOfBook> {
+        self.get_sink().filtered(|event| match event.get_kind() {
+            EventKind::TopOfBook { current, .. } => Some(*current),
+            _ => None
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use crate::account::Account;
+    use crate::matching::PriceTime;
+    use crate::order::{Order, OrderId, OrderType};
+    use crate::quantity::Quantity;
+
+    fn build_order(id: OrderId, order_type: OrderType, price: f64,
+                   quantity: Quantity) -> Order {
+        let mut holdings: HashMap<String, Quantity> = HashMap::new();
+        holdings.insert("BOOK".to_string(), 1000);
+
+        let owner: Account = Account::new(id, "Account".to_string(),
+                                          12000.00, holdings);
+
+        Order::new(id, owner, "BOOK".to_string(), order_type, price, quantity)
+    }
+
+    #[tokio::test]
+    async fn test_streams() -> crate::Result<()> {
+        let mut book: Book<PriceTime, BroadcastSink> =
+            Book::builder(1, "BOOK".to_string())
+            .sink(BroadcastSink::default())
+            .build()?;
+        let events: BookStream<Event> = book.event_stream();
+        let trades: BookStream<Trade> = book.trade_stream();
+        let tops: BookStream<TopOfBook> = book.bbo_stream();
+
+        book.submit(build_order(1, OrderType::Ask, 12.00, 10))?;
+        book.submit(build_order(2, OrderType::Bid, 11.50, 5))?;
+        book.submit(build_order(3, OrderType::Bid, 12.00, 4))?;
+
+        let published: usize = book.get_sink().get_inner().get_events().len();
+        let events: Vec<Result<Event, StreamError>> =
+            events.take(published).collect().await;
+        let trades: Vec<Result<Trade, StreamError>> =
+            trades.take(1).collect().await;
+        let tops: Vec<Result<TopOfBook, StreamError>> =
+            tops.take(3).collect().await;
+
+        assert_eq!(events.len(), published);
+        assert_eq!(trades[0].as_ref().map(Trade::get_quantity), Ok(4));
+        assert_eq!(tops[2], Ok(TopOfBook::new(Some((11.50, 5)),
+                                               Some((12.00, 6)))));
+
+        /* a subscriber that falls behind is told how far */
+        let mut small: BroadcastSink = BroadcastSink::new(MemorySink::new(),
+                                                          1);
+        let mut lagging: BookStream<Event> = small.event_stream();
+
+        for event in book.get_sink().get_inner().get_events() {
+            small.write(event)?;
+        }
+
+        assert!(matches!(lagging.next().await,
+                         Some(Err(StreamError::Lagged(_)))));
+        Ok(())
+    }
+}
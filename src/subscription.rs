@@ -0,0 +1,206 @@
+use std::collections::HashMap;
+use std::sync::mpsc::{self, Receiver, Sender};
+
+use serde::{Serialize, Deserialize};
+
+use crate::account::AccountId;
+use crate::event::{Event, EventKind};
+use crate::order::{Order, OrderId, OrderType};
+
+/* Which of a book's events a subscriber wants (see `Book::subscribe`).
+ * Filters are evaluated by the book as it publishes, so nothing is cloned
+ * or sent for a subscriber that would only throw it away. */
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum EventFilter {
+    Everything,
+    /* trades, and busts of them */
+    Trades,
+    /* changes to the best `n` prices on either side, as they stand after
+     * the change (a level emptied from the top counts), along with the
+     * trades and top of book changes */
+    TopLevels(usize),
+    /* events about the account's own orders */
+    Account(AccountId)
+}
+
+/* the side and price of the level an event changes, if it changes one */
+fn level(kind: &EventKind) -> Option<(OrderType, f64)> {
+    match kind {
+        EventKind::Post { order_type, price, .. } |
+        EventKind::Cancel { order_type, price, .. } |
+        EventKind::Amend { order_type, price, .. } |
+        EventKind::Reduce { order_type, price, .. } |
+        EventKind::Reprice { order_type, price, .. } =>
+            Some((*order_type, *price)),
+        _ => None
+    }
+}
+
+/* the orders an event is about */
+fn orders(kind: &EventKind) -> Vec<OrderId> {
+    match kind {
+        EventKind::Post { order, .. } | EventKind::Cancel { order, .. } |
+        EventKind::Amend { order, .. } | EventKind::Reduce { order, .. } |
+        EventKind::Reprice { order, .. } | EventKind::Route { order, .. } |
+        EventKind::StopTriggered { order, .. } |
+        EventKind::StopCancelled { order, .. } => vec![*order],
+        EventKind::Match(trade) | EventKind::TradeBust(trade) =>
+            vec![trade.get_aggressor(), trade.get_resting()],
+        EventKind::BracketArmed { parent, .. } => vec![*parent],
+        _ => vec![]
+    }
+}
+
+fn passes<F>(filter: EventFilter, event: &Event,
+             owners: &HashMap<OrderId, AccountId>, nth: &F) -> bool
+where F: Fn(OrderType, usize) -> Option<f64> {
+    let kind: &EventKind = event.get_kind();
+
+    match filter {
+        EventFilter::Everything => true,
+        EventFilter::Trades =>
+            matches!(kind, EventKind::Match(_) | EventKind::TradeBust(_)),
+        EventFilter::TopLevels(n) => match level(kind) {
+            Some(_) if n == 0 => false,
+            Some((side, price)) => match (side, nth(side, n)) {
+                (OrderType::Bid, Some(last)) => price >= last,
+                (OrderType::Ask, Some(last)) => price <= last,
+                (_, None) => true
+            },
+            None => matches!(kind, EventKind::Match(_) |
+                                   EventKind::TradeBust(_) |
+                                   EventKind::TopOfBook { .. })
+        },
+        EventFilter::Account(account) => match kind {
+            EventKind::BuyingPowerReject { account: owner, .. } =>
+                *owner == account,
+            _ => orders(kind).iter()
+                .any(|order| owners.get(order) == Some(&account))
+        }
+    }
+}
+
+/* A book's subscribers, each with its filter, and the owners of the orders
+ * that any `Account` filter needs to know about. */
+#[derive(Debug, Default)]
+pub(crate) struct Subscribers {
+    subscribers: Vec<(EventFilter, Sender<Event>)>,
+    owners: HashMap<OrderId, AccountId>
+}
+
+impl Subscribers {
+    pub(crate) fn subscribe(&mut self, filter: EventFilter) ->
+        Receiver<Event> {
+        let (sender, receiver) = mpsc::channel();
+
+        self.subscribers.push((filter, sender));
+        receiver
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.subscribers.len()
+    }
+
+    fn watches(&self, account: AccountId) -> bool {
+        self.subscribers.iter()
+            .any(|(filter, _)| *filter == EventFilter::Account(account))
+    }
+
+    /* notes who owns `order`, if anyone is watching them */
+    pub(crate) fn track(&mut self, order: &Order) {
+        let owner: AccountId = order.get_owner_ref().get_id();
+
+        if self.watches(owner) {
+            self.owners.insert(order.get_id(), owner);
+        }
+    }
+
+    /* Sends `event` to each subscriber whose filter it passes, given the
+     * `n`th best price on each side of the book as it now stands (none if
+     * there are fewer than `n`), and drops those that have gone away. */
+    pub(crate) fn dispatch<F>(&mut self, event: &Event, nth: F)
+    where F: Fn(OrderType, usize) -> Option<f64> {
+        if self.subscribers.is_empty() {
+            return;
+        }
+
+        let owners: &HashMap<OrderId, AccountId> = &self.owners;
+
+        self.subscribers.retain(|(filter, sender)| {
+            !passes(*filter, event, owners, &nth) ||
+                sender.send(event.clone()).is_ok()
+        });
+    }
+}
+
+#[cfg(all(test, not(feature = "decimal-quantity")))]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use crate::account::Account;
+    use crate::book::{Book, BookError};
+    use crate::quantity::Quantity;
+
+    fn build_order(id: OrderId, owner: AccountId, order_type: OrderType,
+                   price: f64, quantity: Quantity) -> Order {
+        let mut holdings: HashMap<String, Quantity> = HashMap::new();
+        holdings.insert("BOOK".to_string(), 1000);
+
+        let owner: Account = Account::new(owner, "Account".to_string(),
+                                          12000.00, holdings);
+
+        Order::new(id, owner, "BOOK".to_string(), order_type, price, quantity)
+    }
+
+    #[test]
+    fn test_filters() -> Result<(), BookError> {
+        let mut book: Book = Book::new(1, "Book".to_string(),
+                                       "BOOK".to_string());
+
+        book.submit(build_order(1, 1, OrderType::Ask, 12.00, 10))?;
+
+        let everything: Receiver<Event> =
+            book.subscribe(EventFilter::Everything);
+        let trades: Receiver<Event> = book.subscribe(EventFilter::Trades);
+        let top: Receiver<Event> = book.subscribe(EventFilter::TopLevels(1));
+        let mine: Receiver<Event> = book.subscribe(EventFilter::Account(1));
+
+        assert_eq!(book.subscribers(), 4);
+
+        book.submit(build_order(2, 2, OrderType::Ask, 12.50, 10))?;
+        book.submit(build_order(3, 2, OrderType::Bid, 11.00, 10))?;
+        book.submit(build_order(4, 2, OrderType::Bid, 10.00, 10))?;
+        book.submit(build_order(5, 2, OrderType::Bid, 12.00, 4))?;
+        book.cancel(1)?;
+
+        let kinds = |receiver: &Receiver<Event>| receiver.try_iter()
+            .map(|event| event.get_kind().clone())
+            .collect::<Vec<EventKind>>();
+        let top: Vec<EventKind> = kinds(&top);
+
+        assert_eq!(everything.try_iter().count(),
+                   book.get_events().len() - 2);
+        assert!(matches!(kinds(&trades).as_slice(), [EventKind::Match(_)]));
+
+        /* the ask behind the best and the bid behind the best are left out,
+         * but the cancel that empties the best ask is not */
+        assert!(!top.iter().any(|kind| matches!(kind,
+            EventKind::Post { order: 2, .. } |
+            EventKind::Post { order: 4, .. })));
+        assert!(top.iter().any(|kind| matches!(kind,
+            EventKind::Cancel { order: 1, .. })));
+
+        /* the resting order from before subscribing is tracked too */
+        assert!(matches!(kinds(&mine).as_slice(),
+                         [EventKind::Match(_), EventKind::Cancel { .. }]));
+
+        /* a subscriber that goes away is forgotten once there is
+         * something to send it */
+        drop(trades);
+        book.submit(build_order(6, 2, OrderType::Ask, 13.00, 1))?;
+        assert_eq!(book.subscribers(), 4);
+        book.submit(build_order(7, 2, OrderType::Ask, 11.00, 1))?;
+        assert_eq!(book.subscribers(), 3);
+        Ok(())
+    }
+}
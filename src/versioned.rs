@@ -0,0 +1,177 @@
+use std::convert::TryFrom;
+
+use serde::{Serialize, Deserialize};
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+
+use crate::binary::VERSION;
+use crate::event::{Event, Trade};
+use crate::order::Order;
+use crate::snapshot::BookSnapshot;
+
+/* A self-describing JSON encoding for what gets persisted for the long
+ * term, meant to outlive upgrades:
+ *
+ *     {"version": 7, "data": { ... }}
+ *
+ * The version is the same as `binary::VERSION`. Reading something written
+ * by an older version brings it up to date before deserializing it, so
+ * event logs and snapshots stay loadable; reading something from a newer
+ * version is refused rather than misread. A bare value with no envelope is
+ * taken to be from version 1, before there was one. */
+
+#[derive(Debug, thiserror::Error)]
+pub enum VersionError {
+    #[error("written by a newer version ({0}) than this one reads")]
+    Unsupported(u16),
+    #[error("JSON error: {0}")]
+    Json(#[from] serde_json::Error),
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Versioned<T> {
+    pub version: u16,
+    pub data: T
+}
+
+/* Things with a versioned encoding. A field added since `from` that is
+ * `#[serde(default)]` needs nothing done here; `upgrade` is for those
+ * that aren't, and for renames and changes of type. */
+pub trait Schema: Serialize + DeserializeOwned {
+    fn upgrade(data: &mut Value, from: u16) {
+        let _ = (data, from);
+    }
+}
+
+impl Schema for Order {
+    fn upgrade(data: &mut Value, from: u16) {
+        /* orders written before they kept their original quantity have
+         * only what was left of them to go on */
+        if from <= 1 {
+            if let Some(order) = data.as_object_mut() {
+                if !order.contains_key("original_quantity") {
+                    let quantity: Value = order.get("quantity").cloned()
+                        .unwrap_or(Value::Null);
+
+                    order.insert("original_quantity".to_string(), quantity);
+                }
+            }
+        }
+    }
+}
+
+impl Schema for BookSnapshot {
+    fn upgrade(data: &mut Value, from: u16) {
+        for side in ["bids", "asks"] {
+            if let Some(orders) = data.get_mut(side)
+                .and_then(Value::as_array_mut) {
+                orders.iter_mut().for_each(|order| Order::upgrade(order,
+                                                                  from));
+            }
+        }
+    }
+}
+
+impl Schema for Event {}
+
+impl Schema for Trade {}
+
+impl Schema for Vec<Event> {
+    fn upgrade(data: &mut Value, from: u16) {
+        if let Some(events) = data.as_array_mut() {
+            events.iter_mut().for_each(|event| Event::upgrade(event, from));
+        }
+    }
+}
+
+pub fn to_json<T: Schema>(value: &T) -> Result<String, VersionError> {
+    Ok(serde_json::to_string(&Versioned {
+        version: VERSION,
+        data: value
+    })?)
+}
+
+pub fn from_json<T: Schema>(json: &str) -> Result<T, VersionError> {
+    let value: Value = serde_json::from_str(json)?;
+    let (version, mut data) = match value {
+        Value::Object(mut envelope) if envelope.len() == 2 &&
+            envelope.contains_key("data") => {
+            let version: u64 = envelope.get("version")
+                .and_then(Value::as_u64)
+                .unwrap_or(1);
+            let data: Value = envelope.remove("data").unwrap_or(Value::Null);
+
+            (u16::try_from(version).unwrap_or(u16::MAX), data)
+        },
+        data => (1, data)
+    };
+
+    if version > VERSION {
+        return Err(VersionError::Unsupported(version));
+    }
+
+    T::upgrade(&mut data, version);
+    Ok(serde_json::from_value(data)?)
+}
+
+#[cfg(all(test, not(feature = "decimal-quantity")))]
+mod tests {
+    use super::*;
+    use crate::event::EventKind;
+    use crate::order::OrderType;
+
+    #[test]
+    fn test_round_trip() -> Result<(), VersionError> {
+        let event: Event = Event::new(3, chrono::Utc::now(),
+                                      EventKind::Settlement { price: 12.5 });
+        let json: String = to_json(&event)?;
+
+        assert!(json.starts_with(&format!("{{\"version\":{},", VERSION)));
+        assert_eq!(from_json::<Event>(&json)?, event);
+        Ok(())
+    }
+
+    #[test]
+    fn test_old_versions() -> Result<(), VersionError> {
+        /* from before events had actors, reasons or transitions */
+        let event: Event = from_json(r#"{"version": 1, "data": {
+            "seq": 1,
+            "timestamp": "2024-01-02T09:30:00Z",
+            "kind": {"Cancel": {"order": 1, "order_type": "Bid",
+                                "price": 12.0, "quantity": 10}}
+        }}"#)?;
+
+        assert_eq!(event.get_reason(), None);
+        assert!(event.get_transitions().is_empty());
+
+        /* unversioned, from before snapshots had pegs and orders kept
+         * their original quantity */
+        let snapshot: BookSnapshot = from_json(r#"{
+            "id": 1, "name": "Book", "ticker": "BOOK",
+            "config": {"tick_size": null, "lot_size": null,
+                       "price_band": null, "circuit_breaker": null},
+            "state": "Continuous", "ltp": null, "last_seq": 1,
+            "bids": [{
+                "id": 1, "ticker": "BOOK", "order_type": "Bid",
+                "price": 12.0, "quantity": 10, "min_quantity": null,
+                "owner": {"id": 1, "name": "Account", "balance": 100.0,
+                          "holdings": {}},
+                "created": "2024-01-02T09:30:00Z",
+                "modified": "2024-01-02T09:30:00Z",
+                "cancelled": "2024-01-02T09:30:00Z",
+                "active": true
+            }],
+            "asks": []
+        }"#)?;
+        let order: &Order = &snapshot.bids[0];
+
+        assert_eq!(order.get_order_type(), OrderType::Bid);
+        assert_eq!(order.get_original_quantity(), 10);
+        assert!(snapshot.pegs.is_empty());
+
+        assert!(matches!(
+            from_json::<Event>(r#"{"version": 65535, "data": {}}"#),
+            Err(VersionError::Unsupported(65535))));
+        Ok(())
+    }
+}
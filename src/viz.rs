@@ -0,0 +1,159 @@
+use std::path::Path;
+
+use plotters::prelude::*;
+use thiserror::Error;
+
+use crate::levels::{Level, Levels};
+use crate::quantity::{self, Quantity};
+
+const BID_COLOUR: RGBColor = RGBColor(0x2c, 0xa0, 0x2c);
+const ASK_COLOUR: RGBColor = RGBColor(0xd6, 0x27, 0x28);
+
+#[derive(Debug, Error)]
+pub enum VizError {
+    #[error("there is nothing to chart")]
+    Empty,
+    #[error("drawing failed: {0}")]
+    Drawing(String),
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error)
+}
+
+fn drawing<E: std::error::Error + Send + Sync>(
+    error: DrawingAreaErrorKind<E>) -> VizError {
+    VizError::Drawing(error.to_string())
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DepthChartOptions {
+    /* in pixels */
+    pub width: u32,
+    pub height: u32,
+    /* number of price levels charted on each side */
+    pub levels: usize,
+    pub title: Option<String>
+}
+
+impl Default for DepthChartOptions {
+    fn default() -> DepthChartOptions {
+        DepthChartOptions {
+            width: 800,
+            height: 480,
+            levels: 50,
+            title: None
+        }
+    }
+}
+
+/* The corners of a side's cumulative depth curve, from the touch outwards:
+ * the curve steps up by each level's size at its price, and runs flat in
+ * between. */
+fn curve(side: &[Level], levels: usize) -> Vec<(f64, f64)> {
+    let mut running: Quantity = quantity::ZERO;
+    let mut points: Vec<(f64, f64)> = vec![];
+
+    for (price, size) in side.iter().take(levels) {
+        points.push((*price, quantity::to_f64(running)));
+        running += *size;
+        points.push((*price, quantity::to_f64(running)));
+    }
+
+    points
+}
+
+/* Draws the cumulative depth of each side, bids to the left of the mid and
+ * asks to the right, as an SVG document. */
+pub fn depth_chart_svg(levels: &Levels, options: &DepthChartOptions) ->
+    Result<String, VizError> {
+    let bids: Vec<(f64, f64)> = curve(levels.get_bids(), options.levels);
+    let asks: Vec<(f64, f64)> = curve(levels.get_asks(), options.levels);
+
+    let prices = || bids.iter().chain(asks.iter()).map(|(price, _)| *price);
+    let (low, high) = prices()
+        .fold(None, |range: Option<(f64, f64)>, price| match range {
+            Some((low, high)) => Some((low.min(price), high.max(price))),
+            None => Some((price, price))
+        })
+        .ok_or(VizError::Empty)?;
+    let depth: f64 = bids.iter()
+        .chain(asks.iter())
+        .map(|(_, depth)| *depth)
+        .fold(0.0, f64::max);
+    /* so that a single price still gets some width */
+    let margin: f64 = ((high - low) * 0.05).max(high.abs() * 0.001)
+        .max(f64::EPSILON);
+
+    let mut svg: String = String::new();
+
+    {
+        let root = SVGBackend::with_string(&mut svg,
+                                           (options.width, options.height))
+            .into_drawing_area();
+        root.fill(&WHITE).map_err(drawing)?;
+
+        let mut builder = ChartBuilder::on(&root);
+        builder.margin(10)
+            .x_label_area_size(30)
+            .y_label_area_size(50);
+
+        if let Some(title) = &options.title {
+            builder.caption(title, ("sans-serif", 20));
+        }
+
+        let mut chart = builder
+            .build_cartesian_2d((low - margin)..(high + margin),
+                                0.0..(depth * 1.05).max(1.0))
+            .map_err(drawing)?;
+
+        chart.configure_mesh()
+            .x_desc("price")
+            .y_desc("cumulative size")
+            .draw()
+            .map_err(drawing)?;
+
+        /* each curve is closed off along the axis at its far end */
+        for (side, colour) in [(bids, BID_COLOUR), (asks, ASK_COLOUR)] {
+            chart.draw_series(AreaSeries::new(side, 0.0, colour.mix(0.3))
+                              .border_style(colour))
+                .map_err(drawing)?;
+        }
+
+        root.present().map_err(drawing)?;
+    }
+
+    Ok(svg)
+}
+
+/* as `depth_chart_svg`, written to `path` */
+pub fn render_depth_chart<P: AsRef<Path>>(levels: &Levels, path: P,
+                                          options: &DepthChartOptions) ->
+    Result<(), VizError> {
+    std::fs::write(path, depth_chart_svg(levels, options)?)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_depth_chart() -> Result<(), VizError> {
+        let levels: Levels = Levels::new(vec![(9.5, 10), (9.0, 5)],
+                                         vec![(10.5, 4), (11.0, 20)]);
+
+        assert_eq!(curve(levels.get_bids(), 10),
+                   vec![(9.5, 0.0), (9.5, 10.0), (9.0, 10.0), (9.0, 15.0)]);
+
+        let svg: String = depth_chart_svg(&levels, &DepthChartOptions {
+            title: Some("BOOK".to_string()),
+            ..DepthChartOptions::default()
+        })?;
+
+        assert!(svg.starts_with("<svg"));
+        assert!(svg.contains("BOOK"));
+        assert!(matches!(depth_chart_svg(&Levels::new(vec![], vec![]),
+                                         &DepthChartOptions::default()),
+                         Err(VizError::Empty)));
+        Ok(())
+    }
+}
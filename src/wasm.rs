@@ -0,0 +1,150 @@
+use std::collections::HashMap;
+
+use js_sys::JSON;
+use serde::Serialize;
+use wasm_bindgen::prelude::*;
+
+use crate::account::Account;
+use crate::book::{Book, BookError, CancelResult};
+use crate::event::{Event, Trade};
+use crate::order::{Order, OrderId, OrderType};
+use crate::quantity::{self, Quantity};
+
+/* JavaScript bindings, for running a book client-side (e.g. in a browser
+ * order book visualizer) when built for `wasm32-unknown-unknown` with the
+ * `wasm` feature. IDs, prices and quantities are plain JS numbers, and
+ * results come back as plain JS objects, in the same shape as their JSON
+ * serialization. Orders are not checked against any account. */
+#[wasm_bindgen]
+#[derive(Debug)]
+pub struct WasmBook {
+    book: Book,
+    /* owns every order, and can always settle */
+    owner: Account
+}
+
+fn to_js<T: Serialize>(value: &T) -> Result<JsValue, JsValue> {
+    let json: String = serde_json::to_string(value)
+        .map_err(|e| JsValue::from_str(&e.to_string()))?;
+
+    JSON::parse(&json)
+}
+
+fn book_error(error: BookError) -> JsValue {
+    JsValue::from_str(&error.to_string())
+}
+
+fn order_id(id: f64) -> Result<OrderId, JsValue> {
+    if id.is_finite() && id >= 0.0 && id.fract() == 0.0 {
+        Ok(id as OrderId)
+    } else {
+        Err(JsValue::from_str("order IDs must be non-negative integers"))
+    }
+}
+
+fn order_type(side: &str) -> Result<OrderType, JsValue> {
+    match side {
+        "bid" | "Bid" | "buy" => Ok(OrderType::Bid),
+        "ask" | "Ask" | "sell" => Ok(OrderType::Ask),
+        _ => Err(JsValue::from_str("side must be \"bid\" or \"ask\""))
+    }
+}
+
+fn to_quantity(value: f64) -> Result<Quantity, JsValue> {
+    quantity::parse(&value.to_string())
+        .ok_or_else(|| JsValue::from_str("quantity not accepted"))
+}
+
+#[wasm_bindgen]
+impl WasmBook {
+    #[wasm_bindgen(constructor)]
+    pub fn new(ticker: String) -> WasmBook {
+        let mut holdings: HashMap<String, Quantity> = HashMap::new();
+        holdings.insert(ticker.clone(), Quantity::from(u64::MAX));
+
+        WasmBook {
+            book: Book::new(1, ticker.clone(), ticker.clone()),
+            owner: Account::new(1, "wasm".to_string(), f64::MAX / 4.0,
+                                holdings)
+        }
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn ticker(&self) -> String {
+        self.book.get_ticker()
+    }
+
+    /* the trades it made, as an array */
+    pub fn submit(&mut self, id: f64, side: &str, price: f64,
+                  quantity: f64) -> Result<JsValue, JsValue> {
+        let order: Order = Order::new(order_id(id)?, self.owner.clone(),
+                                      self.book.get_ticker(),
+                                      order_type(side)?, price,
+                                      to_quantity(quantity)?);
+
+        self.traded(|book| book.submit(order))
+    }
+
+    /* the order as it was when cancelled, with how much of it had filled
+     * and how much was cancelled; see `CancelResult` */
+    pub fn cancel(&mut self, id: f64) -> Result<JsValue, JsValue> {
+        let result: CancelResult = self.book.cancel(order_id(id)?)
+            .map_err(book_error)?;
+
+        to_js(&result)
+    }
+
+    /* the trades it made, as an array; see `Book::modify` */
+    pub fn modify(&mut self, id: f64, price: f64, quantity: f64) ->
+        Result<JsValue, JsValue> {
+        let (id, quantity) = (order_id(id)?, to_quantity(quantity)?);
+
+        self.traded(|book| book.modify(id, price, quantity))
+    }
+
+    /* `{bids: [[price, quantity], ...], asks: [...]}`, best first */
+    pub fn levels(&self) -> Result<JsValue, JsValue> {
+        to_js(&self.book.levels())
+    }
+
+    /* as `levels`, but only the best `depth` on each side */
+    pub fn depth(&self, depth: usize) -> Result<JsValue, JsValue> {
+        to_js(&self.book.depth(depth))
+    }
+
+    #[wasm_bindgen(js_name = topOfBook)]
+    pub fn top_of_book(&self) -> Result<JsValue, JsValue> {
+        to_js(&self.book.top_of_book())
+    }
+
+    #[wasm_bindgen(js_name = lastSeq)]
+    pub fn last_seq(&self) -> f64 {
+        self.book.last_seq() as f64
+    }
+
+    /* every event after `seq`, for a view to catch up with */
+    #[wasm_bindgen(js_name = eventsSince)]
+    pub fn events_since(&self, seq: f64) -> Result<JsValue, JsValue> {
+        let events: &[Event] = self.book.events_since(seq.max(0.0) as u64)
+            .map_err(book_error)?;
+
+        to_js(&events)
+    }
+}
+
+impl WasmBook {
+    fn traded<F>(&mut self, action: F) -> Result<JsValue, JsValue>
+    where
+        F: FnOnce(&mut Book) -> Result<(), BookError>
+    {
+        let traded: usize = self.book.get_trades().len();
+
+        action(&mut self.book).map_err(book_error)?;
+
+        let trades: &[Trade] = self.book.get_trades()
+            .get(traded..)
+            .unwrap_or_default();
+
+        to_js(&trades)
+    }
+}
@@ -0,0 +1,67 @@
+/* Differential testing: random operation sequences are applied to a `Book`
+ * and to the naive `ReferenceBook` alike, and must leave them agreeing on
+ * every fill and on the depth after every step. Prices come from a handful
+ * of ticks either side of a mid so that orders queue, cross and sweep
+ * several levels. */
+
+use ironlobe::order::{OrderId, OrderType};
+use ironlobe::quantity::Quantity;
+use ironlobe::reference::{differential, DiffOp};
+use ironlobe::sim::SimRng;
+
+const SEEDS: u64 = 100;
+const STEPS: usize = 500;
+
+fn random_ops(seed: u64, steps: usize) -> Vec<DiffOp> {
+    let mut rng: SimRng = SimRng::new(seed);
+    let mut ops: Vec<DiffOp> = Vec::with_capacity(steps);
+    let mut next_id: OrderId = 1;
+
+    for _ in 0..steps {
+        let price: f64 = 95.0 + rng.below(11) as f64;
+        let quantity: Quantity = Quantity::from(1 + rng.below(50));
+        /* sometimes one that never existed */
+        let existing: OrderId = 1 + rng.below(next_id as u64 + 2) as OrderId;
+
+        ops.push(match rng.below(10) {
+            0 | 1 => DiffOp::Cancel(existing),
+            2 => DiffOp::Modify {
+                id: existing,
+                price,
+                quantity
+            },
+            3 => DiffOp::Reduce {
+                id: existing,
+                quantity: Quantity::from(1 + rng.below(20))
+            },
+            _ => {
+                let order_type: OrderType = if rng.below(2) == 0 {
+                    OrderType::Bid
+                } else {
+                    OrderType::Ask
+                };
+
+                next_id += 1;
+                DiffOp::Submit {
+                    id: next_id - 1,
+                    order_type,
+                    price,
+                    quantity
+                }
+            }
+        });
+    }
+
+    ops
+}
+
+#[test]
+fn test_book_agrees_with_reference() {
+    for seed in 0..SEEDS {
+        if let Err(divergence) = differential(&random_ops(seed, STEPS)) {
+            panic!("seed {} diverged at step {} ({:?}): {}", seed,
+                   divergence.get_index(), divergence.get_op(),
+                   divergence.get_reason());
+        }
+    }
+}
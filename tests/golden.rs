@@ -0,0 +1,78 @@
+/* Golden-file regression tests for the matcher: fixed scripts of orders are
+ * run through a book and its depth afterwards is checked against that
+ * saved under `tests/golden`. Run with `IRONLOBE_BLESS=1` to regenerate the
+ * files after an intended change in behaviour. */
+
+use std::collections::HashMap;
+
+use ironlobe::account::Account;
+use ironlobe::book::Book;
+use ironlobe::compare::BookAssert;
+use ironlobe::matching::ProRata;
+use ironlobe::order::{Order, OrderId, OrderType};
+use ironlobe::quantity::Quantity;
+use ironlobe::sim::SimRng;
+
+const STEPS: u64 = 2_000;
+
+fn order(id: OrderId, order_type: OrderType, price: f64,
+         quantity: Quantity) -> Order {
+    let mut holdings: HashMap<String, Quantity> = HashMap::new();
+    holdings.insert("BOOK".to_string(), Quantity::from(1_000_000u64));
+
+    let owner: Account = Account::new(id, "Golden".to_string(), 1e12,
+                                      holdings);
+
+    Order::new(id, owner, "BOOK".to_string(), order_type, price, quantity)
+}
+
+/* random submissions and cancels around a fixed mid */
+fn script(seed: u64) -> Vec<(OrderId, Option<Order>)> {
+    let mut rng: SimRng = SimRng::new(seed);
+
+    (1..=STEPS as OrderId)
+        .map(|id| if rng.below(5) == 0 {
+            (1 + rng.below(id as u64) as OrderId, None)
+        } else {
+            let order_type: OrderType = if rng.below(2) == 0 {
+                OrderType::Bid
+            } else {
+                OrderType::Ask
+            };
+
+            (id, Some(order(id, order_type, 95.0 + rng.below(11) as f64,
+                            Quantity::from(1 + rng.below(50)))))
+        })
+        .collect()
+}
+
+#[test]
+fn test_price_time_golden() {
+    let mut book: Book = Book::new(1, "Golden".to_string(),
+                                   "BOOK".to_string());
+
+    for (id, order) in script(7) {
+        let _ = match order {
+            Some(order) => book.submit(order),
+            None => book.cancel(id).map(|_| ())
+        };
+    }
+
+    BookAssert::new(&book).matches_golden("tests/golden/price_time.json");
+}
+
+#[test]
+fn test_pro_rata_golden() {
+    let mut book: Book<ProRata> = Book::with_policy(1, "Golden".to_string(),
+                                                    "BOOK".to_string(),
+                                                    ProRata);
+
+    for (id, order) in script(7) {
+        let _ = match order {
+            Some(order) => book.submit(order),
+            None => book.cancel(id).map(|_| ())
+        };
+    }
+
+    BookAssert::new(&book).matches_golden("tests/golden/pro_rata.json");
+}
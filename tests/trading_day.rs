@@ -0,0 +1,305 @@
+/* A heavyweight end-to-end run: a population of agents trades a single book
+ * through an exchange for a simulated session, its events going to a
+ * write-ahead log, and global invariants are checked throughout and at the
+ * close. Ignored by default; run with `cargo test -- --ignored`. Written
+ * against integer quantities, so not built under `decimal-quantity`. */
+
+#![cfg(not(feature = "decimal-quantity"))]
+
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::path::PathBuf;
+
+use chrono::Utc;
+
+use ironlobe::account::{Account, AccountId};
+use ironlobe::book::Book;
+use ironlobe::builder::{BookBuilder, BookConfig};
+use ironlobe::event::*;
+use ironlobe::exchange::{Exchange, OrderRequest};
+use ironlobe::levels::Levels;
+use ironlobe::matching::PriceTime;
+use ironlobe::order::*;
+use ironlobe::quantity::Quantity;
+use ironlobe::replica::BookReplica;
+use ironlobe::router::{OrderRouter, RoutingStrategy};
+use ironlobe::settlement::SettlementReport;
+use ironlobe::sink::{read_wal, SyncPolicy, WalFormat, WalSink};
+
+const TICKER: &str = "BOOK";
+const AGENTS: u64 = 50;
+const STEPS: u64 = 20_000;
+const BALANCE: f64 = 100_000_000.00;
+const HOLDING: Quantity = 100_000_000;
+
+/* the market's parameters, as a venue would load them */
+const CONFIG: &str = r#"{
+    "tick_size": 0.05,
+    "lot_size": 1,
+    "price_band": [50.0, 150.0],
+    "circuit_breaker": null,
+    "reference_price": 100.0
+}"#;
+
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self, bound: u64) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0 % bound
+    }
+}
+
+struct Session {
+    exchange: Exchange<PriceTime, WalSink>,
+    /* each order placed, by whom and for how much */
+    submitted: HashMap<OrderId, (AccountId, Quantity)>,
+    wal: PathBuf
+}
+
+fn configure(mut builder: BookBuilder<PriceTime, WalSink>,
+             config: &BookConfig) -> BookBuilder<PriceTime, WalSink> {
+    if let Some(tick_size) = config.tick_size {
+        builder = builder.tick_size(tick_size);
+    }
+
+    if let Some(lot_size) = config.lot_size {
+        builder = builder.lot_size(lot_size);
+    }
+
+    if let Some((lower, upper)) = config.price_band {
+        builder = builder.price_band(lower, upper);
+    }
+
+    if let Some(price) = config.reference_price {
+        builder = builder.reference_price(price);
+    }
+
+    builder
+}
+
+fn run_session(seed: u64) -> Session {
+    let config: BookConfig = serde_json::from_str(CONFIG).unwrap();
+    let wal: PathBuf = std::env::temp_dir()
+        .join(format!("ironlobe-trading-day-{}-{:x}.wal",
+                      std::process::id(), seed));
+    let _ = std::fs::remove_file(&wal);
+    let sink: WalSink = WalSink::open(&wal, WalFormat::Binary,
+                                      SyncPolicy::OnFlush).unwrap();
+    let book: Book<PriceTime, WalSink> = configure(
+        Book::builder(1, TICKER.to_string()).sink(sink), &config)
+        .build()
+        .unwrap();
+    let mut router: OrderRouter<PriceTime, WalSink> =
+        OrderRouter::new(RoutingStrategy::Primary);
+    router.add_book(book).unwrap();
+
+    let mut exchange: Exchange<PriceTime, WalSink> = Exchange::new(router);
+
+    for agent in 0..AGENTS {
+        let mut holdings: HashMap<String, Quantity> = HashMap::new();
+        holdings.insert(TICKER.to_string(), HOLDING);
+        exchange.add_account(Account::new(agent as AccountId,
+                                          "Agent".to_string(), BALANCE,
+                                          holdings));
+    }
+
+    let mut rng: Rng = Rng(seed);
+    let mut submitted: HashMap<OrderId, (AccountId, Quantity)> =
+        HashMap::new();
+    let mut mid: f64 = 100.00;
+
+    for step in 1..=STEPS {
+        /* a slow random walk in the agents' view of fair value */
+        mid += (rng.next(3) as f64 - 1.0) * 0.05;
+
+        if rng.next(5) == 0 && !submitted.is_empty() {
+            let _ = exchange.cancel(
+                rng.next(submitted.len() as u64) as OrderId + 1);
+            continue;
+        }
+
+        let agent: AccountId = rng.next(AGENTS) as AccountId;
+        let side: OrderType = if rng.next(2) == 0 {
+            OrderType::Bid
+        } else {
+            OrderType::Ask
+        };
+        let offset: f64 = (rng.next(11) as f64 - 5.0) * 0.05;
+        let price: f64 = ((mid + offset) * 20.0).round() / 20.0;
+        let quantity: Quantity = rng.next(99) as Quantity + 1;
+
+        let id: OrderId = exchange.submit(OrderRequest {
+            ticker: TICKER.to_string(),
+            account: agent,
+            side,
+            price,
+            quantity,
+            client_id: None,
+            tags: Default::default(),
+            idempotency_key: None
+        })
+            .expect("well-funded agents are never rejected")
+            .id;
+        submitted.insert(id, (agent, quantity));
+
+        let levels: Levels = exchange.levels(TICKER).unwrap();
+
+        if let (Some(bid), Some(ask)) = (levels.get_bids().first(),
+                                         levels.get_asks().first()) {
+            assert!(bid.0 < ask.0, "book crossed at step {}", step);
+        }
+    }
+
+    exchange.get_router_mut().get_book_mut(1).unwrap().flush_events()
+        .unwrap();
+
+    Session {exchange, submitted, wal}
+}
+
+fn state_hash(levels: &Levels, trades: &[Trade]) -> u64 {
+    let mut hasher: DefaultHasher = DefaultHasher::new();
+
+    for (price, quantity) in levels.get_bids().iter()
+        .chain(levels.get_asks().iter()) {
+        price.to_bits().hash(&mut hasher);
+        quantity.hash(&mut hasher);
+    }
+
+    for trade in trades {
+        trade.get_price().to_bits().hash(&mut hasher);
+        trade.get_quantity().hash(&mut hasher);
+        trade.get_aggressor().hash(&mut hasher);
+        trade.get_resting().hash(&mut hasher);
+    }
+
+    hasher.finish()
+}
+
+#[test]
+#[ignore]
+fn test_trading_day() {
+    let mut session: Session = run_session(0x9e37_79b9_7f4a_7c15);
+
+    /* trades settle as they are made, and at the close none had failed */
+    let report: SettlementReport = session.exchange.settle(Utc::now());
+    assert!(report.failures.is_empty());
+
+    let exchange: &Exchange<PriceTime, WalSink> = &session.exchange;
+    let book: &Book<PriceTime, WalSink> =
+        exchange.get_router().get_book(1).unwrap();
+
+    /* everything the book did made it to the log, in order */
+    let events: Vec<Event> = read_wal(&session.wal, WalFormat::Binary)
+        .unwrap();
+    std::fs::remove_file(&session.wal).unwrap();
+
+    assert!(!book.get_trades().is_empty());
+    assert!(find_gaps(&events).is_empty());
+    assert_eq!(book.last_seq(), events.len() as u64);
+
+    let trades: Vec<Trade> = events.iter()
+        .filter_map(|event| match event.get_kind() {
+            EventKind::Match(trade) => Some(*trade),
+            _ => None
+        })
+        .collect();
+    assert_eq!(trades.as_slice(), book.get_trades());
+
+    /* conservation of quantity: everything submitted was either traded,
+     * is still resting, or was cancelled */
+    let mut accounted: HashMap<OrderId, Quantity> = HashMap::new();
+
+    for trade in &trades {
+        *accounted.entry(trade.get_aggressor()).or_insert(0) +=
+            trade.get_quantity();
+        *accounted.entry(trade.get_resting()).or_insert(0) +=
+            trade.get_quantity();
+    }
+
+    for event in &events {
+        if let EventKind::Cancel {order, quantity, ..} = event.get_kind() {
+            *accounted.entry(*order).or_insert(0) += *quantity;
+        }
+    }
+
+    for (id, (_, quantity)) in session.submitted.iter() {
+        let resting: Quantity = book.get_order(*id)
+            .map(|order| order.get_quantity())
+            .unwrap_or(0);
+
+        assert_eq!(accounted.get(id).copied().unwrap_or(0) + resting,
+                   *quantity, "order {} not conserved", id);
+    }
+
+    /* the exchange's ledgers reflect exactly the trades each account was
+     * part of */
+    let mut cash: HashMap<AccountId, f64> = HashMap::new();
+    let mut delivered: HashMap<AccountId, i64> = HashMap::new();
+
+    for trade in &trades {
+        let notional: f64 = trade.get_price() * trade.get_quantity() as f64;
+        let buyer: OrderId = match trade.get_aggressor_side() {
+            OrderType::Bid => trade.get_aggressor(),
+            OrderType::Ask => trade.get_resting()
+        };
+        let seller: OrderId = match trade.get_aggressor_side() {
+            OrderType::Bid => trade.get_resting(),
+            OrderType::Ask => trade.get_aggressor()
+        };
+        let buyer: AccountId = session.submitted[&buyer].0;
+        let seller: AccountId = session.submitted[&seller].0;
+
+        *cash.entry(buyer).or_insert(0.0) -= notional;
+        *cash.entry(seller).or_insert(0.0) += notional;
+        *delivered.entry(buyer).or_insert(0) += trade.get_quantity() as i64;
+        *delivered.entry(seller).or_insert(0) -= trade.get_quantity() as i64;
+    }
+
+    for agent in 0..AGENTS as AccountId {
+        let account: &Account = exchange.get_account(agent).unwrap();
+        let balance: f64 = BALANCE + cash.get(&agent).copied().unwrap_or(0.0);
+        let holding: i64 = HOLDING as i64 +
+            delivered.get(&agent).copied().unwrap_or(0);
+
+        assert!((account.get_balance() - balance).abs() < 1e-3,
+                "agent {}'s balance is off", agent);
+        assert_eq!(account.get_holding(TICKER.to_string()).unwrap() as i64,
+                   holding, "agent {}'s holding is off", agent);
+    }
+
+    /* replaying the log into a fresh book leaves it as the session did */
+    let fresh: Book = Book::builder(1, TICKER.to_string()).build().unwrap();
+    let mut replica: BookReplica = BookReplica::from_snapshot(
+        &fresh.snapshot());
+    replica.apply_delta(&events).unwrap();
+    assert_eq!(replica.get_last_seq(), book.last_seq());
+    assert_eq!(state_hash(&replica.levels(), &trades),
+               state_hash(&book.levels(), book.get_trades()));
+
+    /* every order and trade was counted */
+    #[cfg(feature = "metrics")]
+    {
+        let aggressors: std::collections::HashSet<OrderId> = trades.iter()
+            .map(Trade::get_aggressor)
+            .collect();
+
+        assert_eq!(book.metrics().get_trades(), trades.len() as u64);
+        assert_eq!(book.metrics().get_orders_matched(),
+                   aggressors.len() as u64);
+        assert_eq!(book.metrics().get_rejects(), 0);
+        assert_eq!(book.metrics().get_submit_latencies().len(),
+                   session.submitted.len() as u64);
+    }
+
+    /* and the whole session is deterministic */
+    let replay: Session = run_session(0x9e37_79b9_7f4a_7c15);
+    let replayed: &Book<PriceTime, WalSink> =
+        replay.exchange.get_router().get_book(1).unwrap();
+    std::fs::remove_file(&replay.wal).unwrap();
+    assert_eq!(state_hash(&replayed.levels(), replayed.get_trades()),
+               state_hash(&book.levels(), book.get_trades()));
+}